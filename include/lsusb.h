@@ -0,0 +1,93 @@
+#ifndef LSUSB_H
+#define LSUSB_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Transfer states returned by [`lsusb_transfer_poll`].
+ */
+#define LSUSB_TRANSFER_RUNNING 0
+
+#define LSUSB_TRANSFER_DONE 1
+
+#define LSUSB_TRANSFER_FAILED -1
+
+#define LSUSB_TRANSFER_CANCELLED -2
+
+/**
+ * A copy running on a background thread. Opaque to C.
+ */
+typedef struct LsusbTransfer LsusbTransfer;
+
+/**
+ * Snapshot of a transfer's progress, filled in by [`lsusb_transfer_poll`].
+ */
+typedef struct LsusbProgress {
+  uint64_t bytes_done;
+  uint64_t bytes_total;
+  uint64_t files_done;
+  uint64_t files_total;
+} LsusbProgress;
+
+/**
+ * Message describing the last failure on this thread, or NULL. The pointer
+ * stays valid until the next failing call on the same thread.
+ */
+const char *lsusb_last_error(void);
+
+/**
+ * Releases a string returned by this library. NULL is ignored.
+ *
+ * # Safety
+ * `s` must come from this library and not have been freed already.
+ */
+void lsusb_string_free(char *s);
+
+/**
+ * Attached USB devices as a JSON array, or NULL on failure.
+ */
+char *lsusb_devices_json(void);
+
+/**
+ * Starts copying `source` to `dest` in the background. Returns NULL if the
+ * arguments are invalid.
+ *
+ * # Safety
+ * `source` and `dest` must be NUL-terminated strings.
+ */
+struct LsusbTransfer *lsusb_transfer_start(const char *source, const char *dest);
+
+/**
+ * Copies the current progress into `out` (if not NULL) and returns one of
+ * the `LSUSB_TRANSFER_*` states. After `LSUSB_TRANSFER_FAILED`,
+ * [`lsusb_last_error`] describes the failure.
+ *
+ * # Safety
+ * `transfer` must come from [`lsusb_transfer_start`] and not be freed;
+ * `out` must be NULL or point to writable memory.
+ */
+int32_t lsusb_transfer_poll(struct LsusbTransfer *transfer, struct LsusbProgress *out);
+
+/**
+ * Asks the transfer to stop; poll until it reports `LSUSB_TRANSFER_CANCELLED`.
+ *
+ * # Safety
+ * `transfer` must come from [`lsusb_transfer_start`] and not be freed.
+ */
+void lsusb_transfer_cancel(struct LsusbTransfer *transfer);
+
+/**
+ * Cancels the transfer if it is still running, waits for it, and frees it.
+ *
+ * # Safety
+ * `transfer` must come from [`lsusb_transfer_start`] and not be freed
+ * already. NULL is ignored.
+ */
+void lsusb_transfer_free(struct LsusbTransfer *transfer);
+
+#endif  /* LSUSB_H */
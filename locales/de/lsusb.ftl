@@ -0,0 +1,404 @@
+# Deutsche Übersetzung. Fehlende Meldungen erscheinen auf Englisch.
+
+## Fehler und allgemeine Abfragen
+
+error = Fehler: { $error }
+error-listing-devices = Fehler beim Auflisten der Geräte: { $error }
+press-enter = Weiter mit der Eingabetaste...
+nothing-changed = es wurde nichts geändert
+retrying-as-root = Zugriff verweigert; neuer Versuch mit { $program }...
+
+## Das interaktive Menü
+
+menu-prompt = Aktion auswählen
+menu-mount = { $partition } einhängen
+menu-list-devices = USB-Geräte auflisten
+menu-list-partitions = Partitionen auflisten
+menu-sync = Gerät synchronisieren
+menu-unmount = Gerät aushängen
+menu-copy = Datei/Verzeichnis kopieren
+menu-open = Im Dateimanager öffnen
+menu-rename = Datenträger umbenennen
+menu-health = Gerätezustand
+menu-exit = Beenden
+
+device-attached = { $device } angeschlossen
+device-attached-mount = { $device } angeschlossen — „{ $partition } einhängen“ wählen, um es einzuhängen
+device-detached = { $device } entfernt
+device-busy = { $device } ist beschäftigt: { $read }/s gelesen, { $written }/s geschrieben — vor dem Aushängen abwarten
+
+select-device = Gerät auswählen
+select-device-to-sync = Zu synchronisierendes Gerät auswählen
+select-mountpoint-to-unmount = Auszuhängenden Einhängepunkt auswählen
+select-partition-to-open = Zu öffnende Partition auswählen
+select-partition-to-rename = Umzubenennende Partition auswählen
+select-destination = Zielpartition auswählen
+
+no-usb-devices = Keine USB-Geräte gefunden.
+no-usb-partitions = Keine Partitionen auf USB-Geräten gefunden.
+no-mounted-usb-partitions = Keine eingehängten Partitionen auf USB-Geräten gefunden.
+no-mounted-partitions-to-copy = Keine eingehängten Partitionen gefunden. Kopieren nicht möglich.
+no-labelled-partitions = Keine Partitionen mit bekanntem Dateisystem gefunden.
+no-label = ohne Bezeichnung
+new-label = Neue Bezeichnung
+
+copy-source = Pfad der Quelldatei bzw. des Quellverzeichnisses
+copy-to-root = In das Wurzelverzeichnis von { $mountpoint } kopieren?
+copy-subpath = Unterverzeichnis/Dateiname im Ziel
+copy-eject = Laufwerk nach dem Kopieren auswerfen?
+copy-existing = Wenn eine Datei schon vorhanden ist
+existing-overwrite = Überschreiben
+existing-skip = Überspringen
+existing-ask = Jedes Mal fragen
+
+conflict-exists = { $path } ist bereits vorhanden
+conflict-overwrite = Überschreiben
+conflict-skip = Überspringen
+conflict-keep-both = Beide behalten (Kopie umbenennen)
+conflict-overwrite-all = Alle überschreiben
+conflict-skip-all = Alle überspringen
+conflict-keep-both-all = Bei allen beide behalten
+
+type-number = Eine Zahl von 1 bis { $count } eingeben.
+type-yes-no = y oder n eingeben.
+
+## Tabellen
+
+column-name = NAME
+column-size = GRÖSSE
+column-hotplug = HOTPLUG
+column-port = PORT
+column-driver = TREIBER
+column-read = LESEN
+column-write = SCHREIBEN
+column-vendor = HERSTELLER
+column-model = MODELL
+column-type = TYP
+column-fstype = DATEISYSTEM
+column-label = BEZEICHNUNG
+column-mountpoint = EINHÄNGEPUNKT
+column-snapshot = SNAPSHOT
+column-files = DATEIEN
+column-added = NEU
+column-removed = WEG
+column-changed = GEÄNDERT
+column-growth = ZUWACHS
+column-labels = BEZEICHNUNGEN
+column-job = AUFTRAG
+column-kind = ART
+column-progress = FORTSCHRITT
+column-last-20s = LETZTE 20 s
+column-elapsed = DAUER
+column-paths = PFADE
+column-time-utc = ZEIT (UTC)
+column-user = BENUTZER
+column-operation = VORGANG
+column-params = PARAMETER
+column-tool = WERKZEUG
+column-status = STATUS
+column-needed = BEDARF
+column-used-for = WOFÜR
+column-busid = BUSID
+column-state = ZUSTAND
+column-udid = UDID
+column-ios = IOS
+column-mode = MODUS
+column-path = PFAD
+column-alt = ALT
+column-uri = URI
+yes = JA
+no = NEIN
+usage = { $percent } % belegt, { $free } frei
+refreshing = Aktualisierung jede Sekunde; Strg-C beendet.
+partitions-of = Partitionen von { $device }:
+no-partitions = Keine Partitionen gefunden.
+
+## Einhängen, Synchronisieren und Auswerfen
+
+mounting = { $partition } wird eingehängt...
+mounting-read-only = { $partition } wird schreibgeschützt eingehängt...
+mounted-at = Eingehängt unter { $mountpoint }.
+syncing = Gerät { $device } wird synchronisiert...
+synced = Synchronisierung abgeschlossen.
+flushed = { $mountpoint } ist geschrieben.
+flushed-bytes = { $size } nach { $mountpoint } geschrieben.
+flushed-up-to = Bis zu { $size } nach { $mountpoint } geschrieben.
+flushing-remaining = noch { $size } zu schreiben
+unmounting = { $mountpoint } wird ausgehängt...
+unmounted = Erfolgreich ausgehängt.
+powering-off = { $device } wird ausgeschaltet...
+safe-to-unplug = { $device } kann jetzt sicher entfernt werden.
+nothing-to-eject = { $path } liegt nicht auf einem USB-Laufwerk; nichts auszuwerfen.
+
+job-running = Auf dieser Partition läuft ein Vorgang ({ $kind }): { $job }
+job-prompt = Ihm das Dateisystem zu entziehen würde ihn abbrechen
+job-wait = Warten, bis er fertig ist
+job-cancel = Abbrechen
+job-leave = Das Gerät in Ruhe lassen
+job-waiting = Warte...
+
+## Gerätelisten und Zustand
+
+sd-card-rated = { $device }: SD-Karte der Klasse { $speed }
+sd-card-rated-speed = { $device }: SD-Karte der Klasse { $speed }, also mindestens { $mb_s } MB/s beim sequentiellen Schreiben
+bridge-issue = { $device }: Bridge { $vid_pid } ({ $issue }); wenn das Laufwerk langsam ist oder ausfällt, mit usb-storage.quirks={ $vid_pid }:u starten, um usb-storage zu verwenden
+
+column-bus = BUS
+column-vid-pid = VID:PID
+column-speed = TEMPO
+column-class = KLASSE
+column-product = PRODUKT
+column-node = GERÄTEDATEI
+column-chip = CHIP
+column-serial = SERIENNUMMER
+usb-controller = USB-Controller
+bus = Bus { $bus } { $product }
+bus-at = Bus { $bus } { $product } an { $parent }
+hub = Hub
+no-serial-adapters = Keine USB-Seriell-Adapter gefunden.
+serial-also = auch { $link }
+
+health-of = Zustand von { $device }:
+health-overall = Gesamt:
+health-passed = BESTANDEN
+health-failed = FEHLGESCHLAGEN
+health-temperature = Temperatur:
+health-power-on = Betriebszeit:
+health-reallocated = Neu zugewiesen:
+health-pending = Ausstehend:
+health-no-alerts = Keine Werte über den eingestellten Schwellen.
+health-warnings = Warnungen:
+unknown = unbekannt
+sectors = { $count } Sektoren
+celsius = { $degrees } °C
+hours-days = { $hours } Stunden ({ $days } Tage)
+wear = Abnutzung:
+wear-drive = { $percent } % verbraucht (Schätzung des Laufwerks)
+wear-assumed = etwa { $percent } % verbraucht, nach dem, was lsusb geschrieben hat, bei angenommenen { $fills } vollständigen Beschreibungen
+host-writes = Geschrieben:
+host-writes-value = { $size } (Zählung des Laufwerks)
+writes-left = Verbleibend:
+writes-left-value = etwa { $size }
+
+## Bezeichnungen, Bootloader, Verstecke und VeraCrypt
+
+labeled = { $partition } heißt jetzt '{ $label }'.
+rescanned = Partitionstabelle von { $device } neu eingelesen.
+unknown-model = unbekanntes Modell
+and = und
+
+bootloader-installed = Für { $targets } installiert; Menü nach { $config } geschrieben.
+bootloader-menu-empty = Das Menü ist leer: Kernel nach /boot oder ISO-Abbilder nach /isos kopieren und dies erneut ausführen.
+bootloader-kernel = Kernel { $path }
+bootloader-iso = ISO    { $iso }
+bootloader-iso-skipped = { $iso } übersprungen (SYSLINUX kann keine ISO-Dateien starten)
+
+confirm-repartition = { $device } neu partitionieren?
+stash-shrink = { $partition } wird auf { $size } verkleinert, um Platz für ein Versteck von { $stash_size } zu schaffen ({ $stash }).
+stash-free-space = Das Versteck ({ $stash }, { $stash_size }) kommt in den freien Platz am Ende.
+stash-passphrase = Passphrase des Verstecks
+repeat-it = Wiederholen
+passphrases-differ = Die Passphrasen stimmen nicht überein
+stash-created = Versteck auf { $stash } angelegt; mit `lsusb stash open { $device }` öffnen.
+stash-open = Versteck geöffnet unter { $mountpoint }.
+stash-closed = Versteck geschlossen.
+
+column-volume = DATENTRÄGER
+column-device = GERÄT
+veracrypt-none-open = Keine VeraCrypt-Datenträger geöffnet.
+veracrypt-no-candidates = Keine Partition sieht nach einem VeraCrypt-Datenträger aus.
+veracrypt-candidates = Könnten VeraCrypt-Datenträger sein: { $partitions }
+veracrypt-unreadable = Manche Partitionen ließen sich nicht lesen; als root ausführen, um auch sie zu prüfen.
+veracrypt-password = VeraCrypt-Passwort
+volume-open-at = { $volume } geöffnet unter { $mountpoint }.
+volume-closed = { $volume } geschlossen.
+
+## Formatieren, Trimmen, Messungen und Multiboot
+
+confirm-erase = Alles auf { $device } löschen?
+formatting = { $device } wird als { $fs } mit { $cluster } großen Clustern formatiert, ausgerichtet auf { $align }...
+formatted = Fertig. { $partition } ist { $size } groß und mit { $fs } formatiert.
+
+discard-supported = { $disk }: Discard wird unterstützt, in Einheiten von { $granularity }, bis zu { $max } je Anfrage.
+discard-unsupported = { $disk }: Der Kernel sendet an dieses Laufwerk kein Discard.
+provisioning-unmap = SCSI-Provisioning-Modus { $mode }; das Laufwerk kündigt UNMAP an.
+provisioning-no-unmap = SCSI-Provisioning-Modus { $mode }; das Laufwerk kündigt UNMAP nicht an.
+provisioning-unmap-unknown = SCSI-Provisioning-Modus { $mode }; das Laufwerk sagt nicht, ob es UNMAP unterstützt.
+unmap-enabled = Der Kernel sendet jetzt UNMAP an { $disk }, bis es abgezogen wird.
+trimmed-bytes = { $size } freier Platz auf { $mountpoint } getrimmt.
+trimmed = Der freie Platz auf { $mountpoint } wurde getrimmt.
+confirm-discard = Alles auf { $device } ({ $size }) verwerfen?
+discarded = { $device } verworfen.
+discard-honored = Das Laufwerk beachtet Discard: Das Verworfene liest sich jetzt gelöscht.
+discard-ignored = Das Laufwerk hat das Discard angenommen, liefert aber weiter die alten Daten; es ignoriert es.
+discard-unknown = Das Laufwerk hat das Discard angenommen, liefert aber weiter Daten, was Laufwerke ohne Nullen-Garantie dürfen; ob es Discard beachtet, lässt sich nicht sagen.
+
+benchmarking = { $dir } wird mit einer Testdatei von { $size } gemessen...
+bench-sequential-write = Sequentielles Schreiben
+bench-sequential-read = Sequentielles Lesen
+bench-random-read = 4K zufällig lesen QD{ $queue_depth }
+bench-random-write = 4K zufällig schreiben QD{ $queue_depth }
+bench-cached = Das Dateisystem erlaubt nicht, den Seitencache zu umgehen; die Werte können geschönt sein.
+bench-none = Es wurden keine Tests ausgeführt.
+
+multiboot-partitioning = { $device } wird partitioniert und formatiert...
+multiboot-ready = Fertig. ISO-Abbilder nach { $dir }/ auf { $partition } ({ $label }) kopieren oder `lsusb multiboot add { $device } <iso>...` verwenden.
+multiboot-added = { $count } Abbild(er) hinzugefügt; sie erscheinen im Startmenü unter ihrem Namen.
+multiboot-empty = Keine ISO-Abbilder auf { $device }.
+removed = { $name } entfernt.
+
+## Öffnen und Kopieren
+
+opening = { $dir } wird geöffnet...
+calculating-size = Größe wird berechnet...
+total-size = Gesamtgröße: { $bytes } Bytes
+progress-copying = Kopieren
+progress-flushing = Schreiben
+copy-complete = Kopieren abgeschlossen
+skipped-because = { $path } übersprungen ({ $reason })
+
+summary-files = Dateien:
+summary-files-value = { $copied } kopiert, { $skipped } übersprungen, { $failed } fehlgeschlagen
+summary-written = Geschrieben:
+summary-written-value = { $size } in { $secs } s
+summary-speed = Tempo:
+summary-speed-value = { $average }/s im Schnitt, { $peak }/s Spitze
+summary-verified = Geprüft:
+verification-skipped = nicht geprüft
+verification-passed = bestanden
+verification-failed = FEHLGESCHLAGEN
+
+mounting-for-copy = { $partition } wird für das Kopieren eingehängt und danach wieder ausgehängt.
+left-alone = { $count } Datei(en), die schon am Ziel lagen, blieben unberührt.
+
+## Kopierberichte
+
+scanner-flagged = Nicht kopiert, weil der Virenscanner sie gemeldet hat:
+span-volume = Laufwerk { $number }: { $files } Datei(en), { $size }, in { $dest }
+span-full = { $dest } ist voll.
+span-continuing = Weiter auf { $partition } ({ $mountpoint }).
+span-next-drive = Nächstes Laufwerk anschließen und einhängen, dann fortfahren?
+collisions = Diese Quellpfade wären unter demselben Namen kopiert worden:
+collision = { $source } (kollidiert mit { $other }): { $outcome }
+collision-skipped = übersprungen
+collision-copied-as = kopiert als { $path }
+attributes-dropped = Das Ziel konnte { $count } Attribut(e) nicht speichern (FAT und exFAT kennen weder Zugriffsrechte noch erweiterte Attribute):
+and-more = und { $count } weitere
+
+## Löschen, Sperren und Statistik
+
+warning = Warnung: { $message }
+wear-leveling = USB-Sticks verteilen Blöcke intern zum Verschleißausgleich um, daher können ältere Kopien dieser Dateien in Blöcken überdauern, die das Dateisystem nicht mehr erreicht. Nur ein von Anfang an verschlüsseltes Laufwerk oder seine Zerstörung schützt vertrauliche Daten zuverlässig.
+not-on-usb = { $path } liegt nicht auf einem USB-Gerät; mit --force trotzdem löschen
+overwriting = { $path } wird überschrieben...
+overwrote = { $files } Datei(en) mit { $bytes } Bytes überschrieben und entfernt.
+shared-unlinked = { $count } Datei(en) mit weiteren harten Links, etwa in anderen Backup-Snapshots, wurden nur entfernt, nicht überschrieben.
+not-trimmed = Der freie Platz wurde nicht getrimmt (braucht root und ein Dateisystem, das Discard unterstützt).
+not-trimmed-root = Der freie Platz wurde nicht getrimmt, dafür braucht es root; mit `lsusb trim { $mountpoint }` nachholen.
+waiting-for-lock = Warte, bis { $holder } mit { $device } fertig ist...
+signature-good = Die Signatur von { $manifest } ist gültig.
+manifest-signed = { $manifest } geschrieben und signiert.
+manifest-checked = { $files } Datei(en), { $bytes } Bytes geprüft.
+checking-sample = Eine Stichprobe von { $amount } wird geprüft (Startwert { $seed }; --seed { $seed } wiederholt sie).
+copying-to = Kopiere nach { $dest }
+
+stats-of = { $device } (Seriennummer { $serial })
+stats-written = { $size } in { $sessions } Sitzung(en)
+stats-fills = Vollständig beschrieben:
+stats-first-seen = Zuerst gesehen:
+stats-last-seen = Zuletzt gesehen:
+stats-reported-as = Gemeldet als:
+stats-models = { $models } (die Seriennummer ist womöglich nicht eindeutig)
+utc = { $time } UTC
+
+## Backups
+
+backup-no-profiles = Keine Backup-Profile; einen Abschnitt [backup.<name>] in der Konfigurationsdatei anlegen.
+no-backup-profile = kein Backup-Profil '{ $profile }' (vorhanden: { $profiles })
+no-backup-profile-none = kein Backup-Profil '{ $profile }' (vorhanden: keine)
+backup-ready-on = bereit auf { $partition }
+wrote = { $file } geschrieben.
+udev-rule-needs-root = Die udev-Regel zu installieren braucht root; ausführen:
+timer-enabled = { $timer } aktiviert; `systemctl --user list-timers` zeigt den nächsten Lauf.
+runs-on-plug = { $profile } läuft jetzt, sobald sein Stick eingesteckt wird.
+backing-up = Sichere { $profile } auf { $partition } ({ $device })...
+pruned-snapshots = { $count } alte(n) Snapshot(s) entfernt: { $snapshots }.
+restoring = Stelle { $backup } von { $profile } nach { $dest } wieder her...
+restored = { $files } Datei(en), { $bytes } Bytes in { $secs } s wiederhergestellt; { $unchanged } unverändert, { $verified } geprüft.
+conflicts-kept = { $count } Datei(en) behalten, die vom Backup abweichen (--on-conflict overwrite oder keep-both stellt sie wieder her):
+restored-next-to = Neben der vorhandenen Datei wiederhergestellt: { $path }
+no-snapshots = Noch keine Snapshots von { $profile }.
+nothing-to-prune = Nichts zu entfernen.
+would-delete = Würde { $snapshot } löschen
+deleted = { $snapshot } gelöscht
+verifying = Prüfe { $backup } von { $profile }...
+checked = { $files } Datei(en), { $size } in { $secs } s geprüft.
+checked-cached = { $count } unveränderte Datei(en) mit zwischengespeicherten Prüfsummen verglichen.
+file-corrupt = beschädigt  { $path }
+file-missing = fehlt       { $path }
+verify-failed = { $corrupt } beschädigte und { $missing } fehlende Datei(en); aus einer anderen Kopie wiederherstellen oder das Backup erneut ausführen.
+sample-matches = Alle Dateien der Stichprobe stimmen mit ihren Prüfsummen überein.
+all-match = Alle Dateien stimmen mit ihren Prüfsummen überein.
+no-differences = Keine Unterschiede zwischen { $old } und { $new }.
+diff-summary = { $added } neu, { $removed } entfernt, { $changed } geändert; { $growth }.
+
+## Dienst, Aufträge, Prüfprotokoll und doctor
+
+daemon-started = Prüfe SMART der angeschlossenen USB-Laufwerke alle { $secs } s; Strg-C beendet.
+daemon-auto-backups = Sichere { $profiles } beim Einstecken.
+notification-failed = Benachrichtigung konnte nicht gesendet werden: { $error }
+no-smart-data = { $device }: keine SMART-Daten: { $error }
+smart-warning = { $device }: SMART-Warnung
+auto-backup-scheduled = Backup { $profile } beginnt in { $secs } s
+auto-backup-unplug = { $device } vorher abziehen, um es auszulassen.
+auto-backup-aborted = Backup { $profile }: Stick abgezogen, ausgelassen.
+auto-backup-finished = Backup { $profile } fertig
+auto-backup-report = { $files } Datei(en), { $size } in { $secs } s kopiert; { $unchanged } unverändert.
+auto-backup-failed = Backup { $profile } fehlgeschlagen
+
+job-stopping = Auftrag { $job } wurde gebeten anzuhalten.
+no-jobs = Keine lsusb-Aufträge laufen.
+job-not-cancellable = (nicht abbrechbar)
+audit-no-entries = Keine passenden Einträge in { $log }.
+
+doctor-backend = Backend: { $backend }
+doctor-wsl = Läuft unter WSL; USB-Geräte müssen mit usbipd-win von Windows weitergereicht werden.
+doctor-usbipd-found = usbipd-win gefunden unter { $path }
+doctor-usbipd-missing = usbipd-win nicht gefunden: unter Windows installieren (winget install usbipd)
+tool-ok = ok
+tool-missing = fehlt
+tool-required = nötig
+tool-optional = optional
+doctor-all-found = Alle Werkzeuge gefunden.
+doctor-unavailable = Nicht verfügbar:
+doctor-install = { $feature } ({ $program } installieren)
+
+## WSL, Kameras, DFU, iOS, ISO-Abbilder und Termux
+
+wsl-no-devices = Keine USB-Geräte an Windows angeschlossen.
+wsl-attaching = Verbinde { $busid } mit WSL...
+wsl-attached = Verbunden. Es erscheint in `lsusb list`, sobald der Kernel es erkannt hat.
+wsl-detaching = Trenne { $busid }...
+wsl-detached = Getrennt; das Gerät ist wieder bei Windows.
+
+no-cameras = Keine Kameras gefunden.
+camera-importing = Importiere von { $model }...
+camera-imported = { $files } Datei(en), etwa { $bytes } Bytes importiert; { $skipped } bereits heruntergeladen.
+
+no-dfu-devices = Keine DFU-Geräte gefunden.
+dfu-flashing = Schreibe { $firmware } auf { $device } (Alt { $alt }); nicht abziehen, bis das fertig ist.
+
+no-ios-devices = Keine iOS-Geräte gefunden.
+ios-unpaired = Gerät entsperren und „Vertrauen“ tippen, um Details zu sehen
+ios-mounting = Hänge die Medienpartition von { $udid } ein...
+ios-mounted = Eingehängt unter { $mountpoint }.
+
+iso-volume = Datenträger: { $label } ({ $format })
+iso-extracted = { $files } Dateien ({ $size }) nach { $dest } entpackt.
+
+termux-no-storage = Termux hat keinen Speicherzugriff. Das Laufwerk einstecken und `lsusb termux grant` ausführen.
+termux-granted = Erteilt. `lsusb termux dirs` listet das Laufwerk.
+entry-dir = Ordner
+entry-file = Datei
+termux-copying = Kopiere { $source } nach { $dest }...
+termux-copied = { $files } Datei(en), { $bytes } Bytes kopiert.
@@ -0,0 +1,405 @@
+# lsusb's user-facing text. Keys are shared by every language; a message
+# missing from a translation is shown in English.
+
+## Errors and generic prompts
+
+error = Error: { $error }
+error-listing-devices = Error listing devices: { $error }
+press-enter = Press Enter to continue...
+nothing-changed = nothing was changed
+retrying-as-root = Permission denied; retrying with { $program }...
+
+## The interactive menu
+
+menu-prompt = Select an action
+menu-mount = Mount { $partition }
+menu-list-devices = List USB Devices
+menu-list-partitions = List Partitions
+menu-sync = Sync Device
+menu-unmount = Unmount Device
+menu-copy = Copy File/Dir
+menu-open = Open in File Manager
+menu-rename = Rename Volume
+menu-health = Device Health
+menu-exit = Exit
+
+device-attached = { $device } attached
+device-attached-mount = { $device } attached — select "Mount { $partition }" to mount
+device-detached = { $device } detached
+device-busy = { $device } is busy: { $read }/s read, { $written }/s written — wait before unmounting it
+
+select-device = Select a device
+select-device-to-sync = Select a device to sync
+select-mountpoint-to-unmount = Select a mountpoint to unmount
+select-partition-to-open = Select a partition to open
+select-partition-to-rename = Select a partition to rename
+select-destination = Select destination partition
+
+no-usb-devices = No USB devices found.
+no-usb-partitions = No partitions found on USB devices.
+no-mounted-usb-partitions = No mounted partitions found on USB devices.
+no-mounted-partitions-to-copy = No mounted partitions found. Cannot copy.
+no-labelled-partitions = No partitions with a known filesystem found.
+no-label = no label
+new-label = New label
+
+copy-source = Enter path to source file/directory
+copy-to-root = Copy to root of { $mountpoint }?
+copy-subpath = Enter subdirectory/filename in destination
+copy-eject = Eject the drive once the copy is done?
+copy-existing = If a file is already there
+existing-overwrite = Overwrite it
+existing-skip = Skip it
+existing-ask = Ask each time
+
+conflict-exists = { $path } already exists
+conflict-overwrite = Overwrite
+conflict-skip = Skip
+conflict-keep-both = Keep both (rename the copy)
+conflict-overwrite-all = Overwrite all
+conflict-skip-all = Skip all
+conflict-keep-both-all = Keep both for all
+
+type-number = Type a number from 1 to { $count }.
+type-yes-no = Type y or n.
+
+## Tables
+
+column-name = NAME
+column-size = SIZE
+column-hotplug = HOTPLUG
+column-port = PORT
+column-driver = DRIVER
+column-read = READ
+column-write = WRITE
+column-vendor = VENDOR
+column-model = MODEL
+column-type = TYPE
+column-fstype = FSTYPE
+column-label = LABEL
+column-mountpoint = MOUNTPOINT
+column-snapshot = SNAPSHOT
+column-files = FILES
+column-added = ADDED
+column-removed = REMOVED
+column-changed = CHANGED
+column-growth = GROWTH
+column-labels = LABELS
+column-job = JOB
+column-kind = KIND
+column-progress = PROGRESS
+column-last-20s = LAST 20s
+column-elapsed = ELAPSED
+column-paths = PATHS
+column-time-utc = TIME (UTC)
+column-user = USER
+column-operation = OPERATION
+column-params = PARAMS
+column-tool = TOOL
+column-status = STATUS
+column-needed = NEEDED
+column-used-for = USED FOR
+column-busid = BUSID
+column-state = STATE
+column-udid = UDID
+column-ios = IOS
+column-mode = MODE
+column-path = PATH
+column-alt = ALT
+column-uri = URI
+yes = YES
+no = NO
+usage = { $percent }% used, { $free } free
+refreshing = Refreshing every second; press Ctrl-C to stop.
+partitions-of = Partitions for { $device }:
+no-partitions = No partitions found.
+
+## Mounting, syncing and ejecting
+
+mounting = Mounting { $partition }...
+mounting-read-only = Mounting { $partition } read-only...
+mounted-at = Mounted at { $mountpoint }.
+syncing = Syncing device { $device }...
+synced = Sync completed.
+flushed = Flushed { $mountpoint }.
+flushed-bytes = Flushed { $size } to { $mountpoint }.
+flushed-up-to = Flushed up to { $size } to { $mountpoint }.
+flushing-remaining = flushing { $size } remaining
+unmounting = Unmounting { $mountpoint }...
+unmounted = Unmounted successfully.
+powering-off = Powering off { $device }...
+safe-to-unplug = It is now safe to unplug { $device }.
+nothing-to-eject = { $path } isn't on a USB drive; nothing to eject.
+
+job-running = A { $kind } on this partition is running: { $job }
+job-prompt = Pulling the filesystem out from under it would break it
+job-wait = Wait for it to finish
+job-cancel = Cancel it
+job-leave = Leave the device alone
+job-waiting = Waiting...
+
+## Device lists and health
+
+sd-card-rated = { $device }: SD card rated { $speed }
+sd-card-rated-speed = { $device }: SD card rated { $speed }, so at least { $mb_s } MB/s sequential writes
+bridge-issue = { $device }: bridge { $vid_pid } ({ $issue }); if the drive is slow or drops out, boot with usb-storage.quirks={ $vid_pid }:u to use usb-storage instead
+
+column-bus = BUS
+column-vid-pid = VID:PID
+column-speed = SPEED
+column-class = CLASS
+column-product = PRODUCT
+column-node = NODE
+column-chip = CHIP
+column-serial = SERIAL
+usb-controller = USB controller
+bus = Bus { $bus } { $product }
+bus-at = Bus { $bus } { $product } at { $parent }
+hub = hub
+no-serial-adapters = No USB serial adapters found.
+serial-also = also { $link }
+
+health-of = Health of { $device }:
+health-overall = Overall:
+health-passed = PASSED
+health-failed = FAILED
+health-temperature = Temperature:
+health-power-on = Power-on time:
+health-reallocated = Reallocated:
+health-pending = Pending:
+health-no-alerts = No readings above the configured thresholds.
+health-warnings = Warnings:
+unknown = unknown
+sectors = { $count } sectors
+celsius = { $degrees } °C
+hours-days = { $hours } hours ({ $days } days)
+wear = Wear:
+wear-drive = { $percent }% used (the drive's own estimate)
+wear-assumed = about { $percent }% used, from what lsusb wrote, assuming { $fills } drive fills
+host-writes = Host writes:
+host-writes-value = { $size } (the drive's count)
+writes-left = Writes left:
+writes-left-value = about { $size }
+
+## Labels, bootloaders, stashes and VeraCrypt
+
+labeled = { $partition } is now labeled '{ $label }'.
+rescanned = Re-read the partition table of { $device }.
+unknown-model = unknown model
+and = and
+
+bootloader-installed = Installed for { $targets }; menu written to { $config }.
+bootloader-menu-empty = The menu is empty: copy kernels to /boot or ISO images to /isos, then run this again.
+bootloader-kernel = kernel { $path }
+bootloader-iso = iso    { $iso }
+bootloader-iso-skipped = skipped { $iso } (SYSLINUX can't boot ISO files)
+
+confirm-repartition = Repartition { $device }?
+stash-shrink = { $partition } will shrink to { $size } to make room for a { $stash_size } stash ({ $stash }).
+stash-free-space = The stash ({ $stash }, { $stash_size }) goes into free space at the end.
+stash-passphrase = Stash passphrase
+repeat-it = Repeat it
+passphrases-differ = The passphrases don't match
+stash-created = Created the stash on { $stash }; open it with `lsusb stash open { $device }`.
+stash-open = Stash open at { $mountpoint }.
+stash-closed = Stash closed.
+
+column-volume = VOLUME
+column-device = DEVICE
+veracrypt-none-open = No VeraCrypt volumes are open.
+veracrypt-no-candidates = No partitions look like VeraCrypt volumes.
+veracrypt-candidates = Could be VeraCrypt volumes: { $partitions }
+veracrypt-unreadable = Some partitions couldn't be read; run as root to check them too.
+veracrypt-password = VeraCrypt password
+volume-open-at = { $volume } open at { $mountpoint }.
+volume-closed = { $volume } closed.
+
+## Formatting, trimming, benchmarks and multiboot
+
+confirm-erase = Erase everything on { $device }?
+formatting = Formatting { $device } as { $fs } with { $cluster } clusters, aligned to { $align }...
+formatted = Done. { $partition } is { $size } of { $fs }.
+
+discard-supported = { $disk }: discard supported, in { $granularity } units, up to { $max } a request.
+discard-unsupported = { $disk }: the kernel doesn't discard on this drive.
+provisioning-unmap = SCSI provisioning mode { $mode }; the drive advertises UNMAP.
+provisioning-no-unmap = SCSI provisioning mode { $mode }; the drive doesn't advertise UNMAP.
+provisioning-unmap-unknown = SCSI provisioning mode { $mode }; the drive doesn't say whether it supports UNMAP.
+unmap-enabled = The kernel now sends UNMAP to { $disk } until it is unplugged.
+trimmed-bytes = Trimmed { $size } of free space on { $mountpoint }.
+trimmed = Trimmed the free space on { $mountpoint }.
+confirm-discard = Discard everything on { $device } ({ $size })?
+discarded = Discarded { $device }.
+discard-honored = The drive honors discard: what was discarded now reads back erased.
+discard-ignored = The drive accepted the discard but still returns the old data; it ignores it.
+discard-unknown = The drive accepted the discard but still returns data, which drives that don't promise zeros may do; whether it honors discard can't be told.
+
+benchmarking = Benchmarking { $dir } with a { $size } test file...
+bench-sequential-write = Sequential write
+bench-sequential-read = Sequential read
+bench-random-read = 4K random read QD{ $queue_depth }
+bench-random-write = 4K random write QD{ $queue_depth }
+bench-cached = The filesystem doesn't allow bypassing the page cache, so these numbers may be flattering.
+bench-none = No tests were run.
+
+multiboot-partitioning = Partitioning and formatting { $device }...
+multiboot-ready = Done. Copy ISO images into { $dir }/ on { $partition } ({ $label }), or use `lsusb multiboot add { $device } <iso>...`.
+multiboot-added = Added { $count } image(s); they show up in the boot menu as they are named.
+multiboot-empty = No ISO images on { $device }.
+removed = Removed { $name }.
+
+## Opening and copying
+
+opening = Opening { $dir }...
+calculating-size = Calculating size...
+total-size = Total size: { $bytes } bytes
+progress-copying = Copying
+progress-flushing = Flushing
+copy-complete = Copy complete
+skipped-because = Skipped { $path } ({ $reason })
+
+summary-files = Files:
+summary-files-value = { $copied } copied, { $skipped } skipped, { $failed } failed
+summary-written = Written:
+summary-written-value = { $size } in { $secs }s
+summary-speed = Speed:
+summary-speed-value = { $average }/s average, { $peak }/s peak
+summary-verified = Verified:
+verification-skipped = not checked
+verification-passed = passed
+verification-failed = FAILED
+
+mounting-for-copy = Mounting { $partition } for the copy; it is unmounted again afterwards.
+left-alone = { $count } file(s) already at the destination were left alone.
+
+## Copy reports
+
+scanner-flagged = Not copied because the scanner flagged them:
+span-volume = Drive { $number }: { $files } file(s), { $size }, in { $dest }
+span-full = { $dest } is full.
+span-continuing = Continuing on { $partition } ({ $mountpoint }).
+span-next-drive = Plug in and mount the next drive, then continue?
+collisions = These source paths would have been copied to the same name:
+collision = { $source } (clashes with { $other }): { $outcome }
+collision-skipped = skipped
+collision-copied-as = copied as { $path }
+attributes-dropped = The destination couldn't store { $count } attribute(s) (FAT and exFAT have no modes or extended attributes):
+and-more = and { $count } more
+
+## Deleting, locks and statistics
+
+warning = Warning: { $message }
+wear-leveling = Flash drives remap blocks internally for wear leveling, so older copies of these files may survive in blocks the filesystem can no longer reach. Only encrypting the drive from the start, or destroying it, reliably protects sensitive data.
+not-on-usb = { $path } is not on a USB device; pass --force to delete it anyway
+overwriting = Overwriting { $path }...
+overwrote = Overwrote and removed { $files } file(s), { $bytes } bytes.
+shared-unlinked = { $count } file(s) with other hard links, such as in other backup snapshots, were only unlinked, not overwritten.
+not-trimmed = Free space was not trimmed (needs root and a filesystem that supports discard).
+not-trimmed-root = Free space was not trimmed, which needs root; run `lsusb trim { $mountpoint }` to trim it.
+waiting-for-lock = Waiting for { $holder } to finish with { $device }...
+signature-good = The signature of { $manifest } is good.
+manifest-signed = Wrote and signed { $manifest }.
+manifest-checked = Checked { $files } file(s), { $bytes } bytes.
+checking-sample = Checking a sample of { $amount } (seed { $seed }; --seed { $seed } repeats it).
+copying-to = Copying to { $dest }
+
+stats-of = { $device } (serial { $serial })
+stats-written = { $size } in { $sessions } session(s)
+stats-fills = Drive fills:
+stats-first-seen = First seen:
+stats-last-seen = Last seen:
+stats-reported-as = Reported as:
+stats-models = { $models } (the serial number may not be unique)
+utc = { $time } UTC
+
+## Backups
+
+backup-no-profiles = No backup profiles; add a [backup.<name>] section to the config file.
+no-backup-profile = no backup profile '{ $profile }' (have: { $profiles })
+no-backup-profile-none = no backup profile '{ $profile }' (have: none)
+backup-ready-on = ready on { $partition }
+wrote = Wrote { $file }.
+udev-rule-needs-root = Installing the udev rule needs root; run:
+timer-enabled = Enabled { $timer }; `systemctl --user list-timers` shows the next run.
+runs-on-plug = { $profile } now runs whenever its stick is plugged in.
+backing-up = Backing up { $profile } to { $partition } ({ $device })...
+pruned-snapshots = Pruned { $count } old snapshot(s): { $snapshots }.
+restoring = Restoring { $backup } of { $profile } to { $dest }...
+restored = Restored { $files } file(s), { $bytes } bytes in { $secs }s; { $unchanged } unchanged, { $verified } verified.
+conflicts-kept = Kept { $count } file(s) that differ from the backup (--on-conflict overwrite or keep-both to restore them):
+restored-next-to = Restored next to the existing file: { $path }
+no-snapshots = No snapshots of { $profile } yet.
+nothing-to-prune = Nothing to prune.
+would-delete = Would delete { $snapshot }
+deleted = Deleted { $snapshot }
+verifying = Verifying { $backup } of { $profile }...
+checked = Checked { $files } file(s), { $size } in { $secs }s.
+checked-cached = { $count } unchanged file(s) checked against cached hashes.
+file-corrupt = corrupt  { $path }
+file-missing = missing  { $path }
+verify-failed = { $corrupt } corrupt and { $missing } missing file(s); restore them from another copy or run the backup again.
+sample-matches = All sampled files match their recorded checksums.
+all-match = All files match their recorded checksums.
+no-differences = No differences between { $old } and { $new }.
+diff-summary = { $added } added, { $removed } removed, { $changed } changed; { $growth }.
+
+## The daemon, jobs, the audit log and doctor
+
+daemon-started = Checking SMART on attached USB disks every { $secs }s; press Ctrl-C to stop.
+daemon-auto-backups = Backing up { $profiles } when plugged in.
+notification-failed = Failed to send notification: { $error }
+no-smart-data = { $device }: no SMART data: { $error }
+smart-warning = { $device }: SMART warning
+auto-backup-scheduled = Backup { $profile } starts in { $secs }s
+auto-backup-unplug = Unplug { $device } before then to skip it.
+auto-backup-aborted = Backup { $profile }: stick unplugged, skipped.
+auto-backup-finished = Backup { $profile } finished
+auto-backup-report = Copied { $files } file(s), { $size } in { $secs }s; { $unchanged } unchanged.
+auto-backup-failed = Backup { $profile } failed
+
+job-stopping = Asked job { $job } to stop.
+no-jobs = No lsusb jobs running.
+job-not-cancellable = (can't be cancelled)
+audit-no-entries = No matching entries in { $log }.
+
+doctor-backend = Backend: { $backend }
+doctor-wsl = Running under WSL; USB devices must be forwarded from Windows with usbipd-win.
+doctor-usbipd-found = usbipd-win found at { $path }
+doctor-usbipd-missing = usbipd-win not found: install it on Windows (winget install usbipd)
+tool-ok = ok
+tool-missing = missing
+tool-required = required
+tool-optional = optional
+doctor-all-found = All tools found.
+doctor-unavailable = Unavailable:
+doctor-install = { $feature } (install { $program })
+
+## WSL, cameras, DFU, iOS, ISO images and Termux
+
+wsl-no-devices = No USB devices connected to Windows.
+wsl-attaching = Attaching { $busid } to WSL...
+wsl-attached = Attached. It will show up in `lsusb list` once the kernel has probed it.
+wsl-detaching = Detaching { $busid }...
+wsl-detached = Detached; the device is back on Windows.
+
+no-cameras = No cameras found.
+camera-importing = Importing from { $model }...
+camera-imported = Imported { $files } file(s), about { $bytes } bytes; { $skipped } already downloaded.
+
+no-dfu-devices = No DFU devices found.
+dfu-flashing = Flashing { $firmware } to { $device } (alt { $alt }); don't unplug it until this finishes.
+
+no-ios-devices = No iOS devices found.
+ios-unpaired = unlock the device and tap Trust to see details
+ios-mounting = Mounting the media partition of { $udid }...
+ios-mounted = Mounted at { $mountpoint }.
+
+iso-volume = Volume: { $label } ({ $format })
+iso-extracted = Extracted { $files } files ({ $size }) to { $dest }.
+
+termux-no-storage = No storage granted to Termux. Plug in the drive and run `lsusb termux grant`.
+termux-granted = Granted. The drive is listed by `lsusb termux dirs`.
+entry-dir = dir
+entry-file = file
+termux-copying = Copying { $source } to { $dest }...
+termux-copied = Copied { $files } file(s), { $bytes } bytes.
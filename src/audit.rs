@@ -0,0 +1,199 @@
+//! Append-only record of destructive operations, for machines several people
+//! share.
+//!
+//! Every entry is one JSON object per line, written with `O_APPEND` before
+//! the operation starts, so entries from concurrent runs never interleave and
+//! an operation that can't be logged doesn't happen.
+
+use crate::error::{IoContext, Result, UsbError};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// One logged operation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    /// Login name of whoever ran it; under sudo, the invoking user.
+    pub user: String,
+    pub uid: u32,
+    /// What was done, e.g. `rm`.
+    pub operation: String,
+    /// Block device affected, if it could be determined; `vvvv:pppp` for
+    /// firmware flashed over DFU.
+    pub device: Option<String>,
+    /// USB serial number of that device.
+    pub serial: Option<String>,
+    /// Arguments and flags the operation was run with.
+    pub params: Vec<String>,
+}
+
+impl AuditEntry {
+    /// An entry for `operation` by the current user, stamped now.
+    pub fn new(operation: &str, device: Option<String>, params: Vec<String>) -> AuditEntry {
+        let serial = device.as_deref().and_then(device_serial);
+        AuditEntry {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            user: current_user(),
+            uid: unsafe { libc::getuid() },
+            operation: operation.to_string(),
+            device,
+            serial,
+            params,
+        }
+    }
+
+    /// [`new`](Self::new) for an operation on the filesystem holding `path`.
+    pub fn for_path(operation: &str, path: &Path, params: Vec<String>) -> AuditEntry {
+        AuditEntry::new(operation, device_of(path), params)
+    }
+
+    /// `time` as `YYYY-MM-DD HH:MM:SS` UTC.
+    pub fn timestamp(&self) -> String {
+        utc_timestamp(self.time)
+    }
+}
+
+/// Seconds since the epoch as `YYYY-MM-DD HH:MM:SS` UTC.
+pub(crate) fn utc_timestamp(time: u64) -> String {
+    let days = (time / 86_400) as i64;
+    let secs = time % 86_400;
+    // Howard Hinnant's civil_from_days.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// The audit log file.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    /// A log at `path`.
+    pub fn new(path: impl Into<PathBuf>) -> AuditLog {
+        AuditLog { path: path.into() }
+    }
+
+    /// `$XDG_STATE_HOME/lsusb/audit.log`, falling back to
+    /// `~/.local/state/lsusb/audit.log`.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_STATE_HOME")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state"))
+            })?;
+        Some(base.join("lsusb").join("audit.log"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Creates the log (and its directory) if it isn't there yet, so a run
+    /// as root later appends to a file the current user still owns.
+    pub fn create(&self) -> Result<()> {
+        self.open().map(drop)
+    }
+
+    fn open(&self) -> Result<File> {
+        let context = || format!("Failed to write audit log {}", self.path.display());
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).io_context(context)?;
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .io_context(context)
+    }
+
+    /// Appends `entry`, creating the log (and its directory) if needed.
+    pub fn record(&self, entry: &AuditEntry) -> Result<()> {
+        let context = || format!("Failed to write audit log {}", self.path.display());
+        let mut line = serde_json::to_vec(entry).map_err(|e| UsbError::parse("audit entry", e))?;
+        line.push(b'\n');
+        let mut file = self.open()?;
+        // One write, so O_APPEND keeps the line whole.
+        file.write_all(&line).io_context(context)?;
+        file.sync_data().io_context(context)
+    }
+
+    /// Every entry so far, oldest first; empty if nothing was logged yet.
+    pub fn entries(&self) -> Result<Vec<AuditEntry>> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(UsbError::io(
+                    format!("Failed to read audit log {}", self.path.display()),
+                    e,
+                ));
+            }
+        };
+        text.lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| {
+                serde_json::from_str(l)
+                    .map_err(|e| UsbError::parse(self.path.display().to_string(), e))
+            })
+            .collect()
+    }
+}
+
+pub(crate) fn current_user() -> String {
+    ["SUDO_USER", "USER", "LOGNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_else(|| unsafe { libc::getuid() }.to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn device_of(path: &Path) -> Option<String> {
+    crate::transfer::backing_device(path)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn device_of(_path: &Path) -> Option<String> {
+    None
+}
+
+/// The `serial` attribute of the USB device `device_name` sits on, found by
+/// walking up its sysfs path.
+#[cfg(target_os = "linux")]
+fn device_serial(device_name: &str) -> Option<String> {
+    let sys_path = fs::canonicalize(Path::new("/sys/class/block").join(device_name)).ok()?;
+    sys_path
+        .ancestors()
+        .find(|dir| dir.join("idVendor").exists())
+        .and_then(|dir| fs::read_to_string(dir.join("serial")).ok())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn device_serial(_device_name: &str) -> Option<String> {
+    None
+}
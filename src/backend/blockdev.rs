@@ -0,0 +1,160 @@
+//! Flushing a drive's own write cache, below the filesystem layer.
+
+use crate::error::{Result, UsbError};
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::path::Path;
+
+/// `_IO(0x12, 97)` from `<linux/fs.h>`.
+const BLKFLSBUF: u64 = 0x1261;
+/// From `<scsi/sg.h>`.
+const SG_IO: u64 = 0x2285;
+const SG_DXFER_NONE: i32 = -1;
+const SYNCHRONIZE_CACHE_10: u8 = 0x35;
+
+/// `struct sg_io_hdr` from `<scsi/sg.h>`; libc doesn't carry it.
+#[repr(C)]
+struct SgIoHdr {
+    interface_id: i32,
+    dxfer_direction: i32,
+    cmd_len: u8,
+    mx_sb_len: u8,
+    iovec_count: u16,
+    dxfer_len: u32,
+    dxferp: *mut libc::c_void,
+    cmdp: *const u8,
+    sbp: *mut u8,
+    timeout: u32,
+    flags: u32,
+    pack_id: i32,
+    usr_ptr: *mut libc::c_void,
+    status: u8,
+    masked_status: u8,
+    msg_status: u8,
+    sb_len_wr: u8,
+    host_status: u16,
+    driver_status: u16,
+    resid: i32,
+    duration: u32,
+    info: u32,
+}
+
+/// Writes back and drops the kernel's buffers for `device_name` and its
+/// whole disk (`BLKFLSBUF`), then has the drive commit its volatile write
+/// cache (SCSI `SYNCHRONIZE CACHE`), so nothing is still in flight when we
+/// call the device safe to remove.
+///
+/// Both need root; without it, or on drives that aren't SCSI underneath,
+/// the step is skipped rather than failing an otherwise clean unmount.
+pub(crate) fn flush_cache(device_name: &str) -> Result<()> {
+    let disk = whole_disk(device_name);
+    let mut nodes = vec![device_name.to_string()];
+    if disk != device_name {
+        nodes.push(disk.clone());
+    }
+    for name in &nodes {
+        let Some(file) = open_node(name)? else {
+            continue;
+        };
+        if unsafe { libc::ioctl(file.as_raw_fd(), BLKFLSBUF as libc::Ioctl) } != 0 {
+            let err = io::Error::last_os_error();
+            if !skippable(&err) {
+                return Err(UsbError::io(
+                    format!("Failed to flush buffers of /dev/{}", name),
+                    err,
+                ));
+            }
+        }
+    }
+
+    if let Some(file) = open_node(&disk)? {
+        match synchronize_cache(&file) {
+            Err(e) if !skippable(&e) => {
+                return Err(UsbError::io(
+                    format!("Failed to flush the write cache of /dev/{}", disk),
+                    e,
+                ));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn synchronize_cache(file: &File) -> io::Result<()> {
+    let cdb = [SYNCHRONIZE_CACHE_10, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    let mut sense = [0u8; 32];
+    let mut hdr = SgIoHdr {
+        interface_id: b'S' as i32,
+        dxfer_direction: SG_DXFER_NONE,
+        cmd_len: cdb.len() as u8,
+        mx_sb_len: sense.len() as u8,
+        iovec_count: 0,
+        dxfer_len: 0,
+        dxferp: std::ptr::null_mut(),
+        cmdp: cdb.as_ptr(),
+        sbp: sense.as_mut_ptr(),
+        // Cheap sticks can take a while to commit a full cache.
+        timeout: 60_000,
+        flags: 0,
+        pack_id: 0,
+        usr_ptr: std::ptr::null_mut(),
+        status: 0,
+        masked_status: 0,
+        msg_status: 0,
+        sb_len_wr: 0,
+        host_status: 0,
+        driver_status: 0,
+        resid: 0,
+        duration: 0,
+        info: 0,
+    };
+    if unsafe { libc::ioctl(file.as_raw_fd(), SG_IO as libc::Ioctl, &mut hdr) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if hdr.status != 0 || hdr.host_status != 0 || hdr.driver_status != 0 {
+        // Many USB bridges reject the command outright; they have no cache
+        // we could reach anyway.
+        return Err(io::Error::from(io::ErrorKind::Unsupported));
+    }
+    Ok(())
+}
+
+fn open_node(name: &str) -> Result<Option<File>> {
+    let node = Path::new("/dev").join(name);
+    match OpenOptions::new().read(true).write(true).open(&node) {
+        Ok(file) => Ok(Some(file)),
+        Err(e) if skippable(&e) || e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(UsbError::io(
+            format!("Failed to open {}", node.display()),
+            e,
+        )),
+    }
+}
+
+fn skippable(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::PermissionDenied | io::ErrorKind::Unsupported
+    ) || matches!(
+        err.raw_os_error(),
+        Some(libc::EPERM | libc::ENOTTY | libc::EINVAL)
+    )
+}
+
+/// The disk a partition lives on (`sdb1` -> `sdb`); disks map to themselves.
+fn whole_disk(name: &str) -> String {
+    let sys = Path::new("/sys/class/block").join(name);
+    if !sys.join("partition").exists() {
+        return name.to_string();
+    }
+    fs::canonicalize(&sys)
+        .ok()
+        .and_then(|p| {
+            p.parent()?
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| name.to_string())
+}
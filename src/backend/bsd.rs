@@ -0,0 +1,394 @@
+use super::{
+    MountOptions, SystemBackend, classify_unmount_error, probe_missing, run, unescape_mount_field,
+};
+use crate::error::{Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use crate::usb::{Partition, UsbDevice, format_size};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// FreeBSD and OpenBSD, where there is no lsblk.
+///
+/// FreeBSD enumerates with `geom disk list`, uses `camcontrol devlist -v` to
+/// tell which disks hang off a `umass` (USB mass storage) bus and `gpart`
+/// for partitions. OpenBSD uses `sysctl hw.disknames`, the boot dmesg for
+/// the `umass` attachment and `disklabel` for partitions. Both then use the
+/// standard `mount`/`umount`.
+#[derive(Clone)]
+pub struct BsdBackend {
+    exec: Arc<dyn CommandExecutor>,
+}
+
+impl BsdBackend {
+    /// Uses `exec` to run every external command.
+    pub fn with_executor(exec: Arc<dyn CommandExecutor>) -> Self {
+        BsdBackend { exec }
+    }
+
+    fn stdout(&self, program: &str, args: &[&str]) -> Result<String> {
+        let output = run(self.exec.as_ref(), program, args)?;
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// (device, mountpoint) pairs from `mount -p`, which prints fstab lines.
+    fn mounts(&self) -> Result<Vec<(String, String)>> {
+        Ok(self
+            .stdout("mount", &["-p"])?
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                Some((
+                    unescape_mount_field(fields.next()?),
+                    unescape_mount_field(fields.next()?),
+                ))
+            })
+            .collect())
+    }
+
+    #[cfg(target_os = "freebsd")]
+    fn enumerate_disks(&self) -> Result<Vec<UsbDevice>> {
+        let usb = usb_disks_from_camcontrol(&self.stdout("camcontrol", &["devlist", "-v"])?);
+        let mut devices = Vec::new();
+        for disk in parse_geom_disks(&self.stdout("geom", &["disk", "list"])?) {
+            if !usb.contains(&disk.name) {
+                continue;
+            }
+            // A disk without a partition table makes gpart fail; that just
+            // means no partitions.
+            let mut partitions = self
+                .stdout("gpart", &["show", "-p", &disk.name])
+                .map(|out| parse_gpart(&out))
+                .unwrap_or_default();
+            if partitions.is_empty() && self.fstype(&disk.name).is_ok() {
+                partitions.push(Partition::whole_disk(&disk.name, disk.bytes, None));
+            }
+            partitions.iter_mut().for_each(probe_missing);
+            devices.push(UsbDevice {
+                size: format_size(disk.bytes),
+                bytes: disk.bytes,
+                vendor: None,
+                model: disk.descr,
+                hotplug: Some(true),
+                partitions,
+                speed_class: None,
+                name: disk.name,
+            });
+        }
+        Ok(devices)
+    }
+
+    #[cfg(target_os = "openbsd")]
+    fn enumerate_disks(&self) -> Result<Vec<UsbDevice>> {
+        let dmesg = std::fs::read_to_string("/var/run/dmesg.boot")
+            .or_else(|_| self.stdout("dmesg", &[]))
+            .unwrap_or_default();
+        let usb = usb_disks_from_dmesg(&dmesg);
+        let names = self.stdout("sysctl", &["-n", "hw.disknames"])?;
+
+        let mut devices = Vec::new();
+        for name in names.trim().split(',').filter_map(|d| d.split(':').next()) {
+            if !usb.iter().any(|(disk, _)| disk == name) {
+                continue;
+            }
+            let label = self.stdout("disklabel", &[name]).unwrap_or_default();
+            let (bytes, mut partitions) = parse_disklabel(name, &label);
+            partitions.iter_mut().for_each(probe_missing);
+            devices.push(UsbDevice {
+                name: name.to_string(),
+                size: format_size(bytes),
+                bytes: bytes,
+                vendor: None,
+                model: usb
+                    .iter()
+                    .find(|(disk, _)| disk == name)
+                    .and_then(|(_, m)| m.clone()),
+                hotplug: Some(true),
+                partitions,
+                speed_class: None,
+            });
+        }
+        Ok(devices)
+    }
+
+    /// Argument for `mount -t`: FreeBSD's `fstyp` names the filesystem on
+    /// the partition directly; OpenBSD's disklabel records it.
+    #[cfg(target_os = "freebsd")]
+    fn fstype(&self, partition: &str) -> Result<String> {
+        Ok(self
+            .stdout("fstyp", &[&format!("/dev/{}", partition)])?
+            .trim()
+            .to_string())
+    }
+
+    #[cfg(target_os = "openbsd")]
+    fn fstype(&self, partition: &str) -> Result<String> {
+        // "sd1i" is partition "i" of disk "sd1".
+        if partition.len() < 2 || !partition.is_char_boundary(partition.len() - 1) {
+            return Err(UsbError::Unsupported(format!(
+                "Invalid partition name {}",
+                partition
+            )));
+        }
+        let (disk, letter) = partition.split_at(partition.len() - 1);
+        let label = self.stdout("disklabel", &[disk])?;
+        let fstype = label
+            .lines()
+            .find_map(|l| {
+                let l = l.trim_start();
+                let rest = l.strip_prefix(letter)?.strip_prefix(':')?;
+                rest.split_whitespace().nth(2).map(str::to_string)
+            })
+            .ok_or_else(|| {
+                UsbError::parse("disklabel output", format!("no partition {}", partition))
+            })?;
+        Ok(match fstype.as_str() {
+            "MSDOS" => "msdos",
+            "4.2BSD" => "ffs",
+            "ext2fs" => "ext2fs",
+            "NTFS" => "ntfs",
+            "ISO9660" => "cd9660",
+            other => {
+                return Err(UsbError::Unsupported(format!(
+                    "Unsupported filesystem {}",
+                    other
+                )));
+            }
+        }
+        .to_string())
+    }
+}
+
+impl Default for BsdBackend {
+    fn default() -> Self {
+        BsdBackend::with_executor(Arc::new(SystemExecutor))
+    }
+}
+
+impl SystemBackend for BsdBackend {
+    fn enumerate(&self) -> Result<Vec<UsbDevice>> {
+        let mut devices = self.enumerate_disks()?;
+        let mounts = self.mounts()?;
+        for part in devices.iter_mut().flat_map(|d| d.partitions.iter_mut()) {
+            let source = format!("/dev/{}", part.name);
+            part.mountpoint = mounts
+                .iter()
+                .find(|(dev, _)| *dev == source)
+                .map(|(_, mp)| mp.clone());
+        }
+        Ok(devices)
+    }
+
+    fn mount(
+        &self,
+        partition: &str,
+        mountpoint: Option<&Path>,
+        options: MountOptions,
+    ) -> Result<PathBuf> {
+        let mountpoint = mountpoint.ok_or_else(|| {
+            UsbError::Unsupported("The bsd backend needs an explicit mountpoint".into())
+        })?;
+        let target = mountpoint
+            .to_str()
+            .ok_or_else(|| UsbError::Unsupported("Mountpoint is not valid UTF-8".into()))?;
+        let fstype = self.fstype(partition)?;
+        let source = format!("/dev/{}", partition);
+        let mut args = vec!["-t", &fstype];
+        if options.read_only {
+            args.push("-r");
+        }
+        args.extend([source.as_str(), target]);
+        run(self.exec.as_ref(), "mount", &args)?;
+        Ok(mountpoint.to_path_buf())
+    }
+
+    fn unmount(&self, mountpoint: &str) -> Result<()> {
+        run(self.exec.as_ref(), "umount", &[mountpoint])
+            .map(|_| ())
+            .map_err(|e| classify_unmount_error(e, mountpoint))
+    }
+
+    fn power_off(&self, device_name: &str) -> Result<()> {
+        if cfg!(target_os = "freebsd") {
+            run(self.exec.as_ref(), "camcontrol", &["eject", device_name]).map(|_| ())
+        } else {
+            run(self.exec.as_ref(), "eject", &[device_name]).map(|_| ())
+        }
+    }
+
+    fn flush(&self, _device_name: &str) -> Result<()> {
+        run(self.exec.as_ref(), "sync", &[]).map(|_| ())
+    }
+}
+
+#[cfg(target_os = "freebsd")]
+struct GeomDisk {
+    name: String,
+    bytes: u64,
+    descr: Option<String>,
+}
+
+/// Parses `geom disk list`: blocks starting with `Geom name: da0`, each with
+/// `Mediasize: <bytes> (<human>)` and `descr: <model>` lines.
+#[cfg(target_os = "freebsd")]
+fn parse_geom_disks(output: &str) -> Vec<GeomDisk> {
+    let mut disks: Vec<GeomDisk> = Vec::new();
+    for line in output.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix("Geom name:") {
+            disks.push(GeomDisk {
+                name: name.trim().to_string(),
+                bytes: 0,
+                descr: None,
+            });
+        } else if let Some(disk) = disks.last_mut() {
+            if let Some(size) = line.strip_prefix("Mediasize:") {
+                disk.bytes = size
+                    .split_whitespace()
+                    .next()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(0);
+            } else if let Some(descr) = line.strip_prefix("descr:") {
+                let descr = descr.trim();
+                disk.descr = (!descr.is_empty()).then(|| descr.to_string());
+            }
+        }
+    }
+    disks
+}
+
+/// Disk names on buses provided by `umass-sim`, from `camcontrol devlist -v`:
+///
+/// ```text
+/// scbus2 on umass-sim0 bus 0:
+/// <SanDisk Ultra 1.00>  at scbus2 target 0 lun 0 (pass3,da0)
+/// ```
+#[cfg(target_os = "freebsd")]
+fn usb_disks_from_camcontrol(output: &str) -> Vec<String> {
+    let mut on_usb_bus = false;
+    let mut disks = Vec::new();
+    for line in output.lines() {
+        if line.starts_with("scbus") {
+            on_usb_bus = line.contains(" on umass-sim");
+        } else if on_usb_bus && let Some((_, periphs)) = line.rsplit_once('(') {
+            disks.extend(
+                periphs
+                    .trim_end_matches(')')
+                    .split(',')
+                    .filter(|p| p.starts_with("da"))
+                    .map(str::to_string),
+            );
+        }
+    }
+    disks
+}
+
+/// Parses `gpart show -p da0`; the first row describes the disk itself.
+///
+/// ```text
+/// =>      63  31277169    da0  MBR  (15G)
+///         63  31277169  da0s1  fat32lba  [active]  (15G)
+/// ```
+#[cfg(target_os = "freebsd")]
+fn parse_gpart(output: &str) -> Vec<Partition> {
+    output
+        .lines()
+        .filter(|l| !l.trim_start().starts_with("=>"))
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 4 || fields[3] == "-" || fields[2] == "-" {
+                return None;
+            }
+            let sectors: u64 = fields[1].parse().ok()?;
+            Some(Partition {
+                name: fields[2].to_string(),
+                size: format_size(sectors * 512),
+                bytes: sectors * 512,
+                device_type: "part".to_string(),
+                fstype: None,
+                label: None,
+                uuid: None,
+                mountpoint: None,
+            })
+        })
+        .collect()
+}
+
+/// `(disk, model)` for every `sd` disk attached through `umass`, from dmesg:
+///
+/// ```text
+/// scsibus4 at umass0: 2 targets, initiator 0
+/// sd1 at scsibus4 targ 1 lun 0: <SanDisk, Ultra, 1.00> removable
+/// ```
+#[cfg(target_os = "openbsd")]
+fn usb_disks_from_dmesg(dmesg: &str) -> Vec<(String, Option<String>)> {
+    let usb_buses: Vec<&str> = dmesg
+        .lines()
+        .filter(|l| l.contains(" at umass"))
+        .filter_map(|l| l.split_whitespace().next())
+        .collect();
+    let mut disks: Vec<(String, Option<String>)> = Vec::new();
+    for line in dmesg.lines() {
+        let mut words = line.split_whitespace();
+        let (Some(disk), Some("at"), Some(bus)) = (words.next(), words.next(), words.next()) else {
+            continue;
+        };
+        if !disk.starts_with("sd") || !usb_buses.contains(&bus) {
+            continue;
+        }
+        let model = line
+            .split_once('<')
+            .and_then(|(_, rest)| rest.split_once('>'))
+            .map(|(m, _)| {
+                m.split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            });
+        // A device re-attached later in the boot log wins.
+        disks.retain(|(d, _)| d != disk);
+        disks.push((disk.to_string(), model));
+    }
+    disks
+}
+
+/// Total size and partitions from `disklabel sd1`. Partition `c` is the
+/// whole disk and is not listed.
+///
+/// ```text
+/// total sectors: 31277232
+/// #                size           offset  fstype [fsize bsize   cpg]
+///   c:         31277232                0  unused
+///   i:         31277169               63   MSDOS
+/// ```
+#[cfg(target_os = "openbsd")]
+fn parse_disklabel(disk: &str, output: &str) -> (u64, Vec<Partition>) {
+    let mut total = 0;
+    let mut partitions = Vec::new();
+    for line in output.lines().map(str::trim) {
+        if let Some(sectors) = line.strip_prefix("total sectors:") {
+            total = sectors.trim().parse::<u64>().unwrap_or(0) * 512;
+        } else if let Some((letter, rest)) = line.split_once(':') {
+            if letter.len() != 1 || letter == "c" {
+                continue;
+            }
+            let Some(sectors) = rest
+                .split_whitespace()
+                .next()
+                .and_then(|s| s.parse::<u64>().ok())
+            else {
+                continue;
+            };
+            partitions.push(Partition {
+                name: format!("{}{}", disk, letter),
+                size: format_size(sectors * 512),
+                bytes: sectors * 512,
+                device_type: "part".to_string(),
+                fstype: None,
+                label: None,
+                uuid: None,
+                mountpoint: None,
+            });
+        }
+    }
+    (total, partitions)
+}
@@ -0,0 +1,157 @@
+//! Remembering the last enumeration for a short while.
+
+use super::{MountOptions, SystemBackend};
+use crate::error::Result;
+use crate::usb::UsbDevice;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Wraps a backend so [`enumerate`](SystemBackend::enumerate) returns the
+/// previous result for up to `ttl`, for interactive use where every prompt
+/// lists devices and `lsblk` can take a noticeable moment.
+///
+/// The cache is dropped early when the kernel or udev reports a device
+/// change (Linux), and whenever mount, unmount, power-off or flush goes
+/// through the wrapper, since those change what enumeration would show.
+pub struct CachedBackend {
+    inner: Box<dyn SystemBackend>,
+    ttl: Duration,
+    cached: Mutex<Option<(Instant, Vec<UsbDevice>)>>,
+    #[cfg(target_os = "linux")]
+    uevents: Option<uevent::Listener>,
+}
+
+impl CachedBackend {
+    pub fn new(inner: Box<dyn SystemBackend>, ttl: Duration) -> CachedBackend {
+        CachedBackend {
+            inner,
+            ttl,
+            cached: Mutex::new(None),
+            #[cfg(target_os = "linux")]
+            uevents: uevent::Listener::open(),
+        }
+    }
+
+    /// Forgets the cached device list.
+    pub fn invalidate(&self) {
+        *self.cached.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    fn changed_since_cached(&self) -> bool {
+        #[cfg(target_os = "linux")]
+        if let Some(uevents) = &self.uevents {
+            return uevents.pending();
+        }
+        false
+    }
+}
+
+impl SystemBackend for CachedBackend {
+    fn enumerate(&self) -> Result<Vec<UsbDevice>> {
+        // Drain events first, so ones that arrive during the enumeration
+        // below invalidate the next lookup instead of being lost.
+        let changed = self.changed_since_cached();
+        let mut cached = self.cached.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((at, devices)) = cached.as_ref()
+            && !changed
+            && at.elapsed() < self.ttl
+        {
+            return Ok(devices.clone());
+        }
+        let devices = self.inner.enumerate()?;
+        *cached = Some((Instant::now(), devices.clone()));
+        Ok(devices)
+    }
+
+    fn mount(
+        &self,
+        partition: &str,
+        mountpoint: Option<&Path>,
+        options: MountOptions,
+    ) -> Result<PathBuf> {
+        self.invalidate();
+        self.inner.mount(partition, mountpoint, options)
+    }
+
+    fn unmount(&self, mountpoint: &str) -> Result<()> {
+        self.invalidate();
+        self.inner.unmount(mountpoint)
+    }
+
+    fn power_off(&self, device_name: &str) -> Result<()> {
+        self.invalidate();
+        self.inner.power_off(device_name)
+    }
+
+    fn flush(&self, device_name: &str) -> Result<()> {
+        self.inner.flush(device_name)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod uevent {
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    /// Kernel uevents (group 1) and the copies udev re-sends once it has
+    /// processed them (group 2).
+    const GROUPS: u32 = 1 | 2;
+
+    /// A non-blocking `NETLINK_KOBJECT_UEVENT` socket.
+    pub(super) struct Listener {
+        socket: OwnedFd,
+    }
+
+    impl Listener {
+        /// `None` where netlink isn't available, e.g. in some containers;
+        /// the cache then only expires by age.
+        pub(super) fn open() -> Option<Listener> {
+            let fd = unsafe {
+                libc::socket(
+                    libc::AF_NETLINK,
+                    libc::SOCK_DGRAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                    libc::NETLINK_KOBJECT_UEVENT,
+                )
+            };
+            if fd < 0 {
+                return None;
+            }
+            let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+            let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+            addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+            addr.nl_groups = GROUPS;
+            let bound = unsafe {
+                libc::bind(
+                    socket.as_raw_fd(),
+                    &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                    std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+                )
+            };
+            (bound == 0).then_some(Listener { socket })
+        }
+
+        /// Whether any event arrived since the last call; reads them all.
+        pub(super) fn pending(&self) -> bool {
+            let mut buffer = [0u8; 8192];
+            let mut any = false;
+            loop {
+                let n = unsafe {
+                    libc::recv(
+                        self.socket.as_raw_fd(),
+                        buffer.as_mut_ptr().cast(),
+                        buffer.len(),
+                        0,
+                    )
+                };
+                if n >= 0 {
+                    any = true;
+                    continue;
+                }
+                // WouldBlock: drained. ENOBUFS: we missed some, which is a
+                // change too.
+                return any || io::Error::last_os_error().raw_os_error() == Some(libc::ENOBUFS);
+            }
+        }
+    }
+}
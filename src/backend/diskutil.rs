@@ -0,0 +1,206 @@
+use super::{MountOptions, SystemBackend, probe_missing, run};
+use crate::error::{Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use crate::usb::{Partition, UsbDevice, format_size};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DiskList {
+    all_disks_and_partitions: Vec<DiskEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DiskEntry {
+    device_identifier: String,
+    size: u64,
+    content: Option<String>,
+    mount_point: Option<String>,
+    #[serde(default)]
+    partitions: Vec<PartitionEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PartitionEntry {
+    device_identifier: String,
+    size: u64,
+    content: Option<String>,
+    mount_point: Option<String>,
+    volume_name: Option<String>,
+}
+
+/// The subset of `diskutil info -plist` we use.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DiskInfo {
+    bus_protocol: Option<String>,
+    media_name: Option<String>,
+    #[serde(default)]
+    ejectable: bool,
+    mount_point: Option<String>,
+}
+
+/// macOS: enumeration through `diskutil list -plist` and `diskutil info`,
+/// with mount, unmount and eject mapped to the matching `diskutil` verbs.
+#[derive(Clone)]
+pub struct DiskutilBackend {
+    exec: Arc<dyn CommandExecutor>,
+}
+
+impl DiskutilBackend {
+    /// Uses `exec` to run every external command.
+    pub fn with_executor(exec: Arc<dyn CommandExecutor>) -> Self {
+        DiskutilBackend { exec }
+    }
+
+    fn info(&self, identifier: &str) -> Result<DiskInfo> {
+        let output = run(
+            self.exec.as_ref(),
+            "diskutil",
+            &["info", "-plist", identifier],
+        )?;
+        plist::from_bytes(&output.stdout).map_err(|e| UsbError::parse("diskutil info output", e))
+    }
+}
+
+impl Default for DiskutilBackend {
+    fn default() -> Self {
+        DiskutilBackend::with_executor(Arc::new(SystemExecutor))
+    }
+}
+
+impl SystemBackend for DiskutilBackend {
+    fn enumerate(&self) -> Result<Vec<UsbDevice>> {
+        let output = run(
+            self.exec.as_ref(),
+            "diskutil",
+            &["list", "-plist", "external", "physical"],
+        )?;
+        let list: DiskList = plist::from_bytes(&output.stdout)
+            .map_err(|e| UsbError::parse("diskutil list output", e))?;
+
+        let mut devices = Vec::new();
+        for disk in list.all_disks_and_partitions {
+            let info = self.info(&disk.device_identifier)?;
+            if info.bus_protocol.as_deref() != Some("USB") {
+                continue;
+            }
+            let mut partitions: Vec<Partition> = disk
+                .partitions
+                .into_iter()
+                .map(|p| Partition {
+                    name: p.device_identifier,
+                    size: format_size(p.size),
+                    bytes: p.size,
+                    device_type: p.content.unwrap_or_else(|| "part".to_string()),
+                    fstype: None,
+                    label: p.volume_name.filter(|v| !v.is_empty()),
+                    uuid: None,
+                    mountpoint: p.mount_point.filter(|m| !m.is_empty()),
+                })
+                .collect();
+            // Without a partition map, Content names the filesystem itself
+            // (e.g. DOS_FAT_32) rather than a *_partition_scheme.
+            let whole_disk_fs = disk
+                .content
+                .as_deref()
+                .is_some_and(|c| !c.is_empty() && !c.ends_with("_partition_scheme"));
+            if partitions.is_empty() && whole_disk_fs {
+                let mountpoint = disk
+                    .mount_point
+                    .or(info.mount_point)
+                    .filter(|m| !m.is_empty());
+                partitions.push(Partition::whole_disk(
+                    &disk.device_identifier,
+                    disk.size,
+                    mountpoint,
+                ));
+            }
+            partitions.iter_mut().for_each(probe_missing);
+            devices.push(UsbDevice {
+                name: disk.device_identifier,
+                size: format_size(disk.size),
+                bytes: disk.size,
+                // diskutil doesn't split vendor and model; MediaName is
+                // usually "<Vendor> <Model> Media".
+                vendor: None,
+                model: info.media_name,
+                hotplug: Some(info.ejectable),
+                partitions,
+                speed_class: None,
+            });
+        }
+        Ok(devices)
+    }
+
+    fn mount(
+        &self,
+        partition: &str,
+        mountpoint: Option<&Path>,
+        options: MountOptions,
+    ) -> Result<PathBuf> {
+        let mut args = vec!["mount"];
+        if options.read_only {
+            args.push("readOnly");
+        }
+        if let Some(path) = mountpoint {
+            let path = path
+                .to_str()
+                .ok_or_else(|| UsbError::Unsupported("Mountpoint is not valid UTF-8".into()))?;
+            args.extend(["-mountPoint", path]);
+        }
+        args.push(partition);
+        run(self.exec.as_ref(), "diskutil", &args)?;
+        self.info(partition)?
+            .mount_point
+            .filter(|m| !m.is_empty())
+            .map(PathBuf::from)
+            .ok_or_else(|| {
+                UsbError::parse(
+                    "diskutil info output",
+                    format!("{} has no mountpoint", partition),
+                )
+            })
+    }
+
+    fn unmount(&self, mountpoint: &str) -> Result<()> {
+        run(self.exec.as_ref(), "diskutil", &["unmount", mountpoint])
+            .map(|_| ())
+            .map_err(|e| match e {
+                // "Unmount of disk4s1 failed: at least one volume could not
+                //  be unmounted ... dissented by PID 412 (/usr/bin/vim)"
+                UsbError::CommandFailed { ref stderr, .. }
+                    if stderr.contains("dissented") || stderr.contains("in use") =>
+                {
+                    UsbError::MountBusy {
+                        mountpoint: mountpoint.to_string(),
+                        pids: dissenting_pids(stderr),
+                    }
+                }
+                other => other,
+            })
+    }
+
+    fn power_off(&self, device_name: &str) -> Result<()> {
+        run(self.exec.as_ref(), "diskutil", &["eject", device_name]).map(|_| ())
+    }
+
+    fn flush(&self, _device_name: &str) -> Result<()> {
+        run(self.exec.as_ref(), "sync", &[]).map(|_| ())
+    }
+}
+
+fn dissenting_pids(message: &str) -> Vec<u32> {
+    message
+        .split("PID ")
+        .skip(1)
+        .filter_map(|rest| {
+            let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+            digits.parse().ok()
+        })
+        .collect()
+}
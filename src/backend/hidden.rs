@@ -0,0 +1,93 @@
+//! Leaving devices the user never wants to see out of listings.
+
+use super::{MountOptions, SystemBackend};
+use crate::error::Result;
+use crate::policy::DeviceIdentity;
+use crate::usb::UsbDevice;
+use std::path::{Path, PathBuf};
+
+/// Tells who made a device, from its kernel name.
+type Identify = Box<dyn Fn(&str) -> Option<DeviceIdentity>>;
+
+/// Wraps a backend so [`enumerate`](SystemBackend::enumerate) leaves out
+/// devices matching one of the `hide` patterns of the config: built-in
+/// card readers, license dongles that never come out. Each pattern is a
+/// vendor/product ID (`0bda:0158`, or `0bda:*` for a whole vendor) or a
+/// serial number; see [`hides`].
+///
+/// [`find`](SystemBackend::find) still finds hidden devices, so commands
+/// naming one work as usual; it is only the listings that skip them.
+pub struct HidingBackend {
+    inner: Box<dyn SystemBackend>,
+    patterns: Vec<String>,
+    identify: Identify,
+}
+
+impl HidingBackend {
+    /// Hides devices matching `patterns`, identified through sysfs; only
+    /// Linux can tell, so elsewhere nothing is hidden.
+    pub fn new(inner: Box<dyn SystemBackend>, patterns: Vec<String>) -> HidingBackend {
+        HidingBackend::with_identify(inner, patterns, DeviceIdentity::lookup)
+    }
+
+    /// Identifies devices with `identify` instead of sysfs.
+    pub fn with_identify(
+        inner: Box<dyn SystemBackend>,
+        patterns: Vec<String>,
+        identify: impl Fn(&str) -> Option<DeviceIdentity> + 'static,
+    ) -> HidingBackend {
+        HidingBackend {
+            inner,
+            patterns,
+            identify: Box::new(identify),
+        }
+    }
+
+    fn hidden(&self, device: &UsbDevice) -> bool {
+        (self.identify)(&device.name)
+            .is_some_and(|id| self.patterns.iter().any(|pattern| hides(pattern, &id)))
+    }
+}
+
+/// Whether the hide `pattern` matches the device `id`: a `vvvv:pppp` or
+/// `vvvv:*` ID when it has a colon, a serial number otherwise.
+pub fn hides(pattern: &str, id: &DeviceIdentity) -> bool {
+    if pattern.contains(':') {
+        id.matches_vid_pid(pattern)
+    } else {
+        id.serial.as_deref() == Some(pattern)
+    }
+}
+
+impl SystemBackend for HidingBackend {
+    fn enumerate(&self) -> Result<Vec<UsbDevice>> {
+        let mut devices = self.inner.enumerate()?;
+        devices.retain(|device| !self.hidden(device));
+        Ok(devices)
+    }
+
+    fn mount(
+        &self,
+        partition: &str,
+        mountpoint: Option<&Path>,
+        options: MountOptions,
+    ) -> Result<PathBuf> {
+        self.inner.mount(partition, mountpoint, options)
+    }
+
+    fn unmount(&self, mountpoint: &str) -> Result<()> {
+        self.inner.unmount(mountpoint)
+    }
+
+    fn power_off(&self, device_name: &str) -> Result<()> {
+        self.inner.power_off(device_name)
+    }
+
+    fn flush(&self, device_name: &str) -> Result<()> {
+        self.inner.flush(device_name)
+    }
+
+    fn find(&self, device_name: &str) -> Result<UsbDevice> {
+        self.inner.find(device_name)
+    }
+}
@@ -0,0 +1,222 @@
+use super::{
+    MountOptions, SystemBackend, classify_unmount_error, flush_device_cache, is_mmc_disk,
+    mount_source, probe_missing, run, sd_speed_class, sync_filesystems,
+};
+use crate::error::{Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use crate::usb::{Partition, UsbDevice, format_size};
+use serde::{Deserialize, Deserializer};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+struct LsblkOutput {
+    blockdevices: Vec<LsblkDevice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LsblkDevice {
+    name: String,
+    #[serde(deserialize_with = "byte_count")]
+    size: u64,
+    #[serde(rename = "type")]
+    device_type: String,
+    tran: Option<String>,
+    mountpoint: Option<String>,
+    #[serde(default)]
+    fstype: Option<String>,
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    uuid: Option<String>,
+    vendor: Option<String>,
+    model: Option<String>,
+    hotplug: Option<bool>,
+    /// The kernel's removable-media flag.
+    #[serde(default)]
+    rm: Option<bool>,
+    // Children partitions
+    children: Option<Vec<LsblkDevice>>,
+}
+
+impl From<LsblkDevice> for UsbDevice {
+    fn from(dev: LsblkDevice) -> Self {
+        let mut partitions: Vec<Partition> = dev
+            .children
+            .unwrap_or_default()
+            .into_iter()
+            .map(Partition::from)
+            .collect();
+        if partitions.is_empty() && (dev.fstype.is_some() || dev.mountpoint.is_some()) {
+            partitions.push(Partition {
+                fstype: dev.fstype,
+                label: dev.label,
+                uuid: dev.uuid,
+                ..Partition::whole_disk(&dev.name, dev.size, dev.mountpoint)
+            });
+        }
+        UsbDevice {
+            speed_class: sd_speed_class(&dev.name),
+            name: dev.name,
+            size: format_size(dev.size),
+            bytes: dev.size,
+            vendor: dev.vendor,
+            model: dev.model,
+            hotplug: dev.hotplug,
+            partitions,
+        }
+    }
+}
+
+impl From<LsblkDevice> for Partition {
+    fn from(dev: LsblkDevice) -> Self {
+        Partition {
+            name: dev.name,
+            size: format_size(dev.size),
+            bytes: dev.size,
+            device_type: dev.device_type,
+            fstype: dev.fstype,
+            label: dev.label,
+            uuid: dev.uuid,
+            mountpoint: dev.mountpoint,
+        }
+    }
+}
+
+/// `lsblk -b` prints sizes as JSON numbers, but versions before 2.33 quote
+/// every value.
+fn byte_count<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Number(u64),
+        Text(String),
+    }
+    match Raw::deserialize(deserializer)? {
+        Raw::Number(n) => Ok(n),
+        Raw::Text(s) => s.trim().parse().map_err(serde::de::Error::custom),
+    }
+}
+
+/// The original mechanism: `lsblk -J` for enumeration and the standard
+/// `mount`/`umount`/`eject`/`sync` tools for everything else.
+#[derive(Clone)]
+pub struct LsblkBackend {
+    exec: Arc<dyn CommandExecutor>,
+    include_mmc: bool,
+    all_removable: bool,
+}
+
+impl LsblkBackend {
+    /// Uses `exec` to run every external command.
+    pub fn with_executor(exec: Arc<dyn CommandExecutor>) -> Self {
+        LsblkBackend {
+            exec,
+            include_mmc: false,
+            all_removable: false,
+        }
+    }
+
+    /// Also lists SD/MMC cards in slots the kernel drives directly
+    /// (`mmcblk*`), not just disks on the USB bus.
+    pub fn include_mmc(mut self, include: bool) -> Self {
+        self.include_mmc = include;
+        self
+    }
+
+    /// Also lists every other disk the kernel flags as removable or
+    /// hot-pluggable, such as drives in SATA hot-swap bays, and SD/MMC cards
+    /// as with [`include_mmc`](Self::include_mmc).
+    pub fn all_removable(mut self, all: bool) -> Self {
+        self.all_removable = all;
+        self
+    }
+}
+
+impl Default for LsblkBackend {
+    fn default() -> Self {
+        LsblkBackend::with_executor(Arc::new(SystemExecutor))
+    }
+}
+
+impl SystemBackend for LsblkBackend {
+    fn enumerate(&self) -> Result<Vec<UsbDevice>> {
+        let output = run(
+            self.exec.as_ref(),
+            "lsblk",
+            &[
+                "-J",
+                "-b",
+                "-o",
+                "NAME,SIZE,TYPE,TRAN,MOUNTPOINT,FSTYPE,LABEL,UUID,VENDOR,MODEL,HOTPLUG,RM",
+            ],
+        )?;
+
+        let parsed: LsblkOutput = serde_json::from_slice(&output.stdout)
+            .map_err(|e| UsbError::parse("lsblk output", e))?;
+
+        let mut devices: Vec<UsbDevice> = parsed
+            .blockdevices
+            .into_iter()
+            .filter(|d| {
+                d.tran.as_deref() == Some("usb")
+                    || ((self.include_mmc || self.all_removable) && is_mmc_disk(&d.name))
+                    || (self.all_removable
+                        && d.device_type == "disk"
+                        && (d.rm == Some(true) || d.hotplug == Some(true)))
+            })
+            .map(UsbDevice::from)
+            .collect();
+        // lsblk gets these from udev, which may not have probed the device.
+        devices
+            .iter_mut()
+            .flat_map(|d| &mut d.partitions)
+            .for_each(probe_missing);
+        Ok(devices)
+    }
+
+    fn mount(
+        &self,
+        partition: &str,
+        mountpoint: Option<&Path>,
+        options: MountOptions,
+    ) -> Result<PathBuf> {
+        let mountpoint = mountpoint.ok_or_else(|| {
+            UsbError::Unsupported("The lsblk backend needs an explicit mountpoint".into())
+        })?;
+        let source = format!("/dev/{}", partition);
+        let target = mountpoint
+            .to_str()
+            .ok_or_else(|| UsbError::Unsupported("Mountpoint is not valid UTF-8".into()))?;
+        if options.read_only {
+            run(self.exec.as_ref(), "mount", &["-o", "ro", &source, target])?;
+        } else {
+            run(self.exec.as_ref(), "mount", &[&source, target])?;
+        }
+        Ok(mountpoint.to_path_buf())
+    }
+
+    fn unmount(&self, mountpoint: &str) -> Result<()> {
+        let source = mount_source(mountpoint);
+        run(self.exec.as_ref(), "umount", &[mountpoint])
+            .map_err(|e| classify_unmount_error(e, mountpoint))?;
+        match source {
+            Some(device) => flush_device_cache(&device),
+            None => Ok(()),
+        }
+    }
+
+    fn power_off(&self, device_name: &str) -> Result<()> {
+        flush_device_cache(device_name)?;
+        run(
+            self.exec.as_ref(),
+            "eject",
+            &[&format!("/dev/{}", device_name)],
+        )
+        .map(|_| ())
+    }
+
+    fn flush(&self, device_name: &str) -> Result<()> {
+        sync_filesystems(&self.find(device_name)?)
+    }
+}
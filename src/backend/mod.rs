@@ -0,0 +1,533 @@
+//! System backends: the mechanisms used to enumerate, mount, unmount, power
+//! off and flush devices.
+//!
+//! Everything above this layer talks to a [`SystemBackend`] and doesn't care
+//! whether the work is done by `lsblk` and friends, by reading sysfs
+//! directly, by asking udisks2, by `diskutil` on macOS, or by the BSD
+//! base tools.
+
+#[cfg(target_os = "linux")]
+mod blockdev;
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+mod bsd;
+mod cached;
+#[cfg(target_os = "macos")]
+mod diskutil;
+mod hidden;
+mod lsblk;
+#[cfg(target_os = "linux")]
+mod sysfs;
+mod udisks;
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+pub use bsd::BsdBackend;
+pub use cached::CachedBackend;
+#[cfg(target_os = "macos")]
+pub use diskutil::DiskutilBackend;
+pub use hidden::{HidingBackend, hides};
+pub use lsblk::LsblkBackend;
+#[cfg(target_os = "linux")]
+pub use sysfs::SysfsBackend;
+pub use udisks::UdisksBackend;
+
+use crate::error::{IoContext, Result, UsbError};
+use crate::exec::{CommandExecutor, CommandOutput};
+use crate::usb::{Partition, SpeedClass, UsbDevice};
+use serde::Deserialize;
+use std::ffi::{CString, OsString};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStringExt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Operations lsusb needs from the host system.
+pub trait SystemBackend {
+    /// Lists attached USB block devices.
+    fn enumerate(&self) -> Result<Vec<UsbDevice>>;
+
+    /// Mounts `partition` (e.g. `sdb1`). Backends that pick the mountpoint
+    /// themselves accept `None`; the mountpoint actually used is returned.
+    fn mount(
+        &self,
+        partition: &str,
+        mountpoint: Option<&Path>,
+        options: MountOptions,
+    ) -> Result<PathBuf>;
+
+    /// Unmounts the filesystem mounted at `mountpoint`.
+    fn unmount(&self, mountpoint: &str) -> Result<()>;
+
+    /// Powers the device (e.g. `sdb`) off so it can be pulled.
+    fn power_off(&self, device_name: &str) -> Result<()>;
+
+    /// Flushes pending writes destined for the device.
+    fn flush(&self, device_name: &str) -> Result<()>;
+
+    /// Looks up a single USB device by kernel name (e.g. `sdb`).
+    ///
+    /// Fails with [`UsbError::NotUsb`] if the kernel knows the device but it
+    /// isn't on the USB bus, and [`UsbError::DeviceNotFound`] otherwise.
+    fn find(&self, device_name: &str) -> Result<UsbDevice> {
+        if let Some(device) = self
+            .enumerate()?
+            .into_iter()
+            .find(|d| d.name == device_name)
+        {
+            return Ok(device);
+        }
+        if Path::new("/sys/class/block").join(device_name).exists() {
+            Err(UsbError::NotUsb(device_name.to_string()))
+        } else {
+            Err(UsbError::DeviceNotFound(device_name.to_string()))
+        }
+    }
+}
+
+/// How [`SystemBackend::mount`] should mount a partition.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MountOptions {
+    /// Mount without write access, like `mount -o ro`.
+    pub read_only: bool,
+}
+
+/// Settings shared by the backends, applied by [`BackendKind::create_with`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BackendOptions {
+    /// Also list SD/MMC cards in built-in slots and readers the kernel
+    /// drives directly (`mmcblk*`). Linux only; USB card readers are always
+    /// listed.
+    pub include_mmc: bool,
+    /// Also list other disks the kernel flags as removable or hot-pluggable
+    /// (SATA hot-swap bays, card slots), not just USB ones. Linux only.
+    pub all_removable: bool,
+}
+
+/// Which [`SystemBackend`] implementation to use.
+///
+/// The default is `lsblk` on Linux, `diskutil` on macOS and `bsd` on
+/// FreeBSD/OpenBSD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// `lsblk`, `mount`, `umount`, `eject` and `sync` subprocesses.
+    Lsblk,
+    /// Reads sysfs and `/proc/self/mounts` directly and uses syscalls
+    /// (Linux only).
+    Sysfs,
+    /// Delegates to udisks2 through `udisksctl`.
+    Udisks2,
+    /// `diskutil` on macOS.
+    Diskutil,
+    /// geom/camcontrol (FreeBSD) or disklabel (OpenBSD) with the standard
+    /// `mount`/`umount`.
+    Bsd,
+}
+
+impl Default for BackendKind {
+    fn default() -> Self {
+        if cfg!(target_os = "macos") {
+            BackendKind::Diskutil
+        } else if cfg!(any(target_os = "freebsd", target_os = "openbsd")) {
+            BackendKind::Bsd
+        } else {
+            BackendKind::Lsblk
+        }
+    }
+}
+
+impl BackendKind {
+    /// The best backend for whoever is running us: on Linux a regular user
+    /// with udisks2 available gets [`BackendKind::Udisks2`], so mounting,
+    /// unmounting and powering off work without sudo; root, and everyone
+    /// else, gets the platform [default](BackendKind::default).
+    pub fn detect() -> BackendKind {
+        let desktop_session = std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_some()
+            || std::env::var_os("XDG_RUNTIME_DIR").is_some();
+        if cfg!(target_os = "linux")
+            && !crate::privilege::is_root()
+            && desktop_session
+            && crate::exec::which("udisksctl").is_some()
+        {
+            BackendKind::Udisks2
+        } else {
+            BackendKind::default()
+        }
+    }
+
+    /// Builds the backend this kind names, failing if it isn't available on
+    /// this platform.
+    pub fn create(self) -> Result<Box<dyn SystemBackend>> {
+        self.create_with(BackendOptions::default())
+    }
+
+    /// Like [`create`](BackendKind::create), with `options` applied.
+    pub fn create_with(self, options: BackendOptions) -> Result<Box<dyn SystemBackend>> {
+        match self {
+            BackendKind::Lsblk => Ok(Box::new(
+                LsblkBackend::default()
+                    .include_mmc(options.include_mmc)
+                    .all_removable(options.all_removable),
+            )),
+            #[cfg(target_os = "linux")]
+            BackendKind::Sysfs => Ok(Box::new(
+                SysfsBackend::default()
+                    .include_mmc(options.include_mmc)
+                    .all_removable(options.all_removable),
+            )),
+            BackendKind::Udisks2 => Ok(Box::new(
+                UdisksBackend::default()
+                    .include_mmc(options.include_mmc)
+                    .all_removable(options.all_removable),
+            )),
+            #[cfg(target_os = "macos")]
+            BackendKind::Diskutil => Ok(Box::new(DiskutilBackend::default())),
+            #[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+            BackendKind::Bsd => Ok(Box::new(BsdBackend::default())),
+            #[allow(unreachable_patterns)]
+            other => Err(UsbError::Unsupported(format!(
+                "The {} backend is not available on this platform",
+                other
+            ))),
+        }
+    }
+}
+
+/// The platform's default backend.
+pub fn default_backend() -> Result<Box<dyn SystemBackend>> {
+    BackendKind::default().create()
+}
+
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "lsblk" => Ok(BackendKind::Lsblk),
+            "sysfs" => Ok(BackendKind::Sysfs),
+            "udisks2" | "udisks" => Ok(BackendKind::Udisks2),
+            "diskutil" => Ok(BackendKind::Diskutil),
+            "bsd" => Ok(BackendKind::Bsd),
+            other => Err(format!(
+                "unknown backend '{}' (expected lsblk, sysfs, udisks2, diskutil or bsd)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BackendKind::Lsblk => "lsblk",
+            BackendKind::Sysfs => "sysfs",
+            BackendKind::Udisks2 => "udisks2",
+            BackendKind::Diskutil => "diskutil",
+            BackendKind::Bsd => "bsd",
+        })
+    }
+}
+
+/// Runs `f` with `partition` mounted. If it isn't already (`mountpoint` is
+/// `None`), it is mounted for the duration: wherever the backend likes, or
+/// on a fresh private directory under the system temporary directory for
+/// backends that need to be told, and unmounted again afterwards.
+pub fn with_mounted<T>(
+    backend: &dyn SystemBackend,
+    partition: &str,
+    mountpoint: Option<&str>,
+    options: MountOptions,
+    f: impl FnOnce(&Path) -> Result<T>,
+) -> Result<T> {
+    if let Some(mountpoint) = mountpoint {
+        return f(Path::new(mountpoint));
+    }
+    let (mountpoint, created) = match backend.mount(partition, None, options) {
+        Ok(mountpoint) => (mountpoint, false),
+        Err(UsbError::Unsupported(_)) => {
+            let dir = private_dir(&format!("lsusb-{}-", partition))?;
+            match backend.mount(partition, Some(&dir), options) {
+                Ok(mountpoint) => (mountpoint, true),
+                Err(e) => {
+                    let _ = fs::remove_dir(&dir);
+                    return Err(e);
+                }
+            }
+        }
+        Err(e) => return Err(e),
+    };
+    let result = f(&mountpoint);
+    let unmounted = backend.unmount(&mountpoint.to_string_lossy());
+    if created && unmounted.is_ok() {
+        let _ = fs::remove_dir(&mountpoint);
+    }
+    let value = result?;
+    unmounted?;
+    Ok(value)
+}
+
+/// Creates a new directory only the current user can enter, named `prefix`
+/// and a random suffix, under the system temporary directory. `mkdtemp`
+/// never reuses a path that exists, so another user can't have prepared it
+/// (or a symlink in its place) to redirect a mount run as root.
+fn private_dir(prefix: &str) -> Result<PathBuf> {
+    let template = std::env::temp_dir().join(format!("{}XXXXXX", prefix));
+    let context = || {
+        format!(
+            "Failed to create a directory in {}",
+            std::env::temp_dir().display()
+        )
+    };
+    let mut template = CString::new(template.into_os_string().into_vec())
+        .map_err(|_| UsbError::io(context(), io::Error::from(io::ErrorKind::InvalidInput)))?
+        .into_bytes_with_nul();
+    if unsafe { libc::mkdtemp(template.as_mut_ptr().cast()) }.is_null() {
+        return Err(UsbError::io(context(), io::Error::last_os_error()));
+    }
+    template.pop();
+    Ok(PathBuf::from(OsString::from_vec(template)))
+}
+
+/// Runs `program` with `args`, failing if it can't be started or exits
+/// non-zero. Returns the captured output on success.
+pub(crate) fn run(
+    exec: &dyn CommandExecutor,
+    program: &str,
+    args: &[&str],
+) -> Result<CommandOutput> {
+    check_output(program, exec.run(program, args))
+}
+
+/// Turns the raw result of a [`CommandExecutor`] call into lsusb's error
+/// conventions: a program that can't be found is [`UsbError::ToolMissing`],
+/// a non-zero exit is [`UsbError::CommandFailed`].
+pub(crate) fn check_output(
+    program: &str,
+    result: io::Result<CommandOutput>,
+) -> Result<CommandOutput> {
+    let output = result.map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => UsbError::ToolMissing {
+            program: program.to_string(),
+        },
+        _ => UsbError::io(format!("Failed to run {}", program), e),
+    })?;
+
+    if !output.success {
+        return Err(UsbError::CommandFailed {
+            program: program.to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(output)
+}
+
+/// Turns an unmount tool's "target is busy" failure into
+/// [`UsbError::MountBusy`], leaving other errors alone.
+pub(crate) fn classify_unmount_error(err: UsbError, mountpoint: &str) -> UsbError {
+    match err {
+        UsbError::CommandFailed { ref stderr, .. } if stderr.contains("busy") => {
+            UsbError::MountBusy {
+                mountpoint: mountpoint.to_string(),
+                pids: busy_pids(Path::new(mountpoint)),
+            }
+        }
+        other => other,
+    }
+}
+
+/// Fills in the type and UUID of `partition` from its superblock (see
+/// [`crate::probe`]) when the listing didn't have them, keeping whatever it
+/// did say. Device nodes we may not read (non-root) are left alone.
+pub(crate) fn probe_missing(partition: &mut Partition) {
+    if partition.fstype.is_some() && partition.uuid.is_some() {
+        return;
+    }
+    let Ok(Some(found)) = crate::probe::probe_path(&Path::new("/dev").join(&partition.name)) else {
+        return;
+    };
+    partition.fstype = partition.fstype.take().or(Some(found.fstype));
+    partition.label = partition.label.take().or(found.label);
+    partition.uuid = partition.uuid.take().or(found.uuid);
+}
+
+/// Writes out the filesystem mounted at `mountpoint`, and only it, with
+/// `syncfs(2)`. Elsewhere there is no such call: `fsync` on the root flushes
+/// the filesystem's metadata and `sync(2)` the rest, with everything else.
+pub fn sync_filesystem(mountpoint: &Path) -> Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let dir = fs::File::open(mountpoint)
+        .io_context(|| format!("Failed to open {}", mountpoint.display()))?;
+    #[cfg(target_os = "linux")]
+    let failed = unsafe { libc::syncfs(dir.as_raw_fd()) } != 0;
+    #[cfg(not(target_os = "linux"))]
+    let failed = unsafe { libc::fsync(dir.as_raw_fd()) } != 0;
+    if failed {
+        return Err(UsbError::io(
+            format!("Failed to sync {}", mountpoint.display()),
+            io::Error::last_os_error(),
+        ));
+    }
+    #[cfg(not(target_os = "linux"))]
+    unsafe {
+        libc::sync()
+    };
+    Ok(())
+}
+
+/// Flushes only what belongs to `device`: `syncfs(2)` on each filesystem
+/// mounted from it, then `fsync` on its block device nodes for writes that
+/// bypassed a filesystem. Unlike `sync(2)` this doesn't wait on unrelated
+/// disks. Device nodes we may not open (non-root) are skipped; their dirty
+/// pages are only ever written through a mounted filesystem in that case.
+pub(crate) fn sync_filesystems(device: &UsbDevice) -> Result<()> {
+    for mountpoint in device.mountpoints() {
+        sync_filesystem(Path::new(mountpoint))?;
+    }
+
+    let partitions = device
+        .partitions
+        .iter()
+        .map(|p| &p.name)
+        .filter(|name| **name != device.name);
+    let nodes = std::iter::once(&device.name).chain(partitions);
+    for name in nodes {
+        let node = Path::new("/dev").join(name);
+        match fs::File::open(&node) {
+            Ok(file) => file
+                .sync_all()
+                .io_context(|| format!("Failed to flush {}", node.display()))?,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::PermissionDenied | io::ErrorKind::NotFound
+                ) => {}
+            Err(e) => {
+                return Err(UsbError::io(
+                    format!("Failed to open {}", node.display()),
+                    e,
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// PIDs of processes with an open file or working directory under
+/// `mountpoint`. Processes we aren't allowed to inspect are skipped.
+pub fn busy_pids(mountpoint: &Path) -> Vec<u32> {
+    let Ok(procs) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    let uses_mount =
+        |link: PathBuf| fs::read_link(link).is_ok_and(|target| target.starts_with(mountpoint));
+
+    let mut pids: Vec<u32> = procs
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+            let dir = entry.path();
+            let open_files = fs::read_dir(dir.join("fd"))
+                .map(|fds| fds.filter_map(|fd| fd.ok()).any(|fd| uses_mount(fd.path())))
+                .unwrap_or(false);
+            (open_files || uses_mount(dir.join("cwd"))).then_some(pid)
+        })
+        .collect();
+    pids.sort_unstable();
+    pids
+}
+
+/// A line of `/proc/self/mounts`.
+#[derive(Debug, Clone)]
+pub(crate) struct MountEntry {
+    pub source: String,
+    pub target: String,
+    /// Only read on Linux; elsewhere `statfs(2)` names the type.
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub fstype: String,
+}
+
+/// Makes sure nothing for `device_name` is left in the kernel's buffers or
+/// the drive's write cache. Only Linux has a way to do this from userspace.
+pub(crate) fn flush_device_cache(device_name: &str) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    return blockdev::flush_cache(device_name);
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = device_name;
+        Ok(())
+    }
+}
+
+/// The device mounted at `mountpoint`, without `/dev/` (e.g. `sdb1`), if it
+/// is a block device.
+pub(crate) fn mount_source(mountpoint: &str) -> Option<String> {
+    read_mounts()
+        .ok()?
+        .into_iter()
+        .find(|m| m.target == mountpoint)?
+        .source
+        .strip_prefix("/dev/")
+        .map(String::from)
+}
+
+/// Whether `name` is an SD/MMC card's user area. eMMC boot and RPMB areas
+/// (`mmcblk0boot0`, `mmcblk0rpmb`) are separate block devices nobody should
+/// write to.
+pub(crate) fn is_mmc_disk(name: &str) -> bool {
+    name.strip_prefix("mmcblk")
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// The speed rating of the card behind `mmcblkN`, from its SD Status
+/// register. MMC and eMMC have no such register.
+pub(crate) fn sd_speed_class(name: &str) -> Option<SpeedClass> {
+    if !is_mmc_disk(name) {
+        return None;
+    }
+    let ssr = fs::read_to_string(Path::new("/sys/block").join(name).join("device/ssr")).ok()?;
+    SpeedClass::from_ssr(&ssr)
+}
+
+pub(crate) fn read_mounts() -> Result<Vec<MountEntry>> {
+    let table = fs::read_to_string("/proc/self/mounts")
+        .io_context(|| "Failed to read /proc/self/mounts")?;
+    Ok(table
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(' ');
+            let source = fields.next()?;
+            let target = fields.next()?;
+            let fstype = fields.next()?;
+            Some(MountEntry {
+                source: unescape_mount_field(source),
+                target: unescape_mount_field(target),
+                fstype: fstype.to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Undoes the octal escaping (`\040` for space, etc.) the kernel applies to
+/// fields of the mount table.
+pub(crate) fn unescape_mount_field(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 4 <= bytes.len() {
+            let escaped = std::str::from_utf8(&bytes[i + 1..i + 4])
+                .ok()
+                .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+            if let Some(v) = escaped {
+                out.push(v);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
@@ -0,0 +1,279 @@
+use super::{
+    MountEntry, MountOptions, SystemBackend, busy_pids, flush_device_cache, is_mmc_disk,
+    mount_source, probe_missing, read_mounts, sd_speed_class, sync_filesystems,
+};
+use crate::error::{IoContext, Result, UsbError};
+use crate::usb::{Partition, UsbDevice, format_size};
+use std::ffi::CString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SYS_BLOCK: &str = "/sys/block";
+
+/// Talks to the kernel directly: enumeration reads `/sys/block`, mounting
+/// and unmounting are plain syscalls. Needs no external tools, but mount and
+/// power-off need root.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SysfsBackend {
+    include_mmc: bool,
+    all_removable: bool,
+}
+
+impl SysfsBackend {
+    /// See [`LsblkBackend::include_mmc`](super::LsblkBackend::include_mmc).
+    pub fn include_mmc(mut self, include: bool) -> Self {
+        self.include_mmc = include;
+        self
+    }
+
+    /// See [`LsblkBackend::all_removable`](super::LsblkBackend::all_removable).
+    pub fn all_removable(mut self, all: bool) -> Self {
+        self.all_removable = all;
+        self
+    }
+}
+
+impl SystemBackend for SysfsBackend {
+    fn enumerate(&self) -> Result<Vec<UsbDevice>> {
+        let mounts = read_mounts()?;
+        let mut devices = Vec::new();
+
+        for entry in fs::read_dir(SYS_BLOCK).io_context(|| "Failed to read /sys/block")? {
+            let entry = entry.io_context(|| "Failed to read /sys/block entry")?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let sys_path = entry.path();
+
+            // /sys/block/sdb -> ../devices/pci0000:00/.../usb2/2-1/.../block/sdb
+            let Ok(real) = fs::canonicalize(&sys_path) else {
+                continue;
+            };
+            let mmc = (self.include_mmc || self.all_removable) && is_mmc_disk(&name);
+            let removable =
+                self.all_removable && !is_virtual(&real) && is_removable(&sys_path, &real);
+            if !real.to_string_lossy().contains("/usb") && !mmc && !removable {
+                continue;
+            }
+
+            let mut partitions = Vec::new();
+            for child in fs::read_dir(&sys_path)
+                .io_context(|| format!("Failed to read {}", sys_path.display()))?
+            {
+                let child =
+                    child.io_context(|| format!("Failed to read {}", sys_path.display()))?;
+                if !child.path().join("partition").exists() {
+                    continue;
+                }
+                let part_name = child.file_name().to_string_lossy().into_owned();
+                let bytes = sector_count(&child.path()) * 512;
+                partitions.push(Partition {
+                    size: format_size(bytes),
+                    bytes,
+                    device_type: "part".to_string(),
+                    fstype: udev_property(&child.path(), "ID_FS_TYPE"),
+                    label: fs_label(&child.path()),
+                    uuid: udev_property(&child.path(), "ID_FS_UUID"),
+                    mountpoint: mountpoint_of(&mounts, &part_name),
+                    name: part_name,
+                });
+            }
+            partitions.sort_by(|a, b| a.name.cmp(&b.name));
+
+            let bytes = sector_count(&sys_path) * 512;
+            let mountpoint = mountpoint_of(&mounts, &name);
+            if partitions.is_empty() {
+                let mut whole = Partition {
+                    fstype: udev_property(&sys_path, "ID_FS_TYPE"),
+                    label: fs_label(&sys_path),
+                    uuid: udev_property(&sys_path, "ID_FS_UUID"),
+                    ..Partition::whole_disk(&name, bytes, mountpoint)
+                };
+                // Without udev, only the disk itself can say whether it
+                // holds a filesystem.
+                probe_missing(&mut whole);
+                if whole.mountpoint.is_some() || whole.fstype.is_some() {
+                    partitions.push(whole);
+                }
+            }
+            partitions.iter_mut().for_each(probe_missing);
+            devices.push(UsbDevice {
+                size: format_size(bytes),
+                bytes,
+                vendor: read_attr(&sys_path.join("device/vendor")),
+                model: read_attr(&sys_path.join("device/model")),
+                // Everything on the USB bus is hot-pluggable, and so are cards.
+                hotplug: Some(true),
+                partitions,
+                speed_class: sd_speed_class(&name),
+                name,
+            });
+        }
+
+        devices.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(devices)
+    }
+
+    fn mount(
+        &self,
+        partition: &str,
+        mountpoint: Option<&Path>,
+        options: MountOptions,
+    ) -> Result<PathBuf> {
+        let mountpoint = mountpoint.ok_or_else(|| {
+            UsbError::Unsupported("The sysfs backend needs an explicit mountpoint".into())
+        })?;
+        let source = c_path(Path::new(&format!("/dev/{}", partition)))?;
+        let target = c_path(mountpoint)?;
+
+        // Like mount(8) without -t: try every block-device filesystem the
+        // kernel knows about until one accepts the superblock.
+        let filesystems = fs::read_to_string("/proc/filesystems")
+            .io_context(|| "Failed to read /proc/filesystems")?;
+        let flags = if options.read_only {
+            libc::MS_RDONLY
+        } else {
+            0
+        };
+        let mut last_error = None;
+        for fstype in filesystems.lines().filter(|l| !l.starts_with("nodev")) {
+            let Ok(fstype) = CString::new(fstype.trim()) else {
+                continue;
+            };
+            let rc = unsafe {
+                libc::mount(
+                    source.as_ptr(),
+                    target.as_ptr(),
+                    fstype.as_ptr(),
+                    flags,
+                    std::ptr::null(),
+                )
+            };
+            if rc == 0 {
+                return Ok(mountpoint.to_path_buf());
+            }
+            last_error = Some(std::io::Error::last_os_error());
+        }
+        match last_error {
+            Some(e) => Err(UsbError::io(format!("Failed to mount {}", partition), e)),
+            None => Err(UsbError::Unsupported(
+                "No block filesystems registered with the kernel".into(),
+            )),
+        }
+    }
+
+    fn unmount(&self, mountpoint: &str) -> Result<()> {
+        let source = mount_source(mountpoint);
+        let target = c_path(Path::new(mountpoint))?;
+        if unsafe { libc::umount(target.as_ptr()) } != 0 {
+            let err = std::io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::EBUSY) {
+                return Err(UsbError::MountBusy {
+                    mountpoint: mountpoint.to_string(),
+                    pids: busy_pids(Path::new(mountpoint)),
+                });
+            }
+            return Err(UsbError::io(
+                format!("Failed to unmount {}", mountpoint),
+                err,
+            ));
+        }
+        match source {
+            Some(device) => flush_device_cache(&device),
+            None => Ok(()),
+        }
+    }
+
+    fn power_off(&self, device_name: &str) -> Result<()> {
+        flush_device_cache(device_name)?;
+        // Deleting the SCSI device makes the kernel spin it down and detach it.
+        fs::write(
+            Path::new(SYS_BLOCK).join(device_name).join("device/delete"),
+            "1",
+        )
+        .io_context(|| format!("Failed to power off {}", device_name))
+    }
+
+    fn flush(&self, device_name: &str) -> Result<()> {
+        sync_filesystems(&self.find(device_name)?)
+    }
+}
+
+/// Loop devices, RAM disks, device-mapper targets: nothing to pull out.
+fn is_virtual(real: &Path) -> bool {
+    real.starts_with("/sys/devices/virtual")
+}
+
+/// Whether the disk has removable media, or sits behind a port the kernel
+/// flags as hot-pluggable, as lsblk's HOTPLUG column works it out.
+fn is_removable(sys_path: &Path, real: &Path) -> bool {
+    read_attr(&sys_path.join("removable")).as_deref() == Some("1")
+        || real
+            .ancestors()
+            .skip(1)
+            .any(|dir| read_attr(&dir.join("removable")).as_deref() == Some("removable"))
+}
+
+fn read_attr(path: &Path) -> Option<String> {
+    let value = fs::read_to_string(path).ok()?;
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// A property from udev's database entry for the device
+/// (`/run/udev/data/b8:16`), which is readable without root.
+fn udev_property(sys_path: &Path, key: &str) -> Option<String> {
+    let dev = read_attr(&sys_path.join("dev"))?;
+    let db = fs::read_to_string(format!("/run/udev/data/b{}", dev)).ok()?;
+    db.lines()
+        .filter_map(|l| l.strip_prefix("E:")?.split_once('='))
+        .find(|(k, v)| *k == key && !v.is_empty())
+        .map(|(_, v)| v.to_string())
+}
+
+/// The filesystem label udev probed. `ID_FS_LABEL` has anything outside
+/// `[A-Za-z0-9#+-.:=@_]` (spaces included) replaced by `_`, so decode the
+/// `\xNN`-escaped `ID_FS_LABEL_ENC` to get the real, possibly non-ASCII label.
+fn fs_label(sys_path: &Path) -> Option<String> {
+    udev_property(sys_path, "ID_FS_LABEL_ENC").map(|enc| unhex_escapes(&enc))
+}
+
+fn unhex_escapes(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && bytes.get(i + 1) == Some(&b'x') && i + 4 <= bytes.len() {
+            let decoded = std::str::from_utf8(&bytes[i + 2..i + 4])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            if let Some(b) = decoded {
+                out.push(b);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Size in 512-byte sectors, as exposed by the `size` attribute.
+fn sector_count(sys_path: &Path) -> u64 {
+    read_attr(&sys_path.join("size"))
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0)
+}
+
+fn mountpoint_of(mounts: &[MountEntry], name: &str) -> Option<String> {
+    let source = format!("/dev/{}", name);
+    mounts
+        .iter()
+        .find(|m| m.source == source)
+        .map(|m| m.target.clone())
+}
+
+fn c_path(path: &Path) -> Result<CString> {
+    use std::os::unix::ffi::OsStrExt;
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| UsbError::Unsupported(format!("{} contains a NUL byte", path.display())))
+}
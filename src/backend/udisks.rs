@@ -0,0 +1,118 @@
+use super::{
+    LsblkBackend, MountOptions, SystemBackend, classify_unmount_error, flush_device_cache,
+    read_mounts, run, sync_filesystems,
+};
+use crate::error::{Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use crate::usb::UsbDevice;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Delegates to udisks2 via `udisksctl`, which lets desktop users mount and
+/// power off removable media without root.
+///
+/// udisks has no machine-readable listing on the command line, so
+/// enumeration is shared with [`LsblkBackend`].
+#[derive(Clone)]
+pub struct UdisksBackend {
+    exec: Arc<dyn CommandExecutor>,
+    include_mmc: bool,
+    all_removable: bool,
+}
+
+impl UdisksBackend {
+    /// Uses `exec` to run every external command.
+    pub fn with_executor(exec: Arc<dyn CommandExecutor>) -> Self {
+        UdisksBackend {
+            exec,
+            include_mmc: false,
+            all_removable: false,
+        }
+    }
+
+    /// See [`LsblkBackend::include_mmc`].
+    pub fn include_mmc(mut self, include: bool) -> Self {
+        self.include_mmc = include;
+        self
+    }
+
+    /// See [`LsblkBackend::all_removable`].
+    pub fn all_removable(mut self, all: bool) -> Self {
+        self.all_removable = all;
+        self
+    }
+}
+
+impl Default for UdisksBackend {
+    fn default() -> Self {
+        UdisksBackend::with_executor(Arc::new(SystemExecutor))
+    }
+}
+
+impl SystemBackend for UdisksBackend {
+    fn enumerate(&self) -> Result<Vec<UsbDevice>> {
+        LsblkBackend::with_executor(self.exec.clone())
+            .include_mmc(self.include_mmc)
+            .all_removable(self.all_removable)
+            .enumerate()
+    }
+
+    fn mount(
+        &self,
+        partition: &str,
+        mountpoint: Option<&Path>,
+        options: MountOptions,
+    ) -> Result<PathBuf> {
+        if mountpoint.is_some() {
+            return Err(UsbError::Unsupported(
+                "The udisks2 backend chooses the mountpoint itself; omit it".into(),
+            ));
+        }
+        let device = format!("/dev/{}", partition);
+        let mut args = vec!["mount", "--no-user-interaction", "-b", &device];
+        if options.read_only {
+            args.extend(["-o", "ro"]);
+        }
+        let output = run(self.exec.as_ref(), "udisksctl", &args)?;
+        // "Mounted /dev/sdb1 at /run/media/user/DISK"
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let target = stdout
+            .trim()
+            .split_once(" at ")
+            .map(|(_, at)| at.trim_end_matches('.'))
+            .ok_or_else(|| UsbError::parse("udisksctl output", stdout.trim()))?;
+        Ok(PathBuf::from(target))
+    }
+
+    fn unmount(&self, mountpoint: &str) -> Result<()> {
+        let entry = read_mounts()?
+            .into_iter()
+            .find(|m| m.target == mountpoint)
+            .ok_or_else(|| UsbError::Unsupported(format!("{} is not mounted", mountpoint)))?;
+        run(
+            self.exec.as_ref(),
+            "udisksctl",
+            &["unmount", "--no-user-interaction", "-b", &entry.source],
+        )
+        .map_err(|e| classify_unmount_error(e, mountpoint))?;
+        match entry.source.strip_prefix("/dev/") {
+            Some(device) => flush_device_cache(device),
+            None => Ok(()),
+        }
+    }
+
+    fn power_off(&self, device_name: &str) -> Result<()> {
+        flush_device_cache(device_name)?;
+        let device = format!("/dev/{}", device_name);
+        run(
+            self.exec.as_ref(),
+            "udisksctl",
+            &["power-off", "--no-user-interaction", "-b", &device],
+        )
+        .map(|_| ())
+    }
+
+    fn flush(&self, device_name: &str) -> Result<()> {
+        sync_filesystems(&self.find(device_name)?)
+    }
+}
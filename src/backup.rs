@@ -0,0 +1,1444 @@
+//! Repeatable backups to a known stick, described by named profiles in the
+//! config file:
+//!
+//! ```toml
+//! [backup.home-docs]
+//! sources = ["~/Documents", "~/Notes"]
+//! target = { serial = "4C530001231119116284" }   # or { label = "BACKUP" }
+//! exclude = ["*.tmp", "node_modules"]
+//! verify = "full"
+//! ```
+//!
+//! Each source ends up under the profile's directory on the stick, named
+//! after its last component; files already there with the same size and
+//! modification time are left alone. With `snapshots = true` each run gets
+//! its own dated directory instead.
+
+use crate::digest::{hex, sha256_reader};
+use crate::error::{IoContext, Result, UsbError};
+use crate::hashcache::HashCache;
+use crate::ignore::{Ignore, glob};
+use crate::progress::{Phase, ProgressEvent, ProgressSink};
+use crate::transfer::{MtimeTolerance, TransferOptions, TransferReport, copy_files};
+use crate::usb::{Partition, UsbDevice};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime};
+use walkdir::WalkDir;
+
+/// Where profiles without a `dest` put their files on the stick.
+pub const DEFAULT_DIR: &str = "lsusb-backup";
+
+/// One `[backup.<name>]` section.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Files and directories to back up; `~/` is the home directory.
+    pub sources: Vec<PathBuf>,
+    /// Which stick to back up to.
+    pub target: Target,
+    /// Directory on the stick, relative to its root; `lsusb-backup/<name>`
+    /// when unset.
+    #[serde(default)]
+    pub dest: Option<PathBuf>,
+    /// Only files matching one of these are backed up; everything when
+    /// empty. See [`Filters`].
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Files and directories left out. See [`Filters`].
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub verify: Verify,
+    /// Keep a dated snapshot per run instead of one mirror; see [`run`].
+    #[serde(default)]
+    pub snapshots: bool,
+    /// Which snapshots to keep; all of them when unset. See [`Retention`].
+    #[serde(default)]
+    pub keep: Retention,
+    /// Run whenever `lsusb daemon` sees the stick plugged in; see
+    /// [`AutoBackups`].
+    #[serde(default)]
+    pub auto: bool,
+    /// Store deduplicated chunks in a repository instead of plain files;
+    /// see [`crate::repo`]. Every run is an archive, like a snapshot.
+    #[serde(default)]
+    pub chunked: bool,
+    /// Seconds a file's modification time may differ from its copy's for
+    /// the copy to count as unchanged; 2, FAT's resolution, when unset.
+    #[serde(default)]
+    pub mtime_tolerance: Option<u64>,
+    /// Also count copies whose times are off by whole time zone offsets as
+    /// unchanged; see [`MtimeTolerance::timezone_shifts`].
+    #[serde(default)]
+    pub timezone_shifts: bool,
+    /// minisign secret key each run's manifest is signed with; `~/` is the
+    /// home directory. See [`crate::sign`].
+    #[serde(default)]
+    pub sign: Option<PathBuf>,
+}
+
+/// The stick a profile backs up to. Set at least one; with both, both must
+/// match.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Target {
+    /// USB serial number of the drive, as shown by `lsusb backup list`.
+    pub serial: Option<String>,
+    /// Filesystem label of the partition to write to.
+    pub label: Option<String>,
+}
+
+/// Which snapshots [`prune`] keeps. A snapshot is kept if any rule wants
+/// it: one of the newest `last`, or the newest of one of the most recent
+/// `daily` days, `weekly` weeks (Monday to Sunday) or `monthly` months that
+/// have snapshots. With every rule at 0, everything is kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Retention {
+    #[serde(default)]
+    pub last: u32,
+    #[serde(default)]
+    pub daily: u32,
+    #[serde(default)]
+    pub weekly: u32,
+    #[serde(default)]
+    pub monthly: u32,
+}
+
+impl Retention {
+    /// No rules, so nothing is pruned.
+    pub fn is_empty(&self) -> bool {
+        *self == Retention::default()
+    }
+}
+
+/// How much checking follows the copy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verify {
+    /// Trust the writes.
+    None,
+    /// Check that every copied file has the source's size.
+    #[default]
+    Size,
+    /// Read every copied file back from the stick and compare it with the
+    /// source byte for byte.
+    Full,
+}
+
+impl Profile {
+    /// The comparison `mtime_tolerance` and `timezone_shifts` ask for.
+    pub fn tolerance(&self) -> MtimeTolerance {
+        let default = MtimeTolerance::default();
+        MtimeTolerance {
+            window: self
+                .mtime_tolerance
+                .map_or(default.window, Duration::from_secs),
+            timezone_shifts: self.timezone_shifts,
+        }
+    }
+
+    /// The directory the backup goes to, on the stick mounted at `root`,
+    /// with `dest`'s variables (see [`crate::template`]) filled in from
+    /// `vars`.
+    pub fn dest_dir(
+        &self,
+        name: &str,
+        root: &Path,
+        vars: &crate::template::Vars,
+    ) -> Result<PathBuf> {
+        let dest = match &self.dest {
+            Some(dest) => crate::template::expand(dest, vars)?,
+            None => Path::new(DEFAULT_DIR).join(name),
+        };
+        if dest
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)))
+        {
+            return Err(UsbError::Unsupported(format!(
+                "backup.{}.dest must be a relative path inside the stick",
+                name
+            )));
+        }
+        Ok(root.join(dest))
+    }
+
+    /// The include and exclude patterns.
+    pub fn filters(&self) -> Filters {
+        Filters {
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+        }
+    }
+
+    /// The sources with `~/` expanded, each paired with the name it gets on
+    /// the stick. Two sources with the same last component would overwrite
+    /// each other, so that is an error.
+    pub fn resolved_sources(&self) -> Result<Vec<(PathBuf, String)>> {
+        let mut sources: Vec<(PathBuf, String)> = Vec::new();
+        for source in &self.sources {
+            let path = expand_home(source);
+            let name = path
+                .file_name()
+                .ok_or_else(|| {
+                    UsbError::Unsupported(format!("can't back up {}", source.display()))
+                })?
+                .to_string_lossy()
+                .into_owned();
+            if sources.iter().any(|(_, n)| *n == name) {
+                return Err(UsbError::Unsupported(format!(
+                    "two sources are both named {}; back them up in separate profiles",
+                    name
+                )));
+            }
+            sources.push((path, name));
+        }
+        Ok(sources)
+    }
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Finds the drive and partition `target` names among `devices`.
+/// `serial_of` gives a device's USB serial number; see
+/// [`crate::policy::DeviceIdentity::lookup`].
+pub fn find_target<'a>(
+    devices: &'a [UsbDevice],
+    target: &Target,
+    serial_of: impl Fn(&str) -> Option<String>,
+) -> Result<(&'a UsbDevice, &'a Partition)> {
+    if target.serial.is_none() && target.label.is_none() {
+        return Err(UsbError::Unsupported(
+            "the backup target needs a serial or a label".into(),
+        ));
+    }
+    let wanted = || match (&target.serial, &target.label) {
+        (Some(serial), Some(label)) => format!("serial {} with label {}", serial, label),
+        (Some(serial), None) => format!("serial {}", serial),
+        (None, label) => format!("label {}", label.as_deref().unwrap_or_default()),
+    };
+    for device in devices {
+        if let Some(serial) = &target.serial
+            && serial_of(&device.name).as_ref() != Some(serial)
+        {
+            continue;
+        }
+        let partition = match &target.label {
+            Some(label) => device
+                .partitions
+                .iter()
+                .find(|p| p.label.as_ref() == Some(label)),
+            None => match device.partitions.as_slice() {
+                [only] => Some(only),
+                partitions => Some(
+                    partitions
+                        .iter()
+                        .find(|p| p.mountpoint.is_some())
+                        .ok_or_else(|| {
+                            UsbError::Unsupported(format!(
+                                "{} has several partitions; set a label in the backup target",
+                                device.name
+                            ))
+                        })?,
+                ),
+            },
+        };
+        if let Some(partition) = partition {
+            return Ok((device, partition));
+        }
+    }
+    Err(UsbError::DeviceNotFound(format!(
+        "no drive with {} is attached",
+        wanted()
+    )))
+}
+
+/// Glob patterns choosing which files a backup takes. `*` and `?` match
+/// within a path component, `**` across them. A pattern without a `/` is
+/// matched against each file and directory name (`*.tmp`,
+/// `node_modules`); one with a `/` against the path relative to the source
+/// (`build/**/*.o`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filters {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl Filters {
+    /// Whether the file or directory at `relative` (to its source) is left
+    /// out, along with everything under it.
+    pub fn excludes(&self, relative: &Path) -> bool {
+        self.exclude.iter().any(|p| pattern_matches(p, relative))
+    }
+
+    /// Whether the file at `relative` is taken.
+    pub fn includes(&self, relative: &Path) -> bool {
+        !self.excludes(relative)
+            && (self.include.is_empty()
+                || self.include.iter().any(|p| pattern_matches(p, relative)))
+    }
+}
+
+fn pattern_matches(pattern: &str, relative: &Path) -> bool {
+    let path: Vec<String> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    let pattern = pattern.trim_end_matches('/');
+    if pattern.contains('/') {
+        glob(
+            pattern.trim_start_matches('/').as_bytes(),
+            path.join("/").as_bytes(),
+        )
+    } else {
+        path.last()
+            .is_some_and(|name| glob(pattern.as_bytes(), name.as_bytes()))
+    }
+}
+
+/// Marks a snapshot that is still being written.
+const PARTIAL_SUFFIX: &str = ".partial";
+
+/// A snapshot's directory name: its UTC start time as `YYYY-MM-DD_HHMMSS`,
+/// so names sort by age.
+pub fn snapshot_name(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    crate::audit::utc_timestamp(secs)
+        .replace(' ', "_")
+        .replace(':', "")
+}
+
+fn is_snapshot_name(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    bytes.len() == 17
+        && bytes.iter().enumerate().all(|(i, &b)| match i {
+            4 | 7 => b == b'-',
+            10 => b == b'_',
+            _ => b.is_ascii_digit(),
+        })
+}
+
+/// What [`AutoBackups::update`] asks the daemon to do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AutoEvent {
+    /// The profile's stick was plugged in; the backup starts after the
+    /// delay unless the stick goes away first.
+    Scheduled { profile: String, device: String },
+    /// The stick went away during the delay.
+    Aborted { profile: String },
+    /// Time to run the backup.
+    Due { profile: String, device: String },
+}
+
+/// Tracks the sticks of [`Profile::auto`] profiles for a polling daemon:
+/// a backup is scheduled when its stick appears, becomes due once it has
+/// stayed attached for the delay, and runs once per attach.
+#[derive(Debug)]
+pub struct AutoBackups {
+    delay: Duration,
+    pending: HashMap<String, Instant>,
+    done: HashSet<String>,
+}
+
+impl AutoBackups {
+    /// Waits `delay` between a stick appearing and its backup starting, so
+    /// the user can unplug it to skip the run.
+    pub fn new(delay: Duration) -> Self {
+        AutoBackups {
+            delay,
+            pending: HashMap::new(),
+            done: HashSet::new(),
+        }
+    }
+
+    /// Compares the attached `devices` with what was seen before, at time
+    /// `now`.
+    pub fn update<'a>(
+        &mut self,
+        profiles: impl IntoIterator<Item = (&'a String, &'a Profile)>,
+        devices: &[UsbDevice],
+        serial_of: impl Fn(&str) -> Option<String>,
+        now: Instant,
+    ) -> Vec<AutoEvent> {
+        let mut events = Vec::new();
+        for (name, profile) in profiles.into_iter().filter(|(_, p)| p.auto) {
+            let device = match find_target(devices, &profile.target, &serial_of) {
+                Ok((device, _)) => device.name.clone(),
+                Err(_) => {
+                    self.done.remove(name);
+                    if self.pending.remove(name).is_some() {
+                        events.push(AutoEvent::Aborted {
+                            profile: name.clone(),
+                        });
+                    }
+                    continue;
+                }
+            };
+            if self.done.contains(name) {
+                continue;
+            }
+            match self.pending.get(name) {
+                None => {
+                    self.pending.insert(name.clone(), now + self.delay);
+                    events.push(AutoEvent::Scheduled {
+                        profile: name.clone(),
+                        device,
+                    });
+                }
+                Some(&due) if now >= due => {
+                    self.pending.remove(name);
+                    self.done.insert(name.clone());
+                    events.push(AutoEvent::Due {
+                        profile: name.clone(),
+                        device,
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+        events
+    }
+}
+
+/// The complete snapshots in a profile's directory, oldest first.
+pub fn snapshots(dest: &Path) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    let entries = match fs::read_dir(dest) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(names),
+        Err(e) => {
+            return Err(UsbError::io(
+                format!("Failed to read {}", dest.display()),
+                e,
+            ));
+        }
+    };
+    for entry in entries {
+        let entry = entry.io_context(|| format!("Failed to read {}", dest.display()))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if is_snapshot_name(&name) && entry.file_type().is_ok_and(|t| t.is_dir()) {
+            names.push(name);
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// The snapshots (of `names`, as returned by [`snapshots`]) that `keep`
+/// lets go, oldest first. The newest snapshot is never among them.
+pub fn prune_plan(names: &[String], keep: &Retention) -> Vec<String> {
+    if keep.is_empty() {
+        return Vec::new();
+    }
+    let newest_first: Vec<&String> = names.iter().rev().collect();
+    let mut kept = vec![false; newest_first.len()];
+    for flag in kept.iter_mut().take(keep.last.max(1) as usize) {
+        *flag = true;
+    }
+    let rules = [
+        (keep.daily, day as fn(&str) -> i64),
+        (keep.weekly, week),
+        (keep.monthly, month),
+    ];
+    for (count, period) in rules {
+        let mut seen = Vec::new();
+        for (i, name) in newest_first.iter().enumerate() {
+            if seen.len() == count as usize {
+                break;
+            }
+            let p = period(name);
+            if !seen.contains(&p) {
+                seen.push(p);
+                kept[i] = true;
+            }
+        }
+    }
+    let mut pruned: Vec<String> = newest_first
+        .iter()
+        .zip(&kept)
+        .filter(|(_, kept)| !**kept)
+        .map(|(name, _)| name.to_string())
+        .collect();
+    pruned.reverse();
+    pruned
+}
+
+/// Deletes the snapshots in a profile's directory that `keep` lets go (see
+/// [`prune_plan`]). Returns their names.
+pub fn prune(dest: &Path, keep: &Retention) -> Result<Vec<String>> {
+    let pruned = prune_plan(&snapshots(dest)?, keep);
+    for name in &pruned {
+        let path = dest.join(name);
+        fs::remove_dir_all(&path).io_context(|| format!("Failed to remove {}", path.display()))?;
+        let _ = fs::remove_file(manifest_path(&path));
+        let _ = fs::remove_file(crate::sign::signature_path(&manifest_path(&path)));
+    }
+    Ok(pruned)
+}
+
+/// The year, month and day of a snapshot name.
+fn snapshot_date(name: &str) -> (i64, i64, i64) {
+    let field =
+        |range: std::ops::Range<usize>| name.get(range).and_then(|s| s.parse().ok()).unwrap_or(0);
+    (field(0..4), field(5..7), field(8..10))
+}
+
+/// Days since 1970-01-01 of a snapshot.
+fn day(name: &str) -> i64 {
+    let (year, month, day) = snapshot_date(name);
+    // Howard Hinnant's days_from_civil.
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Weeks, Monday to Sunday, since the one holding 1970-01-01 (a Thursday).
+fn week(name: &str) -> i64 {
+    (day(name) + 3).div_euclid(7)
+}
+
+fn month(name: &str) -> i64 {
+    let (year, month, _) = snapshot_date(name);
+    year * 12 + month
+}
+
+/// How a file differs between two snapshots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    /// A different size or modification time.
+    Changed,
+}
+
+/// One file that differs between two snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// Relative to the snapshot, e.g. `Documents/report.odt`.
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+    /// Size in the older snapshot; 0 if it was added.
+    pub old_bytes: u64,
+    /// Size in the newer snapshot; 0 if it was removed.
+    pub new_bytes: u64,
+}
+
+impl Change {
+    /// How much bigger the file got.
+    pub fn delta(&self) -> i64 {
+        self.new_bytes as i64 - self.old_bytes as i64
+    }
+}
+
+/// What changed from snapshot directory `old` to `new`, sorted by path.
+pub fn diff(old: &Path, new: &Path) -> Result<Vec<Change>> {
+    Ok(diff_manifests(&manifest(old)?, &manifest(new)?))
+}
+
+/// A snapshot with its size and what changed since the one before.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotSummary {
+    pub name: String,
+    pub files: u64,
+    /// Total size of its files, counting ones hardlinked from the previous
+    /// snapshot.
+    pub bytes: u64,
+    pub added: u64,
+    pub removed: u64,
+    pub changed: u64,
+    /// Growth in total size since the previous snapshot.
+    pub delta: i64,
+}
+
+/// Every complete snapshot in a profile's directory, oldest first. The
+/// first one counts all its files as added.
+pub fn snapshot_summaries(dest: &Path) -> Result<Vec<SnapshotSummary>> {
+    let mut manifests = Vec::new();
+    for name in snapshots(dest)? {
+        manifests.push((manifest(&dest.join(&name))?, name));
+    }
+    Ok(summarize(manifests))
+}
+
+/// Summaries of consecutive snapshots, given as their manifests and names.
+pub(crate) fn summarize(manifests: Vec<(Manifest, String)>) -> Vec<SnapshotSummary> {
+    let mut summaries = Vec::new();
+    let mut previous = Manifest::new();
+    for (current, name) in manifests {
+        let changes = diff_manifests(&previous, &current);
+        let count = |kind| changes.iter().filter(|c| c.kind == kind).count() as u64;
+        summaries.push(SnapshotSummary {
+            files: current.len() as u64,
+            bytes: current.values().map(|(size, _)| size).sum(),
+            added: count(ChangeKind::Added),
+            removed: count(ChangeKind::Removed),
+            changed: count(ChangeKind::Changed),
+            delta: changes.iter().map(Change::delta).sum(),
+            name,
+        });
+        previous = current;
+    }
+    summaries
+}
+
+pub(crate) type Manifest = BTreeMap<PathBuf, (u64, SystemTime)>;
+
+fn manifest(dir: &Path) -> Result<Manifest> {
+    Ok(walk(dir, &Filters::default(), &Ignore::none())?
+        .into_iter()
+        .map(|(_, relative, size, modified)| (relative, (size, modified)))
+        .collect())
+}
+
+pub(crate) fn diff_manifests(old: &Manifest, new: &Manifest) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for (path, &(old_bytes, old_modified)) in old {
+        match new.get(path) {
+            None => changes.push(Change {
+                path: path.clone(),
+                kind: ChangeKind::Removed,
+                old_bytes,
+                new_bytes: 0,
+            }),
+            Some(&(new_bytes, new_modified))
+                if new_bytes != old_bytes || new_modified != old_modified =>
+            {
+                changes.push(Change {
+                    path: path.clone(),
+                    kind: ChangeKind::Changed,
+                    old_bytes,
+                    new_bytes,
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    for (path, &(new_bytes, _)) in new.iter().filter(|(path, _)| !old.contains_key(*path)) {
+        changes.push(Change {
+            path: path.clone(),
+            kind: ChangeKind::Added,
+            old_bytes: 0,
+            new_bytes,
+        });
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    changes
+}
+
+/// Removes what interrupted runs left behind.
+fn clear_partial_snapshots(dest: &Path) -> Result<()> {
+    for entry in fs::read_dir(dest).io_context(|| format!("Failed to read {}", dest.display()))? {
+        let entry = entry.io_context(|| format!("Failed to read {}", dest.display()))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        if is_dir
+            && name
+                .strip_suffix(PARTIAL_SUFFIX)
+                .is_some_and(is_snapshot_name)
+        {
+            let path = entry.path();
+            fs::remove_dir_all(&path)
+                .io_context(|| format!("Failed to remove {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Fails unless the filesystem holding `dir` supports hard links; FAT and
+/// exFAT don't.
+fn ensure_hard_links(dir: &Path) -> Result<()> {
+    let probe = dir.join(".lsusb-link-probe");
+    let linked = probe.with_extension("linked");
+    File::create(&probe).io_context(|| format!("Failed to create {}", probe.display()))?;
+    let result = fs::hard_link(&probe, &linked);
+    let _ = fs::remove_file(&linked);
+    let _ = fs::remove_file(&probe);
+    result.map_err(|_| {
+        UsbError::Unsupported(
+            "snapshots need a filesystem with hard links, such as ext4; reformat the stick or set snapshots = false"
+                .into(),
+        )
+    })
+}
+
+/// Hardlinks the unchanged file `old` from the previous snapshot into the
+/// new one at `new`.
+fn link(old: &Path, new: &Path) -> Result<()> {
+    if let Some(parent) = new.parent() {
+        fs::create_dir_all(parent)
+            .io_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::hard_link(old, new)
+        .io_context(|| format!("Failed to link {} to {}", new.display(), old.display()))
+}
+
+/// What [`run`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BackupReport {
+    /// Where the backup went: the profile's directory, or the new snapshot
+    /// in it.
+    pub dest: PathBuf,
+    pub files_copied: u64,
+    pub bytes_copied: u64,
+    /// Files already up to date on the stick (hardlinked from the previous
+    /// snapshot when taking snapshots).
+    pub files_unchanged: u64,
+    /// Files checked after copying, per the profile's [`Verify`].
+    pub files_verified: u64,
+    /// Older snapshots deleted afterwards, per the profile's [`Retention`].
+    pub pruned: Vec<String>,
+    pub elapsed: Duration,
+}
+
+/// Backs `profile` up into `dest` (see [`Profile::dest_dir`]), copying new
+/// and changed files and then verifying them.
+///
+/// With [`Profile::snapshots`] every run creates a directory named after
+/// its start time (see [`snapshot_name`]) holding a complete copy, where
+/// files unchanged since the previous snapshot are hard links to it, like
+/// `rsync --link-dest`: each run only stores what changed, and any snapshot
+/// can be browsed or restored on its own. That needs hard links, so ext4
+/// rather than FAT or exFAT.
+pub fn run(
+    profile: &Profile,
+    dest: &Path,
+    options: &TransferOptions,
+    progress: impl ProgressSink,
+) -> Result<BackupReport> {
+    let mut paths: Vec<&Path> = profile.sources.iter().map(PathBuf::as_path).collect();
+    paths.push(dest);
+    let job = crate::jobs::start("backup", &paths, Some(&options.cancel));
+    let mut progress = job.track(progress);
+    if profile.chunked {
+        if profile.sign.is_some() {
+            return Err(UsbError::Unsupported(
+                "signing applies to mirror and snapshot backups, not chunked ones".into(),
+            ));
+        }
+        return crate::repo::backup(profile, dest, options, progress);
+    }
+    let started = Instant::now();
+    progress.emit(ProgressEvent::Phase(Phase::Scanning));
+    fs::create_dir_all(dest).io_context(|| format!("Failed to create {}", dest.display()))?;
+    // A mirror is updated in place. A snapshot is written next to the
+    // previous one under a temporary name, unchanged files hardlinked from
+    // it, and renamed into place once complete.
+    let (root, baseline, finished) = if profile.snapshots {
+        let name = snapshot_name(SystemTime::now());
+        let finished = dest.join(&name);
+        if finished.exists() {
+            return Err(UsbError::Unsupported(format!(
+                "snapshot {} already exists",
+                finished.display()
+            )));
+        }
+        let partial = dest.join(format!("{}{}", name, PARTIAL_SUFFIX));
+        clear_partial_snapshots(dest)?;
+        fs::create_dir_all(&partial)
+            .io_context(|| format!("Failed to create {}", partial.display()))?;
+        ensure_hard_links(&partial)?;
+        let previous = snapshots(dest)?.pop().map(|name| dest.join(name));
+        (partial, previous, Some(finished))
+    } else {
+        (dest.to_path_buf(), Some(dest.to_path_buf()), None)
+    };
+
+    let mut files = Vec::new();
+    let mut contents = Vec::new();
+    let mut unchanged = HashSet::new();
+    for (path, inside, size, modified) in source_files(profile, &options.ignore)? {
+        options.cancel.check()?;
+        let target = root.join(&inside);
+        match baseline.as_ref().map(|b| b.join(&inside)) {
+            Some(old) if is_up_to_date(&old, size, modified, &profile.tolerance()) => {
+                if old != target {
+                    link(&old, &target)?;
+                }
+                unchanged.insert(inside.clone());
+            }
+            _ => files.push((path, target, size)),
+        }
+        contents.push(inside);
+    }
+
+    let copied = copy_files(&files, &root, options, &mut progress)?;
+    let verified = settle(&files, &copied, profile.verify, options)?;
+    // Hashes of unchanged files carry over from the previous manifest.
+    let previous = match &baseline {
+        Some(baseline) => read_manifest(baseline).unwrap_or_default(),
+        None => Vec::new(),
+    };
+    let previous: HashMap<PathBuf, String> = previous
+        .into_iter()
+        .map(|(hash, path)| (path, hash))
+        .collect();
+    let mut manifest = Vec::new();
+    for inside in contents {
+        options.cancel.check()?;
+        let target = root.join(&inside);
+        let hash = match previous.get(&inside) {
+            Some(hash) if unchanged.contains(&inside) => hash.clone(),
+            _ if !target.is_file() => continue,
+            _ => hash_file(&target, |_| {})?,
+        };
+        manifest.push((hash, inside));
+    }
+    let mut pruned = Vec::new();
+    let dest = match finished {
+        Some(finished) => {
+            fs::rename(&root, &finished)
+                .io_context(|| format!("Failed to rename {}", root.display()))?;
+            write_manifest(&finished, &manifest)?;
+            pruned = prune(dest, &profile.keep)?;
+            finished
+        }
+        None => {
+            write_manifest(&root, &manifest)?;
+            root
+        }
+    };
+    if let Some(key) = &profile.sign {
+        crate::sign::Minisign::default().sign(&manifest_path(&dest), &expand_home(key))?;
+    }
+    progress.emit(ProgressEvent::Phase(Phase::Done));
+    Ok(BackupReport {
+        dest,
+        files_copied: copied.files_copied,
+        bytes_copied: copied.bytes_copied,
+        files_unchanged: unchanged.len() as u64,
+        files_verified: verified,
+        pruned,
+        elapsed: started.elapsed(),
+    })
+}
+
+/// Where the checksums of the backup at `backup` (a mirror or a snapshot)
+/// are kept: next to it, as `<name>.sha256`, in `sha256sum` format so
+/// `sha256sum -c` can check it too.
+pub fn manifest_path(backup: &Path) -> PathBuf {
+    let name = backup.file_name().unwrap_or_default().to_string_lossy();
+    backup.with_file_name(format!("{}.sha256", name))
+}
+
+/// The checksums recorded for the backup at `backup`: each file's SHA-256
+/// in hex and its path in the backup.
+pub fn read_manifest(backup: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let path = manifest_path(backup);
+    let text =
+        fs::read_to_string(&path).io_context(|| format!("Failed to read {}", path.display()))?;
+    text.lines()
+        .map(|line| {
+            line.split_once("  ")
+                .filter(|(hash, _)| hash.len() == 64)
+                .map(|(hash, file)| (hash.to_string(), PathBuf::from(file)))
+                .ok_or_else(|| UsbError::Parse {
+                    what: path.display().to_string(),
+                    message: format!("not a checksum line: {}", line),
+                })
+        })
+        .collect()
+}
+
+fn write_manifest(backup: &Path, entries: &[(String, PathBuf)]) -> Result<()> {
+    let path = manifest_path(backup);
+    // Not `.partial`: `<snapshot>.partial` is an unfinished snapshot.
+    let partial = path.with_file_name(format!(
+        "{}.tmp",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let text: String = entries
+        .iter()
+        .map(|(hash, file)| (hash, file.to_string_lossy()))
+        // One line per file; names with a line break can't be listed.
+        .filter(|(_, file)| !file.contains('\n'))
+        .map(|(hash, file)| format!("{}  {}\n", hash, file))
+        .collect();
+    fs::write(&partial, text).io_context(|| format!("Failed to write {}", partial.display()))?;
+    // The old manifest's signature doesn't match the new one; the run
+    // signs it again if the profile asks for that.
+    let _ = fs::remove_file(crate::sign::signature_path(&path));
+    fs::rename(&partial, &path).io_context(|| format!("Failed to rename {}", partial.display()))
+}
+
+/// The SHA-256 of the file at `path` in hex, read from the disk rather than
+/// the page cache where the platform allows.
+pub(crate) fn hash_file(path: &Path, read: impl FnMut(usize)) -> Result<String> {
+    let mut file = File::open(path).io_context(|| format!("Failed to open {}", path.display()))?;
+    drop_cache(&file);
+    let digest = sha256_reader(&mut file, read)
+        .io_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(hex(&digest))
+}
+
+/// What [`verify`] found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Files read and hashed.
+    pub files_checked: u64,
+    pub bytes_checked: u64,
+    /// Files checked against a hash from the [`HashCache`] instead of
+    /// being read (see [`verify_cached`]).
+    pub files_cached: u64,
+    /// Files whose contents no longer match the recorded checksum.
+    pub corrupt: Vec<PathBuf>,
+    /// Files that are recorded but gone.
+    pub missing: Vec<PathBuf>,
+    pub elapsed: Duration,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.corrupt.is_empty() && self.missing.is_empty()
+    }
+}
+
+/// How much of a backup a sampled [`verify`] checks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Amount {
+    /// This many files.
+    Files(usize),
+    /// This percentage of the files; of the chunks, for chunked
+    /// repositories, so that large files are sampled too.
+    Percent(f64),
+}
+
+impl Amount {
+    /// How many of `total` items to pick: at least one if there are any.
+    pub fn of(self, total: usize) -> usize {
+        match self {
+            Amount::Files(n) => n.min(total),
+            Amount::Percent(percent) => {
+                ((total as f64 * percent / 100.0).ceil() as usize).clamp(total.min(1), total)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Amount::Files(n) => write!(f, "{} file(s)", n),
+            Amount::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+impl FromStr for Amount {
+    type Err = String;
+
+    /// `200` for a number of files, `5%` for a percentage.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.strip_suffix('%') {
+            Some(percent) => match percent.trim().parse::<f64>() {
+                Ok(percent) if percent > 0.0 && percent <= 100.0 => Ok(Amount::Percent(percent)),
+                _ => Err(format!("'{}' is not a percentage between 0 and 100", s)),
+            },
+            None => match s.parse::<usize>() {
+                Ok(n) if n > 0 => Ok(Amount::Files(n)),
+                _ => Err(format!(
+                    "'{}' is neither a number of files nor a percentage such as 5%",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+/// A part of a backup picked at random to verify. The same seed picks the
+/// same part of the same backup, so a check can be repeated exactly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub amount: Amount,
+    pub seed: u64,
+}
+
+impl Sample {
+    /// `amount` with a seed from the clock.
+    pub fn random(amount: Amount) -> Sample {
+        let seed = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        Sample { amount, seed }
+    }
+}
+
+/// Re-hashes the files of the backup at `backup` against its manifest (see
+/// [`manifest_path`]): all of them, or a `sample` of them.
+pub fn verify(
+    backup: &Path,
+    sample: Option<Sample>,
+    options: &TransferOptions,
+    progress: impl ProgressSink,
+) -> Result<VerifyReport> {
+    verify_with(backup, sample, None, options, progress)
+}
+
+/// [`verify`], taking the hash of files whose size and modification time
+/// haven't changed since an earlier run from `cache` instead of reading
+/// them, and adding the hashes it computes. Quick, but blind to media that
+/// decayed without the files being touched; see [`crate::hashcache`].
+pub fn verify_cached(
+    backup: &Path,
+    sample: Option<Sample>,
+    cache: &mut HashCache,
+    options: &TransferOptions,
+    progress: impl ProgressSink,
+) -> Result<VerifyReport> {
+    let report = verify_with(backup, sample, Some(&mut *cache), options, progress)?;
+    cache.save()?;
+    Ok(report)
+}
+
+fn verify_with(
+    backup: &Path,
+    sample: Option<Sample>,
+    mut cache: Option<&mut HashCache>,
+    options: &TransferOptions,
+    progress: impl ProgressSink,
+) -> Result<VerifyReport> {
+    let started = Instant::now();
+    let job = crate::jobs::start("verify", &[backup], Some(&options.cancel));
+    let mut progress = job.track(progress);
+    progress.emit(ProgressEvent::Phase(Phase::Scanning));
+    let mut entries = read_manifest(backup)?;
+    if let Some(sample) = sample {
+        let n = sample.amount.of(entries.len());
+        pick(&mut entries, n, sample.seed);
+    }
+    let mut report = VerifyReport::default();
+    let metadata: Vec<Option<Metadata>> = entries
+        .iter()
+        .map(|(_, file)| fs::metadata(backup.join(file)).ok())
+        .collect();
+    let cached: Vec<Option<String>> = entries
+        .iter()
+        .zip(&metadata)
+        .map(|((_, file), metadata)| {
+            let cache = cache.as_deref()?;
+            Some(
+                cache
+                    .get(&backup.join(file), metadata.as_ref()?)?
+                    .to_string(),
+            )
+        })
+        .collect();
+    let total = metadata
+        .iter()
+        .zip(&cached)
+        .filter(|(_, cached)| cached.is_none())
+        .filter_map(|(metadata, _)| Some(metadata.as_ref()?.len()))
+        .sum();
+    progress.emit(ProgressEvent::Totals {
+        bytes: total,
+        files: entries.len() as u64,
+    });
+    progress.emit(ProgressEvent::Phase(Phase::Copying));
+    for (((hash, file), metadata), cached) in entries.into_iter().zip(metadata).zip(cached) {
+        options.cancel.check()?;
+        let path = backup.join(&file);
+        let Some(metadata) = metadata else {
+            report.missing.push(file);
+            continue;
+        };
+        let actual = match cached {
+            Some(actual) => {
+                report.files_cached += 1;
+                actual
+            }
+            None => {
+                progress.emit(ProgressEvent::File {
+                    path: path.clone(),
+                    size: metadata.len(),
+                });
+                let actual = hash_file(&path, |n| {
+                    report.bytes_checked += n as u64;
+                    progress.emit(ProgressEvent::Bytes {
+                        done: report.bytes_checked,
+                        total,
+                    });
+                })?;
+                report.files_checked += 1;
+                if let Some(cache) = cache.as_deref_mut() {
+                    cache.insert(&path, &metadata, actual.clone());
+                }
+                actual
+            }
+        };
+        if actual != hash {
+            report.corrupt.push(file);
+        }
+    }
+    report.elapsed = started.elapsed();
+    progress.emit(ProgressEvent::Phase(Phase::Done));
+    Ok(report)
+}
+
+/// Keeps `n` of `items`, picked at random from `seed`, in their original
+/// order. The same seed keeps the same items.
+pub(crate) fn pick<T>(items: &mut Vec<T>, n: usize, seed: u64) {
+    if n >= items.len() {
+        return;
+    }
+    // splitmix64, so that nearby seeds start far apart and none is zero.
+    let mut state = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    state = (state ^ (state >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    state = (state ^ (state >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    state = (state ^ (state >> 31)) | 1;
+    let mut chosen: Vec<usize> = (0..items.len()).collect();
+    for i in 0..n {
+        // xorshift64
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let j = i + (state % (chosen.len() - i) as u64) as usize;
+        chosen.swap(i, j);
+    }
+    chosen.truncate(n);
+    chosen.sort_unstable();
+    let mut index = 0;
+    let mut next = chosen.into_iter().peekable();
+    items.retain(|_| {
+        let keep = next.peek() == Some(&index);
+        if keep {
+            next.next();
+        }
+        index += 1;
+        keep
+    });
+}
+
+/// Carries modification times over to the copies just written, so the
+/// next run sees them as unchanged, then verifies them. Returns how many
+/// were verified.
+fn settle(
+    files: &[(PathBuf, PathBuf, u64)],
+    copied: &TransferReport,
+    verify: Verify,
+    options: &TransferOptions,
+) -> Result<u64> {
+    let skipped: Vec<&PathBuf> = copied.infected.iter().map(|(path, _)| path).collect();
+    let written: Vec<&(PathBuf, PathBuf, u64)> = files
+        .iter()
+        .filter(|(s, _, _)| !skipped.contains(&s))
+        .collect();
+    for (source, target, _) in &written {
+        if let Ok(modified) = fs::metadata(source).and_then(|m| m.modified()) {
+            let _ = File::options()
+                .write(true)
+                .open(target)
+                .and_then(|f| f.set_modified(modified));
+        }
+    }
+    let mut verified = 0;
+    if verify != Verify::None {
+        for (source, target, size) in &written {
+            options.cancel.check()?;
+            let good = match verify {
+                Verify::Full => same_contents(source, target)?,
+                _ => fs::metadata(target).is_ok_and(|m| m.len() == *size),
+            };
+            if !good {
+                return Err(UsbError::VerificationFailed {
+                    path: target.clone(),
+                });
+            }
+            verified += 1;
+        }
+    }
+    Ok(verified)
+}
+
+/// What [`restore`] does with a file that already exists on the host and
+/// differs from the backup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Conflict {
+    /// Keep the host's file.
+    #[default]
+    Skip,
+    /// Replace it with the backup.
+    Overwrite,
+    /// Keep it and restore the backup next to it as `<name>.restored.<ext>`.
+    KeepBoth,
+}
+
+impl FromStr for Conflict {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(Conflict::Skip),
+            "overwrite" => Ok(Conflict::Overwrite),
+            "keep-both" => Ok(Conflict::KeepBoth),
+            other => Err(format!(
+                "unknown conflict handling '{}' (expected skip, overwrite or keep-both)",
+                other
+            )),
+        }
+    }
+}
+
+/// The directory a restore reads from: the profile's mirror, or one of its
+/// snapshots (the newest unless `snapshot` names one).
+pub fn restore_source(profile: &Profile, dest: &Path, snapshot: Option<&str>) -> Result<PathBuf> {
+    if !profile.snapshots {
+        return match snapshot {
+            Some(_) => Err(UsbError::Unsupported(
+                "this profile keeps a mirror, not snapshots".into(),
+            )),
+            None if dest.is_dir() => Ok(dest.to_path_buf()),
+            None => Err(UsbError::DeviceNotFound(format!(
+                "no backup at {}",
+                dest.display()
+            ))),
+        };
+    }
+    let names = snapshots(dest)?;
+    let name = match snapshot {
+        Some(name) => names.iter().find(|n| *n == name).ok_or_else(|| {
+            UsbError::DeviceNotFound(format!("no snapshot {} in {}", name, dest.display()))
+        })?,
+        None => names.last().ok_or_else(|| {
+            UsbError::DeviceNotFound(format!("no snapshots in {}", dest.display()))
+        })?,
+    };
+    Ok(dest.join(name))
+}
+
+/// What [`restore`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestoreReport {
+    pub files_restored: u64,
+    pub bytes_restored: u64,
+    /// Files already identical on the host.
+    pub files_unchanged: u64,
+    /// Conflicting files left alone ([`Conflict::Skip`]).
+    pub conflicts_skipped: Vec<PathBuf>,
+    /// Conflicting files restored next to the host's ([`Conflict::KeepBoth`]).
+    pub conflicts_kept: Vec<PathBuf>,
+    pub files_verified: u64,
+    pub elapsed: Duration,
+}
+
+/// Copies the backup at `source` (see [`restore_source`]) into `dest` on
+/// the host, one directory per backed-up source as on the stick, and
+/// verifies the copies.
+pub fn restore(
+    source: &Path,
+    dest: &Path,
+    conflict: Conflict,
+    verify: Verify,
+    options: &TransferOptions,
+    progress: impl ProgressSink,
+) -> Result<RestoreReport> {
+    let started = Instant::now();
+    let job = crate::jobs::start("restore", &[source, dest], Some(&options.cancel));
+    let mut progress = job.track(progress);
+    progress.emit(ProgressEvent::Phase(Phase::Scanning));
+    let mut report = RestoreReport::default();
+    let mut files = Vec::new();
+    for (path, relative, size, modified) in walk(source, &Filters::default(), &Ignore::none())? {
+        options.cancel.check()?;
+        let target = dest.join(&relative);
+        if !target.exists() {
+            files.push((path, target, size));
+        } else if is_up_to_date(&target, size, modified, &options.mtime_tolerance) {
+            report.files_unchanged += 1;
+        } else {
+            match conflict {
+                Conflict::Skip => report.conflicts_skipped.push(target),
+                Conflict::Overwrite => files.push((path, target, size)),
+                Conflict::KeepBoth => {
+                    let renamed = restored_name(&target);
+                    report.conflicts_kept.push(renamed.clone());
+                    files.push((path, renamed, size));
+                }
+            }
+        }
+    }
+
+    fs::create_dir_all(dest).io_context(|| format!("Failed to create {}", dest.display()))?;
+    let copied = copy_files(&files, dest, options, &mut progress)?;
+    report.files_verified = settle(&files, &copied, verify, options)?;
+    report.files_restored = copied.files_copied;
+    report.bytes_restored = copied.bytes_copied;
+    report.elapsed = started.elapsed();
+    progress.emit(ProgressEvent::Phase(Phase::Done));
+    Ok(report)
+}
+
+/// `notes.txt` -> `notes.restored.txt`, numbered if that is taken too.
+pub(crate) fn restored_name(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let mut candidate = path.with_file_name(format!("{}.restored{}", stem, ext));
+    let mut n = 2;
+    while candidate.exists() {
+        candidate = path.with_file_name(format!("{}.restored-{}{}", stem, n, ext));
+        n += 1;
+    }
+    candidate
+}
+
+/// Every file `profile` backs up: its path, its path in the backup (under
+/// the source's name), size and modification time.
+pub(crate) fn source_files(
+    profile: &Profile,
+    ignore: &Ignore,
+) -> Result<Vec<(PathBuf, PathBuf, u64, SystemTime)>> {
+    let filters = profile.filters();
+    let mut files = Vec::new();
+    for (source, name) in profile.resolved_sources()? {
+        for (path, relative, size, modified) in walk(&source, &filters, ignore)? {
+            let inside = if relative.as_os_str().is_empty() {
+                PathBuf::from(&name)
+            } else {
+                Path::new(&name).join(&relative)
+            };
+            files.push((path, inside, size, modified));
+        }
+    }
+    Ok(files)
+}
+
+/// The files of `source` that `filters` take and `ignore` doesn't leave
+/// out: path, path relative to `source`, size and modification time.
+fn walk(
+    source: &Path,
+    filters: &Filters,
+    ignore: &Ignore,
+) -> Result<Vec<(PathBuf, PathBuf, u64, SystemTime)>> {
+    let metadata =
+        fs::metadata(source).io_context(|| format!("Failed to read {}", source.display()))?;
+    if metadata.is_file() {
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        return Ok(vec![(
+            source.to_path_buf(),
+            PathBuf::new(),
+            metadata.len(),
+            modified,
+        )]);
+    }
+    let mut ignore = ignore.clone();
+    let mut files = Vec::new();
+    let mut walker = WalkDir::new(source).sort_by_file_name().into_iter();
+    while let Some(entry) = walker.next() {
+        let entry = entry.map_err(|e| {
+            let context = format!("Failed to read {}", e.path().unwrap_or(source).display());
+            UsbError::io(context, io::Error::from(e))
+        })?;
+        let relative = entry
+            .path()
+            .strip_prefix(source)
+            .unwrap_or(entry.path())
+            .to_path_buf();
+        let is_dir = entry.file_type().is_dir();
+        if entry.depth() > 0
+            && (filters.excludes(&relative) || ignore.is_ignored(&relative, is_dir))
+        {
+            if is_dir {
+                walker.skip_current_dir();
+            }
+            continue;
+        }
+        if is_dir {
+            ignore.load(entry.path(), &relative)?;
+        }
+        if !entry.file_type().is_file() || !filters.includes(&relative) {
+            continue;
+        }
+        let metadata = entry.metadata().map_err(|e| {
+            UsbError::io(
+                format!("Failed to read {}", entry.path().display()),
+                io::Error::from(e),
+            )
+        })?;
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        files.push((entry.into_path(), relative, metadata.len(), modified));
+    }
+    Ok(files)
+}
+
+pub(crate) fn is_up_to_date(
+    target: &Path,
+    size: u64,
+    modified: SystemTime,
+    tolerance: &MtimeTolerance,
+) -> bool {
+    let Ok(metadata) = fs::metadata(target) else {
+        return false;
+    };
+    let Ok(target_modified) = metadata.modified() else {
+        return false;
+    };
+    metadata.len() == size && tolerance.same(target_modified, modified)
+}
+
+/// Compares `source` with the copy at `target`, reading the copy from the
+/// stick rather than the page cache where the platform allows.
+fn same_contents(source: &Path, target: &Path) -> Result<bool> {
+    let mut a = File::open(source).io_context(|| format!("Failed to open {}", source.display()))?;
+    let mut b = File::open(target).io_context(|| format!("Failed to open {}", target.display()))?;
+    drop_cache(&b);
+    let mut buf_a = vec![0u8; 64 * 1024];
+    let mut buf_b = vec![0u8; 64 * 1024];
+    loop {
+        let n = read_full(&mut a, &mut buf_a)
+            .io_context(|| format!("Failed to read {}", source.display()))?;
+        let m = read_full(&mut b, &mut buf_b)
+            .io_context(|| format!("Failed to read {}", target.display()))?;
+        if n != m || buf_a[..n] != buf_b[..m] {
+            return Ok(false);
+        }
+        if n == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Makes the next reads of `file` come from the disk.
+pub(crate) fn drop_cache(file: &File) {
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    {
+        use std::os::fd::AsRawFd;
+        // Dirty pages can't be dropped, so write them back first.
+        let _ = file.sync_all();
+        unsafe {
+            libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED);
+        }
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "freebsd")))]
+    let _ = file;
+}
+
+/// Reads until `buf` is full or the file ends.
+pub(crate) fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
@@ -0,0 +1,344 @@
+//! Measuring how fast a stick really is, in a test file on one of its
+//! filesystems.
+//!
+//! Sequential transfers are what a stick's box advertises; random 4 KiB
+//! reads and writes are what running programs, unpacking archives and
+//! keeping many small files cost, and where cheap sticks fall apart. The
+//! random tests keep several requests in flight at once (the queue depth)
+//! from as many threads, each waiting for its own.
+//!
+//! The page cache is bypassed with `O_DIRECT` (`F_NOCACHE` on macOS).
+//! Filesystems that refuse it, such as some FUSE ones, are written with
+//! `O_DSYNC` and have the cache dropped before reading instead, which is
+//! close but not quite the same; [`BenchResult::cached`] says when.
+
+use crate::cancel::CancelToken;
+use crate::error::{IoContext, Result, UsbError};
+use std::alloc::{self, Layout};
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Size of a random request.
+pub const BLOCK: usize = 4096;
+/// Size of a sequential request.
+const CHUNK: usize = 1024 * 1024;
+/// The largest queue depth accepted.
+pub const MAX_QUEUE_DEPTH: usize = 256;
+
+/// Which test a [`BenchResult`] is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Test {
+    SequentialWrite,
+    SequentialRead,
+    RandomRead { queue_depth: usize },
+    RandomWrite { queue_depth: usize },
+}
+
+/// What a test measured.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchResult {
+    pub test: Test,
+    pub bytes: u64,
+    /// Requests completed.
+    pub ops: u64,
+    pub elapsed: Duration,
+    /// Whether the page cache could not be bypassed.
+    pub cached: bool,
+}
+
+impl BenchResult {
+    pub fn bytes_per_sec(&self) -> f64 {
+        self.bytes as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Requests completed per second.
+    pub fn iops(&self) -> f64 {
+        self.ops as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// What to run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    /// Size of the test file; rounded down to whole MiB.
+    pub file_size: u64,
+    pub sequential: bool,
+    pub random: bool,
+    /// A random read and a random write test is run at each.
+    pub queue_depths: Vec<usize>,
+    /// How long each random test runs.
+    pub duration: Duration,
+}
+
+impl Default for Options {
+    /// Everything, on a 256 MiB file, at queue depths 1 and 32 for 10
+    /// seconds each.
+    fn default() -> Self {
+        Options {
+            file_size: 256 * 1024 * 1024,
+            sequential: true,
+            random: true,
+            queue_depths: vec![1, 32],
+            duration: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Runs the tests of `options` in a test file in `dir`, calling `report`
+/// with each result as it is known. The file is removed afterwards.
+pub fn run(
+    dir: &Path,
+    options: &Options,
+    cancel: &CancelToken,
+    mut report: impl FnMut(&BenchResult),
+) -> Result<Vec<BenchResult>> {
+    let file_size = options.file_size / CHUNK as u64 * CHUNK as u64;
+    if file_size == 0 {
+        return Err(UsbError::Unsupported(
+            "the test file must be at least 1 MiB".into(),
+        ));
+    }
+    if let Some(&depth) = options
+        .queue_depths
+        .iter()
+        .find(|&&d| d == 0 || d > MAX_QUEUE_DEPTH)
+    {
+        return Err(UsbError::Unsupported(format!(
+            "queue depth {} is out of range (1 to {})",
+            depth, MAX_QUEUE_DEPTH
+        )));
+    }
+    let test_file = TestFile(dir.join(format!(".lsusb-bench-{}", std::process::id())));
+    let path = &test_file.0;
+    let context = || format!("Failed to benchmark {}", path.display());
+    let mut results = Vec::new();
+    let mut record = |result: BenchResult| {
+        report(&result);
+        results.push(result);
+    };
+
+    // Random tests need the file written first, so this always runs.
+    let (file, cached) = open(path, true).io_context(context)?;
+    let write = sequential(&file, file_size, true, cancel).io_context(context)?;
+    cancel.check()?;
+    if options.sequential {
+        record(result(Test::SequentialWrite, file_size, write, cached));
+    }
+    drop(file);
+    if options.sequential {
+        cancel.check()?;
+        let (file, cached) = open(path, false).io_context(context)?;
+        let read = sequential(&file, file_size, false, cancel).io_context(context)?;
+        cancel.check()?;
+        record(result(Test::SequentialRead, file_size, read, cached));
+    }
+    if options.random {
+        for &queue_depth in &options.queue_depths {
+            for write in [false, true] {
+                cancel.check()?;
+                let (file, cached) = open(path, write).io_context(context)?;
+                let (ops, elapsed) = random(
+                    &file,
+                    file_size,
+                    write,
+                    queue_depth,
+                    options.duration,
+                    cancel,
+                )
+                .io_context(context)?;
+                cancel.check()?;
+                let test = if write {
+                    Test::RandomWrite { queue_depth }
+                } else {
+                    Test::RandomRead { queue_depth }
+                };
+                record(BenchResult {
+                    test,
+                    bytes: ops * BLOCK as u64,
+                    ops,
+                    elapsed,
+                    cached,
+                });
+            }
+        }
+    }
+    Ok(results)
+}
+
+fn result(test: Test, bytes: u64, elapsed: Duration, cached: bool) -> BenchResult {
+    BenchResult {
+        test,
+        bytes,
+        ops: bytes / CHUNK as u64,
+        elapsed,
+        cached,
+    }
+}
+
+/// Removes the test file however the run ends.
+struct TestFile(PathBuf);
+
+impl Drop for TestFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Opens `path` bypassing the page cache, for writing (creating it) or
+/// reading. Returns whether the cache could *not* be bypassed.
+fn open(path: &Path, write: bool) -> io::Result<(File, bool)> {
+    let options = || {
+        let mut options = OpenOptions::new();
+        options.read(true).write(write).create(write);
+        options
+    };
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    match options().custom_flags(libc::O_DIRECT).open(path) {
+        Ok(file) => return Ok((file, false)),
+        Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {}
+        Err(e) => return Err(e),
+    }
+    #[cfg(target_os = "macos")]
+    {
+        use std::os::fd::AsRawFd;
+        let file = options().open(path)?;
+        if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_NOCACHE, 1) } == 0 {
+            return Ok((file, false));
+        }
+    }
+    let file = options()
+        .custom_flags(if write { libc::O_DSYNC } else { 0 })
+        .open(path)?;
+    crate::backup::drop_cache(&file);
+    Ok((file, true))
+}
+
+/// Writes or reads `len` bytes of `file` front to back; returns how long
+/// that took, including getting written data onto the disk.
+fn sequential(file: &File, len: u64, write: bool, cancel: &CancelToken) -> io::Result<Duration> {
+    let mut buf = Aligned::new(CHUNK);
+    fill(&mut buf, 0x9e37_79b9_7f4a_7c15);
+    let start = Instant::now();
+    let mut offset = 0;
+    while offset < len {
+        if cancel.is_cancelled() {
+            break;
+        }
+        if write {
+            file.write_all_at(&buf, offset)?;
+        } else {
+            file.read_exact_at(&mut buf, offset)?;
+        }
+        offset += CHUNK as u64;
+    }
+    if write {
+        file.sync_all()?;
+    }
+    Ok(start.elapsed())
+}
+
+/// Reads or writes random [`BLOCK`]s of `file` with `queue_depth` requests
+/// in flight for `duration`; returns how many completed and how long it
+/// took.
+fn random(
+    file: &File,
+    len: u64,
+    write: bool,
+    queue_depth: usize,
+    duration: Duration,
+    cancel: &CancelToken,
+) -> io::Result<(u64, Duration)> {
+    let blocks = len / BLOCK as u64;
+    let ops = AtomicU64::new(0);
+    let start = Instant::now();
+    let deadline = start + duration;
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..queue_depth)
+            .map(|worker| {
+                let ops = &ops;
+                scope.spawn(move || -> io::Result<()> {
+                    let mut buf = Aligned::new(BLOCK);
+                    let mut state = 0x2545_f491_4f6c_dd1d ^ ((worker as u64 + 1) << 32);
+                    fill(&mut buf, state);
+                    while Instant::now() < deadline && !cancel.is_cancelled() {
+                        let offset = xorshift(&mut state) % blocks * BLOCK as u64;
+                        if write {
+                            file.write_all_at(&buf, offset)?;
+                        } else {
+                            file.read_exact_at(&mut buf, offset)?;
+                        }
+                        ops.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        workers.into_iter().try_for_each(|worker| {
+            worker
+                .join()
+                .unwrap_or_else(|_| Err(io::Error::other("benchmark thread panicked")))
+        })
+    })?;
+    if write {
+        file.sync_all()?;
+    }
+    Ok((ops.into_inner(), start.elapsed()))
+}
+
+fn xorshift(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+/// Fills `buf` with pseudo-random bytes, so controllers that compress or
+/// deduplicate don't get an easy time.
+fn fill(buf: &mut [u8], mut state: u64) {
+    for chunk in buf.chunks_mut(8) {
+        let bytes = xorshift(&mut state).to_le_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
+
+/// A buffer aligned for `O_DIRECT`.
+struct Aligned {
+    ptr: *mut u8,
+    layout: Layout,
+}
+
+impl Aligned {
+    fn new(len: usize) -> Aligned {
+        let layout = Layout::from_size_align(len, BLOCK).expect("valid buffer layout");
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        if ptr.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+        Aligned { ptr, layout }
+    }
+}
+
+impl Deref for Aligned {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.layout.size()) }
+    }
+}
+
+impl DerefMut for Aligned {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.layout.size()) }
+    }
+}
+
+impl Drop for Aligned {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.ptr, self.layout) }
+    }
+}
@@ -0,0 +1,324 @@
+//! Making a stick bootable: installing GRUB or SYSLINUX onto it and writing
+//! a menu for the kernels and ISO images already copied there.
+//!
+//! A filesystem on the stick must be mounted; the bootloader goes into its
+//! `boot/` directory next to the menu.
+
+use crate::backend::run;
+use crate::error::{IoContext, Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Which bootloader to install.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bootloader {
+    /// GRUB for BIOS and/or UEFI, whichever platforms are installed on
+    /// this machine. Boots ISO images that ship a `loopback.cfg`.
+    Grub,
+    /// SYSLINUX (installed with `extlinux`) for BIOS. Boots kernels only.
+    Syslinux,
+}
+
+/// A kernel found on the stick, with the initramfs next to it if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Kernel {
+    /// Path on the stick, e.g. `/boot/vmlinuz-6.1.0`.
+    pub path: String,
+    pub initrd: Option<String>,
+}
+
+/// What the generated menu offers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BootEntries {
+    pub kernels: Vec<Kernel>,
+    /// ISO images, as paths on the stick.
+    pub isos: Vec<String>,
+}
+
+/// Directories searched for ISO images.
+const ISO_DIRS: [&str; 3] = ["", "iso", "isos"];
+
+impl BootEntries {
+    /// Finds kernels (`vmlinuz*` in `/` and `/boot`) and ISO images (in
+    /// `/`, `/iso` and `/isos`) on the filesystem mounted at `root`.
+    pub fn scan(root: &Path) -> BootEntries {
+        let names = |dir: &str| -> Vec<String> {
+            let mut names: Vec<String> = fs::read_dir(root.join(dir))
+                .into_iter()
+                .flatten()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_ok_and(|t| t.is_file()))
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+        let on_stick = |dir: &str, name: &str| {
+            if dir.is_empty() {
+                format!("/{}", name)
+            } else {
+                format!("/{}/{}", dir, name)
+            }
+        };
+
+        let mut entries = BootEntries::default();
+        for dir in ["", "boot"] {
+            let files = names(dir);
+            for kernel in files.iter().filter(|n| n.starts_with("vmlinuz")) {
+                // vmlinuz-6.1.0 -> initrd.img-6.1.0 (Debian), initramfs-6.1.0.img
+                // (Fedora, Arch), or a lone initrd.
+                let version = kernel.trim_start_matches("vmlinuz");
+                let candidates = [
+                    format!("initrd.img{}", version),
+                    format!("initramfs{}.img", version),
+                    format!("initrd{}", version),
+                    format!("initrd{}.img", version),
+                ];
+                let initrd = candidates.into_iter().find(|c| files.contains(c));
+                entries.kernels.push(Kernel {
+                    path: on_stick(dir, kernel),
+                    initrd: initrd.map(|i| on_stick(dir, &i)),
+                });
+            }
+        }
+        for dir in ISO_DIRS {
+            for iso in names(dir)
+                .iter()
+                .filter(|n| n.to_ascii_lowercase().ends_with(".iso"))
+            {
+                entries.isos.push(on_stick(dir, iso));
+            }
+        }
+        entries
+    }
+
+    /// A `grub.cfg` with an entry per kernel and ISO image. ISOs are booted
+    /// through the `loopback.cfg` convention most live distributions
+    /// follow, so no per-distribution kernel arguments are needed.
+    pub fn grub_config(&self) -> String {
+        let mut cfg = String::from(
+            "# Written by lsusb bootloader. Kernels get no command line; add\n\
+             # root= and friends to their linux lines as needed.\n\
+             set timeout=10\n",
+        );
+        for kernel in &self.kernels {
+            let _ = write!(
+                cfg,
+                "\nmenuentry \"{name}\" {{\n    search --no-floppy --set=root --file {path}\n    linux {path}\n",
+                name = title(&kernel.path),
+                path = kernel.path
+            );
+            if let Some(initrd) = &kernel.initrd {
+                let _ = writeln!(cfg, "    initrd {}", initrd);
+            }
+            cfg.push_str("}\n");
+        }
+        for iso in &self.isos {
+            let _ = write!(
+                cfg,
+                "\nmenuentry \"{name}\" {{\n    set iso_path=\"{path}\"\n    export iso_path\n    \
+                 search --no-floppy --set=root --file $iso_path\n    loopback loop $iso_path\n    \
+                 set root=(loop)\n    configfile /boot/grub/loopback.cfg\n}}\n",
+                name = title(iso),
+                path = iso
+            );
+        }
+        cfg
+    }
+
+    /// A `syslinux.cfg` with an entry per kernel. SYSLINUX can't boot ISO
+    /// images from a filesystem, so those are left out.
+    pub fn syslinux_config(&self) -> String {
+        let mut cfg = String::from(
+            "# Written by lsusb bootloader. Kernels get no command line; add\n\
+             # APPEND lines as needed.\n\
+             PROMPT 1\n\
+             TIMEOUT 100\n",
+        );
+        if let Some(first) = self.kernels.first() {
+            let _ = writeln!(cfg, "DEFAULT {}", label(&first.path));
+        }
+        for kernel in &self.kernels {
+            let _ = write!(
+                cfg,
+                "\nLABEL {}\n    LINUX {}\n",
+                label(&kernel.path),
+                kernel.path
+            );
+            if let Some(initrd) = &kernel.initrd {
+                let _ = writeln!(cfg, "    INITRD {}", initrd);
+            }
+        }
+        cfg
+    }
+}
+
+/// The file name, for a menu title.
+fn title(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// A SYSLINUX label: no spaces allowed.
+fn label(path: &str) -> String {
+    title(path).replace(|c: char| c.is_whitespace(), "_")
+}
+
+/// Where distributions put SYSLINUX's MBR boot code.
+const SYSLINUX_MBR: [&str; 4] = [
+    "/usr/lib/syslinux/mbr/mbr.bin",
+    "/usr/lib/syslinux/bios/mbr.bin",
+    "/usr/share/syslinux/mbr.bin",
+    "/usr/lib/syslinux/mbr.bin",
+];
+
+/// The MBR boot code area; the partition table follows it.
+const MBR_CODE_LEN: usize = 440;
+
+/// What [`Installer::install`] did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstallReport {
+    /// The menu that was written.
+    pub config: PathBuf,
+    /// Platforms the bootloader was installed for, e.g. `i386-pc`.
+    pub targets: Vec<String>,
+    pub entries: BootEntries,
+}
+
+/// Runs `grub-install` or `extlinux`.
+#[derive(Clone)]
+pub struct Installer {
+    exec: Arc<dyn CommandExecutor>,
+}
+
+impl Default for Installer {
+    fn default() -> Self {
+        Installer::with_executor(Arc::new(SystemExecutor))
+    }
+}
+
+impl Installer {
+    /// Uses `exec` to run the bootloader tools.
+    pub fn with_executor(exec: Arc<dyn CommandExecutor>) -> Self {
+        Installer { exec }
+    }
+
+    /// Installs `bootloader` onto the disk `disk` (e.g. `sdb`), whose
+    /// filesystem is mounted at `mountpoint`, and writes its menu. An
+    /// existing menu is kept as `<name>.bak`.
+    pub fn install(
+        &self,
+        bootloader: Bootloader,
+        disk: &str,
+        mountpoint: &Path,
+    ) -> Result<InstallReport> {
+        let mnt = mountpoint
+            .to_str()
+            .ok_or_else(|| UsbError::Unsupported("Mountpoint is not valid UTF-8".into()))?;
+        let device = format!("/dev/{}", disk);
+        let entries = BootEntries::scan(mountpoint);
+        let (config, text, targets) = match bootloader {
+            Bootloader::Grub => {
+                let targets = self.install_grub(disk, mountpoint)?;
+                (
+                    mountpoint.join("boot/grub/grub.cfg"),
+                    entries.grub_config(),
+                    targets,
+                )
+            }
+            Bootloader::Syslinux => {
+                let dir = mountpoint.join("boot/syslinux");
+                fs::create_dir_all(&dir)
+                    .io_context(|| format!("Failed to create {}", dir.display()))?;
+                let dir_str = format!("{}/boot/syslinux", mnt);
+                run(self.exec.as_ref(), "extlinux", &["--install", &dir_str])?;
+                write_mbr(&device)?;
+                (
+                    dir.join("syslinux.cfg"),
+                    entries.syslinux_config(),
+                    vec!["bios".to_string()],
+                )
+            }
+        };
+
+        if let Some(parent) = config.parent() {
+            fs::create_dir_all(parent)
+                .io_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        if config.exists() {
+            let backup = config.with_extension("cfg.bak");
+            fs::rename(&config, &backup)
+                .io_context(|| format!("Failed to back up {}", config.display()))?;
+        }
+        fs::write(&config, text).io_context(|| format!("Failed to write {}", config.display()))?;
+        Ok(InstallReport {
+            config,
+            targets,
+            entries,
+        })
+    }
+
+    /// Installs GRUB's boot code onto `disk` and its modules into `boot/`
+    /// of the filesystem at `mountpoint`, for BIOS and UEFI, whichever of
+    /// the two this machine has GRUB modules for. Returns the platforms it
+    /// was installed for. Writing `grub.cfg` is up to the caller.
+    pub fn install_grub(&self, disk: &str, mountpoint: &Path) -> Result<Vec<String>> {
+        let mnt = mountpoint
+            .to_str()
+            .ok_or_else(|| UsbError::Unsupported("Mountpoint is not valid UTF-8".into()))?;
+        let device = format!("/dev/{}", disk);
+        let boot = format!("--boot-directory={}/boot", mnt);
+        let efi = format!("--efi-directory={}", mnt);
+        let attempts: [(&str, Vec<&str>); 2] = [
+            ("i386-pc", vec!["--target=i386-pc", &boot, &device]),
+            (
+                "x86_64-efi",
+                vec![
+                    "--target=x86_64-efi",
+                    &efi,
+                    &boot,
+                    "--removable",
+                    "--no-nvram",
+                ],
+            ),
+        ];
+        let mut targets = Vec::new();
+        let mut first_error = None;
+        for (target, args) in attempts {
+            match run(self.exec.as_ref(), "grub-install", &args) {
+                Ok(_) => targets.push(target.to_string()),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match first_error {
+            Some(e) if targets.is_empty() => Err(e),
+            _ => Ok(targets),
+        }
+    }
+}
+
+/// Writes SYSLINUX's boot code into the MBR of `device`, leaving the
+/// partition table alone.
+fn write_mbr(device: &str) -> Result<()> {
+    let source = SYSLINUX_MBR
+        .iter()
+        .map(Path::new)
+        .find(|p| p.exists())
+        .ok_or_else(|| UsbError::ToolMissing {
+            program: "syslinux (mbr.bin)".into(),
+        })?;
+    let code = fs::read(source).io_context(|| format!("Failed to read {}", source.display()))?;
+    let code = code.get(..MBR_CODE_LEN).unwrap_or(&code);
+    let mut disk = fs::OpenOptions::new()
+        .write(true)
+        .open(device)
+        .io_context(|| format!("Failed to open {}", device))?;
+    disk.write_all(code)
+        .and_then(|_| disk.sync_all())
+        .io_context(|| format!("Failed to write the boot code to {}", device))
+}
@@ -0,0 +1,339 @@
+//! Everything on the USB bus, not just storage: keyboards, webcams, network
+//! adapters, hubs. Read from `/sys/bus/usb/devices`, so Linux only.
+
+use crate::error::{IoContext, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SYS_USB_DEVICES: &str = "/sys/bus/usb/devices";
+const SYS_CLASS_BLOCK: &str = "/sys/class/block";
+const SERIAL_BY_ID: &str = "/dev/serial/by-id";
+
+/// A device on the USB bus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BusDevice {
+    /// Port path, e.g. `1-1.4` (bus 1, port 1, then port 4 of a hub).
+    pub bus_id: String,
+    /// `vvvv:pppp`.
+    pub vid_pid: String,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial: Option<String>,
+    /// Negotiated speed in Mbit/s, e.g. `480` or `5000`.
+    pub speed: Option<String>,
+    pub interfaces: Vec<Interface>,
+}
+
+/// One interface of a [`BusDevice`]; composite devices (a webcam with a
+/// microphone) have several, each with its own class and driver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    /// `bInterfaceNumber`.
+    pub number: u8,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    /// Kernel driver bound to it, e.g. `usbhid` or `uvcvideo`.
+    pub driver: Option<String>,
+    /// The serial port it provides, e.g. `ttyUSB0` or `ttyACM0`.
+    pub tty: Option<String>,
+}
+
+impl BusDevice {
+    /// The distinct class names of its interfaces, e.g. `["Video", "Audio"]`.
+    pub fn classes(&self) -> Vec<&'static str> {
+        let mut classes: Vec<&'static str> = Vec::new();
+        for interface in &self.interfaces {
+            let name = class_name(interface.class);
+            if !classes.contains(&name) {
+                classes.push(name);
+            }
+        }
+        classes
+    }
+
+    /// The port path of the hub it is plugged into, e.g. `1-1` for `1-1.4`;
+    /// `None` when it is plugged straight into the controller's root hub.
+    pub fn parent(&self) -> Option<&str> {
+        self.bus_id.rsplit_once('.').map(|(parent, _)| parent)
+    }
+
+    /// The bus number and the port numbers after it, for sorting `1-2`
+    /// before `1-10`.
+    pub fn ports(&self) -> Vec<u32> {
+        self.bus_id
+            .split(['-', '.'])
+            .filter_map(|n| n.parse().ok())
+            .collect()
+    }
+
+    /// The driver of its mass storage interface: `uas` or `usb-storage`.
+    pub fn storage_driver(&self) -> Option<&str> {
+        self.interfaces
+            .iter()
+            .find(|i| i.class == 0x08)?
+            .driver
+            .as_deref()
+    }
+
+    /// The distinct drivers bound to its interfaces.
+    pub fn drivers(&self) -> Vec<&str> {
+        let mut drivers: Vec<&str> = Vec::new();
+        for driver in self.interfaces.iter().filter_map(|i| i.driver.as_deref()) {
+            if !drivers.contains(&driver) {
+                drivers.push(driver);
+            }
+        }
+        drivers
+    }
+}
+
+/// Every device on every USB bus, root hubs excluded.
+pub fn devices() -> Result<Vec<BusDevice>> {
+    devices_in(Path::new(SYS_USB_DEVICES))
+}
+
+/// Like [`devices`], reading a sysfs-style tree rooted at `root`.
+pub fn devices_in(root: &Path) -> Result<Vec<BusDevice>> {
+    let mut devices = Vec::new();
+    for entry in fs::read_dir(root).io_context(|| format!("Failed to read {}", root.display()))? {
+        let entry = entry.io_context(|| format!("Failed to read {}", root.display()))?;
+        let bus_id = entry.file_name().to_string_lossy().into_owned();
+        // `usb1` is a root hub, `1-1:1.0` an interface.
+        if bus_id.starts_with("usb") || bus_id.contains(':') {
+            continue;
+        }
+        devices.extend(device_at(&entry.path(), bus_id)?);
+    }
+    devices.sort_by(|a, b| a.bus_id.cmp(&b.bus_id));
+    Ok(devices)
+}
+
+/// A USB host controller and the bus its root hub starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Controller {
+    /// Bus number; port paths on this bus start with it, e.g. `2-`.
+    pub bus: u32,
+    /// e.g. `xHCI Host Controller`.
+    pub product: Option<String>,
+    /// The device it is on, e.g. the PCI address `0000:00:14.0`.
+    pub parent: Option<String>,
+    /// Speed of the root hub in Mbit/s.
+    pub speed: Option<String>,
+}
+
+/// Every USB controller, by bus number.
+pub fn controllers() -> Result<Vec<Controller>> {
+    controllers_in(Path::new(SYS_USB_DEVICES))
+}
+
+/// Like [`controllers`], reading a sysfs-style tree rooted at `root`.
+pub fn controllers_in(root: &Path) -> Result<Vec<Controller>> {
+    let mut controllers = Vec::new();
+    for entry in fs::read_dir(root).io_context(|| format!("Failed to read {}", root.display()))? {
+        let entry = entry.io_context(|| format!("Failed to read {}", root.display()))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Some(bus) = name.strip_prefix("usb").and_then(|n| n.parse().ok()) else {
+            continue;
+        };
+        let dir = entry.path();
+        // usb2 -> ../../../devices/pci0000:00/0000:00:14.0/usb2
+        let parent = fs::canonicalize(&dir)
+            .ok()
+            .and_then(|real| Some(real.parent()?.file_name()?.to_string_lossy().into_owned()));
+        controllers.push(Controller {
+            bus,
+            product: read_attr(&dir, "product"),
+            parent,
+            speed: read_attr(&dir, "speed"),
+        });
+    }
+    controllers.sort_by_key(|c| c.bus);
+    Ok(controllers)
+}
+
+/// The USB device the block device `device_name` (e.g. `sdb`) is on.
+pub fn storage_device(device_name: &str) -> Option<BusDevice> {
+    storage_device_in(Path::new(SYS_CLASS_BLOCK), device_name)
+}
+
+/// Like [`storage_device`], with block devices listed in `class_block`.
+pub fn storage_device_in(class_block: &Path, device_name: &str) -> Option<BusDevice> {
+    // /sys/class/block/sdb -> ../../devices/pci0000:00/.../usb2/2-1/2-1:1.0/host6/.../block/sdb
+    let real = fs::canonicalize(class_block.join(device_name)).ok()?;
+    let dir = real.ancestors().find(|dir| dir.join("idVendor").exists())?;
+    let bus_id = dir.file_name()?.to_string_lossy().into_owned();
+    device_at(dir, bus_id).ok().flatten()
+}
+
+fn device_at(dir: &Path, bus_id: String) -> Result<Option<BusDevice>> {
+    let (Some(vendor), Some(product_id)) =
+        (read_attr(dir, "idVendor"), read_attr(dir, "idProduct"))
+    else {
+        return Ok(None);
+    };
+    let mut interfaces = Vec::new();
+    for child in fs::read_dir(dir).io_context(|| format!("Failed to read {}", dir.display()))? {
+        let child = child.io_context(|| format!("Failed to read {}", dir.display()))?;
+        if child
+            .file_name()
+            .to_string_lossy()
+            .starts_with(&format!("{}:", bus_id))
+        {
+            interfaces.extend(interface(&child.path()));
+        }
+    }
+    interfaces.sort_by_key(|i| i.number);
+    Ok(Some(BusDevice {
+        vid_pid: format!("{}:{}", vendor, product_id),
+        manufacturer: read_attr(dir, "manufacturer"),
+        product: read_attr(dir, "product"),
+        serial: read_attr(dir, "serial"),
+        speed: read_attr(dir, "speed"),
+        interfaces,
+        bus_id,
+    }))
+}
+
+/// What is known to go wrong with the USB-to-SATA/NVMe bridge chip
+/// `vid_pid`, for telling users why their drive is slow or drops out.
+pub fn bridge_issue(vid_pid: &str) -> Option<&'static str> {
+    Some(match vid_pid.to_ascii_lowercase().as_str() {
+        "152d:0578" => "JMicron JMS578: UAS resets and disconnects under sustained writes",
+        "152d:0567" => "JMicron JMS567: UAS command errors with some drives",
+        "174c:55aa" => "ASMedia ASM1051/1053: UAS hangs with some drives and hosts",
+        "2109:0711" => "VIA VL711: UAS resets under load",
+        "0bc2:3312" | "0bc2:a013" | "0bc2:331a" => "Seagate bridge: UAS errors on many hosts",
+        "054c:087d" => "Sony bridge: UAS commands it doesn't support",
+        _ => return None,
+    })
+}
+
+fn interface(dir: &Path) -> Option<Interface> {
+    let hex = |name: &str| u8::from_str_radix(&read_attr(dir, name)?, 16).ok();
+    Some(Interface {
+        number: hex("bInterfaceNumber")?,
+        class: hex("bInterfaceClass")?,
+        subclass: hex("bInterfaceSubClass").unwrap_or(0),
+        protocol: hex("bInterfaceProtocol").unwrap_or(0),
+        driver: fs::read_link(dir.join("driver"))
+            .ok()
+            .and_then(|target| Some(target.file_name()?.to_string_lossy().into_owned())),
+        tty: tty_of(dir),
+    })
+}
+
+/// usb-serial drivers put the port straight in the interface directory
+/// (`1-2:1.0/ttyUSB0`), cdc_acm under `tty/` (`1-2:1.0/tty/ttyACM0`).
+fn tty_of(dir: &Path) -> Option<String> {
+    let is_tty = |name: &str| name.starts_with("tty") && name.len() > 3;
+    let entries = fs::read_dir(dir)
+        .ok()?
+        .chain(fs::read_dir(dir.join("tty")).into_iter().flatten());
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .find(|name| is_tty(name))
+}
+
+/// A serial port provided by a USB adapter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerialPort {
+    /// Device node, e.g. `/dev/ttyUSB0`.
+    pub node: PathBuf,
+    /// udev's stable name for it under `/dev/serial/by-id`, which survives
+    /// replugging into another port.
+    pub by_id: Option<PathBuf>,
+    /// The kernel driver, e.g. `ftdi_sio`.
+    pub driver: Option<String>,
+    /// The adapter it belongs to.
+    pub device: BusDevice,
+}
+
+impl SerialPort {
+    /// The adapter family its driver implies, e.g. `FTDI` or `CDC-ACM`.
+    pub fn chip(&self) -> Option<&'static str> {
+        Some(match self.driver.as_deref()? {
+            "cdc_acm" => "CDC-ACM",
+            "ftdi_sio" => "FTDI",
+            "cp210x" => "Silicon Labs CP210x",
+            "ch341" => "WCH CH34x",
+            "pl2303" => "Prolific PL2303",
+            "option" | "qcserial" => "modem",
+            _ => return None,
+        })
+    }
+}
+
+/// Serial ports of attached USB adapters (CDC-ACM, FTDI, CP210x, CH34x, ...).
+pub fn serial_ports() -> Result<Vec<SerialPort>> {
+    serial_ports_in(Path::new(SYS_USB_DEVICES), Path::new(SERIAL_BY_ID))
+}
+
+/// Like [`serial_ports`], reading a sysfs-style tree at `root` and udev
+/// symlinks in `by_id`.
+pub fn serial_ports_in(root: &Path, by_id: &Path) -> Result<Vec<SerialPort>> {
+    // by-id links are relative: usb-FTDI_FT232R_USB_UART_A50285BI-if00-port0 -> ../../ttyUSB0
+    let links: Vec<(String, PathBuf)> = fs::read_dir(by_id)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let target = fs::read_link(e.path()).ok()?;
+            Some((target.file_name()?.to_string_lossy().into_owned(), e.path()))
+        })
+        .collect();
+    let mut ports = Vec::new();
+    for device in devices_in(root)? {
+        for interface in &device.interfaces {
+            let Some(tty) = &interface.tty else {
+                continue;
+            };
+            ports.push(SerialPort {
+                node: Path::new("/dev").join(tty),
+                by_id: links
+                    .iter()
+                    .find(|(name, _)| name == tty)
+                    .map(|(_, link)| link.clone()),
+                driver: interface.driver.clone(),
+                device: device.clone(),
+            });
+        }
+    }
+    ports.sort_by(|a, b| a.node.cmp(&b.node));
+    Ok(ports)
+}
+
+fn read_attr(dir: &Path, name: &str) -> Option<String> {
+    let value = fs::read_to_string(dir.join(name)).ok()?;
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// The name usb.org gives a base class code.
+pub fn class_name(class: u8) -> &'static str {
+    match class {
+        0x01 => "Audio",
+        0x02 => "Communications",
+        0x03 => "HID",
+        0x05 => "Physical",
+        0x06 => "Image",
+        0x07 => "Printer",
+        0x08 => "Mass Storage",
+        0x09 => "Hub",
+        0x0a => "CDC Data",
+        0x0b => "Smart Card",
+        0x0d => "Content Security",
+        0x0e => "Video",
+        0x0f => "Personal Healthcare",
+        0x10 => "Audio/Video",
+        0x11 => "Billboard",
+        0x12 => "USB-C Bridge",
+        0xdc => "Diagnostic",
+        0xe0 => "Wireless",
+        0xef => "Miscellaneous",
+        0xfe => "Application Specific",
+        0xff => "Vendor Specific",
+        _ => "Unknown",
+    }
+}
@@ -0,0 +1,201 @@
+//! Cameras that only speak PTP.
+//!
+//! Many cameras don't offer USB mass storage at all, only the Picture
+//! Transfer Protocol. [gphoto2](http://gphoto.org) talks PTP; this module
+//! detects cameras with `gphoto2 --auto-detect` and downloads everything on
+//! them, reporting the same [`ProgressEvent`]s as a file copy.
+
+use crate::backend::{check_output, run};
+use crate::cancel::CancelToken;
+use crate::error::{IoContext, Result, UsbError};
+use crate::exec::{CommandExecutor, LineWriter, SystemExecutor};
+use crate::progress::{Phase, ProgressEvent, ProgressSink};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// A camera found by `gphoto2 --auto-detect`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Camera {
+    /// e.g. `Canon EOS 80D`.
+    pub model: String,
+    /// gphoto2 port, e.g. `usb:001,012`.
+    pub port: String,
+}
+
+/// What a finished [`Gphoto2::import`] did.
+#[derive(Debug, Clone, Default)]
+pub struct ImportReport {
+    /// Files downloaded.
+    pub files: u64,
+    /// Their approximate size; gphoto2 lists sizes in KB.
+    pub bytes: u64,
+    /// Files that were already in the destination.
+    pub skipped: u64,
+}
+
+/// Runs `gphoto2`.
+#[derive(Clone)]
+pub struct Gphoto2 {
+    exec: Arc<dyn CommandExecutor>,
+}
+
+impl Default for Gphoto2 {
+    fn default() -> Self {
+        Gphoto2::with_executor(Arc::new(SystemExecutor))
+    }
+}
+
+impl Gphoto2 {
+    /// Uses `exec` to run `gphoto2`.
+    pub fn with_executor(exec: Arc<dyn CommandExecutor>) -> Self {
+        Gphoto2 { exec }
+    }
+
+    /// Cameras attached over USB.
+    pub fn cameras(&self) -> Result<Vec<Camera>> {
+        let output = run(self.exec.as_ref(), "gphoto2", &["--auto-detect"])?;
+        Ok(parse_auto_detect(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Downloads every file on the camera at `port` into `dest`, keeping the
+    /// camera's folder layout. Files already there are skipped, so an
+    /// interrupted import can simply be run again.
+    pub fn import(
+        &self,
+        port: &str,
+        dest: &Path,
+        cancel: &CancelToken,
+        mut progress: impl ProgressSink,
+    ) -> Result<ImportReport> {
+        progress.emit(ProgressEvent::Phase(Phase::Scanning));
+        let listing = run(
+            self.exec.as_ref(),
+            "gphoto2",
+            &["--port", port, "--list-files"],
+        )?;
+        let files = parse_file_sizes(&String::from_utf8_lossy(&listing.stdout));
+        let total = files.iter().map(|(_, size)| size).sum();
+        progress.emit(ProgressEvent::Totals {
+            bytes: total,
+            files: files.len() as u64,
+        });
+        // gphoto2 only names the file when saving it.
+        let sizes: HashMap<&str, u64> = files
+            .iter()
+            .map(|(name, size)| (name.as_str(), *size))
+            .collect();
+
+        fs::create_dir_all(dest).io_context(|| format!("Failed to create {}", dest.display()))?;
+        let dest_str = dest.to_str().ok_or_else(|| {
+            UsbError::Unsupported(format!("{} is not valid UTF-8", dest.display()))
+        })?;
+        let pattern = format!("{}/%F/%f.%C", dest_str.trim_end_matches('/'));
+        progress.emit(ProgressEvent::Phase(Phase::Copying));
+
+        let mut done = 0;
+        let mut report = ImportReport::default();
+        // gphoto2 reports each file as it goes; turn that into progress.
+        let mut lines = LineWriter::new(|line: &str| {
+            cancel
+                .check()
+                .map_err(|_| io::Error::from(io::ErrorKind::Interrupted))?;
+            let name = |path: &str| {
+                Path::new(path)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+            };
+            if let Some(path) = line.strip_prefix("Saving file as ") {
+                let size = name(path)
+                    .and_then(|n| sizes.get(n.as_str()).copied())
+                    .unwrap_or(0);
+                progress.emit(ProgressEvent::File {
+                    path: PathBuf::from(path),
+                    size,
+                });
+                report.files += 1;
+                report.bytes += size;
+                done += size;
+            } else if let Some(path) = line.strip_prefix("Skip existing file ") {
+                progress.emit(ProgressEvent::Skipped {
+                    path: PathBuf::from(path),
+                    reason: "already imported".into(),
+                });
+                report.skipped += 1;
+                done += name(path)
+                    .and_then(|n| sizes.get(n.as_str()).copied())
+                    .unwrap_or(0);
+            } else {
+                return Ok(());
+            }
+            progress.emit(ProgressEvent::Bytes { done, total });
+            Ok(())
+        });
+        let result = self.exec.run_to_writer(
+            "gphoto2",
+            // --filename has to come before the action it applies to.
+            &[
+                "--port",
+                port,
+                "--filename",
+                &pattern,
+                "--skip-existing",
+                "--get-all-files",
+            ],
+            &mut lines,
+        );
+        drop(lines);
+        // Stopping mid-download surfaces as a failed write to our pipe.
+        cancel.check()?;
+        check_output("gphoto2", result)?;
+        progress.emit(ProgressEvent::Phase(Phase::Done));
+        Ok(report)
+    }
+}
+
+/// Parses the table `gphoto2 --auto-detect` prints:
+///
+/// ```text
+/// Model                          Port
+/// ----------------------------------------------------------
+/// Canon EOS 80D                  usb:001,012
+/// ```
+pub fn parse_auto_detect(output: &str) -> Vec<Camera> {
+    output
+        .lines()
+        .skip_while(|l| !l.starts_with("---"))
+        .skip(1)
+        .filter_map(|line| {
+            let (model, port) = line.trim_end().rsplit_once(char::is_whitespace)?;
+            Some(Camera {
+                model: model.trim().to_string(),
+                port: port.to_string(),
+            })
+        })
+        .filter(|c| !c.model.is_empty())
+        .collect()
+}
+
+/// File names and sizes from `gphoto2 --list-files`, which prints lines
+/// like `#1     IMG_0001.JPG   rd  6117 KB image/jpeg 1700000000`.
+pub fn parse_file_sizes(output: &str) -> Vec<(String, u64)> {
+    output
+        .lines()
+        .filter(|l| l.starts_with('#'))
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let unit = fields
+                .iter()
+                .position(|f| matches!(*f, "KB" | "MB" | "GB"))?;
+            let amount: u64 = fields.get(unit.checked_sub(1)?)?.parse().ok()?;
+            let scale = match fields[unit] {
+                "KB" => 1 << 10,
+                "MB" => 1 << 20,
+                _ => 1 << 30,
+            };
+            Some((fields.get(1)?.to_string(), amount * scale))
+        })
+        .collect()
+}
@@ -0,0 +1,42 @@
+use crate::error::{Result, UsbError};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Cooperative cancellation flag shared between an operation and whoever
+/// may want to stop it (a signal handler, a UI, another task).
+///
+/// Clones share the same flag. Engines call [`check`](Self::check) between
+/// chunks of work and stop with [`UsbError::Cancelled`] once it is set.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests cancellation. Only touches an atomic, so it is safe to call
+    /// from a signal handler.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Clears a previous cancellation so the token can guard another
+    /// operation.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Returns [`UsbError::Cancelled`] if cancellation was requested.
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(UsbError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Telling which file names a case-insensitive filesystem (FAT, exFAT,
+//! NTFS as Windows uses it, APFS and HFS+ by default) would store as one,
+//! so a copy can deal with the clash instead of one file silently
+//! replacing the other.
+//!
+//! Names are compared by [`fold`]: lower-cased, and with the accented
+//! Latin letters of Latin-1 and Latin Extended-A decomposed into letter and
+//! combining mark, which is how macOS writes them; `é` typed on Linux and
+//! `é` copied off a Mac then compare equal, as they do on APFS. Other
+//! scripts are only case-folded.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+/// For each combining mark, pairs of a precomposed letter and its base.
+const COMPOSED: &[(char, &str)] = &[
+    ('\u{300}', "ÀAÈEÌIÒOÙUàaèeìiòoùu"),
+    (
+        '\u{301}',
+        "ÁAÉEÍIÓOÚUÝYáaéeíióoúuýyĆCćcĹLĺlŃNńnŔRŕrŚSśsŹZźz",
+    ),
+    (
+        '\u{302}',
+        "ÂAÊEÎIÔOÛUâaêeîiôoûuĈCĉcĜGĝgĤHĥhĴJĵjŜSŝsŴWŵwŶYŷy",
+    ),
+    ('\u{303}', "ÃAÑNÕOãañnõoĨIĩiŨUũu"),
+    ('\u{304}', "ĀAāaĒEēeĪIīiŌOōoŪUūu"),
+    ('\u{306}', "ĂAăaĔEĕeĞGğgĬIĭiŎOŏoŬUŭu"),
+    ('\u{307}', "ĊCċcĖEėeĠGġgİIŻZżz"),
+    ('\u{308}', "ÄAËEÏIÖOÜUäaëeïiöoüuÿyŸY"),
+    ('\u{30a}', "ÅAåaŮUůu"),
+    ('\u{30b}', "ŐOőoŰUűu"),
+    ('\u{30c}', "ČCčcĎDďdĚEěeĽLľlŇNňnŘRřrŠSšsŤTťtŽZžz"),
+    ('\u{327}', "ÇCçcĢGģgĶKķkĻLļlŅNņnŖRŗrŞSşsŢTţt"),
+    ('\u{328}', "ĄAąaĘEęeĮIįiŲUųu"),
+];
+
+/// The form of `name` that two names a case-insensitive filesystem treats
+/// as the same share.
+pub fn fold(name: &str) -> String {
+    let mut folded = String::with_capacity(name.len());
+    for c in name.chars() {
+        match decompose(c) {
+            Some((base, mark)) => {
+                folded.extend(base.to_lowercase());
+                folded.push(mark);
+            }
+            None => folded.extend(c.to_lowercase()),
+        }
+    }
+    folded
+}
+
+fn decompose(c: char) -> Option<(char, char)> {
+    if c.is_ascii() {
+        return None;
+    }
+    COMPOSED.iter().find_map(|(mark, pairs)| {
+        let pairs: Vec<char> = pairs.chars().collect();
+        pairs
+            .chunks_exact(2)
+            .find(|pair| pair[0] == c)
+            .map(|pair| (pair[1], *mark))
+    })
+}
+
+/// Whether the filesystem holding the directory `dir` ignores case, found
+/// by creating a file there and looking for it under another case.
+pub fn is_case_insensitive(dir: &Path) -> io::Result<bool> {
+    let probe = dir.join(format!(".lsusb-case-probe-{}", std::process::id()));
+    File::create(&probe)?;
+    let upper = dir.join(format!(".LSUSB-CASE-PROBE-{}", std::process::id()));
+    let insensitive = upper.exists();
+    fs::remove_file(&probe)?;
+    Ok(insensitive)
+}
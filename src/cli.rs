@@ -1,38 +1,682 @@
-use clap::{Parser, Subcommand};
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+use lsusb::crypt::Recipient;
+use lsusb::transfer::Preserve;
+use lsusb::{BackendKind, SizeUnits};
 use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(name = "lsusb")]
 #[command(about = "A tool to manage USB devices", long_about = None)]
+#[command(group(ArgGroup::new("units").args(["bytes", "si", "iec"])))]
 pub struct Cli {
+    /// System backend: lsblk, sysfs, udisks2, diskutil or bsd (defaults to the config file, then the platform default)
+    #[arg(long, global = true)]
+    pub backend: Option<BackendKind>,
+
+    /// Refuse every write and mount read-only, for inspecting untrusted drives
+    #[arg(long, global = true)]
+    pub read_only: bool,
+
+    /// When another lsusb process is writing to the device, wait for it instead of failing
+    #[arg(long, global = true)]
+    pub wait: bool,
+
+    /// Also list SD/MMC cards in built-in slots (mmcblk devices), not just USB disks
+    #[arg(long, global = true)]
+    pub include_mmc: bool,
+
+    /// Also list other removable disks (SATA hot-swap bays, SD/MMC card slots), not just USB ones
+    #[arg(long, global = true)]
+    pub all_removable: bool,
+
+    /// List the devices the config file hides, too
+    #[arg(long, global = true)]
+    pub show_hidden: bool,
+
+    /// No colours, progress bars or screen redraws, and numbered prompts in the menu, for screen readers and serial consoles
+    #[arg(long, global = true)]
+    pub plain: bool,
+
+    /// How copies, backups and flashes show progress: a bar, or one JSON object per line for programs wrapping lsusb
+    #[arg(long, global = true, value_enum, default_value_t = ProgressFormat::Bar)]
+    pub progress: ProgressFormat,
+
+    /// Write `--progress json` lines to this open file descriptor instead of stdout
+    #[arg(long, global = true, value_name = "FD")]
+    pub progress_fd: Option<i32>,
+
+    /// Log destructive operations to this file instead of the one the config file names
+    #[arg(long, global = true, value_name = "FILE")]
+    pub audit_log: Option<PathBuf>,
+
+    /// Print sizes as exact byte counts
+    #[arg(long, global = true)]
+    pub bytes: bool,
+
+    /// Print sizes in powers of 1000 (kB, MB, GB)
+    #[arg(long, global = true)]
+    pub si: bool,
+
+    /// Print sizes in powers of 1024 (KiB, MiB, GiB)
+    #[arg(long, global = true)]
+    pub iec: bool,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
 
+impl Cli {
+    /// The size convention picked by `--bytes`/`--si`/`--iec`.
+    pub fn size_units(&self) -> SizeUnits {
+        if self.bytes {
+            SizeUnits::Bytes
+        } else if self.si {
+            SizeUnits::Si
+        } else if self.iec {
+            SizeUnits::Iec
+        } else {
+            SizeUnits::Compact
+        }
+    }
+}
+
+/// `--progress`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ProgressFormat {
+    #[default]
+    Bar,
+    /// See [`lsusb::progress::JsonLines`].
+    Json,
+}
+
+/// Parses `--offset 1M`-style byte counts.
+fn byte_count(text: &str) -> Result<u64, String> {
+    lsusb::parse_size(text)
+        .ok_or_else(|| format!("'{}' is not a byte count (e.g. 512, 0x1be, 4K, 1M)", text))
+}
+
 #[derive(Subcommand)]
 pub enum Commands {
+    /// Check which external tools are available and what is disabled without them
+    Doctor,
     /// List all USB devices
-    List,
+    List {
+        /// Also list non-storage devices (keyboards, webcams, network adapters) with their class and driver
+        #[arg(long)]
+        all_classes: bool,
+        /// Keep the list on screen, refreshed every second, with each drive's current read and write speed
+        #[arg(long, conflicts_with = "all_classes")]
+        watch: bool,
+        /// Print the devices and their partitions in the stable format for scripts (version 1 by default)
+        #[arg(long, value_name = "VERSION", num_args = 0..=1, default_missing_value = "1", require_equals = true, conflicts_with_all = ["all_classes", "watch"])]
+        porcelain: Option<u32>,
+        /// With --porcelain, end fields and records with NUL bytes instead of escaping values
+        #[arg(short = 'z', requires = "porcelain")]
+        null: bool,
+    },
+    /// Show USB controllers and hubs as a tree, with each device's port path, to find which physical port a drive is in
+    Topology,
+    /// Have the kernel re-read a device's partition table and wait for the partitions to show up
+    Rescan {
+        /// The device name (e.g., sdb)
+        device: String,
+    },
+    /// Show how much lsusb has written to a drive over its life, and when it was first and last seen
+    Stats {
+        /// The device name (e.g., sdb)
+        device: String,
+    },
+    /// List USB serial adapters (FTDI, CP210x, CDC-ACM, ...) and the /dev/tty* node of each
+    Serial,
+    /// Print raw bytes from a device or partition as hex and ASCII
+    Hexdump {
+        /// The device or partition name (e.g., sdb or sdb1)
+        device: String,
+        /// Where to start, in bytes (e.g. 0, 0x1be, 1M)
+        #[arg(long, default_value = "0", value_parser = byte_count)]
+        offset: u64,
+        /// How much to read; one sector by default
+        #[arg(long, default_value = "512", value_parser = byte_count)]
+        length: u64,
+    },
+    /// Install GRUB or SYSLINUX onto a stick with a menu for the kernels and ISOs on it
+    #[command(group(ArgGroup::new("loader").args(["grub", "syslinux"]).required(true)))]
+    Bootloader {
+        /// The device name (e.g., sdb); one of its partitions must be mounted
+        device: String,
+        /// GRUB for BIOS and UEFI; boots ISO images that ship a loopback.cfg
+        #[arg(long)]
+        grub: bool,
+        /// SYSLINUX for BIOS; boots kernels only
+        #[arg(long)]
+        syslinux: bool,
+    },
+    /// Set up a stick that boots any ISO image copied onto it
+    Multiboot {
+        #[command(subcommand)]
+        action: MultibootAction,
+    },
+    /// Erase a stick and format it as one partition, aligned for flash
+    Format {
+        /// The device name (e.g., sdb)
+        device: String,
+        /// Filesystem: vfat, exfat, ntfs or ext4 (vfat up to 32 GiB, exfat above)
+        #[arg(long = "fs", value_name = "FS")]
+        filesystem: Option<lsusb::format::Filesystem>,
+        /// Volume label
+        #[arg(long)]
+        label: Option<String>,
+        /// Cluster (allocation unit) size, e.g. 32K; chosen for the stick's size otherwise
+        #[arg(long, value_name = "SIZE", value_parser = byte_count)]
+        cluster_size: Option<u64>,
+        /// Start and end the partition on multiples of this, e.g. 4M for cards with large erase blocks
+        #[arg(long, value_name = "SIZE", default_value = "1M", value_parser = byte_count)]
+        align: u64,
+        /// Don't ask for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Tell a USB SSD which blocks are free (fstrim), or discard an unmounted device or partition whole (blkdiscard)
+    Trim {
+        /// A mountpoint, or a device or partition name (e.g., sdb1)
+        target: String,
+        /// Have the kernel send UNMAP to a drive that supports it but isn't set up for it (most UAS bridges)
+        #[arg(long)]
+        enable_unmap: bool,
+        /// Don't ask before discarding an unmounted device
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Measure sequential and 4K random read and write speed in a test file on a partition
+    Bench {
+        /// A mounted partition (e.g., sdb1), or a directory on it
+        target: String,
+        /// Size of the test file
+        #[arg(long, value_name = "SIZE", default_value = "256M", value_parser = byte_count)]
+        size: u64,
+        /// Requests kept in flight by the random tests, one test each (e.g. 1,4,32)
+        #[arg(
+            long,
+            value_name = "N,...",
+            value_delimiter = ',',
+            default_value = "1,32"
+        )]
+        queue_depth: Vec<usize>,
+        /// How long each random test runs, in seconds
+        #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+        seconds: u64,
+        /// Only run the sequential tests
+        #[arg(long, conflicts_with = "random")]
+        sequential: bool,
+        /// Only run the random tests
+        #[arg(long)]
+        random: bool,
+    },
+    /// Keep an encrypted partition for keys and credentials at the end of a stick
+    Stash {
+        #[command(subcommand)]
+        action: StashAction,
+    },
+    /// Find, unlock and lock VeraCrypt partitions and containers, through the veracrypt command
+    Veracrypt {
+        #[command(subcommand)]
+        action: VeracryptAction,
+    },
     /// Show partitions for a device
     Parts {
         /// The device name (e.g., sdb)
         device: String,
+        /// Print the partitions in the stable format for scripts (version 1 by default)
+        #[arg(long, value_name = "VERSION", num_args = 0..=1, default_missing_value = "1", require_equals = true)]
+        porcelain: Option<u32>,
+        /// With --porcelain, end fields and records with NUL bytes instead of escaping values
+        #[arg(short = 'z', requires = "porcelain")]
+        null: bool,
     },
     /// Sync a device (flush buffers)
     Sync {
         /// The device name (e.g., sdb)
         device: String,
     },
+    /// Mount a device partition
+    Mount {
+        /// The partition name (e.g., sdb1)
+        partition: String,
+        /// Directory to mount it on (chosen automatically with the udisks2 backend)
+        mountpoint: Option<PathBuf>,
+    },
+    /// Show SMART health: overall verdict, temperature, power-on time and bad sectors
+    Health {
+        /// The device name (e.g., sdb)
+        device: String,
+    },
+    /// Rename the filesystem on a partition
+    Label {
+        /// The partition name (e.g., sdb1)
+        partition: String,
+        /// The new label
+        label: String,
+    },
+    /// Open a partition in the file manager, mounting it first if needed
+    Open {
+        /// The partition name (e.g., sdb1) or a mountpoint
+        target: String,
+    },
     /// Unmount a device partition
     Unmount {
         /// The mountpoint to unmount
         device: String,
     },
+    /// Power off a device so it can be unplugged
+    PowerOff {
+        /// The device name (e.g., sdb)
+        device: String,
+    },
+    /// Forward USB devices from Windows into WSL via usbipd-win
+    Wsl {
+        #[command(subcommand)]
+        action: WslAction,
+    },
+    /// Reach OTG storage on Android through Termux:API
+    Termux {
+        #[command(subcommand)]
+        action: TermuxAction,
+    },
+    /// List and mount iPhones and iPads through libimobiledevice and ifuse
+    Ios {
+        #[command(subcommand)]
+        action: IosAction,
+    },
+    /// Download photos from cameras that only speak PTP, through gphoto2
+    Camera {
+        #[command(subcommand)]
+        action: CameraAction,
+    },
+    /// Flash firmware to dongles and boards over USB DFU, through dfu-util
+    Dfu {
+        #[command(subcommand)]
+        action: DfuAction,
+    },
+    /// Browse and extract the files in an ISO 9660 or UDF image, without mounting it
+    Iso {
+        #[command(subcommand)]
+        action: IsoAction,
+    },
     /// Copy file or directory to a USB partition
     Cp {
         /// Source file or directory
         source: PathBuf,
-        /// Destination path on the USB
+        /// Destination path on the USB, or an unmounted partition (sdb1, or sdb1:DIR) to mount just for the copy
+        #[arg(required_unless_present = "to")]
+        dest: Option<PathBuf>,
+        /// Copy to each of these paths at once instead, reading the source only once (repeatable)
+        #[arg(long, value_name = "DEST", conflicts_with_all = ["dest", "span", "encrypt", "decrypt", "sign"])]
+        to: Vec<PathBuf>,
+        /// Scan each file for malware first (clamd, or `scan_command` from the config) and skip infected ones
+        #[arg(long)]
+        scan: bool,
+        /// Write a SHA-256 manifest of the copy next to it and sign it with this minisign secret key
+        #[arg(long, value_name = "KEY")]
+        sign: Option<PathBuf>,
+        /// Encrypt each file as it is written, for an age recipient or a gpg key (age:<recipient> or gpg:<key>)
+        #[arg(long, value_name = "TOOL:RECIPIENT", conflicts_with = "decrypt")]
+        encrypt: Option<Recipient>,
+        /// Decrypt .age/.gpg files while copying them off the stick, dropping the suffix
+        #[arg(long)]
+        decrypt: bool,
+        /// age identity file used by --decrypt
+        #[arg(long, requires = "decrypt")]
+        identity: Option<PathBuf>,
+        /// Copy everything, ignoring .lsusbignore files and the config's ignore list
+        #[arg(long)]
+        no_ignore: bool,
+        /// Print the summary at the end as JSON
+        #[arg(long)]
+        json: bool,
+        /// For files that would get the same name (differing only in case or accents on a destination that can't tell them apart, or brought together by --flatten): skip, overwrite or keep-both
+        #[arg(long, default_value = "skip")]
+        on_collision: lsusb::backup::Conflict,
+        /// Also copy these attributes: mode, timestamps, xattr (user attributes and ACLs), or all
+        #[arg(long, value_name = "LIST")]
+        preserve: Option<Preserve>,
+        /// When the destination's drive fills up, continue on the next USB drive, leaving lsusb-span.json on each
+        #[arg(long, conflicts_with = "sign")]
+        span: bool,
+        /// Put every file of a directory straight into the destination, leaving out the directories
+        #[arg(long, conflicts_with_all = ["strip_components", "to", "span"])]
+        flatten: bool,
+        /// Drop this many leading directories, counting the copied directory itself, from every path
+        #[arg(long, value_name = "N", default_value_t = 0, conflicts_with_all = ["to", "span"])]
+        strip_components: usize,
+        /// Put everything into this directory under the destination
+        #[arg(long, value_name = "DIR", conflicts_with_all = ["to", "span"])]
+        prefix: Option<PathBuf>,
+        /// Once the copy succeeded, sync, unmount and power off the destination drive(s)
+        #[arg(long, conflicts_with = "span")]
+        eject_after: bool,
+    },
+    /// Check a copy against the manifest `cp --sign` wrote next to it
+    Verify {
+        /// The copied file or directory
+        path: PathBuf,
+        /// First check the manifest's signature against this minisign public key
+        #[arg(long, value_name = "PUBKEY")]
+        signature: Option<PathBuf>,
+    },
+    /// Back up to a known stick with the profiles in the config file
+    Backup {
+        #[command(subcommand)]
+        action: BackupAction,
+    },
+    /// Watch attached disks in the foreground and alert on SMART warnings (run it as a service)
+    Daemon,
+    /// List the copies, backups and flashes running in any lsusb process
+    Jobs {
+        /// Stop this job (PID.ID, as listed)
+        #[arg(long, value_name = "JOB")]
+        cancel: Option<String>,
+    },
+    /// Show the log of destructive operations
+    Audit {
+        /// Only entries for this device (e.g. sdb1)
+        #[arg(long)]
+        device: Option<String>,
+        /// Only entries for this operation (e.g. rm)
+        #[arg(long)]
+        operation: Option<String>,
+    },
+    /// Delete files or directories from a USB partition
+    Rm {
+        /// Files or directories to delete (directories are removed recursively)
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Overwrite file contents with zeros and trim free space before deleting
+        #[arg(long)]
+        secure: bool,
+        /// Also delete paths that are not on a USB device
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum WslAction {
+    /// List USB devices connected to the Windows host
+    List,
+    /// Attach a device to WSL
+    Attach {
+        /// The usbipd bus id (e.g., 1-4)
+        busid: String,
+    },
+    /// Give a device back to Windows
+    Detach {
+        /// The usbipd bus id (e.g., 1-4)
+        busid: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum MultibootAction {
+    /// Partition and format a stick for booting ISO images, erasing everything on it
+    Init {
+        /// The device name (e.g., sdb)
+        device: String,
+        /// Don't ask for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Copy ISO images onto a multiboot stick
+    Add {
+        /// The device name (e.g., sdb)
+        device: String,
+        /// ISO images to copy
+        #[arg(required = true)]
+        isos: Vec<PathBuf>,
+    },
+    /// List the ISO images on a multiboot stick
+    List {
+        /// The device name (e.g., sdb)
+        device: String,
+    },
+    /// Delete an ISO image from a multiboot stick
+    Remove {
+        /// The device name (e.g., sdb)
+        device: String,
+        /// File name of the image, as shown by `lsusb multiboot list`
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum StashAction {
+    /// Create a LUKS stash at the end of a stick, shrinking its last partition if needed
+    Create {
+        /// The device name (e.g., sdb)
+        device: String,
+        /// Size of the stash
+        #[arg(long, default_value = "64M", value_parser = byte_count)]
+        size: u64,
+        /// Don't ask for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Unlock and mount a stick's stash
+    Open {
+        /// The device name (e.g., sdb)
+        device: String,
+        /// Directory to mount it on (chosen automatically with the udisks2 backend)
+        mountpoint: Option<PathBuf>,
+    },
+    /// Unmount and lock a stick's stash
+    Close {
+        /// The device name (e.g., sdb)
+        device: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BackupAction {
+    /// Run a backup profile
+    Run {
+        /// Name of the profile, as in `[backup.<name>]`
+        profile: String,
+        /// Print the summary at the end as JSON
+        #[arg(long)]
+        json: bool,
+        /// Sign the run's manifest with this minisign secret key, instead of the profile's `sign` key
+        #[arg(long, value_name = "KEY")]
+        sign: Option<PathBuf>,
+    },
+    /// List the backup profiles and whether their stick is attached
+    List,
+    /// Run a profile automatically, through a systemd user timer or a udev rule
+    #[command(group(ArgGroup::new("trigger").args(["on_plug", "daily"]).required(true)))]
+    Install {
+        /// Name of the profile, as in `[backup.<name>]`
+        profile: String,
+        /// Whenever the profile's stick is plugged in
+        #[arg(long)]
+        on_plug: bool,
+        /// Once a day
+        #[arg(long)]
+        daily: bool,
+    },
+    /// Copy a backup back to this machine; the newest snapshot unless one is named
+    Restore {
+        /// PROFILE, or PROFILE/SNAPSHOT (e.g. docs/2026-03-01_120000)
+        backup: String,
+        /// Directory to restore into
+        dest: PathBuf,
+        /// What to do with files that exist here and differ: skip, overwrite or keep-both
+        #[arg(long, default_value = "skip")]
+        on_conflict: lsusb::backup::Conflict,
+    },
+    /// List a profile's snapshots and what each run changed
+    Snapshots {
+        /// Name of the profile, as in `[backup.<name>]`
+        profile: String,
+    },
+    /// Delete the snapshots the profile's `keep` rules no longer want
+    Prune {
+        /// Name of the profile, as in `[backup.<name>]`
+        profile: String,
+        /// Only list what would be deleted
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show the files added, removed and changed between two snapshots
+    Diff {
+        /// PROFILE/SNAPSHOT of the older snapshot
+        old: String,
+        /// SNAPSHOT (of the same profile) or PROFILE/SNAPSHOT of the newer one
+        new: String,
+    },
+    /// Re-read a backup and check it against the checksums recorded when it was made
+    Verify {
+        /// PROFILE, or PROFILE/SNAPSHOT; the newest snapshot unless one is named
+        backup: String,
+        /// Check this many files, or a percentage such as 5%, picked at random instead of all of them
+        #[arg(long, value_name = "FILES|PERCENT")]
+        sample: Option<lsusb::backup::Amount>,
+        /// Pick the --sample from this seed, to repeat an earlier run's (default: random)
+        #[arg(long, requires = "sample")]
+        seed: Option<u64>,
+        /// Don't re-read files whose size and modification time match an earlier --quick run (misses decayed media)
+        #[arg(long)]
+        quick: bool,
+        /// First check the manifest's signature against this minisign public key
+        #[arg(long, value_name = "PUBKEY")]
+        signature: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CameraAction {
+    /// List attached cameras
+    List,
+    /// Download every file on a camera, skipping ones already downloaded
+    Import {
+        /// Directory to download into
+        dest: PathBuf,
+        /// gphoto2 port of the camera (e.g. usb:001,012); needed when several are attached
+        #[arg(long)]
+        port: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DfuAction {
+    /// List devices with a DFU interface, one line per alternate setting
+    List,
+    /// Flash a firmware file to a device
+    Download {
+        /// Firmware image (.bin or .dfu)
+        firmware: PathBuf,
+        /// Vendor and product id of the device (e.g. 0483:df11); needed when several are attached
+        #[arg(long, value_name = "VID:PID")]
+        device: Option<String>,
+        /// Serial number of the device
+        #[arg(long)]
+        serial: Option<String>,
+        /// Alternate setting (flash region) to write, as shown by `lsusb dfu list`
+        #[arg(long)]
+        alt: Option<u8>,
+        /// Start address for ST DfuSe devices (e.g. 0x08000000:leave)
+        #[arg(long)]
+        address: Option<String>,
+        /// Reset the device afterwards so it boots the new firmware
+        #[arg(long)]
+        reset: bool,
+        /// Print the summary at the end as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum VeracryptAction {
+    /// List open volumes, and partitions of the sticks that could be VeraCrypt volumes
+    List,
+    /// Unlock and mount a volume
+    Open {
+        /// The partition (e.g., sdb1) or container file
+        volume: String,
+        /// Directory to mount it on (chosen by VeraCrypt otherwise)
+        mountpoint: Option<PathBuf>,
+    },
+    /// Unmount and lock a volume
+    Close {
+        /// The partition (e.g., sdb1) or container file
+        volume: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum IsoAction {
+    /// List the files in a directory of the image
+    Ls {
+        /// The image file, or a device holding one (e.g. /dev/sdb)
+        image: PathBuf,
+        /// Directory (or file) inside the image
+        #[arg(default_value = "/")]
+        path: PathBuf,
+        /// List everything below it as well
+        #[arg(short, long)]
+        recursive: bool,
+    },
+    /// Copy files or directories out of the image
+    Extract {
+        /// The image file, or a device holding one (e.g. /dev/sdb)
+        image: PathBuf,
+        /// Files or directories inside the image; everything if none are given
+        paths: Vec<PathBuf>,
+        /// Directory to extract into
+        #[arg(short = 'C', long = "to", value_name = "DIR", default_value = ".")]
+        dest: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum IosAction {
+    /// List attached iOS devices
+    List,
+    /// Mount a device's media partition (camera roll)
+    Mount {
+        /// The device UDID, as shown by `lsusb ios list`
+        udid: String,
+        /// Directory to mount it on
+        mountpoint: PathBuf,
+    },
+    /// Unmount an ifuse mount
+    Unmount {
+        /// The mountpoint to unmount
+        mountpoint: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TermuxAction {
+    /// List directories Termux has been granted access to
+    Dirs,
+    /// Open the Android folder picker to grant access to a drive
+    Grant,
+    /// List the contents of a granted directory
+    Ls {
+        /// content:// URI as shown by `lsusb termux dirs`
+        uri: String,
+    },
+    /// Copy a local file or directory onto the drive
+    Push {
+        /// Local file or directory
+        source: PathBuf,
+        /// content:// URI of the destination directory
+        dest: String,
+    },
+    /// Copy a file or directory from the drive
+    Pull {
+        /// content:// URI of the file or directory
+        uri: String,
+        /// Local directory to copy into
         dest: PathBuf,
     },
 }
@@ -0,0 +1,3938 @@
+//! Human-facing wrappers around the library: these print tables and status
+//! messages and are shared by the CLI and the TUI.
+
+use crate::cli::{
+    BackupAction, CameraAction, DfuAction, IosAction, IsoAction, MultibootAction, ProgressFormat,
+    StashAction, TermuxAction, VeracryptAction, WslAction,
+};
+use anyhow::{Context, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+use lsusb::audit::{AuditEntry, AuditLog};
+use lsusb::lock::{DeviceLock, Locks};
+use lsusb::notify::Notifier;
+use lsusb::policy::{Access, Policy};
+use lsusb::progress::{Summary, Throttled, Throughput, Verification};
+use lsusb::smart::{AlertTracker, Smart};
+use lsusb::{
+    BackendKind, MountOptions, Phase, ProgressEvent, ProgressSink, SizeUnits, SystemBackend, tr,
+};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use unicode_width::UnicodeWidthStr;
+
+/// Global switches shared by the CLI and the TUI.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Settings {
+    pub units: SizeUnits,
+    /// `--read-only`: refuse writes and mount read-only.
+    pub read_only: bool,
+    /// `--wait`: wait for another lsusb process to finish with a device
+    /// instead of failing.
+    pub wait: bool,
+    pub progress: ProgressFormat,
+    /// `--progress-fd`: where JSON progress goes, if not stdout.
+    pub progress_fd: Option<i32>,
+}
+
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+/// `--plain`: no colours, bars, spinners or screen clearing, progress as
+/// a line per 10%, and numbered prompts in the menu, for screen readers
+/// and serial consoles. Process-wide, as it reaches every table and bar.
+pub fn set_plain(plain: bool) {
+    PLAIN.store(plain, Ordering::Relaxed);
+}
+
+/// Whether [`set_plain`] turned plain output on.
+pub fn plain() -> bool {
+    PLAIN.load(Ordering::Relaxed)
+}
+
+impl Settings {
+    /// Whether stdout carries JSON progress, so nothing else may be
+    /// printed there but JSON.
+    pub fn json_progress_on_stdout(&self) -> bool {
+        self.progress == ProgressFormat::Json && self.progress_fd.is_none()
+    }
+
+    /// Fails if read-only mode forbids `action`.
+    pub fn ensure_writable(&self, action: &str) -> Result<()> {
+        if self.read_only {
+            anyhow::bail!("refusing to {} in read-only mode", action);
+        }
+        Ok(())
+    }
+}
+
+/// The list, redrawn every second with what each drive is reading and
+/// writing, until interrupted.
+pub fn watch_usbs(backend: &dyn SystemBackend, units: SizeUnits) -> Result<()> {
+    let mut meter = lsusb::diskstats::IoMeter::default();
+    meter
+        .sample()
+        .context("watching I/O needs Linux /proc/diskstats")?;
+    loop {
+        std::thread::sleep(Duration::from_secs(1));
+        let activity = meter.sample()?;
+        if plain() {
+            println!();
+        } else {
+            // Clear the screen and start at the top.
+            print!("\x1b[2J\x1b[H");
+        }
+        list_usbs(backend, units, Some(&activity))?;
+        println!("\n{}", tr!("refreshing"));
+    }
+}
+
+/// Lists the drives; with `activity`, also how fast each is being read
+/// and written.
+pub fn list_usbs(
+    backend: &dyn SystemBackend,
+    units: SizeUnits,
+    activity: Option<&HashMap<String, lsusb::diskstats::Activity>>,
+) -> Result<()> {
+    if lsusb::termux::is_termux() {
+        // lsblk can't see OTG storage on Android; the granted SAF
+        // directories are as close as we get.
+        return termux(TermuxAction::Dirs, Settings::default());
+    }
+    let devices = backend.enumerate()?;
+    // Shown alongside, since they aren't block devices.
+    let apple = if lsusb::ios::is_available() {
+        lsusb::ios::Ios::default().devices().unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    if devices.is_empty() && apple.is_empty() {
+        println!("{}", tr!("no-usb-devices"));
+        if lsusb::wsl::is_wsl() {
+            println!("{}", lsusb::wsl::INVISIBLE_DEVICES_HINT);
+        }
+        return Ok(());
+    }
+    if !apple.is_empty() {
+        print_ios_devices(&apple);
+        if devices.is_empty() {
+            return Ok(());
+        }
+        println!();
+    }
+
+    let io_header = if activity.is_some() {
+        format!(
+            "{} {} ",
+            pad(&tr!("column-read"), 11),
+            pad(&tr!("column-write"), 11)
+        )
+    } else {
+        String::new()
+    };
+    println!(
+        "{} {} {} {} {} {}{} {}",
+        pad(&tr!("column-name"), 10),
+        pad(&tr!("column-size"), 12),
+        pad(&tr!("column-hotplug"), 10),
+        pad(&tr!("column-port"), 10),
+        pad(&tr!("column-driver"), 12),
+        io_header,
+        pad(&tr!("column-vendor"), 20),
+        tr!("column-model")
+    );
+    let speed_classes: Vec<_> = devices
+        .iter()
+        .filter_map(|d| Some((d.name.clone(), d.speed_class?)))
+        .collect();
+    let mut bridge_issues = Vec::new();
+    let db = stats_db().filter(|_| activity.is_none());
+    for dev in devices {
+        if let Some(db) = &db
+            && let Some(serial) = usb_serial(&dev.name)
+        {
+            let _ = db.seen(&serial, &model_of(&dev), now_secs());
+        }
+        let hotplug_str = match dev.hotplug {
+            Some(true) => tr!("yes"),
+            Some(false) => tr!("no"),
+            None => "-".to_string(),
+        };
+        let bridge = lsusb::bus::storage_device(&dev.name);
+        let driver = bridge
+            .as_ref()
+            .and_then(|b| b.storage_driver())
+            .unwrap_or("-")
+            .to_string();
+        if let Some(bridge) = &bridge
+            && let Some(issue) = lsusb::bus::bridge_issue(&bridge.vid_pid)
+            && driver == "uas"
+        {
+            bridge_issues.push((dev.name.clone(), bridge.vid_pid.clone(), issue));
+        }
+        let port = bridge.as_ref().map_or("-", |b| b.bus_id.as_str());
+        let io = match activity {
+            Some(activity) => {
+                let io = activity.get(&dev.name).copied().unwrap_or_default();
+                format!(
+                    "{:<11} {:<11} ",
+                    format!("{}/s", units.format(io.read_rate)),
+                    format!("{}/s", units.format(io.write_rate))
+                )
+            }
+            None => String::new(),
+        };
+        println!(
+            "{} {:<12} {} {:<10} {:<12} {}{} {}",
+            pad(&dev.name, 10),
+            units.format(dev.bytes),
+            pad(&hotplug_str, 10),
+            port,
+            driver,
+            io,
+            pad(dev.vendor.as_deref().unwrap_or("-").trim(), 20),
+            dev.model.as_deref().unwrap_or("-")
+        );
+        for part in &dev.partitions {
+            if let Some(mountpoint) = &part.mountpoint
+                && let Ok(usage) = lsusb::usage(Path::new(mountpoint))
+            {
+                println!(
+                    "  {} {}  {}",
+                    pad(&part.name, 10),
+                    usage_bar(usage, units),
+                    mountpoint
+                );
+            }
+        }
+    }
+    for (name, speed) in speed_classes {
+        match speed.min_write_mb_s() {
+            0 => println!(
+                "{}",
+                tr!("sd-card-rated", device = name, speed = speed.to_string())
+            ),
+            mb_s => println!(
+                "{}",
+                tr!(
+                    "sd-card-rated-speed",
+                    device = name,
+                    speed = speed.to_string(),
+                    mb_s = mb_s
+                )
+            ),
+        }
+    }
+    for (name, vid_pid, issue) in bridge_issues {
+        println!(
+            "{}",
+            tr!(
+                "bridge-issue",
+                device = name,
+                vid_pid = vid_pid,
+                issue = issue.to_string()
+            )
+        );
+    }
+    Ok(())
+}
+
+pub fn list_bus() -> Result<()> {
+    let devices = lsusb::bus::devices().context("listing every USB device needs Linux sysfs")?;
+    println!(
+        "{} {} {} {} {} {}",
+        pad(&tr!("column-bus"), 10),
+        pad(&tr!("column-vid-pid"), 10),
+        pad(&tr!("column-speed"), 7),
+        pad(&tr!("column-class"), 24),
+        pad(&tr!("column-driver"), 20),
+        tr!("column-product")
+    );
+    for dev in devices {
+        let product = [dev.manufacturer.as_deref(), dev.product.as_deref()];
+        let product: Vec<&str> = product.into_iter().flatten().collect();
+        println!(
+            "{:<10} {:<10} {:<7} {} {} {}",
+            dev.bus_id,
+            dev.vid_pid,
+            dev.speed
+                .as_ref()
+                .map_or("-".to_string(), |mbit| format!("{}M", mbit)),
+            pad(&dev.classes().join(", "), 24),
+            pad(&dev.drivers().join(", "), 20),
+            if product.is_empty() {
+                "-".to_string()
+            } else {
+                product.join(" ")
+            }
+        );
+    }
+    Ok(())
+}
+
+pub fn topology(backend: &dyn SystemBackend) -> Result<()> {
+    let controllers =
+        lsusb::bus::controllers().context("showing the USB topology needs Linux sysfs")?;
+    let mut devices = lsusb::bus::devices()?;
+    devices.sort_by_key(|d| d.ports());
+    // Which drives are behind which port.
+    let mut disks: Vec<(String, String)> = Vec::new();
+    for dev in backend.enumerate().unwrap_or_default() {
+        if let Some(bridge) = lsusb::bus::storage_device(&dev.name) {
+            disks.push((bridge.bus_id, dev.name));
+        }
+    }
+    for controller in controllers {
+        let product = controller.product.unwrap_or_else(|| tr!("usb-controller"));
+        println!(
+            "{}",
+            match controller.parent {
+                Some(parent) => tr!(
+                    "bus-at",
+                    bus = controller.bus,
+                    product = product,
+                    parent = parent.to_string()
+                ),
+                None => tr!("bus", bus = controller.bus, product = product),
+            }
+        );
+        let bus = format!("{}-", controller.bus);
+        for dev in devices.iter().filter(|d| d.bus_id.starts_with(&bus)) {
+            let depth = dev.bus_id.matches('.').count() + 1;
+            let product = [dev.manufacturer.as_deref(), dev.product.as_deref()];
+            let product: Vec<&str> = product.into_iter().flatten().collect();
+            let drives: Vec<&str> = disks
+                .iter()
+                .filter(|(port, _)| *port == dev.bus_id)
+                .map(|(_, name)| name.as_str())
+                .collect();
+            let what = if dev.interfaces.iter().any(|i| i.class == 0x09) {
+                tr!("hub")
+            } else {
+                drives.join(", ")
+            };
+            println!(
+                "{}{} {:<10} {} {} {}",
+                "  ".repeat(depth),
+                pad(&dev.bus_id, 14_usize.saturating_sub(2 * depth)),
+                dev.vid_pid,
+                pad(
+                    &dev.speed
+                        .as_deref()
+                        .map(|s| format!("{}M", s))
+                        .unwrap_or_else(|| "-".into()),
+                    6
+                ),
+                pad(if what.is_empty() { "-" } else { &what }, 10),
+                if product.is_empty() {
+                    "-".to_string()
+                } else {
+                    product.join(" ")
+                }
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn list_serial() -> Result<()> {
+    let ports = lsusb::bus::serial_ports().context("finding serial adapters needs Linux sysfs")?;
+    if ports.is_empty() {
+        println!("{}", tr!("no-serial-adapters"));
+        return Ok(());
+    }
+    println!(
+        "{} {} {} {} {}",
+        pad(&tr!("column-node"), 14),
+        pad(&tr!("column-vid-pid"), 10),
+        pad(&tr!("column-chip"), 20),
+        pad(&tr!("column-serial"), 20),
+        tr!("column-product")
+    );
+    for port in ports {
+        let product = [
+            port.device.manufacturer.as_deref(),
+            port.device.product.as_deref(),
+        ];
+        let product: Vec<&str> = product.into_iter().flatten().collect();
+        println!(
+            "{:<14} {:<10} {} {} {}",
+            port.node.display(),
+            port.device.vid_pid,
+            pad(port.chip().or(port.driver.as_deref()).unwrap_or("-"), 20),
+            pad(port.device.serial.as_deref().unwrap_or("-"), 20),
+            if product.is_empty() {
+                "-".to_string()
+            } else {
+                product.join(" ")
+            }
+        );
+        if let Some(link) = &port.by_id {
+            println!(
+                "{:<14} {}",
+                "",
+                tr!("serial-also", link = link.display().to_string())
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn hexdump(backend: &dyn SystemBackend, name: &str, offset: u64, length: u64) -> Result<()> {
+    // Only USB drives, so a typo can't dump the system disk's contents.
+    let devices = backend.enumerate()?;
+    if !devices
+        .iter()
+        .any(|d| d.name == name || d.partitions.iter().any(|p| p.name == name))
+    {
+        backend.find(name)?;
+    }
+    let data = lsusb::hexdump::read_range(&Path::new("/dev").join(name), offset, length)?;
+    print!("{}", lsusb::hexdump::format(&data, offset));
+    Ok(())
+}
+
+/// Lines for `wear`: how much is used, what was written by the drive's
+/// own count, and what is left.
+fn wear_lines(wear: &lsusb::wear::Wear, units: SizeUnits) -> Vec<(String, String)> {
+    use lsusb::wear::{Basis, Counter};
+
+    let mut lines = vec![(
+        tr!("wear"),
+        match wear.basis {
+            Basis::Drive => tr!("wear-drive", percent = format!("{:.0}", wear.used_percent)),
+            Basis::Assumed => tr!(
+                "wear-assumed",
+                percent = format!("{:.1}", wear.used_percent),
+                fills = lsusb::wear::ASSUMED_DRIVE_FILLS
+            ),
+        },
+    )];
+    if let Some((bytes, Counter::Drive)) = wear.written {
+        lines.push((
+            tr!("host-writes"),
+            tr!("host-writes-value", size = units.format(bytes)),
+        ));
+    }
+    if let Some(remaining) = wear.remaining {
+        lines.push((
+            tr!("writes-left"),
+            tr!("writes-left-value", size = units.format(remaining)),
+        ));
+    }
+    lines
+}
+
+/// What lsusb has written to `device_name` over its life.
+fn tracked_bytes(device_name: &str) -> Option<u64> {
+    let serial = usb_serial(device_name)?;
+    stats_db()?
+        .get(&serial)
+        .ok()?
+        .map(|stats| stats.bytes_written)
+}
+
+/// Shows the SMART health of `device_name`, flagging readings above
+/// `thresholds`, and how worn it is.
+pub fn health(
+    backend: &dyn SystemBackend,
+    device_name: &str,
+    thresholds: &lsusb::smart::Thresholds,
+    units: SizeUnits,
+) -> Result<()> {
+    let device = backend.find(device_name)?;
+    let report = Smart::default().read(device_name)?;
+    let sectors =
+        |value: Option<u64>| value.map_or_else(|| tr!("unknown"), |v| tr!("sectors", count = v));
+    println!("{}", tr!("health-of", device = device_name));
+    let mut fields = vec![
+        (
+            tr!("health-overall"),
+            match report.passed {
+                Some(true) => tr!("health-passed"),
+                Some(false) => tr!("health-failed"),
+                None => tr!("unknown"),
+            },
+        ),
+        (
+            tr!("health-temperature"),
+            report
+                .temperature
+                .map_or_else(|| tr!("unknown"), |t| tr!("celsius", degrees = t)),
+        ),
+        (
+            tr!("health-power-on"),
+            report.power_on_hours.map_or_else(
+                || tr!("unknown"),
+                |hours| tr!("hours-days", hours = hours, days = hours / 24),
+            ),
+        ),
+        (
+            tr!("health-reallocated"),
+            sectors(report.reallocated_sectors()),
+        ),
+        (tr!("health-pending"), sectors(report.pending_sectors())),
+    ];
+    if let Some(wear) =
+        lsusb::wear::estimate(Some(&report), tracked_bytes(device_name), device.bytes)
+    {
+        fields.extend(wear_lines(&wear, units));
+    }
+    print_fields("  ", 16, &fields);
+    let alerts = report.alerts(thresholds);
+    if alerts.is_empty() {
+        println!("{}", tr!("health-no-alerts"));
+    } else {
+        println!("{}", tr!("health-warnings"));
+        for alert in alerts {
+            println!("  {}", alert);
+        }
+    }
+    Ok(())
+}
+
+/// Renames the filesystem on `partition`.
+pub fn label(
+    backend: &dyn SystemBackend,
+    partition: &str,
+    label: &str,
+    log: &AuditLog,
+    policy: &Policy,
+    settings: Settings,
+) -> Result<()> {
+    settings.ensure_writable("relabel")?;
+    let device = backend
+        .enumerate()?
+        .into_iter()
+        .find(|d| d.partitions.iter().any(|p| p.name == partition))
+        .ok_or_else(|| lsusb::UsbError::DeviceNotFound(partition.to_string()))?;
+    let part = device
+        .partitions
+        .iter()
+        .find(|p| p.name == partition)
+        .expect("found above");
+    policy.check(&device.name, Access::Allow)?;
+    let fstype = part.fstype.as_deref().with_context(|| {
+        format!(
+            "can't tell which filesystem is on {}; run as root so it can be probed",
+            partition
+        )
+    })?;
+    if let Some(mountpoint) = &part.mountpoint
+        && !lsusb::label::relabels_mounted(fstype)
+    {
+        anyhow::bail!(
+            "unmount {} first: {} can't be relabeled while mounted",
+            mountpoint,
+            fstype
+        );
+    }
+    let label = lsusb::label::normalize(fstype, label)?;
+    let _lock = lock_device(&device.name, "label", settings)?;
+    log.record(&AuditEntry::new(
+        "label",
+        Some(partition.to_string()),
+        vec![label.clone()],
+    ))?;
+    lsusb::label::Labeler::default().set(partition, fstype, &label)?;
+    println!("{}", tr!("labeled", partition = partition, label = label));
+    Ok(())
+}
+
+pub fn rescan(backend: &dyn SystemBackend, device_name: &str, settings: Settings) -> Result<()> {
+    // Only USB drives, like everything else here.
+    backend.find(device_name)?;
+    let _lock = lock_device(device_name, "rescan", settings)?;
+    lsusb::rescan::Rescanner::default().rescan(device_name, &[])?;
+    println!("{}", tr!("rescanned", device = device_name));
+    list_partitions(backend, device_name, settings.units)
+}
+
+pub fn install_bootloader(
+    backend: &dyn SystemBackend,
+    device_name: &str,
+    bootloader: lsusb::bootloader::Bootloader,
+    log: &AuditLog,
+    policy: &Policy,
+    settings: Settings,
+) -> Result<()> {
+    settings.ensure_writable("install a bootloader")?;
+    let device = backend.find(device_name)?;
+    policy.check(device_name, Access::Allow)?;
+    let _lock = lock_device(device_name, "bootloader install", settings)?;
+    let mountpoint = device.mountpoints().next().with_context(|| {
+        format!(
+            "mount a partition of {} first; the bootloader and its menu go there",
+            device_name
+        )
+    })?;
+    let flag = match bootloader {
+        lsusb::bootloader::Bootloader::Grub => "--grub",
+        lsusb::bootloader::Bootloader::Syslinux => "--syslinux",
+    };
+    log.record(&AuditEntry::new(
+        "bootloader",
+        Some(device_name.to_string()),
+        vec![flag.into()],
+    ))?;
+    let report = lsusb::bootloader::Installer::default().install(
+        bootloader,
+        device_name,
+        Path::new(mountpoint),
+    )?;
+    println!(
+        "{}",
+        tr!(
+            "bootloader-installed",
+            targets = report.targets.join(&format!(" {} ", tr!("and"))),
+            config = report.config.display().to_string()
+        )
+    );
+    if report.entries.kernels.is_empty() && report.entries.isos.is_empty() {
+        println!("{}", tr!("bootloader-menu-empty"));
+    }
+    for kernel in &report.entries.kernels {
+        println!(
+            "  {}",
+            tr!("bootloader-kernel", path = kernel.path.as_str())
+        );
+    }
+    for iso in &report.entries.isos {
+        match bootloader {
+            lsusb::bootloader::Bootloader::Grub => {
+                println!("  {}", tr!("bootloader-iso", iso = iso.as_str()))
+            }
+            lsusb::bootloader::Bootloader::Syslinux => {
+                println!("  {}", tr!("bootloader-iso-skipped", iso = iso.as_str()))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `sdb (SanDisk Ultra, 16.0G)`, for confirmation prompts.
+fn describe(device: &lsusb::UsbDevice, units: SizeUnits) -> String {
+    let model = [device.vendor.as_deref(), device.model.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        "{} ({}, {})",
+        device.name,
+        if model.is_empty() {
+            tr!("unknown-model")
+        } else {
+            model
+        },
+        units.format(device.bytes)
+    )
+}
+
+/// Asks before a destructive step; `--yes` skips this.
+fn confirm(prompt: &str) -> Result<()> {
+    let confirmed = dialoguer::Confirm::new()
+        .with_prompt(prompt)
+        .default(false)
+        .interact()
+        .context("pass --yes to go ahead without asking")?;
+    if !confirmed {
+        anyhow::bail!("{}", tr!("nothing-changed"));
+    }
+    Ok(())
+}
+
+pub fn stash(
+    backend: &dyn SystemBackend,
+    action: StashAction,
+    log: &AuditLog,
+    policy: &Policy,
+    settings: Settings,
+) -> Result<()> {
+    use lsusb::stash;
+
+    let tools = stash::Stash::default();
+    match action {
+        StashAction::Create {
+            device: device_name,
+            size,
+            yes,
+        } => {
+            settings.ensure_writable("create a stash")?;
+            let device = backend.find(&device_name)?;
+            policy.check(&device_name, Access::Allow)?;
+            let _lock = lock_device(&device_name, "stash create", settings)?;
+            let table = tools.partition_table(&device_name)?;
+            let layout = stash::plan(&device_name, device.bytes, &table, size)?;
+            let stash_size = settings.units.format(layout.sectors * 512);
+            match &layout.shrink {
+                Some(shrink) => println!(
+                    "{}",
+                    tr!(
+                        "stash-shrink",
+                        partition = shrink.partition.as_str(),
+                        size = settings.units.format(shrink.sectors * 512),
+                        stash_size = stash_size,
+                        stash = layout.partition.as_str()
+                    )
+                ),
+                None => println!(
+                    "{}",
+                    tr!(
+                        "stash-free-space",
+                        stash = layout.partition.as_str(),
+                        stash_size = stash_size
+                    )
+                ),
+            }
+            if !yes {
+                confirm(&tr!(
+                    "confirm-repartition",
+                    device = describe(&device, settings.units)
+                ))?;
+            }
+            let passphrase = dialoguer::Password::new()
+                .with_prompt(tr!("stash-passphrase"))
+                .with_confirmation(tr!("repeat-it"), tr!("passphrases-differ"))
+                .interact()?;
+            log.record(&AuditEntry::new(
+                "stash create",
+                Some(device_name.clone()),
+                vec![format!("--size={}", size)],
+            ))?;
+            tools.create(&device, &layout, &passphrase)?;
+            println!(
+                "{}",
+                tr!(
+                    "stash-created",
+                    stash = layout.partition.as_str(),
+                    device = device_name.as_str()
+                )
+            );
+        }
+        StashAction::Open {
+            device: device_name,
+            mountpoint,
+        } => {
+            let device = backend.find(&device_name)?;
+            let access = policy.check(&device_name, Access::ReadOnly)?;
+            let partition = stash::find(&device)?;
+            let options = MountOptions {
+                read_only: settings.read_only || access == Access::ReadOnly,
+            };
+            let passphrase = dialoguer::Password::new()
+                .with_prompt(tr!("stash-passphrase"))
+                .interact()?;
+            let mounted_at = tools.open(
+                backend,
+                &partition.name,
+                &passphrase,
+                mountpoint.as_deref(),
+                options,
+            )?;
+            println!(
+                "{}",
+                tr!("stash-open", mountpoint = mounted_at.display().to_string())
+            );
+        }
+        StashAction::Close {
+            device: device_name,
+        } => {
+            let device = backend.find(&device_name)?;
+            let partition = stash::find(&device)?;
+            tools.close(backend, &partition.name)?;
+            println!("{}", tr!("stash-closed"));
+        }
+    }
+    Ok(())
+}
+
+/// What `volume` of a `veracrypt` command names: a container file, or a
+/// partition's device node.
+fn veracrypt_volume(volume: &str, policy: &Policy, needed: Access) -> Result<(String, Access)> {
+    let path = Path::new(volume);
+    if path.is_file() {
+        let access = policy.check_path(path, needed)?;
+        let path =
+            fs::canonicalize(path).with_context(|| format!("Failed to resolve {}", volume))?;
+        return Ok((path.display().to_string(), access));
+    }
+    let name = volume.strip_prefix("/dev/").unwrap_or(volume);
+    Ok((format!("/dev/{}", name), policy.check(name, needed)?))
+}
+
+pub fn veracrypt(
+    backend: &dyn SystemBackend,
+    action: VeracryptAction,
+    policy: &Policy,
+    settings: Settings,
+) -> Result<()> {
+    use lsusb::veracrypt;
+
+    let tools = veracrypt::Veracrypt::default();
+    match action {
+        VeracryptAction::List => {
+            let open = tools.list()?;
+            if open.is_empty() {
+                println!("{}", tr!("veracrypt-none-open"));
+            } else {
+                println!(
+                    "{} {} {}",
+                    pad(&tr!("column-volume"), 24),
+                    pad(&tr!("column-device"), 24),
+                    tr!("column-mountpoint")
+                );
+                for volume in &open {
+                    let mountpoint = volume.mountpoint.as_ref().map(|m| m.display().to_string());
+                    println!(
+                        "{} {} {}",
+                        pad(&volume.volume, 24),
+                        pad(&volume.device, 24),
+                        mountpoint.as_deref().unwrap_or("-")
+                    );
+                }
+            }
+            let mut candidates = Vec::new();
+            let mut unreadable = false;
+            for device in backend.enumerate()? {
+                let names: Vec<String> = if device.partitions.is_empty() {
+                    vec![device.name.clone()]
+                } else {
+                    device
+                        .partitions
+                        .iter()
+                        .filter(|p| p.fstype.is_none())
+                        .map(|p| p.name.clone())
+                        .collect()
+                };
+                for name in names {
+                    let node = format!("/dev/{}", name);
+                    if open.iter().any(|v| v.volume == node) {
+                        continue;
+                    }
+                    match veracrypt::looks_like_volume_path(Path::new(&node)) {
+                        Ok(true) => candidates.push(name),
+                        Ok(false) => {}
+                        Err(_) => unreadable = true,
+                    }
+                }
+            }
+            println!();
+            if candidates.is_empty() {
+                println!("{}", tr!("veracrypt-no-candidates"));
+            } else {
+                println!(
+                    "{}",
+                    tr!("veracrypt-candidates", partitions = candidates.join(", "))
+                );
+            }
+            if unreadable {
+                println!("{}", tr!("veracrypt-unreadable"));
+            }
+        }
+        VeracryptAction::Open { volume, mountpoint } => {
+            let (volume, access) = veracrypt_volume(&volume, policy, Access::ReadOnly)?;
+            let options = MountOptions {
+                read_only: settings.read_only || access == Access::ReadOnly,
+            };
+            let password = dialoguer::Password::new()
+                .with_prompt(tr!("veracrypt-password"))
+                .interact()?;
+            let mounted_at = tools.open(&volume, &password, mountpoint.as_deref(), options)?;
+            println!(
+                "{}",
+                tr!(
+                    "volume-open-at",
+                    volume = volume.as_str(),
+                    mountpoint = mounted_at.display().to_string()
+                )
+            );
+        }
+        VeracryptAction::Close { volume } => {
+            let (volume, _) = veracrypt_volume(&volume, policy, Access::ReadOnly)?;
+            let mountpoint = tools
+                .list()?
+                .into_iter()
+                .find(|v| v.volume == volume)
+                .and_then(|v| v.mountpoint);
+            if let Some(mountpoint) = &mountpoint {
+                settle_jobs(&[mountpoint.as_path()])?;
+            }
+            tools.close(&volume)?;
+            println!("{}", tr!("volume-closed", volume = volume));
+        }
+    }
+    Ok(())
+}
+
+pub fn format(
+    device: &lsusb::UsbDevice,
+    options: lsusb::format::Options,
+    yes: bool,
+    log: &AuditLog,
+    policy: &Policy,
+    settings: Settings,
+) -> Result<()> {
+    settings.ensure_writable("erase a device")?;
+    let device_name = device.name.as_str();
+    policy.check(device_name, Access::Allow)?;
+    let _lock = lock_device(device_name, "format", settings)?;
+    let plan = lsusb::format::plan(device.bytes, &options)?;
+    if !yes {
+        confirm(&tr!(
+            "confirm-erase",
+            device = describe(device, settings.units)
+        ))?;
+    }
+    let mut params = vec![
+        format!("--fs={}", options.filesystem),
+        format!("--cluster={}", plan.cluster),
+        format!("--align={}", options.align),
+    ];
+    if let Some(label) = &options.label {
+        params.push(format!("--label={}", label));
+    }
+    log.record(&AuditEntry::new(
+        "format",
+        Some(device_name.to_string()),
+        params,
+    ))?;
+    println!(
+        "{}",
+        tr!(
+            "formatting",
+            device = device_name,
+            fs = options.filesystem.to_string(),
+            cluster = SizeUnits::Iec.format(plan.cluster),
+            align = SizeUnits::Iec.format(options.align)
+        )
+    );
+    let (partition, plan) = lsusb::format::Formatter::default().format(device, &options)?;
+    println!(
+        "{}",
+        tr!(
+            "formatted",
+            partition = partition.to_string(),
+            size = settings.units.format(plan.bytes),
+            fs = options.filesystem.to_string()
+        )
+    );
+    Ok(())
+}
+
+/// What the kernel and `disk` offer for discard, for `lsusb trim`.
+fn print_discard_support(disk: &str, support: &lsusb::trim::DiscardSupport) {
+    if support.supported() {
+        println!(
+            "{}",
+            tr!(
+                "discard-supported",
+                disk = disk,
+                granularity = SizeUnits::Iec.format(support.granularity),
+                max = SizeUnits::Iec.format(support.max_bytes)
+            )
+        );
+    } else {
+        println!("{}", tr!("discard-unsupported", disk = disk));
+    }
+    if let Some(mode) = &support.provisioning_mode {
+        let key = match support.unmap_advertised {
+            Some(true) => "provisioning-unmap",
+            Some(false) => "provisioning-no-unmap",
+            None => "provisioning-unmap-unknown",
+        };
+        println!("  {}", tr!(key, mode = mode.as_str()));
+    }
+}
+
+pub fn trim(
+    backend: &dyn SystemBackend,
+    target: &str,
+    enable_unmap: bool,
+    yes: bool,
+    log: &AuditLog,
+    policy: &Policy,
+    settings: Settings,
+) -> Result<()> {
+    use lsusb::trim;
+
+    settings.ensure_writable("trim")?;
+    let devices = backend.enumerate()?;
+    let (disk, name, mountpoint) = if Path::new(target).is_dir() {
+        let target =
+            fs::canonicalize(target).with_context(|| format!("Failed to resolve {}", target))?;
+        devices
+            .iter()
+            .flat_map(|d| d.partitions.iter().map(move |p| (d, p)))
+            .find(|(_, p)| p.mountpoint.as_deref().map(Path::new) == Some(target.as_path()))
+            .map(|(d, p)| (d, p.name.clone(), p.mountpoint.clone()))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{} isn't where a USB partition is mounted",
+                    target.display()
+                )
+            })?
+    } else {
+        let name = target.strip_prefix("/dev/").unwrap_or(target);
+        match devices.iter().find(|d| d.name == name) {
+            Some(disk) => {
+                if let Some(mountpoint) = disk.mountpoints().next() {
+                    anyhow::bail!(
+                        "{} is mounted at {}; trim that, or unmount it to discard the whole drive",
+                        name,
+                        mountpoint
+                    );
+                }
+                (disk, name.to_string(), None)
+            }
+            None => devices
+                .iter()
+                .flat_map(|d| d.partitions.iter().map(move |p| (d, p)))
+                .find(|(_, p)| p.name == name)
+                .map(|(d, p)| (d, p.name.clone(), p.mountpoint.clone()))
+                .ok_or_else(|| lsusb::UsbError::DeviceNotFound(name.to_string()))?,
+        }
+    };
+    policy.check(&name, Access::Allow)?;
+    let _lock = lock_device(&disk.name, "trim", settings)?;
+
+    let mut support = trim::discard_support(&disk.name);
+    print_discard_support(&disk.name, &support);
+    if enable_unmap {
+        if support.can_enable_unmap() {
+            trim::enable_unmap(&disk.name)?;
+            println!("{}", tr!("unmap-enabled", disk = disk.name.as_str()));
+            support = trim::discard_support(&disk.name);
+        } else if support.provisioning_mode.as_deref() != Some("unmap") {
+            anyhow::bail!(
+                "{} doesn't advertise UNMAP, so it can't be enabled",
+                disk.name
+            );
+        }
+    }
+    if !support.supported() {
+        if support.can_enable_unmap() {
+            anyhow::bail!(
+                "discard isn't enabled for {}; try again with --enable-unmap",
+                disk.name
+            );
+        }
+        anyhow::bail!(
+            "{} can't be trimmed: its USB bridge doesn't pass discards through",
+            disk.name
+        );
+    }
+
+    let tools = trim::Trimmer::default();
+    match mountpoint {
+        Some(mountpoint) => {
+            log.record(&AuditEntry::new(
+                "trim",
+                Some(name.clone()),
+                vec![mountpoint.clone()],
+            ))?;
+            match tools.fstrim(&mountpoint)? {
+                Some(bytes) => println!(
+                    "{}",
+                    tr!(
+                        "trimmed-bytes",
+                        size = settings.units.format(bytes),
+                        mountpoint = mountpoint.as_str()
+                    )
+                ),
+                None => println!("{}", tr!("trimmed", mountpoint = mountpoint.as_str())),
+            }
+        }
+        None => {
+            let bytes = if name == disk.name {
+                disk.bytes
+            } else {
+                disk.partitions
+                    .iter()
+                    .find(|p| p.name == name)
+                    .map_or(0, |p| p.bytes)
+            };
+            if !yes {
+                confirm(&tr!(
+                    "confirm-discard",
+                    device = name.as_str(),
+                    size = settings.units.format(bytes)
+                ))?;
+            }
+            log.record(&AuditEntry::new("trim", Some(name.clone()), Vec::new()))?;
+            tools.blkdiscard(&name)?;
+            println!("{}", tr!("discarded", device = name.as_str()));
+            if trim::reads_back_erased_path(Path::new(&format!("/dev/{}", name)), bytes)? {
+                println!("{}", tr!("discard-honored"));
+            } else if support.reads_zeros == Some(true) {
+                println!("{}", tr!("discard-ignored"));
+            } else {
+                println!("{}", tr!("discard-unknown"));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn bench(
+    backend: &dyn SystemBackend,
+    target: &str,
+    options: &lsusb::bench::Options,
+    policy: &Policy,
+    settings: Settings,
+) -> Result<()> {
+    use lsusb::bench::Test;
+
+    settings.ensure_writable("benchmark")?;
+    let dir = if Path::new(target).is_dir() {
+        PathBuf::from(target)
+    } else {
+        let partition = backend
+            .enumerate()?
+            .into_iter()
+            .flat_map(|device| device.partitions)
+            .find(|partition| partition.name == target)
+            .ok_or_else(|| lsusb::UsbError::DeviceNotFound(target.to_string()))?;
+        let mountpoint = partition.mountpoint.with_context(|| {
+            format!(
+                "{} isn't mounted; mount it first with `lsusb mount {}`",
+                target, target
+            )
+        })?;
+        PathBuf::from(mountpoint)
+    };
+    policy.check_path(&dir, Access::Allow)?;
+    let _lock = lock_path(backend, &dir, "bench", settings)?;
+    let available = lsusb::usb::usage(&dir).map_or(u64::MAX, |usage| usage.available);
+    if available < options.file_size {
+        anyhow::bail!(
+            "{} has only {} free; pass a smaller --size",
+            dir.display(),
+            settings.units.format(available)
+        );
+    }
+    println!(
+        "{}",
+        tr!(
+            "benchmarking",
+            dir = dir.display().to_string(),
+            size = settings.units.format(options.file_size)
+        )
+    );
+    let interrupt = crate::interrupt::guard();
+    let mut cached = false;
+    let results = lsusb::bench::run(&dir, options, &interrupt.token(), |result| {
+        cached |= result.cached;
+        let speed = format!("{}/s", settings.units.format(result.bytes_per_sec() as u64));
+        match result.test {
+            Test::SequentialWrite => println!(
+                "  {} {:>12}",
+                pad(&tr!("bench-sequential-write"), 24),
+                speed
+            ),
+            Test::SequentialRead => {
+                println!("  {} {:>12}", pad(&tr!("bench-sequential-read"), 24), speed)
+            }
+            Test::RandomRead { queue_depth } | Test::RandomWrite { queue_depth } => {
+                let key = if matches!(result.test, Test::RandomRead { .. }) {
+                    "bench-random-read"
+                } else {
+                    "bench-random-write"
+                };
+                println!(
+                    "  {} {:>12}  {:>8.0} IOPS",
+                    pad(&tr!(key, queue_depth = queue_depth), 24),
+                    speed,
+                    result.iops()
+                );
+            }
+        }
+    })?;
+    if cached {
+        println!("{}", tr!("bench-cached"));
+    }
+    if results.is_empty() {
+        println!("{}", tr!("bench-none"));
+    }
+    Ok(())
+}
+
+pub fn multiboot(
+    backend: &dyn SystemBackend,
+    action: MultibootAction,
+    log: &AuditLog,
+    policy: &Policy,
+    settings: Settings,
+) -> Result<()> {
+    use lsusb::backend::with_mounted;
+    use lsusb::multiboot;
+
+    match action {
+        MultibootAction::Init {
+            device: device_name,
+            yes,
+        } => {
+            settings.ensure_writable("erase a device")?;
+            let device = backend.find(&device_name)?;
+            policy.check(&device_name, Access::Allow)?;
+            let _lock = lock_device(&device_name, "multiboot init", settings)?;
+            if !yes {
+                confirm(&tr!(
+                    "confirm-erase",
+                    device = describe(&device, settings.units)
+                ))?;
+            }
+            log.record(&AuditEntry::new(
+                "multiboot init",
+                Some(device_name.clone()),
+                Vec::new(),
+            ))?;
+            println!(
+                "{}",
+                tr!("multiboot-partitioning", device = device_name.as_str())
+            );
+            let data = multiboot::Multiboot::default().init(backend, &device)?;
+            println!(
+                "{}",
+                tr!(
+                    "multiboot-ready",
+                    dir = multiboot::ISO_DIR,
+                    partition = data.to_string(),
+                    label = multiboot::DATA_LABEL,
+                    device = device_name.as_str()
+                )
+            );
+        }
+        MultibootAction::Add {
+            device: device_name,
+            isos,
+        } => {
+            settings.ensure_writable("copy")?;
+            if let Some(other) = isos
+                .iter()
+                .find(|p| !p.to_string_lossy().to_ascii_lowercase().ends_with(".iso"))
+            {
+                anyhow::bail!("{} is not an ISO image", other.display());
+            }
+            let device = backend.find(&device_name)?;
+            policy.check(&device_name, Access::Allow)?;
+            let _lock = lock_device(&device_name, "multiboot add", settings)?;
+            let data = multiboot::data_partition(&device)?;
+            let interrupt = crate::interrupt::guard();
+            with_mounted(
+                backend,
+                &data.name,
+                data.mountpoint.as_deref(),
+                MountOptions::default(),
+                |root| {
+                    let dir = multiboot::iso_dir(root)?;
+                    for iso in &isos {
+                        let options = lsusb::TransferOptions {
+                            cancel: interrupt.token(),
+                            ..Default::default()
+                        };
+                        let pb = ProgressBar::hidden();
+                        let render = Throttled::new(
+                            render_progress(&pb, false, settings),
+                            Duration::from_millis(100),
+                        );
+                        let result = lsusb::copy_with_progress(iso, &dir, &options, render);
+                        if result.is_err() {
+                            pb.abandon();
+                        }
+                        result?;
+                    }
+                    Ok(())
+                },
+            )?;
+            println!("{}", tr!("multiboot-added", count = isos.len()));
+        }
+        MultibootAction::List {
+            device: device_name,
+        } => {
+            let device = backend.find(&device_name)?;
+            let data = multiboot::data_partition(&device)?;
+            let options = MountOptions { read_only: true };
+            let isos = with_mounted(
+                backend,
+                &data.name,
+                data.mountpoint.as_deref(),
+                options,
+                multiboot::isos,
+            )?;
+            if isos.is_empty() {
+                println!("{}", tr!("multiboot-empty", device = device_name.as_str()));
+                return Ok(());
+            }
+            println!("{} {}", pad(&tr!("column-size"), 12), tr!("column-name"));
+            for iso in isos {
+                println!("{:<12} {}", settings.units.format(iso.bytes), iso.name);
+            }
+        }
+        MultibootAction::Remove {
+            device: device_name,
+            name,
+        } => {
+            settings.ensure_writable("delete")?;
+            let device = backend.find(&device_name)?;
+            policy.check(&device_name, Access::Allow)?;
+            let _lock = lock_device(&device_name, "multiboot remove", settings)?;
+            let data = multiboot::data_partition(&device)?;
+            log.record(&AuditEntry::new(
+                "multiboot remove",
+                Some(data.name.clone()),
+                vec![name.clone()],
+            ))?;
+            with_mounted(
+                backend,
+                &data.name,
+                data.mountpoint.as_deref(),
+                MountOptions::default(),
+                |root| multiboot::remove_iso(root, &name),
+            )?;
+            println!("{}", tr!("removed", name = name.as_str()));
+        }
+    }
+    Ok(())
+}
+
+pub fn list_partitions(
+    backend: &dyn SystemBackend,
+    device_name: &str,
+    units: SizeUnits,
+) -> Result<()> {
+    let device = backend.find(device_name)?;
+
+    println!("{}", tr!("partitions-of", device = device_name));
+    if device.partitions.is_empty() {
+        println!("{}", tr!("no-partitions"));
+        return Ok(());
+    }
+
+    println!(
+        "{} {} {} {} {} {}",
+        pad(&tr!("column-name"), 10),
+        pad(&tr!("column-size"), 12),
+        pad(&tr!("column-type"), 10),
+        pad(&tr!("column-fstype"), 12),
+        pad(&tr!("column-label"), 20),
+        tr!("column-mountpoint")
+    );
+    for part in &device.partitions {
+        println!(
+            "{} {:<12} {} {:<12} {} {}",
+            pad(&part.name, 10),
+            units.format(part.bytes),
+            pad(&part.device_type, 10),
+            part.fstype.as_deref().unwrap_or("-"),
+            pad(part.label.as_deref().unwrap_or("-"), 20),
+            part.mountpoint.as_deref().unwrap_or("-")
+        );
+        if let Some(mountpoint) = &part.mountpoint
+            && let Ok(usage) = lsusb::usage(Path::new(mountpoint))
+        {
+            println!("{} {}", pad("", 10), usage_bar(usage, units));
+        }
+    }
+    Ok(())
+}
+
+/// `list --porcelain` (all devices) and `parts --porcelain` (the
+/// partitions of `device`), in format `version`.
+pub fn porcelain(
+    backend: &dyn SystemBackend,
+    device: Option<&str>,
+    version: u32,
+    nul: bool,
+) -> Result<()> {
+    if version != lsusb::porcelain::VERSION {
+        anyhow::bail!(
+            "porcelain version {} doesn't exist; this lsusb writes version {}",
+            version,
+            lsusb::porcelain::VERSION
+        );
+    }
+    let devices = match device {
+        Some(name) => vec![backend.find(name)?],
+        None => backend.enumerate()?,
+    };
+    let mut out = lsusb::porcelain::Writer::new(std::io::stdout().lock(), nul)?;
+    for dev in &devices {
+        if device.is_none() {
+            out.device(dev, lsusb::bus::storage_device(&dev.name).as_ref())?;
+        }
+        for part in &dev.partitions {
+            let usage = part
+                .mountpoint
+                .as_ref()
+                .and_then(|m| lsusb::usage(Path::new(m)).ok());
+            out.partition(&dev.name, part, usage)?;
+        }
+    }
+    Ok(())
+}
+
+/// `[#######-------------]  35% used, 9.7G free`, green, then yellow from
+/// 75% and red from 90% on a terminal; without the bar when plain.
+fn usage_bar(usage: lsusb::Usage, units: SizeUnits) -> String {
+    use std::io::IsTerminal;
+    const WIDTH: u64 = 20;
+
+    let percent = usage.percent_used().min(100);
+    let text = tr!(
+        "usage",
+        percent = format!("{:>3}", percent),
+        free = units.format(usage.available)
+    );
+    if plain() {
+        return text;
+    }
+    let filled = (percent * WIDTH).div_ceil(100) as usize;
+    let bar = format!(
+        "[{}{}]",
+        "#".repeat(filled),
+        "-".repeat(WIDTH as usize - filled)
+    );
+    let text = format!("{} {}", bar, text);
+    if !std::io::stdout().is_terminal() || std::env::var_os("NO_COLOR").is_some() {
+        return text;
+    }
+    let color = match percent {
+        90.. => 31,
+        75.. => 33,
+        _ => 32,
+    };
+    format!("\x1b[{}m{}\x1b[0m", color, text)
+}
+
+/// Prints `label value` lines after `indent`, the values lined up at least
+/// `width` cells in and past the longest label, whatever the language.
+fn print_fields(indent: &str, width: usize, fields: &[(String, String)]) {
+    let width = fields
+        .iter()
+        .map(|(label, _)| UnicodeWidthStr::width(label.as_str()))
+        .fold(width, usize::max);
+    for (label, value) in fields {
+        println!("{}{} {}", indent, pad(label, width), value);
+    }
+}
+
+/// Left-aligns `text` in a column `width` terminal cells wide. `{:<N}` pads
+/// by `char` count, which misaligns tables once a label or mountpoint holds
+/// wide (CJK) or combining characters.
+fn pad(text: &str, width: usize) -> String {
+    let used = UnicodeWidthStr::width(text);
+    format!("{}{}", text, " ".repeat(width.saturating_sub(used)))
+}
+
+/// Right-aligns `text` in a column `width` terminal cells wide; see [`pad`].
+fn pad_left(text: &str, width: usize) -> String {
+    let used = UnicodeWidthStr::width(text);
+    format!("{}{}", " ".repeat(width.saturating_sub(used)), text)
+}
+
+pub fn sync_device(backend: &dyn SystemBackend, device_name: &str, units: SizeUnits) -> Result<()> {
+    println!("{}", tr!("syncing", device = device_name));
+    flush(backend, device_name, units)?;
+    println!("{}", tr!("synced"));
+    Ok(())
+}
+
+/// Flushes `device`, with a bar counting down the data still to be written
+/// when there is enough of it to take a while.
+fn flush(backend: &dyn SystemBackend, device: &str, units: SizeUnits) -> Result<()> {
+    flush_with(device, units, || backend.flush(device)).map(drop)
+}
+
+/// Runs `flush`, which writes out (some of) `device`, with [`flush`]'s bar.
+/// Returns how much was written meanwhile, if the kernel said.
+fn flush_with(
+    device: &str,
+    units: SizeUnits,
+    flush: impl FnOnce() -> lsusb::Result<()>,
+) -> Result<Option<u64>> {
+    const SHOW_ABOVE: u64 = 1 << 20;
+
+    let pb = ProgressBar::hidden();
+    let mut flushed = None;
+    let mut shown = false;
+    lsusb::writeback::flush_with_progress(device, flush, |event| match event {
+        ProgressEvent::Totals { bytes, .. } => {
+            flushed = Some(0);
+            if bytes > SHOW_ABOVE {
+                shown = true;
+                start_bar(&pb, bytes);
+                if !plain() {
+                    pb.set_style(ProgressStyle::default_bar()
+                        .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {msg} ({bytes_per_sec}, {eta})")
+                        .unwrap()
+                        .progress_chars("#>-"));
+                }
+            }
+        }
+        ProgressEvent::Bytes { done, total } => {
+            flushed = Some(done);
+            pb.set_length(total);
+            if shown {
+                report_percent(&pb, &tr!("progress-flushing"), done);
+            }
+            pb.set_position(done);
+            pb.set_message(tr!("flushing-remaining", size = units.format(total - done)));
+        }
+        _ => {}
+    })?;
+    pb.finish_and_clear();
+    Ok(flushed)
+}
+
+pub fn mount_partition(
+    backend: &dyn SystemBackend,
+    partition: &str,
+    mountpoint: Option<&Path>,
+    settings: Settings,
+) -> Result<PathBuf> {
+    let options = MountOptions {
+        read_only: settings.read_only,
+    };
+    let key = if options.read_only {
+        "mounting-read-only"
+    } else {
+        "mounting"
+    };
+    println!("{}", tr!(key, partition = partition));
+    let mounted_at = backend.mount(partition, mountpoint, options)?;
+    println!(
+        "{}",
+        tr!("mounted-at", mountpoint = mounted_at.display().to_string())
+    );
+    Ok(mounted_at)
+}
+
+/// Opens `target` in the file manager: a directory, or a partition (e.g.
+/// `sdb1`), which is mounted first if it isn't already.
+pub fn open(
+    backend: &dyn SystemBackend,
+    config: &lsusb::Config,
+    target: &str,
+    mut settings: Settings,
+) -> Result<()> {
+    let dir = if Path::new(target).is_dir() {
+        PathBuf::from(target)
+    } else {
+        let partition = backend
+            .enumerate()?
+            .into_iter()
+            .flat_map(|device| device.partitions)
+            .find(|partition| partition.name == target)
+            .ok_or_else(|| lsusb::UsbError::DeviceNotFound(target.to_string()))?;
+        match partition.mountpoint {
+            Some(mountpoint) => PathBuf::from(mountpoint),
+            None => {
+                let access = config.policy.check(&partition.name, Access::ReadOnly)?;
+                settings.read_only |= access == Access::ReadOnly;
+                mount_partition(backend, &partition.name, None, settings)?
+            }
+        }
+    };
+    println!("{}", tr!("opening", dir = dir.display().to_string()));
+    config.file_manager().open(&dir)?;
+    Ok(())
+}
+
+pub fn unmount_device(
+    backend: &dyn SystemBackend,
+    mountpoint: &str,
+    units: SizeUnits,
+) -> Result<()> {
+    settle_jobs(&[Path::new(mountpoint)])?;
+    // Unmounting writes everything out anyway; this way it shows, and
+    // only this filesystem is waited for.
+    let sync = || lsusb::backend::sync_filesystem(Path::new(mountpoint));
+    match device_of(backend, Path::new(mountpoint))? {
+        Some(device) => match flush_with(&device, units, sync)? {
+            Some(bytes) if bytes > 0 => {
+                // Without root only the system-wide count is known.
+                let key = if lsusb::privilege::is_root() {
+                    "flushed-bytes"
+                } else {
+                    "flushed-up-to"
+                };
+                println!(
+                    "{}",
+                    tr!(key, size = units.format(bytes), mountpoint = mountpoint)
+                );
+            }
+            _ => println!("{}", tr!("flushed", mountpoint = mountpoint)),
+        },
+        None => sync()?,
+    }
+    println!("{}", tr!("unmounting", mountpoint = mountpoint));
+    backend.unmount(mountpoint)?;
+    println!("{}", tr!("unmounted"));
+    Ok(())
+}
+
+pub fn power_off(backend: &dyn SystemBackend, device_name: &str, units: SizeUnits) -> Result<()> {
+    // Whatever power_off says about a device that can't be found.
+    if let Ok(device) = backend.find(device_name) {
+        let mountpoints: Vec<&Path> = device
+            .partitions
+            .iter()
+            .filter_map(|p| p.mountpoint.as_deref())
+            .map(Path::new)
+            .collect();
+        settle_jobs(&mountpoints)?;
+        if !mountpoints.is_empty() {
+            flush(backend, device_name, units)?;
+        }
+    }
+    println!("{}", tr!("powering-off", device = device_name));
+    backend.power_off(device_name)?;
+    println!("{}", tr!("safe-to-unplug", device = device_name));
+    Ok(())
+}
+
+/// `cp --eject-after`: once a copy to `dests` succeeded, syncs, unmounts
+/// and powers off every USB drive they are on, so it can be pulled later
+/// without another step.
+pub fn eject_after(backend: &dyn SystemBackend, dests: &[PathBuf], units: SizeUnits) -> Result<()> {
+    let mut devices = Vec::new();
+    for dest in dests {
+        let device = match partition_dest(backend, dest)? {
+            Some((partition, ..)) => backend
+                .enumerate()?
+                .into_iter()
+                .find(|d| d.partitions.iter().any(|p| p.name == partition))
+                .map(|d| d.name),
+            None => device_of(backend, dest)?,
+        };
+        match device {
+            Some(device) if !devices.contains(&device) => devices.push(device),
+            Some(_) => {}
+            None => println!(
+                "{}",
+                tr!("nothing-to-eject", path = dest.display().to_string())
+            ),
+        }
+    }
+    for device in &devices {
+        power_off(backend, device, units)?;
+    }
+    Ok(())
+}
+
+/// Before `mountpoints` go away, makes sure none of this process's own
+/// copies or backups still use them: asks whether to wait for them or
+/// cancel them, and refuses when the answer is no or there's no one to ask.
+fn settle_jobs(mountpoints: &[&Path]) -> Result<()> {
+    let mut jobs: Vec<lsusb::jobs::Job> = mountpoints
+        .iter()
+        .flat_map(|m| lsusb::jobs::using(m))
+        .collect();
+    jobs.dedup_by_key(|job| job.id);
+    if jobs.is_empty() {
+        return Ok(());
+    }
+    for job in &jobs {
+        println!(
+            "{}",
+            tr!(
+                "job-running",
+                kind = job.kind.to_string(),
+                job = job.to_string()
+            )
+        );
+    }
+    let choice = dialoguer::Select::new()
+        .with_prompt(tr!("job-prompt"))
+        .items(&[tr!("job-wait"), tr!("job-cancel"), tr!("job-leave")])
+        .default(0)
+        .interact();
+    match choice {
+        Ok(0) => println!("{}", tr!("job-waiting")),
+        Ok(1) => {
+            for mountpoint in mountpoints {
+                lsusb::jobs::cancel(mountpoint);
+            }
+        }
+        Ok(_) => anyhow::bail!("{}", tr!("nothing-changed")),
+        // No terminal to ask on.
+        Err(_) => {
+            for mountpoint in mountpoints {
+                lsusb::jobs::ensure_idle(mountpoint)?;
+            }
+        }
+    }
+    for mountpoint in mountpoints {
+        lsusb::jobs::wait(mountpoint, None);
+    }
+    Ok(())
+}
+
+/// Draws a transfer's [`ProgressEvent`]s as a byte progress bar on `pb`.
+fn render_transfer(pb: &ProgressBar, event: ProgressEvent) {
+    match event {
+        ProgressEvent::Phase(Phase::Scanning) => println!("{}", tr!("calculating-size")),
+        ProgressEvent::Totals { bytes, .. } => {
+            println!("{}", tr!("total-size", bytes = bytes));
+            start_bar(pb, bytes);
+        }
+        ProgressEvent::Bytes { done, .. } => {
+            report_percent(pb, &tr!("progress-copying"), done);
+            pb.set_position(done);
+        }
+        ProgressEvent::Phase(Phase::Done) if plain() => eprintln!("{}", tr!("copy-complete")),
+        ProgressEvent::Phase(Phase::Done) => pb.finish_with_message(tr!("copy-complete")),
+        ProgressEvent::Skipped { path, reason } => {
+            let line = tr!(
+                "skipped-because",
+                path = path.display().to_string(),
+                reason = reason
+            );
+            if plain() {
+                eprintln!("{}", line)
+            } else {
+                pb.println(line)
+            }
+        }
+        _ => {}
+    }
+}
+
+/// In plain mode, where `pb` stays hidden, prints `what: 40%` each time
+/// moving it to `done` passes another tenth of its length.
+fn report_percent(pb: &ProgressBar, what: &str, done: u64) {
+    let total = pb.length().unwrap_or(0);
+    if !plain() || total == 0 {
+        return;
+    }
+    let tenth = |bytes: u64| (bytes.min(total) as u128 * 10 / total as u128) as u64;
+    if tenth(done) > tenth(pb.position()) {
+        eprintln!("{}: {}%", what, tenth(done) * 10);
+    }
+}
+
+fn start_bar(pb: &ProgressBar, bytes: u64) {
+    pb.set_length(bytes);
+    if plain() {
+        return;
+    }
+    pb.set_style(ProgressStyle::default_bar()
+        .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta}) {msg}")
+        .unwrap()
+        .progress_chars("#>-"));
+    pb.set_draw_target(indicatif::ProgressDrawTarget::stderr());
+}
+
+/// The last 20 seconds of a transfer's rate history, as a sparkline;
+/// `-` when plain, as screen readers spell the blocks out.
+fn speed_graph(history: &[u64]) -> String {
+    if plain() {
+        return "-".to_string();
+    }
+    lsusb::progress::sparkline(&history[history.len().saturating_sub(20)..])
+}
+
+/// [`render_transfer`], leaving stdout to the JSON summary when `json`;
+/// or, with `--progress json`, the events as JSON lines instead.
+fn render_progress(
+    pb: &ProgressBar,
+    json: bool,
+    settings: Settings,
+) -> Box<dyn FnMut(ProgressEvent) + '_> {
+    if settings.progress == ProgressFormat::Json {
+        let mut lines = lsusb::progress::JsonLines::new(progress_out(settings.progress_fd));
+        return Box::new(move |event| lines.emit(event));
+    }
+    Box::new(move |event| match event {
+        ProgressEvent::Phase(Phase::Scanning) if json => {}
+        ProgressEvent::Totals { bytes, .. } if json => start_bar(pb, bytes),
+        event => render_transfer(pb, event),
+    })
+}
+
+/// Where `--progress json` lines go: stdout, or a duplicate of
+/// `--progress-fd`, so that dropping it leaves the caller's descriptor open.
+fn progress_out(fd: Option<i32>) -> Box<dyn std::io::Write> {
+    use std::os::fd::FromRawFd;
+
+    let Some(fd) = fd else {
+        return Box::new(std::io::stdout());
+    };
+    match unsafe { libc::dup(fd) } {
+        // Checked at startup; closed since by someone else.
+        -1 => Box::new(std::io::sink()),
+        dup => Box::new(unsafe { fs::File::from_raw_fd(dup) }),
+    }
+}
+
+/// Prints the end-of-transfer summary, as one JSON object when `json`.
+fn print_summary(summary: &Summary, units: SizeUnits, json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string(summary)?);
+        return Ok(());
+    }
+    let verified = match summary.verification {
+        Verification::Skipped => tr!("verification-skipped"),
+        Verification::Passed => tr!("verification-passed"),
+        Verification::Failed => tr!("verification-failed"),
+    };
+    print_fields(
+        "",
+        10,
+        &[
+            (
+                tr!("summary-files"),
+                tr!(
+                    "summary-files-value",
+                    copied = summary.files_copied,
+                    skipped = summary.files_skipped,
+                    failed = summary.files_failed
+                ),
+            ),
+            (
+                tr!("summary-written"),
+                tr!(
+                    "summary-written-value",
+                    size = units.format(summary.bytes_written),
+                    secs = format!("{:.1}", summary.elapsed_secs)
+                ),
+            ),
+            (
+                tr!("summary-speed"),
+                tr!(
+                    "summary-speed-value",
+                    average = units.format(summary.average_rate),
+                    peak = units.format(summary.peak_rate)
+                ),
+            ),
+            (tr!("summary-verified"), verified),
+        ],
+    );
+    Ok(())
+}
+
+/// Copies with `options` (scanner, encryption, ignore rules); cancelling
+/// is wired to Ctrl+C here. Returns where the copy ended up, unless that
+/// is on a partition mounted just for the copy.
+pub fn copy_to_usb(
+    backend: &dyn SystemBackend,
+    source: &Path,
+    dest: &Path,
+    settings: Settings,
+    policy: &Policy,
+    options: lsusb::TransferOptions,
+    json: bool,
+) -> Result<Option<PathBuf>> {
+    settings.ensure_writable("copy")?;
+    if let Some((partition, mountpoint, inside)) = partition_dest(backend, dest)? {
+        if let Some(mountpoint) = mountpoint {
+            return copy_to_usb(
+                backend,
+                source,
+                &Path::new(&mountpoint).join(inside),
+                settings,
+                policy,
+                options,
+                json,
+            );
+        }
+        policy.check(&partition, Access::Allow)?;
+        let device = backend
+            .enumerate()?
+            .into_iter()
+            .find(|d| d.partitions.iter().any(|p| p.name == partition))
+            .map(|d| d.name)
+            .unwrap_or_else(|| partition.clone());
+        let note = tr!("mounting-for-copy", partition = partition.as_str());
+        if json {
+            eprintln!("{}", note);
+        } else {
+            println!("{}", note);
+        }
+        let copied = lsusb::backend::with_mounted(
+            backend,
+            &partition,
+            None,
+            MountOptions::default(),
+            |root| {
+                let copied = copy_to_usb(
+                    backend,
+                    source,
+                    &root.join(&inside),
+                    settings,
+                    policy,
+                    options,
+                    json,
+                );
+                if copied.is_ok() {
+                    backend.flush(&device)?;
+                }
+                Ok(copied)
+            },
+        )?;
+        // Unmounted again, so there is no copy left to point at.
+        return copied.map(|_| None);
+    }
+    // Where the copy ends up, as `cp` decides it: inside `dest` if that is
+    // a directory already.
+    let mut copy = match source.file_name() {
+        Some(name) if dest.is_dir() => dest.join(name),
+        _ => dest.to_path_buf(),
+    };
+    // A file copied on its own is renamed by the cipher too.
+    if let Some(target) = options
+        .crypt
+        .as_ref()
+        .filter(|_| source.is_file())
+        .and_then(|crypt| crypt.target(&copy))
+    {
+        copy = target;
+    }
+    let dest = &expand_dest(backend, dest, json)?;
+    policy.check_path(dest, Access::Allow)?;
+    let _lock = lock_path(
+        backend,
+        dest,
+        &format!("copy to {}", dest.display()),
+        settings,
+    )?;
+    let pb = ProgressBar::hidden();
+    let mut meter = Throughput::default();
+    let interrupt = crate::interrupt::guard();
+    // Questions go above the bar, not through it.
+    let on_existing = options.on_existing.clone().map(|prompt| {
+        let pb = pb.clone();
+        lsusb::transfer::ConflictPrompt::new(move |source, existing| {
+            pb.suspend(|| prompt.ask(source, existing))
+        })
+    });
+    let options = lsusb::TransferOptions {
+        cancel: interrupt.token(),
+        on_existing,
+        ..options
+    };
+    // Redrawing per chunk costs real throughput; 10 Hz looks smooth.
+    let mut render = Throttled::new(
+        render_progress(&pb, json, settings),
+        Duration::from_millis(100),
+    );
+    let result = lsusb::copy_with_progress(source, dest, &options, |event: ProgressEvent| {
+        meter.record(&event);
+        if let ProgressEvent::Bytes { .. } = event {
+            pb.set_message(speed_graph(&meter.history()));
+        }
+        render.emit(event);
+    });
+    if result.is_err() {
+        pb.abandon();
+    }
+    let summary = meter.summary("cp", result.as_ref().err().map(|e| e.to_string()));
+    print_summary(&summary, settings.units, json)?;
+    if let Ok(Some(device)) = device_of(backend, dest)
+        && let Ok(device) = backend.find(&device)
+    {
+        record_write(&device, summary.bytes_written);
+    }
+    let report = result?;
+    report_dropped(&report.dropped);
+    report_collisions(&report.collisions);
+    if !report.skipped.is_empty() {
+        eprintln!("{}", tr!("left-alone", count = report.skipped.len()));
+    }
+    if !report.infected.is_empty() {
+        eprintln!("{}", tr!("scanner-flagged"));
+        for (path, signature) in &report.infected {
+            eprintln!("  {}: {}", path.display(), signature);
+        }
+        anyhow::bail!("{} infected file(s) were left out", report.infected.len());
+    }
+    Ok(Some(copy))
+}
+
+/// Writes the manifest of the copy at `copy` and signs it with the secret
+/// key at `key`, for `cp --sign`.
+pub fn sign_copy(copy: &Path, key: &Path, json: bool) -> Result<()> {
+    let interrupt = crate::interrupt::guard();
+    let manifest = lsusb::manifest::write(copy, &interrupt.token())?;
+    lsusb::sign::Minisign::default().sign(&manifest, key)?;
+    let line = tr!("manifest-signed", manifest = manifest.display().to_string());
+    if json {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// `cp --to A --to B`: copies `source` to every destination at once.
+pub fn copy_mirrored(
+    backend: &dyn SystemBackend,
+    source: &Path,
+    dests: &[PathBuf],
+    settings: Settings,
+    policy: &Policy,
+    options: lsusb::TransferOptions,
+    json: bool,
+) -> Result<()> {
+    settings.ensure_writable("copy")?;
+    let dests = &dests
+        .iter()
+        .map(|dest| expand_dest(backend, dest, json))
+        .collect::<Result<Vec<_>>>()?;
+    let mut locks = Vec::new();
+    let mut devices = Vec::new();
+    for dest in dests {
+        policy.check_path(dest, Access::Allow)?;
+        if let Some(device) = device_of(backend, dest)?
+            && !devices.contains(&device)
+        {
+            locks.push(lock_device(
+                &device,
+                &format!("copy to {}", dest.display()),
+                settings,
+            )?);
+            devices.push(device);
+        }
+    }
+    let pb = ProgressBar::hidden();
+    let mut meter = Throughput::default();
+    let interrupt = crate::interrupt::guard();
+    let options = lsusb::TransferOptions {
+        cancel: interrupt.token(),
+        ..options
+    };
+    let mut render = Throttled::new(
+        render_progress(&pb, json, settings),
+        Duration::from_millis(100),
+    );
+    let result = lsusb::copy_mirrored(source, dests, &options, |event: ProgressEvent| {
+        meter.record(&event);
+        if let ProgressEvent::Bytes { .. } = event {
+            pb.set_message(speed_graph(&meter.history()));
+        }
+        render.emit(event);
+    });
+    if result.is_err() {
+        pb.abandon();
+    }
+    let summary = meter.summary("cp", result.as_ref().err().map(|e| e.to_string()));
+    print_summary(&summary, settings.units, json)?;
+    for device in &devices {
+        if let Ok(device) = backend.find(device) {
+            record_write(&device, summary.bytes_written);
+        }
+    }
+    let report = result?;
+    report_dropped(&report.dropped);
+    report_collisions(&report.collisions);
+    if !report.infected.is_empty() {
+        eprintln!("{}", tr!("scanner-flagged"));
+        for (path, signature) in &report.infected {
+            eprintln!("  {}: {}", path.display(), signature);
+        }
+        anyhow::bail!("{} infected file(s) were left out", report.infected.len());
+    }
+    Ok(())
+}
+
+/// `cp --span`: copies into `dest` until its drive is full, then carries on
+/// on the next attached USB partition with room, or asks for one to be
+/// plugged in.
+pub fn copy_spanning(
+    backend: &dyn SystemBackend,
+    source: &Path,
+    dest: &Path,
+    settings: Settings,
+    policy: &Policy,
+    options: lsusb::TransferOptions,
+    json: bool,
+) -> Result<()> {
+    settings.ensure_writable("copy")?;
+    let templated = lsusb::template::is_template(dest);
+    let dest = &expand_dest(backend, dest, json)?;
+    policy.check_path(dest, Access::Allow)?;
+    // The copy needs the directory to exist; nobody could have made a dated one.
+    if templated {
+        fs::create_dir_all(dest).with_context(|| format!("Failed to create {}", dest.display()))?;
+    }
+    let mut used: Vec<String> = device_of(backend, dest)?.into_iter().collect();
+    let mut locks = Vec::new();
+    locks.extend(lock_path(
+        backend,
+        dest,
+        &format!("copy to {}", dest.display()),
+        settings,
+    )?);
+    let pb = ProgressBar::hidden();
+    let mut meter = Throughput::default();
+    let interrupt = crate::interrupt::guard();
+    let options = lsusb::TransferOptions {
+        cancel: interrupt.token(),
+        ..options
+    };
+    let mut render = Throttled::new(
+        render_progress(&pb, json, settings),
+        Duration::from_millis(100),
+    );
+    let result = lsusb::copy_spanning(
+        source,
+        dest,
+        &options,
+        |manifest| {
+            pb.suspend(|| next_span_drive(backend, manifest, &mut used, &mut locks, policy, json))
+        },
+        |event: ProgressEvent| {
+            meter.record(&event);
+            if let ProgressEvent::Bytes { .. } = event {
+                pb.set_message(speed_graph(&meter.history()));
+            }
+            render.emit(event);
+        },
+    );
+    if result.is_err() {
+        pb.abandon();
+    }
+    let summary = meter.summary("cp", result.as_ref().err().map(|e| e.to_string()));
+    print_summary(&summary, settings.units, json)?;
+    let report = result?;
+    report_dropped(&report.transfer.dropped);
+    report_collisions(&report.transfer.collisions);
+    for volume in &report.manifest.volumes {
+        let line = tr!(
+            "span-volume",
+            number = volume.number,
+            files = volume.files.len(),
+            size = settings.units.format(volume.bytes),
+            dest = volume.dest.display().to_string()
+        );
+        if json {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+        if let Ok(Some(device)) = device_of(backend, &volume.dest)
+            && let Ok(device) = backend.find(&device)
+        {
+            record_write(&device, volume.bytes);
+        }
+    }
+    if !report.transfer.infected.is_empty() {
+        eprintln!("{}", tr!("scanner-flagged"));
+        for (path, signature) in &report.transfer.infected {
+            eprintln!("  {}: {}", path.display(), signature);
+        }
+        anyhow::bail!(
+            "{} infected file(s) were left out",
+            report.transfer.infected.len()
+        );
+    }
+    Ok(())
+}
+
+/// Where a spanning copy goes on once the drive it was filling is full: the
+/// mounted, writable USB partition with the most room on a drive it hasn't
+/// used yet; with none, asks for one to be plugged in. `None` gives up.
+fn next_span_drive(
+    backend: &dyn SystemBackend,
+    manifest: &lsusb::SpanManifest,
+    used: &mut Vec<String>,
+    locks: &mut Vec<DeviceLock>,
+    policy: &Policy,
+    json: bool,
+) -> lsusb::Result<Option<PathBuf>> {
+    let note = |text: String| {
+        if json {
+            eprintln!("{}", text)
+        } else {
+            println!("{}", text)
+        }
+    };
+    if let Some(full) = manifest.volumes.last() {
+        note(tr!("span-full", dest = full.dest.display().to_string()));
+    }
+    loop {
+        let mut candidates = backend
+            .enumerate()?
+            .into_iter()
+            .filter(|d| !used.contains(&d.name))
+            .flat_map(|d| {
+                let device = d.name;
+                d.partitions.into_iter().filter_map(move |p| {
+                    Some((device.clone(), p.name, PathBuf::from(p.mountpoint?)))
+                })
+            })
+            .filter(|(_, partition, mountpoint)| {
+                policy.check(partition, Access::Allow).is_ok()
+                    && lsusb::transfer::ensure_writable(mountpoint).is_ok()
+            })
+            .filter_map(|(device, partition, mountpoint)| {
+                let available = lsusb::usage(&mountpoint).ok()?.available;
+                (available > 0).then_some((available, device, partition, mountpoint))
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(available, ..)| std::cmp::Reverse(*available));
+        for (_, device, partition, mountpoint) in candidates {
+            // A drive another lsusb is writing to isn't one to fill.
+            let lock = match Locks::default()
+                .acquire(&device, &format!("copy to {}", mountpoint.display()))
+            {
+                Err(lsusb::UsbError::DeviceLocked { .. }) => continue,
+                lock => lock?,
+            };
+            locks.push(lock);
+            used.push(device);
+            note(tr!(
+                "span-continuing",
+                partition = partition,
+                mountpoint = mountpoint.display().to_string()
+            ));
+            return Ok(Some(mountpoint));
+        }
+        if !std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+            return Ok(None);
+        }
+        let go_on = dialoguer::Confirm::new()
+            .with_prompt(tr!("span-next-drive"))
+            .default(true)
+            .interact()
+            .unwrap_or(false);
+        if !go_on {
+            return Ok(None);
+        }
+    }
+}
+
+/// Lists the source files whose names the destination couldn't tell apart.
+fn report_collisions(collisions: &[lsusb::transfer::Collision]) {
+    if collisions.is_empty() {
+        return;
+    }
+    eprintln!("{}", tr!("collisions"));
+    for collision in collisions {
+        let outcome = match &collision.written_to {
+            None => tr!("collision-skipped"),
+            Some(path) => tr!("collision-copied-as", path = path.display().to_string()),
+        };
+        eprintln!(
+            "  {}",
+            tr!(
+                "collision",
+                source = collision.source.display().to_string(),
+                other = collision.clashes_with.display().to_string(),
+                outcome = outcome
+            )
+        );
+    }
+}
+
+/// Lists what `--preserve` couldn't carry over.
+fn report_dropped(dropped: &[(PathBuf, String)]) {
+    const SHOWN: usize = 10;
+    if dropped.is_empty() {
+        return;
+    }
+    eprintln!("{}", tr!("attributes-dropped", count = dropped.len()));
+    for (path, name) in dropped.iter().take(SHOWN) {
+        eprintln!("  {}: {}", path.display(), name);
+    }
+    if dropped.len() > SHOWN {
+        eprintln!("  {}", tr!("and-more", count = dropped.len() - SHOWN));
+    }
+}
+
+/// A `cp` destination naming a partition, `sdb1` or `sdb1:DIR`, rather
+/// than a path: the partition, where it is mounted if it is, and the path
+/// inside it. Paths that exist win, so a file called `sdb1` is still one.
+fn partition_dest(
+    backend: &dyn SystemBackend,
+    dest: &Path,
+) -> Result<Option<(String, Option<String>, PathBuf)>> {
+    let Some(text) = dest.to_str().filter(|_| !dest.exists()) else {
+        return Ok(None);
+    };
+    let (name, inside) = text.split_once(':').unwrap_or((text, ""));
+    if name.is_empty() || name.contains('/') {
+        return Ok(None);
+    }
+    let devices = backend.enumerate()?;
+    let Some(partition) = devices
+        .iter()
+        .flat_map(|d| &d.partitions)
+        .find(|p| p.name == name)
+    else {
+        return Ok(None);
+    };
+    Ok(Some((
+        partition.name.clone(),
+        partition.mountpoint.clone(),
+        PathBuf::from(inside.trim_start_matches('/')),
+    )))
+}
+
+pub fn verify(path: &Path, public_key: Option<&Path>) -> Result<()> {
+    if let Some(key) = public_key {
+        let manifest = lsusb::manifest::manifest_path(path);
+        lsusb::sign::Minisign::default().verify(&manifest, key)?;
+        println!(
+            "{}",
+            tr!("signature-good", manifest = manifest.display().to_string())
+        );
+    }
+    let interrupt = crate::interrupt::guard();
+    let report = lsusb::manifest::verify(path, &interrupt.token())?;
+    println!(
+        "{}",
+        tr!(
+            "manifest-checked",
+            files = report.files_checked,
+            bytes = report.bytes_checked
+        )
+    );
+    for file in &report.corrupt {
+        eprintln!(
+            "  {}",
+            tr!("file-corrupt", path = file.display().to_string())
+        );
+    }
+    for file in &report.missing {
+        eprintln!(
+            "  {}",
+            tr!("file-missing", path = file.display().to_string())
+        );
+    }
+    match report.corrupt.first().or(report.missing.first()) {
+        Some(file) => Err(lsusb::UsbError::VerificationFailed { path: file.clone() }.into()),
+        None => Ok(()),
+    }
+}
+
+pub fn remove(
+    backend: &dyn SystemBackend,
+    paths: &[PathBuf],
+    secure: bool,
+    force: bool,
+    log: &AuditLog,
+    policy: &Policy,
+    settings: Settings,
+) -> Result<()> {
+    settings.ensure_writable("delete")?;
+    let mut devices = Vec::new();
+    for path in paths {
+        match device_of(backend, path)? {
+            Some(device) if !devices.contains(&device) => devices.push(device),
+            Some(_) => {}
+            None if force => {}
+            None => anyhow::bail!("{}", tr!("not-on-usb", path = path.display().to_string())),
+        }
+    }
+    let _locks = devices
+        .iter()
+        .map(|device| lock_device(device, "rm", settings))
+        .collect::<Result<Vec<_>>>()?;
+    if secure {
+        eprintln!("{}", tr!("warning", message = tr!("wear-leveling")));
+    }
+    let interrupt = crate::interrupt::guard();
+    for path in paths {
+        policy.check_path(path, Access::Allow)?;
+        let mut params = vec![path.display().to_string()];
+        if secure {
+            params.insert(0, "--secure".into());
+        }
+        log.record(&AuditEntry::for_path("rm", path, params))?;
+        if !secure {
+            lsusb::transfer::ensure_writable(path)?;
+            let result = if fs::symlink_metadata(path).is_ok_and(|m| m.is_dir()) {
+                fs::remove_dir_all(path)
+            } else {
+                fs::remove_file(path)
+            };
+            result.with_context(|| format!("Failed to remove {}", path.display()))?;
+            println!("{}", tr!("removed", name = path.display().to_string()));
+            continue;
+        }
+        println!("{}", tr!("overwriting", path = path.display().to_string()));
+        let report = lsusb::erase::secure_delete(path, &interrupt.token())?;
+        println!(
+            "{}",
+            tr!("overwrote", files = report.files, bytes = report.bytes)
+        );
+        if report.shared > 0 {
+            println!("{}", tr!("shared-unlinked", count = report.shared));
+        }
+        if !report.trimmed {
+            // FITRIM needs root. rm is never retried as root, which would
+            // delete what the user isn't allowed to, so point at trim.
+            match mount_of(backend, path)? {
+                Some((_, mountpoint)) if !lsusb::privilege::is_root() => {
+                    println!("{}", tr!("not-trimmed-root", mountpoint = mountpoint))
+                }
+                _ => println!("{}", tr!("not-trimmed")),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The USB device `path` is on, if it is on one.
+fn device_of(backend: &dyn SystemBackend, path: &Path) -> Result<Option<String>> {
+    Ok(mount_of(backend, path)?.map(|(device, _)| device))
+}
+
+/// The USB device `path` is on and where the partition holding it is
+/// mounted, if it is on one.
+fn mount_of(backend: &dyn SystemBackend, path: &Path) -> Result<Option<(String, String)>> {
+    let Ok(path) = std::path::absolute(path) else {
+        return Ok(None);
+    };
+    // Symlinks above `path` are followed, so a link on the stick pointing
+    // into $HOME doesn't pass for the stick; `path` itself is what gets
+    // removed, so a final symlink is left as it is.
+    let path = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => resolve(parent).join(name),
+        _ => resolve(&path),
+    };
+    let devices = backend.enumerate()?;
+    let mount = devices
+        .iter()
+        .flat_map(|d| {
+            d.partitions
+                .iter()
+                .filter_map(move |p| Some((d, p.mountpoint.as_deref()?)))
+        })
+        .map(|(device, mountpoint)| (device, mountpoint, resolve(Path::new(mountpoint))))
+        .filter(|(.., resolved)| path.starts_with(resolved))
+        .max_by_key(|(.., resolved)| resolved.as_os_str().len());
+    Ok(mount.map(|(device, mountpoint, _)| (device.name.clone(), mountpoint.to_string())))
+}
+
+/// `path` with symlinks resolved in its longest existing ancestor.
+fn resolve(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find_map(|ancestor| {
+            let real = fs::canonicalize(ancestor).ok()?;
+            Some(real.join(path.strip_prefix(ancestor).ok()?))
+        })
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// How often the daemon looks for newly attached disks.
+const DAEMON_POLL: Duration = Duration::from_secs(2);
+
+fn backup_profile<'a>(config: &'a lsusb::Config, name: &str) -> Result<&'a lsusb::backup::Profile> {
+    config.backup.get(name).with_context(|| {
+        let names: Vec<&str> = config.backup.keys().map(String::as_str).collect();
+        if names.is_empty() {
+            tr!("no-backup-profile-none", profile = name)
+        } else {
+            tr!(
+                "no-backup-profile",
+                profile = name,
+                profiles = names.join(", ")
+            )
+        }
+    })
+}
+
+/// Runs `f` with profile `name` and its directory on the stick, mounting
+/// the stick with `options` if it isn't mounted already.
+fn with_backup<T>(
+    backend: &dyn SystemBackend,
+    config: &lsusb::Config,
+    settings: Settings,
+    name: &str,
+    options: MountOptions,
+    f: impl FnOnce(&lsusb::backup::Profile, &Path) -> lsusb::Result<T>,
+) -> Result<T> {
+    let profile = backup_profile(config, name)?;
+    let devices = backend.enumerate()?;
+    let (device, partition) = lsusb::backup::find_target(&devices, &profile.target, usb_serial)?;
+    let wanted = if options.read_only {
+        Access::ReadOnly
+    } else {
+        Access::Allow
+    };
+    config.policy.check(&partition.name, wanted)?;
+    let _lock = match options.read_only {
+        true => None,
+        false => Some(lock_device(
+            &device.name,
+            &format!("backup {}", name),
+            settings,
+        )?),
+    };
+    let result = lsusb::backend::with_mounted(
+        backend,
+        &partition.name,
+        partition.mountpoint.as_deref(),
+        options,
+        |root| {
+            f(
+                profile,
+                &profile.dest_dir(name, root, &drive_vars(device, partition))?,
+            )
+        },
+    )?;
+    Ok(result)
+}
+
+/// Takes `device`'s lock for `operation`, so another lsusb process can't
+/// write to it at the same time; with `--wait`, waits for one that is.
+fn lock_device(device: &str, operation: &str, settings: Settings) -> Result<DeviceLock> {
+    let locks = Locks::default();
+    match locks.acquire(device, operation) {
+        Err(lsusb::UsbError::DeviceLocked { holder, .. }) if settings.wait => {
+            println!(
+                "{}",
+                tr!(
+                    "waiting-for-lock",
+                    holder = holder.to_string(),
+                    device = device
+                )
+            );
+            Ok(locks.wait(device, operation, None)?)
+        }
+        result => Ok(result.context("pass --wait to wait until it is done")?),
+    }
+}
+
+/// [`lock_device`] for the USB device `path` is on, if it is on one.
+fn lock_path(
+    backend: &dyn SystemBackend,
+    path: &Path,
+    operation: &str,
+    settings: Settings,
+) -> Result<Option<DeviceLock>> {
+    match device_of(backend, path)? {
+        Some(device) => Ok(Some(lock_device(&device, operation, settings)?)),
+        None => Ok(None),
+    }
+}
+
+/// Says what a sampled verify checks, and how to check the same again.
+fn print_sample(sample: Option<lsusb::backup::Sample>) {
+    if let Some(sample) = sample {
+        println!(
+            "{}",
+            tr!(
+                "checking-sample",
+                amount = sample.amount.to_string(),
+                seed = sample.seed.to_string()
+            )
+        );
+    }
+}
+
+/// `PROFILE/SNAPSHOT` or `PROFILE`.
+fn split_backup(backup: &str) -> (&str, Option<&str>) {
+    match backup.split_once('/') {
+        Some((name, snapshot)) => (name, Some(snapshot)),
+        None => (backup, None),
+    }
+}
+
+fn signed_size(units: SizeUnits, delta: i64) -> String {
+    format!(
+        "{}{}",
+        if delta < 0 { "-" } else { "+" },
+        units.format(delta.unsigned_abs())
+    )
+}
+
+fn stats_db() -> Option<lsusb::stats::StatsDb> {
+    lsusb::stats::StatsDb::default_path().map(lsusb::stats::StatsDb::new)
+}
+
+fn model_of(device: &lsusb::UsbDevice) -> String {
+    let model: Vec<&str> = [device.vendor.as_deref(), device.model.as_deref()]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .collect();
+    model.join(" ")
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Adds a session that wrote `bytes` to the lifetime statistics of
+/// `device`. Best effort: a copy isn't any worse for not being counted.
+fn record_write(device: &lsusb::UsbDevice, bytes: u64) {
+    if let (Some(db), Some(serial)) = (stats_db(), usb_serial(&device.name)) {
+        let _ = db.wrote(&serial, &model_of(device), bytes, now_secs());
+    }
+}
+
+pub fn stats(backend: &dyn SystemBackend, device_name: &str, units: SizeUnits) -> Result<()> {
+    let device = backend.find(device_name)?;
+    let serial = usb_serial(device_name).with_context(|| {
+        format!(
+            "{} reports no USB serial number, so its history can't be told apart",
+            device_name
+        )
+    })?;
+    let db = stats_db()
+        .context("no location for the statistics database; set HOME or XDG_STATE_HOME")?;
+    db.seen(&serial, &model_of(&device), now_secs())?;
+    let stats = db.get(&serial)?.unwrap_or_default();
+    println!(
+        "{}",
+        tr!(
+            "stats-of",
+            device = describe(&device, units),
+            serial = serial.as_str()
+        )
+    );
+    let mut fields = vec![(
+        tr!("summary-written"),
+        tr!(
+            "stats-written",
+            size = units.format(stats.bytes_written),
+            sessions = stats.sessions
+        ),
+    )];
+    if device.bytes > 0 && stats.bytes_written > 0 {
+        // Full-capacity write cycles, the unit flash endurance is rated in.
+        fields.push((
+            tr!("stats-fills"),
+            format!("{:.1}", stats.bytes_written as f64 / device.bytes as f64),
+        ));
+    }
+    // Drives without SMART support just use lsusb's count.
+    let report = Smart::default().read(device_name).ok();
+    if let Some(wear) =
+        lsusb::wear::estimate(report.as_ref(), Some(stats.bytes_written), device.bytes)
+    {
+        fields.extend(wear_lines(&wear, units));
+    }
+    fields.push((
+        tr!("stats-first-seen"),
+        tr!("utc", time = stats.first_seen_utc()),
+    ));
+    fields.push((
+        tr!("stats-last-seen"),
+        tr!("utc", time = stats.last_seen_utc()),
+    ));
+    if stats.models.len() > 1 {
+        fields.push((
+            tr!("stats-reported-as"),
+            tr!("stats-models", models = stats.models.join("; ")),
+        ));
+    }
+    print_fields("  ", 13, &fields);
+    Ok(())
+}
+
+/// `dest` with its template variables (see [`lsusb::template`]) filled in
+/// for the USB partition it is on, saying where that is when it changed.
+fn expand_dest(backend: &dyn SystemBackend, dest: &Path, json: bool) -> Result<PathBuf> {
+    if !lsusb::template::is_template(dest) {
+        return Ok(dest.to_path_buf());
+    }
+    let path = std::path::absolute(dest).unwrap_or_else(|_| dest.to_path_buf());
+    let devices = backend.enumerate()?;
+    let vars = devices
+        .iter()
+        .flat_map(|d| {
+            d.partitions
+                .iter()
+                .filter_map(move |p| Some((d, p, Path::new(p.mountpoint.as_deref()?))))
+        })
+        .filter(|(.., mountpoint)| path.starts_with(mountpoint))
+        .max_by_key(|(.., mountpoint)| mountpoint.as_os_str().len())
+        .map_or_else(lsusb::template::Vars::now, |(device, partition, _)| {
+            drive_vars(device, partition)
+        });
+    let expanded = lsusb::template::expand(dest, &vars)?;
+    let note = tr!("copying-to", dest = expanded.display().to_string());
+    if json {
+        eprintln!("{}", note);
+    } else {
+        println!("{}", note);
+    }
+    Ok(expanded)
+}
+
+/// Template variables for a destination on `partition` of `device`.
+fn drive_vars(device: &lsusb::UsbDevice, partition: &lsusb::Partition) -> lsusb::template::Vars {
+    lsusb::template::Vars::for_drive(partition.label.clone(), usb_serial(&device.name))
+}
+
+/// The USB serial number of `device_name`, for matching backup targets.
+fn usb_serial(device_name: &str) -> Option<String> {
+    lsusb::policy::DeviceIdentity::lookup(device_name).and_then(|id| id.serial)
+}
+
+pub fn backup(
+    backend: &dyn SystemBackend,
+    action: BackupAction,
+    config: &lsusb::Config,
+    settings: Settings,
+) -> Result<()> {
+    match action {
+        BackupAction::List => {
+            if config.backup.is_empty() {
+                println!("{}", tr!("backup-no-profiles"));
+            }
+            let devices = backend.enumerate()?;
+            for (name, profile) in &config.backup {
+                let status = match lsusb::backup::find_target(&devices, &profile.target, usb_serial)
+                {
+                    Ok((_, partition)) => {
+                        tr!("backup-ready-on", partition = partition.name.as_str())
+                    }
+                    Err(e) => e.to_string(),
+                };
+                println!("{} {}", pad(name, 20), status);
+            }
+            if !devices.is_empty() {
+                println!();
+                println!(
+                    "{} {} {}",
+                    pad(&tr!("column-device"), 10),
+                    pad(&tr!("column-serial"), 24),
+                    tr!("column-labels")
+                );
+                for device in &devices {
+                    let labels: Vec<&str> = device
+                        .partitions
+                        .iter()
+                        .filter_map(|p| p.label.as_deref())
+                        .collect();
+                    println!(
+                        "{} {} {}",
+                        pad(&device.name, 10),
+                        pad(usb_serial(&device.name).as_deref().unwrap_or("-"), 24),
+                        if labels.is_empty() {
+                            "-".to_string()
+                        } else {
+                            labels.join(", ")
+                        }
+                    );
+                }
+            }
+        }
+        BackupAction::Install {
+            profile: name,
+            on_plug,
+            ..
+        } => {
+            use lsusb::schedule::{self, Scheduler, Trigger};
+
+            let profile = backup_profile(config, &name)?;
+            let trigger = if on_plug {
+                Trigger::OnPlug
+            } else {
+                Trigger::Daily
+            };
+            let exe = std::env::current_exe().context("Failed to find the lsusb binary")?;
+            let units = schedule::units(&name, profile, &exe, trigger)?;
+            let installed = Scheduler::default().install(&units)?;
+            for file in &installed.files {
+                println!("{}", tr!("wrote", file = file.display().to_string()));
+            }
+            match (&installed.rule_pending, &units.timer) {
+                (Some(rule), _) => {
+                    println!("{}", tr!("udev-rule-needs-root"));
+                    println!(
+                        "  sudo cp {} /etc/udev/rules.d/ && sudo udevadm control --reload",
+                        rule.display()
+                    );
+                }
+                (None, Some(timer)) => {
+                    println!("{}", tr!("timer-enabled", timer = timer.name.as_str()))
+                }
+                (None, None) => println!("{}", tr!("runs-on-plug", profile = name.as_str())),
+            }
+        }
+        BackupAction::Run {
+            profile: name,
+            json,
+            sign,
+        } => {
+            let json = json || settings.json_progress_on_stdout();
+            settings.ensure_writable("back up")?;
+            let mut profile = backup_profile(config, &name)?.clone();
+            if sign.is_some() {
+                profile.sign = sign;
+            }
+            let profile = &profile;
+            let devices = backend.enumerate()?;
+            let (device, partition) =
+                lsusb::backup::find_target(&devices, &profile.target, usb_serial)?;
+            config.policy.check(&partition.name, Access::Allow)?;
+            let _lock = lock_device(&device.name, &format!("backup {}", name), settings)?;
+            let starting = tr!(
+                "backing-up",
+                profile = name.as_str(),
+                partition = partition.name.as_str(),
+                device = device.name.as_str()
+            );
+            if json {
+                eprintln!("{}", starting);
+            } else {
+                println!("{}", starting);
+            }
+            let interrupt = crate::interrupt::guard();
+            let options = lsusb::TransferOptions {
+                cancel: interrupt.token(),
+                ignore: config.ignore(),
+                ..Default::default()
+            };
+            let pb = ProgressBar::hidden();
+            let mut meter = Throughput::default();
+            let mut render = Throttled::new(
+                render_progress(&pb, json, settings),
+                Duration::from_millis(100),
+            );
+            let result = lsusb::backend::with_mounted(
+                backend,
+                &partition.name,
+                partition.mountpoint.as_deref(),
+                MountOptions::default(),
+                |root| {
+                    let dest = profile.dest_dir(&name, root, &drive_vars(device, partition))?;
+                    lsusb::backup::run(profile, &dest, &options, |event: ProgressEvent| {
+                        meter.record(&event);
+                        if let ProgressEvent::Bytes { .. } = event {
+                            pb.set_message(speed_graph(&meter.history()));
+                        }
+                        render.emit(event);
+                    })
+                },
+            );
+            if result.is_err() {
+                pb.abandon();
+            }
+            let mut summary = meter.summary("backup", result.as_ref().err().map(|e| e.to_string()));
+            summary.verification = match &result {
+                Ok(report) if report.files_verified > 0 => Verification::Passed,
+                Err(lsusb::UsbError::VerificationFailed { .. }) => Verification::Failed,
+                _ => Verification::Skipped,
+            };
+            if let Ok(report) = &result {
+                summary.files_skipped += report.files_unchanged;
+            }
+            print_summary(&summary, settings.units, json)?;
+            record_write(device, summary.bytes_written);
+            let report = result?;
+            if !report.pruned.is_empty() {
+                let pruned = tr!(
+                    "pruned-snapshots",
+                    count = report.pruned.len(),
+                    snapshots = report.pruned.join(", ")
+                );
+                if json {
+                    eprintln!("{}", pruned);
+                } else {
+                    println!("{}", pruned);
+                }
+            }
+        }
+        BackupAction::Restore {
+            backup,
+            dest,
+            on_conflict,
+        } => {
+            let (name, snapshot) = split_backup(&backup);
+            let interrupt = crate::interrupt::guard();
+            let options = lsusb::TransferOptions {
+                cancel: interrupt.token(),
+                ..Default::default()
+            };
+            let pb = ProgressBar::hidden();
+            let render = Throttled::new(
+                |event| render_transfer(&pb, event),
+                Duration::from_millis(100),
+            );
+            let result = with_backup(
+                backend,
+                config,
+                settings,
+                name,
+                MountOptions { read_only: true },
+                |profile, backup_dir| {
+                    let options = lsusb::TransferOptions {
+                        mtime_tolerance: profile.tolerance(),
+                        ..options.clone()
+                    };
+                    if profile.chunked {
+                        let archive =
+                            lsusb::repo::Repository::open(backup_dir)?.archive_name(snapshot)?;
+                        println!(
+                            "{}",
+                            tr!(
+                                "restoring",
+                                backup = archive.as_str(),
+                                profile = name,
+                                dest = dest.display().to_string()
+                            )
+                        );
+                        return lsusb::repo::restore(
+                            backup_dir,
+                            &archive,
+                            &dest,
+                            on_conflict,
+                            profile.verify,
+                            &options,
+                            render,
+                        );
+                    }
+                    let source = lsusb::backup::restore_source(profile, backup_dir, snapshot)?;
+                    println!(
+                        "{}",
+                        tr!(
+                            "restoring",
+                            backup = source
+                                .file_name()
+                                .unwrap_or_default()
+                                .to_string_lossy()
+                                .into_owned(),
+                            profile = name,
+                            dest = dest.display().to_string()
+                        )
+                    );
+                    lsusb::backup::restore(
+                        &source,
+                        &dest,
+                        on_conflict,
+                        profile.verify,
+                        &options,
+                        render,
+                    )
+                },
+            );
+            if result.is_err() {
+                pb.abandon();
+            }
+            let report = result?;
+            println!(
+                "{}",
+                tr!(
+                    "restored",
+                    files = report.files_restored,
+                    bytes = report.bytes_restored,
+                    secs = format!("{:.1}", report.elapsed.as_secs_f64()),
+                    unchanged = report.files_unchanged,
+                    verified = report.files_verified
+                )
+            );
+            if !report.conflicts_skipped.is_empty() {
+                println!(
+                    "{}",
+                    tr!("conflicts-kept", count = report.conflicts_skipped.len())
+                );
+                for path in &report.conflicts_skipped {
+                    println!("  {}", path.display());
+                }
+            }
+            for path in &report.conflicts_kept {
+                println!(
+                    "{}",
+                    tr!("restored-next-to", path = path.display().to_string())
+                );
+            }
+        }
+        BackupAction::Snapshots { profile: name } => {
+            let summaries = with_backup(
+                backend,
+                config,
+                settings,
+                &name,
+                MountOptions { read_only: true },
+                |profile, backup_dir| {
+                    if profile.chunked {
+                        return lsusb::repo::snapshot_summaries(backup_dir);
+                    }
+                    if !profile.snapshots {
+                        return Err(lsusb::UsbError::Unsupported(format!(
+                            "{} keeps a mirror, not snapshots",
+                            name
+                        )));
+                    }
+                    lsusb::backup::snapshot_summaries(backup_dir)
+                },
+            )?;
+            if summaries.is_empty() {
+                println!("{}", tr!("no-snapshots", profile = name.as_str()));
+                return Ok(());
+            }
+            println!(
+                "{} {} {} {} {} {} {}",
+                pad(&tr!("column-snapshot"), 18),
+                pad_left(&tr!("column-files"), 8),
+                pad_left(&tr!("column-size"), 10),
+                pad_left(&tr!("column-added"), 6),
+                pad_left(&tr!("column-removed"), 7),
+                pad_left(&tr!("column-changed"), 7),
+                pad_left(&tr!("column-growth"), 11)
+            );
+            for s in &summaries {
+                println!(
+                    "{:<18} {:>8} {:>10} {:>6} {:>7} {:>7} {:>11}",
+                    s.name,
+                    s.files,
+                    settings.units.format(s.bytes),
+                    s.added,
+                    s.removed,
+                    s.changed,
+                    signed_size(settings.units, s.delta)
+                );
+            }
+        }
+        BackupAction::Prune {
+            profile: name,
+            dry_run,
+        } => {
+            let options = MountOptions { read_only: dry_run };
+            if !dry_run {
+                settings.ensure_writable("prune")?;
+            }
+            let pruned = with_backup(
+                backend,
+                config,
+                settings,
+                &name,
+                options,
+                |profile, backup_dir| {
+                    if !(profile.snapshots || profile.chunked) || profile.keep.is_empty() {
+                        return Err(lsusb::UsbError::Unsupported(format!(
+                            "{} has no retention rules; set keep = {{ last = .., daily = .., weekly = .., monthly = .. }} on a snapshot profile",
+                            name
+                        )));
+                    }
+                    match (profile.chunked, dry_run) {
+                        (true, true) => {
+                            let archives = lsusb::repo::Repository::open(backup_dir)?.archives()?;
+                            Ok(lsusb::backup::prune_plan(&archives, &profile.keep))
+                        }
+                        (true, false) => lsusb::repo::prune(backup_dir, &profile.keep),
+                        (false, true) => Ok(lsusb::backup::prune_plan(
+                            &lsusb::backup::snapshots(backup_dir)?,
+                            &profile.keep,
+                        )),
+                        (false, false) => lsusb::backup::prune(backup_dir, &profile.keep),
+                    }
+                },
+            )?;
+            if pruned.is_empty() {
+                println!("{}", tr!("nothing-to-prune"));
+            }
+            for snapshot in &pruned {
+                let key = if dry_run { "would-delete" } else { "deleted" };
+                println!("{}", tr!(key, snapshot = snapshot.as_str()));
+            }
+        }
+        BackupAction::Verify {
+            backup,
+            sample,
+            seed,
+            quick,
+            signature,
+        } => {
+            let (name, snapshot) = split_backup(&backup);
+            let sample = sample.map(|amount| match seed {
+                Some(seed) => lsusb::backup::Sample { amount, seed },
+                None => lsusb::backup::Sample::random(amount),
+            });
+            let interrupt = crate::interrupt::guard();
+            let options = lsusb::TransferOptions {
+                cancel: interrupt.token(),
+                ..Default::default()
+            };
+            let mut cache = match quick {
+                true => {
+                    let path =
+                        lsusb::hashcache::HashCache::default_path().context("HOME is not set")?;
+                    Some(lsusb::hashcache::HashCache::open(path)?)
+                }
+                false => None,
+            };
+            let pb = ProgressBar::hidden();
+            let render = Throttled::new(
+                |event| render_transfer(&pb, event),
+                Duration::from_millis(100),
+            );
+            let result = with_backup(
+                backend,
+                config,
+                settings,
+                name,
+                MountOptions { read_only: true },
+                |profile, backup_dir| {
+                    if profile.chunked && signature.is_some() {
+                        return Err(lsusb::UsbError::Unsupported(
+                        "--signature only applies to mirror and snapshot backups, not chunked ones".into(),
+                    ));
+                    }
+                    if profile.chunked && cache.is_some() {
+                        return Err(lsusb::UsbError::Unsupported(
+                            "--quick only applies to mirror and snapshot backups, not chunked ones"
+                                .into(),
+                        ));
+                    }
+                    if profile.chunked {
+                        let archive =
+                            lsusb::repo::Repository::open(backup_dir)?.archive_name(snapshot)?;
+                        println!(
+                            "{}",
+                            tr!("verifying", backup = archive.as_str(), profile = name)
+                        );
+                        print_sample(sample);
+                        return lsusb::repo::verify(backup_dir, &archive, sample, &options, render);
+                    }
+                    let source = lsusb::backup::restore_source(profile, backup_dir, snapshot)?;
+                    println!(
+                        "{}",
+                        tr!(
+                            "verifying",
+                            backup = source
+                                .file_name()
+                                .unwrap_or_default()
+                                .to_string_lossy()
+                                .into_owned(),
+                            profile = name
+                        )
+                    );
+                    if let Some(key) = &signature {
+                        let manifest = lsusb::backup::manifest_path(&source);
+                        lsusb::sign::Minisign::default().verify(&manifest, key)?;
+                        println!(
+                            "{}",
+                            tr!("signature-good", manifest = manifest.display().to_string())
+                        );
+                    }
+                    print_sample(sample);
+                    match cache.as_mut() {
+                        Some(cache) => {
+                            lsusb::backup::verify_cached(&source, sample, cache, &options, render)
+                        }
+                        None => lsusb::backup::verify(&source, sample, &options, render),
+                    }
+                },
+            );
+            if result.is_err() {
+                pb.abandon();
+            }
+            let report = result?;
+            println!(
+                "{}",
+                tr!(
+                    "checked",
+                    files = report.files_checked,
+                    size = settings.units.format(report.bytes_checked),
+                    secs = format!("{:.1}", report.elapsed.as_secs_f64())
+                )
+            );
+            if report.files_cached > 0 {
+                println!("{}", tr!("checked-cached", count = report.files_cached));
+            }
+            for path in &report.corrupt {
+                println!(
+                    "  {}",
+                    tr!("file-corrupt", path = path.display().to_string())
+                );
+            }
+            for path in &report.missing {
+                println!(
+                    "  {}",
+                    tr!("file-missing", path = path.display().to_string())
+                );
+            }
+            if let Some(path) = report.corrupt.first().or(report.missing.first()) {
+                println!(
+                    "{}",
+                    tr!(
+                        "verify-failed",
+                        corrupt = report.corrupt.len(),
+                        missing = report.missing.len()
+                    )
+                );
+                return Err(lsusb::UsbError::VerificationFailed { path: path.clone() }.into());
+            }
+            match sample {
+                Some(_) => println!("{}", tr!("sample-matches")),
+                None => println!("{}", tr!("all-match")),
+            }
+        }
+        BackupAction::Diff { old, new } => {
+            use lsusb::backup::ChangeKind;
+
+            let (name, Some(old_snapshot)) = split_backup(&old) else {
+                anyhow::bail!("name the older snapshot as PROFILE/SNAPSHOT");
+            };
+            let new_snapshot = match split_backup(&new) {
+                (snapshot, None) => snapshot,
+                (other, Some(_)) if other != name => {
+                    anyhow::bail!("both snapshots must belong to {}", name)
+                }
+                (_, Some(snapshot)) => snapshot,
+            };
+            let changes = with_backup(
+                backend,
+                config,
+                settings,
+                name,
+                MountOptions { read_only: true },
+                |profile, backup_dir| {
+                    if profile.chunked {
+                        return lsusb::repo::diff(backup_dir, old_snapshot, new_snapshot);
+                    }
+                    let old_dir =
+                        lsusb::backup::restore_source(profile, backup_dir, Some(old_snapshot))?;
+                    let new_dir =
+                        lsusb::backup::restore_source(profile, backup_dir, Some(new_snapshot))?;
+                    lsusb::backup::diff(&old_dir, &new_dir)
+                },
+            )?;
+            if changes.is_empty() {
+                println!(
+                    "{}",
+                    tr!("no-differences", old = old_snapshot, new = new_snapshot)
+                );
+                return Ok(());
+            }
+            for change in &changes {
+                let (mark, size) = match change.kind {
+                    ChangeKind::Added => ("+", settings.units.format(change.new_bytes)),
+                    ChangeKind::Removed => ("-", settings.units.format(change.old_bytes)),
+                    ChangeKind::Changed => ("~", signed_size(settings.units, change.delta())),
+                };
+                println!("{} {:>11}  {}", mark, size, change.path.display());
+            }
+            let count = |kind| changes.iter().filter(|c| c.kind == kind).count();
+            println!(
+                "{}",
+                tr!(
+                    "diff-summary",
+                    added = count(ChangeKind::Added),
+                    removed = count(ChangeKind::Removed),
+                    changed = count(ChangeKind::Changed),
+                    growth = signed_size(settings.units, changes.iter().map(|c| c.delta()).sum())
+                )
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn daemon(
+    backend: &dyn SystemBackend,
+    config: &lsusb::Config,
+    settings: Settings,
+) -> Result<()> {
+    use lsusb::backup::{AutoBackups, AutoEvent};
+
+    let interrupt = crate::interrupt::guard();
+    let cancel = interrupt.token();
+    let smart = Smart::default();
+    let notifier = Notifier::new(config.notify.clone());
+    let notify = |device: &str, summary: &str, body: &str| {
+        println!("{}: {}", summary, body);
+        if let Err(e) = notifier.send(device, summary, body) {
+            eprintln!("{}", tr!("notification-failed", error = e.to_string()));
+        }
+    };
+    let interval = Duration::from_secs(config.daemon.smart_interval);
+    let delay = Duration::from_secs(config.daemon.backup_delay);
+    let mut alerts = AlertTracker::new();
+    let mut auto = AutoBackups::new(delay);
+    let mut checked: HashMap<String, Instant> = HashMap::new();
+    let mut unsupported: HashSet<String> = HashSet::new();
+    println!("{}", tr!("daemon-started", secs = interval.as_secs()));
+    let automatic: Vec<&String> = config
+        .backup
+        .iter()
+        .filter(|(_, p)| p.auto)
+        .map(|(name, _)| name)
+        .collect();
+    if !automatic.is_empty() {
+        let names: Vec<&str> = automatic.iter().map(|n| n.as_str()).collect();
+        println!(
+            "{}",
+            tr!("daemon-auto-backups", profiles = names.join(", "))
+        );
+    }
+
+    while !cancel.is_cancelled() {
+        let devices = match backend.enumerate() {
+            Ok(devices) => devices,
+            Err(e) => {
+                eprintln!("{}", tr!("error-listing-devices", error = e.to_string()));
+                Vec::new()
+            }
+        };
+        let attached: Vec<&str> = devices.iter().map(|d| d.name.as_str()).collect();
+        alerts.retain(attached.iter().copied());
+        checked.retain(|name, _| attached.contains(&name.as_str()));
+        unsupported.retain(|name| attached.contains(&name.as_str()));
+
+        for name in attached {
+            if checked.get(name).is_some_and(|at| at.elapsed() < interval) {
+                continue;
+            }
+            checked.insert(name.to_string(), Instant::now());
+            let report = match smart.read(name) {
+                Ok(report) => report,
+                Err(e) => {
+                    // Most flash sticks have no SMART; say so once per attach.
+                    if unsupported.insert(name.to_string()) {
+                        eprintln!(
+                            "{}",
+                            tr!("no-smart-data", device = name, error = e.to_string())
+                        );
+                    }
+                    continue;
+                }
+            };
+            for alert in alerts.update(name, report.alerts(&config.smart)) {
+                notify(
+                    name,
+                    &tr!("smart-warning", device = name),
+                    &alert.to_string(),
+                );
+            }
+        }
+
+        for event in auto.update(&config.backup, &devices, usb_serial, Instant::now()) {
+            match event {
+                AutoEvent::Scheduled { profile, device } => notify(
+                    &device,
+                    &tr!(
+                        "auto-backup-scheduled",
+                        profile = profile.as_str(),
+                        secs = delay.as_secs()
+                    ),
+                    &tr!("auto-backup-unplug", device = device.as_str()),
+                ),
+                AutoEvent::Aborted { profile } => {
+                    println!("{}", tr!("auto-backup-aborted", profile = profile))
+                }
+                AutoEvent::Due { profile, device } => {
+                    let result = settings.ensure_writable("back up").and_then(|()| {
+                        let options = lsusb::TransferOptions {
+                            cancel: cancel.clone(),
+                            ignore: config.ignore(),
+                            ..Default::default()
+                        };
+                        with_backup(
+                            backend,
+                            config,
+                            settings,
+                            &profile,
+                            MountOptions::default(),
+                            |p, dest| lsusb::backup::run(p, dest, &options, |_| {}),
+                        )
+                    });
+                    match result {
+                        Ok(report) => notify(
+                            &device,
+                            &tr!("auto-backup-finished", profile = profile.as_str()),
+                            &tr!(
+                                "auto-backup-report",
+                                files = report.files_copied,
+                                size = settings.units.format(report.bytes_copied),
+                                secs = format!("{:.0}", report.elapsed.as_secs_f64()),
+                                unchanged = report.files_unchanged
+                            ),
+                        ),
+                        Err(e) => notify(
+                            &device,
+                            &tr!("auto-backup-failed", profile = profile.as_str()),
+                            &format!("{:#}", e),
+                        ),
+                    }
+                }
+            }
+        }
+
+        let slept = Instant::now();
+        while slept.elapsed() < DAEMON_POLL && !cancel.is_cancelled() {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+    Ok(())
+}
+
+/// Lists the jobs of every running lsusb process, or cancels `cancel`
+/// (`PID.ID`, as listed).
+pub fn jobs(cancel: Option<&str>, units: SizeUnits) -> Result<()> {
+    let sockets = lsusb::jobs::JobSockets::default();
+    if let Some(job) = cancel {
+        let (pid, id) = job
+            .split_once('.')
+            .and_then(|(pid, id)| Some((pid.parse().ok()?, id.parse().ok()?)))
+            .with_context(|| {
+                format!(
+                    "'{}' is not a job; use PID.ID as `lsusb jobs` lists it",
+                    job
+                )
+            })?;
+        if !sockets.cancel(pid, id)? {
+            anyhow::bail!("No cancellable job {} (it may have finished)", job);
+        }
+        println!("{}", tr!("job-stopping", job = job));
+        return Ok(());
+    }
+    let jobs = sockets.list()?;
+    if jobs.is_empty() {
+        println!("{}", tr!("no-jobs"));
+        return Ok(());
+    }
+    println!(
+        "{} {} {} {} {} {} {}",
+        pad(&tr!("column-job"), 12),
+        pad(&tr!("column-kind"), 8),
+        pad(&tr!("column-progress"), 24),
+        pad(&tr!("column-speed"), 12),
+        pad(&tr!("column-last-20s"), 20),
+        pad(&tr!("column-elapsed"), 8),
+        tr!("column-paths")
+    );
+    for lsusb::jobs::RemoteJob { pid, job } in jobs {
+        let progress = match job.bytes_total {
+            0 => "-".to_string(),
+            total => format!(
+                "{}/{} {}%",
+                units.format(job.bytes_done),
+                units.format(total),
+                job.bytes_done * 100 / total
+            ),
+        };
+        let elapsed = job.started.elapsed().unwrap_or_default().as_secs();
+        let paths: Vec<String> = job.paths.iter().map(|p| p.display().to_string()).collect();
+        println!(
+            "{:<12} {:<8} {:<24} {:<12} {:<20} {:<8} {}{}",
+            format!("{}.{}", pid, job.id),
+            job.kind,
+            progress,
+            format!("{}/s", units.format(job.rate)),
+            speed_graph(&job.history),
+            format!("{}m{:02}s", elapsed / 60, elapsed % 60),
+            paths.join(" -> "),
+            if job.cancellable {
+                String::new()
+            } else {
+                format!(" {}", tr!("job-not-cancellable"))
+            }
+        );
+    }
+    Ok(())
+}
+
+pub fn audit(log: &AuditLog, device: Option<&str>, operation: Option<&str>) -> Result<()> {
+    let entries: Vec<_> = log
+        .entries()?
+        .into_iter()
+        .filter(|e| device.is_none_or(|d| e.device.as_deref() == Some(d)))
+        .filter(|e| operation.is_none_or(|o| e.operation == o))
+        .collect();
+    if entries.is_empty() {
+        println!(
+            "{}",
+            tr!("audit-no-entries", log = log.path().display().to_string())
+        );
+        return Ok(());
+    }
+    println!(
+        "{} {} {} {} {} {}",
+        pad(&tr!("column-time-utc"), 20),
+        pad(&tr!("column-user"), 12),
+        pad(&tr!("column-operation"), 10),
+        pad(&tr!("column-device"), 8),
+        pad(&tr!("column-serial"), 20),
+        tr!("column-params")
+    );
+    for entry in entries {
+        println!(
+            "{:<20} {} {:<10} {:<8} {} {}",
+            entry.timestamp(),
+            pad(&entry.user, 12),
+            entry.operation,
+            entry.device.as_deref().unwrap_or("-"),
+            pad(entry.serial.as_deref().unwrap_or("-"), 20),
+            entry.params.join(" ")
+        );
+    }
+    Ok(())
+}
+
+/// Logs each file a copy replaces to `log` before it is overwritten.
+pub fn log_overwrites(log: AuditLog) -> lsusb::transfer::OverwriteHook {
+    lsusb::transfer::OverwriteHook::new(move |source, existing| {
+        let params = vec![source.display().to_string(), existing.display().to_string()];
+        log.record(&AuditEntry::for_path("cp overwrite", existing, params))
+    })
+}
+
+pub fn doctor(backend: BackendKind) -> Result<()> {
+    println!("{}", tr!("doctor-backend", backend = backend.to_string()));
+    if let Err(e) = backend.create() {
+        println!("  {}", e);
+    }
+    println!();
+
+    if lsusb::wsl::is_wsl() {
+        println!("{}", tr!("doctor-wsl"));
+        match lsusb::exec::which("usbipd.exe") {
+            Some(path) => println!(
+                "  {}",
+                tr!("doctor-usbipd-found", path = path.display().to_string())
+            ),
+            None => println!("  {}", tr!("doctor-usbipd-missing")),
+        }
+        println!();
+    }
+
+    let statuses = lsusb::doctor::check(backend);
+    println!(
+        "{} {} {} {}",
+        pad(&tr!("column-tool"), 12),
+        pad(&tr!("column-status"), 10),
+        pad(&tr!("column-needed"), 10),
+        tr!("column-used-for")
+    );
+    for status in &statuses {
+        println!(
+            "{:<12} {} {} {}",
+            status.tool.program,
+            pad(
+                &tr!(if status.available() {
+                    "tool-ok"
+                } else {
+                    "tool-missing"
+                }),
+                10
+            ),
+            pad(
+                &tr!(if status.tool.required {
+                    "tool-required"
+                } else {
+                    "tool-optional"
+                }),
+                10
+            ),
+            status.tool.used_for
+        );
+    }
+
+    let missing: Vec<_> = statuses.iter().filter(|s| !s.available()).collect();
+    if missing.is_empty() {
+        println!("\n{}", tr!("doctor-all-found"));
+        return Ok(());
+    }
+    println!("\n{}", tr!("doctor-unavailable"));
+    for status in &missing {
+        println!(
+            "  - {}",
+            tr!(
+                "doctor-install",
+                feature = status.tool.used_for,
+                program = status.tool.program
+            )
+        );
+    }
+    if missing.iter().any(|s| s.tool.required) {
+        anyhow::bail!("required tools are missing for the {} backend", backend);
+    }
+    Ok(())
+}
+
+pub fn wsl(action: WslAction) -> Result<()> {
+    if !lsusb::wsl::is_wsl() {
+        anyhow::bail!("not running under WSL");
+    }
+    let usbipd = lsusb::wsl::Usbipd::default();
+    match action {
+        WslAction::List => {
+            let devices = usbipd.list()?;
+            if devices.is_empty() {
+                println!("{}", tr!("wsl-no-devices"));
+                return Ok(());
+            }
+            println!(
+                "{} {} {} {}",
+                pad(&tr!("column-busid"), 8),
+                pad(&tr!("column-vid-pid"), 10),
+                pad(&tr!("column-device"), 40),
+                tr!("column-state")
+            );
+            for dev in devices {
+                println!(
+                    "{:<8} {:<10} {} {}",
+                    dev.busid,
+                    dev.vid_pid,
+                    pad(&dev.description, 40),
+                    dev.state
+                );
+            }
+        }
+        WslAction::Attach { busid } => {
+            println!("{}", tr!("wsl-attaching", busid = busid.as_str()));
+            usbipd.attach(&busid)?;
+            println!("{}", tr!("wsl-attached"));
+        }
+        WslAction::Detach { busid } => {
+            println!("{}", tr!("wsl-detaching", busid = busid.as_str()));
+            usbipd.detach(&busid)?;
+            println!("{}", tr!("wsl-detached"));
+        }
+    }
+    Ok(())
+}
+
+fn print_ios_devices(devices: &[lsusb::ios::IosDevice]) {
+    println!(
+        "{} {} {} {} {}",
+        pad(&tr!("column-udid"), 42),
+        pad(&tr!("column-class"), 8),
+        pad(&tr!("column-model"), 12),
+        pad(&tr!("column-ios"), 8),
+        tr!("column-name")
+    );
+    for dev in devices {
+        if !dev.is_paired() {
+            println!("{:<42} {}", dev.udid, tr!("ios-unpaired"));
+            continue;
+        }
+        println!(
+            "{:<42} {:<8} {:<12} {:<8} {}",
+            dev.udid,
+            dev.class.as_deref().unwrap_or("-"),
+            dev.product_type.as_deref().unwrap_or("-"),
+            dev.version.as_deref().unwrap_or("-"),
+            dev.name.as_deref().unwrap_or("-")
+        );
+    }
+}
+
+pub fn camera(action: CameraAction) -> Result<()> {
+    let gphoto2 = lsusb::camera::Gphoto2::default();
+    match action {
+        CameraAction::List => {
+            let cameras = gphoto2.cameras()?;
+            if cameras.is_empty() {
+                println!("{}", tr!("no-cameras"));
+                return Ok(());
+            }
+            println!("{} {}", pad(&tr!("column-model"), 32), tr!("column-port"));
+            for camera in cameras {
+                println!("{} {}", pad(&camera.model, 32), camera.port);
+            }
+        }
+        CameraAction::Import { dest, port } => {
+            let port = match port {
+                Some(port) => port,
+                None => {
+                    let cameras = gphoto2.cameras()?;
+                    match cameras.as_slice() {
+                        [] => anyhow::bail!("No cameras found"),
+                        [camera] => {
+                            println!("{}", tr!("camera-importing", model = camera.model.as_str()));
+                            camera.port.clone()
+                        }
+                        _ => anyhow::bail!(
+                            "Several cameras are attached; pick one with --port (see `lsusb camera list`)"
+                        ),
+                    }
+                }
+            };
+            let pb = ProgressBar::hidden();
+            let interrupt = crate::interrupt::guard();
+            let render = Throttled::new(
+                |event| render_transfer(&pb, event),
+                Duration::from_millis(100),
+            );
+            let result = gphoto2.import(&port, &dest, &interrupt.token(), render);
+            if result.is_err() {
+                pb.abandon();
+            }
+            let report = result?;
+            println!(
+                "{}",
+                tr!(
+                    "camera-imported",
+                    files = report.files,
+                    bytes = report.bytes,
+                    skipped = report.skipped
+                )
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn dfu(action: DfuAction, log: &AuditLog, settings: Settings) -> Result<()> {
+    let dfu = lsusb::dfu::Dfu::default();
+    match action {
+        DfuAction::List => {
+            let devices = dfu.devices()?;
+            if devices.is_empty() {
+                println!("{}", tr!("no-dfu-devices"));
+                return Ok(());
+            }
+            println!(
+                "{} {} {} {} {} {}",
+                pad(&tr!("column-vid-pid"), 10),
+                pad(&tr!("column-mode"), 8),
+                pad(&tr!("column-path"), 10),
+                pad(&tr!("column-alt"), 4),
+                pad(&tr!("column-serial"), 20),
+                tr!("column-name")
+            );
+            for dev in devices {
+                println!(
+                    "{:<10} {:<8} {:<10} {:<4} {} {}",
+                    dev.vid_pid,
+                    dev.mode,
+                    dev.path,
+                    dev.alt,
+                    pad(dev.serial.as_deref().unwrap_or("-"), 20),
+                    dev.name.as_deref().unwrap_or("-")
+                );
+            }
+        }
+        DfuAction::Download {
+            firmware,
+            device,
+            serial,
+            alt,
+            address,
+            reset,
+            json,
+        } => {
+            let json = json || settings.json_progress_on_stdout();
+            settings.ensure_writable("flash firmware")?;
+            let devices = dfu.devices()?;
+            let selector = lsusb::dfu::Selector {
+                vid_pid: device,
+                serial,
+                alt,
+            };
+            let target = selector.select(&devices)?;
+            let starting = tr!(
+                "dfu-flashing",
+                firmware = firmware.display().to_string(),
+                device = target.vid_pid.as_str(),
+                alt = target.alt.to_string()
+            );
+            if json {
+                eprintln!("{}", starting);
+            } else {
+                println!("{}", starting);
+            }
+            let mut params = vec![
+                format!("--alt={}", target.alt),
+                firmware.display().to_string(),
+            ];
+            if let Some(address) = &address {
+                params.insert(1, format!("--address={}", address));
+            }
+            let mut entry = AuditEntry::new("dfu download", Some(target.vid_pid.clone()), params);
+            entry.serial = target.serial.clone();
+            log.record(&entry)?;
+            let options = lsusb::dfu::DownloadOptions {
+                reset,
+                dfuse_address: address,
+            };
+            let pb = ProgressBar::hidden();
+            let mut meter = Throughput::default();
+            let mut render = Throttled::new(
+                render_progress(&pb, json, settings),
+                Duration::from_millis(100),
+            );
+            let result = dfu.download(target, &firmware, &options, |event: ProgressEvent| {
+                meter.record(&event);
+                if let ProgressEvent::Bytes { .. } = event {
+                    pb.set_message(speed_graph(&meter.history()));
+                }
+                render.emit(event);
+            });
+            if result.is_err() {
+                pb.abandon();
+            }
+            print_summary(
+                &meter.summary("flash", result.as_ref().err().map(|e| e.to_string())),
+                settings.units,
+                json,
+            )?;
+            result?;
+        }
+    }
+    Ok(())
+}
+
+pub fn ios(action: IosAction, settings: Settings) -> Result<()> {
+    let ios = lsusb::ios::Ios::default();
+    match action {
+        IosAction::List => {
+            let devices = ios.devices()?;
+            if devices.is_empty() {
+                println!("{}", tr!("no-ios-devices"));
+            } else {
+                print_ios_devices(&devices);
+            }
+        }
+        IosAction::Mount { udid, mountpoint } => {
+            let options = MountOptions {
+                read_only: settings.read_only,
+            };
+            println!("{}", tr!("ios-mounting", udid = udid.as_str()));
+            ios.mount(&udid, &mountpoint, options)?;
+            println!(
+                "{}",
+                tr!("ios-mounted", mountpoint = mountpoint.display().to_string())
+            );
+        }
+        IosAction::Unmount { mountpoint } => {
+            println!("{}", tr!("unmounting", mountpoint = mountpoint.as_str()));
+            ios.unmount(&mountpoint)?;
+            println!("{}", tr!("unmounted"));
+        }
+    }
+    Ok(())
+}
+
+pub fn iso(action: IsoAction, settings: Settings) -> Result<()> {
+    match action {
+        IsoAction::Ls {
+            image,
+            path,
+            recursive,
+        } => {
+            let mut iso = lsusb::iso::open_path(&image)?;
+            println!(
+                "{}",
+                tr!(
+                    "iso-volume",
+                    label = iso.label().unwrap_or("-").to_string(),
+                    format = iso.format().to_string()
+                )
+            );
+            let entry = iso.find(&path)?;
+            let entries = match (entry.is_dir, recursive) {
+                (false, _) => vec![entry],
+                (true, false) => iso.list(&entry)?,
+                (true, true) => iso.walk(&entry)?.into_iter().skip(1).collect(),
+            };
+            for entry in entries {
+                let size = if entry.is_dir {
+                    "-".to_string()
+                } else {
+                    settings.units.format(entry.size)
+                };
+                let slash = if entry.is_dir { "/" } else { "" };
+                println!("{:>12} {}{}", size, entry.path.display(), slash);
+            }
+        }
+        IsoAction::Extract { image, paths, dest } => {
+            let mut iso = lsusb::iso::open_path(&image)?;
+            let entries = if paths.is_empty() {
+                let root = iso.root().clone();
+                iso.list(&root)?
+            } else {
+                paths
+                    .iter()
+                    .map(|path| iso.find(path))
+                    .collect::<lsusb::Result<_>>()?
+            };
+            let (mut files, mut bytes) = (0, 0);
+            for entry in entries {
+                let (f, b) = iso.extract(&entry, &dest)?;
+                files += f;
+                bytes += b;
+            }
+            println!(
+                "{}",
+                tr!(
+                    "iso-extracted",
+                    files = files,
+                    size = settings.units.format(bytes),
+                    dest = dest.display().to_string()
+                )
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn termux(action: TermuxAction, settings: Settings) -> Result<()> {
+    let saf = lsusb::termux::Saf::default();
+    let interrupt = crate::interrupt::guard();
+    let print_file = |event: ProgressEvent| {
+        if let ProgressEvent::File { path, .. } = event {
+            println!("  {}", path.display());
+        }
+    };
+    match action {
+        TermuxAction::Dirs => {
+            let dirs = saf.dirs()?;
+            if dirs.is_empty() {
+                println!("{}", tr!("termux-no-storage"));
+                return Ok(());
+            }
+            println!("{} {}", pad(&tr!("column-name"), 24), tr!("column-uri"));
+            for dir in dirs {
+                println!("{} {}", pad(&dir.name, 24), dir.uri);
+            }
+        }
+        TermuxAction::Grant => {
+            saf.grant()?;
+            println!("{}", tr!("termux-granted"));
+        }
+        TermuxAction::Ls { uri } => {
+            println!(
+                "{} {} {}",
+                pad(&tr!("column-size"), 10),
+                pad(&tr!("column-type"), 6),
+                tr!("column-name")
+            );
+            for entry in saf.ls(&uri)? {
+                let kind = tr!(if entry.is_dir() {
+                    "entry-dir"
+                } else {
+                    "entry-file"
+                });
+                println!("{:<10} {} {}", entry.length, pad(&kind, 6), entry.name);
+            }
+        }
+        TermuxAction::Push { source, dest } => {
+            settings.ensure_writable("copy")?;
+            println!(
+                "{}",
+                tr!(
+                    "termux-copying",
+                    source = source.display().to_string(),
+                    dest = dest.as_str()
+                )
+            );
+            let report = saf.push(&source, &dest, &interrupt.token(), print_file)?;
+            println!(
+                "{}",
+                tr!("termux-copied", files = report.files, bytes = report.bytes)
+            );
+        }
+        TermuxAction::Pull { uri, dest } => {
+            let entry = saf.stat(&uri)?;
+            println!(
+                "{}",
+                tr!(
+                    "termux-copying",
+                    source = entry.name.as_str(),
+                    dest = dest.display().to_string()
+                )
+            );
+            let report = saf.pull(&entry, &dest, &interrupt.token(), print_file)?;
+            println!(
+                "{}",
+                tr!("termux-copied", files = report.files, bytes = report.bytes)
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use crate::audit::AuditLog;
+use crate::backend::BackendKind;
+use crate::backup::Profile;
+use crate::desktop::FileManager;
+use crate::error::{IoContext, Result, UsbError};
+use crate::ignore::Ignore;
+use crate::notify::NotifyConfig;
+use crate::policy::Policy;
+use crate::privilege::Escalation;
+use crate::scan::Scanner;
+use crate::smart::Thresholds;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Settings read from `$XDG_CONFIG_HOME/lsusb/config.toml` (falling back to
+/// `~/.config/lsusb/config.toml`). Every key is optional.
+///
+/// ```toml
+/// backend = "udisks2"
+/// escalate = "sudo"
+/// read_only = true
+/// ```
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Backend used when `--backend` isn't given.
+    pub backend: Option<BackendKind>,
+    /// How to re-run mount, unmount and power-off as root when they fail
+    /// for lack of privileges; detected when unset.
+    pub escalate: Option<Escalation>,
+    /// Behave as if `--read-only` were always given.
+    pub read_only: bool,
+    /// Behave as if `--include-mmc` were always given.
+    pub include_mmc: bool,
+    /// Behave as if `--all-removable` were always given.
+    pub all_removable: bool,
+    /// Behave as if `--plain` were always given.
+    pub plain: bool,
+    /// Devices left out of `list` and the menu unless `--show-hidden` is
+    /// given, by `vvvv:pppp` vendor/product ID (`vvvv:*` for any product)
+    /// or serial number; see [`HidingBackend`](crate::backend::HidingBackend).
+    pub hide: Vec<String>,
+    /// Scanner for `cp --scan`, run as `scan_command... <file>`; clamd is
+    /// used when unset.
+    pub scan_command: Option<Vec<String>>,
+    /// clamd's socket, if it isn't in one of the usual places.
+    pub clamd_socket: Option<PathBuf>,
+    /// File manager for `lsusb open`, run as `file_manager... <dir>`;
+    /// `xdg-open` is used when unset.
+    pub file_manager: Option<Vec<String>>,
+    /// Where destructive operations are logged; point several users at one
+    /// shared file on lab machines.
+    pub audit_log: Option<PathBuf>,
+    /// Which drives may be mounted and written to; see [`Policy`].
+    pub policy: Policy,
+    /// The `[daemon]` section.
+    pub daemon: DaemonConfig,
+    /// SMART readings `lsusb daemon` alerts on.
+    pub smart: Thresholds,
+    /// Where `lsusb daemon` sends alerts.
+    pub notify: NotifyConfig,
+    /// `[backup.<name>]` profiles for `lsusb backup run <name>`.
+    pub backup: BTreeMap<String, Profile>,
+    /// Patterns left out of every `cp` and backup, in `.lsusbignore`
+    /// syntax; see [`crate::ignore`].
+    pub ignore: Vec<String>,
+}
+
+/// Settings for `lsusb daemon`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DaemonConfig {
+    /// Seconds between SMART checks of each attached disk.
+    pub smart_interval: u64,
+    /// Seconds between plugging in the stick of an `auto` backup profile
+    /// and the backup starting; unplugging it in between skips the run.
+    pub backup_delay: u64,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        DaemonConfig {
+            smart_interval: 600,
+            backup_delay: 30,
+        }
+    }
+}
+
+impl Config {
+    /// Location of the config file, if a home/config directory is known.
+    pub fn path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(base.join("lsusb").join("config.toml"))
+    }
+
+    /// What copies and backups leave out: the `ignore` patterns plus the
+    /// sources' `.lsusbignore` files.
+    pub fn ignore(&self) -> Ignore {
+        Ignore::new(&self.ignore)
+    }
+
+    /// The scanner `cp --scan` should use.
+    pub fn scanner(&self) -> Result<Scanner> {
+        if let Some(command) = &self.scan_command {
+            return Ok(Scanner::command(command.clone()));
+        }
+        match &self.clamd_socket {
+            Some(socket) => Ok(Scanner::Clamd(socket.clone())),
+            None => Scanner::find_clamd(),
+        }
+    }
+
+    /// The file manager `lsusb open` should use.
+    pub fn file_manager(&self) -> FileManager {
+        FileManager::new(self.file_manager.clone())
+    }
+
+    /// The audit log, at `audit_log` or the default location.
+    pub fn audit_log(&self) -> Result<AuditLog> {
+        self.audit_log
+            .clone()
+            .or_else(AuditLog::default_path)
+            .map(AuditLog::new)
+            .ok_or_else(|| {
+                UsbError::Unsupported(
+                    "No location for the audit log; set audit_log in the config".into(),
+                )
+            })
+    }
+
+    /// Loads the config file, returning defaults if it doesn't exist.
+    pub fn load() -> Result<Config> {
+        let Some(path) = Self::path() else {
+            return Ok(Config::default());
+        };
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let text = fs::read_to_string(&path)
+            .io_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&text).map_err(|e| UsbError::parse(path.display().to_string(), e))
+    }
+}
@@ -0,0 +1,230 @@
+//! Per-file encryption while copying, for sticks that can't be reformatted
+//! as LUKS.
+//!
+//! Each file is handed to `age` or `gpg`, which write the result straight to
+//! the destination: encrypted copies get a `.age`/`.gpg` suffix, and
+//! decrypting strips it again, picking the tool from the suffix. Files
+//! without one are copied unchanged when decrypting.
+
+use crate::backend::run;
+use crate::error::{Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// The tool doing the encryption.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cipher {
+    Age,
+    Gpg,
+}
+
+impl Cipher {
+    /// The program that is run.
+    pub fn program(self) -> &'static str {
+        match self {
+            Cipher::Age => "age",
+            Cipher::Gpg => "gpg",
+        }
+    }
+
+    /// Suffix added to encrypted files.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Cipher::Age => "age",
+            Cipher::Gpg => "gpg",
+        }
+    }
+
+    fn from_extension(extension: &str) -> Option<Cipher> {
+        match extension {
+            "age" => Some(Cipher::Age),
+            "gpg" | "asc" => Some(Cipher::Gpg),
+            _ => None,
+        }
+    }
+}
+
+/// Who files are encrypted for, written `age:<recipient>` or
+/// `gpg:<key id or email>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recipient {
+    pub cipher: Cipher,
+    pub id: String,
+}
+
+impl FromStr for Recipient {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (tool, id) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected age:<recipient> or gpg:<key>, got '{}'", s))?;
+        let cipher = match tool {
+            "age" => Cipher::Age,
+            "gpg" => Cipher::Gpg,
+            other => {
+                return Err(format!(
+                    "unknown encryption tool '{}' (expected age or gpg)",
+                    other
+                ));
+            }
+        };
+        if id.is_empty() {
+            return Err(format!("missing {} recipient", tool));
+        }
+        Ok(Recipient {
+            cipher,
+            id: id.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.cipher.program(), self.id)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Mode {
+    Encrypt(Recipient),
+    Decrypt { identity: Option<PathBuf> },
+}
+
+/// Encrypts or decrypts files as [`copy_to_usb`](crate::copy_to_usb)
+/// writes them.
+#[derive(Clone)]
+pub struct Crypt {
+    mode: Mode,
+    exec: Arc<dyn CommandExecutor>,
+}
+
+impl fmt::Debug for Crypt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Crypt").field("mode", &self.mode).finish()
+    }
+}
+
+impl Crypt {
+    /// Encrypts every file for `recipient`.
+    pub fn encrypt(recipient: Recipient) -> Crypt {
+        Crypt {
+            mode: Mode::Encrypt(recipient),
+            exec: Arc::new(SystemExecutor),
+        }
+    }
+
+    /// Decrypts `.age` files with `identity` (age has no keyring) and
+    /// `.gpg`/`.asc` files with gpg's keyring.
+    pub fn decrypt(identity: Option<PathBuf>) -> Crypt {
+        Crypt {
+            mode: Mode::Decrypt { identity },
+            exec: Arc::new(SystemExecutor),
+        }
+    }
+
+    /// Runs `age`/`gpg` through `exec`.
+    pub fn with_executor(self, exec: Arc<dyn CommandExecutor>) -> Crypt {
+        Crypt { exec, ..self }
+    }
+
+    /// Where `dest` ends up once the cipher has renamed it, or `None` if
+    /// the file is copied unchanged.
+    pub fn target(&self, dest: &Path) -> Option<PathBuf> {
+        match &self.mode {
+            Mode::Encrypt(recipient) => {
+                let mut name = OsString::from(dest.as_os_str());
+                name.push(".");
+                name.push(recipient.cipher.extension());
+                Some(PathBuf::from(name))
+            }
+            Mode::Decrypt { .. } => {
+                self.decrypt_cipher(dest)?;
+                Some(dest.with_extension(""))
+            }
+        }
+    }
+
+    /// Encrypts or decrypts `source` into `target`, removing `target` again
+    /// if the tool fails.
+    pub fn apply(&self, source: &Path, target: &Path) -> Result<()> {
+        let input = utf8(source)?;
+        let output = utf8(target)?;
+        let (program, args) = match &self.mode {
+            Mode::Encrypt(recipient) => match recipient.cipher {
+                Cipher::Age => (
+                    "age",
+                    vec![
+                        "--encrypt",
+                        "--recipient",
+                        &recipient.id,
+                        "--output",
+                        output,
+                        input,
+                    ],
+                ),
+                Cipher::Gpg => (
+                    "gpg",
+                    vec![
+                        "--yes",
+                        "--encrypt",
+                        "--recipient",
+                        &recipient.id,
+                        "--output",
+                        output,
+                        input,
+                    ],
+                ),
+            },
+            Mode::Decrypt { identity } => match self.decrypt_cipher(source) {
+                Some(Cipher::Age) => {
+                    let identity = identity.as_deref().ok_or_else(|| {
+                        UsbError::Unsupported(format!(
+                            "Decrypting {} needs an age identity file",
+                            source.display()
+                        ))
+                    })?;
+                    (
+                        "age",
+                        vec![
+                            "--decrypt",
+                            "--identity",
+                            utf8(identity)?,
+                            "--output",
+                            output,
+                            input,
+                        ],
+                    )
+                }
+                Some(Cipher::Gpg) => ("gpg", vec!["--yes", "--decrypt", "--output", output, input]),
+                None => {
+                    return Err(UsbError::Unsupported(format!(
+                        "{} is not encrypted",
+                        source.display()
+                    )));
+                }
+            },
+        };
+        run(self.exec.as_ref(), program, &args)
+            .map(|_| ())
+            .inspect_err(|_| {
+                let _ = fs::remove_file(target);
+            })
+    }
+
+    fn decrypt_cipher(&self, path: &Path) -> Option<Cipher> {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .and_then(Cipher::from_extension)
+    }
+}
+
+fn utf8(path: &Path) -> Result<&str> {
+    path.to_str()
+        .ok_or_else(|| UsbError::Unsupported(format!("{} is not valid UTF-8", path.display())))
+}
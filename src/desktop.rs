@@ -0,0 +1,58 @@
+//! Handing a mounted partition over to the desktop: opening it in the
+//! user's file manager, so mounting from lsusb ends the way it does from the
+//! desktop, with a window on the files.
+
+use crate::backend::run;
+use crate::error::{Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Opens directories in a file manager.
+#[derive(Clone)]
+pub struct FileManager {
+    exec: Arc<dyn CommandExecutor>,
+    command: Vec<String>,
+}
+
+impl FileManager {
+    /// Runs `command` as `command... <dir>`; without one, `xdg-open` (or
+    /// `open` on macOS) hands the directory to whatever the desktop uses.
+    /// The command should return once the window is up, as both of those do.
+    pub fn new(command: Option<Vec<String>>) -> Self {
+        FileManager::with_executor(command, Arc::new(SystemExecutor))
+    }
+
+    /// Uses `exec` to run the command.
+    pub fn with_executor(command: Option<Vec<String>>, exec: Arc<dyn CommandExecutor>) -> Self {
+        let command = command.unwrap_or_else(|| {
+            let opener = if cfg!(target_os = "macos") {
+                "open"
+            } else {
+                "xdg-open"
+            };
+            vec![opener.to_string()]
+        });
+        FileManager { exec, command }
+    }
+
+    /// Opens `dir`.
+    pub fn open(&self, dir: &Path) -> Result<()> {
+        if !dir.is_dir() {
+            return Err(UsbError::Unsupported(format!(
+                "{} is not a directory",
+                dir.display()
+            )));
+        }
+        let Some((program, args)) = self.command.split_first() else {
+            return Err(UsbError::Unsupported(
+                "the file manager command is empty".to_string(),
+            ));
+        };
+        let dir = dir.to_string_lossy();
+        let mut args: Vec<&str> = args.iter().map(String::as_str).collect();
+        args.push(&dir);
+        run(self.exec.as_ref(), program, &args)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+//! Firmware updates over the USB Device Firmware Upgrade (DFU) class, for
+//! dongles and development boards.
+//!
+//! The protocol is spoken by [dfu-util](https://dfu-util.sourceforge.net),
+//! which also handles ST's DfuSe extension and detaching devices that are
+//! still running their application firmware.
+
+use crate::backend::{check_output, run};
+use crate::error::{Result, UsbError};
+use crate::exec::{CommandExecutor, LineWriter, SystemExecutor};
+use crate::progress::{Phase, ProgressEvent, ProgressSink};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Whether a device is running its application or its bootloader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DfuMode {
+    /// Application firmware with a DFU interface; dfu-util detaches it into
+    /// the bootloader before downloading.
+    Runtime,
+    /// The DFU bootloader itself.
+    Dfu,
+}
+
+impl fmt::Display for DfuMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DfuMode::Runtime => "runtime",
+            DfuMode::Dfu => "dfu",
+        })
+    }
+}
+
+/// One DFU interface alternate setting, as listed by `dfu-util -l`. A
+/// device with several flash regions shows up once per region.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DfuDevice {
+    /// `vvvv:pppp`.
+    pub vid_pid: String,
+    pub mode: DfuMode,
+    /// USB port path, e.g. `1-1.4`.
+    pub path: String,
+    /// Alternate setting number.
+    pub alt: u8,
+    /// What the alternate setting flashes, e.g. `@Internal Flash /0x08000000/...`.
+    pub name: Option<String>,
+    pub serial: Option<String>,
+}
+
+/// Narrows down which [`DfuDevice`] to flash; unset fields match anything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Selector {
+    pub vid_pid: Option<String>,
+    pub serial: Option<String>,
+    pub alt: Option<u8>,
+}
+
+impl Selector {
+    fn matches(&self, device: &DfuDevice) -> bool {
+        self.vid_pid
+            .as_ref()
+            .is_none_or(|v| v.eq_ignore_ascii_case(&device.vid_pid))
+            && self
+                .serial
+                .as_ref()
+                .is_none_or(|s| device.serial.as_ref() == Some(s))
+            && self.alt.is_none_or(|a| a == device.alt)
+    }
+
+    /// The single device in `devices` this selects.
+    pub fn select<'a>(&self, devices: &'a [DfuDevice]) -> Result<&'a DfuDevice> {
+        let matching: Vec<&DfuDevice> = devices.iter().filter(|d| self.matches(d)).collect();
+        match matching.as_slice() {
+            [] => Err(UsbError::DeviceNotFound("no matching DFU device".into())),
+            [device] => Ok(device),
+            [first, rest @ ..] if rest.iter().all(|d| d.path == first.path) => {
+                Err(UsbError::Unsupported(format!(
+                    "{} has several alternate settings; pick one with --alt",
+                    first.vid_pid
+                )))
+            }
+            _ => Err(UsbError::Unsupported(
+                "several DFU devices match; pick one with --device or --serial".into(),
+            )),
+        }
+    }
+}
+
+/// How [`Dfu::download`] should flash.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DownloadOptions {
+    /// Reset the device afterwards so it starts the new firmware.
+    pub reset: bool,
+    /// Start address for DfuSe (ST) devices, e.g. `0x08000000` or
+    /// `0x08000000:leave`; plain DFU devices have no addresses.
+    pub dfuse_address: Option<String>,
+}
+
+/// Runs `dfu-util`.
+#[derive(Clone)]
+pub struct Dfu {
+    exec: Arc<dyn CommandExecutor>,
+}
+
+impl Default for Dfu {
+    fn default() -> Self {
+        Dfu::with_executor(Arc::new(SystemExecutor))
+    }
+}
+
+impl Dfu {
+    /// Uses `exec` to run `dfu-util`.
+    pub fn with_executor(exec: Arc<dyn CommandExecutor>) -> Self {
+        Dfu { exec }
+    }
+
+    /// DFU-capable devices attached right now.
+    pub fn devices(&self) -> Result<Vec<DfuDevice>> {
+        let output = run(self.exec.as_ref(), "dfu-util", &["--list"])?;
+        Ok(parse_list(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Downloads (flashes) `firmware` to `device`. There is no cancelling:
+    /// interrupting a download can leave the device without working
+    /// firmware.
+    pub fn download(
+        &self,
+        device: &DfuDevice,
+        firmware: &Path,
+        options: &DownloadOptions,
+        progress: impl ProgressSink,
+    ) -> Result<()> {
+        let total = fs::metadata(firmware)
+            .map_err(|e| UsbError::io(format!("Failed to read {}", firmware.display()), e))?
+            .len();
+        let file = firmware.to_str().ok_or_else(|| {
+            UsbError::Unsupported(format!("{} is not valid UTF-8", firmware.display()))
+        })?;
+        let alt = device.alt.to_string();
+        let mut args = vec![
+            "--device",
+            &device.vid_pid,
+            "--path",
+            &device.path,
+            "--alt",
+            &alt,
+            "--download",
+            file,
+        ];
+        if let Some(address) = &options.dfuse_address {
+            args.extend(["--dfuse-address", address]);
+        }
+        if options.reset {
+            args.push("--reset");
+        }
+
+        // There is no stopping a flash halfway without bricking the device.
+        let job = crate::jobs::start("flash", &[firmware], None);
+        let mut progress = job.track(progress);
+        progress.emit(ProgressEvent::Phase(Phase::Copying));
+        progress.emit(ProgressEvent::Totals {
+            bytes: total,
+            files: 1,
+        });
+        progress.emit(ProgressEvent::File {
+            path: PathBuf::from(firmware),
+            size: total,
+        });
+        let mut lines = LineWriter::new(|line: &str| {
+            if let Some(done) = downloaded_bytes(line) {
+                progress.emit(ProgressEvent::Bytes {
+                    done: done.min(total),
+                    total,
+                });
+            }
+            Ok(())
+        });
+        let result = self.exec.run_to_writer("dfu-util", &args, &mut lines);
+        drop(lines);
+        check_output("dfu-util", result)?;
+        // The DFU suffix isn't sent, so the last report falls short.
+        progress.emit(ProgressEvent::Bytes { done: total, total });
+        progress.emit(ProgressEvent::Phase(Phase::Done));
+        Ok(())
+    }
+}
+
+/// Bytes sent so far, from a `Download [=====    ]  40%   9384 bytes`
+/// progress line.
+fn downloaded_bytes(line: &str) -> Option<u64> {
+    line.strip_prefix("Download")?
+        .trim_end()
+        .strip_suffix("bytes")?
+        .split_whitespace()
+        .last()?
+        .parse()
+        .ok()
+}
+
+/// Parses `dfu-util --list`, which prints lines like
+///
+/// ```text
+/// Found DFU: [0483:df11] ver=2200, devnum=12, cfg=1, intf=0, path="1-1", alt=0, name="@Internal Flash  /0x08000000/04*016Kg", serial="3276365A3236"
+/// ```
+pub fn parse_list(output: &str) -> Vec<DfuDevice> {
+    output.lines().filter_map(parse_found).collect()
+}
+
+fn parse_found(line: &str) -> Option<DfuDevice> {
+    let (mode, rest) = if let Some(rest) = line.strip_prefix("Found DFU: ") {
+        (DfuMode::Dfu, rest)
+    } else {
+        (DfuMode::Runtime, line.strip_prefix("Found Runtime: ")?)
+    };
+    let (vid_pid, fields) = rest.strip_prefix('[')?.split_once(']')?;
+    let fields = parse_fields(fields);
+    let field = |key: &str| {
+        fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+            .filter(|v| !v.is_empty() && v != "UNKNOWN")
+    };
+    Some(DfuDevice {
+        vid_pid: vid_pid.to_string(),
+        mode,
+        path: field("path")?,
+        alt: field("alt")?.parse().ok()?,
+        name: field("name"),
+        serial: field("serial"),
+    })
+}
+
+/// `key=value, key="quoted, value"` pairs.
+fn parse_fields(text: &str) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    let mut rest = text.trim_start_matches([',', ' ']);
+    while let Some((key, after)) = rest.split_once('=') {
+        let (value, next) = match after.strip_prefix('"') {
+            Some(quoted) => match quoted.split_once('"') {
+                Some((value, next)) => (value, next),
+                None => (quoted, ""),
+            },
+            None => after.split_once(',').unwrap_or((after, "")),
+        };
+        fields.push((key.trim().to_string(), value.trim().to_string()));
+        rest = next.trim_start_matches([',', ' ']);
+    }
+    fields
+}
@@ -0,0 +1,114 @@
+//! Per-device I/O activity from `/proc/diskstats`, so users can see that a
+//! drive is still busy before pulling it.
+//!
+//! The kernel only keeps running totals; [`IoMeter`] turns two readings
+//! into rates.
+
+use crate::error::{IoContext, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::time::Instant;
+
+const PROC_DISKSTATS: &str = "/proc/diskstats";
+/// diskstats counts in 512-byte sectors whatever the device's own size.
+const SECTOR: u64 = 512;
+
+/// A device's totals since boot.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counters {
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// Requests the device is working on right now.
+    pub in_flight: u64,
+}
+
+/// What a device did between two readings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Activity {
+    /// Bytes per second.
+    pub read_rate: u64,
+    pub write_rate: u64,
+    /// Requests in progress at the latest reading.
+    pub in_flight: u64,
+}
+
+impl Activity {
+    pub fn is_busy(&self) -> bool {
+        self.read_rate > 0 || self.write_rate > 0 || self.in_flight > 0
+    }
+}
+
+/// Parses `/proc/diskstats`, keyed by device name (e.g. `sdb`, `sdb1`):
+///
+/// ```text
+///    8      16 sdb 1204 0 98312 811 3311 0 2097152 40213 2 40872 41024
+/// ```
+pub fn parse(text: &str) -> HashMap<String, Counters> {
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let number = |i: usize| fields.get(i)?.parse::<u64>().ok();
+            Some((
+                fields.get(2)?.to_string(),
+                Counters {
+                    bytes_read: number(5)? * SECTOR,
+                    bytes_written: number(9)? * SECTOR,
+                    in_flight: number(11)?,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// The current totals of every block device (Linux only).
+pub fn read() -> Result<HashMap<String, Counters>> {
+    let text = fs::read_to_string(PROC_DISKSTATS)
+        .io_context(|| format!("Failed to read {}", PROC_DISKSTATS))?;
+    Ok(parse(&text))
+}
+
+/// Turns successive readings into [`Activity`].
+#[derive(Debug, Clone, Default)]
+pub struct IoMeter {
+    last: Option<(Instant, HashMap<String, Counters>)>,
+}
+
+impl IoMeter {
+    /// Reads `/proc/diskstats` and returns what each device did since the
+    /// previous call; rates are zero on the first.
+    pub fn sample(&mut self) -> Result<HashMap<String, Activity>> {
+        Ok(self.update(read()?, Instant::now()))
+    }
+
+    /// Records `counters` as read at `now`.
+    pub fn update(
+        &mut self,
+        counters: HashMap<String, Counters>,
+        now: Instant,
+    ) -> HashMap<String, Activity> {
+        let previous = self.last.take();
+        let activity = counters
+            .iter()
+            .map(|(name, current)| {
+                let mut activity = Activity {
+                    in_flight: current.in_flight,
+                    ..Activity::default()
+                };
+                if let Some((at, before)) = &previous
+                    && let Some(before) = before.get(name)
+                {
+                    let secs = now.saturating_duration_since(*at).as_secs_f64();
+                    if secs > 0.0 {
+                        let rate =
+                            |now: u64, then: u64| (now.saturating_sub(then) as f64 / secs) as u64;
+                        activity.read_rate = rate(current.bytes_read, before.bytes_read);
+                        activity.write_rate = rate(current.bytes_written, before.bytes_written);
+                    }
+                }
+                (name.clone(), activity)
+            })
+            .collect();
+        self.last = Some((now, counters));
+        activity
+    }
+}
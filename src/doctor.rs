@@ -0,0 +1,121 @@
+//! Checks which external tools the selected backend relies on are present,
+//! so missing ones can be reported up front instead of as a failure halfway
+//! through an operation.
+
+use crate::backend::BackendKind;
+use crate::exec::which;
+use std::path::PathBuf;
+
+/// An external program lsusb may run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tool {
+    pub program: &'static str,
+    /// The feature that stops working without it.
+    pub used_for: &'static str,
+    /// Whether lsusb is unusable with this backend without the tool.
+    pub required: bool,
+}
+
+/// A [`Tool`] and where it was found, if anywhere.
+#[derive(Debug, Clone)]
+pub struct ToolStatus {
+    pub tool: Tool,
+    pub path: Option<PathBuf>,
+}
+
+impl ToolStatus {
+    pub fn available(&self) -> bool {
+        self.path.is_some()
+    }
+}
+
+const fn required(program: &'static str, used_for: &'static str) -> Tool {
+    Tool {
+        program,
+        used_for,
+        required: true,
+    }
+}
+
+const fn optional(program: &'static str, used_for: &'static str) -> Tool {
+    Tool {
+        program,
+        used_for,
+        required: false,
+    }
+}
+
+/// Tools the given backend uses.
+pub fn tools_for(kind: BackendKind) -> Vec<Tool> {
+    match kind {
+        BackendKind::Lsblk => vec![
+            required("lsblk", "listing devices"),
+            required("umount", "unmount"),
+            optional("mount", "mount"),
+            optional("eject", "power-off"),
+            optional("udisksctl", "the udisks2 backend"),
+        ],
+        BackendKind::Sysfs => vec![optional("udisksctl", "the udisks2 backend")],
+        BackendKind::Udisks2 => vec![
+            required("lsblk", "listing devices"),
+            required("udisksctl", "mount, unmount and power-off"),
+        ],
+        BackendKind::Diskutil => vec![
+            required(
+                "diskutil",
+                "listing, mounting, unmounting and ejecting devices",
+            ),
+            optional("sync", "sync"),
+        ],
+        BackendKind::Bsd => {
+            let mut tools = if cfg!(target_os = "openbsd") {
+                vec![
+                    required("sysctl", "listing devices"),
+                    optional("disklabel", "listing partitions and mount"),
+                    optional("eject", "power-off"),
+                ]
+            } else {
+                vec![
+                    required("geom", "listing devices"),
+                    required("camcontrol", "detecting USB disks and power-off"),
+                    optional("gpart", "listing partitions"),
+                    optional("fstyp", "mount"),
+                ]
+            };
+            tools.extend([
+                required("mount", "mount and listing mountpoints"),
+                required("umount", "unmount"),
+                optional("sync", "sync"),
+            ]);
+            tools
+        }
+    }
+}
+
+/// Looks up every tool the backend uses on `PATH`.
+pub fn check(kind: BackendKind) -> Vec<ToolStatus> {
+    tools_for(kind)
+        .into_iter()
+        .map(|tool| ToolStatus {
+            path: which(tool.program),
+            tool,
+        })
+        .collect()
+}
+
+/// The feature a program is needed for, preferring a backend that
+/// requires it over one where it is optional.
+pub fn used_for(program: &str) -> Option<&'static str> {
+    let mut matches: Vec<Tool> = [
+        BackendKind::Lsblk,
+        BackendKind::Udisks2,
+        BackendKind::Diskutil,
+        BackendKind::Bsd,
+    ]
+    .into_iter()
+    .flat_map(tools_for)
+    .filter(|t| t.program == program)
+    .collect();
+    matches.sort_by_key(|t| !t.required);
+    matches.first().map(|t| t.used_for)
+}
@@ -0,0 +1,148 @@
+//! Deleting files so their contents don't linger on the stick.
+//!
+//! Every file is overwritten with zeros and flushed before it is unlinked,
+//! then on Linux the filesystem is asked to discard its free space
+//! (`FITRIM`) so the controller can erase the blocks. Neither reaches copies
+//! the controller has already remapped for wear leveling, which `lsusb rm
+//! --secure` warns about.
+
+use crate::cancel::CancelToken;
+use crate::error::{IoContext, Result, UsbError};
+use crate::transfer::ensure_writable;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use walkdir::WalkDir;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// What a finished [`secure_delete`] did.
+#[derive(Debug, Clone, Default)]
+pub struct EraseReport {
+    /// Files overwritten and removed.
+    pub files: u64,
+    /// Bytes of file content overwritten.
+    pub bytes: u64,
+    /// Files only unlinked, because other hard links still reach their
+    /// contents.
+    pub shared: u64,
+    /// Whether the filesystem accepted `FITRIM` afterwards.
+    pub trimmed: bool,
+}
+
+/// Overwrites and removes `path`, recursing into directories.
+pub fn secure_delete(path: &Path, cancel: &CancelToken) -> Result<EraseReport> {
+    let metadata =
+        fs::symlink_metadata(path).io_context(|| format!("Failed to read {}", path.display()))?;
+    ensure_writable(path)?;
+    let mut report = EraseReport::default();
+    // A handle on the filesystem `path` is on, taken before it is gone: a
+    // directory is opened itself, since its parent is on another
+    // filesystem if it is a mount root.
+    let parent = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let filesystem = File::open(if metadata.is_dir() { path } else { parent }).ok();
+
+    if metadata.is_dir() {
+        // Children before their directory, so each directory is empty by
+        // the time it is removed.
+        for entry in WalkDir::new(path).contents_first(true) {
+            cancel.check()?;
+            let entry = entry.map_err(|e| {
+                UsbError::io(format!("Failed to read {}", path.display()), e.into())
+            })?;
+            if entry.file_type().is_dir() {
+                fs::remove_dir(entry.path())
+                    .io_context(|| format!("Failed to remove {}", entry.path().display()))?;
+            } else {
+                erase_file(
+                    entry.path(),
+                    entry.file_type().is_file(),
+                    cancel,
+                    &mut report,
+                )?;
+            }
+        }
+    } else {
+        erase_file(path, metadata.is_file(), cancel, &mut report)?;
+    }
+
+    report.trimmed = filesystem.is_some_and(|filesystem| trim(&filesystem));
+    Ok(report)
+}
+
+/// Zeros a regular file in place and unlinks it; symlinks, other special
+/// files and files with other hard links are only unlinked.
+fn erase_file(
+    path: &Path,
+    regular: bool,
+    cancel: &CancelToken,
+    report: &mut EraseReport,
+) -> Result<()> {
+    let shared = regular
+        && fs::symlink_metadata(path)
+            .io_context(|| format!("Failed to read {}", path.display()))?
+            .nlink()
+            > 1;
+    if shared {
+        report.shared += 1;
+    } else if regular {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(path)
+            .io_context(|| format!("Failed to open {}", path.display()))?;
+        let len = file
+            .metadata()
+            .io_context(|| format!("Failed to read {}", path.display()))?
+            .len();
+        let zeros = vec![0u8; CHUNK_SIZE];
+        let mut left = len;
+        while left > 0 {
+            cancel.check()?;
+            let n = left.min(CHUNK_SIZE as u64) as usize;
+            file.write_all(&zeros[..n])
+                .io_context(|| format!("Failed to overwrite {}", path.display()))?;
+            left -= n as u64;
+        }
+        // The zeros must reach the stick before the blocks are freed.
+        file.sync_all()
+            .io_context(|| format!("Failed to flush {}", path.display()))?;
+        report.bytes += len;
+    }
+    fs::remove_file(path).io_context(|| format!("Failed to remove {}", path.display()))?;
+    report.files += 1;
+    Ok(())
+}
+
+/// Asks the filesystem `dir` (an open directory) is on to discard all of
+/// its free space. Needs root and a filesystem and stick that support
+/// discard; returns whether it worked.
+#[cfg(target_os = "linux")]
+fn trim(dir: &File) -> bool {
+    use std::os::fd::AsRawFd;
+
+    /// `_IOWR('X', 121, struct fstrim_range)` from `<linux/fs.h>`.
+    const FITRIM: u64 = 0xc018_5879;
+
+    #[repr(C)]
+    struct FstrimRange {
+        start: u64,
+        len: u64,
+        minlen: u64,
+    }
+
+    let mut range = FstrimRange {
+        start: 0,
+        len: u64::MAX,
+        minlen: 0,
+    };
+    unsafe { libc::ioctl(dir.as_raw_fd(), FITRIM as libc::Ioctl, &mut range) == 0 }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn trim(_dir: &File) -> bool {
+    false
+}
@@ -0,0 +1,154 @@
+use crate::policy::Access;
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Everything the library can fail with.
+///
+/// Variants are coarse on purpose: they are what callers (and the CLI's exit
+/// codes) want to branch on. Human detail lives in the messages.
+#[derive(Debug, Error)]
+pub enum UsbError {
+    /// No block device with this name exists.
+    #[error("Device {0} not found")]
+    DeviceNotFound(String),
+
+    /// The device exists but is not attached over USB.
+    #[error("Device {0} is not a USB device")]
+    NotUsb(String),
+
+    /// The filesystem can't be unmounted because processes still use it.
+    #[error("{mountpoint} is busy{}", describe_pids(pids))]
+    MountBusy { mountpoint: String, pids: Vec<u32> },
+
+    /// lsusb itself is still copying to or reading from the filesystem.
+    #[error("{mountpoint} is in use by a running lsusb job: {job}")]
+    JobRunning { mountpoint: String, job: String },
+
+    /// Another lsusb process holds the device's lock.
+    #[error("{device} is in use by another lsusb process: {holder}")]
+    DeviceLocked { device: String, holder: String },
+
+    /// The destination doesn't have room for the transfer, or for `path`
+    /// if it is the one file that doesn't fit.
+    #[error(
+        "Not enough space on destination{}: {needed} bytes needed, {available} available",
+        describe_path(path)
+    )]
+    InsufficientSpace {
+        needed: u64,
+        available: u64,
+        path: Option<PathBuf>,
+    },
+
+    /// The destination is on read-only media: the write-protect switch is
+    /// on, the kernel marked the device read-only, or it is mounted `ro`.
+    #[error("{} is write-protected", path.display())]
+    WriteProtected { path: PathBuf },
+
+    /// The device policy in the config doesn't allow this operation on the
+    /// drive.
+    #[error("{device} is {} by the device policy", describe_access(*access))]
+    PolicyDenied { device: String, access: Access },
+
+    /// A copied file doesn't match its source.
+    #[error("Verification failed for {}", path.display())]
+    VerificationFailed { path: PathBuf },
+
+    /// A required external program isn't installed.
+    #[error(
+        "{program} is not installed or not in PATH{}. Run `lsusb doctor` for details",
+        describe_tool(program)
+    )]
+    ToolMissing { program: String },
+
+    /// An external program ran but reported failure.
+    #[error("{program} command failed{}", describe_stderr(stderr))]
+    CommandFailed { program: String, stderr: String },
+
+    /// Output from a tool or a file couldn't be understood.
+    #[error("Failed to parse {what}: {message}")]
+    Parse { what: String, message: String },
+
+    /// The operation was stopped through its [`CancelToken`](crate::CancelToken).
+    #[error("Operation cancelled")]
+    Cancelled,
+
+    /// The operation can't be done with this backend, platform or input.
+    #[error("{0}")]
+    Unsupported(String),
+
+    /// An I/O error, with a description of what was being attempted.
+    #[error("{context}")]
+    Io {
+        context: String,
+        #[source]
+        source: io::Error,
+    },
+}
+
+/// Result alias used throughout the library.
+pub type Result<T, E = UsbError> = std::result::Result<T, E>;
+
+impl UsbError {
+    pub(crate) fn io(context: impl Into<String>, source: io::Error) -> Self {
+        UsbError::Io {
+            context: context.into(),
+            source,
+        }
+    }
+
+    pub(crate) fn parse(what: impl Into<String>, message: impl ToString) -> Self {
+        UsbError::Parse {
+            what: what.into(),
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Attaches a description to `io::Result`s, producing [`UsbError::Io`].
+pub(crate) trait IoContext<T> {
+    fn io_context<C: Into<String>>(self, context: impl FnOnce() -> C) -> Result<T>;
+}
+
+impl<T> IoContext<T> for io::Result<T> {
+    fn io_context<C: Into<String>>(self, context: impl FnOnce() -> C) -> Result<T> {
+        self.map_err(|e| UsbError::io(context(), e))
+    }
+}
+
+fn describe_path(path: &Option<PathBuf>) -> String {
+    path.as_ref()
+        .map_or(String::new(), |path| format!(" for {}", path.display()))
+}
+
+fn describe_pids(pids: &[u32]) -> String {
+    if pids.is_empty() {
+        return String::new();
+    }
+    let list: Vec<String> = pids.iter().map(u32::to_string).collect();
+    format!(" (in use by PID {})", list.join(", "))
+}
+
+fn describe_access(access: Access) -> &'static str {
+    match access {
+        Access::Deny => "blocked",
+        _ => "read-only",
+    }
+}
+
+fn describe_stderr(stderr: &str) -> String {
+    let stderr = stderr.trim();
+    if stderr.is_empty() {
+        String::new()
+    } else {
+        format!(": {}", stderr)
+    }
+}
+
+fn describe_tool(program: &str) -> String {
+    match crate::doctor::used_for(program) {
+        Some(feature) => format!(" (needed for {})", feature),
+        None => String::new(),
+    }
+}
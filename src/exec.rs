@@ -0,0 +1,166 @@
+//! External process invocation, behind a trait so backends can be driven by
+//! canned output in tests.
+
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// What a finished external command produced.
+#[derive(Debug, Clone, Default)]
+pub struct CommandOutput {
+    /// Whether the process exited with status 0.
+    pub success: bool,
+    /// Exit status, unless the process was killed by a signal.
+    pub code: Option<i32>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// Runs external programs on behalf of a backend.
+pub trait CommandExecutor: Send + Sync {
+    /// Runs `program` with `args` to completion, capturing its output.
+    ///
+    /// An `Err` means the program couldn't be started at all; a non-zero
+    /// exit is reported through [`CommandOutput::success`].
+    fn run(&self, program: &str, args: &[&str]) -> io::Result<CommandOutput>;
+
+    /// Like [`run`](Self::run), streaming `input` to the program's stdin.
+    fn run_with_stdin(
+        &self,
+        program: &str,
+        args: &[&str],
+        input: &mut dyn Read,
+    ) -> io::Result<CommandOutput> {
+        let _ = (program, args, input);
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "executor cannot feed stdin",
+        ))
+    }
+
+    /// Like [`run`](Self::run), streaming stdout into `out` instead of
+    /// buffering it; [`CommandOutput::stdout`] is left empty.
+    fn run_to_writer(
+        &self,
+        program: &str,
+        args: &[&str],
+        out: &mut dyn Write,
+    ) -> io::Result<CommandOutput> {
+        let output = self.run(program, args)?;
+        out.write_all(&output.stdout)?;
+        Ok(CommandOutput {
+            stdout: Vec::new(),
+            ..output
+        })
+    }
+}
+
+/// Splits what a program writes into lines and hands each to `f`, for
+/// following a tool's progress output through
+/// [`run_to_writer`](CommandExecutor::run_to_writer). A carriage return ends
+/// a line too, since progress bars redraw themselves with one.
+pub(crate) struct LineWriter<F> {
+    pending: Vec<u8>,
+    f: F,
+}
+
+impl<F: FnMut(&str) -> io::Result<()>> LineWriter<F> {
+    pub(crate) fn new(f: F) -> Self {
+        LineWriter {
+            pending: Vec::new(),
+            f,
+        }
+    }
+}
+
+impl<F: FnMut(&str) -> io::Result<()>> Write for LineWriter<F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        while let Some(end) = self.pending.iter().position(|&b| b == b'\n' || b == b'\r') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            (self.f)(String::from_utf8_lossy(&line).trim_end())?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Spawns real processes with [`std::process::Command`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemExecutor;
+
+impl CommandExecutor for SystemExecutor {
+    fn run(&self, program: &str, args: &[&str]) -> io::Result<CommandOutput> {
+        let output = Command::new(program).args(args).output()?;
+        Ok(CommandOutput {
+            success: output.status.success(),
+            code: output.status.code(),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        })
+    }
+
+    fn run_with_stdin(
+        &self,
+        program: &str,
+        args: &[&str],
+        input: &mut dyn Read,
+    ) -> io::Result<CommandOutput> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            io::copy(input, &mut stdin)?;
+        }
+        let output = child.wait_with_output()?;
+        Ok(CommandOutput {
+            success: output.status.success(),
+            code: output.status.code(),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        })
+    }
+
+    fn run_to_writer(
+        &self,
+        program: &str,
+        args: &[&str],
+        out: &mut dyn Write,
+    ) -> io::Result<CommandOutput> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdout) = child.stdout.take() {
+            io::copy(&mut stdout, out)?;
+        }
+        let output = child.wait_with_output()?;
+        Ok(CommandOutput {
+            success: output.status.success(),
+            code: output.status.code(),
+            stdout: Vec::new(),
+            stderr: output.stderr,
+        })
+    }
+}
+
+/// Finds `program` in the directories listed in `PATH`, like `which(1)`.
+pub fn which(program: &str) -> Option<PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(program))
+        .find(|candidate| {
+            candidate
+                .metadata()
+                .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        })
+}
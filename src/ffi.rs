@@ -0,0 +1,227 @@
+//! C ABI for embedding the engine in non-Rust front ends. Enabled with the
+//! `ffi` feature; the matching header is `include/lsusb.h`.
+//!
+//! Strings returned by this API are owned by the caller and must be released
+//! with [`lsusb_string_free`]. Functions that fail return NULL or a negative
+//! value and leave a message for [`lsusb_last_error`].
+
+use crate::UsbError;
+use crate::cancel::CancelToken;
+use crate::progress::ProgressEvent;
+use crate::transfer::{self, TransferOptions};
+use crate::usb;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+use std::path::PathBuf;
+use std::ptr;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: impl ToString) {
+    let message = CString::new(message.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+fn to_c_string(s: String) -> *mut c_char {
+    match CString::new(s) {
+        Ok(s) => s.into_raw(),
+        Err(e) => {
+            set_last_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+unsafe fn path_arg(arg: *const c_char, what: &str) -> Option<PathBuf> {
+    if arg.is_null() {
+        set_last_error(format!("{} is NULL", what));
+        return None;
+    }
+    match unsafe { CStr::from_ptr(arg) }.to_str() {
+        Ok(s) => Some(PathBuf::from(s)),
+        Err(_) => {
+            set_last_error(format!("{} is not valid UTF-8", what));
+            None
+        }
+    }
+}
+
+/// Message describing the last failure on this thread, or NULL. The pointer
+/// stays valid until the next failing call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn lsusb_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// Releases a string returned by this library. NULL is ignored.
+///
+/// # Safety
+/// `s` must come from this library and not have been freed already.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lsusb_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+/// Attached USB devices as a JSON array, or NULL on failure.
+#[unsafe(no_mangle)]
+pub extern "C" fn lsusb_devices_json() -> *mut c_char {
+    let json = usb::get_usb_devices().and_then(|devices| {
+        serde_json::to_string(&devices).map_err(|e| UsbError::parse("device list", e))
+    });
+    match json {
+        Ok(json) => to_c_string(json),
+        Err(e) => {
+            set_last_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Snapshot of a transfer's progress, filled in by [`lsusb_transfer_poll`].
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LsusbProgress {
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub files_done: u64,
+    pub files_total: u64,
+}
+
+/// Transfer states returned by [`lsusb_transfer_poll`].
+pub const LSUSB_TRANSFER_RUNNING: i32 = 0;
+pub const LSUSB_TRANSFER_DONE: i32 = 1;
+pub const LSUSB_TRANSFER_FAILED: i32 = -1;
+pub const LSUSB_TRANSFER_CANCELLED: i32 = -2;
+
+/// A copy running on a background thread. Opaque to C.
+pub struct LsusbTransfer {
+    progress: Arc<Mutex<LsusbProgress>>,
+    cancel: CancelToken,
+    handle: Option<JoinHandle<crate::Result<()>>>,
+    outcome: Option<Outcome>,
+}
+
+enum Outcome {
+    Done,
+    Cancelled,
+    Failed(String),
+}
+
+/// Starts copying `source` to `dest` in the background. Returns NULL if the
+/// arguments are invalid.
+///
+/// # Safety
+/// `source` and `dest` must be NUL-terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lsusb_transfer_start(
+    source: *const c_char,
+    dest: *const c_char,
+) -> *mut LsusbTransfer {
+    let (Some(source), Some(dest)) = (unsafe { path_arg(source, "source") }, unsafe {
+        path_arg(dest, "dest")
+    }) else {
+        return ptr::null_mut();
+    };
+
+    let progress = Arc::new(Mutex::new(LsusbProgress::default()));
+    let options = TransferOptions::default();
+    let cancel = options.cancel.clone();
+    let shared = progress.clone();
+    let handle = thread::spawn(move || {
+        transfer::copy_with_progress(&source, &dest, &options, |event| {
+            let mut p = shared.lock().unwrap_or_else(|e| e.into_inner());
+            match event {
+                ProgressEvent::Totals { bytes, files } => {
+                    p.bytes_total = bytes;
+                    p.files_total = files;
+                }
+                ProgressEvent::File { .. } => p.files_done += 1,
+                ProgressEvent::Bytes { done, .. } => p.bytes_done = done,
+                ProgressEvent::Phase(_) | ProgressEvent::Skipped { .. } => {}
+            }
+        })
+        .map(|_| ())
+    });
+
+    Box::into_raw(Box::new(LsusbTransfer {
+        progress,
+        cancel,
+        handle: Some(handle),
+        outcome: None,
+    }))
+}
+
+/// Copies the current progress into `out` (if not NULL) and returns one of
+/// the `LSUSB_TRANSFER_*` states. After `LSUSB_TRANSFER_FAILED`,
+/// [`lsusb_last_error`] describes the failure.
+///
+/// # Safety
+/// `transfer` must come from [`lsusb_transfer_start`] and not be freed;
+/// `out` must be NULL or point to writable memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lsusb_transfer_poll(
+    transfer: *mut LsusbTransfer,
+    out: *mut LsusbProgress,
+) -> i32 {
+    let Some(transfer) = (unsafe { transfer.as_mut() }) else {
+        set_last_error("transfer is NULL");
+        return LSUSB_TRANSFER_FAILED;
+    };
+    if !out.is_null() {
+        unsafe { *out = *transfer.progress.lock().unwrap_or_else(|e| e.into_inner()) };
+    }
+
+    if transfer.handle.as_ref().is_some_and(|h| h.is_finished()) {
+        let result = transfer.handle.take().map(|h| h.join());
+        transfer.outcome = Some(match result {
+            Some(Ok(Ok(()))) => Outcome::Done,
+            Some(Ok(Err(UsbError::Cancelled))) => Outcome::Cancelled,
+            Some(Ok(Err(e))) => Outcome::Failed(e.to_string()),
+            _ => Outcome::Failed("transfer thread panicked".to_string()),
+        });
+    }
+
+    match &transfer.outcome {
+        None => LSUSB_TRANSFER_RUNNING,
+        Some(Outcome::Done) => LSUSB_TRANSFER_DONE,
+        Some(Outcome::Cancelled) => LSUSB_TRANSFER_CANCELLED,
+        Some(Outcome::Failed(message)) => {
+            set_last_error(message);
+            LSUSB_TRANSFER_FAILED
+        }
+    }
+}
+
+/// Asks the transfer to stop; poll until it reports `LSUSB_TRANSFER_CANCELLED`.
+///
+/// # Safety
+/// `transfer` must come from [`lsusb_transfer_start`] and not be freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lsusb_transfer_cancel(transfer: *mut LsusbTransfer) {
+    if let Some(transfer) = unsafe { transfer.as_ref() } {
+        transfer.cancel.cancel();
+    }
+}
+
+/// Cancels the transfer if it is still running, waits for it, and frees it.
+///
+/// # Safety
+/// `transfer` must come from [`lsusb_transfer_start`] and not be freed
+/// already. NULL is ignored.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lsusb_transfer_free(transfer: *mut LsusbTransfer) {
+    if transfer.is_null() {
+        return;
+    }
+    let mut transfer = unsafe { Box::from_raw(transfer) };
+    transfer.cancel.cancel();
+    if let Some(handle) = transfer.handle.take() {
+        let _ = handle.join();
+    }
+}
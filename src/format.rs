@@ -0,0 +1,310 @@
+//! Formatting a whole stick as one partition, with the partition and the
+//! filesystem's clusters lined up with the flash underneath.
+//!
+//! Flash is written in pages and erased in much larger blocks, typically 1
+//! to 4 MiB on sticks and SD cards. A partition that starts off such a
+//! boundary makes every cluster straddle two pages, and many writes cost
+//! twice. The partition therefore starts and ends on a multiple of the
+//! alignment (1 MiB unless asked otherwise), and the cluster size defaults
+//! to what the SD Association's and Microsoft's formatters pick for the
+//! size of the stick. Disks past the 2 TiB an MBR partition table can
+//! address get a GPT one instead.
+
+use crate::backend::{check_output, run};
+use crate::error::{Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use crate::rescan::Rescanner;
+use crate::usb::{UsbDevice, partition_name};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+const SECTOR: u64 = 512;
+const KIB: u64 = 1024;
+const MIB: u64 = 1024 * KIB;
+const GIB: u64 = 1024 * MIB;
+/// The alignment used unless another is asked for.
+pub const DEFAULT_ALIGN: u64 = MIB;
+/// An MBR partition table can't address past 2 TiB.
+const MAX_SECTORS: u64 = u32::MAX as u64;
+/// The sectors a GPT keeps at the end of the disk for its backup copy.
+const GPT_BACKUP_SECTORS: u64 = 33;
+
+/// A filesystem [`Formatter`] can make.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filesystem {
+    Fat32,
+    Exfat,
+    Ntfs,
+    Ext4,
+}
+
+impl Filesystem {
+    /// What SD cards come formatted with: FAT32 up to 32 GiB, exFAT
+    /// above.
+    pub fn default_for(bytes: u64) -> Filesystem {
+        if bytes <= 32 * GIB {
+            Filesystem::Fat32
+        } else {
+            Filesystem::Exfat
+        }
+    }
+
+    /// The cluster size used for a `bytes`-byte partition when none is
+    /// asked for.
+    pub fn default_cluster(self, bytes: u64) -> u64 {
+        match self {
+            Filesystem::Fat32 => match bytes {
+                b if b <= 8 * GIB => 4 * KIB,
+                b if b <= 16 * GIB => 8 * KIB,
+                b if b <= 32 * GIB => 16 * KIB,
+                _ => 32 * KIB,
+            },
+            Filesystem::Exfat => match bytes {
+                b if b <= 256 * MIB => 4 * KIB,
+                b if b <= 32 * GIB => 32 * KIB,
+                _ => 128 * KIB,
+            },
+            Filesystem::Ntfs | Filesystem::Ext4 => 4 * KIB,
+        }
+    }
+
+    /// The cluster sizes the filesystem, or its mkfs, allows.
+    fn cluster_range(self) -> (u64, u64) {
+        match self {
+            Filesystem::Fat32 => (SECTOR, 64 * KIB),
+            Filesystem::Exfat => (SECTOR, 32 * MIB),
+            Filesystem::Ntfs => (SECTOR, 64 * KIB),
+            Filesystem::Ext4 => (KIB, 4 * KIB),
+        }
+    }
+
+    /// The MBR partition type.
+    fn partition_type(self) -> &'static str {
+        match self {
+            Filesystem::Fat32 => "c",
+            Filesystem::Exfat | Filesystem::Ntfs => "7",
+            Filesystem::Ext4 => "83",
+        }
+    }
+
+    /// The GPT partition type: Microsoft basic data, or Linux filesystem
+    /// data.
+    fn gpt_partition_type(self) -> &'static str {
+        match self {
+            Filesystem::Fat32 | Filesystem::Exfat | Filesystem::Ntfs => {
+                "EBD0A0A2-B9E5-4433-87C0-68B6B72699C7"
+            }
+            Filesystem::Ext4 => "0FC63DAF-8483-4772-8E79-3D69D8477DE4",
+        }
+    }
+}
+
+impl fmt::Display for Filesystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Filesystem::Fat32 => "vfat",
+            Filesystem::Exfat => "exfat",
+            Filesystem::Ntfs => "ntfs",
+            Filesystem::Ext4 => "ext4",
+        })
+    }
+}
+
+impl FromStr for Filesystem {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "vfat" | "fat32" | "fat" => Ok(Filesystem::Fat32),
+            "exfat" => Ok(Filesystem::Exfat),
+            "ntfs" => Ok(Filesystem::Ntfs),
+            "ext4" => Ok(Filesystem::Ext4),
+            other => Err(format!(
+                "unknown filesystem '{}' (expected vfat, exfat, ntfs or ext4)",
+                other
+            )),
+        }
+    }
+}
+
+/// How to format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    pub filesystem: Filesystem,
+    pub label: Option<String>,
+    /// Cluster (allocation unit) size in bytes; the filesystem's default
+    /// for the stick's size if `None`.
+    pub cluster: Option<u64>,
+    /// Where the partition starts and ends a multiple of, in bytes.
+    pub align: u64,
+}
+
+impl Options {
+    /// `filesystem` with no label, the default cluster size and 1 MiB
+    /// alignment.
+    pub fn new(filesystem: Filesystem) -> Options {
+        Options {
+            filesystem,
+            label: None,
+            cluster: None,
+            align: DEFAULT_ALIGN,
+        }
+    }
+}
+
+/// What [`Formatter::format`] settled on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    /// The `sfdisk` script.
+    pub script: String,
+    /// Size of the partition in bytes.
+    pub bytes: u64,
+    /// Cluster size in bytes.
+    pub cluster: u64,
+}
+
+/// The partition table and cluster size for a disk of `bytes` bytes:
+/// one partition from the first alignment boundary to the last whole one,
+/// in an MBR table, or a GPT one when the disk is too big for MBR.
+pub fn plan(bytes: u64, options: &Options) -> Result<Plan> {
+    let align = options.align;
+    if !align.is_power_of_two() || !(4 * KIB..=64 * MIB).contains(&align) {
+        return Err(UsbError::Unsupported(format!(
+            "alignment must be a power of two from 4 KiB to 64 MiB, not {} bytes",
+            align
+        )));
+    }
+    let align_sectors = align / SECTOR;
+    let sectors = bytes / SECTOR;
+    let (label, usable, partition_type) = if sectors > MAX_SECTORS {
+        (
+            "gpt",
+            sectors - GPT_BACKUP_SECTORS,
+            options.filesystem.gpt_partition_type(),
+        )
+    } else {
+        ("dos", sectors, options.filesystem.partition_type())
+    };
+    let size = usable.saturating_sub(align_sectors) / align_sectors * align_sectors;
+    if size == 0 {
+        return Err(UsbError::Unsupported(format!(
+            "the disk is too small to align to {} bytes",
+            align
+        )));
+    }
+    let partition_bytes = size * SECTOR;
+    let cluster = options
+        .cluster
+        .unwrap_or_else(|| options.filesystem.default_cluster(partition_bytes));
+    let (min, max) = options.filesystem.cluster_range();
+    if !cluster.is_power_of_two() || !(min..=max).contains(&cluster) {
+        return Err(UsbError::Unsupported(format!(
+            "{} clusters must be a power of two from {} to {} bytes, not {}",
+            options.filesystem, min, max, cluster
+        )));
+    }
+    Ok(Plan {
+        script: format!(
+            "label: {}\nunit: sectors\n\nstart={}, size={}, type={}\n",
+            label, align_sectors, size, partition_type
+        ),
+        bytes: partition_bytes,
+        cluster,
+    })
+}
+
+/// The mkfs command line that makes `options.filesystem` on `node` with
+/// `cluster`-byte clusters.
+pub fn mkfs_command(node: &str, options: &Options, cluster: u64) -> (&'static str, Vec<String>) {
+    let (program, mut args, label_flag) = match options.filesystem {
+        Filesystem::Fat32 => (
+            "mkfs.vfat",
+            vec![
+                "-F".to_string(),
+                "32".into(),
+                "-s".into(),
+                (cluster / SECTOR).to_string(),
+            ],
+            "-n",
+        ),
+        Filesystem::Exfat => (
+            "mkfs.exfat",
+            vec!["-c".to_string(), cluster.to_string()],
+            "-L",
+        ),
+        Filesystem::Ntfs => (
+            "mkfs.ntfs",
+            vec!["-Q".to_string(), "-c".into(), cluster.to_string()],
+            "-L",
+        ),
+        Filesystem::Ext4 => (
+            "mkfs.ext4",
+            vec!["-q".to_string(), "-b".into(), cluster.to_string()],
+            "-L",
+        ),
+    };
+    if let Some(label) = &options.label {
+        args.extend([label_flag.to_string(), label.clone()]);
+    }
+    args.push(node.to_string());
+    (program, args)
+}
+
+/// Repartitions and formats with `sfdisk` and the mkfs tools.
+#[derive(Clone)]
+pub struct Formatter {
+    exec: Arc<dyn CommandExecutor>,
+    rescanner: Rescanner,
+}
+
+impl Default for Formatter {
+    fn default() -> Self {
+        Formatter::with_executor(Arc::new(SystemExecutor))
+    }
+}
+
+impl Formatter {
+    /// Uses `exec` to run the partitioning and formatting tools.
+    pub fn with_executor(exec: Arc<dyn CommandExecutor>) -> Self {
+        Formatter {
+            rescanner: Rescanner::with_executor(exec.clone()),
+            exec,
+        }
+    }
+
+    /// Waits for the new partition's node in `dir` instead of `/dev`.
+    pub fn dev_dir(mut self, dir: PathBuf) -> Self {
+        self.rescanner = self.rescanner.dev_dir(dir);
+        self
+    }
+
+    /// Erases `device` and formats it as one partition following
+    /// `options`. Returns the partition's name, e.g. `sdb1`, and what was
+    /// planned. Nothing on `device` may be mounted.
+    pub fn format(&self, device: &UsbDevice, options: &Options) -> Result<(String, Plan)> {
+        if let Some(mountpoint) = device.mountpoints().next() {
+            return Err(UsbError::Unsupported(format!(
+                "{} is mounted at {}; unmount it first",
+                device.name, mountpoint
+            )));
+        }
+        let plan = plan(device.bytes, options)?;
+        let disk = format!("/dev/{}", device.name);
+        let partition = partition_name(&device.name, 1);
+        check_output(
+            "sfdisk",
+            self.exec.run_with_stdin(
+                "sfdisk",
+                &["--wipe", "always", &disk],
+                &mut plan.script.as_bytes(),
+            ),
+        )?;
+        self.rescanner.rescan(&device.name, &[&partition])?;
+        let (program, args) = mkfs_command(&format!("/dev/{}", partition), options, plan.cluster);
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        run(self.exec.as_ref(), program, &args)?;
+        Ok((partition, plan))
+    }
+}
@@ -0,0 +1,157 @@
+//! Remembered file hashes, so checking a large backup again doesn't mean
+//! reading every byte of it again.
+//!
+//! A hash is keyed by the file's path, size and modification time and only
+//! reused while all three still match. That catches files that were
+//! replaced, edited or truncated, but not media that decayed underneath an
+//! unchanged directory entry; only reading the data catches that, so
+//! callers use the cache when asked to and not by default.
+//!
+//! The cache is one JSON file on the host, merged and rewritten under an
+//! exclusive `flock(2)` like the drive statistics.
+
+use crate::error::{IoContext, Result, UsbError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// What a hash was computed for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Entry {
+    size: u64,
+    /// Modification time, seconds and nanoseconds since the epoch.
+    secs: u64,
+    nanos: u32,
+    sha256: String,
+}
+
+impl Entry {
+    fn stamp(metadata: &Metadata) -> Option<(u64, u32)> {
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some((modified.as_secs(), modified.subsec_nanos()))
+    }
+}
+
+/// The hash cache, loaded into memory; [`save`](HashCache::save) writes
+/// what was added back.
+#[derive(Debug, Clone, Default)]
+pub struct HashCache {
+    path: PathBuf,
+    entries: BTreeMap<String, Entry>,
+    added: BTreeMap<String, Entry>,
+}
+
+impl HashCache {
+    /// Loads the cache at `path`; a missing file is an empty cache.
+    pub fn open(path: impl Into<PathBuf>) -> Result<HashCache> {
+        let path = path.into();
+        let entries = match fs::read_to_string(&path) {
+            Ok(text) => parse(&path, &text)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => {
+                return Err(UsbError::io(
+                    format!("Failed to read {}", path.display()),
+                    e,
+                ));
+            }
+        };
+        Ok(HashCache {
+            path,
+            entries,
+            added: BTreeMap::new(),
+        })
+    }
+
+    /// `$XDG_STATE_HOME/lsusb/hashes.json`, falling back to
+    /// `~/.local/state/lsusb/hashes.json`.
+    pub fn default_path() -> Option<PathBuf> {
+        crate::audit::AuditLog::default_path().map(|log| log.with_file_name("hashes.json"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The SHA-256 in hex recorded for `path`, if its size and modification
+    /// time are still those in `metadata`.
+    pub fn get(&self, path: &Path, metadata: &Metadata) -> Option<&str> {
+        let entry = self.entries.get(&key(path))?;
+        let (secs, nanos) = Entry::stamp(metadata)?;
+        (entry.size == metadata.len() && entry.secs == secs && entry.nanos == nanos)
+            .then_some(entry.sha256.as_str())
+    }
+
+    /// Records `sha256` as the hash of `path` as described by `metadata`.
+    pub fn insert(&mut self, path: &Path, metadata: &Metadata, sha256: String) {
+        let Some((secs, nanos)) = Entry::stamp(metadata) else {
+            return;
+        };
+        let entry = Entry {
+            size: metadata.len(),
+            secs,
+            nanos,
+            sha256,
+        };
+        self.entries.insert(key(path), entry.clone());
+        self.added.insert(key(path), entry);
+    }
+
+    /// Merges what was inserted into the file, keeping what other processes
+    /// added in the meantime.
+    pub fn save(&mut self) -> Result<()> {
+        use std::os::fd::AsRawFd;
+
+        if self.added.is_empty() {
+            return Ok(());
+        }
+        let context = || format!("Failed to update {}", self.path.display());
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).io_context(context)?;
+        }
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)
+            .io_context(context)?;
+        // Released when the file is closed.
+        while unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() != Some(libc::EINTR) {
+                return Err(UsbError::io(context(), e));
+            }
+        }
+        let mut text = String::new();
+        file.read_to_string(&mut text).io_context(context)?;
+        let mut entries = if text.trim().is_empty() {
+            BTreeMap::new()
+        } else {
+            parse(&self.path, &text)?
+        };
+        entries.append(&mut self.added);
+        let json = serde_json::to_vec(&entries).map_err(|e| UsbError::parse("hash cache", e))?;
+        file.rewind()
+            .and_then(|()| file.set_len(0))
+            .and_then(|()| file.write_all(&json))
+            .io_context(context)?;
+        self.entries = entries;
+        Ok(())
+    }
+}
+
+/// Absolute, so the same file reached by different relative paths is one
+/// entry.
+fn key(path: &Path) -> String {
+    std::path::absolute(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn parse(path: &Path, text: &str) -> Result<BTreeMap<String, Entry>> {
+    serde_json::from_str(text).map_err(|e| UsbError::parse(path.display().to_string(), e))
+}
@@ -0,0 +1,68 @@
+//! Reading raw bytes off a device and printing them the way `hexdump -C`
+//! does, for looking at boot sectors and checking that a wipe left zeros.
+
+use crate::error::{IoContext, Result};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Bytes per output line.
+const WIDTH: usize = 16;
+
+/// Reads up to `length` bytes of `path` (usually `/dev/sdX`) starting at
+/// `offset`; less if the device ends first.
+pub fn read_range(path: &Path, offset: u64, length: u64) -> Result<Vec<u8>> {
+    let mut file = File::open(path).io_context(|| format!("Failed to open {}", path.display()))?;
+    file.seek(SeekFrom::Start(offset))
+        .io_context(|| format!("Failed to seek {} to {}", path.display(), offset))?;
+    let mut data = Vec::new();
+    file.take(length)
+        .read_to_end(&mut data)
+        .io_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(data)
+}
+
+/// Formats `data`, which was read from `offset`, as offset, hex and ASCII
+/// columns. Runs of identical lines are collapsed into a single `*`, so a
+/// zeroed megabyte takes three lines.
+pub fn format(data: &[u8], offset: u64) -> String {
+    let mut out = String::new();
+    let mut previous: Option<&[u8]> = None;
+    let mut collapsed = false;
+    for (i, line) in data.chunks(WIDTH).enumerate() {
+        if previous == Some(line) && line.len() == WIDTH {
+            if !collapsed {
+                out.push_str("*\n");
+                collapsed = true;
+            }
+            continue;
+        }
+        previous = Some(line);
+        collapsed = false;
+
+        let _ = write!(out, "{:08x} ", offset + (i * WIDTH) as u64);
+        for column in 0..WIDTH {
+            if column % 8 == 0 {
+                out.push(' ');
+            }
+            match line.get(column) {
+                Some(byte) => {
+                    let _ = write!(out, "{:02x} ", byte);
+                }
+                None => out.push_str("   "),
+            }
+        }
+        out.push_str(" |");
+        out.extend(line.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        }));
+        out.push_str("|\n");
+    }
+    let _ = writeln!(out, "{:08x}", offset + data.len() as u64);
+    out
+}
@@ -0,0 +1,135 @@
+//! Translations of the text lsusb shows people: menus, prompts, table
+//! headers and status messages, kept as [Fluent](https://projectfluent.org)
+//! files under `locales/`, one per language, and compiled in.
+//!
+//! The language comes from `LANGUAGE`, `LC_ALL`, `LC_MESSAGES` or `LANG`,
+//! the first that is set, as gettext picks it: `de_DE.UTF-8` selects
+//! German. Messages a translation lacks, and every language without one,
+//! fall back to English. Error messages from the library stay in English,
+//! so they can be searched for.
+//!
+//! Front ends look messages up with [`tr!`](crate::tr).
+
+use fluent_bundle::FluentResource;
+use fluent_bundle::concurrent::FluentBundle;
+use std::sync::OnceLock;
+use unic_langid::LanguageIdentifier;
+
+pub use fluent_bundle::FluentArgs;
+
+/// The bundled translations: language and Fluent source. English first;
+/// it is what every other falls back to.
+pub const LOCALES: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en/lsusb.ftl")),
+    ("de", include_str!("../locales/de/lsusb.ftl")),
+];
+
+/// A language's messages, with English behind them.
+pub struct Translations {
+    language: &'static str,
+    bundle: Option<FluentBundle<FluentResource>>,
+    english: FluentBundle<FluentResource>,
+}
+
+impl Translations {
+    /// The translations for `language` (e.g. `de`), or only English when
+    /// there are none for it.
+    pub fn new(language: &str) -> Translations {
+        let (language, bundle) = match LOCALES.iter().skip(1).find(|(lang, _)| *lang == language) {
+            Some(&(lang, source)) => (lang, Some(load(lang, source))),
+            None => ("en", None),
+        };
+        Translations {
+            language,
+            bundle,
+            english: load("en", LOCALES[0].1),
+        }
+    }
+
+    /// The language used, `en` if there was no translation.
+    pub fn language(&self) -> &str {
+        self.language
+    }
+
+    /// Message `key` with `args` filled in; the key itself if no bundle
+    /// has it.
+    pub fn message(&self, key: &str, args: Option<&FluentArgs>) -> String {
+        self.bundle
+            .iter()
+            .chain(std::iter::once(&self.english))
+            .find_map(|bundle| {
+                let pattern = bundle.get_message(key)?.value()?;
+                let mut errors = Vec::new();
+                Some(
+                    bundle
+                        .format_pattern(pattern, args, &mut errors)
+                        .into_owned(),
+                )
+            })
+            .unwrap_or_else(|| key.to_string())
+    }
+}
+
+fn load(language: &str, source: &'static str) -> FluentBundle<FluentResource> {
+    let id: LanguageIdentifier = language.parse().expect("bundled locales have valid names");
+    let mut bundle = FluentBundle::new_concurrent(vec![id]);
+    // Unicode isolation marks show up as junk in most terminals.
+    bundle.set_use_isolating(false);
+    let resource = FluentResource::try_new(source.to_string()).expect("bundled translations parse");
+    bundle
+        .add_resource(resource)
+        .expect("bundled translations have no duplicate messages");
+    bundle
+}
+
+/// The language asked for by the environment, as a bare language code
+/// (`de` for `de_DE.UTF-8`); `en` when nothing is set, or for `C` and
+/// `POSIX`.
+pub fn detect() -> String {
+    ["LANGUAGE", "LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+        .map_or_else(|| "en".to_string(), |value| language_of(&value))
+}
+
+/// The language code of a locale name such as `pt_BR.UTF-8@euro`; the
+/// first of a `LANGUAGE` list such as `de:en`.
+pub fn language_of(locale: &str) -> String {
+    let first = locale.split(':').next().unwrap_or(locale);
+    let language = first
+        .split(['_', '.', '@', '-'])
+        .next()
+        .unwrap_or(first)
+        .to_ascii_lowercase();
+    match language.as_str() {
+        "" | "c" | "posix" => "en".to_string(),
+        _ => language,
+    }
+}
+
+static CURRENT: OnceLock<Translations> = OnceLock::new();
+
+/// The translations [`tr!`](crate::tr) uses: those for the environment's
+/// language (see [`detect`]), loaded on first use.
+pub fn current() -> &'static Translations {
+    CURRENT.get_or_init(|| Translations::new(&detect()))
+}
+
+/// Message `key` in the current language, with the arguments given as
+/// `name = value`:
+///
+/// ```
+/// let _ = lsusb::tr!("no-usb-devices");
+/// let _ = lsusb::tr!("unmounting", mountpoint = "/media/usb");
+/// ```
+#[macro_export]
+macro_rules! tr {
+    ($key:expr) => {
+        $crate::i18n::current().message($key, None)
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = $crate::i18n::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::i18n::current().message($key, Some(&args))
+    }};
+}
@@ -0,0 +1,155 @@
+//! `.lsusbignore` files: gitignore-style patterns, read from the source
+//! directories of `cp` and `backup run`, for files that shouldn't end up on
+//! removable media (build output, caches, virtualenvs).
+//!
+//! A file's patterns apply to its own directory and everything below it.
+//! Later patterns override earlier ones, and those of a deeper file override
+//! its parents'. As with git, a file inside an ignored directory can't be
+//! brought back with `!`, since the directory isn't read at all.
+
+use crate::error::{IoContext, Result};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Name of the ignore files looked for in every source directory.
+pub const IGNORE_FILE: &str = ".lsusbignore";
+
+/// What a walk leaves out: global patterns (the config's `ignore` list)
+/// plus those of the [`IGNORE_FILE`]s it finds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ignore {
+    rules: Vec<Rule>,
+    read_files: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    /// Directory of the file the rule came from, relative to the walk root.
+    base: PathBuf,
+    pattern: String,
+    negated: bool,
+    dir_only: bool,
+    /// Matched against the path from `base` rather than the name alone.
+    anchored: bool,
+}
+
+impl Default for Ignore {
+    /// Honours [`IGNORE_FILE`]s, with no global patterns.
+    fn default() -> Self {
+        Ignore::new(&[])
+    }
+}
+
+impl Ignore {
+    /// Honours [`IGNORE_FILE`]s, and leaves out whatever `patterns` match
+    /// anywhere in the walk.
+    pub fn new(patterns: &[String]) -> Self {
+        let mut ignore = Ignore {
+            rules: Vec::new(),
+            read_files: true,
+        };
+        ignore.add(Path::new(""), &patterns.join("\n"));
+        ignore
+    }
+
+    /// Leaves nothing out and reads no files.
+    pub fn none() -> Self {
+        Ignore {
+            rules: Vec::new(),
+            read_files: false,
+        }
+    }
+
+    /// Adds the patterns in `text`, in `.lsusbignore` syntax, for the
+    /// directory at `base` (relative to the walk root).
+    pub fn add(&mut self, base: &Path, text: &str) {
+        for line in text.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.strip_prefix('\\').unwrap_or(line)),
+            };
+            let dir_only = line.ends_with('/');
+            let pattern = line.trim_end_matches('/');
+            if pattern.is_empty() {
+                continue;
+            }
+            self.rules.push(Rule {
+                base: base.to_path_buf(),
+                anchored: pattern.contains('/'),
+                pattern: pattern.trim_start_matches('/').to_string(),
+                negated,
+                dir_only,
+            });
+        }
+    }
+
+    /// Reads the [`IGNORE_FILE`] in `dir`, which is at `relative` from the
+    /// walk root, if there is one.
+    pub fn load(&mut self, dir: &Path, relative: &Path) -> Result<()> {
+        if !self.read_files {
+            return Ok(());
+        }
+        let path = dir.join(IGNORE_FILE);
+        match fs::read_to_string(&path) {
+            Ok(text) => {
+                self.add(relative, &text);
+                Ok(())
+            }
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::NotFound | io::ErrorKind::NotADirectory
+                ) =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(e).io_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    /// Whether the file or directory at `relative` from the walk root is
+    /// left out.
+    pub fn is_ignored(&self, relative: &Path, is_dir: bool) -> bool {
+        let mut ignored = false;
+        for rule in &self.rules {
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+            let Ok(below) = relative.strip_prefix(&rule.base) else {
+                continue;
+            };
+            let matched = if rule.anchored {
+                glob(rule.pattern.as_bytes(), below.to_string_lossy().as_bytes())
+            } else {
+                below.file_name().is_some_and(|name| {
+                    glob(rule.pattern.as_bytes(), name.to_string_lossy().as_bytes())
+                })
+            };
+            if matched {
+                ignored = !rule.negated;
+            }
+        }
+        ignored
+    }
+}
+
+/// Matches `text` against `pattern` with `*`, `**` and `?` wildcards.
+pub(crate) fn glob(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', rest @ ..] => {
+            let rest = rest.strip_prefix(b"/").unwrap_or(rest);
+            (0..=text.len()).any(|i| glob(rest, &text[i..]))
+        }
+        [b'*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != b'/')
+            .any(|i| glob(rest, &text[i..])),
+        [b'?', rest @ ..] => text.first().is_some_and(|&c| c != b'/') && glob(rest, &text[1..]),
+        [c, rest @ ..] => text.first() == Some(c) && glob(rest, &text[1..]),
+    }
+}
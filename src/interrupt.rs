@@ -0,0 +1,43 @@
+//! Ctrl-C handling: while an operation runs, SIGINT cancels it instead of
+//! killing the process mid-write.
+
+use lsusb::CancelToken;
+use std::sync::OnceLock;
+
+static TOKEN: OnceLock<CancelToken> = OnceLock::new();
+
+extern "C" fn on_sigint(_: libc::c_int) {
+    if let Some(token) = TOKEN.get() {
+        token.cancel();
+    }
+}
+
+/// Routes Ctrl-C to a [`CancelToken`] until dropped, then restores the
+/// previous SIGINT disposition so prompts can be interrupted as usual.
+pub struct InterruptGuard {
+    token: CancelToken,
+    previous: libc::sighandler_t,
+}
+
+impl InterruptGuard {
+    pub fn token(&self) -> CancelToken {
+        self.token.clone()
+    }
+}
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        unsafe {
+            libc::signal(libc::SIGINT, self.previous);
+        }
+    }
+}
+
+/// Starts routing Ctrl-C to a fresh (un-cancelled) token.
+pub fn guard() -> InterruptGuard {
+    let token = TOKEN.get_or_init(CancelToken::new).clone();
+    token.reset();
+    let previous =
+        unsafe { libc::signal(libc::SIGINT, on_sigint as *const () as libc::sighandler_t) };
+    InterruptGuard { token, previous }
+}
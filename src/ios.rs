@@ -0,0 +1,109 @@
+//! iPhones and iPads.
+//!
+//! iOS devices don't show up as USB mass storage; their media partition
+//! (the camera roll, `DCIM`) is reached over usbmuxd with
+//! [libimobiledevice](https://libimobiledevice.org). This module lists them
+//! with `idevice_id`/`ideviceinfo` and mounts the media partition with
+//! `ifuse`.
+
+use crate::backend::{MountOptions, run};
+use crate::error::{IoContext, Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor, which};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+/// An iOS device attached over USB.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IosDevice {
+    pub udid: String,
+    /// The name set on the device, e.g. `Alice's iPhone`.
+    pub name: Option<String>,
+    /// `iPhone`, `iPad` or `iPod`.
+    pub class: Option<String>,
+    /// Hardware model identifier, e.g. `iPhone14,5`.
+    pub product_type: Option<String>,
+    /// iOS version, e.g. `17.4.1`.
+    pub version: Option<String>,
+}
+
+impl IosDevice {
+    /// Whether `ideviceinfo` could read anything, which needs the device to
+    /// be unlocked and to trust this computer.
+    pub fn is_paired(&self) -> bool {
+        self.product_type.is_some()
+    }
+}
+
+/// Whether libimobiledevice's tools are installed.
+pub fn is_available() -> bool {
+    which("idevice_id").is_some()
+}
+
+/// Runs the libimobiledevice tools and ifuse.
+#[derive(Clone)]
+pub struct Ios {
+    exec: Arc<dyn CommandExecutor>,
+}
+
+impl Default for Ios {
+    fn default() -> Self {
+        Ios::with_executor(Arc::new(SystemExecutor))
+    }
+}
+
+impl Ios {
+    /// Uses `exec` to run every external command.
+    pub fn with_executor(exec: Arc<dyn CommandExecutor>) -> Self {
+        Ios { exec }
+    }
+
+    /// Devices attached over USB (network-paired ones are left out).
+    pub fn devices(&self) -> Result<Vec<IosDevice>> {
+        let output = run(self.exec.as_ref(), "idevice_id", &["-l"])?;
+        let mut devices = Vec::new();
+        for udid in String::from_utf8_lossy(&output.stdout).split_whitespace() {
+            // An untrusted device still gets listed, just without details.
+            let info = run(self.exec.as_ref(), "ideviceinfo", &["-u", udid])
+                .map(|o| String::from_utf8_lossy(&o.stdout).into_owned())
+                .unwrap_or_default();
+            let key = |name: &str| {
+                info.lines()
+                    .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+                    .map(|value| value.trim().to_string())
+            };
+            devices.push(IosDevice {
+                udid: udid.to_string(),
+                name: key("DeviceName"),
+                class: key("DeviceClass"),
+                product_type: key("ProductType"),
+                version: key("ProductVersion"),
+            });
+        }
+        Ok(devices)
+    }
+
+    /// Mounts the media partition of the device `udid` on `mountpoint`,
+    /// creating the directory if needed.
+    pub fn mount(&self, udid: &str, mountpoint: &Path, options: MountOptions) -> Result<()> {
+        fs::create_dir_all(mountpoint)
+            .io_context(|| format!("Failed to create {}", mountpoint.display()))?;
+        let target = mountpoint
+            .to_str()
+            .ok_or_else(|| UsbError::Unsupported("Mountpoint is not valid UTF-8".into()))?;
+        let mut args = vec![target, "--udid", udid];
+        if options.read_only {
+            args.extend(["-o", "ro"]);
+        }
+        run(self.exec.as_ref(), "ifuse", &args).map(|_| ())
+    }
+
+    /// Unmounts an ifuse mount.
+    pub fn unmount(&self, mountpoint: &str) -> Result<()> {
+        if cfg!(target_os = "linux") {
+            run(self.exec.as_ref(), "fusermount", &["-u", mountpoint]).map(|_| ())
+        } else {
+            run(self.exec.as_ref(), "umount", &[mountpoint]).map(|_| ())
+        }
+    }
+}
@@ -0,0 +1,761 @@
+//! Reading ISO 9660 and UDF images in-process, for `lsusb iso ls` and
+//! `lsusb iso extract`: what is on an installer image, and single files out
+//! of it, without a loop mount or root.
+//!
+//! ISO 9660 is read with Rock Ridge names when the image has them, else
+//! with Joliet's, else with the plain 8.3-style ones; files over 4 GiB,
+//! recorded as several extents, are joined up. UDF (the Windows installer
+//! images, DVDs) is read from version 1.02 to 2.01; the metadata
+//! partitions of UDF 2.50 and later aren't supported. An image with both
+//! is read as UDF, which on hybrid images is the complete view.
+
+use crate::error::{IoContext, Result, UsbError};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+
+const SECTOR: u64 = 2048;
+/// Larger directories than this are taken to be corrupt.
+const MAX_DIRECTORY: u64 = 64 << 20;
+
+/// Which filesystem, and for ISO 9660 which names, an [`Image`] is read
+/// with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Iso9660,
+    Joliet,
+    RockRidge,
+    Udf,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Iso9660 => "ISO 9660",
+            Format::Joliet => "ISO 9660 with Joliet names",
+            Format::RockRidge => "ISO 9660 with Rock Ridge names",
+            Format::Udf => "UDF",
+        })
+    }
+}
+
+/// A file or directory in an [`Image`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Where it is from the image's root, e.g. `boot/grub/grub.cfg`; empty
+    /// for the root itself.
+    pub path: PathBuf,
+    pub is_dir: bool,
+    /// In bytes; what a directory's records take up, for directories.
+    pub size: u64,
+    data: Data,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Data {
+    Extents(Vec<Extent>),
+    /// UDF keeps small files in their file entry.
+    Inline(Vec<u8>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Extent {
+    /// Byte offset in the image.
+    offset: u64,
+    len: u64,
+    /// Allocated but never written: reads as zeros.
+    sparse: bool,
+}
+
+/// An ISO 9660 or UDF image, opened for reading.
+#[derive(Debug)]
+pub struct Image<R> {
+    reader: R,
+    format: Format,
+    label: Option<String>,
+    root: Entry,
+    udf: Option<Udf>,
+}
+
+#[derive(Debug)]
+struct Udf {
+    /// Where each partition of the logical volume starts, in bytes, by
+    /// partition reference number.
+    partitions: Vec<u64>,
+    block: u64,
+}
+
+/// [`Image::open`] on the image file (or device) at `path`.
+pub fn open_path(path: &Path) -> Result<Image<File>> {
+    let file = File::open(path).io_context(|| format!("Failed to open {}", path.display()))?;
+    Image::open(file)
+}
+
+impl<R: Read + Seek> Image<R> {
+    /// Reads the volume descriptors of the image in `reader`.
+    pub fn open(mut reader: R) -> Result<Image<R>> {
+        if let Some(image) = open_udf(&mut reader)? {
+            let (label, root, udf) = image;
+            return Ok(Image {
+                reader,
+                format: Format::Udf,
+                label,
+                root,
+                udf: Some(udf),
+            });
+        }
+        let (format, label, root) = open_iso9660(&mut reader)?;
+        Ok(Image {
+            reader,
+            format,
+            label,
+            root,
+            udf: None,
+        })
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// The volume name, if it has one.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// The file or directory at `path` (relative to the image's root;
+    /// `/boot` and `boot` are the same).
+    pub fn find(&mut self, path: &Path) -> Result<Entry> {
+        let mut entry = self.root.clone();
+        for component in path.components() {
+            match component {
+                Component::RootDir | Component::CurDir => continue,
+                Component::Normal(name) => {
+                    if !entry.is_dir {
+                        return Err(not_found(path));
+                    }
+                    entry = self
+                        .list(&entry)?
+                        .into_iter()
+                        .find(|child| child.path.file_name() == Some(name))
+                        .ok_or_else(|| not_found(path))?;
+                }
+                _ => {
+                    return Err(UsbError::Unsupported(format!(
+                        "{} isn't a path inside the image",
+                        path.display()
+                    )));
+                }
+            }
+        }
+        Ok(entry)
+    }
+
+    /// What is in directory `dir`, sorted by name.
+    pub fn list(&mut self, dir: &Entry) -> Result<Vec<Entry>> {
+        if dir.size > MAX_DIRECTORY {
+            return Err(corrupt(format!(
+                "directory {} is {} bytes",
+                dir.path.display(),
+                dir.size
+            )));
+        }
+        let mut records = Vec::new();
+        self.read_to(dir, &mut records)?;
+        let mut entries = match self.udf.as_ref() {
+            Some(udf) => udf_directory(&mut self.reader, udf, &dir.path, &records)?,
+            None => iso_directory(&dir.path, &records, self.format),
+        };
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(entries)
+    }
+
+    /// `dir` and everything below it, each directory before what is in it.
+    pub fn walk(&mut self, dir: &Entry) -> Result<Vec<Entry>> {
+        let mut found = Vec::new();
+        // Directories already listed, by where their records are, so a
+        // corrupt image that links a directory into itself ends.
+        let mut seen = HashSet::new();
+        let mut pending = vec![dir.clone()];
+        while let Some(entry) = pending.pop() {
+            if entry.is_dir && seen.insert(entry.data.clone()) {
+                let children = self.list(&entry)?;
+                pending.extend(children.into_iter().rev());
+            }
+            found.push(entry);
+        }
+        Ok(found)
+    }
+
+    /// Writes what file `entry` holds to `out`, returning how many bytes
+    /// that was.
+    pub fn read_to(&mut self, entry: &Entry, out: &mut impl Write) -> Result<u64> {
+        let context = || format!("Failed to read {} from the image", entry.path.display());
+        let extents = match &entry.data {
+            Data::Inline(data) => {
+                let len = (data.len() as u64).min(entry.size);
+                out.write_all(&data[..len as usize]).io_context(context)?;
+                return Ok(len);
+            }
+            Data::Extents(extents) => extents,
+        };
+        let mut left = entry.size;
+        let mut buf = vec![0u8; 1 << 20];
+        for extent in extents {
+            let mut extent_left = extent.len.min(left);
+            left -= extent_left;
+            if !extent.sparse {
+                self.reader
+                    .seek(SeekFrom::Start(extent.offset))
+                    .io_context(context)?;
+            }
+            while extent_left > 0 {
+                let chunk = &mut buf[..extent_left.min(1 << 20) as usize];
+                if extent.sparse {
+                    chunk.fill(0);
+                } else {
+                    self.reader.read_exact(chunk).io_context(context)?;
+                }
+                out.write_all(chunk).io_context(context)?;
+                extent_left -= chunk.len() as u64;
+            }
+        }
+        Ok(entry.size - left)
+    }
+
+    /// Copies `entry`, and everything below it if it's a directory, into
+    /// the directory `dest`. Returns the number of files and bytes written.
+    pub fn extract(&mut self, entry: &Entry, dest: &Path) -> Result<(u64, u64)> {
+        let base = entry.path.parent().unwrap_or(Path::new(""));
+        let (mut files, mut bytes) = (0, 0);
+        for item in self.walk(entry)? {
+            let relative = item.path.strip_prefix(base).unwrap_or(&item.path);
+            let target = dest.join(relative);
+            if item.is_dir {
+                fs::create_dir_all(&target)
+                    .io_context(|| format!("Failed to create {}", target.display()))?;
+                continue;
+            }
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)
+                    .io_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+            let file = File::create(&target)
+                .io_context(|| format!("Failed to create {}", target.display()))?;
+            let mut out = io::BufWriter::new(file);
+            bytes += self.read_to(&item, &mut out)?;
+            out.flush()
+                .io_context(|| format!("Failed to write {}", target.display()))?;
+            files += 1;
+        }
+        Ok((files, bytes))
+    }
+
+    /// The root directory.
+    pub fn root(&self) -> &Entry {
+        &self.root
+    }
+}
+
+fn not_found(path: &Path) -> UsbError {
+    UsbError::Unsupported(format!("{} isn't in the image", path.display()))
+}
+
+fn corrupt(message: impl ToString) -> UsbError {
+    UsbError::parse("ISO image", message)
+}
+
+fn read_at(reader: &mut (impl Read + Seek), offset: u64, len: usize) -> Result<Option<Vec<u8>>> {
+    let context = || "Failed to read the image".to_string();
+    reader.seek(SeekFrom::Start(offset)).io_context(context)?;
+    let mut buf = vec![0u8; len];
+    match reader.read_exact(&mut buf) {
+        Ok(()) => Ok(Some(buf)),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(UsbError::io(context(), e)),
+    }
+}
+
+fn le16(buf: &[u8]) -> u16 {
+    u16::from_le_bytes([buf[0], buf[1]])
+}
+
+fn le32(buf: &[u8]) -> u32 {
+    u32::from_le_bytes(buf[..4].try_into().unwrap())
+}
+
+fn le64(buf: &[u8]) -> u64 {
+    u64::from_le_bytes(buf[..8].try_into().unwrap())
+}
+
+/// A name from the image as one path component: never empty, `.`, `..`
+/// or containing a slash, so extracting can't write outside its target.
+fn component(name: String) -> Option<String> {
+    let name = name.replace(['/', '\0'], "_");
+    (!name.is_empty() && name != "." && name != "..").then_some(name)
+}
+
+// ISO 9660.
+
+fn open_iso9660(reader: &mut (impl Read + Seek)) -> Result<(Format, Option<String>, Entry)> {
+    let mut primary = None;
+    let mut joliet = None;
+    for sector in 16..64 {
+        let Some(descriptor) = read_at(reader, sector * SECTOR, SECTOR as usize)? else {
+            break;
+        };
+        if &descriptor[1..6] != b"CD001" {
+            break;
+        }
+        match descriptor[0] {
+            1 if primary.is_none() => primary = Some(descriptor),
+            // A supplementary descriptor with a UCS-2 escape sequence.
+            2 if matches!(&descriptor[88..91], b"%/@" | b"%/C" | b"%/E") => {
+                joliet = Some(descriptor)
+            }
+            255 => break,
+            _ => {}
+        }
+    }
+    let primary = primary.ok_or_else(|| corrupt("no ISO 9660 or UDF volume descriptor"))?;
+    let root = |descriptor: &[u8]| Entry {
+        path: PathBuf::new(),
+        is_dir: true,
+        size: u64::from(le32(&descriptor[156 + 10..])),
+        data: Data::Extents(vec![Extent {
+            offset: u64::from(le32(&descriptor[156 + 2..])) * SECTOR,
+            len: u64::from(le32(&descriptor[156 + 10..])),
+            sparse: false,
+        }]),
+    };
+    let primary_root = root(&primary);
+    if has_rock_ridge(reader, &primary_root)? {
+        return Ok((Format::RockRidge, iso_text(&primary[40..72]), primary_root));
+    }
+    match joliet {
+        Some(joliet) => Ok((Format::Joliet, ucs2(&joliet[40..72]), root(&joliet))),
+        None => Ok((Format::Iso9660, iso_text(&primary[40..72]), primary_root)),
+    }
+}
+
+/// Whether the root's `.` record starts with the SUSP `SP` entry that
+/// announces Rock Ridge.
+fn has_rock_ridge(reader: &mut (impl Read + Seek), root: &Entry) -> Result<bool> {
+    let Data::Extents(extents) = &root.data else {
+        return Ok(false);
+    };
+    let Some(record) = read_at(reader, extents[0].offset, 256)? else {
+        return Ok(false);
+    };
+    let len = usize::from(record[0]);
+    let system_use = 34;
+    Ok(len >= system_use + 7
+        && &record[system_use..system_use + 2] == b"SP"
+        && record[system_use + 4..system_use + 6] == [0xbe, 0xef])
+}
+
+fn iso_directory(dir: &Path, records: &[u8], format: Format) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut at = 0;
+    // Whether the last record was a multi-extent part still to be joined.
+    let mut continued = false;
+    while at < records.len() {
+        let len = usize::from(records[at]);
+        if len == 0 {
+            // Records don't cross sectors; the rest of this one is padding.
+            at = (at / SECTOR as usize + 1) * SECTOR as usize;
+            continue;
+        }
+        if at + len > records.len() || len < 34 {
+            break;
+        }
+        let record = &records[at..at + len];
+        at += len;
+        let name_len = usize::from(record[32]);
+        if 33 + name_len > record.len() {
+            continue;
+        }
+        let raw = &record[33..33 + name_len];
+        // `.` and `..`.
+        if raw == [0] || raw == [1] {
+            continue;
+        }
+        let flags = record[25];
+        let is_dir = flags & 0x02 != 0;
+        let extent = Extent {
+            offset: u64::from(le32(&record[2..])) * SECTOR,
+            len: u64::from(le32(&record[10..])),
+            sparse: false,
+        };
+        let system_use = &record[(33 + name_len + (1 - name_len % 2)).min(record.len())..];
+        let name = match format {
+            Format::RockRidge => rock_ridge_name(system_use).or_else(|| iso_name(raw, is_dir)),
+            Format::Joliet => ucs2(raw).map(|n| strip_version(&n, is_dir)),
+            _ => iso_name(raw, is_dir),
+        };
+        let Some(name) = name.and_then(component) else {
+            continue;
+        };
+        let path = dir.join(&name);
+        if continued && let Some(last) = entries.last_mut().filter(|e| e.path == path) {
+            if let Data::Extents(extents) = &mut last.data {
+                extents.push(extent);
+            }
+            last.size += extent.len;
+        } else {
+            entries.push(Entry {
+                path,
+                is_dir,
+                size: extent.len,
+                data: Data::Extents(vec![extent]),
+            });
+        }
+        continued = flags & 0x80 != 0;
+    }
+    entries
+}
+
+fn iso_name(raw: &[u8], is_dir: bool) -> Option<String> {
+    Some(strip_version(&String::from_utf8_lossy(raw), is_dir))
+}
+
+/// `README.TXT;1` as `README.TXT`, and `NOEXT.;1` as `NOEXT`.
+fn strip_version(name: &str, is_dir: bool) -> String {
+    if is_dir {
+        return name.to_string();
+    }
+    let name = name.rsplit_once(';').map_or(name, |(name, _)| name);
+    name.strip_suffix('.').unwrap_or(name).to_string()
+}
+
+/// The Rock Ridge `NM` entries of a record's system use area, joined.
+fn rock_ridge_name(mut area: &[u8]) -> Option<String> {
+    let mut name = Vec::new();
+    while area.len() >= 4 {
+        let len = usize::from(area[2]);
+        if len < 4 || len > area.len() {
+            break;
+        }
+        if &area[..2] == b"NM" && len >= 5 {
+            // Bits 1 and 2 mean `.` and `..`; those records are skipped.
+            if area[4] & 0x06 != 0 {
+                return None;
+            }
+            name.extend_from_slice(&area[5..len]);
+        }
+        area = &area[len..];
+    }
+    (!name.is_empty()).then(|| String::from_utf8_lossy(&name).into_owned())
+}
+
+fn iso_text(field: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(field)
+        .trim_end_matches([' ', '\0'])
+        .to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+fn ucs2(field: &[u8]) -> Option<String> {
+    let units: Vec<u16> = field
+        .chunks_exact(2)
+        .map(|c| u16::from_be_bytes([c[0], c[1]]))
+        .collect();
+    let text = String::from_utf16_lossy(&units)
+        .trim_end_matches([' ', '\0'])
+        .to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+// UDF.
+
+const TAG_AVDP: u16 = 2;
+const TAG_PARTITION: u16 = 5;
+const TAG_LOGICAL_VOLUME: u16 = 6;
+const TAG_TERMINATING: u16 = 8;
+const TAG_FILE_SET: u16 = 256;
+const TAG_FILE_ID: u16 = 257;
+const TAG_ALLOCATION_EXTENT: u16 = 258;
+const TAG_FILE_ENTRY: u16 = 261;
+const TAG_EXTENDED_FILE_ENTRY: u16 = 266;
+/// The largest logical block size accepted.
+const MAX_UDF_BLOCK: u64 = 64 * 1024;
+
+/// Whether `buf` starts with a descriptor tag `id` whose checksum holds.
+fn is_tag(buf: &[u8], id: u16) -> bool {
+    if buf.len() < 16 || le16(buf) != id {
+        return false;
+    }
+    let sum = buf[..16]
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i != 4)
+        .fold(0u8, |sum, (_, &b)| sum.wrapping_add(b));
+    sum == buf[4]
+}
+
+/// Where a UDF `long_ad` points: block `block` of partition `partition`.
+#[derive(Debug, Clone, Copy)]
+struct LongAd {
+    block: u32,
+    partition: u16,
+}
+
+impl LongAd {
+    fn parse(buf: &[u8]) -> LongAd {
+        LongAd {
+            block: le32(&buf[4..]),
+            partition: le16(&buf[8..]),
+        }
+    }
+}
+
+impl Udf {
+    fn offset(&self, partition: u16, block: u32) -> Result<u64> {
+        let start = self
+            .partitions
+            .get(usize::from(partition))
+            .ok_or_else(|| corrupt(format!("partition reference {} doesn't exist", partition)))?;
+        u64::from(block)
+            .checked_mul(self.block)
+            .and_then(|offset| offset.checked_add(*start))
+            .ok_or_else(|| {
+                corrupt(format!(
+                    "block {} of partition {} is past any image",
+                    block, partition
+                ))
+            })
+    }
+}
+
+fn open_udf(reader: &mut (impl Read + Seek)) -> Result<Option<(Option<String>, Entry, Udf)>> {
+    let Some(anchor) = read_at(reader, 256 * SECTOR, SECTOR as usize)? else {
+        return Ok(None);
+    };
+    if !is_tag(&anchor, TAG_AVDP) {
+        return Ok(None);
+    }
+    let (vds_len, vds_at) = (
+        u64::from(le32(&anchor[16..])),
+        u64::from(le32(&anchor[20..])),
+    );
+    let mut partitions = Vec::new();
+    let mut volume = None;
+    for sector in vds_at..vds_at + (vds_len / SECTOR).min(64) {
+        let Some(descriptor) = read_at(reader, sector * SECTOR, SECTOR as usize)? else {
+            break;
+        };
+        if is_tag(&descriptor, TAG_PARTITION) {
+            partitions.push((
+                le16(&descriptor[22..]),
+                u64::from(le32(&descriptor[188..])) * SECTOR,
+            ));
+        } else if is_tag(&descriptor, TAG_LOGICAL_VOLUME) {
+            volume.get_or_insert(descriptor);
+        } else if is_tag(&descriptor, TAG_TERMINATING) {
+            break;
+        }
+    }
+    let volume = volume.ok_or_else(|| corrupt("UDF image without a logical volume descriptor"))?;
+    // Partition maps name the partitions by number; references index them.
+    let mut starts = Vec::new();
+    let mut maps = &volume[440..];
+    for _ in 0..le32(&volume[268..]) {
+        // Type 1 maps are 6 bytes, the others longer.
+        if maps.len() < 6 || usize::from(maps[1]) > maps.len() || maps[1] < 6 {
+            return Err(corrupt("a UDF partition map is cut short"));
+        }
+        if maps[0] != 1 {
+            return Err(UsbError::Unsupported(
+                "UDF metadata, sparable and virtual partitions (UDF 2.50 and later, rewritable discs) aren't supported"
+                    .to_string(),
+            ));
+        }
+        let number = le16(&maps[4..]);
+        let start = partitions
+            .iter()
+            .find(|(n, _)| *n == number)
+            .map(|(_, start)| *start)
+            .ok_or_else(|| corrupt(format!("UDF partition {} has no descriptor", number)))?;
+        starts.push(start);
+        maps = &maps[usize::from(maps[1])..];
+    }
+    let block = u64::from(le32(&volume[212..]));
+    if !block.is_power_of_two() || !(512..=MAX_UDF_BLOCK).contains(&block) {
+        return Err(corrupt(format!("UDF logical block size {}", block)));
+    }
+    let udf = Udf {
+        partitions: starts,
+        block,
+    };
+    let file_set = LongAd::parse(&volume[248..]);
+    let at = udf.offset(file_set.partition, file_set.block)?;
+    let file_set = read_at(reader, at, SECTOR as usize)?.filter(|d| is_tag(d, TAG_FILE_SET));
+    let file_set = file_set.ok_or_else(|| corrupt("UDF file set descriptor not found"))?;
+    let root = udf_entry(
+        reader,
+        &udf,
+        PathBuf::new(),
+        LongAd::parse(&file_set[400..]),
+    )?;
+    Ok(Some((dstring(&volume[84..212]), root, udf)))
+}
+
+/// Reads the (extended) file entry at `icb`.
+fn udf_entry(
+    reader: &mut (impl Read + Seek),
+    udf: &Udf,
+    path: PathBuf,
+    icb: LongAd,
+) -> Result<Entry> {
+    let bad = |what: &str| corrupt(format!("{} of {}", what, path.display()));
+    let at = udf.offset(icb.partition, icb.block)?;
+    let entry =
+        read_at(reader, at, udf.block as usize)?.ok_or_else(|| bad("file entry past the end"))?;
+    let ad_start = if is_tag(&entry, TAG_FILE_ENTRY) {
+        176
+    } else if is_tag(&entry, TAG_EXTENDED_FILE_ENTRY) {
+        216
+    } else {
+        return Err(bad("no file entry"));
+    };
+    let is_dir = entry[27] == 4;
+    let size = le64(&entry[56..]);
+    let ea_len = le32(&entry[ad_start - 8..]) as usize;
+    let ad_len = le32(&entry[ad_start - 4..]) as usize;
+    let ads = entry
+        .get(ad_start + ea_len..ad_start + ea_len + ad_len)
+        .ok_or_else(|| bad("allocation descriptors past the file entry"))?
+        .to_vec();
+    let data = match le16(&entry[34..]) & 7 {
+        0 => Data::Extents(udf_extents(reader, udf, icb.partition, ads, false)?),
+        1 => Data::Extents(udf_extents(reader, udf, icb.partition, ads, true)?),
+        3 => Data::Inline(ads),
+        other => return Err(bad(&format!("allocation descriptor type {}", other))),
+    };
+    Ok(Entry {
+        path,
+        is_dir,
+        size,
+        data,
+    })
+}
+
+/// The extents in a list of short (`long` false) or long allocation
+/// descriptors, following continuations.
+fn udf_extents(
+    reader: &mut (impl Read + Seek),
+    udf: &Udf,
+    partition: u16,
+    mut ads: Vec<u8>,
+    long: bool,
+) -> Result<Vec<Extent>> {
+    let size = if long { 16 } else { 8 };
+    let mut extents = Vec::new();
+    let mut at = 0;
+    // Continuations followed so far; a loop of them is corruption.
+    let mut hops = 0;
+    while at + size <= ads.len() {
+        let ad = &ads[at..at + size];
+        at += size;
+        let raw = le32(ad);
+        let (kind, len) = (raw >> 30, u64::from(raw & 0x3fff_ffff));
+        if len == 0 {
+            break;
+        }
+        let (block, partition) = if long {
+            (le32(&ad[4..]), le16(&ad[8..]))
+        } else {
+            (le32(&ad[4..]), partition)
+        };
+        let offset = udf.offset(partition, block)?;
+        if kind == 3 {
+            hops += 1;
+            // An allocation extent descriptor fills at most one block.
+            let len = len.min(udf.block) as usize;
+            if len < 24 {
+                return Err(corrupt("a UDF allocation extent is cut short"));
+            }
+            let next = read_at(reader, offset, len)?
+                .filter(|d| is_tag(d, TAG_ALLOCATION_EXTENT) && hops < 1024);
+            let next = next.ok_or_else(|| corrupt("a UDF allocation extent is missing"))?;
+            let next_len = (le32(&next[20..]) as usize).min(len - 24);
+            ads = next[24..24 + next_len].to_vec();
+            at = 0;
+            continue;
+        }
+        extents.push(Extent {
+            offset,
+            len,
+            sparse: kind != 0,
+        });
+    }
+    Ok(extents)
+}
+
+fn udf_directory(
+    reader: &mut (impl Read + Seek),
+    udf: &Udf,
+    dir: &Path,
+    records: &[u8],
+) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    let mut at = 0;
+    while at + 38 <= records.len() {
+        let fid = &records[at..];
+        if !is_tag(fid, TAG_FILE_ID) {
+            break;
+        }
+        let characteristics = fid[18];
+        let name_len = usize::from(fid[19]);
+        let use_len = usize::from(le16(&fid[36..]));
+        let len = (38 + use_len + name_len).div_ceil(4) * 4;
+        if at + 38 + use_len + name_len > records.len() {
+            break;
+        }
+        at += len;
+        // Deleted, or the parent.
+        if characteristics & 0x0c != 0 {
+            continue;
+        }
+        let Some(name) = cs0(&fid[38 + use_len..38 + use_len + name_len]).and_then(component)
+        else {
+            continue;
+        };
+        entries.push(udf_entry(
+            reader,
+            udf,
+            dir.join(name),
+            LongAd::parse(&fid[20..]),
+        )?);
+    }
+    Ok(entries)
+}
+
+/// OSTA compressed Unicode: a byte saying 8 or 16 bits per character, then
+/// the characters.
+fn cs0(bytes: &[u8]) -> Option<String> {
+    let (&kind, chars) = bytes.split_first()?;
+    let text = match kind {
+        8 => chars.iter().map(|&b| char::from(b)).collect(),
+        16 => {
+            let units: Vec<u16> = chars
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => return None,
+    };
+    Some(text)
+}
+
+/// A fixed-size `dstring` field: [`cs0`] text whose length is in the last
+/// byte.
+fn dstring(field: &[u8]) -> Option<String> {
+    let len = usize::from(*field.last()?).min(field.len() - 1);
+    cs0(&field[..len])
+        .map(|s| s.trim_end().to_string())
+        .filter(|s| !s.is_empty())
+}
@@ -0,0 +1,458 @@
+//! Jobs this process is running against removable media: copies, backups,
+//! restores and verifications, so unmounting or ejecting a device can first
+//! check whether lsusb itself is still using it instead of pulling the
+//! filesystem out from under its own transfer.
+//!
+//! The engines register themselves; front ends look jobs up by mountpoint
+//! and can wait for them or cancel them.
+//!
+//! Other lsusb processes can see them too, for a copy started in a terminal
+//! that is now buried somewhere: once [`JobSockets::publish`] is called,
+//! the first job opens a Unix socket that answers [`JobSockets::list`] and
+//! [`JobSockets::cancel`].
+
+use crate::cancel::CancelToken;
+use crate::error::{IoContext, Result, UsbError};
+use crate::progress::{Phase, ProgressEvent, ProgressSink, Throughput};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+static JOBS: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+/// Notified whenever a job ends.
+static ENDED: Condvar = Condvar::new();
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+/// Where [`JobSockets::publish`] asked for the socket to go.
+static PUBLISH: OnceLock<PathBuf> = OnceLock::new();
+/// Set once the socket is up (or failed to come up).
+static SERVING: OnceLock<()> = OnceLock::new();
+
+/// How long either end of the socket waits for the other.
+const SOCKET_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A running job.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: u64,
+    /// What it does: `copy`, `backup`, `restore`, `verify` or `flash`.
+    pub kind: String,
+    /// The files or directories it reads or writes.
+    pub paths: Vec<PathBuf>,
+    pub started: SystemTime,
+    /// Whether [`cancel`] can stop it; a firmware flash can't be.
+    pub cancellable: bool,
+    /// Bytes done so far, of `bytes_total`, as last reported by the engine.
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    /// The file it is working on.
+    pub file: Option<PathBuf>,
+    /// Average bytes per second since the data started moving.
+    pub rate: u64,
+    /// Bytes per second over each recent second, oldest first, for a
+    /// [`sparkline`](crate::progress::sparkline).
+    #[serde(default)]
+    pub history: Vec<u64>,
+}
+
+impl Job {
+    /// Whether the job uses anything under `mountpoint`.
+    pub fn uses(&self, mountpoint: &Path) -> bool {
+        let mountpoint = absolute(mountpoint);
+        self.paths.iter().any(|path| path.starts_with(&mountpoint))
+    }
+}
+
+impl fmt::Display for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let paths: Vec<String> = self.paths.iter().map(|p| p.display().to_string()).collect();
+        write!(f, "{} ({})", self.kind, paths.join(" -> "))
+    }
+}
+
+struct Entry {
+    job: Job,
+    cancel: Option<CancelToken>,
+    progress: Arc<Mutex<Progress>>,
+}
+
+impl Entry {
+    fn snapshot(&self) -> Job {
+        let progress = self.progress.lock().unwrap_or_else(|e| e.into_inner());
+        let secs = progress
+            .copying_since
+            .map_or(0.0, |since| since.elapsed().as_secs_f64());
+        Job {
+            bytes_done: progress.done,
+            bytes_total: progress.total,
+            file: progress.file.clone(),
+            rate: if secs > 0.0 {
+                (progress.done as f64 / secs) as u64
+            } else {
+                0
+            },
+            history: progress.meter.history(),
+            ..self.job.clone()
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Progress {
+    done: u64,
+    total: u64,
+    file: Option<PathBuf>,
+    copying_since: Option<Instant>,
+    meter: Throughput,
+}
+
+/// Keeps a job registered until it is dropped.
+#[derive(Debug)]
+#[must_use = "the job ends when the guard is dropped"]
+pub struct JobGuard {
+    id: u64,
+    progress: Arc<Mutex<Progress>>,
+}
+
+impl JobGuard {
+    /// Wraps the engine's progress sink, so the job's progress shows up in
+    /// [`running`] and [`JobSockets::list`].
+    pub fn track<S: ProgressSink>(&self, sink: S) -> Tracked<S> {
+        Tracked {
+            progress: Arc::clone(&self.progress),
+            sink,
+        }
+    }
+}
+
+/// A [`ProgressSink`] that records progress for its job on the way through;
+/// see [`JobGuard::track`].
+#[derive(Debug)]
+pub struct Tracked<S> {
+    progress: Arc<Mutex<Progress>>,
+    sink: S,
+}
+
+impl<S: ProgressSink> ProgressSink for Tracked<S> {
+    fn emit(&mut self, event: ProgressEvent) {
+        {
+            let mut progress = self.progress.lock().unwrap_or_else(|e| e.into_inner());
+            progress.meter.record(&event);
+            match &event {
+                ProgressEvent::Phase(Phase::Copying) => {
+                    progress.copying_since = Some(Instant::now())
+                }
+                ProgressEvent::Totals { bytes, .. } => progress.total = *bytes,
+                ProgressEvent::File { path, .. } => progress.file = Some(path.clone()),
+                ProgressEvent::Bytes { done, total } => {
+                    progress.done = *done;
+                    progress.total = *total;
+                }
+                _ => {}
+            }
+        }
+        self.sink.emit(event);
+    }
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        lock().retain(|entry| entry.job.id != self.id);
+        ENDED.notify_all();
+    }
+}
+
+/// Registers a `kind` job on `paths`, which [`cancel`] stops through
+/// `cancel`; `None` for jobs that must not be interrupted.
+pub fn start(kind: &str, paths: &[&Path], cancel: Option<&CancelToken>) -> JobGuard {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let progress = Arc::new(Mutex::new(Progress::default()));
+    lock().push(Entry {
+        job: Job {
+            id,
+            kind: kind.to_string(),
+            paths: paths.iter().map(|path| absolute(path)).collect(),
+            started: SystemTime::now(),
+            cancellable: cancel.is_some(),
+            bytes_done: 0,
+            bytes_total: 0,
+            file: None,
+            rate: 0,
+            history: Vec::new(),
+        },
+        cancel: cancel.cloned(),
+        progress: Arc::clone(&progress),
+    });
+    if let Some(dir) = PUBLISH.get() {
+        SERVING.get_or_init(|| {
+            // Best effort: a copy doesn't fail because others can't watch it.
+            let _ = serve(dir);
+        });
+    }
+    JobGuard { id, progress }
+}
+
+/// Every running job, oldest first.
+pub fn running() -> Vec<Job> {
+    lock().iter().map(Entry::snapshot).collect()
+}
+
+/// The running jobs that use anything under `mountpoint`.
+pub fn using(mountpoint: &Path) -> Vec<Job> {
+    lock()
+        .iter()
+        .filter(|entry| entry.job.uses(mountpoint))
+        .map(Entry::snapshot)
+        .collect()
+}
+
+/// Asks the jobs under `mountpoint` to stop; they end with
+/// [`UsbError::Cancelled`] once they notice. Returns how many were asked.
+pub fn cancel(mountpoint: &Path) -> usize {
+    let jobs = lock();
+    let using: Vec<&CancelToken> = jobs
+        .iter()
+        .filter(|entry| entry.job.uses(mountpoint))
+        .filter_map(|entry| entry.cancel.as_ref())
+        .collect();
+    for cancel in &using {
+        cancel.cancel();
+    }
+    using.len()
+}
+
+/// Asks job `id` to stop. Returns whether there is such a job and it can
+/// be cancelled.
+pub fn cancel_id(id: u64) -> bool {
+    let jobs = lock();
+    match jobs
+        .iter()
+        .find(|entry| entry.job.id == id)
+        .and_then(|entry| entry.cancel.as_ref())
+    {
+        Some(cancel) => {
+            cancel.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Waits until no job uses anything under `mountpoint`, for at most
+/// `timeout` if one is given. Returns whether that happened.
+pub fn wait(mountpoint: &Path, timeout: Option<Duration>) -> bool {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut jobs = lock();
+    while jobs.iter().any(|entry| entry.job.uses(mountpoint)) {
+        jobs = match deadline {
+            None => ENDED.wait(jobs).unwrap_or_else(|e| e.into_inner()),
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                if left.is_zero() {
+                    return false;
+                }
+                ENDED
+                    .wait_timeout(jobs, left)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0
+            }
+        };
+    }
+    true
+}
+
+/// Fails with [`UsbError::JobRunning`] if a job uses anything under
+/// `mountpoint`.
+pub fn ensure_idle(mountpoint: &Path) -> Result<()> {
+    match using(mountpoint).into_iter().next() {
+        Some(job) => Err(UsbError::JobRunning {
+            mountpoint: mountpoint.display().to_string(),
+            job: job.to_string(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// A job of another lsusb process, as [`JobSockets::list`] reports it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteJob {
+    pub pid: u32,
+    pub job: Job,
+}
+
+/// The sockets through which lsusb processes share their jobs: one per
+/// process, named after its pid, in a directory only the user can get at.
+#[derive(Debug, Clone)]
+pub struct JobSockets {
+    dir: PathBuf,
+}
+
+impl Default for JobSockets {
+    fn default() -> Self {
+        let dir = match std::env::var_os("XDG_RUNTIME_DIR") {
+            Some(runtime) if !runtime.is_empty() => PathBuf::from(runtime).join("lsusb"),
+            _ => std::env::temp_dir().join(format!("lsusb-{}", unsafe { libc::geteuid() })),
+        };
+        JobSockets::in_dir(dir)
+    }
+}
+
+impl JobSockets {
+    /// Keeps the sockets in `dir` instead of the default directory.
+    pub fn in_dir(dir: PathBuf) -> Self {
+        JobSockets { dir }
+    }
+
+    /// Shares this process's jobs from its first job on. Only the first
+    /// call in a process counts.
+    pub fn publish(&self) {
+        let _ = PUBLISH.set(self.dir.clone());
+    }
+
+    /// The jobs of every lsusb process sharing them, this one included.
+    /// Sockets left behind by processes that have exited are cleaned up.
+    pub fn list(&self) -> Result<Vec<RemoteJob>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(UsbError::io(
+                    format!("Failed to read {}", self.dir.display()),
+                    e,
+                ));
+            }
+        };
+        let mut pids: Vec<u32> = entries
+            .filter_map(|entry| {
+                entry
+                    .ok()?
+                    .file_name()
+                    .to_str()?
+                    .strip_suffix(".sock")?
+                    .parse()
+                    .ok()
+            })
+            .collect();
+        pids.sort_unstable();
+        let mut jobs = Vec::new();
+        for pid in pids {
+            // A process that stops answering doesn't hide the others.
+            let Ok(Some(reply)) = self.request(pid, "list") else {
+                continue;
+            };
+            let listed: Vec<Job> = serde_json::from_str(&reply).map_err(|e| UsbError::Parse {
+                what: format!("jobs of process {}", pid),
+                message: e.to_string(),
+            })?;
+            jobs.extend(listed.into_iter().map(|job| RemoteJob { pid, job }));
+        }
+        Ok(jobs)
+    }
+
+    /// Asks job `id` of process `pid` to stop. Returns whether there is
+    /// such a job and it can be cancelled.
+    pub fn cancel(&self, pid: u32, id: u64) -> Result<bool> {
+        let reply = self
+            .request(pid, &format!("cancel {}", id))
+            .io_context(|| format!("Failed to reach lsusb process {}", pid))?;
+        Ok(reply.is_some_and(|reply| reply.trim() == "true"))
+    }
+
+    /// Sends one request line to process `pid` and reads its reply; `None`
+    /// if the process is gone.
+    fn request(&self, pid: u32, line: &str) -> io::Result<Option<String>> {
+        let path = self.dir.join(format!("{}.sock", pid));
+        let mut stream = match UnixStream::connect(&path) {
+            Ok(stream) => stream,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::ConnectionRefused | io::ErrorKind::NotFound
+                ) =>
+            {
+                let _ = fs::remove_file(&path);
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        };
+        stream.set_read_timeout(Some(SOCKET_TIMEOUT))?;
+        stream.set_write_timeout(Some(SOCKET_TIMEOUT))?;
+        writeln!(stream, "{}", line)?;
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply)?;
+        Ok(Some(reply))
+    }
+}
+
+/// Opens this process's socket in `dir` and answers it on a thread of its
+/// own for as long as the process lives.
+fn serve(dir: &Path) -> Result<()> {
+    create_private_dir(dir)?;
+    let path = dir.join(format!("{}.sock", std::process::id()));
+    // Left behind by an earlier process with the same pid.
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)
+        .io_context(|| format!("Failed to listen on {}", path.display()))?;
+    std::thread::Builder::new()
+        .name("lsusb-jobs".into())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = answer(stream);
+            }
+        })
+        .io_context(|| "Failed to start the jobs socket thread".to_string())?;
+    Ok(())
+}
+
+fn answer(stream: UnixStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(SOCKET_TIMEOUT))?;
+    stream.set_write_timeout(Some(SOCKET_TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let reply = match line.split_whitespace().collect::<Vec<_>>()[..] {
+        ["list"] => serde_json::to_string(&running()).map_err(io::Error::other)?,
+        ["cancel", id] => id.parse().is_ok_and(cancel_id).to_string(),
+        _ => return Ok(()),
+    };
+    (&stream).write_all(reply.as_bytes())
+}
+
+/// Creates `dir` for the user alone and checks that nobody else got there
+/// first, so nobody else can listen in place of our sockets.
+fn create_private_dir(dir: &Path) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    match fs::DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(e) => {
+            return Err(UsbError::io(
+                format!("Failed to create {}", dir.display()),
+                e,
+            ));
+        }
+    }
+    let meta =
+        fs::symlink_metadata(dir).io_context(|| format!("Failed to read {}", dir.display()))?;
+    if !meta.is_dir() || meta.uid() != unsafe { libc::geteuid() } || meta.mode() & 0o022 != 0 {
+        return Err(UsbError::Unsupported(format!(
+            "{} is not a private directory",
+            dir.display()
+        )));
+    }
+    Ok(())
+}
+
+fn lock() -> MutexGuard<'static, Vec<Entry>> {
+    // A job that panicked still removes itself; the list stays usable.
+    JOBS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
@@ -0,0 +1,97 @@
+//! Renaming a partition's filesystem: checking the new label against what
+//! the filesystem can store, then running its own tool (`fatlabel`,
+//! `exfatlabel`, `e2label` or `ntfslabel`) on the device node.
+
+use crate::backend::run;
+use crate::error::{Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use std::sync::Arc;
+
+/// Characters FAT doesn't allow in a volume label.
+const FAT_FORBIDDEN: &str = "\"*+,./:;<=>?[\\]|";
+
+/// Checks `label` against the rules of filesystem `fstype` (as `blkid`
+/// names it) and returns it the way it will be stored: FAT labels are
+/// upper-cased, as DOS and Windows show them.
+pub fn normalize(fstype: &str, label: &str) -> Result<String> {
+    let invalid = |why: String| {
+        Err(UsbError::Unsupported(format!(
+            "Invalid {} label '{}': {}",
+            fstype, label, why
+        )))
+    };
+    if label.is_empty() {
+        return invalid("it is empty".into());
+    }
+    if label.chars().any(char::is_control) {
+        return invalid("it contains control characters".into());
+    }
+    match fstype {
+        "vfat" => {
+            if !label.is_ascii() {
+                return invalid("FAT labels are ASCII only".into());
+            }
+            if let Some(c) = label.chars().find(|c| FAT_FORBIDDEN.contains(*c)) {
+                return invalid(format!("'{}' isn't allowed on FAT", c));
+            }
+            if label.len() > 11 {
+                return invalid("FAT labels are at most 11 characters".into());
+            }
+            Ok(label.to_ascii_uppercase())
+        }
+        "exfat" if label.encode_utf16().count() > 11 => {
+            invalid("exFAT labels are at most 11 characters".into())
+        }
+        "ntfs" if label.encode_utf16().count() > 128 => {
+            invalid("NTFS labels are at most 128 characters".into())
+        }
+        "ext2" | "ext3" | "ext4" if label.len() > 16 => {
+            invalid("ext labels are at most 16 bytes".into())
+        }
+        "exfat" | "ntfs" | "ext2" | "ext3" | "ext4" => Ok(label.to_string()),
+        other => Err(UsbError::Unsupported(format!(
+            "Can't relabel {} filesystems",
+            other
+        ))),
+    }
+}
+
+/// Whether `fstype` can be relabeled while mounted; the others keep their
+/// own idea of the label in memory and would write the old one back.
+pub fn relabels_mounted(fstype: &str) -> bool {
+    matches!(fstype, "ext2" | "ext3" | "ext4")
+}
+
+/// Runs the filesystem tools that set labels.
+#[derive(Clone)]
+pub struct Labeler {
+    exec: Arc<dyn CommandExecutor>,
+}
+
+impl Default for Labeler {
+    fn default() -> Self {
+        Labeler::with_executor(Arc::new(SystemExecutor))
+    }
+}
+
+impl Labeler {
+    /// Uses `exec` to run the tools.
+    pub fn with_executor(exec: Arc<dyn CommandExecutor>) -> Self {
+        Labeler { exec }
+    }
+
+    /// Sets the label of the `fstype` filesystem on `partition` (e.g.
+    /// `sdb1`) to `label`, after [`normalize`]. Returns the label written.
+    pub fn set(&self, partition: &str, fstype: &str, label: &str) -> Result<String> {
+        let label = normalize(fstype, label)?;
+        let program = match fstype {
+            "vfat" => "fatlabel",
+            "exfat" => "exfatlabel",
+            "ntfs" => "ntfslabel",
+            _ => "e2label",
+        };
+        let node = format!("/dev/{}", partition);
+        run(self.exec.as_ref(), program, &[&node, &label])?;
+        Ok(label)
+    }
+}
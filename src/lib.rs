@@ -0,0 +1,89 @@
+//! Core library behind the `lsusb` tool.
+//!
+//! Device types and enumeration, mounting, unmounting and syncing live in
+//! [`usb`], on top of a pluggable [`backend::SystemBackend`]; the recursive
+//! copy engine lives in [`transfer`]. The `lsusb` binary is a
+//! thin CLI/TUI layered on top of these, so other tools can reuse the same
+//! logic without shelling out to it. [`monitor`] reports devices coming and
+//! going, and long operations report through [`progress`].
+//!
+//! With the `async` feature, [`nonblocking`] offers tokio-friendly variants
+//! of enumeration, monitoring and transfers. With the `ffi` feature, [`ffi`]
+//! exposes a small C ABI.
+
+pub mod audit;
+pub mod backend;
+pub mod backup;
+pub mod bench;
+pub mod bootloader;
+pub mod bus;
+pub mod camera;
+pub mod cancel;
+pub mod casefold;
+pub mod config;
+pub mod crypt;
+pub mod desktop;
+pub mod dfu;
+pub mod digest;
+pub mod diskstats;
+pub mod doctor;
+pub mod erase;
+pub mod error;
+pub mod exec;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod format;
+pub mod hashcache;
+pub mod hexdump;
+pub mod i18n;
+pub mod ignore;
+pub mod ios;
+pub mod iso;
+pub mod jobs;
+pub mod label;
+pub mod lock;
+pub mod manifest;
+pub mod monitor;
+pub mod multiboot;
+#[cfg(feature = "async")]
+pub mod nonblocking;
+pub mod notify;
+pub mod policy;
+pub mod porcelain;
+pub mod preflight;
+pub mod privilege;
+pub mod probe;
+pub mod progress;
+pub mod repo;
+pub mod rescan;
+pub mod scan;
+pub mod schedule;
+pub mod sign;
+pub mod smart;
+pub mod stash;
+pub mod stats;
+pub mod template;
+pub mod termux;
+pub mod transfer;
+pub mod trim;
+pub mod usb;
+pub mod veracrypt;
+pub mod wear;
+pub mod writeback;
+pub mod wsl;
+pub mod xattr;
+
+pub use backend::{BackendKind, MountOptions, SystemBackend};
+pub use cancel::CancelToken;
+pub use config::Config;
+pub use error::{Result, UsbError};
+pub use monitor::{DeviceEvent, DeviceMonitor};
+pub use progress::{Phase, ProgressEvent, ProgressSink, Summary, Throughput};
+pub use transfer::{
+    SpanManifest, SpanReport, TransferOptions, TransferReport, copy_mirrored, copy_spanning,
+    copy_to_usb, copy_with_progress,
+};
+pub use usb::{
+    Partition, SizeUnits, SpeedClass, Usage, UsbDevice, find_usb_device, get_usb_devices,
+    mount_partition, parse_size, sync_device, unmount_device, usage,
+};
@@ -0,0 +1,202 @@
+//! Per-device advisory locks, so two lsusb processes can't write to the same
+//! device at once: one formatting a stick while another copies to it.
+//!
+//! Each device has a lock file, `flock(2)`ed for as long as the lock is held
+//! and released by the kernel if the process dies. The holder writes its pid
+//! and what it is doing into the file, for the process that finds the lock
+//! taken to report. The files live in one directory shared by all users
+//! (sticky and world-writable, like `/tmp`), so root's `multiboot init`
+//! and a user's `cp` see each other.
+
+use crate::error::{IoContext, Result, UsbError};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often [`Locks::wait`] retries.
+const RETRY: Duration = Duration::from_millis(200);
+
+/// Who holds a device lock, as written into its lock file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holder {
+    pub pid: u32,
+    /// What it is doing, e.g. `copy to /run/media/user/DISK`.
+    pub operation: String,
+    /// When it took the lock, in seconds since the Unix epoch.
+    pub since: u64,
+}
+
+impl fmt::Display for Holder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (pid {})", self.operation, self.pid)
+    }
+}
+
+/// A held device lock; dropping it releases the lock.
+#[derive(Debug)]
+pub struct DeviceLock {
+    _file: File,
+    device: String,
+}
+
+impl DeviceLock {
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+}
+
+/// The lock files of every device.
+#[derive(Debug, Clone)]
+pub struct Locks {
+    dir: PathBuf,
+}
+
+impl Default for Locks {
+    fn default() -> Self {
+        Locks::in_dir(std::env::temp_dir().join("lsusb-locks"))
+    }
+}
+
+impl Locks {
+    /// Keeps the lock files in `dir` instead of the shared directory.
+    pub fn in_dir(dir: PathBuf) -> Self {
+        Locks { dir }
+    }
+
+    /// Locks `device` (a disk, e.g. `sdb`) for `operation`. Fails with
+    /// [`UsbError::DeviceLocked`] if another process holds it.
+    pub fn acquire(&self, device: &str, operation: &str) -> Result<DeviceLock> {
+        let (mut file, path) = self.open(device)?;
+        if !try_lock(&file).io_context(|| format!("Failed to lock {}", path.display()))? {
+            return Err(UsbError::DeviceLocked {
+                device: device.to_string(),
+                holder: read_holder(&mut file)
+                    .map_or_else(|| "another lsusb process".into(), |h| h.to_string()),
+            });
+        }
+        let since = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let record = format!(
+            "{}\n{}\n{}\n",
+            std::process::id(),
+            since,
+            operation.replace('\n', " ")
+        );
+        file.set_len(0)
+            .and_then(|()| file.rewind())
+            .and_then(|()| file.write_all(record.as_bytes()))
+            .io_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(DeviceLock {
+            _file: file,
+            device: device.to_string(),
+        })
+    }
+
+    /// [`acquire`](Self::acquire), waiting for the holder to finish, for at
+    /// most `timeout` if one is given.
+    pub fn wait(
+        &self,
+        device: &str,
+        operation: &str,
+        timeout: Option<Duration>,
+    ) -> Result<DeviceLock> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            match self.acquire(device, operation) {
+                Err(UsbError::DeviceLocked { .. })
+                    if deadline.is_none_or(|d| Instant::now() < d) =>
+                {
+                    std::thread::sleep(RETRY)
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Who holds the lock on `device`, if anyone does.
+    pub fn holder(&self, device: &str) -> Result<Option<Holder>> {
+        let (mut file, path) = self.open(device)?;
+        if try_lock(&file).io_context(|| format!("Failed to lock {}", path.display()))? {
+            // Nobody; closing the file releases it again.
+            return Ok(None);
+        }
+        Ok(read_holder(&mut file))
+    }
+
+    fn open(&self, device: &str) -> Result<(File, PathBuf)> {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        if device.is_empty() || device.contains(['/', '\0']) || device.starts_with('.') {
+            return Err(UsbError::Unsupported(format!(
+                "'{}' is not a device name",
+                device
+            )));
+        }
+        create_shared_dir(&self.dir)?;
+        let path = self.dir.join(format!("{}.lock", device));
+        // Anyone can create files here: don't follow a symlink planted in
+        // place of a lock file into somebody else's file.
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .custom_flags(libc::O_NOFOLLOW)
+            .mode(0o666)
+            .open(&path)
+            .io_context(|| format!("Failed to open {}", path.display()))?;
+        // Past the umask, so other users can lock it too; fails harmlessly
+        // on files someone else created.
+        let _ = file.set_permissions(fs::Permissions::from_mode(0o666));
+        Ok((file, path))
+    }
+}
+
+fn read_holder(file: &mut File) -> Option<Holder> {
+    let mut text = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut text).ok()?;
+    let mut lines = text.lines();
+    Some(Holder {
+        pid: lines.next()?.parse().ok()?,
+        since: lines.next()?.parse().ok()?,
+        operation: lines.next()?.to_string(),
+    })
+}
+
+/// `flock(LOCK_EX | LOCK_NB)`: whether the lock was taken.
+fn try_lock(file: &File) -> io::Result<bool> {
+    use std::os::fd::AsRawFd;
+
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+            return Ok(true);
+        }
+        let e = io::Error::last_os_error();
+        match e.raw_os_error() {
+            Some(libc::EWOULDBLOCK) => return Ok(false),
+            Some(libc::EINTR) => continue,
+            _ => return Err(e),
+        }
+    }
+}
+
+fn create_shared_dir(dir: &Path) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    match fs::DirBuilder::new().mode(0o1777).create(dir) {
+        Ok(()) => {
+            // The umask took some of the bits away.
+            let _ = fs::set_permissions(dir, fs::Permissions::from_mode(0o1777));
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+        Err(e) => Err(UsbError::io(
+            format!("Failed to create {}", dir.display()),
+            e,
+        )),
+    }
+}
@@ -1,33 +1,539 @@
 mod cli;
-mod usb;
+mod commands;
+mod interrupt;
 mod tui;
 
-use clap::Parser;
 use anyhow::Result;
+use clap::Parser;
+use lsusb::{UsbError, tr};
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", tr!("error", error = format!("{:#}", e)));
+            exit_code(&e)
+        }
+    }
+}
+
+/// Exit status for a failed run, so scripts can tell failure kinds apart.
+/// 2 is left to clap for usage errors.
+fn exit_code(err: &anyhow::Error) -> ExitCode {
+    let Some(err) = err.downcast_ref::<UsbError>() else {
+        return ExitCode::FAILURE;
+    };
+    ExitCode::from(match err {
+        UsbError::DeviceNotFound(_) | UsbError::NotUsb(_) => 3,
+        UsbError::MountBusy { .. }
+        | UsbError::JobRunning { .. }
+        | UsbError::DeviceLocked { .. } => 4,
+        UsbError::InsufficientSpace { .. } => 5,
+        UsbError::VerificationFailed { .. } => 6,
+        UsbError::ToolMissing { .. } => 7,
+        UsbError::Cancelled => 130,
+        UsbError::Io { source, .. } if source.kind() == std::io::ErrorKind::PermissionDenied => 8,
+        UsbError::WriteProtected { .. } => 9,
+        UsbError::PolicyDenied { .. } => 10,
+        _ => 1,
+    })
+}
 
-fn main() -> Result<()> {
+fn run() -> Result<()> {
     let cli = cli::Cli::parse();
+    let mut config = lsusb::Config::load()?;
+    if let Some(path) = &cli.audit_log {
+        config.audit_log = Some(path.clone());
+    }
+    let backend_kind = match cli.backend.or(config.backend) {
+        Some(kind) => kind,
+        // udisks picks mountpoints itself; an explicit one needs the
+        // privileged path.
+        None if matches!(
+            cli.command,
+            Some(cli::Commands::Mount {
+                mountpoint: Some(_),
+                ..
+            })
+        ) =>
+        {
+            lsusb::BackendKind::default()
+        }
+        None => lsusb::BackendKind::detect(),
+    };
+    commands::set_plain(cli.plain || config.plain);
+    let mut settings = commands::Settings {
+        units: cli.size_units(),
+        read_only: cli.read_only || config.read_only,
+        wait: cli.wait,
+        progress: cli.progress,
+        progress_fd: cli.progress_fd,
+    };
+    if let Some(fd) = cli.progress_fd
+        && unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1
+    {
+        anyhow::bail!("--progress-fd {}: that file descriptor isn't open", fd);
+    }
+    if let Some(cli::Commands::Mount { partition, .. }) = &cli.command {
+        // Decided here so an escalated re-run inherits it as --read-only.
+        let access = config
+            .policy
+            .check(partition, lsusb::policy::Access::ReadOnly)?;
+        settings.read_only |= access == lsusb::policy::Access::ReadOnly;
+    }
+    if let Some(cli::Commands::Doctor) = cli.command {
+        // Runs before the backend is created so it still works when that fails.
+        return commands::doctor(backend_kind);
+    }
+    if let Some(cli::Commands::Jobs { cancel }) = &cli.command {
+        return commands::jobs(cancel.as_deref(), settings.units);
+    }
+    // Lets `lsusb jobs` in another terminal see what this one is doing.
+    lsusb::jobs::JobSockets::default().publish();
+    let include_mmc = cli.include_mmc || config.include_mmc;
+    let all_removable = cli.all_removable || config.all_removable;
+    let mut backend = backend_kind.create_with(lsusb::backend::BackendOptions {
+        include_mmc,
+        all_removable,
+    })?;
+    let lists = matches!(
+        cli.command,
+        None | Some(cli::Commands::List {
+            all_classes: false,
+            ..
+        })
+    );
+    if lists && !cli.show_hidden && !config.hide.is_empty() {
+        backend = Box::new(lsusb::backend::HidingBackend::new(
+            backend,
+            config.hide.clone(),
+        ));
+    }
+    if cli.command.is_none() {
+        // The menu lists devices at nearly every prompt.
+        backend = Box::new(lsusb::backend::CachedBackend::new(
+            backend,
+            std::time::Duration::from_secs(2),
+        ));
+    }
+    let backend = backend.as_ref();
+    let privileged = matches!(
+        cli.command,
+        Some(
+            cli::Commands::Mount { .. }
+                | cli::Commands::Unmount { .. }
+                | cli::Commands::PowerOff { .. }
+                | cli::Commands::Hexdump { .. }
+                | cli::Commands::Bootloader { .. }
+                | cli::Commands::Multiboot { .. }
+                | cli::Commands::Format { .. }
+                | cli::Commands::Trim { .. }
+                | cli::Commands::Stash { .. }
+                | cli::Commands::Veracrypt { .. }
+                | cli::Commands::Label { .. }
+                | cli::Commands::Health { .. }
+                | cli::Commands::Rescan { .. }
+        )
+    );
+
+    let result = dispatch(cli.command, backend, &config, settings);
+    match result {
+        Err(e) if privileged && escalation_can_help(&e) => {
+            let escalation = config
+                .escalate
+                .unwrap_or_else(lsusb::privilege::Escalation::detect);
+            match escalation.program() {
+                Some(program) => {
+                    // root has its own config file; keep what ours decided.
+                    let mut extra = Vec::new();
+                    if cli.backend.is_none() {
+                        extra.extend(["--backend".to_string(), backend_kind.to_string()]);
+                    }
+                    if settings.read_only && !cli.read_only {
+                        extra.push("--read-only".to_string());
+                    }
+                    if include_mmc && !cli.include_mmc {
+                        extra.push("--include-mmc".to_string());
+                    }
+                    if all_removable && !cli.all_removable {
+                        extra.push("--all-removable".to_string());
+                    }
+                    if commands::plain() && !cli.plain {
+                        extra.push("--plain".to_string());
+                    }
+                    // Otherwise root would log to its own home directory. A
+                    // shared log only root can create is left to root.
+                    if cli.audit_log.is_none()
+                        && let Ok(log) = config.audit_log()
+                    {
+                        let _ = log.create();
+                        extra.extend(["--audit-log".to_string(), log.path().display().to_string()]);
+                    }
+                    Err(reinvoke_as_root(program, &extra))
+                }
+                None => Err(e),
+            }
+        }
+        other => other,
+    }
+}
+
+fn escalation_can_help(err: &anyhow::Error) -> bool {
+    !lsusb::privilege::is_root()
+        && err
+            .downcast_ref::<UsbError>()
+            .is_some_and(lsusb::privilege::needs_privileges)
+}
+
+/// Replaces this process with `program lsusb <same arguments> <extra>`; only
+/// returns if that couldn't be started.
+fn reinvoke_as_root(program: &str, extra: &[String]) -> anyhow::Error {
+    use std::os::unix::process::CommandExt;
+
+    eprintln!("{}", tr!("retrying-as-root", program = program));
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => return anyhow::Error::new(e).context("Failed to locate the lsusb executable"),
+    };
+    let err = std::process::Command::new(program)
+        .arg(exe)
+        .args(std::env::args_os().skip(1))
+        .args(extra)
+        .exec();
+    anyhow::Error::new(err).context(format!("Failed to run {}", program))
+}
 
-    match cli.command {
+fn dispatch(
+    command: Option<cli::Commands>,
+    backend: &dyn lsusb::SystemBackend,
+    config: &lsusb::Config,
+    settings: commands::Settings,
+) -> Result<()> {
+    match command {
         Some(command) => match command {
-            cli::Commands::List => {
-                usb::list_usbs()?;
+            cli::Commands::Doctor | cli::Commands::Jobs { .. } => unreachable!("handled above"),
+            cli::Commands::List {
+                all_classes: true, ..
+            } => {
+                commands::list_bus()?;
+            }
+            cli::Commands::List { watch: true, .. } => {
+                commands::watch_usbs(backend, settings.units)?;
+            }
+            cli::Commands::List {
+                porcelain: Some(version),
+                null,
+                ..
+            } => {
+                commands::porcelain(backend, None, version, null)?;
+            }
+            cli::Commands::List { .. } => {
+                commands::list_usbs(backend, settings.units, None)?;
             }
-            cli::Commands::Parts { device } => {
-                usb::list_partitions(&device)?;
+            cli::Commands::Bootloader { device, grub, .. } => {
+                let bootloader = if grub {
+                    lsusb::bootloader::Bootloader::Grub
+                } else {
+                    lsusb::bootloader::Bootloader::Syslinux
+                };
+                commands::install_bootloader(
+                    backend,
+                    &device,
+                    bootloader,
+                    &config.audit_log()?,
+                    &config.policy,
+                    settings,
+                )?;
+            }
+            cli::Commands::Format {
+                device,
+                filesystem,
+                label,
+                cluster_size,
+                align,
+                yes,
+            } => {
+                let device = backend.find(&device)?;
+                let options =
+                    lsusb::format::Options {
+                        label,
+                        cluster: cluster_size,
+                        align,
+                        ..lsusb::format::Options::new(filesystem.unwrap_or_else(|| {
+                            lsusb::format::Filesystem::default_for(device.bytes)
+                        }))
+                    };
+                commands::format(
+                    &device,
+                    options,
+                    yes,
+                    &config.audit_log()?,
+                    &config.policy,
+                    settings,
+                )?;
+            }
+            cli::Commands::Trim {
+                target,
+                enable_unmap,
+                yes,
+            } => {
+                commands::trim(
+                    backend,
+                    &target,
+                    enable_unmap,
+                    yes,
+                    &config.audit_log()?,
+                    &config.policy,
+                    settings,
+                )?;
+            }
+            cli::Commands::Bench {
+                target,
+                size,
+                queue_depth,
+                seconds,
+                sequential,
+                random,
+            } => {
+                let options = lsusb::bench::Options {
+                    file_size: size,
+                    sequential: !random,
+                    random: !sequential,
+                    queue_depths: queue_depth,
+                    duration: std::time::Duration::from_secs(seconds),
+                };
+                commands::bench(backend, &target, &options, &config.policy, settings)?;
+            }
+            cli::Commands::Multiboot { action } => {
+                commands::multiboot(
+                    backend,
+                    action,
+                    &config.audit_log()?,
+                    &config.policy,
+                    settings,
+                )?;
+            }
+            cli::Commands::Backup { action } => {
+                commands::backup(backend, action, config, settings)?;
+            }
+            cli::Commands::Veracrypt { action } => {
+                commands::veracrypt(backend, action, &config.policy, settings)?;
+            }
+            cli::Commands::Stash { action } => {
+                commands::stash(
+                    backend,
+                    action,
+                    &config.audit_log()?,
+                    &config.policy,
+                    settings,
+                )?;
+            }
+            cli::Commands::Hexdump {
+                device,
+                offset,
+                length,
+            } => {
+                commands::hexdump(backend, &device, offset, length)?;
+            }
+            cli::Commands::Rescan { device } => {
+                commands::rescan(backend, &device, settings)?;
+            }
+            cli::Commands::Stats { device } => {
+                commands::stats(backend, &device, settings.units)?;
+            }
+            cli::Commands::Topology => {
+                commands::topology(backend)?;
+            }
+            cli::Commands::Serial => {
+                commands::list_serial()?;
+            }
+            cli::Commands::Parts {
+                device,
+                porcelain: Some(version),
+                null,
+            } => {
+                commands::porcelain(backend, Some(&device), version, null)?;
+            }
+            cli::Commands::Parts { device, .. } => {
+                commands::list_partitions(backend, &device, settings.units)?;
             }
             cli::Commands::Sync { device } => {
-                usb::sync_device(&device)?;
+                commands::sync_device(backend, &device, settings.units)?;
+            }
+            cli::Commands::Mount {
+                partition,
+                mountpoint,
+            } => {
+                commands::mount_partition(backend, &partition, mountpoint.as_deref(), settings)?;
+            }
+            cli::Commands::Health { device } => {
+                commands::health(backend, &device, &config.smart, settings.units)?;
+            }
+            cli::Commands::Label { partition, label } => {
+                commands::label(
+                    backend,
+                    &partition,
+                    &label,
+                    &config.audit_log()?,
+                    &config.policy,
+                    settings,
+                )?;
+            }
+            cli::Commands::Open { target } => {
+                commands::open(backend, config, &target, settings)?;
             }
             cli::Commands::Unmount { device } => {
-                usb::unmount_device(&device)?;
+                commands::unmount_device(backend, &device, settings.units)?;
+            }
+            cli::Commands::PowerOff { device } => {
+                commands::power_off(backend, &device, settings.units)?;
+            }
+            cli::Commands::Wsl { action } => {
+                commands::wsl(action)?;
+            }
+            cli::Commands::Termux { action } => {
+                commands::termux(action, settings)?;
+            }
+            cli::Commands::Camera { action } => {
+                commands::camera(action)?;
+            }
+            cli::Commands::Dfu { action } => {
+                commands::dfu(action, &config.audit_log()?, settings)?;
+            }
+            cli::Commands::Iso { action } => {
+                commands::iso(action, settings)?;
+            }
+            cli::Commands::Ios { action } => {
+                commands::ios(action, settings)?;
+            }
+            cli::Commands::Cp {
+                source,
+                dest,
+                scan,
+                sign,
+                encrypt,
+                decrypt,
+                identity,
+                no_ignore,
+                json,
+                span,
+                to,
+                preserve,
+                on_collision,
+                flatten,
+                strip_components,
+                prefix,
+                eject_after,
+            } => {
+                let scanner = if scan { Some(config.scanner()?) } else { None };
+                let crypt = match encrypt {
+                    Some(recipient) => Some(lsusb::crypt::Crypt::encrypt(recipient)),
+                    None if decrypt => Some(lsusb::crypt::Crypt::decrypt(identity)),
+                    None => None,
+                };
+                let ignore = if no_ignore {
+                    lsusb::ignore::Ignore::none()
+                } else {
+                    config.ignore()
+                };
+                let json = json || settings.json_progress_on_stdout();
+                let options = lsusb::TransferOptions {
+                    scanner,
+                    crypt,
+                    ignore,
+                    preserve: preserve.unwrap_or_default(),
+                    on_collision,
+                    reshape: lsusb::transfer::Reshape {
+                        flatten,
+                        strip_components,
+                        prefix,
+                    },
+                    on_overwrite: Some(commands::log_overwrites(config.audit_log()?)),
+                    ..Default::default()
+                };
+                let dests = match dest {
+                    _ if !to.is_empty() => {
+                        commands::copy_mirrored(
+                            backend,
+                            &source,
+                            &to,
+                            settings,
+                            &config.policy,
+                            options,
+                            json,
+                        )?;
+                        to
+                    }
+                    Some(dest) if span => {
+                        commands::copy_spanning(
+                            backend,
+                            &source,
+                            &dest,
+                            settings,
+                            &config.policy,
+                            options,
+                            json,
+                        )?;
+                        vec![dest]
+                    }
+                    Some(dest) => {
+                        let copy = commands::copy_to_usb(
+                            backend,
+                            &source,
+                            &dest,
+                            settings,
+                            &config.policy,
+                            options,
+                            json,
+                        )?;
+                        match (copy, sign) {
+                            (Some(copy), Some(key)) => commands::sign_copy(&copy, &key, json)?,
+                            (None, Some(_)) => anyhow::bail!(
+                                "{} was unmounted again after the copy, so it wasn't signed; mount it first",
+                                dest.display()
+                            ),
+                            (_, None) => {}
+                        }
+                        vec![dest]
+                    }
+                    None => unreachable!("clap requires a destination without --to"),
+                };
+                if eject_after {
+                    commands::eject_after(backend, &dests, settings.units)?;
+                }
+            }
+            cli::Commands::Verify { path, signature } => {
+                commands::verify(&path, signature.as_deref())?;
+            }
+            cli::Commands::Rm {
+                paths,
+                secure,
+                force,
+            } => {
+                commands::remove(
+                    backend,
+                    &paths,
+                    secure,
+                    force,
+                    &config.audit_log()?,
+                    &config.policy,
+                    settings,
+                )?;
+            }
+            cli::Commands::Daemon => {
+                commands::daemon(backend, config, settings)?;
             }
-            cli::Commands::Cp { source, dest } => {
-                usb::copy_to_usb(&source, &dest)?;
+            cli::Commands::Audit { device, operation } => {
+                commands::audit(
+                    &config.audit_log()?,
+                    device.as_deref(),
+                    operation.as_deref(),
+                )?;
             }
         },
         None => {
-            tui::run()?;
+            tui::run(backend, config, settings)?;
         }
     }
 
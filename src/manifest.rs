@@ -0,0 +1,138 @@
+//! Manifests of copies: the SHA-256 of every file of a copy, kept next to it
+//! as `<copy>.sha256` so the files can be checked again after the stick has
+//! changed hands. `cp --sign` writes and signs one (see [`crate::sign`]);
+//! `lsusb verify` checks it.
+//!
+//! The format is `sha256sum`'s, with paths relative to the manifest's own
+//! directory, so `sha256sum -c` run there checks it too.
+
+use crate::cancel::CancelToken;
+use crate::digest::{Sha256, hex};
+use crate::error::{IoContext, Result, UsbError};
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read};
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Where the manifest of the copy at `copy` is kept.
+pub fn manifest_path(copy: &Path) -> PathBuf {
+    let name = copy.file_name().unwrap_or_default().to_string_lossy();
+    copy.with_file_name(format!("{}.sha256", name))
+}
+
+/// Hashes every regular file of `copy`, a file or a directory, and writes
+/// them to [`manifest_path`], replacing what was there. Returns the
+/// manifest's path.
+pub fn write(copy: &Path, cancel: &CancelToken) -> Result<PathBuf> {
+    let path = manifest_path(copy);
+    let base = copy.parent().unwrap_or(Path::new(""));
+    let mut text = String::new();
+    for entry in WalkDir::new(copy).sort_by_file_name() {
+        let entry = entry
+            .map_err(|e| UsbError::io(format!("Failed to read {}", copy.display()), e.into()))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry
+            .path()
+            .strip_prefix(base)
+            .unwrap_or(entry.path())
+            .to_string_lossy();
+        // One line per file; names with a line break can't be listed.
+        if relative.contains('\n') {
+            continue;
+        }
+        cancel.check()?;
+        text.push_str(&format!("{}  {}\n", hash_file(entry.path())?, relative));
+    }
+    fs::write(&path, text).io_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// The entries of the manifest of `copy`: each file's SHA-256 in hex and
+/// its path relative to the manifest's directory.
+pub fn read(copy: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let path = manifest_path(copy);
+    let text =
+        fs::read_to_string(&path).io_context(|| format!("Failed to read {}", path.display()))?;
+    text.lines()
+        .map(|line| {
+            line.split_once("  ")
+                .filter(|(hash, _)| hash.len() == 64)
+                .map(|(hash, file)| (hash.to_string(), PathBuf::from(file)))
+                // Only names below the manifest: one passed from hand to
+                // hand mustn't send the check elsewhere on the system.
+                .filter(|(_, file)| file.components().all(|c| matches!(c, Component::Normal(_))))
+                .ok_or_else(|| {
+                    UsbError::parse(
+                        path.display().to_string(),
+                        format!("not a checksum line: {}", line),
+                    )
+                })
+        })
+        .collect()
+}
+
+/// What [`verify`] found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    pub files_checked: u64,
+    pub bytes_checked: u64,
+    /// Files whose contents no longer match the manifest.
+    pub corrupt: Vec<PathBuf>,
+    /// Files in the manifest that are gone.
+    pub missing: Vec<PathBuf>,
+}
+
+impl VerifyReport {
+    /// Whether every file matched.
+    pub fn is_ok(&self) -> bool {
+        self.corrupt.is_empty() && self.missing.is_empty()
+    }
+}
+
+/// Re-hashes the files listed in the manifest of `copy`.
+pub fn verify(copy: &Path, cancel: &CancelToken) -> Result<VerifyReport> {
+    let base = copy.parent().unwrap_or(Path::new(""));
+    let mut report = VerifyReport::default();
+    for (hash, file) in read(copy)? {
+        cancel.check()?;
+        let path = base.join(&file);
+        match fs::metadata(&path) {
+            Ok(metadata) if metadata.is_file() => {
+                report.files_checked += 1;
+                report.bytes_checked += metadata.len();
+                if hash_file(&path)? != hash {
+                    report.corrupt.push(file);
+                }
+            }
+            Ok(_) => report.missing.push(file),
+            Err(e) if e.kind() == ErrorKind::NotFound => report.missing.push(file),
+            Err(e) => {
+                return Err(UsbError::io(
+                    format!("Failed to read {}", path.display()),
+                    e,
+                ));
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// The SHA-256 of the file at `path` in hex.
+fn hash_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).io_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hash = Sha256::new();
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .io_context(|| format!("Failed to read {}", path.display()))?;
+        if n == 0 {
+            return Ok(hex(&hash.finish()));
+        }
+        hash.update(&buf[..n]);
+    }
+}
@@ -0,0 +1,59 @@
+use crate::error::Result;
+use crate::usb::{self, UsbDevice};
+
+/// A change in the set of attached USB devices.
+#[derive(Debug, Clone)]
+pub enum DeviceEvent {
+    /// A device that was not present at the previous poll appeared.
+    Attached(UsbDevice),
+    /// The device with this kernel name went away.
+    Detached(String),
+}
+
+/// Polls the device list and reports what changed since the last poll.
+///
+/// There is no kernel notification involved: each [`poll`](Self::poll)
+/// re-enumerates and diffs against the previous result.
+#[derive(Debug, Default)]
+pub struct DeviceMonitor {
+    known: Vec<UsbDevice>,
+}
+
+impl DeviceMonitor {
+    /// Creates a monitor seeded with the devices attached right now, so the
+    /// first poll only reports changes.
+    pub fn new() -> Result<Self> {
+        Ok(DeviceMonitor {
+            known: usb::get_usb_devices()?,
+        })
+    }
+
+    /// Devices seen at the last poll.
+    pub fn devices(&self) -> &[UsbDevice] {
+        &self.known
+    }
+
+    /// Re-enumerates devices and returns attach/detach events.
+    pub fn poll(&mut self) -> Result<Vec<DeviceEvent>> {
+        let current = usb::get_usb_devices()?;
+        let events = diff_devices(&self.known, &current);
+        self.known = current;
+        Ok(events)
+    }
+}
+
+/// Computes the events that turn `old` into `new`, matching devices by name.
+pub fn diff_devices(old: &[UsbDevice], new: &[UsbDevice]) -> Vec<DeviceEvent> {
+    let mut events: Vec<DeviceEvent> = old
+        .iter()
+        .filter(|o| !new.iter().any(|n| n.name == o.name))
+        .map(|o| DeviceEvent::Detached(o.name.clone()))
+        .collect();
+    events.extend(
+        new.iter()
+            .filter(|n| !old.iter().any(|o| o.name == n.name))
+            .cloned()
+            .map(DeviceEvent::Attached),
+    );
+    events
+}
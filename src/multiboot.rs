@@ -0,0 +1,247 @@
+//! Multi-ISO boot sticks: a large exFAT partition the user copies ISO
+//! images onto, and a small FAT32 partition holding GRUB, whose menu lists
+//! whatever is in `isos/` when the machine boots.
+//!
+//! The menu is generated by GRUB at boot time, so adding or removing an
+//! image is just a file copy or delete; nothing has to be regenerated.
+
+use crate::backend::{check_output, run, with_mounted};
+use crate::bootloader::Installer;
+use crate::error::{IoContext, Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use crate::rescan::Rescanner;
+use crate::usb::{Partition, UsbDevice, partition_name};
+use crate::{MountOptions, SystemBackend};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Label of the exFAT partition that holds the images.
+pub const DATA_LABEL: &str = "LSUSB";
+/// Label of the FAT32 partition that holds GRUB.
+pub const BOOT_LABEL: &str = "LSUSB-BOOT";
+/// Directory on the data partition the menu is built from.
+pub const ISO_DIR: &str = "isos";
+
+const SECTOR: u64 = 512;
+/// Partitions start and end on 1 MiB boundaries.
+const ALIGN: u64 = 2048;
+/// GRUB with BIOS and UEFI modules needs about 30 MiB.
+const BOOT_SECTORS: u64 = 64 * 1024 * 1024 / SECTOR;
+/// Below this there is no room for even a small ISO.
+const MIN_DATA_SECTORS: u64 = 256 * 1024 * 1024 / SECTOR;
+/// An MBR partition table can't address past 2 TiB.
+const MAX_SECTORS: u64 = u32::MAX as u64;
+
+/// An `sfdisk` script for a disk of `bytes` bytes: the data partition
+/// first, so Windows (which only mounts the first partition of removable
+/// disks) sees the images, then the boot partition, marked active for
+/// BIOS and typed EFI System for UEFI.
+pub fn partition_script(bytes: u64) -> Result<String> {
+    let sectors = (bytes / SECTOR).min(MAX_SECTORS);
+    let data = sectors.saturating_sub(ALIGN + BOOT_SECTORS) / ALIGN * ALIGN;
+    if data < MIN_DATA_SECTORS {
+        return Err(UsbError::Unsupported(format!(
+            "a multiboot stick needs at least {} MiB",
+            (ALIGN + BOOT_SECTORS + MIN_DATA_SECTORS) * SECTOR / (1024 * 1024)
+        )));
+    }
+    Ok(format!(
+        "label: dos\nunit: sectors\n\nstart={}, size={}, type=7\nstart={}, size={}, type=ef, bootable\n",
+        ALIGN,
+        data,
+        ALIGN + data,
+        BOOT_SECTORS
+    ))
+}
+
+/// The `grub.cfg` written to the boot partition. It finds the data
+/// partition by label and offers every `isos/*.iso` through the
+/// `loopback.cfg` convention most live distributions follow.
+pub fn grub_menu() -> String {
+    format!(
+        "# Written by lsusb multiboot. The entries are found at boot time;\n\
+         # copy ISO images into /{dir} on the {data} partition.\n\
+         insmod part_msdos\n\
+         insmod exfat\n\
+         insmod regexp\n\
+         insmod loopback\n\
+         set timeout=10\n\
+         search --no-floppy --set=data --label {data}\n\
+         for isofile in ($data)/{dir}/*.iso; do\n    \
+             if [ ! -f \"$isofile\" ]; then continue; fi\n    \
+             regexp --set=isoname \".*/{dir}/(.*)\" \"$isofile\"\n    \
+             menuentry \"$isoname\" \"$isoname\" {{\n        \
+                 set iso_path=\"/{dir}/$2\"\n        \
+                 export iso_path\n        \
+                 search --no-floppy --set=root --label {data}\n        \
+                 loopback loop $iso_path\n        \
+                 set root=(loop)\n        \
+                 configfile /boot/grub/loopback.cfg\n    \
+             }}\n\
+         done\n",
+        dir = ISO_DIR,
+        data = DATA_LABEL
+    )
+}
+
+/// An image on the stick.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Iso {
+    /// File name, e.g. `debian-12.5.0-amd64-netinst.iso`.
+    pub name: String,
+    pub bytes: u64,
+}
+
+/// The images in [`ISO_DIR`] on the data partition mounted at `root`,
+/// sorted by name. A missing directory means no images.
+pub fn isos(root: &Path) -> Result<Vec<Iso>> {
+    let dir = root.join(ISO_DIR);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(UsbError::io(format!("Failed to read {}", dir.display()), e)),
+    };
+    let mut isos = Vec::new();
+    for entry in entries {
+        let entry = entry.io_context(|| format!("Failed to read {}", dir.display()))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let metadata = entry
+            .metadata()
+            .io_context(|| format!("Failed to read {}", entry.path().display()))?;
+        if metadata.is_file() && name.to_ascii_lowercase().ends_with(".iso") {
+            isos.push(Iso {
+                name,
+                bytes: metadata.len(),
+            });
+        }
+    }
+    isos.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(isos)
+}
+
+/// [`ISO_DIR`] on the data partition mounted at `root`, created if it's
+/// missing, for copying images into.
+pub fn iso_dir(root: &Path) -> Result<PathBuf> {
+    let dir = root.join(ISO_DIR);
+    fs::create_dir_all(&dir).io_context(|| format!("Failed to create {}", dir.display()))?;
+    Ok(dir)
+}
+
+/// Deletes image `name` from the data partition mounted at `root`. Names
+/// that would point outside [`ISO_DIR`] are refused.
+pub fn remove_iso(root: &Path, name: &str) -> Result<()> {
+    if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+        return Err(UsbError::Unsupported(format!(
+            "'{}' is not an ISO file name",
+            name
+        )));
+    }
+    let path = root.join(ISO_DIR).join(name);
+    fs::remove_file(&path).io_context(|| format!("Failed to remove {}", path.display()))
+}
+
+/// The data partition of a stick set up by [`Multiboot::init`].
+pub fn data_partition(device: &UsbDevice) -> Result<&Partition> {
+    device
+        .partitions
+        .iter()
+        .find(|p| p.label.as_deref() == Some(DATA_LABEL))
+        .ok_or_else(|| {
+            UsbError::Unsupported(format!(
+                "{} has no {} partition; set it up with `lsusb multiboot init` first",
+                device.name, DATA_LABEL
+            ))
+        })
+}
+
+/// Partitions, formats and installs GRUB with `sfdisk`, `mkfs.exfat`,
+/// `mkfs.vfat` and `grub-install`.
+#[derive(Clone)]
+pub struct Multiboot {
+    exec: Arc<dyn CommandExecutor>,
+    rescanner: Rescanner,
+}
+
+impl Default for Multiboot {
+    fn default() -> Self {
+        Multiboot::with_executor(Arc::new(SystemExecutor))
+    }
+}
+
+impl Multiboot {
+    /// Uses `exec` to run the partitioning and formatting tools.
+    pub fn with_executor(exec: Arc<dyn CommandExecutor>) -> Self {
+        Multiboot {
+            rescanner: Rescanner::with_executor(exec.clone()),
+            exec,
+        }
+    }
+
+    /// Waits for the new partitions' nodes in `dir` instead of `/dev`.
+    pub fn dev_dir(mut self, dir: PathBuf) -> Self {
+        self.rescanner = self.rescanner.dev_dir(dir);
+        self
+    }
+
+    /// Turns `device` into a multiboot stick, erasing everything on it.
+    /// Returns the name of the data partition, e.g. `sdb1`. Nothing is
+    /// left mounted.
+    pub fn init(&self, backend: &dyn SystemBackend, device: &UsbDevice) -> Result<String> {
+        if let Some(mountpoint) = device.mountpoints().next() {
+            return Err(UsbError::Unsupported(format!(
+                "{} is mounted at {}; unmount it first",
+                device.name, mountpoint
+            )));
+        }
+        let script = partition_script(device.bytes)?;
+        let disk = format!("/dev/{}", device.name);
+        let data = partition_name(&device.name, 1);
+        let boot = partition_name(&device.name, 2);
+
+        check_output(
+            "sfdisk",
+            self.exec.run_with_stdin(
+                "sfdisk",
+                &["--wipe", "always", &disk],
+                &mut script.as_bytes(),
+            ),
+        )?;
+        self.rescanner.rescan(&device.name, &[&data, &boot])?;
+        run(
+            self.exec.as_ref(),
+            "mkfs.exfat",
+            &["-L", DATA_LABEL, &format!("/dev/{}", data)],
+        )?;
+        run(
+            self.exec.as_ref(),
+            "mkfs.vfat",
+            &["-F", "32", "-n", BOOT_LABEL, &format!("/dev/{}", boot)],
+        )?;
+
+        with_mounted(
+            backend,
+            &boot,
+            None,
+            MountOptions::default(),
+            |mountpoint| {
+                Installer::with_executor(self.exec.clone())
+                    .install_grub(&device.name, mountpoint)?;
+                let dir = mountpoint.join("boot/grub");
+                fs::create_dir_all(&dir)
+                    .io_context(|| format!("Failed to create {}", dir.display()))?;
+                let config = dir.join("grub.cfg");
+                fs::write(&config, grub_menu())
+                    .io_context(|| format!("Failed to write {}", config.display()))
+            },
+        )?;
+        with_mounted(
+            backend,
+            &data,
+            None,
+            MountOptions::default(),
+            |mountpoint| iso_dir(mountpoint).map(drop),
+        )?;
+        Ok(data)
+    }
+}
@@ -0,0 +1,98 @@
+//! Async variants of the blocking API, for GUI and daemon consumers running
+//! on a tokio runtime. Enabled with the `async` feature.
+//!
+//! The underlying work still uses blocking syscalls and subprocesses; it is
+//! moved onto tokio's blocking pool so callers never tie up a runtime worker.
+
+use crate::cancel::CancelToken;
+use crate::error::{Result, UsbError};
+use crate::monitor::{DeviceEvent, DeviceMonitor};
+use crate::progress::ProgressEvent;
+use crate::transfer::{self, TransferOptions, TransferReport};
+use crate::usb::{self, UsbDevice};
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::{self, JoinHandle};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+/// Async variant of [`usb::get_usb_devices`].
+pub async fn get_usb_devices() -> Result<Vec<UsbDevice>> {
+    task::spawn_blocking(usb::get_usb_devices)
+        .await
+        .map_err(task_failed)?
+}
+
+/// Watches for devices being attached or detached, polling every `interval`.
+///
+/// The returned stream ends if enumeration fails or the receiver is dropped.
+pub async fn monitor(interval: Duration) -> Result<UnboundedReceiverStream<DeviceEvent>> {
+    let mut monitor = task::spawn_blocking(DeviceMonitor::new)
+        .await
+        .map_err(task_failed)??;
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            let polled = task::spawn_blocking(move || {
+                let events = monitor.poll();
+                (monitor, events)
+            })
+            .await;
+            let Ok((returned, Ok(events))) = polled else {
+                break;
+            };
+            monitor = returned;
+            for event in events {
+                if tx.send(event).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    Ok(UnboundedReceiverStream::new(rx))
+}
+
+/// A copy running on the blocking pool.
+pub struct Transfer {
+    /// Progress updates, ending when the copy finishes.
+    pub progress: UnboundedReceiverStream<ProgressEvent>,
+    cancel: CancelToken,
+    handle: JoinHandle<Result<TransferReport>>,
+}
+
+impl Transfer {
+    /// Asks the copy to stop; [`finish`](Self::finish) then returns
+    /// [`UsbError::Cancelled`].
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Waits for the copy to finish and returns its report.
+    pub async fn finish(self) -> Result<TransferReport> {
+        self.handle.await.map_err(task_failed)?
+    }
+}
+
+/// Async variant of [`transfer::copy_with_progress`].
+pub fn copy_to_usb(source: PathBuf, dest: PathBuf, options: TransferOptions) -> Transfer {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let cancel = options.cancel.clone();
+    let handle = task::spawn_blocking(move || {
+        transfer::copy_with_progress(&source, &dest, &options, |event| {
+            let _ = tx.send(event);
+        })
+    });
+    Transfer {
+        progress: UnboundedReceiverStream::new(rx),
+        cancel,
+        handle,
+    }
+}
+
+fn task_failed(err: task::JoinError) -> UsbError {
+    UsbError::io("Background task failed", io::Error::other(err))
+}
@@ -0,0 +1,95 @@
+//! Telling someone about an event when nobody is watching the terminal:
+//! a desktop notification through `notify-send`, and/or a JSON `POST` to a
+//! webhook through `curl`.
+
+use crate::backend::{check_output, run};
+use crate::error::{Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// The `[notify]` section of the config file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    /// Show desktop notifications.
+    pub desktop: bool,
+    /// URL that receives every notification as JSON:
+    /// `{"summary": ..., "body": ..., "device": ...}`.
+    pub webhook: Option<String>,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        NotifyConfig {
+            desktop: true,
+            webhook: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    summary: &'a str,
+    body: &'a str,
+    device: &'a str,
+}
+
+/// Sends notifications where [`NotifyConfig`] says.
+#[derive(Clone)]
+pub struct Notifier {
+    exec: Arc<dyn CommandExecutor>,
+    config: NotifyConfig,
+}
+
+impl Notifier {
+    pub fn new(config: NotifyConfig) -> Self {
+        Notifier::with_executor(config, Arc::new(SystemExecutor))
+    }
+
+    /// Uses `exec` to run `notify-send` and `curl`.
+    pub fn with_executor(config: NotifyConfig, exec: Arc<dyn CommandExecutor>) -> Self {
+        Notifier { exec, config }
+    }
+
+    /// Sends `summary` and `body` about `device` everywhere configured. Each
+    /// channel is tried even if another fails; the first failure is returned.
+    pub fn send(&self, device: &str, summary: &str, body: &str) -> Result<()> {
+        let mut result = Ok(());
+        if self.config.desktop {
+            result = run(
+                self.exec.as_ref(),
+                "notify-send",
+                &["--app-name=lsusb", summary, body],
+            )
+            .map(|_| ());
+        }
+        if let Some(url) = &self.config.webhook {
+            let payload = serde_json::to_vec(&Payload {
+                summary,
+                body,
+                device,
+            })
+            .map_err(|e| UsbError::parse("notification", e))?;
+            let sent = self.exec.run_with_stdin(
+                "curl",
+                &[
+                    "--silent",
+                    "--show-error",
+                    "--fail",
+                    "--max-time",
+                    "10",
+                    "--header",
+                    "Content-Type: application/json",
+                    "--data-binary",
+                    "@-",
+                    url,
+                ],
+                &mut payload.as_slice(),
+            );
+            let sent = check_output("curl", sent).map(|_| ());
+            result = result.and(sent);
+        }
+        result
+    }
+}
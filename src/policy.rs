@@ -0,0 +1,188 @@
+//! Which USB drives may be written to, from the `[policy]` config table.
+//!
+//! Rules match a drive by vendor/product ID, serial number or capacity; the
+//! first rule that matches decides its [`Access`], and `default` covers the
+//! rest:
+//!
+//! ```toml
+//! [policy]
+//! default = "read-only"
+//!
+//! [[policy.rules]]
+//! serial = "4C530001230912104355"
+//! access = "allow"
+//!
+//! [[policy.rules]]
+//! vid_pid = "0781:*"
+//! access = "deny"
+//! ```
+//!
+//! Drives are identified through sysfs, so rules are only enforced on Linux;
+//! drives that aren't on USB are never restricted.
+
+use crate::error::{Result, UsbError};
+use serde::Deserialize;
+use std::fmt;
+use std::path::Path;
+
+/// What a drive may be used for, from least to most.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Access {
+    /// Not even mounted.
+    Deny,
+    /// Mounted read-only; nothing is written.
+    ReadOnly,
+    #[default]
+    Allow,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Access::Deny => "deny",
+            Access::ReadOnly => "read-only",
+            Access::Allow => "allow",
+        })
+    }
+}
+
+/// One rule; every criterion given has to match.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// `vvvv:pppp` in hex, or `vvvv:*` for any product of a vendor.
+    pub vid_pid: Option<String>,
+    pub serial: Option<String>,
+    /// Matches drives of at least this many bytes.
+    pub min_bytes: Option<u64>,
+    /// Matches drives of at most this many bytes.
+    pub max_bytes: Option<u64>,
+    pub access: Access,
+}
+
+impl Rule {
+    fn matches(&self, id: &DeviceIdentity) -> bool {
+        self.vid_pid
+            .as_deref()
+            .is_none_or(|pattern| id.matches_vid_pid(pattern))
+            && self
+                .serial
+                .as_deref()
+                .is_none_or(|s| id.serial.as_deref() == Some(s))
+            && self.min_bytes.is_none_or(|min| id.bytes >= min)
+            && self.max_bytes.is_none_or(|max| id.bytes <= max)
+    }
+}
+
+/// The `[policy]` table. Without one, every drive is allowed.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    /// Access for drives no rule matches.
+    pub default: Access,
+    pub rules: Vec<Rule>,
+}
+
+impl Policy {
+    /// Access for the drive described by `id`.
+    pub fn access(&self, id: &DeviceIdentity) -> Access {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(id))
+            .map_or(self.default, |rule| rule.access)
+    }
+
+    /// The access granted to `device_name` (a disk or partition), failing
+    /// with [`UsbError::PolicyDenied`] if it's less than `needed`.
+    pub fn check(&self, device_name: &str, needed: Access) -> Result<Access> {
+        let Some(id) = DeviceIdentity::lookup(device_name) else {
+            return Ok(Access::Allow);
+        };
+        let access = self.access(&id);
+        if access < needed {
+            return Err(UsbError::PolicyDenied {
+                device: device_name.to_string(),
+                access,
+            });
+        }
+        Ok(access)
+    }
+
+    /// [`check`](Self::check) for the drive holding `path`, or its nearest
+    /// existing ancestor.
+    pub fn check_path(&self, path: &Path, needed: Access) -> Result<Access> {
+        if self.rules.is_empty() && self.default == Access::Allow {
+            return Ok(Access::Allow);
+        }
+        match device_of(path) {
+            Some(device) => self.check(&device, needed),
+            None => Ok(Access::Allow),
+        }
+    }
+}
+
+/// What rules are matched against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceIdentity {
+    /// USB vendor ID, four lowercase hex digits.
+    pub vendor_id: String,
+    pub product_id: String,
+    pub serial: Option<String>,
+    /// Capacity of the whole drive.
+    pub bytes: u64,
+}
+
+impl DeviceIdentity {
+    /// Whether `pattern`, `vvvv:pppp` or `vvvv:*` in hex, matches the IDs.
+    pub fn matches_vid_pid(&self, pattern: &str) -> bool {
+        let (vendor, product) = pattern.split_once(':').unwrap_or((pattern, "*"));
+        vendor.eq_ignore_ascii_case(&self.vendor_id)
+            && (product == "*" || product.eq_ignore_ascii_case(&self.product_id))
+    }
+
+    /// Reads the identity of the USB drive `device_name` is on from sysfs;
+    /// `None` if it isn't a USB drive (or not on Linux).
+    #[cfg(target_os = "linux")]
+    pub fn lookup(device_name: &str) -> Option<DeviceIdentity> {
+        use std::fs;
+
+        let class = Path::new("/sys/class/block");
+        let mut sys_path = fs::canonicalize(class.join(device_name)).ok()?;
+        if sys_path.join("partition").exists() {
+            sys_path.pop();
+        }
+        let read = |dir: &Path, attr: &str| {
+            fs::read_to_string(dir.join(attr))
+                .ok()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        let sectors: u64 = read(&sys_path, "size")?.parse().ok()?;
+        let usb = sys_path
+            .ancestors()
+            .find(|dir| dir.join("idVendor").exists())?;
+        Some(DeviceIdentity {
+            vendor_id: read(usb, "idVendor")?,
+            product_id: read(usb, "idProduct")?,
+            serial: read(usb, "serial"),
+            bytes: sectors * 512,
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn lookup(_device_name: &str) -> Option<DeviceIdentity> {
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn device_of(path: &Path) -> Option<String> {
+    let existing = path.ancestors().find(|p| p.exists())?;
+    crate::transfer::backing_device(existing)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn device_of(_path: &Path) -> Option<String> {
+    None
+}
@@ -0,0 +1,131 @@
+//! `--porcelain` output of `list` and `parts`: what the tables show, in a
+//! documented format that stays the same from release to release, for
+//! scripts. The tables are for people and can change at any time.
+//!
+//! Version 1:
+//!
+//! ```text
+//! version=1
+//! record=device name=sdb size=61530439680 vendor=SanDisk model=Ultra serial=4C5300012311 hotplug=1 port=2-1 driver=usb-storage
+//! record=partition device=sdb name=sdb1 size=61529391104 type=part fstype=vfat label=MY\x20STICK uuid=1A2B-3C4D mountpoint=/media/usb fs_total=61513826304 fs_used=1048576 fs_available=61512777728
+//! ```
+//!
+//! The first record gives the version. Each record is a line of
+//! `key=value` fields separated by single spaces; every record of a kind
+//! has all its fields, in this order, with an empty value for what isn't
+//! known (`label=`). Sizes are in bytes; `hotplug` is `1` or `0`; the
+//! `fs_` fields, the filesystem's own size and usage, are only filled in
+//! for mounted partitions. In values, `\` is written `\\`, and spaces and
+//! other control characters `\xNN`.
+//!
+//! With `-z`, nothing is escaped: each field ends in a NUL byte instead of
+//! a space, and each record in a second NUL instead of a newline.
+//!
+//! Later releases may add fields to the end of a record and new kinds of
+//! record, which readers should skip. Anything else bumps the version;
+//! `--porcelain=1` keeps asking for this one.
+
+use crate::bus::BusDevice;
+use crate::usb::{Partition, Usage, UsbDevice};
+use std::io::{self, Write};
+
+/// The version [`Writer`] writes.
+pub const VERSION: u32 = 1;
+
+/// Writes porcelain records to `out`.
+#[derive(Debug)]
+pub struct Writer<W> {
+    out: W,
+    nul: bool,
+}
+
+impl<W: Write> Writer<W> {
+    /// Starts the output with the version record; NUL-separated if `nul`.
+    pub fn new(out: W, nul: bool) -> io::Result<Self> {
+        let mut writer = Writer { out, nul };
+        writer.record(&[("version", VERSION.to_string())])?;
+        Ok(writer)
+    }
+
+    /// A `device` record; `bus` is the USB device it sits on, for the
+    /// serial number, port and driver.
+    pub fn device(&mut self, device: &UsbDevice, bus: Option<&BusDevice>) -> io::Result<()> {
+        self.record(&[
+            ("record", "device".to_string()),
+            ("name", device.name.clone()),
+            ("size", device.bytes.to_string()),
+            ("vendor", text(device.vendor.as_deref().map(str::trim))),
+            ("model", text(device.model.as_deref().map(str::trim))),
+            ("serial", text(bus.and_then(|b| b.serial.as_deref()))),
+            (
+                "hotplug",
+                text(device.hotplug.map(|h| if h { "1" } else { "0" })),
+            ),
+            ("port", text(bus.map(|b| b.bus_id.as_str()))),
+            ("driver", text(bus.and_then(|b| b.storage_driver()))),
+        ])
+    }
+
+    /// A `partition` record for `partition` of `device`, with `usage` if
+    /// it is mounted.
+    pub fn partition(
+        &mut self,
+        device: &str,
+        partition: &Partition,
+        usage: Option<Usage>,
+    ) -> io::Result<()> {
+        let size = |field: fn(&Usage) -> u64| {
+            usage
+                .as_ref()
+                .map_or(String::new(), |u| field(u).to_string())
+        };
+        self.record(&[
+            ("record", "partition".to_string()),
+            ("device", device.to_string()),
+            ("name", partition.name.clone()),
+            ("size", partition.bytes.to_string()),
+            ("type", partition.device_type.clone()),
+            ("fstype", text(partition.fstype.as_deref())),
+            ("label", text(partition.label.as_deref())),
+            ("uuid", text(partition.uuid.as_deref())),
+            ("mountpoint", text(partition.mountpoint.as_deref())),
+            ("fs_total", size(|u| u.total)),
+            ("fs_used", size(|u| u.used)),
+            ("fs_available", size(|u| u.available)),
+        ])
+    }
+
+    fn record(&mut self, fields: &[(&str, String)]) -> io::Result<()> {
+        let mut line = String::new();
+        for (i, (key, value)) in fields.iter().enumerate() {
+            if self.nul {
+                line.push_str(&format!("{}={}\0", key, value));
+            } else {
+                if i > 0 {
+                    line.push(' ');
+                }
+                line.push_str(&format!("{}={}", key, escape(value)));
+            }
+        }
+        line.push(if self.nul { '\0' } else { '\n' });
+        self.out.write_all(line.as_bytes())
+    }
+}
+
+fn text(value: Option<&str>) -> String {
+    value.unwrap_or_default().to_string()
+}
+
+/// `value` as the line format writes it: `\` doubled, and spaces and
+/// control characters as `\xNN`.
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ' ' | '\0'..='\x1f' | '\x7f' => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
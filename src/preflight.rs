@@ -0,0 +1,142 @@
+//! Checking the names a copy is about to create against what the
+//! destination filesystem can store, so a copy that would fail on file
+//! 9,000 of 10,000 (or write names Windows can't open) is refused before
+//! anything is written, with every offending path listed.
+
+use std::fmt;
+use std::path::Path;
+
+/// Longest path the kernel accepts, in bytes, including the NUL.
+const PATH_MAX: usize = 4096;
+/// Characters Windows doesn't allow in names on FAT, exFAT and NTFS.
+const WINDOWS_FORBIDDEN: &str = "\"*:<>?\\|";
+/// Names Windows reserves for devices, with or without an extension.
+const WINDOWS_RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// What a filesystem allows in names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Longest name, in bytes of UTF-8 or in UTF-16 code units.
+    pub name_len: usize,
+    pub utf16: bool,
+    /// Windows' rules: no `"*:<>?\|` or control characters, no trailing
+    /// dot or space, no device names such as `CON`.
+    pub windows: bool,
+}
+
+impl Limits {
+    /// The rules of filesystem `fstype`, as the mount table names it;
+    /// those of ext4 for unknown ones.
+    pub fn for_fstype(fstype: &str) -> Limits {
+        match fstype {
+            "vfat" | "msdos" | "exfat" | "ntfs" | "ntfs3" | "msdosfs" | "exfatfs" | "ntfs-3g" => {
+                Limits {
+                    name_len: 255,
+                    utf16: true,
+                    windows: true,
+                }
+            }
+            "hfs" | "apfs" => Limits {
+                name_len: 255,
+                utf16: true,
+                windows: false,
+            },
+            _ => Limits {
+                name_len: 255,
+                utf16: false,
+                windows: false,
+            },
+        }
+    }
+}
+
+/// Why a path can't be created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// A component is `len` long, over `limit` (bytes, or UTF-16 units).
+    NameTooLong {
+        len: usize,
+        limit: usize,
+    },
+    /// The whole path is `len` bytes, over what the kernel takes.
+    PathTooLong {
+        len: usize,
+    },
+    ForbiddenCharacter(char),
+    /// Ends in a dot or a space, which Windows drops.
+    TrailingDotOrSpace,
+    /// A Windows device name.
+    ReservedName,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::NameTooLong { len, limit } => {
+                write!(f, "a name is too long ({} > {})", len, limit)
+            }
+            Problem::PathTooLong { len } => write!(
+                f,
+                "the path is {} bytes, the limit is {}",
+                len,
+                PATH_MAX - 1
+            ),
+            Problem::ForbiddenCharacter(c) if c.is_control() => {
+                write!(f, "contains control character {:?}", c)
+            }
+            Problem::ForbiddenCharacter(c) => write!(f, "contains '{}'", c),
+            Problem::TrailingDotOrSpace => write!(f, "a name ends in a dot or a space"),
+            Problem::ReservedName => write!(f, "a name is reserved on Windows"),
+        }
+    }
+}
+
+/// The first reason `path` can't be created under `limits`, checking the
+/// components of `new` (the part the copy creates) and the total length.
+pub fn check(path: &Path, new: &Path, limits: &Limits) -> Option<Problem> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let len = path.as_os_str().as_bytes().len();
+    if len >= PATH_MAX {
+        return Some(Problem::PathTooLong { len });
+    }
+    new.iter()
+        .find_map(|name| check_name(&name.to_string_lossy(), limits))
+}
+
+fn check_name(name: &str, limits: &Limits) -> Option<Problem> {
+    let len = if limits.utf16 {
+        name.encode_utf16().count()
+    } else {
+        name.len()
+    };
+    if len > limits.name_len {
+        return Some(Problem::NameTooLong {
+            len,
+            limit: limits.name_len,
+        });
+    }
+    if !limits.windows {
+        return None;
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| c.is_control() || WINDOWS_FORBIDDEN.contains(*c))
+    {
+        return Some(Problem::ForbiddenCharacter(c));
+    }
+    if name.ends_with(['.', ' ']) && name != "." && name != ".." {
+        return Some(Problem::TrailingDotOrSpace);
+    }
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    if WINDOWS_RESERVED
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        return Some(Problem::ReservedName);
+    }
+    None
+}
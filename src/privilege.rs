@@ -0,0 +1,72 @@
+//! Getting root for the few operations that need it.
+//!
+//! lsusb doesn't ask for root up front. When a privileged action fails
+//! because we aren't root, the binary re-runs just that action through
+//! pkexec or sudo instead of giving up.
+
+use crate::error::UsbError;
+use crate::exec::which;
+use serde::Deserialize;
+
+/// How to re-run an action as root, set with `escalate` in the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Escalation {
+    /// polkit's `pkexec`, which asks graphically on a desktop.
+    Pkexec,
+    /// `sudo`, asking on the terminal.
+    Sudo,
+    /// Never escalate; report the permission error instead.
+    None,
+}
+
+impl Escalation {
+    /// pkexec if installed, then sudo, otherwise [`Escalation::None`].
+    pub fn detect() -> Escalation {
+        if which("pkexec").is_some() {
+            Escalation::Pkexec
+        } else if which("sudo").is_some() {
+            Escalation::Sudo
+        } else {
+            Escalation::None
+        }
+    }
+
+    /// The program to prefix the command with.
+    pub fn program(self) -> Option<&'static str> {
+        match self {
+            Escalation::Pkexec => Some("pkexec"),
+            Escalation::Sudo => Some("sudo"),
+            Escalation::None => None,
+        }
+    }
+}
+
+/// Whether the process runs with an effective UID of 0.
+pub fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// Whether `err` is the kind of failure running as root would fix: EPERM or
+/// EACCES from a syscall, or `mount`/`umount`/`eject` refusing a non-root
+/// user.
+pub fn needs_privileges(err: &UsbError) -> bool {
+    match err {
+        UsbError::Io { source, .. } => {
+            source.kind() == std::io::ErrorKind::PermissionDenied
+                || source.raw_os_error() == Some(libc::EPERM)
+        }
+        UsbError::CommandFailed { stderr, .. } => {
+            let stderr = stderr.to_lowercase();
+            [
+                "must be superuser",
+                "only root",
+                "permission denied",
+                "operation not permitted",
+            ]
+            .iter()
+            .any(|needle| stderr.contains(needle))
+        }
+        _ => false,
+    }
+}
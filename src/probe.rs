@@ -0,0 +1,309 @@
+//! Identifying a filesystem from its superblock, like `blkid`, so the type,
+//! label and UUID of a partition are known even when lsblk leaves them out
+//! (no udev database, an old util-linux) or isn't installed at all.
+//!
+//! Recognises FAT12/16/32, exFAT, ext2/3/4, NTFS, ISO 9660 and LUKS, and
+//! names them as `blkid` does (`vfat`, `exfat`, `ext4`, `ntfs`, `iso9660`,
+//! `crypto_LUKS`), with labels and UUIDs formatted the same way.
+
+use crate::error::{IoContext, Result};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// What [`probe`] found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filesystem {
+    /// The `blkid` type name, e.g. `vfat`.
+    pub fstype: String,
+    pub label: Option<String>,
+    pub uuid: Option<String>,
+}
+
+/// Identifies the filesystem at the start of `dev`, if it is one of the
+/// kinds above.
+pub fn probe(dev: &mut (impl Read + Seek)) -> io::Result<Option<Filesystem>> {
+    for probe in [luks, ext, iso9660, ntfs, exfat, fat] {
+        if let Some(found) = probe(dev)? {
+            return Ok(Some(found));
+        }
+    }
+    Ok(None)
+}
+
+/// [`probe`] on the device node or image at `path`.
+pub fn probe_path(path: &Path) -> Result<Option<Filesystem>> {
+    let mut file = File::open(path).io_context(|| format!("Failed to open {}", path.display()))?;
+    probe(&mut file).io_context(|| format!("Failed to read {}", path.display()))
+}
+
+fn luks(dev: &mut (impl Read + Seek)) -> io::Result<Option<Filesystem>> {
+    let Some(header) = read_at(dev, 0, 208)? else {
+        return Ok(None);
+    };
+    if &header[..6] != b"LUKS\xba\xbe" {
+        return Ok(None);
+    }
+    // Only LUKS2 headers have a label.
+    let label = (u16::from_be_bytes([header[6], header[7]]) >= 2)
+        .then(|| text(&header[24..72]))
+        .flatten();
+    Ok(Some(Filesystem {
+        fstype: "crypto_LUKS".to_string(),
+        label,
+        uuid: text(&header[168..208]),
+    }))
+}
+
+fn ext(dev: &mut (impl Read + Seek)) -> io::Result<Option<Filesystem>> {
+    const HAS_JOURNAL: u32 = 0x4;
+    // Features ext2 and ext3 don't have: huge files, group descriptor
+    // checksums, unlimited subdirectories, large inodes; extents, 64-bit
+    // block numbers, flexible block groups.
+    const EXT4_RO_COMPAT: u32 = 0x8 | 0x10 | 0x20 | 0x40;
+    const EXT4_INCOMPAT: u32 = 0x40 | 0x80 | 0x200;
+
+    let Some(sb) = read_at(dev, 1024, 136)? else {
+        return Ok(None);
+    };
+    if sb[56..58] != [0x53, 0xef] {
+        return Ok(None);
+    }
+    let compat = le32(&sb[92..]);
+    let incompat = le32(&sb[96..]);
+    let ro_compat = le32(&sb[100..]);
+    let fstype = if incompat & EXT4_INCOMPAT != 0 || ro_compat & EXT4_RO_COMPAT != 0 {
+        "ext4"
+    } else if compat & HAS_JOURNAL != 0 {
+        "ext3"
+    } else {
+        "ext2"
+    };
+    Ok(Some(Filesystem {
+        fstype: fstype.to_string(),
+        label: text(&sb[120..136]),
+        uuid: Some(uuid(&sb[104..120])),
+    }))
+}
+
+fn iso9660(dev: &mut (impl Read + Seek)) -> io::Result<Option<Filesystem>> {
+    // The primary volume descriptor, in sector 16.
+    let Some(pvd) = read_at(dev, 16 * 2048, 830)? else {
+        return Ok(None);
+    };
+    if pvd[0] != 1 || &pvd[1..6] != b"CD001" {
+        return Ok(None);
+    }
+    // blkid's UUID is the creation time, `YYYYMMDDHHMMSScc`, dashed.
+    let created = &pvd[813..829];
+    let uuid =
+        (created.iter().all(u8::is_ascii_digit) && created.iter().any(|&b| b != b'0')).then(|| {
+            let c = String::from_utf8_lossy(created);
+            format!(
+                "{}-{}-{}-{}-{}-{}-{}",
+                &c[0..4],
+                &c[4..6],
+                &c[6..8],
+                &c[8..10],
+                &c[10..12],
+                &c[12..14],
+                &c[14..16]
+            )
+        });
+    Ok(Some(Filesystem {
+        fstype: "iso9660".to_string(),
+        label: text(&pvd[40..72]),
+        uuid,
+    }))
+}
+
+fn ntfs(dev: &mut (impl Read + Seek)) -> io::Result<Option<Filesystem>> {
+    let Some(boot) = read_at(dev, 0, 512)? else {
+        return Ok(None);
+    };
+    if &boot[3..11] != b"NTFS    " {
+        return Ok(None);
+    }
+    let serial = u64::from_le_bytes(boot[0x48..0x50].try_into().unwrap());
+    Ok(Some(Filesystem {
+        fstype: "ntfs".to_string(),
+        label: ntfs_label(dev, &boot)?,
+        uuid: Some(format!("{:016X}", serial)),
+    }))
+}
+
+/// The volume name, from the `$Volume` file: record 3 of the MFT.
+fn ntfs_label(dev: &mut (impl Read + Seek), boot: &[u8]) -> io::Result<Option<String>> {
+    const VOLUME_NAME: u32 = 0x60;
+    const END: u32 = 0xffff_ffff;
+
+    // The boot sector is as untrusted as the rest of the stick: anything
+    // out of range means no label, not a crash.
+    let sector = u64::from(u16::from_le_bytes([boot[0x0b], boot[0x0c]]));
+    if !sector.is_power_of_two() || !(256..=4096).contains(&sector) || boot[0x0d] == 0 {
+        return Ok(None);
+    }
+    let cluster = sector * u64::from(boot[0x0d]);
+    // Clusters per record, or if negative, the log2 of the record size.
+    let per_record = boot[0x40] as i8;
+    let record_size = match per_record {
+        -20..=-9 => 1u64 << -per_record,
+        1.. => per_record as u64 * cluster,
+        _ => return Ok(None),
+    };
+    if !(256..=65536).contains(&record_size) {
+        return Ok(None);
+    }
+    let Some(offset) = u64::from_le_bytes(boot[0x30..0x38].try_into().unwrap())
+        .checked_mul(cluster)
+        .and_then(|mft| mft.checked_add(3 * record_size))
+    else {
+        return Ok(None);
+    };
+    let Some(mut record) = read_at(dev, offset, record_size as usize)? else {
+        return Ok(None);
+    };
+    if &record[..4] != b"FILE" {
+        return Ok(None);
+    }
+    // Undo the fixups: the last two bytes of every sector were swapped for
+    // the update sequence number when the record was written.
+    let fixups = usize::from(u16::from_le_bytes([record[4], record[5]]));
+    let count = usize::from(u16::from_le_bytes([record[6], record[7]]));
+    for i in 1..count {
+        let end = i * sector as usize;
+        if end > record.len() || fixups + 2 * i + 2 > record.len() {
+            return Ok(None);
+        }
+        record.copy_within(fixups + 2 * i..fixups + 2 * i + 2, end - 2);
+    }
+
+    let mut offset = usize::from(u16::from_le_bytes([record[0x14], record[0x15]]));
+    while offset + 24 <= record.len() {
+        let kind = le32(&record[offset..]);
+        let length = le32(&record[offset + 4..]) as usize;
+        if kind == END || length == 0 {
+            break;
+        }
+        if kind == VOLUME_NAME {
+            let size = le32(&record[offset + 16..]) as usize;
+            let start = offset
+                + usize::from(u16::from_le_bytes([
+                    record[offset + 20],
+                    record[offset + 21],
+                ]));
+            return Ok(record.get(start..start + size).and_then(utf16));
+        }
+        offset += length;
+    }
+    Ok(None)
+}
+
+fn exfat(dev: &mut (impl Read + Seek)) -> io::Result<Option<Filesystem>> {
+    const LABEL_ENTRY: u8 = 0x83;
+
+    let Some(boot) = read_at(dev, 0, 512)? else {
+        return Ok(None);
+    };
+    if &boot[3..11] != b"EXFAT   " {
+        return Ok(None);
+    }
+    let serial = le32(&boot[100..]);
+    let sector_shift = u32::from(boot[108]);
+    let cluster_shift = sector_shift + u32::from(boot[109]);
+    let mut label = None;
+    if sector_shift <= 12 && cluster_shift <= 25 {
+        // The label is an entry in the root directory; it is near the top.
+        let heap = u64::from(le32(&boot[88..])) << sector_shift;
+        let root = heap + (u64::from(le32(&boot[96..])).saturating_sub(2) << cluster_shift);
+        let len = (1usize << cluster_shift).min(64 * 1024);
+        if let Some(dir) = read_at(dev, root, len)? {
+            for entry in dir.chunks_exact(32).take_while(|entry| entry[0] != 0) {
+                if entry[0] == LABEL_ENTRY {
+                    let chars = usize::from(entry[1]).min(11);
+                    label = utf16(&entry[2..2 + 2 * chars]);
+                    break;
+                }
+            }
+        }
+    }
+    Ok(Some(Filesystem {
+        fstype: "exfat".to_string(),
+        label,
+        uuid: Some(format!("{:04X}-{:04X}", serial >> 16, serial & 0xffff)),
+    }))
+}
+
+fn fat(dev: &mut (impl Read + Seek)) -> io::Result<Option<Filesystem>> {
+    let Some(boot) = read_at(dev, 0, 512)? else {
+        return Ok(None);
+    };
+    if boot[510..512] != [0x55, 0xaa] {
+        return Ok(None);
+    }
+    // FAT32 moved the extended boot record to make room for its fields.
+    let ebr = if &boot[82..87] == b"FAT32" {
+        64
+    } else if &boot[54..57] == b"FAT" {
+        36
+    } else {
+        return Ok(None);
+    };
+    if boot[ebr + 2] != 0x29 {
+        // No serial number or label recorded.
+        return Ok(Some(Filesystem {
+            fstype: "vfat".to_string(),
+            label: None,
+            uuid: None,
+        }));
+    }
+    let serial = le32(&boot[ebr + 3..]);
+    let label = text(&boot[ebr + 7..ebr + 18]).filter(|label| label != "NO NAME");
+    Ok(Some(Filesystem {
+        fstype: "vfat".to_string(),
+        label,
+        uuid: Some(format!("{:04X}-{:04X}", serial >> 16, serial & 0xffff)),
+    }))
+}
+
+/// `len` bytes at `offset`, or `None` if the device ends first.
+fn read_at(dev: &mut (impl Read + Seek), offset: u64, len: usize) -> io::Result<Option<Vec<u8>>> {
+    dev.seek(SeekFrom::Start(offset))?;
+    let mut buf = Vec::with_capacity(len);
+    dev.take(len as u64).read_to_end(&mut buf)?;
+    Ok((buf.len() == len).then_some(buf))
+}
+
+fn le32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// A space- or NUL-padded on-disk string.
+fn text(bytes: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(bytes);
+    let text = text.trim_end_matches(['\0', ' ']);
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn utf16(bytes: &[u8]) -> Option<String> {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    let text = String::from_utf16_lossy(&units);
+    let text = text.trim_end_matches('\0');
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// `8-4-4-4-12` hex, as ext and blkid print UUIDs.
+fn uuid(bytes: &[u8]) -> String {
+    let hex = crate::digest::hex(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
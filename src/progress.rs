@@ -0,0 +1,354 @@
+//! Structured progress reporting for long-running operations.
+//!
+//! Engines never draw anything themselves; they emit [`ProgressEvent`]s into
+//! a [`ProgressSink`] and front ends (the CLI bar, the TUI, async streams)
+//! decide how to present them.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// Coarse stage of an operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    /// Walking the source to work out how much there is to do.
+    Scanning,
+    /// Moving data.
+    Copying,
+    /// All work finished successfully.
+    Done,
+}
+
+/// One progress update.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEvent {
+    /// The operation moved to a new phase.
+    Phase(Phase),
+    /// Scanning finished; this is the total amount of work.
+    Totals { bytes: u64, files: u64 },
+    /// Work on a file started.
+    File { path: PathBuf, size: u64 },
+    /// Running byte count across the whole operation.
+    Bytes { done: u64, total: u64 },
+    /// A file was deliberately not copied.
+    Skipped { path: PathBuf, reason: String },
+}
+
+/// Receives progress events from an engine.
+pub trait ProgressSink {
+    fn emit(&mut self, event: ProgressEvent);
+}
+
+impl<F: FnMut(ProgressEvent)> ProgressSink for F {
+    fn emit(&mut self, event: ProgressEvent) {
+        self(event)
+    }
+}
+
+/// Events are sent over the channel; a dropped receiver is ignored so the
+/// operation still completes.
+impl ProgressSink for mpsc::Sender<ProgressEvent> {
+    fn emit(&mut self, event: ProgressEvent) {
+        let _ = self.send(event);
+    }
+}
+
+/// Discards every event.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {
+    fn emit(&mut self, _event: ProgressEvent) {}
+}
+
+/// Writes each event as a line of JSON, for programs that draw their own
+/// progress around lsusb:
+///
+/// ```text
+/// {"event":"phase","phase":"scanning"}
+/// {"event":"totals","bytes":1048576,"files":2}
+/// {"event":"file","path":"photos/a.jpg","size":524288}
+/// {"event":"bytes","done":262144,"total":1048576}
+/// {"event":"skipped","path":"photos/b.jpg","reason":"unchanged"}
+/// {"event":"phase","phase":"done"}
+/// ```
+///
+/// Fields are only ever added. A reader that went away is ignored, as
+/// with a channel, so the operation still completes.
+#[derive(Debug)]
+pub struct JsonLines<W> {
+    out: W,
+}
+
+impl<W: Write> JsonLines<W> {
+    pub fn new(out: W) -> Self {
+        JsonLines { out }
+    }
+}
+
+/// A [`JsonLines`] line; `event` comes first.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+enum Line<'a> {
+    Phase {
+        phase: Phase,
+    },
+    Totals {
+        bytes: u64,
+        files: u64,
+    },
+    File {
+        path: std::borrow::Cow<'a, str>,
+        size: u64,
+    },
+    Bytes {
+        done: u64,
+        total: u64,
+    },
+    Skipped {
+        path: std::borrow::Cow<'a, str>,
+        reason: &'a str,
+    },
+}
+
+impl<W: Write> ProgressSink for JsonLines<W> {
+    fn emit(&mut self, event: ProgressEvent) {
+        let line = match &event {
+            ProgressEvent::Phase(phase) => Line::Phase { phase: *phase },
+            ProgressEvent::Totals { bytes, files } => Line::Totals {
+                bytes: *bytes,
+                files: *files,
+            },
+            ProgressEvent::File { path, size } => Line::File {
+                path: path.to_string_lossy(),
+                size: *size,
+            },
+            ProgressEvent::Bytes { done, total } => Line::Bytes {
+                done: *done,
+                total: *total,
+            },
+            ProgressEvent::Skipped { path, reason } => Line::Skipped {
+                path: path.to_string_lossy(),
+                reason,
+            },
+        };
+        let Ok(line) = serde_json::to_string(&line) else {
+            return;
+        };
+        // Flushed line by line: the reader is watching as it happens.
+        let _ = writeln!(self.out, "{}", line).and_then(|()| self.out.flush());
+    }
+}
+
+/// Forwards [`ProgressEvent::Bytes`] to `inner` at most once per `interval`,
+/// so a display isn't redrawn for every small chunk the engine copies.
+///
+/// Every other event passes straight through, preceded by the latest byte
+/// count still held back, and a count that reaches the total is never held
+/// back, so the display always ends on the real figure.
+#[derive(Debug)]
+pub struct Throttled<S> {
+    inner: S,
+    interval: Duration,
+    last: Option<Instant>,
+    pending: Option<ProgressEvent>,
+}
+
+impl<S: ProgressSink> Throttled<S> {
+    pub fn new(inner: S, interval: Duration) -> Self {
+        Throttled {
+            inner,
+            interval,
+            last: None,
+            pending: None,
+        }
+    }
+}
+
+impl<S: ProgressSink> ProgressSink for Throttled<S> {
+    fn emit(&mut self, event: ProgressEvent) {
+        if let ProgressEvent::Bytes { done, total } = event {
+            let due = self.last.is_none_or(|at| at.elapsed() >= self.interval);
+            if due || done >= total {
+                self.pending = None;
+                self.last = Some(Instant::now());
+                self.inner.emit(event);
+            } else {
+                self.pending = Some(event);
+            }
+            return;
+        }
+        if let Some(bytes) = self.pending.take() {
+            self.inner.emit(bytes);
+        }
+        self.inner.emit(event);
+    }
+}
+
+/// Span over which [`Throughput`] measures its peak rate.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// How many windows [`Throughput::history`] keeps: the last minute.
+const HISTORY: usize = 60;
+
+/// Bars of [`sparkline`], lowest first.
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Measures an operation from its events, for the summary at the end: how
+/// many files it started and skipped, how many bytes it moved and how fast.
+#[derive(Debug, Clone)]
+pub struct Throughput {
+    started: Instant,
+    files: u64,
+    skipped: u64,
+    bytes: u64,
+    /// Byte count at which the file being copied is complete.
+    file_end: u64,
+    /// Where the current peak window began: when, and the byte count then.
+    window: (Instant, u64),
+    /// Best rate over a whole window, in bytes per second.
+    peak: f64,
+    /// The rate of each recent window, oldest first.
+    history: VecDeque<u64>,
+}
+
+impl Default for Throughput {
+    fn default() -> Self {
+        Throughput::new(Instant::now())
+    }
+}
+
+impl Throughput {
+    /// Times the operation from `started`.
+    pub fn new(started: Instant) -> Self {
+        Throughput {
+            started,
+            files: 0,
+            skipped: 0,
+            bytes: 0,
+            file_end: 0,
+            window: (started, 0),
+            peak: 0.0,
+            history: VecDeque::with_capacity(HISTORY),
+        }
+    }
+
+    pub fn record(&mut self, event: &ProgressEvent) {
+        self.record_at(event, Instant::now());
+    }
+
+    /// Records `event` as received at `now`.
+    pub fn record_at(&mut self, event: &ProgressEvent, now: Instant) {
+        match *event {
+            // Scanning time doesn't count towards the peak.
+            ProgressEvent::Phase(Phase::Copying) => self.window = (now, self.bytes),
+            ProgressEvent::File { size, .. } => {
+                self.files += 1;
+                self.file_end = self.bytes + size;
+            }
+            ProgressEvent::Skipped { .. } => self.skipped += 1,
+            ProgressEvent::Bytes { done, .. } => {
+                self.bytes = done;
+                let (since, from) = self.window;
+                let span = now.saturating_duration_since(since);
+                if span >= RATE_WINDOW {
+                    let rate = done.saturating_sub(from) as f64 / span.as_secs_f64();
+                    self.peak = self.peak.max(rate);
+                    self.window = (now, done);
+                    // A stall shows as the windows it lasted, not just one.
+                    let windows = (span.as_secs_f64() / RATE_WINDOW.as_secs_f64()) as usize;
+                    for _ in 0..windows.min(HISTORY) {
+                        if self.history.len() == HISTORY {
+                            self.history.pop_front();
+                        }
+                        self.history.push_back(rate as u64);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The rate over each of the last windows of up to a second, oldest
+    /// first, in bytes per second; at most a minute's worth.
+    pub fn history(&self) -> Vec<u64> {
+        self.history.iter().copied().collect()
+    }
+
+    /// What was measured up to `now`, as a [`Summary`] of `operation`,
+    /// which stopped with `error` if it failed; the file it was on then
+    /// counts as failed. The caller fills in what events don't say, such
+    /// as files left alone as unchanged and how verification went.
+    pub fn summary_at(&self, operation: &str, error: Option<String>, now: Instant) -> Summary {
+        let elapsed = now.saturating_duration_since(self.started).as_secs_f64();
+        let average = if elapsed > 0.0 {
+            self.bytes as f64 / elapsed
+        } else {
+            0.0
+        };
+        let failed = u64::from(error.is_some() && self.files > 0 && self.bytes < self.file_end);
+        Summary {
+            operation: operation.to_string(),
+            files_copied: self.files - failed,
+            files_skipped: self.skipped,
+            files_failed: failed,
+            bytes_written: self.bytes,
+            elapsed_secs: elapsed,
+            average_rate: average as u64,
+            // Runs shorter than a window never measured one.
+            peak_rate: self.peak.max(average) as u64,
+            verification: Verification::Skipped,
+            error,
+        }
+    }
+
+    pub fn summary(&self, operation: &str, error: Option<String>) -> Summary {
+        self.summary_at(operation, error, Instant::now())
+    }
+}
+
+/// `samples` as a row of bars scaled to the largest, e.g. `▅▇█▇▂▁▁▆`:
+/// transfer rates over time, where a stalling or throttling drive stands
+/// out at a glance.
+pub fn sparkline(samples: &[u64]) -> String {
+    let max = samples.iter().copied().max().unwrap_or(0).max(1);
+    samples
+        .iter()
+        .map(|&sample| SPARKS[(sample as u128 * (SPARKS.len() as u128 - 1) / max as u128) as usize])
+        .collect()
+}
+
+/// The end-of-transfer report of `cp`, `backup run` and `dfu download`,
+/// printed as text or, with `--json`, as one JSON object.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    pub operation: String,
+    pub files_copied: u64,
+    /// Left out on purpose: unchanged since the last run, ignored, or
+    /// flagged by the scanner.
+    pub files_skipped: u64,
+    pub files_failed: u64,
+    pub bytes_written: u64,
+    pub elapsed_secs: f64,
+    /// Bytes per second.
+    pub average_rate: u64,
+    /// Best rate over one second, in bytes per second.
+    pub peak_rate: u64,
+    pub verification: Verification,
+    /// Why the operation stopped, if it failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Whether the data written was read back and checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verification {
+    Skipped,
+    Passed,
+    Failed,
+}
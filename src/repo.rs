@@ -0,0 +1,741 @@
+//! Chunked backup repositories. A profile with `chunked = true` stores each
+//! file as content-defined chunks named by their SHA-256 instead of as a
+//! plain copy, so a large file that changed in a few places (a VM image, a
+//! mail store) only adds the chunks around the changes, and data that
+//! appears in several files or runs is stored once.
+//!
+//! The repository is the profile's directory on the stick:
+//!
+//! - `chunks/ab/abcd…`: one file per chunk, fanned out by the first two
+//!   hex digits.
+//! - `archives/<YYYY-MM-DD_HHMMSS>.json`: one per run, listing every file
+//!   with its chunks, named like [`crate::backup::snapshot_name`].
+//! - `index`: the hashes of the stored chunks, one per line, so a run
+//!   doesn't look each chunk up on the stick.
+//!
+//! Chunk boundaries come from a gear rolling hash, as in FastCDC: they
+//! depend on the bytes around them rather than on offsets, so inserting
+//! data only changes the chunks it lands in.
+
+use crate::backup::{
+    Amount, BackupReport, Change, Conflict, Manifest, Profile, RestoreReport, Retention, Sample,
+    SnapshotSummary, Verify, VerifyReport, diff_manifests, drop_cache, is_up_to_date, pick,
+    prune_plan, read_full, restored_name, snapshot_name, source_files,
+};
+use crate::digest::{hex, sha256, sha256_reader};
+use crate::error::{IoContext, Result, UsbError};
+use crate::progress::{Phase, ProgressEvent, ProgressSink};
+use crate::transfer::{TransferOptions, ensure_writable};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+const CHUNK_DIR: &str = "chunks";
+const ARCHIVE_DIR: &str = "archives";
+const INDEX_FILE: &str = "index";
+
+/// No chunk is smaller than this, except the last of a file.
+pub const MIN_CHUNK: usize = 256 * 1024;
+/// Nor larger than this.
+pub const MAX_CHUNK: usize = 4 * 1024 * 1024;
+/// A boundary where the top 20 bits of the hash are zero: one every MiB
+/// on average, past the minimum.
+const BOUNDARY_MASK: u64 = !0 << 44;
+
+/// Random values for each byte, the same on every machine.
+static GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0; 256];
+    let mut state: u64 = 0x6c73_7573_622d_6364;
+    let mut i = 0;
+    while i < 256 {
+        // splitmix64
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Where the first chunk of `data` ends. `data` must hold [`MAX_CHUNK`]
+/// bytes unless it is the end of the file.
+pub fn cut_point(data: &[u8]) -> usize {
+    if data.len() <= MIN_CHUNK {
+        return data.len();
+    }
+    let end = data.len().min(MAX_CHUNK);
+    let mut hash = 0u64;
+    for (i, &byte) in data.iter().enumerate().take(end).skip(MIN_CHUNK) {
+        hash = (hash << 1).wrapping_add(GEAR[byte as usize]);
+        if hash & BOUNDARY_MASK == 0 {
+            return i + 1;
+        }
+    }
+    end
+}
+
+/// Splits everything `reader` (reading `path`) yields into chunks, handing
+/// each to `f`.
+pub fn chunks(
+    reader: &mut impl Read,
+    path: &Path,
+    mut f: impl FnMut(&[u8]) -> Result<()>,
+) -> Result<()> {
+    let mut buf = vec![0u8; MAX_CHUNK];
+    let mut filled = 0;
+    loop {
+        filled += read_full(reader, &mut buf[filled..])
+            .io_context(|| format!("Failed to read {}", path.display()))?;
+        if filled == 0 {
+            return Ok(());
+        }
+        let cut = cut_point(&buf[..filled]);
+        f(&buf[..cut])?;
+        buf.copy_within(cut..filled, 0);
+        filled -= cut;
+    }
+}
+
+/// One run: every file backed up, in the order they were read.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Archive {
+    pub files: Vec<ArchiveFile>,
+}
+
+/// A file in an [`Archive`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveFile {
+    /// Under the source's name, e.g. `Documents/report.odt`.
+    pub path: PathBuf,
+    pub size: u64,
+    /// Modification time: seconds and nanoseconds since the epoch.
+    pub modified: (u64, u32),
+    /// Hashes of its chunks, in order.
+    pub chunks: Vec<String>,
+}
+
+impl ArchiveFile {
+    fn modified(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::new(self.modified.0, self.modified.1)
+    }
+}
+
+impl Archive {
+    fn manifest(&self) -> Manifest {
+        self.files
+            .iter()
+            .map(|f| (f.path.clone(), (f.size, f.modified())))
+            .collect()
+    }
+}
+
+/// A chunked repository on the stick.
+#[derive(Debug)]
+pub struct Repository {
+    root: PathBuf,
+    known: HashSet<String>,
+    added: Vec<String>,
+}
+
+impl Repository {
+    /// Opens the repository at `root`, creating it if needed.
+    pub fn create(root: &Path) -> Result<Self> {
+        for dir in [root.join(CHUNK_DIR), root.join(ARCHIVE_DIR)] {
+            fs::create_dir_all(&dir)
+                .io_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        Repository::open(root)
+    }
+
+    /// Opens the existing repository at `root`.
+    pub fn open(root: &Path) -> Result<Self> {
+        if !root.join(ARCHIVE_DIR).is_dir() {
+            return Err(UsbError::DeviceNotFound(format!(
+                "no chunked backup at {}",
+                root.display()
+            )));
+        }
+        let index = root.join(INDEX_FILE);
+        let known = match fs::read_to_string(&index) {
+            // Anything but a hash is skipped: the index is as untrusted as the
+            // rest of the stick, and its lines name files prune deletes.
+            Ok(text) => text
+                .lines()
+                .filter(|line| is_hash(line))
+                .map(str::to_string)
+                .collect(),
+            // Rebuilt from what's there, e.g. after the index was deleted.
+            Err(e) if e.kind() == io::ErrorKind::NotFound => stored_chunks(&root.join(CHUNK_DIR))?,
+            Err(e) => {
+                return Err(UsbError::io(
+                    format!("Failed to read {}", index.display()),
+                    e,
+                ));
+            }
+        };
+        Ok(Repository {
+            root: root.to_path_buf(),
+            known,
+            added: Vec::new(),
+        })
+    }
+
+    /// The archives, oldest first.
+    pub fn archives(&self) -> Result<Vec<String>> {
+        let dir = self.root.join(ARCHIVE_DIR);
+        let mut names = Vec::new();
+        for entry in
+            fs::read_dir(&dir).io_context(|| format!("Failed to read {}", dir.display()))?
+        {
+            let entry = entry.io_context(|| format!("Failed to read {}", dir.display()))?;
+            if let Some(name) = entry.file_name().to_string_lossy().strip_suffix(".json") {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Archive `name`, or the newest one.
+    pub fn archive_name(&self, name: Option<&str>) -> Result<String> {
+        let mut names = self.archives()?;
+        match name {
+            Some(name) => names.into_iter().find(|n| n == name).ok_or_else(|| {
+                UsbError::DeviceNotFound(format!("no archive {} in {}", name, self.root.display()))
+            }),
+            None => names.pop().ok_or_else(|| {
+                UsbError::DeviceNotFound(format!("no archives in {}", self.root.display()))
+            }),
+        }
+    }
+
+    pub fn load(&self, name: &str) -> Result<Archive> {
+        let path = self.archive_path(name);
+        let text = fs::read_to_string(&path)
+            .io_context(|| format!("Failed to read {}", path.display()))?;
+        let corrupt = |message: String| UsbError::Parse {
+            what: path.display().to_string(),
+            message,
+        };
+        let archive: Archive = serde_json::from_str(&text).map_err(|e| corrupt(e.to_string()))?;
+        // Paths are joined onto the restore target and hashes onto the chunk
+        // directory, so neither may lead outside them.
+        for file in &archive.files {
+            let mut components = file.path.components().peekable();
+            if components.peek().is_none() || !components.all(|c| matches!(c, Component::Normal(_)))
+            {
+                return Err(corrupt(format!("unsafe path {}", file.path.display())));
+            }
+            if let Some(hash) = file.chunks.iter().find(|hash| !is_hash(hash)) {
+                return Err(corrupt(format!(
+                    "invalid chunk hash {:?} for {}",
+                    hash,
+                    file.path.display()
+                )));
+            }
+        }
+        Ok(archive)
+    }
+
+    /// Where chunk `hash` is stored.
+    pub fn chunk_path(&self, hash: &str) -> PathBuf {
+        self.root
+            .join(CHUNK_DIR)
+            .join(hash.get(..2).unwrap_or("00"))
+            .join(hash)
+    }
+
+    fn archive_path(&self, name: &str) -> PathBuf {
+        self.root.join(ARCHIVE_DIR).join(format!("{}.json", name))
+    }
+
+    /// Stores `data` unless the repository has it already. Returns its hash
+    /// and whether it was written.
+    fn store(&mut self, data: &[u8]) -> Result<(String, bool)> {
+        let hash = hex(&sha256(data));
+        if self.known.contains(&hash) {
+            return Ok((hash, false));
+        }
+        let path = self.chunk_path(&hash);
+        let dir = path.parent().unwrap_or(&self.root);
+        fs::create_dir_all(dir).io_context(|| format!("Failed to create {}", dir.display()))?;
+        // Written under a temporary name so a chunk file is never partial.
+        let partial = path.with_extension("partial");
+        fs::write(&partial, data)
+            .io_context(|| format!("Failed to write {}", partial.display()))?;
+        fs::rename(&partial, &path)
+            .io_context(|| format!("Failed to rename {}", partial.display()))?;
+        self.known.insert(hash.clone());
+        self.added.push(hash.clone());
+        Ok((hash, true))
+    }
+
+    /// Appends the chunks stored since the last call to the index.
+    fn save_index(&mut self) -> Result<()> {
+        if self.added.is_empty() {
+            return Ok(());
+        }
+        let path = self.root.join(INDEX_FILE);
+        let mut index = File::options()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .io_context(|| format!("Failed to open {}", path.display()))?;
+        let lines: String = self
+            .added
+            .iter()
+            .map(|hash| format!("{}\n", hash))
+            .collect();
+        index
+            .write_all(lines.as_bytes())
+            .io_context(|| format!("Failed to write {}", path.display()))?;
+        self.added.clear();
+        Ok(())
+    }
+
+    fn save(&self, name: &str, archive: &Archive) -> Result<()> {
+        let path = self.archive_path(name);
+        let partial = path.with_extension("partial");
+        let json = serde_json::to_vec(archive).map_err(|e| UsbError::Parse {
+            what: "archive".into(),
+            message: e.to_string(),
+        })?;
+        fs::write(&partial, json)
+            .io_context(|| format!("Failed to write {}", partial.display()))?;
+        fs::rename(&partial, &path).io_context(|| format!("Failed to rename {}", partial.display()))
+    }
+
+    /// Reads chunk `hash`, checking it against its hash if `verify`.
+    fn read_chunk(&self, hash: &str, verify: bool) -> Result<Vec<u8>> {
+        let path = self.chunk_path(hash);
+        let data = fs::read(&path).io_context(|| format!("Failed to read {}", path.display()))?;
+        if verify && hex(&sha256(&data)) != hash {
+            return Err(UsbError::VerificationFailed { path });
+        }
+        Ok(data)
+    }
+}
+
+/// Whether `name` is a SHA-256 in hex, as chunks are named.
+fn is_hash(name: &str) -> bool {
+    name.len() == 64 && name.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn stored_chunks(dir: &Path) -> Result<HashSet<String>> {
+    let mut hashes = HashSet::new();
+    for entry in walkdir::WalkDir::new(dir).min_depth(2).max_depth(2) {
+        let entry = entry.map_err(|e| {
+            UsbError::io(
+                format!("Failed to read {}", dir.display()),
+                io::Error::from(e),
+            )
+        })?;
+        let name = entry.file_name().to_string_lossy();
+        if entry.file_type().is_file() && is_hash(&name) {
+            hashes.insert(name.into_owned());
+        }
+    }
+    Ok(hashes)
+}
+
+/// [`crate::backup::run`] for chunked profiles: reads the files that
+/// changed since the previous archive, stores the chunks the repository
+/// doesn't have, and writes a new archive.
+pub(crate) fn backup(
+    profile: &Profile,
+    dest: &Path,
+    options: &TransferOptions,
+    mut progress: impl ProgressSink,
+) -> Result<BackupReport> {
+    let started = Instant::now();
+    progress.emit(ProgressEvent::Phase(Phase::Scanning));
+    fs::create_dir_all(dest).io_context(|| format!("Failed to create {}", dest.display()))?;
+    ensure_writable(dest)?;
+    let mut repo = Repository::create(dest)?;
+    let name = snapshot_name(SystemTime::now());
+    if repo.archive_path(&name).exists() {
+        return Err(UsbError::Unsupported(format!(
+            "archive {} already exists",
+            name
+        )));
+    }
+    let previous: HashMap<PathBuf, ArchiveFile> = match repo.archives()?.pop() {
+        Some(last) => repo
+            .load(&last)?
+            .files
+            .into_iter()
+            .map(|f| (f.path.clone(), f))
+            .collect(),
+        None => HashMap::new(),
+    };
+
+    let mut archive = Archive::default();
+    let mut changed = Vec::new();
+    for (path, inside, size, modified) in source_files(profile, &options.ignore)? {
+        options.cancel.check()?;
+        let since_epoch = modified
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        let modified = (since_epoch.as_secs(), since_epoch.subsec_nanos());
+        match previous.get(&inside) {
+            Some(old) if old.size == size && old.modified == modified => {
+                archive.files.push(old.clone())
+            }
+            _ => changed.push((path, inside, size, modified)),
+        }
+    }
+    let unchanged = archive.files.len() as u64;
+    let total = changed.iter().map(|(_, _, size, _)| size).sum();
+    progress.emit(ProgressEvent::Totals {
+        bytes: total,
+        files: changed.len() as u64,
+    });
+    progress.emit(ProgressEvent::Phase(Phase::Copying));
+
+    let mut report = BackupReport {
+        files_unchanged: unchanged,
+        ..Default::default()
+    };
+    let mut written: Vec<(String, usize)> = Vec::new();
+    let mut done = 0;
+    for (path, inside, size, modified) in changed {
+        progress.emit(ProgressEvent::File {
+            path: path.clone(),
+            size,
+        });
+        let mut file =
+            File::open(&path).io_context(|| format!("Failed to open {}", path.display()))?;
+        let mut hashes = Vec::new();
+        chunks(&mut file, &path, |data| {
+            options.cancel.check()?;
+            let (hash, new) = repo.store(data)?;
+            if new {
+                report.bytes_copied += data.len() as u64;
+                written.push((hash.clone(), data.len()));
+            }
+            hashes.push(hash);
+            done += data.len() as u64;
+            progress.emit(ProgressEvent::Bytes { done, total });
+            Ok(())
+        })?;
+        report.files_copied += 1;
+        archive.files.push(ArchiveFile {
+            path: inside,
+            size,
+            modified,
+            chunks: hashes,
+        });
+    }
+    repo.save_index()?;
+
+    if profile.verify != Verify::None {
+        for (hash, len) in &written {
+            options.cancel.check()?;
+            let path = repo.chunk_path(hash);
+            let good = match profile.verify {
+                Verify::Full => repo.read_chunk(hash, true).is_ok(),
+                _ => fs::metadata(&path).is_ok_and(|m| m.len() == *len as u64),
+            };
+            if !good {
+                return Err(UsbError::VerificationFailed { path });
+            }
+        }
+        report.files_verified = report.files_copied;
+    }
+    archive.files.sort_by(|a, b| a.path.cmp(&b.path));
+    repo.save(&name, &archive)?;
+    report.pruned = prune(dest, &profile.keep)?;
+    report.dest = repo.archive_path(&name);
+    report.elapsed = started.elapsed();
+    progress.emit(ProgressEvent::Phase(Phase::Done));
+    Ok(report)
+}
+
+/// [`crate::backup::restore`] for chunked repositories: writes archive
+/// `name` of the repository at `repo` into `dest`.
+pub fn restore(
+    repo: &Path,
+    name: &str,
+    dest: &Path,
+    conflict: Conflict,
+    verify: Verify,
+    options: &TransferOptions,
+    progress: impl ProgressSink,
+) -> Result<RestoreReport> {
+    let started = Instant::now();
+    let job = crate::jobs::start("restore", &[repo, dest], Some(&options.cancel));
+    let mut progress = job.track(progress);
+    progress.emit(ProgressEvent::Phase(Phase::Scanning));
+    let repo = Repository::open(repo)?;
+    let archive = repo.load(name)?;
+    let mut report = RestoreReport::default();
+    let mut files = Vec::new();
+    for file in &archive.files {
+        let target = dest.join(&file.path);
+        if !target.exists() {
+            files.push((file, target));
+        } else if is_up_to_date(
+            &target,
+            file.size,
+            file.modified(),
+            &options.mtime_tolerance,
+        ) {
+            report.files_unchanged += 1;
+        } else {
+            match conflict {
+                Conflict::Skip => report.conflicts_skipped.push(target),
+                Conflict::Overwrite => files.push((file, target)),
+                Conflict::KeepBoth => {
+                    let renamed = restored_name(&target);
+                    report.conflicts_kept.push(renamed.clone());
+                    files.push((file, renamed));
+                }
+            }
+        }
+    }
+
+    let total = files.iter().map(|(f, _)| f.size).sum();
+    progress.emit(ProgressEvent::Totals {
+        bytes: total,
+        files: files.len() as u64,
+    });
+    progress.emit(ProgressEvent::Phase(Phase::Copying));
+    let mut done = 0;
+    for (file, target) in files {
+        progress.emit(ProgressEvent::File {
+            path: target.clone(),
+            size: file.size,
+        });
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .io_context(|| format!("Failed to create directory {}", parent.display()))?;
+        }
+        let mut write = |output: &mut File, done: &mut u64| -> Result<()> {
+            for hash in &file.chunks {
+                options.cancel.check()?;
+                let data = repo.read_chunk(hash, verify != Verify::None)?;
+                output
+                    .write_all(&data)
+                    .io_context(|| format!("Failed to write {}", target.display()))?;
+                *done += data.len() as u64;
+                progress.emit(ProgressEvent::Bytes { done: *done, total });
+            }
+            output
+                .set_modified(file.modified())
+                .io_context(|| format!("Failed to write {}", target.display()))
+        };
+        let mut output = File::create(&target)
+            .io_context(|| format!("Failed to create {}", target.display()))?;
+        if let Err(e) = write(&mut output, &mut done) {
+            drop(output);
+            let _ = fs::remove_file(&target);
+            return Err(e);
+        }
+        if verify != Verify::None {
+            if fs::metadata(&target).map(|m| m.len()).ok() != Some(file.size) {
+                return Err(UsbError::VerificationFailed { path: target });
+            }
+            report.files_verified += 1;
+        }
+        report.files_restored += 1;
+        report.bytes_restored += file.size;
+    }
+    report.elapsed = started.elapsed();
+    progress.emit(ProgressEvent::Phase(Phase::Done));
+    Ok(report)
+}
+
+/// [`crate::backup::prune`] for chunked repositories: deletes the archives
+/// `keep` lets go, then the chunks no remaining archive uses.
+pub fn prune(dest: &Path, keep: &Retention) -> Result<Vec<String>> {
+    let repo = Repository::open(dest)?;
+    let pruned = prune_plan(&repo.archives()?, keep);
+    if pruned.is_empty() {
+        return Ok(pruned);
+    }
+    for name in &pruned {
+        let path = repo.archive_path(name);
+        fs::remove_file(&path).io_context(|| format!("Failed to remove {}", path.display()))?;
+    }
+    let mut used = HashSet::new();
+    for name in repo.archives()? {
+        used.extend(repo.load(&name)?.files.into_iter().flat_map(|f| f.chunks));
+    }
+    // The index first: a chunk it lists but that is gone would never be
+    // written again.
+    let index = dest.join(INDEX_FILE);
+    let partial = index.with_extension("partial");
+    let lines: String = used.iter().map(|hash| format!("{}\n", hash)).collect();
+    fs::write(&partial, lines).io_context(|| format!("Failed to write {}", partial.display()))?;
+    fs::rename(&partial, &index)
+        .io_context(|| format!("Failed to rename {}", partial.display()))?;
+    for hash in repo.known.iter().filter(|hash| !used.contains(*hash)) {
+        let path = repo.chunk_path(hash);
+        match fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(UsbError::io(
+                    format!("Failed to remove {}", path.display()),
+                    e,
+                ));
+            }
+        }
+    }
+    Ok(pruned)
+}
+
+/// [`crate::backup::verify`] for chunked repositories: re-hashes the chunks
+/// of all files of archive `name`, or of a `sample`: of the files, or for a
+/// percentage, of the chunks, which only checks the files those are in. A
+/// file is missing if one of its chunks is, and corrupt if one doesn't
+/// match.
+pub fn verify(
+    repo: &Path,
+    name: &str,
+    sample: Option<Sample>,
+    options: &TransferOptions,
+    progress: impl ProgressSink,
+) -> Result<VerifyReport> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        Good,
+        Corrupt,
+        Missing,
+    }
+
+    let started = Instant::now();
+    let job = crate::jobs::start("verify", &[repo], Some(&options.cancel));
+    let mut progress = job.track(progress);
+    progress.emit(ProgressEvent::Phase(Phase::Scanning));
+    let repo = Repository::open(repo)?;
+    let mut files = repo.load(name)?.files;
+    // The chunks to read; all of them if `None`.
+    let mut wanted: Option<HashSet<String>> = None;
+    match sample {
+        Some(
+            sample @ Sample {
+                amount: Amount::Percent(_),
+                ..
+            },
+        ) => {
+            let mut seen = HashSet::new();
+            let mut chunks: Vec<&String> = files
+                .iter()
+                .flat_map(|f| &f.chunks)
+                .filter(|hash| seen.insert(*hash))
+                .collect();
+            let n = sample.amount.of(chunks.len());
+            pick(&mut chunks, n, sample.seed);
+            let chunks: HashSet<String> = chunks.into_iter().cloned().collect();
+            files.retain(|f| f.chunks.iter().any(|hash| chunks.contains(hash)));
+            wanted = Some(chunks);
+        }
+        Some(sample) => {
+            let n = sample.amount.of(files.len());
+            pick(&mut files, n, sample.seed);
+        }
+        None => {}
+    }
+    let is_wanted = |hash: &String| wanted.as_ref().is_none_or(|wanted| wanted.contains(hash));
+    // Chunk sizes aren't recorded, so a sampled file counts in proportion.
+    let total = files
+        .iter()
+        .map(|f| match f.chunks.len() as u64 {
+            0 => f.size,
+            chunks => {
+                f.size * f.chunks.iter().filter(|hash| is_wanted(hash)).count() as u64 / chunks
+            }
+        })
+        .sum();
+    progress.emit(ProgressEvent::Totals {
+        bytes: total,
+        files: files.len() as u64,
+    });
+    progress.emit(ProgressEvent::Phase(Phase::Copying));
+    let mut report = VerifyReport::default();
+    // Chunks shared between files are read once.
+    let mut checked: HashMap<&str, State> = HashMap::new();
+    for file in &files {
+        progress.emit(ProgressEvent::File {
+            path: file.path.clone(),
+            size: file.size,
+        });
+        let mut state = State::Good;
+        for hash in file.chunks.iter().filter(|hash| is_wanted(hash)) {
+            options.cancel.check()?;
+            let chunk = match checked.get(hash.as_str()) {
+                Some(&chunk) => chunk,
+                None => {
+                    let path = repo.chunk_path(hash);
+                    let chunk = match File::open(&path) {
+                        Ok(mut chunk) => {
+                            drop_cache(&chunk);
+                            let digest = sha256_reader(&mut chunk, |n| {
+                                report.bytes_checked += n as u64;
+                                progress.emit(ProgressEvent::Bytes {
+                                    done: report.bytes_checked,
+                                    total,
+                                });
+                            })
+                            .io_context(|| format!("Failed to read {}", path.display()))?;
+                            if hex(&digest) == *hash {
+                                State::Good
+                            } else {
+                                State::Corrupt
+                            }
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => State::Missing,
+                        Err(e) => {
+                            return Err(UsbError::io(
+                                format!("Failed to open {}", path.display()),
+                                e,
+                            ));
+                        }
+                    };
+                    checked.insert(hash, chunk);
+                    chunk
+                }
+            };
+            if state == State::Good {
+                state = chunk;
+            }
+        }
+        match state {
+            State::Good => report.files_checked += 1,
+            State::Corrupt => {
+                report.files_checked += 1;
+                report.corrupt.push(file.path.clone());
+            }
+            State::Missing => report.missing.push(file.path.clone()),
+        }
+    }
+    report.elapsed = started.elapsed();
+    progress.emit(ProgressEvent::Phase(Phase::Done));
+    Ok(report)
+}
+
+/// [`crate::backup::snapshot_summaries`] for chunked repositories.
+pub fn snapshot_summaries(dest: &Path) -> Result<Vec<SnapshotSummary>> {
+    let repo = Repository::open(dest)?;
+    let mut manifests = Vec::new();
+    for name in repo.archives()? {
+        manifests.push((repo.load(&name)?.manifest(), name));
+    }
+    Ok(crate::backup::summarize(manifests))
+}
+
+/// [`crate::backup::diff`] for two archives of the repository at `dest`.
+pub fn diff(dest: &Path, old: &str, new: &str) -> Result<Vec<Change>> {
+    let repo = Repository::open(dest)?;
+    let old = repo.load(&repo.archive_name(Some(old))?)?;
+    let new = repo.load(&repo.archive_name(Some(new))?)?;
+    Ok(diff_manifests(&old.manifest(), &new.manifest()))
+}
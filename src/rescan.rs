@@ -0,0 +1,75 @@
+//! Making the kernel re-read a disk's partition table after it was
+//! rewritten, and waiting for the new partitions' device nodes, so nothing
+//! formats or mounts a partition that isn't there yet, or is still the old
+//! one.
+
+use crate::backend::run;
+use crate::error::{Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How long [`Rescanner::rescan`] waits for partition nodes to appear.
+const NODE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often it looks.
+const POLL: Duration = Duration::from_millis(100);
+
+/// Runs `blockdev --rereadpt` (the `BLKRRPART` ioctl) and `udevadm settle`.
+#[derive(Clone)]
+pub struct Rescanner {
+    exec: Arc<dyn CommandExecutor>,
+    dev_dir: PathBuf,
+}
+
+impl Default for Rescanner {
+    fn default() -> Self {
+        Rescanner::with_executor(Arc::new(SystemExecutor))
+    }
+}
+
+impl Rescanner {
+    /// Uses `exec` to run the tools.
+    pub fn with_executor(exec: Arc<dyn CommandExecutor>) -> Self {
+        Rescanner {
+            exec,
+            dev_dir: PathBuf::from("/dev"),
+        }
+    }
+
+    /// Looks for device nodes in `dir` instead of `/dev`.
+    pub fn dev_dir(mut self, dir: PathBuf) -> Self {
+        self.dev_dir = dir;
+        self
+    }
+
+    /// Has the kernel re-read the partition table of `disk` (e.g. `sdb`)
+    /// and waits until udev is done and the nodes of `expect` (e.g.
+    /// `["sdb1", "sdb2"]`) exist. The kernel refuses while a partition of
+    /// the disk is mounted.
+    pub fn rescan(&self, disk: &str, expect: &[&str]) -> Result<()> {
+        let node = format!("/dev/{}", disk);
+        run(self.exec.as_ref(), "blockdev", &["--rereadpt", &node]).map_err(|e| match e {
+            UsbError::CommandFailed { stderr, .. } if stderr.contains("busy") => UsbError::Unsupported(format!(
+                "{} is in use, so the kernel can't re-read its partition table; unmount its partitions first",
+                disk
+            )),
+            other => other,
+        })?;
+        // Without udev (containers) the kernel's own nodes are all there is.
+        let _ = self.exec.run("udevadm", &["settle"]);
+        let deadline = Instant::now() + NODE_TIMEOUT;
+        for name in expect {
+            while !self.dev_dir.join(name).exists() {
+                if Instant::now() >= deadline {
+                    return Err(UsbError::Unsupported(format!(
+                        "/dev/{} didn't appear after re-reading the partition table of {}",
+                        name, disk
+                    )));
+                }
+                std::thread::sleep(POLL);
+            }
+        }
+        Ok(())
+    }
+}
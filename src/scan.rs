@@ -0,0 +1,166 @@
+//! Malware scanning of files before they are copied.
+//!
+//! Either clamd is asked over its Unix socket (`INSTREAM`, so clamd needn't
+//! be able to read the file itself), or a configured command such as
+//! `clamdscan --no-summary` is run with the file path appended, following
+//! the ClamAV exit status convention: 0 clean, 1 infected, anything else an
+//! error.
+
+use crate::backend::check_output;
+use crate::error::{IoContext, Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Where clamd's socket usually lives on Debian/Ubuntu, Arch and Fedora.
+const CLAMD_SOCKETS: [&str; 3] = [
+    "/run/clamav/clamd.ctl",
+    "/run/clamav/clamd.sock",
+    "/run/clamd.scan/clamd.sock",
+];
+
+/// clamd's default StreamMaxLength is 25M; chunks well below that.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Result of scanning one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Clean,
+    /// Infected; carries the signature name the scanner reported.
+    Infected(String),
+}
+
+/// A malware scanner.
+#[derive(Clone)]
+pub enum Scanner {
+    /// clamd listening on this Unix socket.
+    Clamd(PathBuf),
+    /// A program run as `command... <file>`.
+    Command {
+        command: Vec<String>,
+        exec: Arc<dyn CommandExecutor>,
+    },
+}
+
+impl std::fmt::Debug for Scanner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scanner::Clamd(socket) => f.debug_tuple("Clamd").field(socket).finish(),
+            Scanner::Command { command, .. } => {
+                f.debug_struct("Command").field("command", command).finish()
+            }
+        }
+    }
+}
+
+impl Scanner {
+    /// Runs `command` (program and leading arguments) for every file.
+    pub fn command(command: Vec<String>) -> Scanner {
+        Scanner::command_with_executor(command, Arc::new(SystemExecutor))
+    }
+
+    /// Like [`command`](Self::command), running it through `exec`.
+    pub fn command_with_executor(command: Vec<String>, exec: Arc<dyn CommandExecutor>) -> Scanner {
+        Scanner::Command { command, exec }
+    }
+
+    /// clamd on the first of the usual socket paths that exists.
+    pub fn find_clamd() -> Result<Scanner> {
+        CLAMD_SOCKETS
+            .iter()
+            .map(Path::new)
+            .find(|p| p.exists())
+            .map(|p| Scanner::Clamd(p.to_path_buf()))
+            .ok_or_else(|| {
+                UsbError::Unsupported("clamd socket not found; is clamav-daemon running?".into())
+            })
+    }
+
+    /// Scans the file at `path`.
+    pub fn scan(&self, path: &Path) -> Result<Verdict> {
+        match self {
+            Scanner::Clamd(socket) => scan_clamd(socket, path),
+            Scanner::Command { command, exec } => {
+                let (program, args) = command
+                    .split_first()
+                    .ok_or_else(|| UsbError::Unsupported("Empty scan command".into()))?;
+                let file = path.to_str().ok_or_else(|| {
+                    UsbError::Unsupported(format!("{} is not valid UTF-8", path.display()))
+                })?;
+                let mut args: Vec<&str> = args.iter().map(String::as_str).collect();
+                args.push(file);
+                let output = exec.run(program, &args);
+                // Exit status 1 is "found something", not a failure to run.
+                if let Ok(output) = &output
+                    && output.code == Some(1)
+                {
+                    return Ok(Verdict::Infected(signature(&String::from_utf8_lossy(
+                        &output.stdout,
+                    ))));
+                }
+                check_output(program, output).map(|_| Verdict::Clean)
+            }
+        }
+    }
+}
+
+fn scan_clamd(socket: &Path, path: &Path) -> Result<Verdict> {
+    let context = || {
+        format!(
+            "Failed to scan {} with clamd at {}",
+            path.display(),
+            socket.display()
+        )
+    };
+    let mut file = File::open(path).io_context(|| format!("Failed to open {}", path.display()))?;
+    let mut stream = UnixStream::connect(socket).io_context(context)?;
+    stream.write_all(b"zINSTREAM\0").io_context(context)?;
+
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    loop {
+        let n = file
+            .read(&mut buffer)
+            .io_context(|| format!("Failed to read {}", path.display()))?;
+        stream
+            .write_all(&(n as u32).to_be_bytes())
+            .io_context(context)?;
+        if n == 0 {
+            break;
+        }
+        stream.write_all(&buffer[..n]).io_context(context)?;
+    }
+
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).io_context(context)?;
+    parse_clamd_reply(reply.trim_end_matches(['\0', '\n']))
+}
+
+/// Interprets `stream: OK`, `stream: Eicar-Signature FOUND` or
+/// `... ERROR`.
+pub fn parse_clamd_reply(reply: &str) -> Result<Verdict> {
+    let status = reply.split_once(": ").map_or(reply, |(_, status)| status);
+    if status == "OK" {
+        Ok(Verdict::Clean)
+    } else if let Some(name) = status.strip_suffix(" FOUND") {
+        Ok(Verdict::Infected(name.to_string()))
+    } else {
+        Err(UsbError::parse("clamd reply", reply))
+    }
+}
+
+/// The signature name from `clamscan`-style output (`/path: Name FOUND`),
+/// or the whole first line for other scanners.
+fn signature(stdout: &str) -> String {
+    let line = stdout
+        .lines()
+        .find(|l| l.ends_with(" FOUND"))
+        .or_else(|| stdout.lines().next())
+        .unwrap_or("");
+    line.rsplit_once(": ")
+        .map_or(line, |(_, s)| s)
+        .trim_end_matches(" FOUND")
+        .to_string()
+}
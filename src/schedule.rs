@@ -0,0 +1,239 @@
+//! Running backup profiles automatically: a systemd user service per
+//! profile, started by a daily timer or, through a udev rule, whenever the
+//! profile's stick is plugged in.
+//!
+//! The service runs as the user so `~/` in sources means their home and
+//! udisks2 can mount the stick without root. udev rules live in
+//! `/etc/udev/rules.d`, which needs root; when it isn't writable the rule
+//! is left in the user's config directory to be copied there.
+
+use crate::backend::run;
+use crate::backup::Profile;
+use crate::error::{IoContext, Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const UDEV_RULES_DIR: &str = "/etc/udev/rules.d";
+
+/// When a profile runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    /// Whenever its stick is plugged in.
+    OnPlug,
+    /// Once a day, catching up after the machine was off.
+    Daily,
+}
+
+/// A generated file: its name and contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitFile {
+    pub name: String,
+    pub contents: String,
+}
+
+/// What [`units`] generates for a profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Units {
+    /// Runs `lsusb backup run <profile>` once.
+    pub service: UnitFile,
+    /// For [`Trigger::Daily`].
+    pub timer: Option<UnitFile>,
+    /// For [`Trigger::OnPlug`].
+    pub udev_rule: Option<UnitFile>,
+}
+
+/// The systemd units (and udev rule) that run `profile` as `name` with the
+/// `lsusb` binary at `exe`.
+pub fn units(name: &str, profile: &Profile, exe: &Path, trigger: Trigger) -> Result<Units> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(UsbError::Unsupported(format!(
+            "profile name '{}' can't be used in a unit name; stick to letters, digits, '-', '_' and '.'",
+            name
+        )));
+    }
+    let exe = exe
+        .to_str()
+        .ok_or_else(|| UsbError::Unsupported("the lsusb path is not valid UTF-8".into()))?;
+    let unit = format!("lsusb-backup-{}", name);
+    let service = UnitFile {
+        name: format!("{}.service", unit),
+        contents: format!(
+            "# Written by lsusb backup install.\n\
+             [Unit]\n\
+             Description=lsusb backup profile {name}\n\
+             \n\
+             [Service]\n\
+             Type=oneshot\n\
+             ExecStart=\"{exe}\" backup run {name}\n",
+        ),
+    };
+    let timer = (trigger == Trigger::Daily).then(|| UnitFile {
+        name: format!("{}.timer", unit),
+        contents: format!(
+            "# Written by lsusb backup install.\n\
+             [Unit]\n\
+             Description=Daily lsusb backup profile {name}\n\
+             \n\
+             [Timer]\n\
+             OnCalendar=daily\n\
+             Persistent=true\n\
+             RandomizedDelaySec=15min\n\
+             \n\
+             [Install]\n\
+             WantedBy=timers.target\n",
+        ),
+    });
+    let udev_rule = match trigger {
+        Trigger::OnPlug => Some(UnitFile {
+            name: format!("90-{}.rules", unit),
+            contents: format!(
+                "# Written by lsusb backup install: run backup profile {name} when its stick is plugged in.\n\
+                 ACTION==\"add\", SUBSYSTEM==\"block\", {matches}, TAG+=\"systemd\", ENV{{SYSTEMD_USER_WANTS}}+=\"{service}\"\n",
+                matches = udev_matches(profile)?,
+                service = service.name,
+            ),
+        }),
+        Trigger::Daily => None,
+    };
+    Ok(Units {
+        service,
+        timer,
+        udev_rule,
+    })
+}
+
+/// udev match keys for a profile's target.
+fn udev_matches(profile: &Profile) -> Result<String> {
+    if profile.target.serial.is_none() && profile.target.label.is_none() {
+        return Err(UsbError::Unsupported(
+            "the backup target needs a serial or a label".into(),
+        ));
+    }
+    let escape = |value: &str| value.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut matches = Vec::new();
+    if let Some(serial) = &profile.target.serial {
+        matches.push(format!("ENV{{ID_SERIAL_SHORT}}==\"{}\"", escape(serial)));
+    }
+    match &profile.target.label {
+        Some(label) => matches.push(format!("ENV{{ID_FS_LABEL}}==\"{}\"", escape(label))),
+        // Once per drive rather than once per partition.
+        None => matches.push("ENV{DEVTYPE}==\"disk\"".into()),
+    }
+    Ok(matches.join(", "))
+}
+
+/// Where systemd looks for the user's own units.
+pub fn user_unit_dir() -> Option<PathBuf> {
+    crate::config::Config::path()?
+        .parent()?
+        .parent()
+        .map(|config| config.join("systemd").join("user"))
+}
+
+/// What [`Scheduler::install`] did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Installed {
+    /// Every file written.
+    pub files: Vec<PathBuf>,
+    /// A udev rule that couldn't go into `/etc/udev/rules.d` for lack of
+    /// permission and was written here instead, to be copied as root.
+    pub rule_pending: Option<PathBuf>,
+}
+
+/// Writes units and tells systemd (and udev) about them.
+#[derive(Clone)]
+pub struct Scheduler {
+    exec: Arc<dyn CommandExecutor>,
+    unit_dir: Option<PathBuf>,
+    rules_dir: PathBuf,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Scheduler::with_executor(Arc::new(SystemExecutor))
+    }
+}
+
+impl Scheduler {
+    /// Uses `exec` to run `systemctl` and `udevadm`.
+    pub fn with_executor(exec: Arc<dyn CommandExecutor>) -> Self {
+        Scheduler {
+            exec,
+            unit_dir: user_unit_dir(),
+            rules_dir: PathBuf::from(UDEV_RULES_DIR),
+        }
+    }
+
+    /// Writes units into `unit_dir` and rules into `rules_dir` instead of
+    /// the usual places.
+    pub fn directories(mut self, unit_dir: PathBuf, rules_dir: PathBuf) -> Self {
+        self.unit_dir = Some(unit_dir);
+        self.rules_dir = rules_dir;
+        self
+    }
+
+    /// Writes `units`, reloads systemd and enables the timer or reloads
+    /// the udev rules.
+    pub fn install(&self, units: &Units) -> Result<Installed> {
+        let unit_dir = self.unit_dir.as_ref().ok_or_else(|| {
+            UsbError::Unsupported("No home directory to install systemd user units into".into())
+        })?;
+        fs::create_dir_all(unit_dir)
+            .io_context(|| format!("Failed to create {}", unit_dir.display()))?;
+        let mut files = Vec::new();
+        for unit in [Some(&units.service), units.timer.as_ref()]
+            .into_iter()
+            .flatten()
+        {
+            files.push(write(unit_dir, unit)?);
+        }
+        run(
+            self.exec.as_ref(),
+            "systemctl",
+            &["--user", "daemon-reload"],
+        )?;
+        if let Some(timer) = &units.timer {
+            run(
+                self.exec.as_ref(),
+                "systemctl",
+                &["--user", "enable", "--now", &timer.name],
+            )?;
+        }
+
+        let mut rule_pending = None;
+        if let Some(rule) = &units.udev_rule {
+            match write(&self.rules_dir, rule) {
+                Ok(path) => {
+                    files.push(path);
+                    run(self.exec.as_ref(), "udevadm", &["control", "--reload"])?;
+                }
+                Err(UsbError::Io { source, .. })
+                    if source.kind() == io::ErrorKind::PermissionDenied =>
+                {
+                    let path = write(unit_dir, rule)?;
+                    files.push(path.clone());
+                    rule_pending = Some(path);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(Installed {
+            files,
+            rule_pending,
+        })
+    }
+}
+
+fn write(dir: &Path, file: &UnitFile) -> Result<PathBuf> {
+    let path = dir.join(&file.name);
+    fs::write(&path, &file.contents)
+        .io_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
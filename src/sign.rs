@@ -0,0 +1,178 @@
+//! Signing manifests with `minisign`, so whoever is handed the stick can
+//! check both that the files are intact and who wrote them: those of copies
+//! (see [`crate::manifest`]) and of mirror and snapshot backups.
+//!
+//! The signature of a manifest `<copy>.sha256` sits next to it as
+//! `<copy>.sha256.minisig`. `minisign` is run with its output captured, so
+//! it can't ask for a password: the secret key has to be one without
+//! (`minisign -G -W`).
+
+use crate::backend::run;
+use crate::error::{IoContext, Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The signature algorithm minisign writes into keys and signatures; `ED`
+/// in signatures over a prehashed file.
+const ALGORITHM: &[u8; 2] = b"Ed";
+const KEY_ID_LEN: usize = 8;
+const PUBLIC_KEY_LEN: usize = 2 + KEY_ID_LEN + 32;
+const SIGNATURE_LEN: usize = 2 + KEY_ID_LEN + 64;
+const GLOBAL_SIGNATURE_LEN: usize = 64;
+
+/// Where the signature of `file` is kept.
+pub fn signature_path(file: &Path) -> PathBuf {
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    file.with_file_name(format!("{}.minisig", name))
+}
+
+/// Runs `minisign`.
+#[derive(Clone)]
+pub struct Minisign {
+    exec: Arc<dyn CommandExecutor>,
+}
+
+impl Default for Minisign {
+    fn default() -> Self {
+        Minisign::with_executor(Arc::new(SystemExecutor))
+    }
+}
+
+impl Minisign {
+    /// Uses `exec` to run `minisign`.
+    pub fn with_executor(exec: Arc<dyn CommandExecutor>) -> Self {
+        Minisign { exec }
+    }
+
+    /// Signs `file` with the secret key at `secret_key`, writing
+    /// [`signature_path`].
+    pub fn sign(&self, file: &Path, secret_key: &Path) -> Result<()> {
+        let signature = signature_path(file);
+        run(
+            self.exec.as_ref(),
+            "minisign",
+            &[
+                "-S",
+                "-s",
+                &secret_key.to_string_lossy(),
+                "-m",
+                &file.to_string_lossy(),
+                "-x",
+                &signature.to_string_lossy(),
+            ],
+        )
+        .map(drop)
+    }
+
+    /// Checks [`signature_path`] against `file` and the public key at
+    /// `public_key`. The key has to be readable and well-formed first; after
+    /// that, a missing or malformed signature, one by another key, and any
+    /// failure of `minisign` itself are all [`UsbError::VerificationFailed`].
+    pub fn verify(&self, file: &Path, public_key: &Path) -> Result<()> {
+        let key_id = read_public_key(public_key)?;
+        let signature = signature_path(file);
+        let text = match fs::read_to_string(&signature) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(UsbError::VerificationFailed { path: signature });
+            }
+            Err(e) => {
+                return Err(UsbError::io(
+                    format!("Failed to read {}", signature.display()),
+                    e,
+                ));
+            }
+        };
+        if signature_key_id(&text) != Some(key_id) {
+            return Err(UsbError::VerificationFailed { path: signature });
+        }
+        let result = run(
+            self.exec.as_ref(),
+            "minisign",
+            &[
+                "-V",
+                "-q",
+                "-p",
+                &public_key.to_string_lossy(),
+                "-m",
+                &file.to_string_lossy(),
+                "-x",
+                &signature.to_string_lossy(),
+            ],
+        );
+        match result {
+            Ok(_) => Ok(()),
+            Err(UsbError::CommandFailed { .. }) => {
+                Err(UsbError::VerificationFailed { path: signature })
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// The key id of the minisign public key at `path`.
+fn read_public_key(path: &Path) -> Result<[u8; KEY_ID_LEN]> {
+    let text =
+        fs::read_to_string(path).io_context(|| format!("Failed to read {}", path.display()))?;
+    payload_lines(&text)
+        .next()
+        .and_then(base64_decode)
+        .filter(|key| key.len() == PUBLIC_KEY_LEN && key.starts_with(ALGORITHM))
+        .map(|key| key_id(&key))
+        .ok_or_else(|| UsbError::parse(path.display().to_string(), "not a minisign public key"))
+}
+
+/// The id of the key that made the minisign signature `text`, if it is one:
+/// the signature line, a trusted comment, and the global signature.
+fn signature_key_id(text: &str) -> Option<[u8; KEY_ID_LEN]> {
+    let mut lines = text
+        .lines()
+        .filter(|line| !line.starts_with("untrusted comment:"));
+    let signature = base64_decode(lines.next()?)?;
+    let algorithm_ok = signature.starts_with(ALGORITHM) || signature.starts_with(b"ED");
+    if signature.len() != SIGNATURE_LEN
+        || !algorithm_ok
+        || !lines.next()?.starts_with("trusted comment:")
+    {
+        return None;
+    }
+    let global = base64_decode(lines.next()?)?;
+    (global.len() == GLOBAL_SIGNATURE_LEN).then(|| key_id(&signature))
+}
+
+fn key_id(decoded: &[u8]) -> [u8; KEY_ID_LEN] {
+    decoded[2..2 + KEY_ID_LEN].try_into().unwrap()
+}
+
+/// The lines of a minisign file other than comments.
+fn payload_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.contains("comment:"))
+}
+
+/// Standard, padded base64, as minisign writes it.
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim().trim_end_matches('=');
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut bits, mut n) = (0u32, 0);
+    for c in text.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        bits = bits << 6 | value as u32;
+        n += 6;
+        if n >= 8 {
+            n -= 8;
+            out.push((bits >> n) as u8);
+        }
+    }
+    Some(out)
+}
@@ -0,0 +1,391 @@
+//! SMART health data, read with `smartctl` from
+//! [smartmontools](https://www.smartmontools.org).
+//!
+//! Most USB-SATA bridges pass SMART commands through (smartctl picks the
+//! right `-d sat` variant itself); cheap flash sticks usually don't support
+//! SMART at all.
+
+use crate::error::{Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+/// ATA attribute ids lsusb looks at by name.
+const REALLOCATED_SECTORS: u8 = 5;
+const PENDING_SECTORS: u8 = 197;
+/// Attributes whose normalised value is the life left, in percent:
+/// SSD_Life_Left, Media_Wearout_Indicator, Remaining_Lifetime_Perc,
+/// Percent_Lifetime_Remain, Wear_Leveling_Count; the first one present
+/// counts.
+const LIFE_LEFT: [u8; 5] = [231, 233, 169, 202, 177];
+/// Total_LBAs_Written and Crucial's Total_Host_Sector_Write, in sectors.
+const SECTORS_WRITTEN: [u8; 2] = [241, 246];
+/// NVMe counts data written in units of 1000 sectors.
+const NVME_DATA_UNIT: u64 = 512 * 1000;
+
+/// A vendor-defined ATA SMART attribute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmartAttribute {
+    pub id: u8,
+    /// smartctl's name for it, e.g. `Reallocated_Sector_Ct`.
+    pub name: String,
+    /// Normalised value, where lower is worse.
+    pub value: u64,
+    pub raw: u64,
+}
+
+/// What `smartctl` reported about a disk.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SmartReport {
+    /// The drive's own overall verdict.
+    pub passed: Option<bool>,
+    /// Current temperature in °C.
+    pub temperature: Option<u64>,
+    pub power_on_hours: Option<u64>,
+    pub attributes: Vec<SmartAttribute>,
+    /// NVMe: how much of its rated endurance the drive has used, in percent
+    /// (may pass 100).
+    pub nvme_percentage_used: Option<u64>,
+    /// NVMe: bytes the host has written.
+    pub nvme_bytes_written: Option<u64>,
+}
+
+impl SmartReport {
+    /// The raw value of the ATA attribute `id`.
+    pub fn raw(&self, id: u8) -> Option<u64> {
+        self.attributes.iter().find(|a| a.id == id).map(|a| a.raw)
+    }
+
+    /// Sectors the drive has remapped to spares.
+    pub fn reallocated_sectors(&self) -> Option<u64> {
+        self.raw(REALLOCATED_SECTORS)
+    }
+
+    /// Unreadable sectors waiting to be remapped.
+    pub fn pending_sectors(&self) -> Option<u64> {
+        self.raw(PENDING_SECTORS)
+    }
+
+    /// How much of its endurance the drive says it has used up, in
+    /// percent, from the NVMe health log or an ATA wear attribute.
+    pub fn wear_percent(&self) -> Option<u64> {
+        self.nvme_percentage_used.or_else(|| {
+            LIFE_LEFT
+                .iter()
+                .find_map(|&id| self.attributes.iter().find(|a| a.id == id))
+                .map(|a| 100 - a.value.min(100))
+        })
+    }
+
+    /// Bytes written to the drive over its life, by its own count.
+    pub fn bytes_written(&self) -> Option<u64> {
+        self.nvme_bytes_written.or_else(|| {
+            SECTORS_WRITTEN
+                .iter()
+                .find_map(|&id| self.raw(id))
+                .map(|sectors| sectors * 512)
+        })
+    }
+
+    /// Every reading above its limit in `thresholds`, plus a failed overall
+    /// verdict.
+    pub fn alerts(&self, thresholds: &Thresholds) -> Vec<SmartAlert> {
+        let readings = [
+            (
+                Reading::ReallocatedSectors,
+                self.reallocated_sectors(),
+                thresholds.reallocated_sectors,
+            ),
+            (
+                Reading::PendingSectors,
+                self.pending_sectors(),
+                thresholds.pending_sectors,
+            ),
+            (
+                Reading::Temperature,
+                self.temperature,
+                thresholds.temperature,
+            ),
+        ];
+        let mut alerts: Vec<SmartAlert> = readings
+            .into_iter()
+            .filter_map(|(reading, value, threshold)| {
+                let value = value?;
+                (value > threshold).then_some(SmartAlert {
+                    reading,
+                    value,
+                    threshold,
+                })
+            })
+            .collect();
+        if self.passed == Some(false) {
+            alerts.push(SmartAlert {
+                reading: Reading::OverallHealth,
+                value: 0,
+                threshold: 0,
+            });
+        }
+        alerts
+    }
+}
+
+/// Limits above which a reading is worth telling someone about, from the
+/// `[smart]` section of the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Thresholds {
+    pub reallocated_sectors: u64,
+    pub pending_sectors: u64,
+    /// °C.
+    pub temperature: u64,
+}
+
+impl Default for Thresholds {
+    /// Any remapped or pending sector, or more than 55 °C.
+    fn default() -> Self {
+        Thresholds {
+            reallocated_sectors: 0,
+            pending_sectors: 0,
+            temperature: 55,
+        }
+    }
+}
+
+/// Which reading a [`SmartAlert`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Reading {
+    ReallocatedSectors,
+    PendingSectors,
+    Temperature,
+    OverallHealth,
+}
+
+/// A reading above its threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmartAlert {
+    pub reading: Reading,
+    pub value: u64,
+    pub threshold: u64,
+}
+
+impl fmt::Display for SmartAlert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reading {
+            Reading::ReallocatedSectors => {
+                write!(
+                    f,
+                    "{} reallocated sectors (threshold {})",
+                    self.value, self.threshold
+                )
+            }
+            Reading::PendingSectors => {
+                write!(
+                    f,
+                    "{} pending sectors (threshold {})",
+                    self.value, self.threshold
+                )
+            }
+            Reading::Temperature => write!(
+                f,
+                "temperature {} °C (threshold {} °C)",
+                self.value, self.threshold
+            ),
+            Reading::OverallHealth => {
+                f.write_str("the drive reports its SMART health check as failed")
+            }
+        }
+    }
+}
+
+/// Remembers which alerts were already raised per device, so a daemon
+/// polling every few minutes reports a problem once rather than every time.
+/// An alert is raised again after its reading has dropped back below the
+/// threshold in between.
+#[derive(Debug, Default)]
+pub struct AlertTracker {
+    active: HashMap<String, HashSet<Reading>>,
+}
+
+impl AlertTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the alerts found for `device` and returns those that weren't
+    /// active at its previous check.
+    pub fn update(&mut self, device: &str, alerts: Vec<SmartAlert>) -> Vec<SmartAlert> {
+        let previous = self.active.remove(device).unwrap_or_default();
+        let current = alerts.iter().map(|a| a.reading).collect();
+        self.active.insert(device.to_string(), current);
+        alerts
+            .into_iter()
+            .filter(|a| !previous.contains(&a.reading))
+            .collect()
+    }
+
+    /// Forgets devices not in `attached`, so one that is plugged back in
+    /// gets reported afresh.
+    pub fn retain<'a>(&mut self, attached: impl IntoIterator<Item = &'a str>) {
+        let attached: HashSet<&str> = attached.into_iter().collect();
+        self.active
+            .retain(|device, _| attached.contains(device.as_str()));
+    }
+}
+
+/// Runs `smartctl`.
+#[derive(Clone)]
+pub struct Smart {
+    exec: Arc<dyn CommandExecutor>,
+}
+
+impl Default for Smart {
+    fn default() -> Self {
+        Smart::with_executor(Arc::new(SystemExecutor))
+    }
+}
+
+impl Smart {
+    /// Uses `exec` to run `smartctl`.
+    pub fn with_executor(exec: Arc<dyn CommandExecutor>) -> Self {
+        Smart { exec }
+    }
+
+    /// Reads the health verdict and attributes of `device` (e.g. `sdb`).
+    /// Usually needs root.
+    pub fn read(&self, device: &str) -> Result<SmartReport> {
+        let node = format!("/dev/{}", device);
+        let output = self
+            .exec
+            .run("smartctl", &["-j", "-H", "-A", &node])
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => UsbError::ToolMissing {
+                    program: "smartctl".into(),
+                },
+                _ => UsbError::io("Failed to run smartctl", e),
+            })?;
+        // The exit status is a bit mask: bits 0 and 1 mean nothing could be
+        // read, the higher ones describe the disk's health.
+        if output.code.unwrap_or(1) & 0b11 != 0 {
+            let messages: Vec<String> = serde_json::from_slice::<SmartctlJson>(&output.stdout)
+                .map(|json| {
+                    json.smartctl
+                        .messages
+                        .into_iter()
+                        .map(|m| m.string)
+                        .collect()
+                })
+                .unwrap_or_default();
+            let stderr = if messages.is_empty() {
+                String::from_utf8_lossy(&output.stderr).trim().to_string()
+            } else {
+                messages.join("; ")
+            };
+            return Err(UsbError::CommandFailed {
+                program: "smartctl".into(),
+                stderr,
+            });
+        }
+        parse_smartctl_json(&output.stdout)
+    }
+}
+
+/// Parses `smartctl -j -H -A` output.
+pub fn parse_smartctl_json(json: &[u8]) -> Result<SmartReport> {
+    let parsed: SmartctlJson =
+        serde_json::from_slice(json).map_err(|e| UsbError::parse("smartctl output", e))?;
+    Ok(parsed.into())
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct SmartctlJson {
+    smartctl: SmartctlInfo,
+    smart_status: Option<SmartStatus>,
+    temperature: Option<Current>,
+    power_on_time: Option<Hours>,
+    ata_smart_attributes: Option<AttributeTable>,
+    nvme_smart_health_information_log: Option<NvmeHealth>,
+}
+
+#[derive(Deserialize)]
+struct NvmeHealth {
+    percentage_used: Option<u64>,
+    data_units_written: Option<u64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct SmartctlInfo {
+    messages: Vec<Message>,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    string: String,
+}
+
+#[derive(Deserialize)]
+struct SmartStatus {
+    passed: bool,
+}
+
+#[derive(Deserialize)]
+struct Current {
+    current: u64,
+}
+
+#[derive(Deserialize)]
+struct Hours {
+    hours: u64,
+}
+
+#[derive(Deserialize)]
+struct AttributeTable {
+    table: Vec<RawAttribute>,
+}
+
+#[derive(Deserialize)]
+struct RawAttribute {
+    id: u8,
+    name: String,
+    value: u64,
+    raw: RawValue,
+}
+
+#[derive(Deserialize)]
+struct RawValue {
+    value: u64,
+}
+
+impl From<SmartctlJson> for SmartReport {
+    fn from(json: SmartctlJson) -> Self {
+        SmartReport {
+            passed: json.smart_status.map(|s| s.passed),
+            temperature: json.temperature.map(|t| t.current),
+            power_on_hours: json.power_on_time.map(|t| t.hours),
+            attributes: json
+                .ata_smart_attributes
+                .map(|t| t.table)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|a| SmartAttribute {
+                    id: a.id,
+                    name: a.name,
+                    value: a.value,
+                    raw: a.raw.value,
+                })
+                .collect(),
+            nvme_percentage_used: json
+                .nvme_smart_health_information_log
+                .as_ref()
+                .and_then(|l| l.percentage_used),
+            nvme_bytes_written: json
+                .nvme_smart_health_information_log
+                .and_then(|l| l.data_units_written)
+                .map(|units| units * NVME_DATA_UNIT),
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! A small LUKS-encrypted "stash" partition at the end of an otherwise
+//! ordinary stick, for carrying keys and credentials.
+//!
+//! Creating one shrinks the last partition (and its filesystem) to make
+//! room if the end of the stick isn't free. The stash is found again by its
+//! LUKS label, and opened and closed with `cryptsetup`.
+
+use crate::backend::{check_output, read_mounts, run};
+use crate::error::{Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use crate::rescan::Rescanner;
+use crate::usb::{Partition, UsbDevice, partition_name};
+use crate::{MountOptions, SystemBackend};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// LUKS label of the stash partition.
+pub const STASH_LABEL: &str = "LSUSB-STASH";
+
+const SECTOR: u64 = 512;
+/// Partitions start and end on 1 MiB boundaries.
+const ALIGN: u64 = 2048;
+/// GPT keeps a backup header in the last 33 sectors.
+const GPT_BACKUP_SECTORS: u64 = 33;
+
+/// A disk's partition table, as printed by `sfdisk --json`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PartitionTable {
+    /// `dos` or `gpt`.
+    pub label: String,
+    #[serde(default)]
+    pub partitions: Vec<TableEntry>,
+}
+
+/// One partition of a [`PartitionTable`], in sectors.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TableEntry {
+    /// Device node, e.g. `/dev/sdb1`.
+    pub node: String,
+    pub start: u64,
+    pub size: u64,
+}
+
+impl TableEntry {
+    /// The partition number, from the trailing digits of the node.
+    fn number(&self) -> Option<u32> {
+        let digits = self.node.len()
+            - self
+                .node
+                .trim_end_matches(|c: char| c.is_ascii_digit())
+                .len();
+        self.node[self.node.len() - digits..].parse().ok()
+    }
+}
+
+/// Parses `sfdisk --json` output.
+pub fn parse_sfdisk_json(output: &str) -> Result<PartitionTable> {
+    #[derive(Deserialize)]
+    struct Output {
+        partitiontable: PartitionTable,
+    }
+    serde_json::from_str::<Output>(output)
+        .map(|o| o.partitiontable)
+        .map_err(|e| UsbError::Parse {
+            what: "sfdisk output".into(),
+            message: e.to_string(),
+        })
+}
+
+/// A partition that has to shrink to make room for the stash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shrink {
+    /// e.g. `sdb1`.
+    pub partition: String,
+    pub number: u32,
+    pub start: u64,
+    /// New size in sectors.
+    pub sectors: u64,
+}
+
+/// Where [`Stash::create`] puts the stash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub shrink: Option<Shrink>,
+    /// The stash partition, e.g. `sdb2`.
+    pub partition: String,
+    pub number: u32,
+    pub start: u64,
+    pub sectors: u64,
+}
+
+/// Works out where a stash of `bytes` goes on `disk` (e.g. `sdb`, of
+/// `disk_bytes`): the last `bytes` of the stick, rounded up to whole MiB,
+/// shrinking the partition in the way if there is one.
+pub fn plan(disk: &str, disk_bytes: u64, table: &PartitionTable, bytes: u64) -> Result<Layout> {
+    let sectors = bytes.div_ceil(SECTOR).div_ceil(ALIGN) * ALIGN;
+    let mut end = disk_bytes / SECTOR;
+    if table.label == "gpt" {
+        end = end.saturating_sub(GPT_BACKUP_SECTORS);
+    }
+    let end = end / ALIGN * ALIGN;
+    let start = end
+        .checked_sub(sectors)
+        .filter(|&s| s >= ALIGN)
+        .ok_or_else(|| UsbError::Unsupported("the stash doesn't fit on the stick".into()))?;
+    if table.label == "dos" && table.partitions.len() >= 4 {
+        return Err(UsbError::Unsupported(
+            "the MBR partition table already has 4 partitions".into(),
+        ));
+    }
+
+    let last = table.partitions.iter().max_by_key(|p| p.start + p.size);
+    let shrink = match last {
+        Some(p) if p.start + p.size > start => {
+            if p.start + ALIGN >= start {
+                return Err(UsbError::Unsupported(format!(
+                    "{} is too small to give up {} MiB",
+                    p.node,
+                    sectors * SECTOR / (1024 * 1024)
+                )));
+            }
+            let number = p.number().ok_or_else(|| {
+                UsbError::Unsupported(format!("can't tell the partition number of {}", p.node))
+            })?;
+            Some(Shrink {
+                partition: partition_name(disk, number),
+                number,
+                start: p.start,
+                sectors: start - p.start,
+            })
+        }
+        _ => None,
+    };
+    let number = table
+        .partitions
+        .iter()
+        .filter_map(TableEntry::number)
+        .max()
+        .unwrap_or(0)
+        + 1;
+    Ok(Layout {
+        shrink,
+        partition: partition_name(disk, number),
+        number,
+        start,
+        sectors,
+    })
+}
+
+/// The stash partition of `device`, if it has one.
+pub fn find(device: &UsbDevice) -> Result<&Partition> {
+    device
+        .partitions
+        .iter()
+        .find(|p| p.label.as_deref() == Some(STASH_LABEL))
+        .ok_or_else(|| {
+            UsbError::Unsupported(format!(
+                "{} has no stash partition; make one with `lsusb stash create`",
+                device.name
+            ))
+        })
+}
+
+/// The device-mapper name an open stash on `partition` gets.
+pub fn mapper_name(partition: &str) -> String {
+    format!("lsusb-stash-{}", partition)
+}
+
+/// Runs `cryptsetup`, `blkid`, `sfdisk` and the filesystem resize tools.
+#[derive(Clone)]
+pub struct Stash {
+    exec: Arc<dyn CommandExecutor>,
+    rescanner: Rescanner,
+}
+
+impl Default for Stash {
+    fn default() -> Self {
+        Stash::with_executor(Arc::new(SystemExecutor))
+    }
+}
+
+impl Stash {
+    /// Uses `exec` to run the tools.
+    pub fn with_executor(exec: Arc<dyn CommandExecutor>) -> Self {
+        Stash {
+            rescanner: Rescanner::with_executor(exec.clone()),
+            exec,
+        }
+    }
+
+    /// Waits for the new partition's node in `dir` instead of `/dev`.
+    pub fn dev_dir(mut self, dir: PathBuf) -> Self {
+        self.rescanner = self.rescanner.dev_dir(dir);
+        self
+    }
+
+    /// The partition table of `disk` (e.g. `sdb`).
+    pub fn partition_table(&self, disk: &str) -> Result<PartitionTable> {
+        let output = run(
+            self.exec.as_ref(),
+            "sfdisk",
+            &["--json", &format!("/dev/{}", disk)],
+        )?;
+        parse_sfdisk_json(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    /// Carves `layout` out of `device` and sets up LUKS2 with `passphrase`
+    /// and an ext4 filesystem inside. Nothing on `device` may be mounted.
+    pub fn create(&self, device: &UsbDevice, layout: &Layout, passphrase: &str) -> Result<()> {
+        if let Some(mountpoint) = device.mountpoints().next() {
+            return Err(UsbError::Unsupported(format!(
+                "{} is mounted at {}; unmount it first",
+                device.name, mountpoint
+            )));
+        }
+        let disk = format!("/dev/{}", device.name);
+        if let Some(shrink) = &layout.shrink {
+            // The filesystem first: if it doesn't fit, the table is untouched.
+            self.shrink_filesystem(&shrink.partition, shrink.sectors * SECTOR)?;
+            let number = shrink.number.to_string();
+            let line = format!("{}, {}\n", shrink.start, shrink.sectors);
+            self.sfdisk(&["--no-reread", "-N", &number, &disk], &line)?;
+        }
+        let line = format!("start={}, size={}, type=L\n", layout.start, layout.sectors);
+        self.sfdisk(&["--append", &disk], &line)?;
+        self.rescanner.rescan(&device.name, &[&layout.partition])?;
+
+        let node = format!("/dev/{}", layout.partition);
+        self.cryptsetup(
+            &[
+                "luksFormat",
+                "--batch-mode",
+                "--type",
+                "luks2",
+                "--label",
+                STASH_LABEL,
+                "--key-file",
+                "-",
+                &node,
+            ],
+            passphrase,
+        )?;
+        let name = mapper_name(&layout.partition);
+        self.cryptsetup(&["open", "--key-file", "-", &node, &name], passphrase)?;
+        let formatted = run(
+            self.exec.as_ref(),
+            "mkfs.ext4",
+            &["-q", "-L", "stash", &format!("/dev/mapper/{}", name)],
+        );
+        run(self.exec.as_ref(), "cryptsetup", &["close", &name])?;
+        formatted.map(drop)
+    }
+
+    /// Unlocks the stash on `partition` and mounts it, at `mountpoint` or
+    /// wherever the backend likes. Returns the mountpoint.
+    pub fn open(
+        &self,
+        backend: &dyn SystemBackend,
+        partition: &str,
+        passphrase: &str,
+        mountpoint: Option<&Path>,
+        options: MountOptions,
+    ) -> Result<PathBuf> {
+        let name = mapper_name(partition);
+        let node = format!("/dev/{}", partition);
+        let mut args = vec!["open", "--key-file", "-", &node, &name];
+        if options.read_only {
+            args.insert(1, "--readonly");
+        }
+        self.cryptsetup(&args, passphrase)?;
+        match backend.mount(&format!("mapper/{}", name), mountpoint, options) {
+            Ok(mountpoint) => Ok(mountpoint),
+            Err(e) => {
+                let _ = run(self.exec.as_ref(), "cryptsetup", &["close", &name]);
+                Err(e)
+            }
+        }
+    }
+
+    /// Unmounts and locks the stash on `partition`.
+    pub fn close(&self, backend: &dyn SystemBackend, partition: &str) -> Result<()> {
+        let name = mapper_name(partition);
+        let source = format!("/dev/mapper/{}", name);
+        for entry in read_mounts()?.into_iter().filter(|m| m.source == source) {
+            backend.unmount(&entry.target)?;
+        }
+        run(self.exec.as_ref(), "cryptsetup", &["close", &name]).map(drop)
+    }
+
+    /// Shrinks the filesystem on `partition` to at most `bytes`.
+    fn shrink_filesystem(&self, partition: &str, bytes: u64) -> Result<()> {
+        let node = format!("/dev/{}", partition);
+        let output = run(
+            self.exec.as_ref(),
+            "blkid",
+            &["-o", "value", "-s", "TYPE", &node],
+        )?;
+        let fstype = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        match fstype.as_str() {
+            "ext2" | "ext3" | "ext4" => {
+                // resize2fs insists on a freshly checked filesystem.
+                run(self.exec.as_ref(), "e2fsck", &["-f", "-p", &node])?;
+                run(
+                    self.exec.as_ref(),
+                    "resize2fs",
+                    &[&node, &format!("{}K", bytes / 1024)],
+                )
+                .map(drop)
+            }
+            "ntfs" => check_output(
+                "ntfsresize",
+                self.exec.run_with_stdin(
+                    "ntfsresize",
+                    &["--size", &bytes.to_string(), &node],
+                    &mut "y\n".as_bytes(),
+                ),
+            )
+            .map(drop),
+            // fatresize shrinks the partition along with the filesystem;
+            // the table is rewritten with the exact size afterwards anyway.
+            "vfat" => run(
+                self.exec.as_ref(),
+                "fatresize",
+                &["--size", &bytes.to_string(), &node],
+            )
+            .map(drop),
+            "" => Ok(()),
+            other => Err(UsbError::Unsupported(format!(
+                "can't shrink {} ({}); free up space at the end of the stick first",
+                partition, other
+            ))),
+        }
+    }
+
+    fn sfdisk(&self, args: &[&str], script: &str) -> Result<()> {
+        check_output(
+            "sfdisk",
+            self.exec
+                .run_with_stdin("sfdisk", args, &mut script.as_bytes()),
+        )
+        .map(drop)
+    }
+
+    /// cryptsetup reads the key from stdin with `--key-file -`, and all of
+    /// it, so there is no trailing newline.
+    fn cryptsetup(&self, args: &[&str], passphrase: &str) -> Result<()> {
+        check_output(
+            "cryptsetup",
+            self.exec
+                .run_with_stdin("cryptsetup", args, &mut passphrase.as_bytes()),
+        )
+        .map(drop)
+    }
+}
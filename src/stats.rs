@@ -0,0 +1,156 @@
+//! Lifetime statistics per drive: how much lsusb has written to it, in how
+//! many sessions, and when it was first and last seen, for judging when a
+//! heavily used stick is due for retirement.
+//!
+//! Drives are keyed by USB serial number, so a stick keeps its history
+//! whatever port or device name it gets. The database is one JSON file,
+//! rewritten under an exclusive `flock(2)` so concurrent runs don't lose
+//! each other's updates.
+
+use crate::audit::utc_timestamp;
+use crate::error::{IoContext, Result, UsbError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+/// What is known about one drive.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DriveStats {
+    /// Bytes lsusb copied onto it, over all sessions.
+    pub bytes_written: u64,
+    /// Copies and backups that wrote to it.
+    pub sessions: u64,
+    /// Seconds since the Unix epoch.
+    pub first_seen: u64,
+    pub last_seen: u64,
+    /// Every vendor and model string it has reported; more than one means
+    /// the serial number isn't as unique as it should be.
+    pub models: Vec<String>,
+}
+
+impl DriveStats {
+    /// `first_seen` as `YYYY-MM-DD HH:MM:SS` UTC.
+    pub fn first_seen_utc(&self) -> String {
+        utc_timestamp(self.first_seen)
+    }
+
+    /// `last_seen` as `YYYY-MM-DD HH:MM:SS` UTC.
+    pub fn last_seen_utc(&self) -> String {
+        utc_timestamp(self.last_seen)
+    }
+
+    fn saw(&mut self, model: &str, now: u64) {
+        if self.first_seen == 0 {
+            self.first_seen = now;
+        }
+        self.last_seen = self.last_seen.max(now);
+        if !model.is_empty() && !self.models.iter().any(|m| m == model) {
+            self.models.push(model.to_string());
+        }
+    }
+}
+
+/// The statistics file.
+#[derive(Debug, Clone)]
+pub struct StatsDb {
+    path: PathBuf,
+}
+
+impl StatsDb {
+    /// A database at `path`.
+    pub fn new(path: impl Into<PathBuf>) -> StatsDb {
+        StatsDb { path: path.into() }
+    }
+
+    /// `$XDG_STATE_HOME/lsusb/stats.json`, falling back to
+    /// `~/.local/state/lsusb/stats.json`.
+    pub fn default_path() -> Option<PathBuf> {
+        crate::audit::AuditLog::default_path().map(|log| log.with_file_name("stats.json"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Every drive recorded, by serial number.
+    pub fn drives(&self) -> Result<BTreeMap<String, DriveStats>> {
+        match fs::read_to_string(&self.path) {
+            Ok(text) => self.parse(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(UsbError::io(
+                format!("Failed to read {}", self.path.display()),
+                e,
+            )),
+        }
+    }
+
+    /// The drive with serial number `serial`, if it was ever recorded.
+    pub fn get(&self, serial: &str) -> Result<Option<DriveStats>> {
+        Ok(self.drives()?.remove(serial))
+    }
+
+    /// Notes that drive `serial`, reporting `model`, was attached at `now`
+    /// (seconds since the epoch).
+    pub fn seen(&self, serial: &str, model: &str, now: u64) -> Result<()> {
+        self.update(|drives| {
+            drives
+                .entry(serial.to_string())
+                .or_default()
+                .saw(model, now)
+        })
+    }
+
+    /// Records a session that wrote `bytes` to drive `serial` at `now`.
+    pub fn wrote(&self, serial: &str, model: &str, bytes: u64, now: u64) -> Result<()> {
+        self.update(|drives| {
+            let drive = drives.entry(serial.to_string()).or_default();
+            drive.saw(model, now);
+            drive.sessions += 1;
+            drive.bytes_written += bytes;
+        })
+    }
+
+    fn update(&self, change: impl FnOnce(&mut BTreeMap<String, DriveStats>)) -> Result<()> {
+        use std::os::fd::AsRawFd;
+
+        let context = || format!("Failed to update {}", self.path.display());
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).io_context(context)?;
+        }
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)
+            .io_context(context)?;
+        // Released when the file is closed.
+        while unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() != Some(libc::EINTR) {
+                return Err(UsbError::io(context(), e));
+            }
+        }
+        let mut text = String::new();
+        file.read_to_string(&mut text).io_context(context)?;
+        let mut drives = if text.trim().is_empty() {
+            BTreeMap::new()
+        } else {
+            self.parse(&text)?
+        };
+        change(&mut drives);
+        let json = serde_json::to_vec_pretty(&drives)
+            .map_err(|e| UsbError::parse("drive statistics", e))?;
+        file.rewind()
+            .and_then(|()| file.set_len(0))
+            .and_then(|()| file.write_all(&json))
+            .io_context(context)
+    }
+
+    fn parse(&self, text: &str) -> Result<BTreeMap<String, DriveStats>> {
+        serde_json::from_str(text).map_err(|e| UsbError::parse(self.path.display().to_string(), e))
+    }
+}
@@ -0,0 +1,151 @@
+//! Destination templates: `{label}/{date}` and the like in a copy's or a
+//! backup's destination, filled in when it runs, so recurring transfers
+//! land in dated folders without anyone creating them first.
+//!
+//! The variables are `label` and `serial` (of the stick being written to),
+//! `hostname`, `user`, `date` (`YYYY-MM-DD`) and `time` (`HHMMSS`), both in
+//! local time. `{{` and `}}` stand for literal braces. A value never adds
+//! directories: any `/` in it becomes `_`.
+
+use crate::error::{Result, UsbError};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// What the variables expand to.
+#[derive(Debug, Clone)]
+pub struct Vars {
+    /// Filesystem label of the destination partition.
+    pub label: Option<String>,
+    /// USB serial number of the destination drive.
+    pub serial: Option<String>,
+    pub hostname: Option<String>,
+    pub user: Option<String>,
+    pub time: SystemTime,
+}
+
+impl Vars {
+    /// This machine, user and moment, for a destination with no known
+    /// label or serial.
+    pub fn now() -> Vars {
+        Vars {
+            label: None,
+            serial: None,
+            hostname: hostname(),
+            user: Some(crate::audit::current_user()),
+            time: SystemTime::now(),
+        }
+    }
+
+    /// [`now`](Vars::now) for a destination with this label and serial.
+    pub fn for_drive(label: Option<String>, serial: Option<String>) -> Vars {
+        Vars {
+            label,
+            serial,
+            ..Vars::now()
+        }
+    }
+
+    fn get(&self, name: &str) -> Result<String> {
+        let missing = |what: &str| {
+            UsbError::Unsupported(format!(
+                "{{{}}} is used, but the destination has no {}",
+                name, what
+            ))
+        };
+        let value = match name {
+            "label" => self
+                .label
+                .clone()
+                .filter(|l| !l.is_empty())
+                .ok_or_else(|| missing("filesystem label"))?,
+            "serial" => self
+                .serial
+                .clone()
+                .ok_or_else(|| missing("USB serial number"))?,
+            "hostname" => self.hostname.clone().ok_or_else(|| missing("host name"))?,
+            "user" => self.user.clone().ok_or_else(|| missing("user"))?,
+            "date" => {
+                let (year, month, day, ..) = local_time(self.time);
+                format!("{:04}-{:02}-{:02}", year, month, day)
+            }
+            "time" => {
+                let (.., hour, minute, second) = local_time(self.time);
+                format!("{:02}{:02}{:02}", hour, minute, second)
+            }
+            other => {
+                return Err(UsbError::Unsupported(format!(
+                    "Unknown variable {{{}}} (expected label, serial, hostname, user, date or time)",
+                    other
+                )));
+            }
+        };
+        Ok(match value.replace(['/', '\0'], "_") {
+            dots if dots == "." || dots == ".." => "_".repeat(dots.len()),
+            value => value,
+        })
+    }
+}
+
+/// Whether `path` has anything to expand.
+pub fn is_template(path: &Path) -> bool {
+    path.to_string_lossy().contains(['{', '}'])
+}
+
+/// `path` with its variables replaced by their values in `vars`.
+pub fn expand(path: &Path, vars: &Vars) -> Result<PathBuf> {
+    let text = path
+        .to_str()
+        .ok_or_else(|| UsbError::Unsupported(format!("{} isn't valid UTF-8", path.display())))?;
+    let unbalanced = || UsbError::Unsupported(format!("Unbalanced braces in {}", text));
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find(['{', '}']) {
+        expanded.push_str(&rest[..at]);
+        let brace = &rest[at..];
+        if brace.starts_with("{{") || brace.starts_with("}}") {
+            expanded.push_str(&brace[..1]);
+            rest = &brace[2..];
+        } else if brace.starts_with('}') {
+            return Err(unbalanced());
+        } else {
+            let end = brace.find('}').ok_or_else(unbalanced)?;
+            expanded.push_str(&vars.get(&brace[1..end])?);
+            rest = &brace[end + 1..];
+        }
+    }
+    expanded.push_str(rest);
+    Ok(PathBuf::from(expanded))
+}
+
+/// `time` in the local time zone: year, month, day, hour, minute, second.
+fn local_time(time: SystemTime) -> (i32, u32, u32, u32, u32, u32) {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs()) as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&secs, &mut tm) }.is_null() {
+        return (1970, 1, 1, 0, 0, 0);
+    }
+    (
+        tm.tm_year + 1900,
+        tm.tm_mon as u32 + 1,
+        tm.tm_mday as u32,
+        tm.tm_hour as u32,
+        tm.tm_min as u32,
+        tm.tm_sec as u32,
+    )
+}
+
+fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    let name = String::from_utf8_lossy(&buf[..len]);
+    // The short name, as the shell prompt shows it.
+    name.split('.')
+        .next()
+        .filter(|n| !n.is_empty())
+        .map(String::from)
+}
@@ -0,0 +1,236 @@
+//! Android support through Termux.
+//!
+//! Android doesn't let apps see `/sys/block` or mount anything, so OTG
+//! sticks are reached through the Storage Access Framework: the user grants
+//! Termux a directory (`termux-saf-managedir`), and the `termux-saf-*`
+//! commands from Termux:API then list, read and write files under it by
+//! `content://` URI.
+
+use crate::backend::{check_output, run};
+use crate::cancel::CancelToken;
+use crate::error::{IoContext, Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use crate::progress::{Phase, ProgressEvent, ProgressSink};
+use serde::Deserialize;
+use std::fs::{self, File};
+use std::path::Path;
+use std::sync::Arc;
+use walkdir::WalkDir;
+
+/// Whether we are running inside Termux.
+pub fn is_termux() -> bool {
+    std::env::var_os("TERMUX_VERSION").is_some()
+        || std::env::var("PREFIX").is_ok_and(|p| p.contains("com.termux"))
+}
+
+/// A file or directory reachable through the Storage Access Framework.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SafEntry {
+    pub name: String,
+    pub uri: String,
+    /// MIME type; `inode/directory` for directories.
+    #[serde(rename = "type", default)]
+    pub mime_type: String,
+    /// Size in bytes, for files.
+    #[serde(default)]
+    pub length: u64,
+}
+
+impl SafEntry {
+    pub fn is_dir(&self) -> bool {
+        self.mime_type == "inode/directory"
+    }
+}
+
+/// What a push or pull did.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SafReport {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// Runs the Termux:API `termux-saf-*` commands.
+#[derive(Clone)]
+pub struct Saf {
+    exec: Arc<dyn CommandExecutor>,
+}
+
+impl Default for Saf {
+    fn default() -> Self {
+        Saf::with_executor(Arc::new(SystemExecutor))
+    }
+}
+
+impl Saf {
+    /// Uses `exec` to run the `termux-saf-*` commands.
+    pub fn with_executor(exec: Arc<dyn CommandExecutor>) -> Self {
+        Saf { exec }
+    }
+
+    fn json<T: for<'de> Deserialize<'de>>(&self, program: &str, args: &[&str]) -> Result<T> {
+        let output = run(self.exec.as_ref(), program, args)?;
+        serde_json::from_slice(&output.stdout)
+            .map_err(|e| UsbError::parse(format!("{} output", program), e))
+    }
+
+    /// Directories the user has granted Termux access to; an OTG stick shows
+    /// up here once it has been picked in `termux-saf-managedir`.
+    pub fn dirs(&self) -> Result<Vec<SafEntry>> {
+        self.json("termux-saf-dirs", &[])
+    }
+
+    /// Opens Android's folder picker so the user can grant a directory.
+    pub fn grant(&self) -> Result<()> {
+        run(self.exec.as_ref(), "termux-saf-managedir", &[]).map(|_| ())
+    }
+
+    /// Entries directly inside the directory at `uri`.
+    pub fn ls(&self, uri: &str) -> Result<Vec<SafEntry>> {
+        self.json("termux-saf-ls", &[uri])
+    }
+
+    /// The entry at `uri`.
+    pub fn stat(&self, uri: &str) -> Result<SafEntry> {
+        self.json("termux-saf-stat", &[uri])
+    }
+
+    fn mkdir(&self, parent: &str, name: &str) -> Result<String> {
+        if let Some(existing) = self
+            .ls(parent)?
+            .into_iter()
+            .find(|e| e.name == name && e.is_dir())
+        {
+            return Ok(existing.uri);
+        }
+        let output = run(self.exec.as_ref(), "termux-saf-mkdir", &[parent, name])?;
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn upload(&self, source: &Path, parent: &str, name: &str) -> Result<u64> {
+        let output = run(self.exec.as_ref(), "termux-saf-create", &[parent, name])?;
+        let uri = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let mut file =
+            File::open(source).io_context(|| format!("Failed to open source file {:?}", source))?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        check_output(
+            "termux-saf-write",
+            self.exec
+                .run_with_stdin("termux-saf-write", &[&uri], &mut file),
+        )?;
+        Ok(size)
+    }
+
+    /// Copies `source` (a file or directory) into the SAF directory at
+    /// `dest_uri`.
+    pub fn push(
+        &self,
+        source: &Path,
+        dest_uri: &str,
+        cancel: &CancelToken,
+        mut progress: impl ProgressSink,
+    ) -> Result<SafReport> {
+        let mut report = SafReport::default();
+        let root_name = file_name(source)?;
+        progress.emit(ProgressEvent::Phase(Phase::Copying));
+
+        if source.is_file() {
+            progress.emit(ProgressEvent::File {
+                path: source.to_path_buf(),
+                size: 0,
+            });
+            report.bytes += self.upload(source, dest_uri, &root_name)?;
+            report.files += 1;
+        } else {
+            // SAF addresses directories by URI, so remember the URI created
+            // for each directory as the walk descends.
+            let mut dir_uris = vec![(source.to_path_buf(), self.mkdir(dest_uri, &root_name)?)];
+            for entry in WalkDir::new(source).min_depth(1) {
+                cancel.check()?;
+                let entry =
+                    entry.map_err(|e| UsbError::io("Failed to read directory entry", e.into()))?;
+                let parent = entry.path().parent().unwrap_or(source);
+                let parent_uri = dir_uris
+                    .iter()
+                    .rev()
+                    .find(|(p, _)| p == parent)
+                    .map(|(_, uri)| uri.clone())
+                    .ok_or_else(|| {
+                        UsbError::Unsupported(format!("Lost track of {}", parent.display()))
+                    })?;
+                let name = file_name(entry.path())?;
+                if entry.file_type().is_dir() {
+                    let uri = self.mkdir(&parent_uri, &name)?;
+                    dir_uris.push((entry.path().to_path_buf(), uri));
+                } else {
+                    progress.emit(ProgressEvent::File {
+                        path: entry.path().to_path_buf(),
+                        size: 0,
+                    });
+                    report.bytes += self.upload(entry.path(), &parent_uri, &name)?;
+                    report.files += 1;
+                }
+            }
+        }
+        progress.emit(ProgressEvent::Phase(Phase::Done));
+        Ok(report)
+    }
+
+    /// Copies the SAF file or directory `entry` into the local directory
+    /// `dest`.
+    pub fn pull(
+        &self,
+        entry: &SafEntry,
+        dest: &Path,
+        cancel: &CancelToken,
+        mut progress: impl ProgressSink,
+    ) -> Result<SafReport> {
+        let mut report = SafReport::default();
+        fs::create_dir_all(dest)
+            .io_context(|| format!("Failed to create directory {}", dest.display()))?;
+        progress.emit(ProgressEvent::Phase(Phase::Copying));
+        self.pull_into(entry, dest, cancel, &mut progress, &mut report)?;
+        progress.emit(ProgressEvent::Phase(Phase::Done));
+        Ok(report)
+    }
+
+    fn pull_into(
+        &self,
+        entry: &SafEntry,
+        dest: &Path,
+        cancel: &CancelToken,
+        progress: &mut impl ProgressSink,
+        report: &mut SafReport,
+    ) -> Result<()> {
+        cancel.check()?;
+        let target = dest.join(&entry.name);
+        if entry.is_dir() {
+            fs::create_dir_all(&target)
+                .io_context(|| format!("Failed to create directory {}", target.display()))?;
+            for child in self.ls(&entry.uri)? {
+                self.pull_into(&child, &target, cancel, progress, report)?;
+            }
+            return Ok(());
+        }
+
+        progress.emit(ProgressEvent::File {
+            path: target.clone(),
+            size: entry.length,
+        });
+        let mut file = File::create(&target)
+            .io_context(|| format!("Failed to create dest file {:?}", target))?;
+        check_output(
+            "termux-saf-read",
+            self.exec
+                .run_to_writer("termux-saf-read", &[&entry.uri], &mut file),
+        )?;
+        report.files += 1;
+        report.bytes += entry.length;
+        Ok(())
+    }
+}
+
+fn file_name(path: &Path) -> Result<String> {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .ok_or_else(|| UsbError::Unsupported(format!("Invalid source name {}", path.display())))
+}
@@ -0,0 +1,2092 @@
+use crate::backup::Conflict;
+use crate::cancel::CancelToken;
+use crate::crypt::Crypt;
+use crate::error::{IoContext, Result, UsbError};
+use crate::ignore::Ignore;
+use crate::progress::{NoProgress, Phase, ProgressEvent, ProgressSink};
+use crate::scan::{Scanner, Verdict};
+use jwalk::WalkDirGeneric;
+use jwalk::rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Tuning knobs for [`copy_to_usb`].
+#[derive(Debug, Clone)]
+pub struct TransferOptions {
+    /// Size of the read/write buffer a copy starts with, in bytes. With
+    /// `adaptive_buffer` it then follows the destination's throughput (see
+    /// [`AdaptiveBuffer`]); without, every write uses it.
+    pub buffer_size: usize,
+    pub adaptive_buffer: bool,
+    /// Checked between chunks; cancelling stops the copy with
+    /// [`UsbError::Cancelled`] and removes the partially written file.
+    pub cancel: CancelToken,
+    /// Scans every source file before it is written; infected files are
+    /// left out and listed in [`TransferReport::infected`].
+    pub scanner: Option<Scanner>,
+    /// Encrypts (or decrypts) each file on its way to the destination
+    /// instead of copying it byte for byte.
+    pub crypt: Option<Crypt>,
+    /// What directory copies leave out; by default whatever the sources'
+    /// `.lsusbignore` files say.
+    pub ignore: Ignore,
+    /// What is carried over besides the contents; by default nothing.
+    pub preserve: Preserve,
+    /// What to do with source files that would land on one name: those
+    /// whose names only differ in case or accents, when the destination
+    /// can't tell them apart, and those [`Reshape::flatten`] brings together.
+    pub on_collision: Conflict,
+    /// Where in the destination directory copies put each path; by
+    /// default where it was in the source.
+    pub reshape: Reshape,
+    /// When a file already at the destination counts as unchanged, for
+    /// restores; backup runs take it from their profile.
+    pub mtime_tolerance: MtimeTolerance,
+    /// Asked about each file that is already at the destination; without
+    /// it, the file is overwritten, as `cp` does. Copies to several
+    /// destinations at once always overwrite.
+    pub on_existing: Option<ConflictPrompt>,
+    /// Told about each file at the destination just before it is replaced;
+    /// an error stops the copy with that file untouched.
+    pub on_overwrite: Option<OverwriteHook>,
+}
+
+/// Decides what a copy does with a file already at the destination, given
+/// the source and that file: overwrite it, skip the source, or write it
+/// under a free name next to it. Called on the copying thread, one file at
+/// a time, so it can ask someone; the copy waits for the answer.
+#[derive(Clone)]
+pub struct ConflictPrompt(Arc<AskFn>);
+
+type AskFn = dyn Fn(&Path, &Path) -> Conflict + Send + Sync;
+
+impl ConflictPrompt {
+    pub fn new(ask: impl Fn(&Path, &Path) -> Conflict + Send + Sync + 'static) -> ConflictPrompt {
+        ConflictPrompt(Arc::new(ask))
+    }
+
+    pub fn ask(&self, source: &Path, existing: &Path) -> Conflict {
+        (self.0)(source, existing)
+    }
+}
+
+impl fmt::Debug for ConflictPrompt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ConflictPrompt")
+    }
+}
+
+/// Called with the source and the file it is about to replace, e.g. to
+/// log the overwrite first.
+#[derive(Clone)]
+pub struct OverwriteHook(Arc<OverwriteFn>);
+
+type OverwriteFn = dyn Fn(&Path, &Path) -> Result<()> + Send + Sync;
+
+impl OverwriteHook {
+    pub fn new(hook: impl Fn(&Path, &Path) -> Result<()> + Send + Sync + 'static) -> OverwriteHook {
+        OverwriteHook(Arc::new(hook))
+    }
+
+    pub fn before(&self, source: &Path, existing: &Path) -> Result<()> {
+        (self.0)(source, existing)
+    }
+}
+
+impl fmt::Debug for OverwriteHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OverwriteHook")
+    }
+}
+
+/// How far apart a file's modification time and its copy's may be for the
+/// copy to still count as up to date. FAT stores times in 2-second steps,
+/// and in local time with no zone, so a stick written before a daylight
+/// saving change or in another time zone shows every time shifted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MtimeTolerance {
+    pub window: Duration,
+    /// Also accept differences of a whole number of quarter hours, up to
+    /// the 14 hours time zones span, within `window`. A file edited exactly
+    /// that much later without changing size is missed, so this is opt-in.
+    pub timezone_shifts: bool,
+}
+
+impl Default for MtimeTolerance {
+    fn default() -> Self {
+        MtimeTolerance {
+            window: Duration::from_secs(2),
+            timezone_shifts: false,
+        }
+    }
+}
+
+impl MtimeTolerance {
+    /// Whether `a` and `b` count as the same time.
+    pub fn same(&self, a: SystemTime, b: SystemTime) -> bool {
+        const QUARTER_HOUR: u64 = 15 * 60;
+        const MAX_SHIFT: Duration = Duration::from_secs(14 * 3600);
+
+        let difference = a
+            .duration_since(b)
+            .or_else(|_| b.duration_since(a))
+            .unwrap_or_default();
+        if difference <= self.window {
+            return true;
+        }
+        if !self.timezone_shifts || difference > MAX_SHIFT + self.window {
+            return false;
+        }
+        let window = self.window.as_secs_f64();
+        let off = difference.as_secs_f64() % QUARTER_HOUR as f64;
+        off <= window || QUARTER_HOUR as f64 - off <= window
+    }
+}
+
+/// What a copy carries over from each source file and directory besides
+/// its contents, as `cp --preserve` takes it: a comma-separated list of
+/// `mode`, `timestamps` and `xattr` (user attributes and POSIX ACLs), or
+/// `all`. Not applied to files that are encrypted or decrypted on the way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Preserve {
+    pub mode: bool,
+    pub timestamps: bool,
+    pub xattrs: bool,
+}
+
+impl Preserve {
+    pub const ALL: Preserve = Preserve {
+        mode: true,
+        timestamps: true,
+        xattrs: true,
+    };
+
+    fn any(&self) -> bool {
+        self.mode || self.timestamps || self.xattrs
+    }
+}
+
+impl FromStr for Preserve {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut preserve = Preserve::default();
+        for item in s.split(',') {
+            match item.trim() {
+                "mode" => preserve.mode = true,
+                "timestamps" => preserve.timestamps = true,
+                "xattr" => preserve.xattrs = true,
+                "all" => preserve = Preserve::ALL,
+                other => {
+                    return Err(format!(
+                        "unknown attribute '{}' (expected mode, timestamps, xattr or all)",
+                        other
+                    ));
+                }
+            }
+        }
+        Ok(preserve)
+    }
+}
+
+/// How a directory copy rearranges the source's paths, each taken relative
+/// to the destination directory and so starting with the copy's own name
+/// (`docs/2024/report.pdf` for `cp docs /media/usb`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reshape {
+    /// Puts every file straight into the destination (or `prefix`),
+    /// leaving the directories out.
+    pub flatten: bool,
+    /// Drops this many leading directories from every path, like `tar
+    /// --strip-components`; what has no more than that is left out.
+    pub strip_components: usize,
+    /// A relative directory everything goes into.
+    pub prefix: Option<PathBuf>,
+}
+
+impl Reshape {
+    /// Whether paths stay as they are.
+    pub fn is_identity(&self) -> bool {
+        !self.flatten && self.strip_components == 0 && self.prefix.is_none()
+    }
+
+    /// Where `path`, a directory if `is_dir`, goes; `None` if it's left out.
+    pub fn apply(&self, path: &Path, is_dir: bool) -> Option<PathBuf> {
+        let kept: PathBuf = if self.flatten {
+            if is_dir {
+                return None;
+            }
+            PathBuf::from(path.file_name()?)
+        } else {
+            path.components().skip(self.strip_components).collect()
+        };
+        if kept.as_os_str().is_empty() {
+            return None;
+        }
+        Some(match &self.prefix {
+            Some(prefix) => prefix.join(kept),
+            None => kept,
+        })
+    }
+
+    fn validate(&self) -> Result<()> {
+        if let Some(prefix) = &self.prefix
+            && prefix
+                .components()
+                .any(|c| !matches!(c, std::path::Component::Normal(_)))
+        {
+            return Err(UsbError::Unsupported(format!(
+                "The prefix {} must be a relative path without '..'",
+                prefix.display()
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Default for TransferOptions {
+    fn default() -> Self {
+        TransferOptions {
+            buffer_size: 256 * 1024,
+            adaptive_buffer: true,
+            cancel: CancelToken::default(),
+            scanner: None,
+            crypt: None,
+            ignore: Ignore::default(),
+            preserve: Preserve::default(),
+            mtime_tolerance: MtimeTolerance::default(),
+            on_collision: Conflict::default(),
+            reshape: Reshape::default(),
+            on_existing: None,
+            on_overwrite: None,
+        }
+    }
+}
+
+/// What a finished [`copy_to_usb`] call actually did.
+#[derive(Debug, Clone, Default)]
+pub struct TransferReport {
+    /// Number of regular files written to the destination.
+    pub files_copied: u64,
+    /// Total number of bytes written.
+    pub bytes_copied: u64,
+    /// Wall-clock time spent copying, including the size calculation.
+    pub elapsed: Duration,
+    /// Source files the scanner flagged and that were not copied, with the
+    /// signature it reported.
+    pub infected: Vec<(PathBuf, String)>,
+    /// What [`TransferOptions::preserve`] asked for but the destination
+    /// couldn't store (FAT and exFAT have no extended attributes or modes):
+    /// the copy's path and the attribute's name, or `mode`.
+    pub dropped: Vec<(PathBuf, String)>,
+    /// Source files that would have landed on another one's name.
+    pub collisions: Vec<Collision>,
+    /// Files already at the destination that
+    /// [`TransferOptions::on_existing`] said to leave alone.
+    pub skipped: Vec<PathBuf>,
+}
+
+/// Two source files, or directories, that would be stored under one name:
+/// on a case-insensitive destination, or after [`Reshape::flatten`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Collision {
+    pub source: PathBuf,
+    /// The one copied first, under the name both want.
+    pub clashes_with: PathBuf,
+    /// Where `source` went instead, relative to the destination directory
+    /// (so starting with the copy's name, unless reshaped); `None` when it
+    /// was skipped. The other's name with [`Conflict::Overwrite`].
+    pub written_to: Option<PathBuf>,
+}
+
+/// Recursively copies `source` to `dest`, following `cp -r` semantics: if
+/// `dest` is an existing directory the source is copied *into* it.
+///
+/// Runs silently; use [`copy_with_progress`] to observe progress.
+pub fn copy_to_usb(
+    source: &Path,
+    dest: &Path,
+    options: &TransferOptions,
+) -> Result<TransferReport> {
+    copy_with_progress(source, dest, options, NoProgress)
+}
+
+/// Same as [`copy_to_usb`], reporting [`ProgressEvent`]s to `progress` as it
+/// goes: a scanning phase, the totals, then per-file and byte updates.
+pub fn copy_with_progress(
+    source: &Path,
+    dest: &Path,
+    options: &TransferOptions,
+    progress: impl ProgressSink,
+) -> Result<TransferReport> {
+    let started = Instant::now();
+    let job = crate::jobs::start("copy", &[source, dest], Some(&options.cancel));
+    let mut progress = job.track(progress);
+    let mut report = TransferReport::default();
+    // Fail before scanning rather than on the first write deep into the copy.
+    ensure_writable(dest)?;
+    options.reshape.validate()?;
+    if source.is_file() && !options.reshape.is_identity() {
+        return Err(UsbError::Unsupported(
+            "Paths can only be reshaped when copying a directory".into(),
+        ));
+    }
+
+    progress.emit(ProgressEvent::Phase(Phase::Scanning));
+    let mut total_size = 0;
+    let mut total_files = 0;
+    let mut tree = Vec::new();
+    let mut plan = Vec::new();
+    let existing = existing_part(dest);
+    if source.is_file() {
+        total_size = source
+            .metadata()
+            .io_context(|| format!("Failed to read {}", source.display()))?
+            .len();
+        total_files = 1;
+    } else {
+        tree = collect_tree(source, &options.ignore, &options.cancel)?;
+        plan = plan_names(
+            &tree,
+            source,
+            dest,
+            existing,
+            options,
+            &mut report.collisions,
+        )?;
+        for (entry, target) in tree.iter().zip(&plan) {
+            if let (Some(len), Some(_)) = (entry.file_len, target) {
+                total_size += len;
+                total_files += 1;
+            }
+        }
+    }
+    if source.is_file() {
+        if let Some(path) = destination_root(source, dest) {
+            preflight(dest, [creates(existing, &path, Path::new(""))])?;
+        }
+    } else {
+        let base = directory_base(dest);
+        preflight(
+            dest,
+            plan.iter()
+                .flatten()
+                .map(|target| creates(existing, &base, target)),
+        )?;
+    }
+
+    let replaced = if source.is_file() {
+        replaced_size(destination_root(source, dest))
+    } else {
+        let base = directory_base(dest);
+        replaced_size(tree.iter().zip(&plan).filter_map(|(entry, target)| {
+            entry
+                .file_len
+                .and(target.as_ref())
+                .map(|target| base.join(target))
+        }))
+    };
+    ensure_space(dest, total_size, replaced)?;
+
+    progress.emit(ProgressEvent::Totals {
+        bytes: total_size,
+        files: total_files,
+    });
+    progress.emit(ProgressEvent::Phase(Phase::Copying));
+    let mut tracker = Tracker {
+        sink: &mut progress,
+        done: 0,
+        total: total_size,
+        buffer: AdaptiveBuffer::for_options(options),
+    };
+
+    if source.is_file() {
+        let file_name = source.file_name().ok_or_else(|| {
+            UsbError::Unsupported(format!("Invalid source file name {}", source.display()))
+        })?;
+        let dest_path = if dest.is_dir() {
+            dest.join(file_name)
+        } else {
+            dest.to_path_buf()
+        };
+
+        copy_file(source, &dest_path, &mut tracker, options, &mut report)?;
+    } else {
+        // Directory copy; the plan's paths start with the copy's name.
+        let base = directory_base(dest);
+        let top = base.join(options.reshape.prefix.as_deref().unwrap_or(Path::new("")));
+        fs::create_dir_all(&top)
+            .io_context(|| format!("Failed to create destination directory {}", top.display()))?;
+
+        for (entry, target) in tree.iter().zip(&plan) {
+            // Left out over a name clash, or by the reshaping.
+            let Some(target) = target else {
+                continue;
+            };
+            let dest_path = base.join(target);
+            if entry.is_dir {
+                fs::create_dir_all(&dest_path)
+                    .io_context(|| format!("Failed to create directory {}", dest_path.display()))?;
+            } else {
+                copy_file(&entry.path, &dest_path, &mut tracker, options, &mut report)?;
+            }
+        }
+        // Last, and deepest first, so writing into them doesn't move the
+        // times again.
+        for (entry, target) in tree
+            .iter()
+            .zip(&plan)
+            .rev()
+            .filter(|(entry, _)| entry.is_dir)
+        {
+            if let Some(target) = target {
+                preserve(
+                    &entry.path,
+                    &base.join(target),
+                    options.preserve,
+                    &mut report,
+                )?;
+            }
+        }
+    }
+
+    progress.emit(ProgressEvent::Phase(Phase::Done));
+    report.elapsed = started.elapsed();
+    Ok(report)
+}
+
+/// The deepest part of `dest` that is already there; the current directory
+/// for a bare relative path none of which exists yet.
+fn existing_part(dest: &Path) -> &Path {
+    dest.ancestors()
+        .find(|p| p.is_dir())
+        .unwrap_or(Path::new("."))
+}
+
+/// The directory a copy of a directory to `dest` goes into, with the
+/// copy's name: `dest` itself if it exists (`cp -r src dst` puts `src`
+/// inside `dst`), its parent if not (`dst` is the new directory's name).
+fn directory_base(dest: &Path) -> PathBuf {
+    if dest.is_dir() {
+        dest.to_path_buf()
+    } else {
+        dest.parent().unwrap_or(Path::new("")).to_path_buf()
+    }
+}
+
+/// Where each entry of `tree` goes, relative to [`directory_base`]: its
+/// own path under the copy's name, as [`TransferOptions::reshape`] moves it
+/// (`None` if it leaves it out). A name an earlier entry already got, or
+/// only differing from one in case or accents when the filesystem at
+/// `existing` ignores case (see [`crate::casefold`]), is a clash, and
+/// `options.on_collision` decides: skip it (`None`, with everything inside
+/// it), write it over the earlier one, or write it under a free name. Each
+/// clash is added to `collisions`.
+fn plan_names(
+    tree: &[TreeEntry],
+    source: &Path,
+    dest: &Path,
+    existing: &Path,
+    options: &TransferOptions,
+    collisions: &mut Vec<Collision>,
+) -> Result<Vec<Option<PathBuf>>> {
+    let name = if dest.is_dir() {
+        source.file_name()
+    } else {
+        dest.file_name()
+    }
+    .ok_or_else(|| {
+        UsbError::Unsupported(format!("Invalid source dir name {}", source.display()))
+    })?;
+    let reshape = &options.reshape;
+    let relative = |entry: &TreeEntry| match entry.path.strip_prefix(source) {
+        Ok(inside) if !inside.as_os_str().is_empty() => Path::new(name).join(inside),
+        _ => PathBuf::from(name),
+    };
+    let insensitive = crate::casefold::is_case_insensitive(existing).io_context(|| {
+        format!(
+            "Failed to check whether {} ignores case",
+            existing.display()
+        )
+    })?;
+    if !insensitive && reshape.is_identity() {
+        return Ok(tree.iter().map(|entry| Some(relative(entry))).collect());
+    }
+    let key = |path: &Path| {
+        let path = path.to_string_lossy();
+        if insensitive {
+            crate::casefold::fold(&path)
+        } else {
+            path.into_owned()
+        }
+    };
+    // By (folded) target: the target and the source that got it.
+    let mut taken: HashMap<String, (PathBuf, PathBuf)> = HashMap::new();
+    // Where each directory that wasn't left out went, by its source path.
+    let mut dirs: HashMap<&Path, Option<PathBuf>> = HashMap::new();
+    let mut plan = Vec::with_capacity(tree.len());
+    for entry in tree {
+        let Some(shaped) = reshape.apply(&relative(entry), entry.is_dir) else {
+            plan.push(None);
+            continue;
+        };
+        let target = match (
+            entry.path.parent().and_then(|parent| dirs.get(parent)),
+            shaped.file_name(),
+        ) {
+            (Some(None), _) => None,
+            (Some(Some(dir)), Some(name)) => Some(dir.join(name)),
+            _ => Some(shaped),
+        };
+        let target = match target {
+            None => None,
+            Some(target) => match taken.get(&key(&target)) {
+                None => {
+                    taken.insert(key(&target), (target.clone(), entry.path.clone()));
+                    Some(target)
+                }
+                Some((first, first_source)) => {
+                    let written = match options.on_collision {
+                        Conflict::Skip => None,
+                        Conflict::Overwrite => Some(first.clone()),
+                        Conflict::KeepBoth => {
+                            let free =
+                                free_name(&target, |candidate| taken.contains_key(&key(candidate)));
+                            Some(free)
+                        }
+                    };
+                    collisions.push(Collision {
+                        source: entry.path.clone(),
+                        clashes_with: first_source.clone(),
+                        written_to: written.clone(),
+                    });
+                    if let Some(written) = &written {
+                        taken
+                            .entry(key(written))
+                            .or_insert((written.clone(), entry.path.clone()));
+                    }
+                    written
+                }
+            },
+        };
+        if entry.is_dir {
+            dirs.insert(&entry.path, target.clone());
+        }
+        plan.push(target);
+    }
+    Ok(plan)
+}
+
+/// `name (2).ext`, `name (3).ext`... next to `path`, the first that isn't
+/// `taken`.
+fn free_name(path: &Path, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (2..)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, ext)))
+        .find(|candidate| !taken(candidate))
+        .expect("there is always a free name")
+}
+
+/// Copies `source` to every path in `dests` at once, with [`copy_to_usb`]'s
+/// semantics for each. The source is read only once: every chunk goes to
+/// one writer thread per destination, so a dozen sticks take about as long
+/// as the slowest of them. Progress and the report count each file once,
+/// however many destinations there are. Can't encrypt or decrypt.
+pub fn copy_mirrored(
+    source: &Path,
+    dests: &[PathBuf],
+    options: &TransferOptions,
+    progress: impl ProgressSink,
+) -> Result<TransferReport> {
+    let started = Instant::now();
+    let paths: Vec<&Path> = std::iter::once(source)
+        .chain(dests.iter().map(PathBuf::as_path))
+        .collect();
+    let job = crate::jobs::start("copy", &paths, Some(&options.cancel));
+    let mut progress = job.track(progress);
+    let mut report = TransferReport::default();
+    if options.crypt.is_some() {
+        return Err(UsbError::Unsupported(
+            "Encrypting or decrypting can't be combined with copying to several destinations"
+                .into(),
+        ));
+    }
+    if !options.reshape.is_identity() {
+        return Err(UsbError::Unsupported(
+            "Paths can't be reshaped when copying to several destinations".into(),
+        ));
+    }
+    for dest in dests {
+        ensure_writable(dest)?;
+    }
+
+    progress.emit(ProgressEvent::Phase(Phase::Scanning));
+    let mut total_size = 0;
+    let mut total_files = 0;
+    let mut tree = Vec::new();
+    // Per destination, where each entry of `tree` goes under its
+    // [`directory_base`]: each is planned on its own, as they needn't all
+    // ignore case.
+    let mut plans = Vec::new();
+    let bases: Vec<PathBuf> = dests.iter().map(|dest| directory_base(dest)).collect();
+    if source.is_file() {
+        total_size = source
+            .metadata()
+            .io_context(|| format!("Failed to read {}", source.display()))?
+            .len();
+        total_files = 1;
+    } else {
+        tree = collect_tree(source, &options.ignore, &options.cancel)?;
+        for dest in dests {
+            let mut collisions = Vec::new();
+            plans.push(plan_names(
+                &tree,
+                source,
+                dest,
+                existing_part(dest),
+                options,
+                &mut collisions,
+            )?);
+            for collision in collisions {
+                if !report.collisions.contains(&collision) {
+                    report.collisions.push(collision);
+                }
+            }
+        }
+        for (i, entry) in tree.iter().enumerate() {
+            if let Some(len) = entry.file_len
+                && plans.iter().any(|plan| plan[i].is_some())
+            {
+                total_size += len;
+                total_files += 1;
+            }
+        }
+    }
+    for (dest, (plan, base)) in dests.iter().zip(plans.iter().zip(&bases)) {
+        let existing = existing_part(dest);
+        preflight(
+            dest,
+            plan.iter()
+                .flatten()
+                .map(|target| creates(existing, base, target)),
+        )?;
+    }
+    if source.is_file() {
+        for dest in dests {
+            if let Some(root) = destination_root(source, dest) {
+                preflight(dest, [creates(existing_part(dest), &root, Path::new(""))])?;
+            }
+        }
+    }
+    for (i, dest) in dests.iter().enumerate() {
+        let replaced = if source.is_file() {
+            replaced_size(destination_root(source, dest))
+        } else {
+            replaced_size(tree.iter().zip(&plans[i]).filter_map(|(entry, target)| {
+                entry
+                    .file_len
+                    .and(target.as_ref())
+                    .map(|target| bases[i].join(target))
+            }))
+        };
+        ensure_space(dest, total_size, replaced)?;
+    }
+
+    progress.emit(ProgressEvent::Totals {
+        bytes: total_size,
+        files: total_files,
+    });
+    progress.emit(ProgressEvent::Phase(Phase::Copying));
+    let mut tracker = Tracker {
+        sink: &mut progress,
+        done: 0,
+        total: total_size,
+        buffer: AdaptiveBuffer::for_options(options),
+    };
+    let file_name = source.file_name().ok_or_else(|| {
+        UsbError::Unsupported(format!("Invalid source name {}", source.display()))
+    })?;
+    let roots: Vec<PathBuf> = dests
+        .iter()
+        .map(|dest| {
+            if dest.is_dir() {
+                dest.join(file_name)
+            } else {
+                dest.clone()
+            }
+        })
+        .collect();
+
+    if source.is_file() {
+        mirror_file(source, &roots, &mut tracker, options, &mut report)?;
+    } else {
+        for root in &roots {
+            fs::create_dir_all(root).io_context(|| {
+                format!("Failed to create destination directory {}", root.display())
+            })?;
+        }
+        // Where entry `i` goes on every destination that doesn't leave it out.
+        let targets = |i: usize| -> Vec<PathBuf> {
+            plans
+                .iter()
+                .zip(&bases)
+                .filter_map(|(plan, base)| Some(base.join(plan[i].as_ref()?)))
+                .collect()
+        };
+        for (i, entry) in tree.iter().enumerate() {
+            let targets = targets(i);
+            if targets.is_empty() {
+                continue;
+            }
+            if entry.is_dir {
+                for dir in &targets {
+                    fs::create_dir_all(dir)
+                        .io_context(|| format!("Failed to create directory {}", dir.display()))?;
+                }
+            } else {
+                mirror_file(&entry.path, &targets, &mut tracker, options, &mut report)?;
+            }
+        }
+        for (i, entry) in tree
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, entry)| entry.is_dir)
+        {
+            for target in targets(i) {
+                preserve(&entry.path, &target, options.preserve, &mut report)?;
+            }
+        }
+    }
+
+    progress.emit(ProgressEvent::Phase(Phase::Done));
+    report.elapsed = started.elapsed();
+    Ok(report)
+}
+
+/// [`copy_file`] to several destinations, reading `source` once. On any
+/// failure none of the copies is left behind.
+fn mirror_file(
+    source: &Path,
+    dests: &[PathBuf],
+    tracker: &mut Tracker<'_, impl ProgressSink>,
+    options: &TransferOptions,
+    report: &mut TransferReport,
+) -> Result<()> {
+    if let Some(scanner) = &options.scanner
+        && let Verdict::Infected(signature) = scanner.scan(source)?
+    {
+        tracker.sink.emit(ProgressEvent::Skipped {
+            path: source.to_path_buf(),
+            reason: format!("infected: {}", signature),
+        });
+        report.infected.push((source.to_path_buf(), signature));
+        return Ok(());
+    }
+    if let Some(hook) = &options.on_overwrite {
+        for dest in dests.iter().filter(|dest| dest.exists()) {
+            hook.before(source, dest)?;
+        }
+    }
+    let mut file_in =
+        File::open(source).io_context(|| format!("Failed to open source file {:?}", source))?;
+    let size = file_in.metadata().map(|m| m.len()).unwrap_or(0);
+    tracker.file(source, size);
+
+    let mut outputs: Vec<(File, bool)> = Vec::new();
+    let mut result = Ok(0);
+    for dest in dests {
+        match File::create(dest) {
+            Ok(file) => outputs.push((file, false)),
+            Err(e) => {
+                result = Err(UsbError::io(
+                    format!("Failed to create dest file {:?}", dest),
+                    e,
+                ));
+                break;
+            }
+        }
+        let (file, preallocated) = outputs.last_mut().unwrap();
+        match preallocate(file, size) {
+            Ok(done) => *preallocated = done,
+            Err(e) => {
+                result = Err(match e.raw_os_error() {
+                    Some(libc::ENOSPC) => UsbError::InsufficientSpace {
+                        needed: size,
+                        available: available_space(dest).unwrap_or(0),
+                        path: None,
+                    },
+                    _ => UsbError::io(
+                        format!("Failed to allocate {} bytes for {:?}", size, dest),
+                        e,
+                    ),
+                });
+                break;
+            }
+        }
+    }
+    if result.is_ok() {
+        result = fan_out(
+            source,
+            &mut file_in,
+            &mut outputs,
+            dests,
+            size,
+            tracker,
+            options,
+        );
+    }
+    if let Ok(copied) = result
+        && copied < size
+    {
+        // The source shrank while it was read.
+        for ((output, preallocated), dest) in outputs.iter().zip(dests) {
+            if *preallocated && let Err(e) = output.set_len(copied) {
+                result = Err(UsbError::io(format!("Failed to write to {:?}", dest), e));
+            }
+        }
+    }
+    let created = outputs.len();
+    drop(outputs);
+    match result {
+        Ok(copied) => {
+            report.bytes_copied += copied;
+            report.files_copied += 1;
+            for dest in dests {
+                preserve(source, dest, options.preserve, report)?;
+            }
+            Ok(())
+        }
+        Err(e) => {
+            for dest in &dests[..created] {
+                let _ = fs::remove_file(dest);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Reads `input` chunk by chunk, handing each chunk to one writer thread
+/// per output over a bounded channel, so reading runs ahead of the writes
+/// and the slowest stick sets the pace.
+fn fan_out(
+    source: &Path,
+    input: &mut File,
+    outputs: &mut [(File, bool)],
+    dests: &[PathBuf],
+    len: u64,
+    tracker: &mut Tracker<'_, impl ProgressSink>,
+    options: &TransferOptions,
+) -> Result<u64> {
+    let fit = |size: usize| size.min(usize::try_from(len).unwrap_or(usize::MAX).max(1));
+    std::thread::scope(|scope| {
+        let mut senders = Vec::new();
+        let mut writers = Vec::new();
+        for ((output, _), dest) in outputs.iter_mut().zip(dests) {
+            let (tx, rx) = mpsc::sync_channel::<Arc<Vec<u8>>>(PIPELINE_DEPTH);
+            senders.push(tx);
+            writers.push(scope.spawn(move || -> Result<()> {
+                for chunk in rx {
+                    output
+                        .write_all(&chunk)
+                        .io_context(|| format!("Failed to write to {:?}", dest))?;
+                }
+                Ok(())
+            }));
+        }
+
+        let mut copied = 0;
+        let read = loop {
+            if let Err(e) = options.cancel.check() {
+                break Err(e);
+            }
+            let mut buffer = vec![0; fit(tracker.buffer.size())];
+            let n = match input.read(&mut buffer) {
+                Ok(0) => break Ok(()),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => break Err(UsbError::io(format!("Failed to read from {:?}", source), e)),
+            };
+            buffer.truncate(n);
+            let chunk = Arc::new(buffer);
+            let started = Instant::now();
+            // A writer that hung up failed; its error is collected below.
+            if senders.iter().any(|tx| tx.send(chunk.clone()).is_err()) {
+                break Ok(());
+            }
+            tracker.buffer.record(n, started.elapsed());
+            copied += n as u64;
+            tracker.advance(n as u64);
+        };
+        drop(senders);
+        for writer in writers {
+            writer.join().expect("writer thread panicked")?;
+        }
+        read.map(|()| copied)
+    })
+}
+
+/// Copies each `(source, dest, size)` file as one transfer, with a single
+/// running byte count and the space check up front, creating missing parent
+/// directories. For engines that pick the files themselves; the caller
+/// reports the scanning and done phases.
+pub(crate) fn copy_files(
+    files: &[(PathBuf, PathBuf, u64)],
+    dest_root: &Path,
+    options: &TransferOptions,
+    progress: &mut impl ProgressSink,
+) -> Result<TransferReport> {
+    let started = Instant::now();
+    let mut report = TransferReport::default();
+    ensure_writable(dest_root)?;
+    let total_size = files.iter().map(|(_, _, size)| size).sum();
+    ensure_space(
+        dest_root,
+        total_size,
+        replaced_size(files.iter().map(|(_, dest, _)| dest)),
+    )?;
+
+    progress.emit(ProgressEvent::Totals {
+        bytes: total_size,
+        files: files.len() as u64,
+    });
+    progress.emit(ProgressEvent::Phase(Phase::Copying));
+    let mut tracker = Tracker {
+        sink: progress,
+        done: 0,
+        total: total_size,
+        buffer: AdaptiveBuffer::for_options(options),
+    };
+    for (source, dest, _) in files {
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)
+                .io_context(|| format!("Failed to create directory {}", parent.display()))?;
+        }
+        copy_file(source, dest, &mut tracker, options, &mut report)?;
+    }
+    report.elapsed = started.elapsed();
+    Ok(report)
+}
+
+/// The file [`copy_spanning`] leaves in the root of every drive it wrote to.
+pub const SPAN_MANIFEST: &str = "lsusb-span.json";
+/// Room left on a drive beyond each file's size, for the directory entries
+/// and the partly used last cluster the file costs on top.
+const SPAN_SLACK: u64 = 1024 * 1024;
+
+/// Which part of a spanning copy went to which drive.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpanManifest {
+    pub source: PathBuf,
+    /// False in the manifests of all but the last drive, which were written
+    /// before the copy moved on and only list the drives up to their own.
+    pub complete: bool,
+    pub volumes: Vec<SpanVolume>,
+}
+
+/// One drive of a spanning copy.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpanVolume {
+    /// Counting from 1.
+    pub number: u32,
+    /// The directory the files went into.
+    pub dest: PathBuf,
+    /// The files on this drive, relative to `dest`.
+    pub files: Vec<PathBuf>,
+    pub bytes: u64,
+}
+
+/// What a finished [`copy_spanning`] call did.
+#[derive(Debug, Clone, Default)]
+pub struct SpanReport {
+    pub transfer: TransferReport,
+    pub manifest: SpanManifest,
+}
+
+/// Copies `source` into the directory `dest` like [`copy_with_progress`],
+/// but when the next file doesn't fit, moves on to the directory `next`
+/// returns (given the manifest so far) and carries on there; `None` stops
+/// the copy with [`UsbError::InsufficientSpace`]. Each drive keeps the same
+/// layout under its directory, files are never split, and every drive gets
+/// a [`SPAN_MANIFEST`] when the copy leaves it.
+pub fn copy_spanning(
+    source: &Path,
+    dest: &Path,
+    options: &TransferOptions,
+    mut next: impl FnMut(&SpanManifest) -> Result<Option<PathBuf>>,
+    progress: impl ProgressSink,
+) -> Result<SpanReport> {
+    let started = Instant::now();
+    let job = crate::jobs::start("copy", &[source, dest], Some(&options.cancel));
+    let mut progress = job.track(progress);
+    let mut report = SpanReport::default();
+    report.manifest.source = source.to_path_buf();
+    span_target(dest)?;
+    if !options.reshape.is_identity() {
+        return Err(UsbError::Unsupported(
+            "Paths can't be reshaped when copying across drives".into(),
+        ));
+    }
+
+    progress.emit(ProgressEvent::Phase(Phase::Scanning));
+    let name = source.file_name().map(PathBuf::from).ok_or_else(|| {
+        UsbError::Unsupported(format!("Invalid source name {}", source.display()))
+    })?;
+    // Paths relative to each drive's directory, with file sizes.
+    let mut entries = Vec::new();
+    if source.is_file() {
+        let len = source
+            .metadata()
+            .io_context(|| format!("Failed to read {}", source.display()))?
+            .len();
+        entries.push((source.to_path_buf(), name, Some(len)));
+    } else {
+        // Names are planned once, against the first drive: clashes are the
+        // same wherever the files end up, and restoring joins the drives.
+        let tree = collect_tree(source, &options.ignore, &options.cancel)?;
+        let plan = plan_names(
+            &tree,
+            source,
+            dest,
+            existing_part(dest),
+            options,
+            &mut report.transfer.collisions,
+        )?;
+        for (entry, relative) in tree.into_iter().zip(plan) {
+            // Left out over a name clash.
+            let Some(relative) = relative else {
+                continue;
+            };
+            let len = if entry.is_dir { None } else { entry.file_len };
+            entries.push((entry.path, relative, len));
+        }
+    }
+    let total_size = entries.iter().filter_map(|(_, _, len)| *len).sum();
+    let total_files = entries.iter().filter(|(_, _, len)| len.is_some()).count() as u64;
+
+    progress.emit(ProgressEvent::Totals {
+        bytes: total_size,
+        files: total_files,
+    });
+    progress.emit(ProgressEvent::Phase(Phase::Copying));
+    let mut tracker = Tracker {
+        sink: &mut progress,
+        done: 0,
+        total: total_size,
+        buffer: AdaptiveBuffer::for_options(options),
+    };
+    report.manifest.volumes.push(SpanVolume {
+        number: 1,
+        dest: dest.to_path_buf(),
+        ..SpanVolume::default()
+    });
+
+    for (path, relative, len) in &entries {
+        let Some(len) = *len else {
+            if path.is_dir() {
+                let dir = span_volume(&report.manifest).dest.join(relative);
+                fs::create_dir_all(&dir)
+                    .io_context(|| format!("Failed to create directory {}", dir.display()))?;
+            }
+            continue;
+        };
+        loop {
+            let volume = span_volume(&report.manifest).dest.clone();
+            let available = available_space(&volume)?;
+            // A drive just swapped in that can't take the file means no
+            // drive of that size ever will; asking for more would never end.
+            let fresh =
+                report.manifest.volumes.len() > 1 && span_volume(&report.manifest).files.is_empty();
+            if fresh && available < len + SPAN_SLACK {
+                write_span_manifest(&report.manifest)?;
+                return Err(UsbError::InsufficientSpace {
+                    needed: len + SPAN_SLACK,
+                    available,
+                    path: Some(path.clone()),
+                });
+            }
+            if available >= len + SPAN_SLACK {
+                let target = volume.join(relative);
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent).io_context(|| {
+                        format!("Failed to create directory {}", parent.display())
+                    })?;
+                }
+                let (done, copied) = (tracker.done, report.transfer.files_copied);
+                match copy_file(path, &target, &mut tracker, options, &mut report.transfer) {
+                    Ok(()) => {
+                        if report.transfer.files_copied > copied {
+                            let volume = report.manifest.volumes.last_mut().unwrap();
+                            volume.files.push(relative.clone());
+                            volume.bytes += len;
+                        }
+                        break;
+                    }
+                    // Smaller than it said; try the file again on the next drive.
+                    Err(UsbError::InsufficientSpace { .. }) => {
+                        let _ = fs::remove_file(&target);
+                        tracker.done = done;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            write_span_manifest(&report.manifest)?;
+            let remaining = total_size - tracker.done;
+            let Some(dest) = next(&report.manifest)? else {
+                return Err(UsbError::InsufficientSpace {
+                    needed: remaining,
+                    available,
+                    path: None,
+                });
+            };
+            span_target(&dest)?;
+            let number = report.manifest.volumes.len() as u32 + 1;
+            report.manifest.volumes.push(SpanVolume {
+                number,
+                dest,
+                ..SpanVolume::default()
+            });
+            // Copies of the directories on the drives before; their modes
+            // and times aren't preserved.
+            for ancestor in relative
+                .ancestors()
+                .skip(1)
+                .filter(|a| !a.as_os_str().is_empty())
+            {
+                let dir = span_volume(&report.manifest).dest.join(ancestor);
+                fs::create_dir_all(&dir)
+                    .io_context(|| format!("Failed to create directory {}", dir.display()))?;
+            }
+        }
+    }
+
+    report.manifest.complete = true;
+    write_span_manifest(&report.manifest)?;
+    progress.emit(ProgressEvent::Phase(Phase::Done));
+    report.transfer.elapsed = started.elapsed();
+    Ok(report)
+}
+
+/// Fails unless `dest` is a writable directory.
+fn span_target(dest: &Path) -> Result<()> {
+    if !dest.is_dir() {
+        return Err(UsbError::Unsupported(format!(
+            "{} is not a directory; a spanning copy needs one on each drive",
+            dest.display()
+        )));
+    }
+    ensure_writable(dest)
+}
+
+fn span_volume(manifest: &SpanManifest) -> &SpanVolume {
+    manifest
+        .volumes
+        .last()
+        .expect("a spanning copy always has a drive")
+}
+
+/// Writes `manifest` as it stands to the drive currently being filled.
+fn write_span_manifest(manifest: &SpanManifest) -> Result<()> {
+    let path = span_volume(manifest).dest.join(SPAN_MANIFEST);
+    let json =
+        serde_json::to_vec_pretty(manifest).map_err(|e| UsbError::parse("span manifest", e))?;
+    fs::write(&path, json).io_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Keeps the running byte count for a transfer and forwards updates.
+struct Tracker<'a, S: ProgressSink> {
+    sink: &'a mut S,
+    done: u64,
+    total: u64,
+    /// Carried from file to file, so each one starts at the size the
+    /// previous ones settled on.
+    buffer: AdaptiveBuffer,
+}
+
+impl<S: ProgressSink> Tracker<'_, S> {
+    fn file(&mut self, path: &Path, size: u64) {
+        self.sink.emit(ProgressEvent::File {
+            path: path.to_path_buf(),
+            size,
+        });
+    }
+
+    fn advance(&mut self, n: u64) {
+        self.done += n;
+        self.sink.emit(ProgressEvent::Bytes {
+            done: self.done,
+            total: self.total,
+        });
+    }
+}
+
+/// Scans (if asked to) and copies or encrypts one file, recording the
+/// outcome.
+fn copy_file(
+    source: &Path,
+    dest: &Path,
+    tracker: &mut Tracker<'_, impl ProgressSink>,
+    options: &TransferOptions,
+    report: &mut TransferReport,
+) -> Result<()> {
+    // What is actually written for `dest`.
+    let written = |dest: &Path| {
+        options
+            .crypt
+            .as_ref()
+            .and_then(|c| c.target(dest))
+            .unwrap_or_else(|| dest.to_path_buf())
+    };
+    let renamed;
+    let dest = match &options.on_existing {
+        Some(prompt) if written(dest).exists() => match prompt.ask(source, &written(dest)) {
+            Conflict::Overwrite => dest,
+            Conflict::Skip => {
+                tracker.sink.emit(ProgressEvent::Skipped {
+                    path: source.to_path_buf(),
+                    reason: "already at the destination".into(),
+                });
+                report.skipped.push(written(dest));
+                return Ok(());
+            }
+            Conflict::KeepBoth => {
+                renamed = free_name(dest, |candidate| written(candidate).exists());
+                &renamed
+            }
+        },
+        _ => dest,
+    };
+    if let Some(scanner) = &options.scanner
+        && let Verdict::Infected(signature) = scanner.scan(source)?
+    {
+        tracker.sink.emit(ProgressEvent::Skipped {
+            path: source.to_path_buf(),
+            reason: format!("infected: {}", signature),
+        });
+        report.infected.push((source.to_path_buf(), signature));
+        return Ok(());
+    }
+    if let Some(hook) = &options.on_overwrite
+        && written(dest).exists()
+    {
+        hook.before(source, &written(dest))?;
+    }
+    if let Some(crypt) = &options.crypt
+        && let Some(target) = crypt.target(dest)
+    {
+        options.cancel.check()?;
+        let size = source.metadata().map(|m| m.len()).unwrap_or(0);
+        tracker.file(source, size);
+        crypt.apply(source, &target)?;
+        tracker.advance(size);
+        report.bytes_copied += size;
+        report.files_copied += 1;
+        return Ok(());
+    }
+    report.bytes_copied += copy_file_with_progress(source, dest, tracker, options)?;
+    report.files_copied += 1;
+    preserve(source, dest, options.preserve, report)
+}
+
+/// Carries what `preserve` asks for over from `source` to its copy `dest`,
+/// noting in `report` what the destination can't store.
+fn preserve(
+    source: &Path,
+    dest: &Path,
+    preserve: Preserve,
+    report: &mut TransferReport,
+) -> Result<()> {
+    if !preserve.any() {
+        return Ok(());
+    }
+    let metadata =
+        fs::metadata(source).io_context(|| format!("Failed to read {}", source.display()))?;
+    if preserve.xattrs {
+        let dropped = crate::xattr::copy(source, dest).io_context(|| {
+            format!(
+                "Failed to copy the extended attributes of {}",
+                source.display()
+            )
+        })?;
+        report
+            .dropped
+            .extend(dropped.into_iter().map(|name| (dest.to_path_buf(), name)));
+    }
+    if preserve.mode
+        && let Err(e) = fs::set_permissions(dest, metadata.permissions())
+    {
+        // vfat refuses modes it can't represent unless mounted `quiet`.
+        if e.raw_os_error() != Some(libc::EPERM) && !crate::xattr::unsupported(&e) {
+            return Err(UsbError::io(
+                format!("Failed to set the mode of {}", dest.display()),
+                e,
+            ));
+        }
+        report
+            .dropped
+            .push((dest.to_path_buf(), "mode".to_string()));
+    }
+    if preserve.timestamps {
+        let mut times = fs::FileTimes::new();
+        if let Ok(accessed) = metadata.accessed() {
+            times = times.set_accessed(accessed);
+        }
+        if let Ok(modified) = metadata.modified() {
+            times = times.set_modified(modified);
+        }
+        File::open(dest)
+            .and_then(|file| file.set_times(times))
+            .io_context(|| format!("Failed to set the times of {}", dest.display()))?;
+    }
+    Ok(())
+}
+
+fn copy_file_with_progress(
+    source: &Path,
+    dest: &Path,
+    tracker: &mut Tracker<'_, impl ProgressSink>,
+    options: &TransferOptions,
+) -> Result<u64> {
+    let file_in =
+        File::open(source).io_context(|| format!("Failed to open source file {:?}", source))?;
+    let size = file_in.metadata().map(|m| m.len()).unwrap_or(0);
+    tracker.file(source, size);
+    let mut file_out =
+        File::create(dest).io_context(|| format!("Failed to create dest file {:?}", dest))?;
+    let preallocated = match preallocate(&file_out, size) {
+        Ok(preallocated) => preallocated,
+        Err(e) => {
+            drop(file_out);
+            let _ = fs::remove_file(dest);
+            return Err(match e.raw_os_error() {
+                Some(libc::ENOSPC) => UsbError::InsufficientSpace {
+                    needed: size,
+                    available: available_space(dest).unwrap_or(0),
+                    path: None,
+                },
+                _ => UsbError::io(
+                    format!("Failed to allocate {} bytes for {:?}", size, dest),
+                    e,
+                ),
+            });
+        }
+    };
+    let hints = CacheHints::start(&file_in, &file_out);
+
+    let mut result = pipe(file_in, &mut file_out, size, hints, tracker, options)
+        .map_err(|e| e.into_usb_error(source, dest));
+    if let Ok(copied) = result
+        && preallocated
+        && copied < size
+    {
+        // The source shrank while it was read.
+        result = file_out
+            .set_len(copied)
+            .map(|()| copied)
+            .io_context(|| format!("Failed to write to {:?}", dest));
+    }
+    // Don't leave a truncated file behind that looks like a real copy. A
+    // preallocated one has the full size whatever went wrong.
+    if matches!(result, Err(UsbError::Cancelled)) || (result.is_err() && preallocated) {
+        drop(file_out);
+        let _ = fs::remove_file(dest);
+    }
+    result
+}
+
+/// Reserves `size` bytes for `file` before it is written, so the
+/// filesystem can place it in one piece (FAT and exFAT fragment badly
+/// otherwise) and a stick without room fails before the copy rather than
+/// at the end of it. Returns whether the file now has that length;
+/// filesystems that can't preallocate are left to grow the file as usual.
+#[cfg(target_os = "linux")]
+fn preallocate(file: &File, size: u64) -> io::Result<bool> {
+    use std::os::fd::AsRawFd;
+
+    if size == 0 {
+        return Ok(false);
+    }
+    if unsafe { libc::fallocate(file.as_raw_fd(), 0, 0, size as libc::off_t) } == 0 {
+        return Ok(true);
+    }
+    allocation_failed(io::Error::last_os_error())
+}
+
+#[cfg(target_os = "freebsd")]
+fn preallocate(file: &File, size: u64) -> io::Result<bool> {
+    use std::os::fd::AsRawFd;
+
+    if size == 0 {
+        return Ok(false);
+    }
+    // Returns the error rather than setting errno.
+    match unsafe { libc::posix_fallocate(file.as_raw_fd(), 0, size as libc::off_t) } {
+        0 => Ok(true),
+        errno => allocation_failed(io::Error::from_raw_os_error(errno)),
+    }
+}
+
+/// macOS reserves the space without changing the file's length.
+#[cfg(target_os = "macos")]
+fn preallocate(file: &File, size: u64) -> io::Result<bool> {
+    use std::os::fd::AsRawFd;
+
+    if size == 0 {
+        return Ok(false);
+    }
+    let mut store = libc::fstore_t {
+        fst_flags: libc::F_ALLOCATECONTIG,
+        fst_posmode: libc::F_PEOFPOSMODE,
+        fst_offset: 0,
+        fst_length: size as libc::off_t,
+        fst_bytesalloc: 0,
+    };
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_PREALLOCATE, &mut store) } == 0 {
+        return Ok(false);
+    }
+    // Not in one piece, then.
+    store.fst_flags = libc::F_ALLOCATEALL;
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_PREALLOCATE, &mut store) } == 0 {
+        return Ok(false);
+    }
+    allocation_failed(io::Error::last_os_error())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "freebsd", target_os = "macos")))]
+fn preallocate(_file: &File, _size: u64) -> io::Result<bool> {
+    Ok(false)
+}
+
+/// Out of space, or a file too large for the filesystem (4 GiB on FAT),
+/// fails the copy; anything else only means preallocation isn't supported.
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "macos"))]
+fn allocation_failed(e: io::Error) -> io::Result<bool> {
+    match e.raw_os_error() {
+        Some(libc::ENOSPC | libc::EFBIG) => Err(e),
+        _ => Ok(false),
+    }
+}
+
+/// Buffers in flight between the reader and the writer.
+const PIPELINE_DEPTH: usize = 4;
+
+/// Smallest buffer [`AdaptiveBuffer`] shrinks to.
+pub const MIN_BUFFER: usize = 64 * 1024;
+/// Largest buffer [`AdaptiveBuffer`] grows to.
+pub const MAX_BUFFER: usize = 16 * 1024 * 1024;
+/// Full-buffer writes per measurement.
+const SAMPLE_WRITES: u32 = 8;
+/// A write that takes longer than this makes the buffer shrink, so
+/// progress and cancellation stay responsive on slow sticks.
+const MAX_WRITE_TIME: Duration = Duration::from_millis(250);
+
+/// Picks the copy buffer size from measured throughput instead of a fixed
+/// guess. Writes are timed in samples of a few; after each sample the size
+/// doubles or halves, keeping the direction while throughput improves by
+/// more than 5% and stepping back once when it drops, after which it stays
+/// put. Only writes slower than [`MAX_WRITE_TIME`] move it again.
+///
+/// Writes shorter than the buffer (the ends of files) say nothing about the
+/// size and are ignored, so a flood of small files leaves it alone.
+#[derive(Debug, Clone)]
+pub struct AdaptiveBuffer {
+    size: usize,
+    adaptive: bool,
+    growing: bool,
+    settled: bool,
+    /// Throughput of the previous sample, in bytes per second.
+    previous: Option<f64>,
+    writes: u32,
+    bytes: u64,
+    busy: Duration,
+}
+
+impl AdaptiveBuffer {
+    /// Starts at `size`, within [`MIN_BUFFER`] and [`MAX_BUFFER`].
+    pub fn new(size: usize) -> Self {
+        AdaptiveBuffer {
+            size: size.clamp(MIN_BUFFER, MAX_BUFFER),
+            adaptive: true,
+            growing: true,
+            settled: false,
+            previous: None,
+            writes: 0,
+            bytes: 0,
+            busy: Duration::ZERO,
+        }
+    }
+
+    /// Always `size`.
+    pub fn fixed(size: usize) -> Self {
+        AdaptiveBuffer {
+            size: size.max(1),
+            adaptive: false,
+            ..AdaptiveBuffer::new(size)
+        }
+    }
+
+    fn for_options(options: &TransferOptions) -> Self {
+        if options.adaptive_buffer {
+            AdaptiveBuffer::new(options.buffer_size)
+        } else {
+            AdaptiveBuffer::fixed(options.buffer_size)
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Records a write of `bytes` that took `took`.
+    pub fn record(&mut self, bytes: usize, took: Duration) {
+        if !self.adaptive || bytes < self.size {
+            return;
+        }
+        self.writes += 1;
+        self.bytes += bytes as u64;
+        self.busy += took;
+        if self.writes < SAMPLE_WRITES {
+            return;
+        }
+        let rate = self.bytes as f64 / self.busy.as_secs_f64().max(1e-9);
+        let per_write = self.busy / self.writes;
+        self.writes = 0;
+        self.bytes = 0;
+        self.busy = Duration::ZERO;
+
+        if per_write > MAX_WRITE_TIME {
+            self.resize(false);
+            self.previous = None;
+            return;
+        }
+        if self.settled {
+            return;
+        }
+        match self.previous {
+            Some(previous) if rate < previous * 0.95 => {
+                // The last step made it worse: undo it and stay there.
+                self.growing = !self.growing;
+                self.settled = true;
+            }
+            Some(previous) if rate < previous * 1.05 => {
+                self.settled = true;
+                return;
+            }
+            _ => {}
+        }
+        self.previous = Some(rate);
+        if self.growing && per_write * 2 > MAX_WRITE_TIME {
+            self.settled = true;
+            return;
+        }
+        self.resize(self.growing);
+    }
+
+    fn resize(&mut self, grow: bool) {
+        self.size = if grow { self.size * 2 } else { self.size / 2 }.clamp(MIN_BUFFER, MAX_BUFFER);
+    }
+}
+
+/// Why [`pipe`] stopped early.
+enum PipeError {
+    Read(io::Error),
+    Write(io::Error),
+    Cancelled,
+}
+
+impl PipeError {
+    fn into_usb_error(self, source: &Path, dest: &Path) -> UsbError {
+        match self {
+            PipeError::Read(e) => UsbError::io(format!("Failed to read from {:?}", source), e),
+            PipeError::Write(e) => UsbError::io(format!("Failed to write to {:?}", dest), e),
+            PipeError::Cancelled => UsbError::Cancelled,
+        }
+    }
+}
+
+/// Copies `input` to `output` with reads on a separate thread, handing
+/// filled buffers over a bounded channel and getting them back empty, so the
+/// next read overlaps a slow USB write instead of waiting for it. Progress
+/// and cancellation stay on the calling (writing) thread.
+fn pipe(
+    mut input: File,
+    output: &mut File,
+    len: u64,
+    hints: CacheHints,
+    tracker: &mut Tracker<'_, impl ProgressSink>,
+    options: &TransferOptions,
+) -> std::result::Result<u64, PipeError> {
+    // No bigger than the file, so small files don't allocate large buffers.
+    let fit = |size: usize| size.min(usize::try_from(len).unwrap_or(usize::MAX).max(1));
+    let buffer_size = AtomicUsize::new(fit(tracker.buffer.size()));
+    std::thread::scope(|scope| {
+        let (filled_tx, filled) = mpsc::sync_channel::<io::Result<Vec<u8>>>(PIPELINE_DEPTH);
+        let (empty, empty_rx) = mpsc::channel::<Vec<u8>>();
+        for _ in 0..PIPELINE_DEPTH {
+            let _ = empty.send(Vec::new());
+        }
+
+        let buffer_size = &buffer_size;
+        scope.spawn(move || {
+            let mut hints = hints;
+            let mut read = 0;
+            // Ends when the writer hangs up, which it does on any error.
+            while let Ok(mut buffer) = empty_rx.recv() {
+                buffer.resize(buffer_size.load(Ordering::Relaxed), 0);
+                let n = loop {
+                    match input.read(&mut buffer) {
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        other => break other,
+                    }
+                };
+                match n {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        buffer.truncate(n);
+                        read += n as u64;
+                        hints.read(&input, read);
+                        if filled_tx.send(Ok(buffer)).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        let _ = filled_tx.send(Err(e));
+                        break;
+                    }
+                }
+            }
+        });
+
+        let mut hints = hints;
+        let mut copied = 0;
+        for buffer in filled.iter() {
+            options.cancel.check().map_err(|_| PipeError::Cancelled)?;
+            let buffer = buffer.map_err(PipeError::Read)?;
+            let started = Instant::now();
+            output.write_all(&buffer).map_err(PipeError::Write)?;
+            copied += buffer.len() as u64;
+            // Waiting for writeback counts: that's when a slow stick shows.
+            hints.written(output, copied);
+            tracker.buffer.record(buffer.len(), started.elapsed());
+            buffer_size.store(fit(tracker.buffer.size()), Ordering::Relaxed);
+            tracker.advance(buffer.len() as u64);
+            let _ = empty.send(buffer);
+        }
+        Ok(copied)
+    })
+}
+
+/// Bytes copied between page cache hints.
+pub const HINT_WINDOW: u64 = 8 * 1024 * 1024;
+
+/// Which destination bytes a copy hands to writeback, and which it then
+/// waits for and drops from the page cache, a [`HINT_WINDOW`] at a time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WritebackWindows {
+    /// Bytes up to here have been handed to writeback.
+    started: u64,
+    /// Bytes up to here are on disk and have been dropped.
+    dropped: u64,
+}
+
+impl WritebackWindows {
+    /// Called with the running count of bytes written. Once a window's worth
+    /// has piled up, returns the range to start writing back and the one
+    /// before it, if there is one yet, to wait for and drop. Neither range
+    /// is ever empty: a length of 0 means the whole file to
+    /// `sync_file_range` and `posix_fadvise`.
+    pub fn written(&mut self, copied: u64) -> Option<(Range<u64>, Option<Range<u64>>)> {
+        if copied - self.started < HINT_WINDOW {
+            return None;
+        }
+        let start = self.started..copied;
+        let drop = Some(self.dropped..self.started).filter(|range| !range.is_empty());
+        self.dropped = self.started;
+        self.started = copied;
+        Some((start, drop))
+    }
+}
+
+/// Keeps a multi-gigabyte copy from evicting the rest of the desktop from
+/// the page cache: source pages are dropped once read and destination pages
+/// once written back, a window at a time. Pages that were cached before the
+/// copy started go too, which is the price of not caching the rest.
+///
+/// The reader and the writer each work on their own copy, using only their
+/// half of the fields.
+#[derive(Clone, Copy)]
+struct CacheHints {
+    /// Source bytes up to here have been dropped.
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    read_advised: u64,
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    writeback: WritebackWindows,
+}
+
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+impl CacheHints {
+    fn start(input: &File, output: &File) -> CacheHints {
+        use std::os::fd::AsRawFd;
+
+        advise(input.as_raw_fd(), 0, 0, libc::POSIX_FADV_SEQUENTIAL);
+        advise(output.as_raw_fd(), 0, 0, libc::POSIX_FADV_NOREUSE);
+        CacheHints {
+            read_advised: 0,
+            writeback: WritebackWindows::default(),
+        }
+    }
+
+    /// Called with the running count of bytes read after each chunk.
+    fn read(&mut self, input: &File, read: u64) {
+        use std::os::fd::AsRawFd;
+
+        if read - self.read_advised >= HINT_WINDOW {
+            advise(
+                input.as_raw_fd(),
+                self.read_advised,
+                read - self.read_advised,
+                libc::POSIX_FADV_DONTNEED,
+            );
+            self.read_advised = read;
+        }
+    }
+
+    /// Called with the running count of bytes written after each chunk.
+    fn written(&mut self, output: &File, copied: u64) {
+        use std::os::fd::AsRawFd;
+
+        let Some((start, drop)) = self.writeback.written(copied) else {
+            return;
+        };
+        let fd = output.as_raw_fd();
+        // Start writing back this window, wait for the previous one and drop
+        // it: dirty pages can't be dropped until they're on disk.
+        #[cfg(target_os = "linux")]
+        unsafe {
+            libc::sync_file_range(
+                fd,
+                start.start as libc::off64_t,
+                (start.end - start.start) as libc::off64_t,
+                libc::SYNC_FILE_RANGE_WRITE,
+            );
+        }
+        if let Some(drop) = drop {
+            #[cfg(target_os = "linux")]
+            unsafe {
+                libc::sync_file_range(
+                    fd,
+                    drop.start as libc::off64_t,
+                    (drop.end - drop.start) as libc::off64_t,
+                    libc::SYNC_FILE_RANGE_WAIT_BEFORE
+                        | libc::SYNC_FILE_RANGE_WRITE
+                        | libc::SYNC_FILE_RANGE_WAIT_AFTER,
+                );
+            }
+            advise(
+                fd,
+                drop.start,
+                drop.end - drop.start,
+                libc::POSIX_FADV_DONTNEED,
+            );
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "freebsd")))]
+impl CacheHints {
+    fn start(_input: &File, _output: &File) -> CacheHints {
+        CacheHints {}
+    }
+
+    fn read(&mut self, _input: &File, _read: u64) {}
+
+    fn written(&mut self, _output: &File, _copied: u64) {}
+}
+
+/// `posix_fadvise`, ignoring failure: these are hints, and some
+/// filesystems (FUSE, network mounts) reject them.
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn advise(fd: std::os::fd::RawFd, offset: u64, len: u64, advice: libc::c_int) {
+    unsafe {
+        libc::posix_fadvise(fd, offset as libc::off_t, len as libc::off_t, advice);
+    }
+}
+
+/// A path found while sizing a directory source, kept so the copy doesn't
+/// walk the tree a second time.
+struct TreeEntry {
+    path: PathBuf,
+    is_dir: bool,
+    /// Size, for regular files.
+    file_len: Option<u64>,
+}
+
+/// State for the walk: per directory, the ignore rules in force; per entry,
+/// each regular file's size, or why it couldn't be read.
+type Sizes = (Ignore, Option<std::result::Result<u64, io::Error>>);
+
+/// Lists `source` recursively, parents before their children, leaving out
+/// what `ignore` says. Directories are read, and the files in each stat'ed,
+/// on a thread pool: on a photo library with hundreds of thousands of files
+/// this pass used to take minutes before the copy could start.
+fn collect_tree(source: &Path, ignore: &Ignore, cancel: &CancelToken) -> Result<Vec<TreeEntry>> {
+    let root = source.to_path_buf();
+    // The first ignore file that couldn't be read.
+    let failed = Arc::new(Mutex::new(None));
+    let failed_load = failed.clone();
+    let walk = WalkDirGeneric::<Sizes>::new(source)
+        .skip_hidden(false)
+        .sort(true)
+        .root_read_dir_state(ignore.clone())
+        .process_read_dir(move |_, dir, ignore, children| {
+            let relative = dir.strip_prefix(&root).unwrap_or(dir).to_path_buf();
+            if let Err(e) = ignore.load(dir, &relative) {
+                failed_load.lock().unwrap().get_or_insert(e);
+            }
+            children.retain(|child| {
+                child.as_ref().map_or(true, |c| {
+                    !ignore.is_ignored(&relative.join(&c.file_name), c.file_type.is_dir())
+                })
+            });
+            children
+                .par_iter_mut()
+                .flatten()
+                .filter(|c| c.file_type.is_file())
+                .for_each(|child| {
+                    child.client_state =
+                        Some(child.metadata().map(|m| m.len()).map_err(io::Error::from));
+                });
+        });
+
+    let mut tree = Vec::new();
+    for entry in walk {
+        cancel.check()?;
+        let entry = entry.map_err(walk_error)?;
+        let path = entry.path();
+        let file_len = match entry.client_state {
+            Some(Ok(len)) => Some(len),
+            Some(Err(e)) => {
+                return Err(UsbError::io(
+                    format!("Failed to read {}", path.display()),
+                    e,
+                ));
+            }
+            None => None,
+        };
+        tree.push(TreeEntry {
+            path,
+            is_dir: entry.file_type.is_dir(),
+            file_len,
+        });
+    }
+    match failed.lock().unwrap().take() {
+        Some(e) => Err(e),
+        None => Ok(tree),
+    }
+}
+
+fn walk_error(err: jwalk::Error) -> UsbError {
+    let context = match err.path() {
+        Some(path) => format!("Failed to read directory entry {}", path.display()),
+        None => "Failed to read directory entry".to_string(),
+    };
+    UsbError::io(context, io::Error::from(err))
+}
+
+/// Fails with [`UsbError::WriteProtected`] if `dest` (or its nearest
+/// existing ancestor) is on a read-only mount or, on Linux, on a block device
+/// the kernel flags read-only (`/sys/class/block/<dev>/ro`), which is what a
+/// stick's write-protect switch turns into.
+pub fn ensure_writable(dest: &Path) -> Result<()> {
+    let (existing, stat) = statvfs(dest)?;
+    // The flag's type differs between platforms; compare in u64.
+    #[allow(clippy::unnecessary_cast)]
+    let read_only = stat.f_flag as u64 & libc::ST_RDONLY as u64 != 0;
+    if read_only {
+        return Err(UsbError::WriteProtected { path: existing });
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(device) = backing_device(&existing)
+        && fs::read_to_string(Path::new("/sys/class/block").join(&device).join("ro"))
+            .is_ok_and(|ro| ro.trim() == "1")
+    {
+        return Err(UsbError::WriteProtected { path: existing });
+    }
+    Ok(())
+}
+
+/// The block device (e.g. `sdb1`) holding the filesystem `path` is on.
+#[cfg(target_os = "linux")]
+pub(crate) fn backing_device(path: &Path) -> Option<String> {
+    let path = fs::canonicalize(path).ok()?;
+    crate::backend::read_mounts()
+        .ok()?
+        .into_iter()
+        .filter(|m| path.starts_with(&m.target))
+        .max_by_key(|m| m.target.len())?
+        .source
+        .strip_prefix("/dev/")
+        .map(String::from)
+}
+
+/// The type of the filesystem holding `path` (or its nearest existing
+/// ancestor), as the mount table names it, e.g. `vfat`.
+#[cfg(target_os = "linux")]
+pub(crate) fn filesystem_type(path: &Path) -> Option<String> {
+    let existing = path.ancestors().find(|p| p.exists())?;
+    let path = fs::canonicalize(existing).ok()?;
+    crate::backend::read_mounts()
+        .ok()?
+        .into_iter()
+        .filter(|m| path.starts_with(&m.target))
+        .max_by_key(|m| m.target.len())
+        .map(|m| m.fstype)
+}
+
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+pub(crate) fn filesystem_type(path: &Path) -> Option<String> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let existing = path.ancestors().find(|p| p.exists())?;
+    let c_path = CString::new(existing.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(stat.f_fstypename.as_ptr()) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "freebsd")))]
+pub(crate) fn filesystem_type(_path: &Path) -> Option<String> {
+    None
+}
+
+/// Where [`copy_to_usb`] puts `source`: inside `dest` if that is a
+/// directory, at `dest` otherwise.
+fn destination_root(source: &Path, dest: &Path) -> Option<PathBuf> {
+    Some(if dest.is_dir() {
+        dest.join(source.file_name()?)
+    } else {
+        dest.to_path_buf()
+    })
+}
+
+/// For `target` under `root`, its full path and the part of it the copy
+/// creates, below the directory `existing`.
+fn creates(existing: &Path, root: &Path, target: &Path) -> (PathBuf, PathBuf) {
+    let path = if target.as_os_str().is_empty() {
+        root.to_path_buf()
+    } else {
+        root.join(target)
+    };
+    let new = path.strip_prefix(existing).unwrap_or(&path).to_path_buf();
+    (path, new)
+}
+
+/// Fails, listing them, if any of the `(path, new)` pairs can't be created
+/// on the filesystem at `dest`; see [`crate::preflight::check`].
+fn preflight(dest: &Path, paths: impl IntoIterator<Item = (PathBuf, PathBuf)>) -> Result<()> {
+    const SHOWN: usize = 20;
+
+    let fstype = filesystem_type(dest).unwrap_or_default();
+    let limits = crate::preflight::Limits::for_fstype(&fstype);
+    let problems: Vec<(PathBuf, crate::preflight::Problem)> = paths
+        .into_iter()
+        .filter_map(|(path, new)| Some((crate::preflight::check(&path, &new, &limits)?, path)))
+        .map(|(problem, path)| (path, problem))
+        .collect();
+    if problems.is_empty() {
+        return Ok(());
+    }
+    let on = if fstype.is_empty() {
+        String::new()
+    } else {
+        format!(" on {}", fstype)
+    };
+    let mut message = format!(
+        "{} path(s) can't be created{}; rename them and copy again:",
+        problems.len(),
+        on
+    );
+    for (path, problem) in problems.iter().take(SHOWN) {
+        message.push_str(&format!("\n  {}: {}", path.display(), problem));
+    }
+    if problems.len() > SHOWN {
+        message.push_str(&format!("\n  and {} more", problems.len() - SHOWN));
+    }
+    Err(UsbError::Unsupported(message))
+}
+
+/// Fails unless the filesystem holding `dest` has room for `needed` bytes
+/// once the `replaced` bytes of the files they overwrite are freed: copying
+/// over what is already there takes up only the difference.
+fn ensure_space(dest: &Path, needed: u64, replaced: u64) -> Result<()> {
+    let available = available_space(dest)?;
+    let needed = needed.saturating_sub(replaced);
+    if needed > available {
+        return Err(UsbError::InsufficientSpace {
+            needed,
+            available,
+            path: None,
+        });
+    }
+    Ok(())
+}
+
+/// The bytes the existing files among `paths` take up, which writing over
+/// them gives back.
+fn replaced_size(paths: impl IntoIterator<Item = impl AsRef<Path>>) -> u64 {
+    paths
+        .into_iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Free space available to unprivileged users on the filesystem holding
+/// `dest`, or its nearest existing ancestor if `dest` doesn't exist yet.
+fn available_space(dest: &Path) -> Result<u64> {
+    let (_, stat) = statvfs(dest)?;
+    // 32-bit on some platforms.
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// `statvfs(2)` on `dest`'s nearest existing ancestor, returned alongside.
+pub(crate) fn statvfs(dest: &Path) -> Result<(PathBuf, libc::statvfs)> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let existing = dest
+        .ancestors()
+        .find(|p| p.exists())
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let c_path = CString::new(existing.as_os_str().as_bytes()).map_err(|_| {
+        UsbError::Unsupported(format!("{} contains a NUL byte", existing.display()))
+    })?;
+
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(UsbError::io(
+            format!("Failed to query free space on {}", existing.display()),
+            io::Error::last_os_error(),
+        ));
+    }
+    Ok((existing.to_path_buf(), stat))
+}
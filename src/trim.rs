@@ -0,0 +1,189 @@
+//! TRIM: telling a USB SSD which blocks are free, so it can erase them
+//! ahead of time and keep writing fast.
+//!
+//! Whether that reaches the drive depends on the whole chain. The kernel
+//! has to offer discard for the disk (`/sys/block/<disk>/queue`); for USB
+//! drives that means the SCSI `UNMAP` command, which the bridge must pass
+//! through and which usb-storage never enables by itself. UAS bridges often
+//! advertise it in their Logical Block Provisioning page but are left in
+//! provisioning mode `full` until someone writes `unmap` to
+//! `provisioning_mode`. [`DiscardSupport`] reports each of these.
+//!
+//! A mounted filesystem is trimmed with `fstrim`; an unmounted disk or
+//! partition is discarded whole with `blkdiscard`, which destroys what is
+//! on it. A filesystem trim can't show whether the drive did anything; a
+//! whole discard can: [`reads_back_erased`] samples the device afterwards.
+
+use crate::backend::run;
+use crate::error::{IoContext, Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const SYS_BLOCK: &str = "/sys/block";
+/// How many blocks [`reads_back_erased`] looks at.
+const SAMPLES: u64 = 16;
+const SAMPLE_BYTES: usize = 4096;
+
+/// What a disk offers for discard.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiscardSupport {
+    /// The smallest unit discarded, in bytes; 0 if the kernel doesn't
+    /// offer discard for the disk.
+    pub granularity: u64,
+    /// The most one request may discard, in bytes; 0 if unsupported.
+    pub max_bytes: u64,
+    /// The kernel's SCSI provisioning mode (`unmap`, `writesame_16`,
+    /// `full`, `disabled`...); `None` for disks that aren't SCSI.
+    pub provisioning_mode: Option<String>,
+    /// Whether the device says it supports `UNMAP`, from its Logical Block
+    /// Provisioning VPD page; `None` if it has none.
+    pub unmap_advertised: Option<bool>,
+    /// Whether the device promises unmapped blocks read back as zeros.
+    pub reads_zeros: Option<bool>,
+}
+
+impl DiscardSupport {
+    /// Whether the kernel will pass discards on to the disk.
+    pub fn supported(&self) -> bool {
+        self.max_bytes > 0
+    }
+
+    /// Whether the device supports `UNMAP` but the kernel hasn't been told
+    /// to use it, as with most UAS bridges.
+    pub fn can_enable_unmap(&self) -> bool {
+        self.unmap_advertised == Some(true)
+            && self
+                .provisioning_mode
+                .as_deref()
+                .is_some_and(|mode| mode != "unmap")
+    }
+}
+
+/// What `disk` (e.g. `sdb`, not a partition) offers for discard.
+pub fn discard_support(disk: &str) -> DiscardSupport {
+    discard_support_in(Path::new(SYS_BLOCK), disk)
+}
+
+/// [`discard_support`] with `sys_block` in place of `/sys/block`.
+pub fn discard_support_in(sys_block: &Path, disk: &str) -> DiscardSupport {
+    let dir = sys_block.join(disk);
+    let number = |name: &str| {
+        fs::read_to_string(dir.join("queue").join(name))
+            .ok()
+            .and_then(|text| text.trim().parse().ok())
+            .unwrap_or(0)
+    };
+    let provisioning = provisioning_mode_path(&dir);
+    let vpd = fs::read(dir.join("device/vpd_pgb2"))
+        .ok()
+        .filter(|page| page.len() > 5);
+    DiscardSupport {
+        granularity: number("discard_granularity"),
+        max_bytes: number("discard_max_bytes"),
+        provisioning_mode: provisioning
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|mode| mode.trim().to_string()),
+        unmap_advertised: vpd.as_ref().map(|page| page[5] & 0x80 != 0),
+        reads_zeros: vpd.as_ref().map(|page| page[5] & 0x04 != 0),
+    }
+}
+
+/// `device/scsi_disk/<h:c:t:l>/provisioning_mode` under the disk's
+/// sysfs directory `dir`.
+fn provisioning_mode_path(dir: &Path) -> Option<PathBuf> {
+    fs::read_dir(dir.join("device/scsi_disk"))
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path().join("provisioning_mode"))
+        .find(|path| path.exists())
+}
+
+/// Has the kernel send `UNMAP` to `disk`. Needs root; lasts until the
+/// drive is unplugged.
+pub fn enable_unmap(disk: &str) -> Result<()> {
+    enable_unmap_in(Path::new(SYS_BLOCK), disk)
+}
+
+/// [`enable_unmap`] with `sys_block` in place of `/sys/block`.
+pub fn enable_unmap_in(sys_block: &Path, disk: &str) -> Result<()> {
+    let path = provisioning_mode_path(&sys_block.join(disk)).ok_or_else(|| {
+        UsbError::Unsupported(format!(
+            "{} is no SCSI disk, so it has no provisioning mode",
+            disk
+        ))
+    })?;
+    fs::write(&path, "unmap").io_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Parses what `fstrim --verbose` prints,
+/// `/media/usb: 14.9 GiB (15998234624 bytes) trimmed`, into the bytes.
+pub fn parse_fstrim(output: &str) -> Option<u64> {
+    let (_, rest) = output.rsplit_once('(')?;
+    rest.split_once(" bytes)")?.0.trim().parse().ok()
+}
+
+/// Whether `len` bytes of `dev` read back as erased flash (all zeros or
+/// all ones) at [`SAMPLES`] places spread over it: after a discard, that
+/// the drive really dropped the data.
+pub fn reads_back_erased(dev: &mut (impl Read + Seek), len: u64) -> io::Result<bool> {
+    let blocks = len / SAMPLE_BYTES as u64;
+    if blocks == 0 {
+        return Ok(false);
+    }
+    let mut buf = vec![0u8; SAMPLE_BYTES];
+    for sample in 0..SAMPLES.min(blocks) {
+        let block = sample * (blocks - 1) / (SAMPLES - 1).max(1);
+        dev.seek(SeekFrom::Start(block * SAMPLE_BYTES as u64))?;
+        dev.read_exact(&mut buf)?;
+        if !(buf.iter().all(|&b| b == 0) || buf.iter().all(|&b| b == 0xff)) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// [`reads_back_erased`] on the device node at `path`.
+pub fn reads_back_erased_path(path: &Path, len: u64) -> Result<bool> {
+    let mut file = File::open(path).io_context(|| format!("Failed to open {}", path.display()))?;
+    reads_back_erased(&mut file, len).io_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// Runs `fstrim` and `blkdiscard`.
+#[derive(Clone)]
+pub struct Trimmer {
+    exec: Arc<dyn CommandExecutor>,
+}
+
+impl Default for Trimmer {
+    fn default() -> Self {
+        Trimmer::with_executor(Arc::new(SystemExecutor))
+    }
+}
+
+impl Trimmer {
+    /// Uses `exec` to run the tools.
+    pub fn with_executor(exec: Arc<dyn CommandExecutor>) -> Self {
+        Trimmer { exec }
+    }
+
+    /// Discards the free space of the filesystem mounted at `mountpoint`.
+    /// Returns how many bytes the filesystem passed on, if `fstrim` said.
+    pub fn fstrim(&self, mountpoint: &str) -> Result<Option<u64>> {
+        let output = run(self.exec.as_ref(), "fstrim", &["--verbose", mountpoint])?;
+        Ok(parse_fstrim(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Discards all of `device` (e.g. `sdb` or `sdb1`), destroying what is
+    /// on it.
+    pub fn blkdiscard(&self, device: &str) -> Result<()> {
+        run(
+            self.exec.as_ref(),
+            "blkdiscard",
+            &[&format!("/dev/{}", device)],
+        )
+        .map(drop)
+    }
+}
@@ -1,176 +1,539 @@
-use crate::usb;
+use crate::commands;
+use crate::commands::Settings;
 use anyhow::Result;
-use dialoguer::{theme::ColorfulTheme, Select, Input, Confirm};
+use dialoguer::{Confirm, Input, Select, theme::ColorfulTheme};
+use lsusb::backup::Conflict;
+use lsusb::policy::Access;
+use lsusb::transfer::ConflictPrompt;
+use lsusb::{DeviceEvent, SystemBackend, UsbDevice, tr};
+use std::io::{BufRead, Write};
 use std::path::PathBuf;
+use std::sync::Mutex;
 
-pub fn run() -> Result<()> {
+pub fn run(backend: &dyn SystemBackend, config: &lsusb::Config, settings: Settings) -> Result<()> {
+    let mut known = backend.enumerate().unwrap_or_default();
+    // Partitions of sticks plugged in since the menu opened, offered for
+    // mounting at the top of the menu until they are mounted or unplugged.
+    let mut arrived: Vec<String> = Vec::new();
+    // What each drive read and wrote between one menu and the next.
+    let mut io = lsusb::diskstats::IoMeter::default();
     loop {
-        let options = &[
-            "List USB Devices",
-            "List Partitions",
-            "Sync Device",
-            "Unmount Device",
-            "Copy File/Dir",
-            "Exit",
+        let devices = backend.enumerate().unwrap_or_default();
+        let mut status = Vec::new();
+        for event in lsusb::monitor::diff_devices(&known, &devices) {
+            match event {
+                DeviceEvent::Attached(device) => {
+                    let unmounted: Vec<String> = device
+                        .partitions
+                        .iter()
+                        .filter(|p| p.mountpoint.is_none())
+                        .map(|p| p.name.clone())
+                        .collect();
+                    match unmounted.first() {
+                        Some(first) => status.push(tr!(
+                            "device-attached-mount",
+                            device = describe(&device),
+                            partition = first.as_str()
+                        )),
+                        None => status.push(tr!("device-attached", device = describe(&device))),
+                    }
+                    arrived.extend(unmounted);
+                }
+                DeviceEvent::Detached(name) => status.push(tr!("device-detached", device = name)),
+            }
+        }
+        known = devices;
+        let activity = io.sample().unwrap_or_default();
+        for device in &known {
+            if let Some(busy) = activity.get(&device.name).filter(|a| a.is_busy()) {
+                status.push(tr!(
+                    "device-busy",
+                    device = device.name.as_str(),
+                    read = settings.units.format(busy.read_rate),
+                    written = settings.units.format(busy.write_rate)
+                ));
+            }
+        }
+        arrived.retain(|name| {
+            known
+                .iter()
+                .flat_map(|d| &d.partitions)
+                .any(|p| &p.name == name && p.mountpoint.is_none())
+        });
+
+        let mut items: Vec<String> = arrived
+            .iter()
+            .map(|name| tr!("menu-mount", partition = name.as_str()))
+            .collect();
+        let quick = items.len();
+        let options = [
+            "menu-list-devices",
+            "menu-list-partitions",
+            "menu-sync",
+            "menu-unmount",
+            "menu-copy",
+            "menu-open",
+            "menu-rename",
+            "menu-health",
+            "menu-exit",
         ];
+        items.extend(options.iter().map(|key| tr!(key)));
 
-        let selection = Select::with_theme(&ColorfulTheme::default())
-            .with_prompt("Select an action")
-            .default(0)
-            .items(&options[..])
-            .interact()?;
+        let mut prompt = String::new();
+        for line in &status {
+            prompt.push_str(line);
+            prompt.push('\n');
+        }
+        prompt.push_str(&tr!("menu-prompt"));
+        let selection = select(prompt, &items, Some(0))?;
 
-        match selection {
+        if selection < quick {
+            let partition = &arrived[selection];
+            let mut settings = settings;
+            let mounted = config
+                .policy
+                .check(partition, Access::ReadOnly)
+                .map_err(anyhow::Error::from)
+                .and_then(|access| {
+                    settings.read_only |= access == Access::ReadOnly;
+                    commands::mount_partition(backend, partition, None, settings)
+                });
+            if let Err(e) = mounted {
+                println!("{}", tr!("error", error = format!("{:#}", e)));
+            }
+            wait_user();
+            continue;
+        }
+        match selection - quick {
             0 => {
-                if let Err(e) = usb::list_usbs() {
-                    println!("Error: {}", e);
+                if let Err(e) = commands::list_usbs(backend, settings.units, Some(&activity)) {
+                    println!("{}", tr!("error", error = format!("{:#}", e)));
                 }
                 wait_user();
             }
             1 => {
                 // List Partitions
-                match usb::get_usb_devices() {
+                match backend.enumerate() {
                     Ok(devices) => {
                         if devices.is_empty() {
-                            println!("No USB devices found.");
+                            println!("{}", tr!("no-usb-devices"));
                             wait_user();
                             continue;
                         }
-                        let device_names: Vec<String> = devices.iter().map(|d| d.name.clone()).collect();
-                        let selection = Select::with_theme(&ColorfulTheme::default())
-                            .with_prompt("Select a device")
-                            .items(&device_names)
-                            .interact()?;
-                        
-                        if let Err(e) = usb::list_partitions(&device_names[selection]) {
-                            println!("Error: {}", e);
+                        let device_names: Vec<String> =
+                            devices.iter().map(|d| d.name.clone()).collect();
+                        let selection = select(tr!("select-device"), &device_names, None)?;
+
+                        if let Err(e) = commands::list_partitions(
+                            backend,
+                            &device_names[selection],
+                            settings.units,
+                        ) {
+                            println!("{}", tr!("error", error = format!("{:#}", e)));
                         }
                     }
-                    Err(e) => println!("Error listing devices: {}", e),
+                    Err(e) => println!(
+                        "{}",
+                        tr!("error-listing-devices", error = format!("{:#}", e))
+                    ),
                 }
                 wait_user();
             }
             2 => {
                 // Sync
-                match usb::get_usb_devices() {
+                match backend.enumerate() {
                     Ok(devices) => {
-                         if devices.is_empty() {
-                            println!("No USB devices found.");
+                        if devices.is_empty() {
+                            println!("{}", tr!("no-usb-devices"));
                             wait_user();
                             continue;
                         }
-                         let device_names: Vec<String> = devices.iter().map(|d| d.name.clone()).collect();
-                        let selection = Select::with_theme(&ColorfulTheme::default())
-                            .with_prompt("Select a device to sync")
-                            .items(&device_names)
-                            .interact()?;
-                        
-                        if let Err(e) = usb::sync_device(&device_names[selection]) {
-                            println!("Error: {}", e);
+                        let device_names: Vec<String> =
+                            devices.iter().map(|d| d.name.clone()).collect();
+                        let selection = select(tr!("select-device-to-sync"), &device_names, None)?;
+
+                        if let Err(e) =
+                            commands::sync_device(backend, &device_names[selection], settings.units)
+                        {
+                            println!("{}", tr!("error", error = format!("{:#}", e)));
                         }
                     }
-                    Err(e) => println!("Error listing devices: {}", e),
+                    Err(e) => println!(
+                        "{}",
+                        tr!("error-listing-devices", error = format!("{:#}", e))
+                    ),
                 }
                 wait_user();
             }
             3 => {
-                 // Unmount
-                 match usb::get_usb_devices() {
-                     Ok(devices) => {
-                         let mut mountpoints = Vec::new();
-                         for dev in &devices {
-                             if let Some(children) = &dev.children {
-                                 for child in children {
-                                     if let Some(mp) = &child.mountpoint {
-                                         mountpoints.push(mp.clone());
-                                     }
-                                 }
-                             }
-                         }
-                        
+                // Unmount
+                match backend.enumerate() {
+                    Ok(devices) => {
+                        let mountpoints: Vec<String> = devices
+                            .iter()
+                            .flat_map(|d| d.mountpoints())
+                            .map(String::from)
+                            .collect();
+
                         if mountpoints.is_empty() {
-                            println!("No mounted partitions found on USB devices.");
+                            println!("{}", tr!("no-mounted-usb-partitions"));
                             wait_user();
                             continue;
                         }
 
-                        let selection = Select::with_theme(&ColorfulTheme::default())
-                            .with_prompt("Select a mountpoint to unmount")
-                            .items(&mountpoints)
-                            .interact()?;
+                        let selection =
+                            select(tr!("select-mountpoint-to-unmount"), &mountpoints, None)?;
 
-                        if let Err(e) = usb::unmount_device(&mountpoints[selection]) {
-                            println!("Error: {}", e);
+                        if let Err(e) = commands::unmount_device(
+                            backend,
+                            &mountpoints[selection],
+                            settings.units,
+                        ) {
+                            println!("{}", tr!("error", error = format!("{:#}", e)));
                         }
-                     }
-                     Err(e) => println!("Error listing devices: {}", e),
-                 }
+                    }
+                    Err(e) => println!(
+                        "{}",
+                        tr!("error-listing-devices", error = format!("{:#}", e))
+                    ),
+                }
                 wait_user();
             }
             4 => {
-                 // Copy
-                 let source: String = Input::with_theme(&ColorfulTheme::default())
-                    .with_prompt("Enter path to source file/directory")
-                    .interact_text()?;
+                // Copy
+                if let Err(e) = settings.ensure_writable("copy") {
+                    println!("{}", tr!("error", error = format!("{:#}", e)));
+                    wait_user();
+                    continue;
+                }
+                let source: String = input(tr!("copy-source"), None, |_| Ok(()))?;
 
                 // Select destination partition
-                 match usb::get_usb_devices() {
-                     Ok(devices) => {
-                         let mut mountpoints = Vec::new();
-                         for dev in &devices {
-                             if let Some(children) = &dev.children {
-                                 for child in children {
-                                     if let Some(mp) = &child.mountpoint {
-                                         mountpoints.push(mp.clone());
-                                     }
-                                 }
-                             }
-                         }
-                         
-                         if mountpoints.is_empty() {
-                             println!("No mounted partitions found. Cannot copy.");
-                             wait_user();
-                             continue;
-                         }
-
-                        let selection = Select::with_theme(&ColorfulTheme::default())
-                            .with_prompt("Select destination partition")
-                            .items(&mountpoints)
-                            .interact()?;
-                        
+                match backend.enumerate() {
+                    Ok(devices) => {
+                        let mountpoints: Vec<String> = devices
+                            .iter()
+                            .flat_map(|d| d.mountpoints())
+                            .map(String::from)
+                            .collect();
+
+                        if mountpoints.is_empty() {
+                            println!("{}", tr!("no-mounted-partitions-to-copy"));
+                            wait_user();
+                            continue;
+                        }
+
+                        let selection = select(tr!("select-destination"), &mountpoints, None)?;
+
                         let dest_root = PathBuf::from(&mountpoints[selection]);
-                        
-                        let use_root = Confirm::with_theme(&ColorfulTheme::default())
-                            .with_prompt(format!("Copy to root of {}?", mountpoints[selection]))
-                            .default(true)
-                            .interact()?;
+
+                        let use_root = confirm(
+                            tr!("copy-to-root", mountpoint = mountpoints[selection].as_str()),
+                            true,
+                        )?;
 
                         let final_dest = if use_root {
                             dest_root
                         } else {
-                             let subpath: String = Input::with_theme(&ColorfulTheme::default())
-                                .with_prompt("Enter subdirectory/filename in destination")
-                                .interact_text()?;
+                            let subpath: String = input(tr!("copy-subpath"), None, |_| Ok(()))?;
                             dest_root.join(subpath)
                         };
 
-                        if let Err(e) = usb::copy_to_usb(&PathBuf::from(source), &final_dest) {
-                            println!("Error: {}", e);
+                        let eject = confirm(tr!("copy-eject"), false)?;
+
+                        let existing = select(
+                            tr!("copy-existing"),
+                            &[
+                                tr!("existing-overwrite"),
+                                tr!("existing-skip"),
+                                tr!("existing-ask"),
+                            ],
+                            Some(0),
+                        )?;
+                        let on_existing = match existing {
+                            0 => None,
+                            1 => Some(ConflictPrompt::new(|_, _| Conflict::Skip)),
+                            _ => Some(ask_on_conflict()),
+                        };
+
+                        let options = lsusb::TransferOptions {
+                            ignore: config.ignore(),
+                            on_existing,
+                            on_overwrite: Some(commands::log_overwrites(config.audit_log()?)),
+                            ..Default::default()
+                        };
+                        let copied = commands::copy_to_usb(
+                            backend,
+                            &PathBuf::from(source),
+                            &final_dest,
+                            settings,
+                            &config.policy,
+                            options,
+                            false,
+                        );
+                        if let Err(e) = copied.and_then(|_| match eject {
+                            true => commands::eject_after(backend, &[final_dest], settings.units),
+                            false => Ok(()),
+                        }) {
+                            println!("{}", tr!("error", error = format!("{:#}", e)));
+                        }
+                    }
+                    Err(e) => println!(
+                        "{}",
+                        tr!("error-listing-devices", error = format!("{:#}", e))
+                    ),
+                }
+                wait_user();
+            }
+            5 => {
+                // Open
+                match backend.enumerate() {
+                    Ok(devices) => {
+                        let partitions: Vec<String> = devices
+                            .into_iter()
+                            .flat_map(|d| d.partitions)
+                            .map(|p| p.name)
+                            .collect();
+
+                        if partitions.is_empty() {
+                            println!("{}", tr!("no-usb-partitions"));
+                            wait_user();
+                            continue;
                         }
-                     }
-                     Err(e) => println!("Error listing devices: {}", e),
-                 }
-                 wait_user();
 
+                        let selection = select(tr!("select-partition-to-open"), &partitions, None)?;
+
+                        if let Err(e) =
+                            commands::open(backend, config, &partitions[selection], settings)
+                        {
+                            println!("{}", tr!("error", error = format!("{:#}", e)));
+                        }
+                    }
+                    Err(e) => println!(
+                        "{}",
+                        tr!("error-listing-devices", error = format!("{:#}", e))
+                    ),
+                }
+                wait_user();
+            }
+            6 => {
+                // Rename
+                if let Err(e) = settings.ensure_writable("relabel") {
+                    println!("{}", tr!("error", error = format!("{:#}", e)));
+                    wait_user();
+                    continue;
+                }
+                let partitions: Vec<(String, String, Option<String>)> = known
+                    .iter()
+                    .flat_map(|d| &d.partitions)
+                    .filter_map(|p| Some((p.name.clone(), p.fstype.clone()?, p.label.clone())))
+                    .collect();
+                if partitions.is_empty() {
+                    println!("{}", tr!("no-labelled-partitions"));
+                    wait_user();
+                    continue;
+                }
+                let names: Vec<String> = partitions
+                    .iter()
+                    .map(|(name, fstype, label)| {
+                        format!(
+                            "{} ({}, {})",
+                            name,
+                            fstype,
+                            label.clone().unwrap_or_else(|| tr!("no-label"))
+                        )
+                    })
+                    .collect();
+                let selection = select(tr!("select-partition-to-rename"), &names, None)?;
+                let (name, fstype, label) = &partitions[selection];
+
+                let new_label = input(tr!("new-label"), label.clone(), |input| {
+                    lsusb::label::normalize(fstype, input)
+                        .map(drop)
+                        .map_err(|e| e.to_string())
+                })?;
+                let relabeled = config
+                    .audit_log()
+                    .map_err(anyhow::Error::from)
+                    .and_then(|log| {
+                        commands::label(backend, name, &new_label, &log, &config.policy, settings)
+                    });
+                if let Err(e) = relabeled {
+                    println!("{}", tr!("error", error = format!("{:#}", e)));
+                }
+                wait_user();
+            }
+            7 => {
+                // Health
+                if known.is_empty() {
+                    println!("{}", tr!("no-usb-devices"));
+                    wait_user();
+                    continue;
+                }
+                let names: Vec<String> = known.iter().map(describe).collect();
+                let selection = select(tr!("select-device"), &names, None)?;
+                if let Err(e) = commands::health(
+                    backend,
+                    &known[selection].name,
+                    &config.smart,
+                    settings.units,
+                ) {
+                    println!("{}", tr!("error", error = format!("{:#}", e)));
+                }
+                wait_user();
             }
-            5 => break,
+            8 => break,
             _ => break,
         }
     }
     Ok(())
 }
 
+/// `sdc (SanDisk Ultra 57.3G)`.
+fn describe(device: &UsbDevice) -> String {
+    let name: Vec<&str> = [device.vendor.as_deref(), device.model.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect();
+    if name.is_empty() {
+        format!("{} ({})", device.name, device.size)
+    } else {
+        format!("{} ({} {})", device.name, name.join(" "), device.size)
+    }
+}
+
+/// Asks what to do with each file already at the destination, until an
+/// answer "for all" settles the rest of the copy. Without a terminal to
+/// ask on, the file is skipped.
+fn ask_on_conflict() -> ConflictPrompt {
+    let for_all: Mutex<Option<Conflict>> = Mutex::new(None);
+    ConflictPrompt::new(move |_, existing| {
+        let mut for_all = for_all.lock().unwrap();
+        if let Some(choice) = *for_all {
+            return choice;
+        }
+        let choices = [Conflict::Overwrite, Conflict::Skip, Conflict::KeepBoth];
+        let answer = select(
+            tr!("conflict-exists", path = existing.display().to_string()),
+            &[
+                tr!("conflict-overwrite"),
+                tr!("conflict-skip"),
+                tr!("conflict-keep-both"),
+                tr!("conflict-overwrite-all"),
+                tr!("conflict-skip-all"),
+                tr!("conflict-keep-both-all"),
+            ],
+            Some(1),
+        );
+        match answer {
+            Ok(i) if i >= choices.len() => {
+                *for_all = Some(choices[i - choices.len()]);
+                choices[i - choices.len()]
+            }
+            Ok(i) => choices[i],
+            Err(_) => Conflict::Skip,
+        }
+    })
+}
+
+/// Which of `items` is picked: with the arrow keys, or in plain mode by
+/// typing its number, Enter alone taking `default`.
+fn select(prompt: String, items: &[String], default: Option<usize>) -> Result<usize> {
+    if !commands::plain() {
+        let theme = ColorfulTheme::default();
+        let mut select = Select::with_theme(&theme).with_prompt(prompt).items(items);
+        if let Some(default) = default {
+            select = select.default(default);
+        }
+        return Ok(select.interact()?);
+    }
+    println!("{}", prompt);
+    for (i, item) in items.iter().enumerate() {
+        println!("  {}) {}", i + 1, item);
+    }
+    let hint = default.map_or(String::new(), |d| format!(" [{}]", d + 1));
+    loop {
+        let answer = read_answer(&format!("1-{}{}", items.len(), hint))?;
+        if answer.is_empty()
+            && let Some(default) = default
+        {
+            return Ok(default);
+        }
+        match answer.parse::<usize>() {
+            Ok(n) if (1..=items.len()).contains(&n) => return Ok(n - 1),
+            _ => println!("{}", tr!("type-number", count = items.len())),
+        }
+    }
+}
+
+/// A yes-or-no answer; `y` or `n` in plain mode.
+fn confirm(prompt: String, default: bool) -> Result<bool> {
+    if !commands::plain() {
+        return Ok(Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .default(default)
+            .interact()?);
+    }
+    loop {
+        let answer = read_answer(&format!(
+            "{} {}",
+            prompt,
+            if default { "[Y/n]" } else { "[y/N]" }
+        ))?;
+        match answer.to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => println!("{}", tr!("type-yes-no")),
+        }
+    }
+}
+
+/// A line of text, starting from `initial`, asked for again until
+/// `validate` accepts it. Empty answers are only taken as `initial`.
+fn input(
+    prompt: String,
+    initial: Option<String>,
+    validate: impl Fn(&String) -> Result<(), String>,
+) -> Result<String> {
+    if !commands::plain() {
+        return Ok(Input::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .with_initial_text(initial.unwrap_or_default())
+            .validate_with(|input: &String| validate(input))
+            .interact_text()?);
+    }
+    let hint = initial
+        .as_ref()
+        .map_or(String::new(), |i| format!(" [{}]", i));
+    loop {
+        let answer = match read_answer(&format!("{}{}", prompt, hint))? {
+            answer if answer.is_empty() => match &initial {
+                Some(initial) => initial.clone(),
+                None => continue,
+            },
+            answer => answer,
+        };
+        match validate(&answer) {
+            Ok(()) => return Ok(answer),
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+/// Prints `prompt: ` and reads a line, without its newline; fails at the
+/// end of input rather than asking forever.
+fn read_answer(prompt: &str) -> Result<String> {
+    print!("{}: ", prompt);
+    std::io::stdout().flush()?;
+    let mut line = String::new();
+    if std::io::stdin().lock().read_line(&mut line)? == 0 {
+        anyhow::bail!("no more input");
+    }
+    Ok(line.trim_end_matches(['\n', '\r']).to_string())
+}
+
 fn wait_user() {
-    println!("\nPress Enter to continue...");
+    println!("\n{}", tr!("press-enter"));
     let _ = std::io::stdin().read_line(&mut String::new());
 }
@@ -1,218 +1,314 @@
-use anyhow::{Context, Result};
-use serde::Deserialize;
-use std::process::Command;
+//! Device types and convenience functions using the platform's default
+//! backend. Pick a specific [`SystemBackend`](crate::SystemBackend) for
+//! other mechanisms.
+
+use crate::backend::{MountOptions, default_backend};
+use crate::error::Result;
+use serde::Serialize;
+use std::fmt;
 use std::path::Path;
 
-#[derive(Debug, Deserialize)]
-pub struct LsblkOutput {
-    pub blockdevices: Vec<Device>,
+/// A USB block device (a whole disk, e.g. `sdb`).
+#[derive(Debug, Clone, Serialize)]
+pub struct UsbDevice {
+    /// Kernel device name, e.g. `sdb`.
+    pub name: String,
+    /// Human readable size as reported by lsblk, e.g. `14.9G`.
+    pub size: String,
+    /// Exact size in bytes.
+    pub bytes: u64,
+    /// Vendor string reported by the device, if any.
+    pub vendor: Option<String>,
+    /// Model string reported by the device, if any.
+    pub model: Option<String>,
+    /// Whether the kernel flags the device as hot-pluggable.
+    pub hotplug: Option<bool>,
+    /// Partitions found on the device.
+    pub partitions: Vec<Partition>,
+    /// The rating of an SD card in a slot the kernel drives directly
+    /// (`mmcblk*`); card readers behind USB don't pass it through.
+    pub speed_class: Option<SpeedClass>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Device {
+/// A partition on a [`UsbDevice`].
+#[derive(Debug, Clone, Serialize)]
+pub struct Partition {
+    /// Kernel device name, e.g. `sdb1`.
     pub name: String,
+    /// Human readable size as reported by lsblk.
     pub size: String,
-    #[serde(rename = "type")]
+    /// Exact size in bytes.
+    pub bytes: u64,
+    /// lsblk device type, usually `part`.
     pub device_type: String,
-    pub tran: Option<String>,
+    /// Filesystem type as `blkid` names it (`vfat`, `ext4`, ...), if known.
+    pub fstype: Option<String>,
+    /// Filesystem label, if it has one.
+    pub label: Option<String>,
+    /// Filesystem UUID (or serial number, for FAT and NTFS), if known.
+    pub uuid: Option<String>,
+    /// Where the partition is mounted, if it is mounted.
     pub mountpoint: Option<String>,
-    pub vendor: Option<String>,
-    pub model: Option<String>,
-    pub hotplug: Option<bool>,
-    // Children partitions
-    pub children: Option<Vec<Device>>,
-}
-
-pub fn get_usb_devices() -> Result<Vec<Device>> {
-    let output = Command::new("lsblk")
-        .args(&[
-            "-J",
-            "-o",
-            "NAME,SIZE,TYPE,TRAN,MOUNTPOINT,VENDOR,MODEL,HOTPLUG",
-        ])
-        .output()
-        .context("Failed to execute lsblk")?;
-
-    if !output.status.success() {
-        anyhow::bail!("lsblk failed: {}", String::from_utf8_lossy(&output.stderr));
+}
+
+impl Partition {
+    /// Stands in for a filesystem written straight onto a whole disk with no
+    /// partition table (common on sticks formatted by cameras or `mkfs
+    /// /dev/sdX`), so listing, mounting, unmounting and copying treat it like
+    /// any other partition. It carries the disk's own name and the `disk`
+    /// device type.
+    pub fn whole_disk(name: &str, bytes: u64, mountpoint: Option<String>) -> Partition {
+        Partition {
+            name: name.to_string(),
+            size: format_size(bytes),
+            bytes,
+            device_type: "disk".to_string(),
+            fstype: None,
+            label: None,
+            uuid: None,
+            mountpoint,
+        }
     }
+}
 
-    let parsed: LsblkOutput = serde_json::from_slice(&output.stdout)
-        .context("Failed to parse lsblk output")?;
+/// The kernel name of partition `number` on `disk`: `sdb1`, but
+/// `mmcblk0p1` and `nvme0n1p1` for disks whose names end in a digit.
+pub fn partition_name(disk: &str, number: u32) -> String {
+    if disk.ends_with(|c: char| c.is_ascii_digit()) {
+        format!("{}p{}", disk, number)
+    } else {
+        format!("{}{}", disk, number)
+    }
+}
 
-    let usb_devices: Vec<Device> = parsed
-        .blockdevices
-        .into_iter()
-        .filter(|d| d.tran.as_deref() == Some("usb"))
-        .collect();
+impl UsbDevice {
+    /// Mountpoints of every mounted partition on this device.
+    pub fn mountpoints(&self) -> impl Iterator<Item = &str> {
+        self.partitions
+            .iter()
+            .filter_map(|p| p.mountpoint.as_deref())
+    }
+}
 
-    Ok(usb_devices)
+/// The speed ratings an SD card declares in its SD Status register, which
+/// promise a minimum sequential write speed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SpeedClass {
+    /// Class 2, 4, 6 or 10.
+    pub class: Option<u8>,
+    /// UHS speed grade: U1 or U3.
+    pub uhs: Option<u8>,
+    /// Video speed class: V6 up to V90.
+    pub video: Option<u8>,
+    /// Application performance class (random I/O): A1 or A2.
+    pub app: Option<u8>,
 }
 
-pub fn list_usbs() -> Result<()> {
-    let devices = get_usb_devices()?;
-    if devices.is_empty() {
-        println!("No USB devices found.");
-        return Ok(());
+impl SpeedClass {
+    /// Decodes the SD Status register as the kernel prints it in
+    /// `/sys/block/mmcblkN/device/ssr`: 512 bits of hex, most significant
+    /// first. `None` if it isn't that, or the card claims no rating at all.
+    pub fn from_ssr(hex: &str) -> Option<SpeedClass> {
+        let hex = hex.trim();
+        if hex.len() != 128 {
+            return None;
+        }
+        let byte = |i: usize| u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok();
+        // Bit n of the register is in byte (511 - n) / 8.
+        let class = match byte(8)? {
+            1 => Some(2),
+            2 => Some(4),
+            3 => Some(6),
+            4 => Some(10),
+            _ => None,
+        };
+        let nonzero = |v: u8| (v != 0).then_some(v);
+        let speed = SpeedClass {
+            class,
+            uhs: nonzero(byte(14)? >> 4),
+            video: nonzero(byte(15)?),
+            app: nonzero(byte(21)? & 0x0f),
+        };
+        (speed != SpeedClass::default()).then_some(speed)
     }
 
-    println!("{:<10} {:<10} {:<10} {:<20} {:<20}", "NAME", "SIZE", "HOTPLUG", "VENDOR", "MODEL");
-    for dev in devices {
-        let hotplug_str = match dev.hotplug {
-            Some(true) => "YES",
-            Some(false) => "NO",
-            None => "-",
-        };
-        println!(
-            "{:<10} {:<10} {:<10} {:<20} {:<20}",
-            dev.name,
-            dev.size,
-            hotplug_str,
-            dev.vendor.as_deref().unwrap_or("-"),
-            dev.model.as_deref().unwrap_or("-")
-        );
+    /// The sequential write speed the ratings guarantee, in MB/s.
+    pub fn min_write_mb_s(&self) -> u32 {
+        let uhs = self.uhs.map_or(0, |u| u as u32 * 10);
+        [
+            self.class.map_or(0, u32::from),
+            uhs,
+            self.video.map_or(0, u32::from),
+        ]
+        .into_iter()
+        .max()
+        .unwrap_or(0)
     }
-    Ok(())
-}
-
-pub fn list_partitions(device_name: &str) -> Result<()> {
-    let devices = get_usb_devices()?;
-    let device = devices.iter().find(|d| d.name == device_name).with_context(|| format!("Device {} not found or is not a USB device", device_name))?;
-
-    println!("Partitions for {}:", device_name);
-    if let Some(children) = &device.children {
-        println!("{:<10} {:<10} {:<10} {:<20}", "NAME", "SIZE", "TYPE", "MOUNTPOINT");
-        for child in children {
-            println!(
-                "{:<10} {:<10} {:<10} {:<20}",
-                child.name,
-                child.size,
-                child.device_type,
-                child.mountpoint.as_deref().unwrap_or("-")
-            );
-        }
-    } else {
-        println!("No partitions found.");
+}
+
+impl fmt::Display for SpeedClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let marks = [
+            self.class.map(|c| format!("Class {}", c)),
+            self.uhs.map(|u| format!("U{}", u)),
+            self.video.map(|v| format!("V{}", v)),
+            self.app.map(|a| format!("A{}", a)),
+        ];
+        let marks: Vec<String> = marks.into_iter().flatten().collect();
+        f.write_str(&marks.join(" "))
     }
+}
 
-    Ok(())
+/// Enumerates the USB block devices currently attached to the system.
+pub fn get_usb_devices() -> Result<Vec<UsbDevice>> {
+    default_backend()?.enumerate()
 }
 
+/// Looks up a single USB device by kernel name (e.g. `sdb`).
+pub fn find_usb_device(device_name: &str) -> Result<UsbDevice> {
+    default_backend()?.find(device_name)
+}
+
+/// Flushes pending writes so the device is safe to remove.
 pub fn sync_device(device_name: &str) -> Result<()> {
-    println!("Syncing device {}...", device_name);
-    // In Linux, 'sync' flushes all buffers. There isn't a per-device sync command easily accessible 
-    // without valid file descriptors or using sg_utils.
-    // For simplicity, we are running global sync, or we could try to sync specifically if we had a mountpoint.
-    // But the user requested "sync and unmount a usb".
-    // Let's run the global 'sync' command for safety.
-    let status = Command::new("sync").status().context("Failed to run sync")?;
-    if !status.success() {
-        anyhow::bail!("sync command failed");
-    }
-    println!("Sync completed.");
-    Ok(())
+    default_backend()?.flush(device_name)
 }
 
+/// Mounts a partition (e.g. `sdb1`) at `mountpoint`.
+pub fn mount_partition(partition: &str, mountpoint: &Path) -> Result<()> {
+    default_backend()?
+        .mount(partition, Some(mountpoint), MountOptions::default())
+        .map(|_| ())
+}
+
+/// Unmounts the filesystem mounted at `mountpoint`.
 pub fn unmount_device(mountpoint: &str) -> Result<()> {
-    println!("Unmounting {}...", mountpoint);
-    let status = Command::new("umount")
-        .arg(mountpoint)
-        .status()
-        .context("Failed to run umount")?;
-
-    if !status.success() {
-        anyhow::bail!("umount command failed");
-    }
-    println!("Unmounted successfully.");
-    Ok(())
+    default_backend()?.unmount(mountpoint)
 }
 
-use indicatif::{ProgressBar, ProgressStyle};
-use walkdir::WalkDir;
-use std::fs::{self, File};
-use std::io::{Read, Write};
+/// Space on a mounted filesystem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Usage {
+    pub total: u64,
+    pub used: u64,
+    /// Free space available to unprivileged users; less than `total - used`
+    /// on filesystems that reserve blocks for root.
+    pub available: u64,
+}
 
-pub fn copy_to_usb(source: &Path, dest: &Path) -> Result<()> {
-    println!("Calculating size...");
-    
-    let mut total_size = 0;
-    if source.is_file() {
-        total_size = source.metadata()?.len();
-    } else {
-        for entry in WalkDir::new(source) {
-            let entry = entry.context("Failed to read directory entry")?;
-            if entry.metadata()?.is_file() {
-                total_size += entry.metadata()?.len();
-            }
+impl Usage {
+    /// Percentage used, rounded up, as `df` shows it: of the space a user
+    /// can fill, so a full disk is 100% even with root's reserve left.
+    pub fn percent_used(&self) -> u64 {
+        let usable = self.used + self.available;
+        if usable == 0 {
+            return 0;
         }
+        (self.used * 100).div_ceil(usable)
     }
+}
 
-    println!("Total size: {} bytes", total_size);
+/// How full the filesystem mounted at `mountpoint` is.
+pub fn usage(mountpoint: &Path) -> Result<Usage> {
+    let (_, stat) = crate::transfer::statvfs(mountpoint)?;
+    // 32-bit on some platforms.
+    #[allow(clippy::unnecessary_cast)]
+    let (blocks, free, available, size) = (
+        stat.f_blocks as u64,
+        stat.f_bfree as u64,
+        stat.f_bavail as u64,
+        stat.f_frsize as u64,
+    );
+    Ok(Usage {
+        total: blocks * size,
+        used: blocks.saturating_sub(free) * size,
+        available: available * size,
+    })
+}
 
-    let pb = ProgressBar::new(total_size);
-    pb.set_style(ProgressStyle::default_bar()
-        .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
-        .unwrap()
-        .progress_chars("#>-"));
+/// How sizes are written out for people.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SizeUnits {
+    /// lsblk's compact 1024-based form: `14.9G`.
+    #[default]
+    Compact,
+    /// Powers of 1024 with IEC suffixes: `14.9 GiB`.
+    Iec,
+    /// Powers of 1000 with SI suffixes: `16.0 GB`.
+    Si,
+    /// The exact byte count: `16008609792`.
+    Bytes,
+}
 
-    if source.is_file() {
-        let file_name = source.file_name().context("Invalid source file name")?;
-        let dest_path = if dest.is_dir() {
-            dest.join(file_name)
-        } else {
-            dest.to_path_buf()
-        };
-        
-        copy_file_with_progress(source, &dest_path, &pb)?;
-    } else {
-        // Directory copy
-         // If dest is a dir that exists, we probably want to copy source INTO it (like cp -r)
-         // But if user selected a partition mountpoint (root), we might copy source dir logic.
-         // Let's assume dest is the target parent or exact target. 
-         // Standard 'cp -r src dst' where dst exists -> src is copied inside dst.
-         
-         let file_name = source.file_name().context("Invalid source dir name")?;
-         let target_root = if dest.is_dir() {
-             dest.join(file_name)
-         } else {
-             // If dest doesn't exist, we create it as the new dir name
-             dest.to_path_buf()
-         };
-
-         fs::create_dir_all(&target_root).context("Failed to create destination directory")?;
-
-        for entry in WalkDir::new(source) {
-            let entry = entry.context("Failed to read directory entry")?;
-            let entry_path = entry.path();
-            
-            // Calculate relative path
-            let relative_path = entry_path.strip_prefix(source)?;
-            let dest_path = target_root.join(relative_path);
-
-            if entry.file_type().is_dir() {
-                fs::create_dir_all(&dest_path).context("Failed to create directory")?;
-            } else {
-                copy_file_with_progress(entry_path, &dest_path, &pb)?;
-            }
+impl SizeUnits {
+    /// Formats `bytes` in this convention.
+    pub fn format(self, bytes: u64) -> String {
+        match self {
+            SizeUnits::Compact => format_size(bytes),
+            SizeUnits::Iec => scaled(
+                bytes,
+                1024.0,
+                &["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"],
+            ),
+            SizeUnits::Si => scaled(bytes, 1000.0, &["B", "kB", "MB", "GB", "TB", "PB", "EB"]),
+            SizeUnits::Bytes => bytes.to_string(),
         }
     }
+}
+
+fn scaled(bytes: u64, base: f64, units: &[&str]) -> String {
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= base && unit < units.len() - 1 {
+        value /= base;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, units[0])
+    } else {
+        format!("{:.1} {}", value, units[unit])
+    }
+}
 
-    pb.finish_with_message("Copy complete");
-    Ok(())
+/// Parses a byte count as people write it on the command line: `512`,
+/// `0x1be`, or with a 1024-based suffix, `4K`, `1M`, `2G` (`KiB` etc. and a
+/// trailing `B` are accepted too).
+pub fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        return u64::from_str_radix(hex, 16).ok();
+    }
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, suffix) = text.split_at(split);
+    let number: u64 = number.parse().ok()?;
+    let shift = match suffix
+        .trim()
+        .to_ascii_uppercase()
+        .trim_end_matches('B')
+        .trim_end_matches('I')
+    {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => return None,
+    };
+    number.checked_mul(1 << shift)
 }
 
-fn copy_file_with_progress(source: &Path, dest: &Path, pb: &ProgressBar) -> Result<()> {
-    let mut file_in = File::open(source).context(format!("Failed to open source file {:?}", source))?;
-    let mut file_out = File::create(dest).context(format!("Failed to create dest file {:?}", dest))?;
-    
-    let mut buffer = [0u8; 8192];
-    loop {
-        let n = file_in.read(&mut buffer).context("Failed to read from file")?;
-        if n == 0 {
-             break;
-        }
-        file_out.write_all(&buffer[..n]).context("Failed to write to file")?;
-        pb.inc(n as u64);
+/// Formats a byte count the way lsblk does (`14.9G`, `512M`).
+pub(crate) fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 7] = ["B", "K", "M", "G", "T", "P", "E"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
     }
-    Ok(())
+    let rounded = format!("{:.1}", value);
+    let rounded = rounded.strip_suffix(".0").unwrap_or(&rounded);
+    format!("{}{}", rounded, UNITS[unit])
 }
@@ -0,0 +1,189 @@
+//! VeraCrypt volumes: encrypted partitions and container files, often made
+//! on Windows so a stick can be unlocked on any machine.
+//!
+//! A VeraCrypt volume has no signature; without the password it is
+//! indistinguishable from random data. [`looks_like_volume`] therefore only
+//! says a partition or file *could* be one: nothing [`probe`](crate::probe)
+//! recognises, and a first 64 KiB that is as random as the header area of
+//! a volume is. Opening and closing go through the `veracrypt` command line
+//! in text mode.
+
+use crate::MountOptions;
+use crate::backend::{check_output, run};
+use crate::error::{IoContext, Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor, which};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// How much of the start [`looks_like_volume`] inspects: the primary
+/// header and the random fill after it.
+const HEADER_AREA: usize = 64 * 1024;
+/// Bits per byte above which data is taken to be random. Random data
+/// of [`HEADER_AREA`] bytes comes to about 7.997; text, code and
+/// filesystem metadata are well below.
+const RANDOM_ENTROPY: f64 = 7.99;
+
+/// Whether `dev` could be a VeraCrypt volume (see the module docs).
+pub fn looks_like_volume(dev: &mut (impl Read + Seek)) -> io::Result<bool> {
+    if crate::probe::probe(dev)?.is_some() {
+        return Ok(false);
+    }
+    dev.seek(SeekFrom::Start(0))?;
+    let mut header = Vec::with_capacity(HEADER_AREA);
+    dev.take(HEADER_AREA as u64).read_to_end(&mut header)?;
+    Ok(header.len() == HEADER_AREA && entropy(&header) > RANDOM_ENTROPY)
+}
+
+/// [`looks_like_volume`] on the device node or container file at `path`.
+pub fn looks_like_volume_path(path: &Path) -> Result<bool> {
+    let mut file = File::open(path).io_context(|| format!("Failed to open {}", path.display()))?;
+    looks_like_volume(&mut file).io_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// Shannon entropy of `data`, in bits per byte.
+fn entropy(data: &[u8]) -> f64 {
+    let mut counts = [0u64; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&n| n > 0)
+        .map(|&n| {
+            let p = n as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Whether the `veracrypt` command is installed.
+pub fn is_available() -> bool {
+    which("veracrypt").is_some()
+}
+
+/// A volume VeraCrypt has open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenVolume {
+    /// VeraCrypt's slot number.
+    pub slot: u32,
+    /// The partition or container file, e.g. `/dev/sdb1`.
+    pub volume: String,
+    /// The decrypted device, e.g. `/dev/mapper/veracrypt1`.
+    pub device: String,
+    /// Where it is mounted; `None` if it isn't.
+    pub mountpoint: Option<PathBuf>,
+}
+
+/// Parses `veracrypt --text --list`:
+/// `1: /dev/sdb1 /dev/mapper/veracrypt1 /media/veracrypt1`.
+pub fn parse_list(output: &str) -> Vec<OpenVolume> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (slot, rest) = line.trim().split_once(": ")?;
+            // Container paths may have spaces; the last two fields don't.
+            let mut fields = rest.rsplitn(3, ' ');
+            let mountpoint = fields.next()?;
+            let device = fields.next()?;
+            let volume = fields.next()?;
+            Some(OpenVolume {
+                slot: slot.parse().ok()?,
+                volume: volume.to_string(),
+                device: device.to_string(),
+                mountpoint: (mountpoint != "-").then(|| PathBuf::from(mountpoint)),
+            })
+        })
+        .collect()
+}
+
+/// Runs `veracrypt`.
+#[derive(Clone)]
+pub struct Veracrypt {
+    exec: Arc<dyn CommandExecutor>,
+}
+
+impl Default for Veracrypt {
+    fn default() -> Self {
+        Veracrypt::with_executor(Arc::new(SystemExecutor))
+    }
+}
+
+impl Veracrypt {
+    /// Uses `exec` to run every external command.
+    pub fn with_executor(exec: Arc<dyn CommandExecutor>) -> Self {
+        Veracrypt { exec }
+    }
+
+    /// The volumes VeraCrypt has open.
+    pub fn list(&self) -> Result<Vec<OpenVolume>> {
+        let output = self.exec.run("veracrypt", &["--text", "--list"]);
+        // It reports having nothing open as an error.
+        if let Ok(output) = &output
+            && !output.success
+            && String::from_utf8_lossy(&output.stderr).contains("No volumes mounted")
+        {
+            return Ok(Vec::new());
+        }
+        let output = check_output("veracrypt", output)?;
+        Ok(parse_list(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Unlocks `volume` (a device node or container file) with `password`
+    /// and mounts it, at `mountpoint` or wherever VeraCrypt picks (e.g.
+    /// `/media/veracrypt1`). Returns the mountpoint.
+    pub fn open(
+        &self,
+        volume: &str,
+        password: &str,
+        mountpoint: Option<&Path>,
+        options: MountOptions,
+    ) -> Result<PathBuf> {
+        let mut args = vec![
+            "--text",
+            "--non-interactive",
+            "--stdin",
+            "--pim=0",
+            "--keyfiles=",
+            "--protect-hidden=no",
+        ];
+        if options.read_only {
+            args.push("--mount-options=ro");
+        }
+        args.push(volume);
+        let target = mountpoint
+            .map(|m| {
+                m.to_str()
+                    .ok_or_else(|| UsbError::Unsupported("Mountpoint is not valid UTF-8".into()))
+            })
+            .transpose()?;
+        args.extend(target);
+        check_output(
+            "veracrypt",
+            self.exec
+                .run_with_stdin("veracrypt", &args, &mut password.as_bytes()),
+        )?;
+        if let Some(mountpoint) = mountpoint {
+            return Ok(mountpoint.to_path_buf());
+        }
+        self.list()?
+            .into_iter()
+            .find(|open| open.volume == volume)
+            .and_then(|open| open.mountpoint)
+            .ok_or_else(|| {
+                UsbError::Unsupported(format!("VeraCrypt opened {} but didn't mount it", volume))
+            })
+    }
+
+    /// Unmounts and locks `volume`.
+    pub fn close(&self, volume: &str) -> Result<()> {
+        run(
+            self.exec.as_ref(),
+            "veracrypt",
+            &["--text", "--non-interactive", "--dismount", volume],
+        )
+        .map(drop)
+    }
+}
@@ -0,0 +1,85 @@
+//! How worn a drive's flash is, and roughly how much more it can take.
+//!
+//! SSDs keep score themselves: NVMe drives report the percentage of their
+//! rated endurance used, SATA SSDs a life-left attribute, and most count
+//! what the host wrote. Flash sticks and SD cards report nothing, so the
+//! bytes lsusb itself has written to them ([`stats`](crate::stats)) are
+//! held against an assumed endurance of [`ASSUMED_DRIVE_FILLS`] full
+//! writes instead, which is as rough as it sounds: it misses whatever
+//! other programs and machines wrote.
+
+use crate::smart::SmartReport;
+
+/// Full-capacity writes cheap TLC flash is assumed to last without SMART
+/// data, allowing for write amplification.
+pub const ASSUMED_DRIVE_FILLS: u64 = 500;
+
+/// Where a [`Wear`] figure comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Basis {
+    /// The drive's own wear indicator.
+    Drive,
+    /// Bytes written held against [`ASSUMED_DRIVE_FILLS`].
+    Assumed,
+}
+
+/// Where the bytes written come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Counter {
+    /// The drive's own count.
+    Drive,
+    /// What lsusb has written; a lower bound.
+    Lsusb,
+}
+
+/// An estimate of a drive's wear.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wear {
+    /// Endurance used, in percent; may pass 100.
+    pub used_percent: f64,
+    pub basis: Basis,
+    /// Bytes written over the drive's life, and by whose count.
+    pub written: Option<(u64, Counter)>,
+    /// Bytes that can still be written before the endurance is used up,
+    /// if that can be told.
+    pub remaining: Option<u64>,
+}
+
+/// The wear of a drive of `capacity` bytes, from its SMART `report` if it
+/// could be read and the `tracked` bytes lsusb has written to it. `None`
+/// when there is nothing to go on.
+pub fn estimate(report: Option<&SmartReport>, tracked: Option<u64>, capacity: u64) -> Option<Wear> {
+    let written = report
+        .and_then(SmartReport::bytes_written)
+        .map(|bytes| (bytes, Counter::Drive))
+        .or(tracked
+            .filter(|&bytes| bytes > 0)
+            .map(|bytes| (bytes, Counter::Lsusb)));
+    if let Some(used) = report.and_then(SmartReport::wear_percent) {
+        // The drive's percentage against its own count of writes gives its
+        // rated endurance; lsusb's count is too partial for that.
+        let remaining = match written {
+            Some((bytes, Counter::Drive)) if used > 0 => {
+                Some((bytes * 100 / used).saturating_sub(bytes))
+            }
+            _ => None,
+        };
+        return Some(Wear {
+            used_percent: used as f64,
+            basis: Basis::Drive,
+            written,
+            remaining,
+        });
+    }
+    let (bytes, _) = written?;
+    if capacity == 0 {
+        return None;
+    }
+    let endurance = capacity.saturating_mul(ASSUMED_DRIVE_FILLS);
+    Some(Wear {
+        used_percent: bytes as f64 * 100.0 / endurance as f64,
+        basis: Basis::Assumed,
+        written,
+        remaining: Some(endurance.saturating_sub(bytes)),
+    })
+}
@@ -0,0 +1,99 @@
+//! How much data the kernel still has to write out to a device, so a front
+//! end can show a flush draining instead of an eject that looks hung.
+//!
+//! Linux counts dirty and in-flight pages per device (per "bdi") in debugfs,
+//! `/sys/kernel/debug/bdi/<major:minor>/stats`, which only root can read.
+//! Everyone else gets the system-wide `Dirty` and `Writeback` from
+//! `/proc/meminfo`: an upper bound, which drains along with the device's
+//! own data when it is the one being written to.
+
+use crate::error::Result;
+use crate::progress::{Phase, ProgressEvent, ProgressSink};
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// How often [`flush_with_progress`] looks at the counters.
+const POLL: Duration = Duration::from_millis(250);
+
+/// Runs `flush` (e.g. `|| backend.flush("sdb")`), reporting the data it
+/// drains from disk `device` to `progress` meanwhile: the totals once, then
+/// byte updates as the pending count falls. Nothing but the phases is
+/// reported when the kernel doesn't say what is pending.
+pub fn flush_with_progress(
+    device: &str,
+    flush: impl FnOnce() -> Result<()>,
+    mut progress: impl ProgressSink + Send,
+) -> Result<()> {
+    let done = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            progress.emit(ProgressEvent::Phase(Phase::Copying));
+            let Some(start) = pending(device) else {
+                return;
+            };
+            let mut total = start;
+            progress.emit(ProgressEvent::Totals {
+                bytes: total,
+                files: 0,
+            });
+            while !done.load(Ordering::Relaxed) {
+                std::thread::sleep(POLL);
+                if let Some(pending) = pending(device) {
+                    // More may have been dirtied since we started counting.
+                    total = total.max(pending);
+                    progress.emit(ProgressEvent::Bytes {
+                        done: total - pending,
+                        total,
+                    });
+                }
+            }
+            progress.emit(ProgressEvent::Bytes { done: total, total });
+        });
+        let result = flush();
+        done.store(true, Ordering::Relaxed);
+        result
+    })?;
+    progress.emit(ProgressEvent::Phase(Phase::Done));
+    Ok(())
+}
+
+/// Bytes waiting to be written to disk `device` (e.g. `sdb`), or `None` if
+/// the kernel doesn't say.
+pub fn pending(device: &str) -> Option<u64> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let dev = fs::read_to_string(format!("/sys/class/block/{}/dev", device)).ok()?;
+    fs::read_to_string(format!("/sys/kernel/debug/bdi/{}/stats", dev.trim()))
+        .ok()
+        .and_then(|stats| parse_bdi_stats(&stats))
+        .or_else(|| parse_meminfo(&fs::read_to_string("/proc/meminfo").ok()?))
+}
+
+/// Dirty plus in-flight bytes from a bdi `stats` file:
+///
+/// ```text
+/// BdiWriteback:          512 kB
+/// BdiReclaimable:      20480 kB
+/// ```
+pub fn parse_bdi_stats(text: &str) -> Option<u64> {
+    Some(kilobytes(text, "BdiWriteback")? + kilobytes(text, "BdiReclaimable")?)
+}
+
+/// System-wide dirty plus in-flight bytes from `/proc/meminfo`.
+pub fn parse_meminfo(text: &str) -> Option<u64> {
+    Some(kilobytes(text, "Dirty")? + kilobytes(text, "Writeback")?)
+}
+
+/// The `key:   N kB` line of `text`, in bytes.
+fn kilobytes(text: &str, key: &str) -> Option<u64> {
+    text.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if name.trim() != key {
+            return None;
+        }
+        let kb: u64 = value.trim().strip_suffix("kB")?.trim().parse().ok()?;
+        Some(kb * 1024)
+    })
+}
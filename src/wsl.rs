@@ -0,0 +1,153 @@
+//! Windows Subsystem for Linux support.
+//!
+//! Under WSL the Linux kernel only sees USB devices that Windows forwards to
+//! it over USB/IP with [usbipd-win](https://github.com/dorssel/usbipd-win).
+//! This module detects WSL and drives `usbipd.exe` through WSL's Windows
+//! interop, so a stick can be attached without leaving the tool.
+
+use crate::backend::run;
+use crate::error::{Result, UsbError};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+const USBIPD: &str = "usbipd.exe";
+
+/// Whether we are running inside WSL (1 or 2).
+pub fn is_wsl() -> bool {
+    if Path::new("/proc/sys/fs/binfmt_misc/WSLInterop").exists() {
+        return true;
+    }
+    fs::read_to_string("/proc/sys/kernel/osrelease")
+        .map(|release| {
+            let release = release.to_lowercase();
+            release.contains("microsoft") || release.contains("wsl")
+        })
+        .unwrap_or(false)
+}
+
+/// Explanation shown when no devices are visible under WSL.
+pub const INVISIBLE_DEVICES_HINT: &str = "Running under WSL: USB devices plugged into Windows are not visible \
+to Linux until they are forwarded with usbipd-win. Run `lsusb wsl list` to see attachable devices and \
+`lsusb wsl attach <BUSID>` to forward one.";
+
+/// A USB device as listed by `usbipd list` on the Windows side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsbipdDevice {
+    /// usbipd bus id, e.g. `1-4`.
+    pub busid: String,
+    /// `VID:PID`, e.g. `0781:5581`.
+    pub vid_pid: String,
+    pub description: String,
+    /// `Not shared`, `Shared` or `Attached`.
+    pub state: String,
+}
+
+impl UsbipdDevice {
+    pub fn is_attached(&self) -> bool {
+        self.state.starts_with("Attached")
+    }
+
+    /// Whether `usbipd bind` (which needs an elevated Windows prompt) has
+    /// been run for the device already.
+    pub fn is_shared(&self) -> bool {
+        !self.state.starts_with("Not shared")
+    }
+}
+
+/// Runs `usbipd.exe` on the Windows host.
+#[derive(Clone)]
+pub struct Usbipd {
+    exec: Arc<dyn CommandExecutor>,
+}
+
+impl Default for Usbipd {
+    fn default() -> Self {
+        Usbipd::with_executor(Arc::new(SystemExecutor))
+    }
+}
+
+impl Usbipd {
+    /// Uses `exec` to run `usbipd.exe`.
+    pub fn with_executor(exec: Arc<dyn CommandExecutor>) -> Self {
+        Usbipd { exec }
+    }
+
+    /// Devices currently connected to the Windows host.
+    pub fn list(&self) -> Result<Vec<UsbipdDevice>> {
+        let output = run(self.exec.as_ref(), USBIPD, &["list"])?;
+        Ok(parse_usbipd_list(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Forwards the device with `busid` into WSL.
+    pub fn attach(&self, busid: &str) -> Result<()> {
+        let device = self
+            .list()?
+            .into_iter()
+            .find(|d| d.busid == busid)
+            .ok_or_else(|| UsbError::DeviceNotFound(busid.to_string()))?;
+        if !device.is_shared() {
+            return Err(UsbError::Unsupported(format!(
+                "{} is not shared yet; run `usbipd bind --busid {}` once in an administrator prompt on Windows",
+                busid, busid
+            )));
+        }
+        run(
+            self.exec.as_ref(),
+            USBIPD,
+            &["attach", "--wsl", "--busid", busid],
+        )
+        .map(|_| ())
+    }
+
+    /// Hands the device with `busid` back to Windows.
+    pub fn detach(&self, busid: &str) -> Result<()> {
+        run(self.exec.as_ref(), USBIPD, &["detach", "--busid", busid]).map(|_| ())
+    }
+}
+
+/// Parses the `Connected:` table of `usbipd list`:
+///
+/// ```text
+/// Connected:
+/// BUSID  VID:PID    DEVICE                          STATE
+/// 1-4    0781:5581  USB Mass Storage Device         Not shared
+/// ```
+///
+/// Columns are located from the header, since descriptions contain spaces.
+pub fn parse_usbipd_list(output: &str) -> Vec<UsbipdDevice> {
+    let mut lines = output
+        .lines()
+        .skip_while(|l| !l.trim_start().starts_with("BUSID"));
+    let Some(header) = lines.next() else {
+        return Vec::new();
+    };
+    let (Some(vid_col), Some(device_col), Some(state_col)) = (
+        header.find("VID:PID"),
+        header.find("DEVICE"),
+        header.find("STATE"),
+    ) else {
+        return Vec::new();
+    };
+
+    lines
+        .take_while(|l| !l.trim().is_empty())
+        .filter_map(|line| {
+            let column = |start: usize, end: Option<usize>| -> Option<String> {
+                let end = end.unwrap_or(line.len()).min(line.len());
+                line.get(start.min(end)..end).map(|s| s.trim().to_string())
+            };
+            let busid = column(0, Some(vid_col))?;
+            if busid.is_empty() {
+                return None;
+            }
+            Some(UsbipdDevice {
+                busid,
+                vid_pid: column(vid_col, Some(device_col))?,
+                description: column(device_col, Some(state_col))?,
+                state: column(state_col, None)?,
+            })
+        })
+        .collect()
+}
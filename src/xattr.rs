@@ -0,0 +1,210 @@
+//! Extended attributes, for `cp --preserve`: user attributes (tags, Finder
+//! info, download origins) and, on Linux, the POSIX ACLs the kernel keeps
+//! as `system.posix_acl_*` attributes.
+//!
+//! FAT and exFAT can store none of these; attributes the destination
+//! refuses are returned to the caller to report rather than failing the
+//! copy. FreeBSD's `extattr` interface isn't supported.
+
+use std::ffi::{CString, OsStr, OsString};
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::Path;
+
+/// The names of `path`'s extended attributes worth copying.
+pub fn list(path: &Path) -> io::Result<Vec<OsString>> {
+    let path = c_path(path.as_os_str())?;
+    let mut buf = Vec::new();
+    loop {
+        let len = sys::list(&path, &mut [])?;
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        buf.resize(len, 0);
+        match sys::list(&path, &mut buf) {
+            Ok(len) => {
+                buf.truncate(len);
+                break;
+            }
+            // Grew in between.
+            Err(e) if e.raw_os_error() == Some(libc::ERANGE) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(buf
+        .split(|&b| b == 0)
+        .filter(|name| !name.is_empty() && copied(name))
+        .map(|name| OsString::from_vec(name.to_vec()))
+        .collect())
+}
+
+/// The value of attribute `name` of `path`.
+pub fn get(path: &Path, name: &OsStr) -> io::Result<Vec<u8>> {
+    let (path, name) = (c_path(path.as_os_str())?, c_path(name)?);
+    loop {
+        let len = sys::get(&path, &name, &mut [])?;
+        let mut value = vec![0; len];
+        match sys::get(&path, &name, &mut value) {
+            Ok(len) => {
+                value.truncate(len);
+                return Ok(value);
+            }
+            Err(e) if e.raw_os_error() == Some(libc::ERANGE) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Sets attribute `name` of `path` to `value`.
+pub fn set(path: &Path, name: &OsStr, value: &[u8]) -> io::Result<()> {
+    let (path, name) = (c_path(path.as_os_str())?, c_path(name)?);
+    sys::set(&path, &name, value)
+}
+
+/// Copies the attributes of `source` to `dest`, returning the names of
+/// those the destination (or this platform) can't store.
+pub fn copy(source: &Path, dest: &Path) -> io::Result<Vec<String>> {
+    let names = match list(source) {
+        Ok(names) => names,
+        Err(e) if unsupported(&e) => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut dropped = Vec::new();
+    for name in names {
+        let value = get(source, &name)?;
+        match set(dest, &name, &value) {
+            Ok(()) => {}
+            Err(e) if unsupported(&e) => dropped.push(name.to_string_lossy().into_owned()),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(dropped)
+}
+
+/// Whether `e` says the filesystem has no (such) attributes.
+pub fn unsupported(e: &io::Error) -> bool {
+    matches!(e.raw_os_error(), Some(code) if code == libc::ENOTSUP || code == libc::EOPNOTSUPP)
+}
+
+/// On Linux, the `trusted` and `security` namespaces need privileges and
+/// belong to the system they were set on; the rest is copied.
+#[cfg(target_os = "linux")]
+fn copied(name: &[u8]) -> bool {
+    name.starts_with(b"user.")
+        || name == b"system.posix_acl_access"
+        || name == b"system.posix_acl_default"
+}
+
+#[cfg(not(target_os = "linux"))]
+fn copied(_name: &[u8]) -> bool {
+    true
+}
+
+fn c_path(path: &OsStr) -> io::Result<CString> {
+    CString::new(path.as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a NUL byte"))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn result(ret: libc::ssize_t) -> io::Result<usize> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret as usize)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use super::result;
+    use std::ffi::CStr;
+    use std::io;
+
+    pub fn list(path: &CStr, buf: &mut [u8]) -> io::Result<usize> {
+        result(unsafe { libc::listxattr(path.as_ptr(), buf.as_mut_ptr().cast(), buf.len()) })
+    }
+
+    pub fn get(path: &CStr, name: &CStr, buf: &mut [u8]) -> io::Result<usize> {
+        result(unsafe {
+            libc::getxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                buf.as_mut_ptr().cast(),
+                buf.len(),
+            )
+        })
+    }
+
+    pub fn set(path: &CStr, name: &CStr, value: &[u8]) -> io::Result<()> {
+        let ret = unsafe {
+            libc::setxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr().cast(),
+                value.len(),
+                0,
+            )
+        };
+        result(ret as libc::ssize_t).map(drop)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod sys {
+    use super::result;
+    use std::ffi::CStr;
+    use std::io;
+
+    pub fn list(path: &CStr, buf: &mut [u8]) -> io::Result<usize> {
+        result(unsafe { libc::listxattr(path.as_ptr(), buf.as_mut_ptr().cast(), buf.len(), 0) })
+    }
+
+    pub fn get(path: &CStr, name: &CStr, buf: &mut [u8]) -> io::Result<usize> {
+        result(unsafe {
+            libc::getxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                buf.as_mut_ptr().cast(),
+                buf.len(),
+                0,
+                0,
+            )
+        })
+    }
+
+    pub fn set(path: &CStr, name: &CStr, value: &[u8]) -> io::Result<()> {
+        let ret = unsafe {
+            libc::setxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr().cast(),
+                value.len(),
+                0,
+                0,
+            )
+        };
+        result(ret as libc::ssize_t).map(drop)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod sys {
+    use std::ffi::CStr;
+    use std::io;
+
+    fn unsupported() -> io::Error {
+        io::Error::from_raw_os_error(libc::EOPNOTSUPP)
+    }
+
+    pub fn list(_path: &CStr, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(unsupported())
+    }
+
+    pub fn get(_path: &CStr, _name: &CStr, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(unsupported())
+    }
+
+    pub fn set(_path: &CStr, _name: &CStr, _value: &[u8]) -> io::Result<()> {
+        Err(unsupported())
+    }
+}
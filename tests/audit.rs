@@ -0,0 +1,53 @@
+use lsusb::audit::{AuditEntry, AuditLog};
+
+fn entry(time: u64, operation: &str) -> AuditEntry {
+    AuditEntry {
+        time,
+        user: "alice".into(),
+        uid: 1000,
+        operation: operation.into(),
+        device: Some("sdb1".into()),
+        serial: Some("4C530001230912104355".into()),
+        params: vec!["--secure".into(), "/media/alice/STICK/payroll".into()],
+    }
+}
+
+#[test]
+fn entries_are_appended_and_read_back_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = AuditLog::new(dir.path().join("state/lsusb/audit.log"));
+
+    log.record(&entry(1_700_000_000, "rm")).unwrap();
+    log.record(&entry(1_700_000_060, "rm")).unwrap();
+
+    let entries = log.entries().unwrap();
+    assert_eq!(
+        entries,
+        [entry(1_700_000_000, "rm"), entry(1_700_000_060, "rm")]
+    );
+    assert_eq!(
+        std::fs::read_to_string(log.path()).unwrap().lines().count(),
+        2
+    );
+}
+
+#[test]
+fn missing_log_has_no_entries() {
+    let dir = tempfile::tempdir().unwrap();
+
+    assert!(
+        AuditLog::new(dir.path().join("audit.log"))
+            .entries()
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn timestamps_are_utc() {
+    assert_eq!(entry(0, "rm").timestamp(), "1970-01-01 00:00:00");
+    assert_eq!(
+        entry(1_709_210_096, "rm").timestamp(),
+        "2024-02-29 12:34:56"
+    );
+}
@@ -0,0 +1,662 @@
+mod common;
+
+use common::{FakeExecutor, fixture};
+use lsusb::backend::LsblkBackend;
+use lsusb::backup::{
+    self, Amount, ChangeKind, Conflict, Filters, Profile, Retention, Sample, Target, Verify,
+};
+use lsusb::progress::ProgressEvent;
+use lsusb::template::Vars;
+use lsusb::{Config, SystemBackend, TransferOptions, UsbDevice};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+fn devices() -> Vec<UsbDevice> {
+    let exec = Arc::new(FakeExecutor::new().succeed("lsblk", &fixture("lsblk_usb_stick.json")));
+    let mut devices = LsblkBackend::with_executor(exec).enumerate().unwrap();
+    devices[0].partitions[1].label = Some("BACKUP".into());
+    devices
+}
+
+fn serial(name: &str) -> Option<String> {
+    (name == "sdb").then(|| "4C530001231119116284".to_string())
+}
+
+fn profile(sources: &[&Path]) -> Profile {
+    Profile {
+        sources: sources.iter().map(|s| s.to_path_buf()).collect(),
+        target: Target::default(),
+        dest: None,
+        include: Vec::new(),
+        exclude: vec!["*.tmp".into(), "node_modules".into()],
+        verify: Verify::Full,
+        snapshots: false,
+        keep: Default::default(),
+        auto: false,
+        chunked: false,
+        mtime_tolerance: None,
+        timezone_shifts: false,
+        sign: None,
+    }
+}
+
+#[test]
+fn profiles_are_read_from_the_config_file() {
+    let config: Config = toml::from_str(
+        r#"
+        [backup.home-docs]
+        sources = ["~/Documents"]
+        target = { serial = "4C530001231119116284" }
+        exclude = ["*.tmp"]
+        verify = "full"
+        "#,
+    )
+    .unwrap();
+
+    let profile = &config.backup["home-docs"];
+    assert_eq!(
+        profile.target.serial.as_deref(),
+        Some("4C530001231119116284")
+    );
+    assert_eq!(profile.verify, Verify::Full);
+    assert_eq!(
+        profile
+            .dest_dir("home-docs", Path::new("/media/stick"), &Vars::now())
+            .unwrap(),
+        Path::new("/media/stick/lsusb-backup/home-docs")
+    );
+}
+
+#[test]
+fn destinations_can_be_templated() {
+    let mut profile = profile(&[Path::new("/tmp")]);
+    profile.dest = Some("backups/{label}/{serial}".into());
+    let vars = Vars::for_drive(Some("STICK".into()), Some("4C53".into()));
+
+    assert_eq!(
+        profile
+            .dest_dir("p", Path::new("/media/stick"), &vars)
+            .unwrap(),
+        Path::new("/media/stick/backups/STICK/4C53")
+    );
+}
+
+#[test]
+fn destinations_outside_the_stick_are_refused() {
+    let mut profile = profile(&[Path::new("/tmp")]);
+    profile.dest = Some("../elsewhere".into());
+
+    assert!(
+        profile
+            .dest_dir("p", Path::new("/media/stick"), &Vars::now())
+            .is_err()
+    );
+}
+
+#[test]
+fn the_target_is_found_by_serial_or_label() {
+    let devices = devices();
+    let by_label = Target {
+        serial: None,
+        label: Some("BACKUP".into()),
+    };
+    let by_serial = Target {
+        serial: Some("4C530001231119116284".into()),
+        label: None,
+    };
+
+    assert_eq!(
+        backup::find_target(&devices, &by_label, serial)
+            .unwrap()
+            .1
+            .name,
+        "sdb2"
+    );
+    // Two partitions and no label: the mounted one.
+    assert_eq!(
+        backup::find_target(&devices, &by_serial, serial)
+            .unwrap()
+            .1
+            .name,
+        "sdb1"
+    );
+    let elsewhere = Target {
+        serial: Some("0000".into()),
+        label: Some("BACKUP".into()),
+    };
+    assert!(backup::find_target(&devices, &elsewhere, serial).is_err());
+    assert!(backup::find_target(&devices, &Target::default(), serial).is_err());
+}
+
+#[test]
+fn filters_match_names_anywhere_and_paths_from_the_source() {
+    let filters = Filters {
+        include: vec!["*.md".into(), "src/**/*.rs".into()],
+        exclude: vec!["draft-*".into()],
+    };
+
+    assert!(filters.includes(Path::new("notes/todo.md")));
+    assert!(filters.includes(Path::new("src/a/b/main.rs")));
+    assert!(filters.includes(Path::new("src/lib.rs")));
+    assert!(!filters.includes(Path::new("lib.rs")));
+    assert!(!filters.includes(Path::new("notes/draft-1.md")));
+    assert!(filters.excludes(Path::new("draft-old")));
+}
+
+#[test]
+fn a_second_run_only_copies_what_changed() {
+    let home = tempfile::tempdir().unwrap();
+    let docs = home.path().join("Documents");
+    fs::create_dir_all(docs.join("node_modules/x")).unwrap();
+    fs::write(docs.join("report.odt"), b"quarterly").unwrap();
+    fs::write(docs.join("scratch.tmp"), b"junk").unwrap();
+    fs::write(docs.join("node_modules/x/index.js"), b"js").unwrap();
+    let single = home.path().join("todo.txt");
+    fs::write(&single, b"milk").unwrap();
+    let stick = tempfile::tempdir().unwrap();
+    let dest = stick.path().join("lsusb-backup/docs");
+    let profile = profile(&[&docs, &single]);
+
+    let first = backup::run(&profile, &dest, &TransferOptions::default(), |_| {}).unwrap();
+
+    assert_eq!(
+        (
+            first.files_copied,
+            first.files_unchanged,
+            first.files_verified
+        ),
+        (2, 0, 2)
+    );
+    assert_eq!(
+        fs::read(dest.join("Documents/report.odt")).unwrap(),
+        b"quarterly"
+    );
+    assert_eq!(fs::read(dest.join("todo.txt")).unwrap(), b"milk");
+    assert!(!dest.join("Documents/scratch.tmp").exists());
+    assert!(!dest.join("Documents/node_modules").exists());
+
+    fs::write(&single, b"milk, eggs").unwrap();
+    let second = backup::run(&profile, &dest, &TransferOptions::default(), |_| {}).unwrap();
+
+    assert_eq!((second.files_copied, second.files_unchanged), (1, 1));
+    assert_eq!(fs::read(dest.join("todo.txt")).unwrap(), b"milk, eggs");
+}
+
+#[test]
+fn snapshots_hardlink_what_did_not_change() {
+    use std::os::unix::fs::MetadataExt;
+
+    let home = tempfile::tempdir().unwrap();
+    let docs = home.path().join("Documents");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(docs.join("report.odt"), b"quarterly").unwrap();
+    fs::write(docs.join("notes.txt"), b"v1").unwrap();
+    let stick = tempfile::tempdir().unwrap();
+    let dest = stick.path().join("lsusb-backup/docs");
+    // A run that was interrupted.
+    fs::create_dir_all(dest.join("2020-01-01_000000.partial/Documents")).unwrap();
+    let mut profile = profile(&[&docs]);
+    profile.snapshots = true;
+
+    let first = backup::run(&profile, &dest, &TransferOptions::default(), |_| {}).unwrap();
+    // Snapshot names have a resolution of one second.
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    fs::write(docs.join("notes.txt"), b"v2, longer").unwrap();
+    let second = backup::run(&profile, &dest, &TransferOptions::default(), |_| {}).unwrap();
+
+    let names = backup::snapshots(&dest).unwrap();
+    assert_eq!(names.len(), 2);
+    assert_eq!(first.dest, dest.join(&names[0]));
+    assert_eq!(second.dest, dest.join(&names[1]));
+    assert!(!dest.join("2020-01-01_000000.partial").exists());
+    assert_eq!((second.files_copied, second.files_unchanged), (1, 1));
+    assert_eq!(
+        fs::read(first.dest.join("Documents/notes.txt")).unwrap(),
+        b"v1"
+    );
+    assert_eq!(
+        fs::read(second.dest.join("Documents/notes.txt")).unwrap(),
+        b"v2, longer"
+    );
+    let inode = |snapshot: &Path| {
+        fs::metadata(snapshot.join("Documents/report.odt"))
+            .unwrap()
+            .ino()
+    };
+    assert_eq!(inode(&first.dest), inode(&second.dest));
+    let report_line = |snapshot: &Path| {
+        let manifest = fs::read_to_string(backup::manifest_path(snapshot)).unwrap();
+        manifest
+            .lines()
+            .find(|l| l.ends_with("report.odt"))
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(report_line(&first.dest), report_line(&second.dest));
+}
+
+#[test]
+fn stray_files_named_like_unfinished_snapshots_are_left_alone() {
+    let home = tempfile::tempdir().unwrap();
+    fs::write(home.path().join("notes.txt"), b"v1").unwrap();
+    let stick = tempfile::tempdir().unwrap();
+    let dest = stick.path().join("lsusb-backup/docs");
+    fs::create_dir_all(&dest).unwrap();
+    fs::write(dest.join("2020-01-01_000000.partial"), b"not a snapshot").unwrap();
+    let mut profile = profile(&[home.path()]);
+    profile.snapshots = true;
+
+    let run = backup::run(&profile, &dest, &TransferOptions::default(), |_| {}).unwrap();
+
+    assert!(dest.join("2020-01-01_000000.partial").is_file());
+    assert!(backup::manifest_path(&run.dest).is_file());
+    let names: Vec<_> = fs::read_dir(&dest)
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    assert!(
+        !names
+            .iter()
+            .any(|name| name.to_string_lossy().ends_with(".tmp"))
+    );
+}
+
+#[test]
+fn snapshot_names_sort_by_time() {
+    let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_792_000_000);
+
+    assert_eq!(backup::snapshot_name(time), "2026-10-14_174640");
+}
+
+#[test]
+fn sources_with_the_same_name_are_refused() {
+    let a = tempfile::tempdir().unwrap();
+    let b = tempfile::tempdir().unwrap();
+    fs::create_dir_all(a.path().join("docs")).unwrap();
+    fs::create_dir_all(b.path().join("docs")).unwrap();
+
+    let profile = profile(&[&a.path().join("docs"), &b.path().join("docs")]);
+
+    assert!(profile.resolved_sources().is_err());
+}
+
+/// A mirror backup of `report.odt` and `notes.txt` on a fresh stick.
+fn backed_up(home: &Path, stick: &Path) -> (Profile, std::path::PathBuf) {
+    let docs = home.join("Documents");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(docs.join("report.odt"), b"quarterly").unwrap();
+    fs::write(docs.join("notes.txt"), b"v1").unwrap();
+    let dest = stick.join("lsusb-backup/docs");
+    let profile = profile(&[&docs]);
+    backup::run(&profile, &dest, &TransferOptions::default(), |_| {}).unwrap();
+    (profile, dest)
+}
+
+#[test]
+fn copies_shifted_by_a_time_zone_count_as_unchanged_only_when_asked() {
+    let home = tempfile::tempdir().unwrap();
+    let stick = tempfile::tempdir().unwrap();
+    let (mut profile, dest) = backed_up(home.path(), stick.path());
+    // As a FAT stick shows its files after a daylight saving change.
+    let copy = dest.join("Documents/notes.txt");
+    let modified = fs::metadata(&copy).unwrap().modified().unwrap();
+    fs::File::options()
+        .write(true)
+        .open(&copy)
+        .unwrap()
+        .set_modified(modified + std::time::Duration::from_secs(3601))
+        .unwrap();
+
+    profile.timezone_shifts = true;
+    let shifted = backup::run(&profile, &dest, &TransferOptions::default(), |_| {}).unwrap();
+    assert_eq!((shifted.files_copied, shifted.files_unchanged), (0, 2));
+
+    profile.timezone_shifts = false;
+    let strict = backup::run(&profile, &dest, &TransferOptions::default(), |_| {}).unwrap();
+    assert_eq!((strict.files_copied, strict.files_unchanged), (1, 1));
+}
+
+#[test]
+fn restore_copies_the_backup_back_and_verifies_it() {
+    let home = tempfile::tempdir().unwrap();
+    let stick = tempfile::tempdir().unwrap();
+    let (profile, dest) = backed_up(home.path(), stick.path());
+    let restored = tempfile::tempdir().unwrap();
+
+    let source = backup::restore_source(&profile, &dest, None).unwrap();
+    let report = backup::restore(
+        &source,
+        restored.path(),
+        Conflict::Skip,
+        Verify::Full,
+        &TransferOptions::default(),
+        |_| {},
+    )
+    .unwrap();
+
+    assert_eq!(
+        (
+            report.files_restored,
+            report.files_verified,
+            report.bytes_restored
+        ),
+        (2, 2, 11)
+    );
+    assert_eq!(
+        fs::read(restored.path().join("Documents/report.odt")).unwrap(),
+        b"quarterly"
+    );
+    assert!(backup::restore_source(&profile, &dest, Some("2020-01-01_000000")).is_err());
+
+    let again = backup::restore(
+        &source,
+        restored.path(),
+        Conflict::Skip,
+        Verify::Full,
+        &TransferOptions::default(),
+        |_| {},
+    )
+    .unwrap();
+    assert_eq!((again.files_restored, again.files_unchanged), (0, 2));
+}
+
+#[test]
+fn restore_handles_files_that_changed_on_the_host() {
+    let home = tempfile::tempdir().unwrap();
+    let stick = tempfile::tempdir().unwrap();
+    let (_, dest) = backed_up(home.path(), stick.path());
+    fs::write(home.path().join("Documents/notes.txt"), b"edited since").unwrap();
+    let notes = home.path().join("Documents/notes.txt");
+    let restore = |conflict| {
+        backup::restore(
+            &dest,
+            home.path(),
+            conflict,
+            Verify::Size,
+            &TransferOptions::default(),
+            |_| {},
+        )
+        .unwrap()
+    };
+
+    let skipped = restore(Conflict::Skip);
+    assert_eq!(skipped.conflicts_skipped, [notes.as_path()]);
+    assert_eq!((skipped.files_restored, skipped.files_unchanged), (0, 1));
+    assert_eq!(fs::read(&notes).unwrap(), b"edited since");
+
+    let kept = restore(Conflict::KeepBoth);
+    let beside = home.path().join("Documents/notes.restored.txt");
+    assert_eq!(kept.conflicts_kept, [beside.as_path()]);
+    assert_eq!(fs::read(&beside).unwrap(), b"v1");
+    assert_eq!(fs::read(&notes).unwrap(), b"edited since");
+
+    let overwritten = restore(Conflict::Overwrite);
+    assert_eq!(overwritten.files_restored, 1);
+    assert_eq!(fs::read(&notes).unwrap(), b"v1");
+    assert_eq!("keep-both".parse::<Conflict>(), Ok(Conflict::KeepBoth));
+}
+
+#[test]
+fn verify_reports_corrupt_and_missing_files() {
+    let home = tempfile::tempdir().unwrap();
+    let stick = tempfile::tempdir().unwrap();
+    let (_, dest) = backed_up(home.path(), stick.path());
+    let verify =
+        |sample| backup::verify(&dest, sample, &TransferOptions::default(), |_| {}).unwrap();
+    let manifest = fs::read_to_string(backup::manifest_path(&dest)).unwrap();
+    assert_eq!(manifest.lines().count(), 2);
+    assert!(manifest.contains("  Documents/notes.txt\n"));
+
+    let clean = verify(None);
+    assert!(clean.is_ok());
+    assert_eq!((clean.files_checked, clean.bytes_checked), (2, 11));
+    let one = Sample {
+        amount: Amount::Files(1),
+        seed: 7,
+    };
+    assert_eq!(verify(Some(one)).files_checked, 1);
+
+    fs::write(dest.join("Documents/report.odt"), b"quarterlx").unwrap();
+    fs::remove_file(dest.join("Documents/notes.txt")).unwrap();
+    let damaged = verify(None);
+    assert_eq!(damaged.corrupt, [Path::new("Documents/report.odt")]);
+    assert_eq!(damaged.missing, [Path::new("Documents/notes.txt")]);
+    assert!(!damaged.is_ok());
+}
+
+#[test]
+fn samples_are_reproducible_from_their_seed() {
+    let home = tempfile::tempdir().unwrap();
+    let stick = tempfile::tempdir().unwrap();
+    let (profile, dest) = backed_up(home.path(), stick.path());
+    for i in 0..40 {
+        fs::write(
+            home.path().join(format!("Documents/extra{}.txt", i)),
+            format!("extra {}", i),
+        )
+        .unwrap();
+    }
+    backup::run(&profile, &dest, &TransferOptions::default(), |_| {}).unwrap();
+    let checked = |seed| {
+        let sample = Sample {
+            amount: Amount::Percent(10.0),
+            seed,
+        };
+        let mut files = Vec::new();
+        backup::verify(&dest, Some(sample), &TransferOptions::default(), |event| {
+            if let ProgressEvent::File { path, .. } = event {
+                files.push(path);
+            }
+        })
+        .unwrap();
+        files
+    };
+    assert_eq!(checked(42).len(), 5);
+    assert_eq!(checked(42), checked(42));
+    assert!((0..8).any(|seed| checked(seed) != checked(42)));
+
+    assert_eq!("5%".parse::<Amount>(), Ok(Amount::Percent(5.0)));
+    assert_eq!("200".parse::<Amount>(), Ok(Amount::Files(200)));
+    assert!("0%".parse::<Amount>().is_err());
+    assert!("150%".parse::<Amount>().is_err());
+    assert_eq!(Amount::Percent(1.0).of(3), 1);
+    assert_eq!(Amount::Percent(100.0).of(3), 3);
+}
+
+#[test]
+fn cached_verify_skips_unchanged_files_but_rereads_changed_ones() {
+    let home = tempfile::tempdir().unwrap();
+    let stick = tempfile::tempdir().unwrap();
+    let (_, dest) = backed_up(home.path(), stick.path());
+    let mut cache = lsusb::hashcache::HashCache::open(home.path().join("hashes.json")).unwrap();
+    let mut verify = || {
+        backup::verify_cached(&dest, None, &mut cache, &TransferOptions::default(), |_| {}).unwrap()
+    };
+
+    let first = verify();
+    assert!(first.is_ok());
+    assert_eq!((first.files_checked, first.files_cached), (2, 0));
+    let second = verify();
+    assert!(second.is_ok());
+    assert_eq!(
+        (
+            second.files_checked,
+            second.files_cached,
+            second.bytes_checked
+        ),
+        (0, 2, 0)
+    );
+
+    fs::write(dest.join("Documents/report.odt"), b"quarterly, edited").unwrap();
+    let third = verify();
+    assert_eq!((third.files_checked, third.files_cached), (1, 1));
+    assert_eq!(third.corrupt, [Path::new("Documents/report.odt")]);
+}
+
+#[test]
+fn snapshots_are_summarised_and_diffed() {
+    let home = tempfile::tempdir().unwrap();
+    let docs = home.path().join("Documents");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(docs.join("report.odt"), b"quarterly").unwrap();
+    fs::write(docs.join("notes.txt"), b"v1").unwrap();
+    let stick = tempfile::tempdir().unwrap();
+    let dest = stick.path().join("lsusb-backup/docs");
+    let mut profile = profile(&[&docs]);
+    profile.snapshots = true;
+
+    let first = backup::run(&profile, &dest, &TransferOptions::default(), |_| {}).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    fs::write(docs.join("notes.txt"), b"v2, longer").unwrap();
+    fs::remove_file(docs.join("report.odt")).unwrap();
+    fs::write(docs.join("plan.md"), b"# plan").unwrap();
+    let second = backup::run(&profile, &dest, &TransferOptions::default(), |_| {}).unwrap();
+
+    let changes = backup::diff(&first.dest, &second.dest).unwrap();
+    let summary: Vec<(&str, ChangeKind, i64)> = changes
+        .iter()
+        .map(|c| (c.path.to_str().unwrap(), c.kind, c.delta()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("Documents/notes.txt", ChangeKind::Changed, 8),
+            ("Documents/plan.md", ChangeKind::Added, 6),
+            ("Documents/report.odt", ChangeKind::Removed, -9),
+        ]
+    );
+
+    let summaries = backup::snapshot_summaries(&dest).unwrap();
+    assert_eq!(summaries.len(), 2);
+    assert_eq!(
+        (summaries[0].files, summaries[0].bytes, summaries[0].added),
+        (2, 11, 2)
+    );
+    let latest = &summaries[1];
+    assert_eq!(
+        (latest.added, latest.removed, latest.changed, latest.delta),
+        (1, 1, 1, 5)
+    );
+}
+
+#[test]
+fn retention_keeps_the_newest_of_each_period() {
+    let names: Vec<String> = [
+        "2026-08-20_120000",
+        "2026-09-30_120000",
+        "2026-10-05_090000",
+        "2026-10-12_080000",
+        "2026-10-13_080000",
+        "2026-10-14_080000",
+        "2026-10-14_200000",
+    ]
+    .map(String::from)
+    .to_vec();
+    let keep: Retention = toml::from_str("last = 1\ndaily = 2\nweekly = 2\nmonthly = 3").unwrap();
+
+    assert_eq!(
+        backup::prune_plan(&names, &keep),
+        ["2026-10-12_080000", "2026-10-14_080000"]
+    );
+    assert!(backup::prune_plan(&names, &Retention::default()).is_empty());
+    let newest_only = Retention {
+        daily: 1,
+        ..Default::default()
+    };
+    assert_eq!(backup::prune_plan(&names, &newest_only), names[..6]);
+}
+
+#[test]
+fn runs_prune_old_snapshots() {
+    let home = tempfile::tempdir().unwrap();
+    fs::write(home.path().join("notes.txt"), b"v1").unwrap();
+    let stick = tempfile::tempdir().unwrap();
+    let dest = stick.path().join("lsusb-backup/notes");
+    fs::create_dir_all(dest.join("2020-01-01_000000")).unwrap();
+    fs::create_dir_all(dest.join("2020-01-02_000000")).unwrap();
+    fs::write(dest.join("2020-01-01_000000.sha256"), "").unwrap();
+    let mut profile = profile(&[&home.path().join("notes.txt")]);
+    profile.snapshots = true;
+    profile.keep.last = 2;
+
+    let report = backup::run(&profile, &dest, &TransferOptions::default(), |_| {}).unwrap();
+
+    assert_eq!(report.pruned, ["2020-01-01_000000"]);
+    assert_eq!(backup::snapshots(&dest).unwrap().len(), 2);
+    assert!(!dest.join("2020-01-01_000000.sha256").exists());
+}
+
+#[test]
+fn auto_backups_wait_for_the_delay_and_run_once_per_plug() {
+    use backup::{AutoBackups, AutoEvent};
+    use std::collections::BTreeMap;
+    use std::time::{Duration, Instant};
+
+    let mut docs = profile(&[Path::new("/tmp")]);
+    docs.target.serial = Some("4C530001231119116284".into());
+    docs.auto = true;
+    let mut manual = docs.clone();
+    manual.auto = false;
+    let profiles = BTreeMap::from([("docs".to_string(), docs), ("manual".to_string(), manual)]);
+    let attached = devices();
+    let mut auto = AutoBackups::new(Duration::from_secs(30));
+    let start = Instant::now();
+    let at = |secs| start + Duration::from_secs(secs);
+
+    assert_eq!(
+        auto.update(&profiles, &attached, serial, at(0)),
+        [AutoEvent::Scheduled {
+            profile: "docs".into(),
+            device: "sdb".into()
+        }]
+    );
+    assert!(auto.update(&profiles, &attached, serial, at(10)).is_empty());
+    assert_eq!(
+        auto.update(&profiles, &attached, serial, at(30)),
+        [AutoEvent::Due {
+            profile: "docs".into(),
+            device: "sdb".into()
+        }]
+    );
+    assert!(
+        auto.update(&profiles, &attached, serial, at(100))
+            .is_empty()
+    );
+
+    // Unplugged and plugged back in, then unplugged during the delay.
+    assert!(auto.update(&profiles, &[], serial, at(200)).is_empty());
+    assert!(matches!(
+        auto.update(&profiles, &attached, serial, at(201))[..],
+        [AutoEvent::Scheduled { .. }]
+    ));
+    assert_eq!(
+        auto.update(&profiles, &[], serial, at(205)),
+        [AutoEvent::Aborted {
+            profile: "docs".into()
+        }]
+    );
+}
+
+#[test]
+fn backups_honour_ignore_files_and_the_global_list() {
+    let home = tempfile::tempdir().unwrap();
+    let docs = home.path().join("Documents");
+    fs::create_dir_all(docs.join("thesis/__pycache__")).unwrap();
+    fs::write(docs.join("thesis/.lsusbignore"), b"*.aux\n").unwrap();
+    fs::write(docs.join("thesis/main.tex"), b"\\begin").unwrap();
+    fs::write(docs.join("thesis/main.aux"), b"aux").unwrap();
+    fs::write(docs.join("thesis/__pycache__/plot.pyc"), b"pyc").unwrap();
+    let stick = tempfile::tempdir().unwrap();
+    let dest = stick.path().join("lsusb-backup/docs");
+    let options = TransferOptions {
+        ignore: lsusb::ignore::Ignore::new(&["__pycache__/".into()]),
+        ..Default::default()
+    };
+
+    backup::run(&profile(&[&docs]), &dest, &options, |_| {}).unwrap();
+
+    assert!(dest.join("Documents/thesis/main.tex").exists());
+    assert!(!dest.join("Documents/thesis/main.aux").exists());
+    assert!(!dest.join("Documents/thesis/__pycache__").exists());
+}
@@ -0,0 +1,96 @@
+use lsusb::UsbError;
+use lsusb::bench::{self, Options, Test};
+use lsusb::cancel::CancelToken;
+use std::fs;
+use std::time::Duration;
+
+fn quick() -> Options {
+    Options {
+        file_size: 2 * 1024 * 1024,
+        queue_depths: vec![1, 4],
+        duration: Duration::from_millis(100),
+        ..Options::default()
+    }
+}
+
+#[test]
+fn every_test_reports_and_the_file_is_removed() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut reported = Vec::new();
+
+    let results = bench::run(dir.path(), &quick(), &CancelToken::new(), |r| {
+        reported.push(r.test)
+    })
+    .unwrap();
+
+    let tests: Vec<Test> = results.iter().map(|r| r.test).collect();
+    assert_eq!(
+        tests,
+        [
+            Test::SequentialWrite,
+            Test::SequentialRead,
+            Test::RandomRead { queue_depth: 1 },
+            Test::RandomWrite { queue_depth: 1 },
+            Test::RandomRead { queue_depth: 4 },
+            Test::RandomWrite { queue_depth: 4 },
+        ]
+    );
+    assert_eq!(reported, tests);
+    assert_eq!(results[0].bytes, 2 * 1024 * 1024);
+    for random in &results[2..] {
+        assert!(random.ops > 0);
+        assert_eq!(random.bytes, random.ops * bench::BLOCK as u64);
+        assert!(random.iops() > 0.0);
+    }
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn random_only_still_writes_the_file_but_reports_just_the_random_tests() {
+    let dir = tempfile::tempdir().unwrap();
+    let options = Options {
+        sequential: false,
+        queue_depths: vec![2],
+        ..quick()
+    };
+
+    let results = bench::run(dir.path(), &options, &CancelToken::new(), |_| {}).unwrap();
+
+    let tests: Vec<Test> = results.iter().map(|r| r.test).collect();
+    assert_eq!(
+        tests,
+        [
+            Test::RandomRead { queue_depth: 2 },
+            Test::RandomWrite { queue_depth: 2 }
+        ]
+    );
+}
+
+#[test]
+fn bad_options_and_cancellation_stop_the_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let tiny = Options {
+        file_size: 1000,
+        ..quick()
+    };
+    assert!(matches!(
+        bench::run(dir.path(), &tiny, &CancelToken::new(), |_| {}),
+        Err(UsbError::Unsupported(_))
+    ));
+    let deep = Options {
+        queue_depths: vec![0],
+        ..quick()
+    };
+    assert!(matches!(
+        bench::run(dir.path(), &deep, &CancelToken::new(), |_| {}),
+        Err(UsbError::Unsupported(_))
+    ));
+
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    assert!(matches!(
+        bench::run(dir.path(), &quick(), &cancel, |_| {}),
+        Err(UsbError::Cancelled)
+    ));
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+}
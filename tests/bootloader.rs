@@ -0,0 +1,95 @@
+mod common;
+
+use common::FakeExecutor;
+use lsusb::bootloader::{BootEntries, Bootloader, Installer, Kernel};
+use std::fs;
+use std::sync::Arc;
+
+fn stick() -> tempfile::TempDir {
+    let stick = tempfile::tempdir().unwrap();
+    fs::create_dir_all(stick.path().join("boot")).unwrap();
+    fs::create_dir_all(stick.path().join("isos")).unwrap();
+    for file in [
+        "boot/vmlinuz-6.1.0-18-amd64",
+        "boot/initrd.img-6.1.0-18-amd64",
+        "vmlinuz-rescue",
+        "isos/ubuntu-24.04-desktop-amd64.iso",
+        "notes.txt",
+    ] {
+        fs::write(stick.path().join(file), b"").unwrap();
+    }
+    stick
+}
+
+#[test]
+fn finds_kernels_with_their_initrds_and_iso_images() {
+    let stick = stick();
+
+    let entries = BootEntries::scan(stick.path());
+
+    assert_eq!(
+        entries.kernels,
+        [
+            Kernel {
+                path: "/vmlinuz-rescue".into(),
+                initrd: None,
+            },
+            Kernel {
+                path: "/boot/vmlinuz-6.1.0-18-amd64".into(),
+                initrd: Some("/boot/initrd.img-6.1.0-18-amd64".into()),
+            },
+        ]
+    );
+    assert_eq!(entries.isos, ["/isos/ubuntu-24.04-desktop-amd64.iso"]);
+
+    let grub = entries.grub_config();
+    assert!(
+        grub.contains("menuentry \"vmlinuz-6.1.0-18-amd64\""),
+        "{grub}"
+    );
+    assert!(
+        grub.contains("    initrd /boot/initrd.img-6.1.0-18-amd64\n"),
+        "{grub}"
+    );
+    assert!(
+        grub.contains("set iso_path=\"/isos/ubuntu-24.04-desktop-amd64.iso\""),
+        "{grub}"
+    );
+    assert!(
+        grub.contains("configfile /boot/grub/loopback.cfg"),
+        "{grub}"
+    );
+    let syslinux = entries.syslinux_config();
+    assert!(syslinux.contains("DEFAULT vmlinuz-rescue\n"), "{syslinux}");
+    assert!(
+        syslinux.contains("LABEL vmlinuz-6.1.0-18-amd64\n    LINUX /boot/vmlinuz-6.1.0-18-amd64\n"),
+        "{syslinux}"
+    );
+    assert!(!syslinux.contains(".iso"), "{syslinux}");
+}
+
+#[test]
+fn grub_is_installed_for_bios_and_uefi_and_the_old_menu_kept() {
+    let stick = stick();
+    fs::create_dir_all(stick.path().join("boot/grub")).unwrap();
+    fs::write(stick.path().join("boot/grub/grub.cfg"), "# hand-written\n").unwrap();
+    let exec = Arc::new(FakeExecutor::new().succeed("grub-install", b""));
+
+    let report = Installer::with_executor(exec.clone())
+        .install(Bootloader::Grub, "sdb", stick.path())
+        .unwrap();
+
+    assert_eq!(report.targets, ["i386-pc", "x86_64-efi"]);
+    let calls = exec.calls();
+    assert_eq!(calls[0].last().map(String::as_str), Some("/dev/sdb"));
+    assert!(calls[1].contains(&"--removable".to_string()));
+    assert!(
+        fs::read_to_string(&report.config)
+            .unwrap()
+            .contains("set iso_path=")
+    );
+    assert_eq!(
+        fs::read_to_string(stick.path().join("boot/grub/grub.cfg.bak")).unwrap(),
+        "# hand-written\n"
+    );
+}
@@ -0,0 +1,223 @@
+use lsusb::bus::{bridge_issue, controllers_in, devices_in, storage_device_in};
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::Path;
+
+fn attrs(dir: &Path, attrs: &[(&str, &str)]) {
+    fs::create_dir_all(dir).unwrap();
+    for (name, value) in attrs {
+        fs::write(dir.join(name), format!("{}\n", value)).unwrap();
+    }
+}
+
+#[test]
+fn composite_devices_list_each_interface_class_and_driver() {
+    let sys = tempfile::tempdir().unwrap();
+    let drivers = sys.path().join("drivers");
+    fs::create_dir_all(drivers.join("uvcvideo")).unwrap();
+    fs::create_dir_all(drivers.join("snd-usb-audio")).unwrap();
+    let root = sys.path().join("devices");
+    attrs(
+        &root.join("usb1"),
+        &[("idVendor", "1d6b"), ("idProduct", "0002")],
+    );
+    let webcam = root.join("1-1.4");
+    attrs(
+        &webcam,
+        &[
+            ("idVendor", "046d"),
+            ("idProduct", "085b"),
+            ("manufacturer", "Logitech"),
+            ("product", "C925e"),
+            ("speed", "480"),
+        ],
+    );
+    for (interface, class, driver) in [
+        ("1-1.4:1.0", "0e", "uvcvideo"),
+        ("1-1.4:1.2", "01", "snd-usb-audio"),
+        ("1-1.4:1.3", "01", "snd-usb-audio"),
+    ] {
+        let number = &interface[interface.len() - 1..];
+        attrs(
+            &webcam.join(interface),
+            &[
+                ("bInterfaceNumber", &format!("0{}", number)),
+                ("bInterfaceClass", class),
+            ],
+        );
+        symlink(drivers.join(driver), webcam.join(interface).join("driver")).unwrap();
+    }
+    let keyboard = root.join("1-2");
+    attrs(&keyboard, &[("idVendor", "04d9"), ("idProduct", "0169")]);
+    attrs(
+        &keyboard.join("1-2:1.0"),
+        &[
+            ("bInterfaceNumber", "00"),
+            ("bInterfaceClass", "03"),
+            ("bInterfaceProtocol", "01"),
+        ],
+    );
+
+    let devices = devices_in(&root).unwrap();
+
+    assert_eq!(devices.len(), 2);
+    let cam = &devices[0];
+    assert_eq!(cam.bus_id, "1-1.4");
+    assert_eq!(cam.vid_pid, "046d:085b");
+    assert_eq!(cam.product.as_deref(), Some("C925e"));
+    assert_eq!(cam.interfaces.len(), 3);
+    assert_eq!(cam.classes(), ["Video", "Audio"]);
+    assert_eq!(cam.drivers(), ["uvcvideo", "snd-usb-audio"]);
+    let kbd = &devices[1];
+    assert_eq!(kbd.classes(), ["HID"]);
+    assert_eq!(kbd.interfaces[0].protocol, 1);
+    assert!(kbd.drivers().is_empty());
+}
+
+#[test]
+fn serial_adapters_map_to_their_tty_nodes() {
+    let sys = tempfile::tempdir().unwrap();
+    let drivers = sys.path().join("drivers");
+    for driver in ["ftdi_sio", "cdc_acm"] {
+        fs::create_dir_all(drivers.join(driver)).unwrap();
+    }
+    let root = sys.path().join("devices");
+    let ftdi = root.join("1-3");
+    attrs(
+        &ftdi,
+        &[
+            ("idVendor", "0403"),
+            ("idProduct", "6001"),
+            ("product", "FT232R USB UART"),
+            ("serial", "A50285BI"),
+        ],
+    );
+    attrs(
+        &ftdi.join("1-3:1.0"),
+        &[("bInterfaceNumber", "00"), ("bInterfaceClass", "ff")],
+    );
+    fs::create_dir(ftdi.join("1-3:1.0/ttyUSB0")).unwrap();
+    symlink(drivers.join("ftdi_sio"), ftdi.join("1-3:1.0/driver")).unwrap();
+    let pico = root.join("1-4");
+    attrs(&pico, &[("idVendor", "2e8a"), ("idProduct", "000a")]);
+    attrs(
+        &pico.join("1-4:1.0"),
+        &[("bInterfaceNumber", "00"), ("bInterfaceClass", "02")],
+    );
+    fs::create_dir_all(pico.join("1-4:1.0/tty/ttyACM0")).unwrap();
+    symlink(drivers.join("cdc_acm"), pico.join("1-4:1.0/driver")).unwrap();
+    attrs(
+        &pico.join("1-4:1.1"),
+        &[("bInterfaceNumber", "01"), ("bInterfaceClass", "0a")],
+    );
+    let by_id = sys.path().join("by-id");
+    fs::create_dir(&by_id).unwrap();
+    let link = by_id.join("usb-FTDI_FT232R_USB_UART_A50285BI-if00-port0");
+    symlink("../../ttyUSB0", &link).unwrap();
+
+    let ports = lsusb::bus::serial_ports_in(&root, &by_id).unwrap();
+
+    assert_eq!(ports.len(), 2);
+    assert_eq!(ports[0].node, Path::new("/dev/ttyACM0"));
+    assert_eq!(ports[0].chip(), Some("CDC-ACM"));
+    assert_eq!(ports[0].by_id, None);
+    assert_eq!(ports[1].node, Path::new("/dev/ttyUSB0"));
+    assert_eq!(ports[1].chip(), Some("FTDI"));
+    assert_eq!(ports[1].device.serial.as_deref(), Some("A50285BI"));
+    assert_eq!(ports[1].by_id.as_deref(), Some(link.as_path()));
+}
+
+#[test]
+fn block_devices_map_to_their_bridge_and_its_storage_driver() {
+    let sys = tempfile::tempdir().unwrap();
+    let uas = sys.path().join("drivers/uas");
+    fs::create_dir_all(&uas).unwrap();
+    let bridge = sys.path().join("devices/pci0000:00/0000:00:14.0/usb2/2-1");
+    attrs(
+        &bridge,
+        &[
+            ("idVendor", "152d"),
+            ("idProduct", "0578"),
+            ("speed", "5000"),
+        ],
+    );
+    let interface = bridge.join("2-1:1.0");
+    attrs(
+        &interface,
+        &[
+            ("bInterfaceNumber", "00"),
+            ("bInterfaceClass", "08"),
+            ("bInterfaceProtocol", "62"),
+        ],
+    );
+    symlink(&uas, interface.join("driver")).unwrap();
+    let disk = interface.join("host6/target6:0:0/6:0:0:0/block/sdb");
+    fs::create_dir_all(&disk).unwrap();
+    let class_block = sys.path().join("class/block");
+    fs::create_dir_all(&class_block).unwrap();
+    symlink(&disk, class_block.join("sdb")).unwrap();
+
+    let device = storage_device_in(&class_block, "sdb").unwrap();
+
+    assert_eq!(device.bus_id, "2-1");
+    assert_eq!(device.vid_pid, "152d:0578");
+    assert_eq!(device.storage_driver(), Some("uas"));
+    assert!(bridge_issue(&device.vid_pid).is_some());
+    assert!(bridge_issue("0781:5581").is_none());
+    assert!(storage_device_in(&class_block, "sdc").is_none());
+}
+
+#[test]
+fn controllers_and_hubs_give_the_port_tree() {
+    let sys = tempfile::tempdir().unwrap();
+    let pci = sys.path().join("devices/pci0000:00/0000:00:14.0");
+    attrs(
+        &pci.join("usb2"),
+        &[
+            ("idVendor", "1d6b"),
+            ("idProduct", "0003"),
+            ("product", "xHCI Host Controller"),
+            ("speed", "5000"),
+        ],
+    );
+    let root = sys.path().join("bus");
+    fs::create_dir_all(&root).unwrap();
+    symlink(pci.join("usb2"), root.join("usb2")).unwrap();
+    for (port, id) in [
+        ("2-1", "05e3:0626"),
+        ("2-1.4", "0781:5581"),
+        ("2-10", "152d:0578"),
+        ("2-2", "174c:55aa"),
+    ] {
+        let (vendor, product) = id.split_once(':').unwrap();
+        attrs(
+            &root.join(port),
+            &[("idVendor", vendor), ("idProduct", product)],
+        );
+    }
+
+    let controllers = controllers_in(&root).unwrap();
+    assert_eq!(controllers.len(), 1);
+    assert_eq!(controllers[0].bus, 2);
+    assert_eq!(controllers[0].parent.as_deref(), Some("0000:00:14.0"));
+    assert_eq!(
+        controllers[0].product.as_deref(),
+        Some("xHCI Host Controller")
+    );
+
+    let mut devices = devices_in(&root).unwrap();
+    devices.sort_by_key(|d| d.ports());
+    let ports: Vec<_> = devices
+        .iter()
+        .map(|d| (d.bus_id.as_str(), d.parent()))
+        .collect();
+    assert_eq!(
+        ports,
+        [
+            ("2-1", None),
+            ("2-1.4", Some("2-1")),
+            ("2-2", None),
+            ("2-10", None)
+        ]
+    );
+}
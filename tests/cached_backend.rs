@@ -0,0 +1,53 @@
+mod common;
+
+use common::{FakeExecutor, fixture};
+use lsusb::SystemBackend;
+use lsusb::backend::{CachedBackend, LsblkBackend};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn cached(ttl: Duration) -> (CachedBackend, Arc<FakeExecutor>) {
+    let exec = Arc::new(
+        FakeExecutor::new()
+            .succeed("lsblk", &fixture("lsblk_usb_stick.json"))
+            .succeed("umount", b""),
+    );
+    let inner = LsblkBackend::with_executor(exec.clone());
+    (CachedBackend::new(Box::new(inner), ttl), exec)
+}
+
+fn lsblk_calls(exec: &FakeExecutor) -> usize {
+    exec.calls().iter().filter(|c| c[0] == "lsblk").count()
+}
+
+#[test]
+fn repeated_enumeration_is_served_from_the_cache() {
+    let (backend, exec) = cached(Duration::from_secs(60));
+
+    let first = backend.enumerate().unwrap();
+    let second = backend.find("sdb").unwrap();
+
+    assert_eq!(first[0].name, second.name);
+    assert_eq!(lsblk_calls(&exec), 1);
+}
+
+#[test]
+fn unmounting_invalidates_the_cache() {
+    let (backend, exec) = cached(Duration::from_secs(60));
+
+    backend.enumerate().unwrap();
+    backend.unmount("/run/media/user/SANDISK").unwrap();
+    backend.enumerate().unwrap();
+
+    assert_eq!(lsblk_calls(&exec), 2);
+}
+
+#[test]
+fn expired_entries_are_refreshed() {
+    let (backend, exec) = cached(Duration::ZERO);
+
+    backend.enumerate().unwrap();
+    backend.enumerate().unwrap();
+
+    assert_eq!(lsblk_calls(&exec), 2);
+}
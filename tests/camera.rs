@@ -0,0 +1,74 @@
+mod common;
+
+use common::{FakeExecutor, fixture};
+use lsusb::camera::{Gphoto2, parse_auto_detect};
+use lsusb::{CancelToken, ProgressEvent};
+use std::sync::Arc;
+
+#[test]
+fn parses_detected_cameras() {
+    let cameras =
+        parse_auto_detect(&String::from_utf8(fixture("gphoto2_auto_detect.txt")).unwrap());
+
+    assert_eq!(cameras.len(), 2);
+    assert_eq!(cameras[0].model, "Canon EOS 80D");
+    assert_eq!(cameras[1].model, "Nikon DSC D750 (PTP mode)");
+    assert_eq!(cameras[1].port, "usb:001,013");
+}
+
+#[test]
+fn import_reports_progress_per_downloaded_file() {
+    let dest = tempfile::tempdir().unwrap();
+    let saved = dest
+        .path()
+        .join("store_00010001/DCIM/100CANON/IMG_0001.JPG");
+    let existing = dest
+        .path()
+        .join("store_00010001/DCIM/100CANON/IMG_0002.CR2");
+    // The fake answers --list-files and --get-all-files alike; each parser
+    // only looks at its own lines.
+    let output = format!(
+        "There are 2 files in folder '/store_00010001/DCIM/100CANON'.\n\
+         #1     IMG_0001.JPG               rd  6117 KB image/jpeg 1700000000\n\
+         #2     IMG_0002.CR2               rd    25 MB image/x-canon-cr2 1700000001\n\
+         Saving file as {}\n\
+         Skip existing file {}\n",
+        saved.display(),
+        existing.display()
+    );
+    let exec = Arc::new(FakeExecutor::new().succeed("gphoto2", output.as_bytes()));
+
+    let mut events = Vec::new();
+    let report = Gphoto2::with_executor(exec.clone())
+        .import("usb:001,012", dest.path(), &CancelToken::new(), |e| {
+            events.push(e)
+        })
+        .unwrap();
+
+    assert_eq!(report.files, 1);
+    assert_eq!(report.skipped, 1);
+    assert_eq!(report.bytes, 6117 * 1024);
+    let total = 6117 * 1024 + 25 * 1024 * 1024;
+    assert!(events.contains(&ProgressEvent::Totals {
+        bytes: total,
+        files: 2
+    }));
+    assert!(events.contains(&ProgressEvent::File {
+        path: saved,
+        size: 6117 * 1024
+    }));
+    assert!(events.contains(&ProgressEvent::Bytes { done: total, total }));
+    let pattern = format!("{}/%F/%f.%C", dest.path().display());
+    assert_eq!(
+        exec.calls()[1],
+        [
+            "gphoto2",
+            "--port",
+            "usb:001,012",
+            "--filename",
+            &pattern,
+            "--skip-existing",
+            "--get-all-files"
+        ]
+    );
+}
@@ -0,0 +1,21 @@
+use lsusb::casefold::{fold, is_case_insensitive};
+
+#[test]
+fn names_differing_in_case_or_normalization_fold_alike() {
+    assert_eq!(fold("IMG_0001.JPG"), fold("img_0001.jpg"));
+    // Precomposed, as typed on Linux, and decomposed, as macOS writes it.
+    assert_eq!(fold("Résumé.pdf"), fold("Re\u{301}sume\u{301}.pdf"));
+    assert_eq!(fold("ÅNGSTRÖM"), fold("ångström"));
+    assert_ne!(fold("resume.pdf"), fold("résumé.pdf"));
+    assert_eq!(fold("ΣΟΦΙΑ"), fold("σοφια"));
+}
+
+#[test]
+fn the_probe_leaves_nothing_behind() {
+    let dir = tempfile::tempdir().unwrap();
+    let insensitive = is_case_insensitive(dir.path()).unwrap();
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+    std::fs::write(dir.path().join("a"), b"").unwrap();
+    assert_eq!(insensitive, dir.path().join("A").exists());
+}
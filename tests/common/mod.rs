@@ -0,0 +1,104 @@
+#![allow(dead_code)]
+
+use lsusb::exec::{CommandExecutor, CommandOutput};
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::sync::Mutex;
+
+/// Replays canned output per program (optionally per program and first
+/// argument) and records every invocation.
+#[derive(Default)]
+pub struct FakeExecutor {
+    responses: HashMap<String, CommandOutput>,
+    pub calls: Mutex<Vec<Vec<String>>>,
+}
+
+impl FakeExecutor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn succeed(mut self, program: &str, stdout: &[u8]) -> Self {
+        self.responses.insert(
+            program.to_string(),
+            CommandOutput {
+                success: true,
+                code: Some(0),
+                stdout: stdout.to_vec(),
+                stderr: Vec::new(),
+            },
+        );
+        self
+    }
+
+    /// Like [`succeed`](Self::succeed), but only when the first argument is
+    /// `verb` (e.g. `diskutil info`).
+    pub fn succeed_verb(self, program: &str, verb: &str, stdout: &[u8]) -> Self {
+        self.succeed(&format!("{} {}", program, verb), stdout)
+    }
+
+    pub fn fail(mut self, program: &str, stderr: &str) -> Self {
+        self.responses.insert(
+            program.to_string(),
+            CommandOutput {
+                success: false,
+                code: Some(1),
+                stdout: Vec::new(),
+                stderr: stderr.as_bytes().to_vec(),
+            },
+        );
+        self
+    }
+
+    /// Exits with `code`, printing `stdout`.
+    pub fn exit(mut self, program: &str, code: i32, stdout: &[u8]) -> Self {
+        self.responses.insert(
+            program.to_string(),
+            CommandOutput {
+                success: code == 0,
+                code: Some(code),
+                stdout: stdout.to_vec(),
+                stderr: Vec::new(),
+            },
+        );
+        self
+    }
+
+    pub fn calls(&self) -> Vec<Vec<String>> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+impl CommandExecutor for FakeExecutor {
+    fn run(&self, program: &str, args: &[&str]) -> io::Result<CommandOutput> {
+        let mut call = vec![program.to_string()];
+        call.extend(args.iter().map(|a| a.to_string()));
+        self.calls.lock().unwrap().push(call);
+
+        let verb_key = args.first().map(|verb| format!("{} {}", program, verb));
+        verb_key
+            .and_then(|key| self.responses.get(&key))
+            .or_else(|| self.responses.get(program))
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No such file or directory"))
+    }
+
+    /// Records stdin as a final pseudo-argument so tests can check it.
+    fn run_with_stdin(
+        &self,
+        program: &str,
+        args: &[&str],
+        input: &mut dyn Read,
+    ) -> io::Result<CommandOutput> {
+        let mut stdin = String::new();
+        input.read_to_string(&mut stdin)?;
+        let mut args = args.to_vec();
+        args.push(&stdin);
+        self.run(program, &args)
+    }
+}
+
+pub fn fixture(name: &str) -> Vec<u8> {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read(&path).unwrap_or_else(|e| panic!("missing fixture {}: {}", path, e))
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use common::FakeExecutor;
+use lsusb::crypt::{Cipher, Crypt, Recipient};
+use lsusb::{TransferOptions, UsbError, copy_to_usb};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+#[test]
+fn parses_recipients() {
+    let recipient: Recipient = "age:age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p"
+        .parse()
+        .unwrap();
+    assert_eq!(recipient.cipher, Cipher::Age);
+    assert_eq!(
+        "gpg:alice@example.org".parse::<Recipient>().unwrap().id,
+        "alice@example.org"
+    );
+    assert!("pgp:alice".parse::<Recipient>().is_err());
+    assert!("age:".parse::<Recipient>().is_err());
+}
+
+#[test]
+fn encrypting_copies_through_age_with_a_suffix() {
+    let src = tempfile::tempdir().unwrap();
+    let dest = tempfile::tempdir().unwrap();
+    fs::create_dir(src.path().join("taxes")).unwrap();
+    fs::write(src.path().join("taxes/2025.pdf"), "%PDF").unwrap();
+    let exec = Arc::new(FakeExecutor::new().succeed("age", b""));
+    let options = TransferOptions {
+        crypt: Some(Crypt::encrypt("age:age1xyz".parse().unwrap()).with_executor(exec.clone())),
+        ..Default::default()
+    };
+
+    let report = copy_to_usb(&src.path().join("taxes"), dest.path(), &options).unwrap();
+
+    assert_eq!(report.files_copied, 1);
+    assert_eq!(report.bytes_copied, 4);
+    let input = src.path().join("taxes/2025.pdf");
+    let output = dest.path().join("taxes/2025.pdf.age");
+    assert_eq!(
+        exec.calls(),
+        [[
+            "age",
+            "--encrypt",
+            "--recipient",
+            "age1xyz",
+            "--output",
+            output.to_str().unwrap(),
+            input.to_str().unwrap()
+        ]]
+    );
+}
+
+#[test]
+fn decrypting_strips_the_suffix_and_copies_plain_files() {
+    let src = tempfile::tempdir().unwrap();
+    let dest = tempfile::tempdir().unwrap();
+    fs::write(src.path().join("notes.txt.gpg"), "-----BEGIN PGP").unwrap();
+    fs::write(src.path().join("README"), "plain").unwrap();
+    let exec = Arc::new(FakeExecutor::new().succeed("gpg", b""));
+    let options = TransferOptions {
+        crypt: Some(Crypt::decrypt(None).with_executor(exec.clone())),
+        ..Default::default()
+    };
+
+    let report = copy_to_usb(src.path(), &dest.path().join("out"), &options).unwrap();
+
+    assert_eq!(report.files_copied, 2);
+    assert_eq!(
+        fs::read_to_string(dest.path().join("out/README")).unwrap(),
+        "plain"
+    );
+    let calls = exec.calls();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0][..4], ["gpg", "--yes", "--decrypt", "--output"]);
+    assert_eq!(Path::new(&calls[0][4]), dest.path().join("out/notes.txt"));
+}
+
+#[test]
+fn decrypting_age_needs_an_identity() {
+    let src = tempfile::tempdir().unwrap();
+    let dest = tempfile::tempdir().unwrap();
+    fs::write(src.path().join("key.age"), "age-encryption.org/v1").unwrap();
+    let exec = Arc::new(FakeExecutor::new().succeed("age", b""));
+    let options = TransferOptions {
+        crypt: Some(Crypt::decrypt(None).with_executor(exec.clone())),
+        ..Default::default()
+    };
+
+    let err = copy_to_usb(&src.path().join("key.age"), dest.path(), &options).unwrap_err();
+
+    assert!(matches!(err, UsbError::Unsupported(_)), "{err}");
+    assert!(exec.calls().is_empty());
+}
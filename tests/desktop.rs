@@ -0,0 +1,60 @@
+mod common;
+
+use common::FakeExecutor;
+use lsusb::UsbError;
+use lsusb::desktop::FileManager;
+use std::sync::Arc;
+
+#[test]
+#[cfg(not(target_os = "macos"))]
+fn opens_directories_with_xdg_open_by_default() {
+    let stick = tempfile::tempdir().unwrap();
+    let exec = Arc::new(FakeExecutor::new().succeed("xdg-open", b""));
+
+    FileManager::with_executor(None, exec.clone())
+        .open(stick.path())
+        .unwrap();
+
+    assert_eq!(
+        exec.calls(),
+        vec![vec![
+            "xdg-open".to_string(),
+            stick.path().display().to_string()
+        ]]
+    );
+}
+
+#[test]
+fn runs_the_configured_file_manager() {
+    let stick = tempfile::tempdir().unwrap();
+    let exec = Arc::new(FakeExecutor::new().succeed("nautilus", b""));
+    let command = vec!["nautilus".to_string(), "--new-window".to_string()];
+
+    FileManager::with_executor(Some(command), exec.clone())
+        .open(stick.path())
+        .unwrap();
+
+    assert_eq!(
+        exec.calls(),
+        vec![vec![
+            "nautilus".to_string(),
+            "--new-window".to_string(),
+            stick.path().display().to_string()
+        ]]
+    );
+}
+
+#[test]
+fn refuses_what_is_not_a_directory() {
+    let stick = tempfile::tempdir().unwrap();
+    let file = stick.path().join("notes.txt");
+    std::fs::write(&file, b"hi").unwrap();
+    let exec = Arc::new(FakeExecutor::new());
+
+    let err = FileManager::with_executor(None, exec.clone())
+        .open(&file)
+        .unwrap_err();
+
+    assert!(matches!(err, UsbError::Unsupported(_)));
+    assert!(exec.calls().is_empty());
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use common::{FakeExecutor, fixture};
+use lsusb::dfu::{Dfu, DfuMode, DownloadOptions, Selector, parse_list};
+use lsusb::{ProgressEvent, UsbError};
+use std::fs;
+use std::sync::Arc;
+
+#[test]
+fn lists_runtime_and_dfu_mode_interfaces() {
+    let devices = parse_list(&String::from_utf8(fixture("dfu_util_list.txt")).unwrap());
+
+    assert_eq!(devices.len(), 3);
+    assert_eq!(devices[0].vid_pid, "1d50:6089");
+    assert_eq!(devices[0].mode, DfuMode::Runtime);
+    assert_eq!(devices[0].name, None);
+    assert_eq!(devices[2].mode, DfuMode::Dfu);
+    assert_eq!(devices[2].path, "1-4");
+    assert_eq!(devices[2].alt, 0);
+    assert_eq!(
+        devices[2].name.as_deref(),
+        Some("@Internal Flash  /0x08000000/064*0002Kg")
+    );
+    assert_eq!(devices[2].serial.as_deref(), Some("3276365A3236"));
+}
+
+#[test]
+fn several_alternate_settings_need_an_explicit_alt() {
+    let devices = parse_list(&String::from_utf8(fixture("dfu_util_list.txt")).unwrap());
+    let stm32 = Selector {
+        vid_pid: Some("0483:DF11".into()),
+        ..Selector::default()
+    };
+
+    let err = stm32.select(&devices).unwrap_err();
+    assert!(err.to_string().contains("--alt"), "{err}");
+
+    let flash = Selector {
+        alt: Some(0),
+        ..stm32
+    };
+    assert_eq!(
+        flash.select(&devices).unwrap().name.as_deref(),
+        Some("@Internal Flash  /0x08000000/064*0002Kg")
+    );
+    assert!(matches!(
+        Selector::default().select(&[]),
+        Err(UsbError::DeviceNotFound(_))
+    ));
+}
+
+#[test]
+fn download_follows_dfu_utils_progress_bar() {
+    let dir = tempfile::tempdir().unwrap();
+    let firmware = dir.path().join("app.bin");
+    fs::write(&firmware, vec![0u8; 23456]).unwrap();
+    let output = b"Opening DFU capable USB device...\n\
+        Erase   \t[=========================] 100%        23456 bytes\n\
+        Download\t[==========               ]  40%         9384 bytes\r\
+        Download\t[=========================] 100%        23456 bytes\n\
+        Download done.\nFile downloaded successfully\n";
+    let exec = Arc::new(FakeExecutor::new().succeed("dfu-util", output));
+    let devices = parse_list(&String::from_utf8(fixture("dfu_util_list.txt")).unwrap());
+    let options = DownloadOptions {
+        reset: false,
+        dfuse_address: Some("0x08000000:leave".into()),
+    };
+
+    let mut events = Vec::new();
+    Dfu::with_executor(exec.clone())
+        .download(&devices[2], &firmware, &options, |e| events.push(e))
+        .unwrap();
+
+    assert!(events.contains(&ProgressEvent::Bytes {
+        done: 9384,
+        total: 23456
+    }));
+    assert!(events.contains(&ProgressEvent::Bytes {
+        done: 23456,
+        total: 23456
+    }));
+    let args = &exec.calls()[0];
+    assert_eq!(
+        args[1..7],
+        ["--device", "0483:df11", "--path", "1-4", "--alt", "0"].map(String::from)
+    );
+    assert!(args.ends_with(&[
+        "--dfuse-address".to_string(),
+        "0x08000000:leave".to_string()
+    ]));
+}
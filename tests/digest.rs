@@ -0,0 +1,30 @@
+use lsusb::digest::{Sha256, hex, sha256};
+
+#[test]
+fn sha256_matches_the_published_test_vectors() {
+    assert_eq!(
+        hex(&sha256(b"")),
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+    assert_eq!(
+        hex(&sha256(b"abc")),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_eq!(
+        hex(&sha256(
+            b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+        )),
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+    );
+
+    // A million 'a's, fed in uneven pieces.
+    let mut hash = Sha256::new();
+    let data = vec![b'a'; 1_000_000];
+    for piece in data.chunks(4099) {
+        hash.update(piece);
+    }
+    assert_eq!(
+        hex(&hash.finish()),
+        "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+    );
+}
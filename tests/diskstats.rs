@@ -0,0 +1,52 @@
+use lsusb::diskstats::{Activity, IoMeter, parse};
+use std::time::{Duration, Instant};
+
+const BEFORE: &str = "\
+   8       0 sda 90211 1204 4581230 38812 70112 55230 9912334 91230 0 81234 130042
+   8      16 sdb 1204 0 98312 811 3311 0 2097152 40213 2 40872 41024
+   8      17 sdb1 1100 0 96000 790 3311 0 2097152 40213 2 40850 41003
+";
+
+const AFTER: &str = "\
+   8       0 sda 90211 1204 4581230 38812 70112 55230 9912334 91230 0 81234 130042
+   8      16 sdb 1204 0 98312 811 3411 0 2138112 40913 0 41872 41924
+";
+
+#[test]
+fn diskstats_are_parsed_into_byte_counts() {
+    let stats = parse(BEFORE);
+
+    assert_eq!(stats.len(), 3);
+    assert_eq!(stats["sdb"].bytes_read, 98312 * 512);
+    assert_eq!(stats["sdb"].bytes_written, 2097152 * 512);
+    assert_eq!(stats["sdb"].in_flight, 2);
+}
+
+#[test]
+fn two_readings_give_rates() {
+    let start = Instant::now();
+    let mut meter = IoMeter::default();
+
+    let first = meter.update(parse(BEFORE), start);
+    assert_eq!(
+        first["sdb"],
+        Activity {
+            read_rate: 0,
+            write_rate: 0,
+            in_flight: 2
+        }
+    );
+
+    let second = meter.update(parse(AFTER), start + Duration::from_secs(2));
+    // 40960 sectors written in two seconds.
+    assert_eq!(
+        second["sdb"],
+        Activity {
+            read_rate: 0,
+            write_rate: 40960 * 512 / 2,
+            in_flight: 0
+        }
+    );
+    assert!(second["sdb"].is_busy());
+    assert!(!second["sda"].is_busy());
+}
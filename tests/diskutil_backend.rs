@@ -0,0 +1,45 @@
+#![cfg(target_os = "macos")]
+
+mod common;
+
+use common::{FakeExecutor, fixture};
+use lsusb::backend::DiskutilBackend;
+use lsusb::{SystemBackend, UsbError};
+use std::sync::Arc;
+
+#[test]
+fn enumerate_reads_diskutil_plists() {
+    let exec = Arc::new(
+        FakeExecutor::new()
+            .succeed_verb("diskutil", "list", &fixture("diskutil_list_external.plist"))
+            .succeed_verb("diskutil", "info", &fixture("diskutil_info_disk4.plist")),
+    );
+    let backend = DiskutilBackend::with_executor(exec);
+
+    let devices = backend.enumerate().unwrap();
+
+    assert_eq!(devices.len(), 1);
+    assert_eq!(devices[0].name, "disk4");
+    assert_eq!(devices[0].model.as_deref(), Some("SanDisk Ultra Media"));
+    assert_eq!(devices[0].partitions[0].name, "disk4s1");
+    assert_eq!(
+        devices[0].partitions[0].mountpoint.as_deref(),
+        Some("/Volumes/SANDISK")
+    );
+}
+
+#[test]
+fn unmount_reports_dissenting_processes() {
+    let exec = Arc::new(FakeExecutor::new().fail(
+        "diskutil",
+        "Volume SANDISK on disk4s1 failed to unmount: dissented by PID 412 (/usr/bin/vim)",
+    ));
+    let backend = DiskutilBackend::with_executor(exec);
+
+    let err = backend.unmount("/Volumes/SANDISK").unwrap_err();
+
+    assert!(
+        matches!(err, UsbError::MountBusy { ref pids, .. } if pids == &[412]),
+        "{err}"
+    );
+}
@@ -0,0 +1,68 @@
+use lsusb::CancelToken;
+use lsusb::erase::secure_delete;
+use std::fs::{self, File};
+use std::io::Read;
+
+#[test]
+fn secure_delete_zeros_contents_before_unlinking() {
+    let dir = tempfile::tempdir().unwrap();
+    let secret = dir.path().join("secret.txt");
+    fs::write(&secret, "hunter2").unwrap();
+    // An open handle keeps the inode alive and shows what was left in it.
+    let mut witness = File::open(&secret).unwrap();
+
+    let report = secure_delete(&secret, &CancelToken::default()).unwrap();
+
+    assert_eq!(report.files, 1);
+    assert_eq!(report.bytes, 7);
+    assert!(!secret.exists());
+    let mut left = Vec::new();
+    witness.read_to_end(&mut left).unwrap();
+    assert_eq!(left, [0; 7]);
+}
+
+#[test]
+fn secure_delete_only_unlinks_hard_linked_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let old = dir.path().join("2025-01-01_000000/notes.txt");
+    let new = dir.path().join("2025-02-01_000000/notes.txt");
+    fs::create_dir_all(old.parent().unwrap()).unwrap();
+    fs::create_dir_all(new.parent().unwrap()).unwrap();
+    fs::write(&old, "keep me").unwrap();
+    fs::hard_link(&old, &new).unwrap();
+
+    let report = secure_delete(old.parent().unwrap(), &CancelToken::default()).unwrap();
+
+    assert_eq!((report.files, report.bytes, report.shared), (1, 0, 1));
+    assert!(!old.exists());
+    assert_eq!(fs::read_to_string(&new).unwrap(), "keep me");
+}
+
+#[test]
+fn secure_delete_removes_directory_trees() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("payroll");
+    fs::create_dir_all(root.join("2025/q1")).unwrap();
+    fs::write(root.join("2025/q1/jan.csv"), "a,b").unwrap();
+    fs::write(root.join("summary.pdf"), "%PDF-1.7").unwrap();
+
+    let report = secure_delete(&root, &CancelToken::default()).unwrap();
+
+    assert_eq!(report.files, 2);
+    assert_eq!(report.bytes, 11);
+    assert!(!root.exists());
+}
+
+#[test]
+fn secure_delete_stops_when_cancelled() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a"), "x").unwrap();
+    let cancel = CancelToken::default();
+    cancel.cancel();
+
+    assert!(matches!(
+        secure_delete(dir.path(), &cancel),
+        Err(lsusb::UsbError::Cancelled)
+    ));
+    assert!(dir.path().join("a").exists());
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use common::{FakeExecutor, fixture};
+use lsusb::SystemBackend;
+use lsusb::backend::LsblkBackend;
+use lsusb::format::{self, Filesystem, Formatter, Options};
+use std::fs;
+use std::sync::Arc;
+
+const GIB: u64 = 1024 * 1024 * 1024;
+const STICK_BYTES: u64 = 16_008_609_792;
+
+#[test]
+fn the_partition_starts_and_ends_on_the_alignment() {
+    let plan = format::plan(STICK_BYTES, &Options::new(Filesystem::Exfat)).unwrap();
+    assert_eq!(
+        plan.script,
+        "label: dos\nunit: sectors\n\nstart=2048, size=31264768, type=7\n"
+    );
+    assert_eq!(plan.cluster, 32 * 1024);
+
+    let options = Options {
+        align: 4 * 1024 * 1024,
+        ..Options::new(Filesystem::Fat32)
+    };
+    let plan = format::plan(STICK_BYTES, &options).unwrap();
+    assert!(
+        plan.script.contains("start=8192, size=31252480, type=c"),
+        "{}",
+        plan.script
+    );
+    assert_eq!(plan.bytes % (4 * 1024 * 1024), 0);
+
+    let odd = Options {
+        align: 3 * 1024 * 1024,
+        ..Options::new(Filesystem::Fat32)
+    };
+    assert!(format::plan(STICK_BYTES, &odd).is_err());
+}
+
+#[test]
+fn disks_past_what_mbr_can_address_get_gpt() {
+    let disk = 4 * 1024 * GIB;
+    let plan = format::plan(disk, &Options::new(Filesystem::Exfat)).unwrap();
+    // All of it but the backup GPT at the end, down to the last whole MiB.
+    let size = (disk / 512 - 33 - 2048) / 2048 * 2048;
+    assert_eq!(
+        plan.script,
+        format!(
+            "label: gpt\nunit: sectors\n\nstart=2048, size={}, type=EBD0A0A2-B9E5-4433-87C0-68B6B72699C7\n",
+            size
+        )
+    );
+    assert_eq!(plan.bytes, size * 512);
+    assert!(plan.bytes > 2 * 1024 * GIB);
+}
+
+#[test]
+fn defaults_follow_the_size_of_the_media() {
+    assert_eq!(Filesystem::default_for(16 * GIB), Filesystem::Fat32);
+    assert_eq!(Filesystem::default_for(64 * GIB), Filesystem::Exfat);
+    assert_eq!(Filesystem::Fat32.default_cluster(4 * GIB), 4096);
+    assert_eq!(Filesystem::Fat32.default_cluster(30 * GIB), 16 * 1024);
+    assert_eq!(Filesystem::Exfat.default_cluster(128 * 1024 * 1024), 4096);
+    assert_eq!(Filesystem::Exfat.default_cluster(256 * GIB), 128 * 1024);
+}
+
+#[test]
+fn cluster_sizes_the_filesystem_cant_have_are_refused() {
+    for (filesystem, cluster) in [
+        (Filesystem::Fat32, 128 * 1024),
+        (Filesystem::Ext4, 64 * 1024),
+        (Filesystem::Exfat, 3000),
+    ] {
+        let options = Options {
+            cluster: Some(cluster),
+            ..Options::new(filesystem)
+        };
+        assert!(
+            format::plan(STICK_BYTES, &options).is_err(),
+            "{} {}",
+            filesystem,
+            cluster
+        );
+    }
+}
+
+#[test]
+fn mkfs_gets_the_cluster_size_in_its_own_terms() {
+    let options = Options {
+        label: Some("PHOTOS".into()),
+        ..Options::new(Filesystem::Fat32)
+    };
+    assert_eq!(
+        format::mkfs_command("/dev/sdb1", &options, 32 * 1024),
+        (
+            "mkfs.vfat",
+            ["-F", "32", "-s", "64", "-n", "PHOTOS", "/dev/sdb1"]
+                .map(String::from)
+                .to_vec()
+        )
+    );
+    let (program, args) =
+        format::mkfs_command("/dev/sdb1", &Options::new(Filesystem::Exfat), 128 * 1024);
+    assert_eq!(
+        (program, args),
+        (
+            "mkfs.exfat",
+            ["-c", "131072", "/dev/sdb1"].map(String::from).to_vec()
+        )
+    );
+}
+
+#[test]
+fn format_partitions_then_makes_the_filesystem() {
+    let exec = Arc::new(
+        ["sfdisk", "blockdev", "udevadm", "mkfs.exfat"]
+            .into_iter()
+            .fold(FakeExecutor::new(), |exec, program| {
+                exec.succeed(program, b"")
+            })
+            .succeed("lsblk", &fixture("lsblk_usb_stick.json")),
+    );
+    let backend = LsblkBackend::with_executor(exec.clone());
+    let mut device = backend.find("sdb").unwrap();
+    let formatter = || {
+        let dev = tempfile::tempdir().unwrap();
+        fs::write(dev.path().join("sdb1"), b"").unwrap();
+        (
+            Formatter::with_executor(exec.clone()).dev_dir(dev.path().to_path_buf()),
+            dev,
+        )
+    };
+    assert!(
+        formatter()
+            .0
+            .format(&device, &Options::new(Filesystem::Exfat))
+            .is_err()
+    );
+
+    for partition in &mut device.partitions {
+        partition.mountpoint = None;
+    }
+    let (formatter, _dev) = formatter();
+    let (partition, plan) = formatter
+        .format(&device, &Options::new(Filesystem::Exfat))
+        .unwrap();
+
+    assert_eq!((partition.as_str(), plan.cluster), ("sdb1", 32 * 1024));
+    let calls = exec.calls();
+    let sfdisk = calls.iter().find(|c| c[0] == "sfdisk").unwrap();
+    assert_eq!(sfdisk[1..4], ["--wipe", "always", "/dev/sdb"]);
+    assert_eq!(
+        calls.last().unwrap(),
+        &["mkfs.exfat", "-c", "32768", "/dev/sdb1"]
+    );
+}
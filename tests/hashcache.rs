@@ -0,0 +1,45 @@
+use lsusb::hashcache::HashCache;
+use std::fs;
+
+#[test]
+fn hashes_survive_a_reload_until_the_file_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("photo.jpg");
+    fs::write(&file, b"pixels").unwrap();
+    let path = dir.path().join("state/hashes.json");
+
+    let mut cache = HashCache::open(&path).unwrap();
+    let metadata = fs::metadata(&file).unwrap();
+    assert_eq!(cache.get(&file, &metadata), None);
+    cache.insert(&file, &metadata, "ab".repeat(32));
+    cache.save().unwrap();
+
+    let reloaded = HashCache::open(&path).unwrap();
+    assert_eq!(
+        reloaded.get(&file, &metadata),
+        Some("ab".repeat(32).as_str())
+    );
+
+    fs::write(&file, b"other pixels").unwrap();
+    assert_eq!(reloaded.get(&file, &fs::metadata(&file).unwrap()), None);
+}
+
+#[test]
+fn saving_keeps_what_another_process_added() {
+    let dir = tempfile::tempdir().unwrap();
+    let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+    fs::write(&a, b"a").unwrap();
+    fs::write(&b, b"b").unwrap();
+    let path = dir.path().join("hashes.json");
+
+    let mut first = HashCache::open(&path).unwrap();
+    let mut second = HashCache::open(&path).unwrap();
+    first.insert(&a, &fs::metadata(&a).unwrap(), "1".into());
+    second.insert(&b, &fs::metadata(&b).unwrap(), "2".into());
+    first.save().unwrap();
+    second.save().unwrap();
+
+    let merged = HashCache::open(&path).unwrap();
+    assert_eq!(merged.get(&a, &fs::metadata(&a).unwrap()), Some("1"));
+    assert_eq!(merged.get(&b, &fs::metadata(&b).unwrap()), Some("2"));
+}
@@ -0,0 +1,45 @@
+use lsusb::hexdump::{format, read_range};
+use lsusb::parse_size;
+use std::fs;
+
+#[test]
+fn formats_like_hexdump_c_and_collapses_repeated_lines() {
+    let mut data = b"\xeb\x3c\x90mkfs.fat\x00\x02\x04\x01\x00".to_vec();
+    data.extend([0u8; 64]);
+    data.extend(b"\x55\xaa");
+
+    let dump = format(&data, 0x1b0);
+
+    assert_eq!(
+        dump,
+        "000001b0  eb 3c 90 6d 6b 66 73 2e  66 61 74 00 02 04 01 00  |.<.mkfs.fat.....|\n\
+         000001c0  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|\n\
+         *\n\
+         00000200  55 aa                                             |U.|\n\
+         00000202\n"
+    );
+}
+
+#[test]
+fn reads_the_requested_range_stopping_at_the_end() {
+    let dir = tempfile::tempdir().unwrap();
+    let image = dir.path().join("disk.img");
+    fs::write(&image, (0..=255u8).collect::<Vec<_>>()).unwrap();
+
+    assert_eq!(
+        read_range(&image, 0x10, 4).unwrap(),
+        [0x10, 0x11, 0x12, 0x13]
+    );
+    assert_eq!(read_range(&image, 250, 512).unwrap().len(), 6);
+}
+
+#[test]
+fn sizes_accept_hex_and_binary_suffixes() {
+    assert_eq!(parse_size("512"), Some(512));
+    assert_eq!(parse_size("0x1be"), Some(0x1be));
+    assert_eq!(parse_size("4K"), Some(4096));
+    assert_eq!(parse_size("1MiB"), Some(1 << 20));
+    assert_eq!(parse_size("2gb"), Some(2 << 30));
+    assert_eq!(parse_size("ten"), None);
+    assert_eq!(parse_size("5X"), None);
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use common::{FakeExecutor, fixture};
+use lsusb::SystemBackend;
+use lsusb::backend::{HidingBackend, LsblkBackend, hides};
+use lsusb::policy::DeviceIdentity;
+use std::sync::Arc;
+
+fn dongle() -> DeviceIdentity {
+    DeviceIdentity {
+        vendor_id: "0781".into(),
+        product_id: "5581".into(),
+        serial: Some("4C530001230912104355".into()),
+        bytes: 32_000_000_000,
+    }
+}
+
+#[test]
+fn hide_patterns_match_ids_or_serials() {
+    assert!(hides("0781:5581", &dongle()));
+    assert!(hides("0781:*", &dongle()));
+    assert!(hides("4C530001230912104355", &dongle()));
+    assert!(!hides("0781:5580", &dongle()));
+    assert!(!hides("0781", &dongle()));
+}
+
+#[test]
+fn hidden_devices_are_left_out_of_listings_but_still_found_by_name() {
+    let exec = Arc::new(FakeExecutor::new().succeed("lsblk", &fixture("lsblk_usb_stick.json")));
+    let inner = LsblkBackend::with_executor(exec);
+    let backend = HidingBackend::with_identify(Box::new(inner), vec!["0781:*".into()], |name| {
+        (name == "sdb").then(dongle)
+    });
+
+    assert!(backend.enumerate().unwrap().iter().all(|d| d.name != "sdb"));
+    assert_eq!(backend.find("sdb").unwrap().name, "sdb");
+}
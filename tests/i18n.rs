@@ -0,0 +1,56 @@
+use lsusb::i18n::{FluentArgs, LOCALES, Translations, language_of};
+
+#[test]
+fn locale_names_reduce_to_their_language() {
+    assert_eq!(language_of("de_DE.UTF-8"), "de");
+    assert_eq!(language_of("pt_BR.UTF-8@euro"), "pt");
+    assert_eq!(language_of("de:en"), "de");
+    assert_eq!(language_of("C"), "en");
+    assert_eq!(language_of("POSIX"), "en");
+}
+
+#[test]
+fn messages_are_translated_with_english_behind_them() {
+    let german = Translations::new("de");
+    assert_eq!(german.language(), "de");
+    assert_eq!(
+        german.message("no-usb-devices", None),
+        "Keine USB-Geräte gefunden."
+    );
+    let mut args = FluentArgs::new();
+    args.set("mountpoint", "/media/usb");
+    assert_eq!(
+        german.message("unmounting", Some(&args)),
+        "/media/usb wird ausgehängt..."
+    );
+
+    let unknown = Translations::new("xx");
+    assert_eq!(unknown.language(), "en");
+    assert_eq!(
+        unknown.message("no-usb-devices", None),
+        "No USB devices found."
+    );
+    assert_eq!(unknown.message("no-such-message", None), "no-such-message");
+}
+
+#[test]
+fn translations_only_use_keys_english_has() {
+    let keys = |source: &str| -> Vec<String> {
+        source
+            .lines()
+            .filter(|line| !line.starts_with(['#', ' ']) && line.contains(" = "))
+            .map(|line| line.split(" = ").next().unwrap().to_string())
+            .collect()
+    };
+    let english = keys(LOCALES[0].1);
+    for (language, source) in &LOCALES[1..] {
+        for key in keys(source) {
+            assert!(
+                english.contains(&key),
+                "{} has {}, which English doesn't",
+                language,
+                key
+            );
+        }
+    }
+}
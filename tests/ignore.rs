@@ -0,0 +1,30 @@
+use lsusb::ignore::Ignore;
+use std::path::Path;
+
+#[test]
+fn patterns_follow_gitignore_rules() {
+    let mut ignore = Ignore::new(&["*.pyc".into()]);
+    ignore.add(
+        Path::new(""),
+        "# build output\ntarget/\n/dist\n*.log\n!keep.log\n\\#notes\nsrc/**/*.o\n",
+    );
+    ignore.add(Path::new("web"), "node_modules/\n/cache\n");
+    let ignored = |path: &str, is_dir| ignore.is_ignored(Path::new(path), is_dir);
+
+    assert!(ignored("app/main.pyc", false));
+    assert!(ignored("target", true));
+    assert!(ignored("crates/x/target", true));
+    assert!(!ignored("target", false));
+    assert!(ignored("dist", true));
+    assert!(!ignored("docs/dist", true));
+    assert!(ignored("logs/run.log", false));
+    assert!(!ignored("logs/keep.log", false));
+    assert!(ignored("#notes", false));
+    assert!(ignored("src/a/b/x.o", false));
+    assert!(!ignored("lib/x.o", false));
+    assert!(ignored("web/node_modules", true));
+    assert!(!ignored("node_modules", true));
+    assert!(ignored("web/cache", true));
+    assert!(!ignored("web/src/cache", true));
+    assert!(!Ignore::none().is_ignored(Path::new("target"), true));
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::{FakeExecutor, fixture};
+use lsusb::MountOptions;
+use lsusb::ios::Ios;
+use std::sync::Arc;
+
+#[test]
+fn devices_carry_model_details() {
+    let exec = Arc::new(
+        FakeExecutor::new()
+            .succeed("idevice_id", b"00008110-001A2B3C4D5E801E\n")
+            .succeed("ideviceinfo", &fixture("ideviceinfo_iphone.txt")),
+    );
+
+    let devices = Ios::with_executor(exec.clone()).devices().unwrap();
+
+    assert_eq!(devices.len(), 1);
+    assert_eq!(devices[0].name.as_deref(), Some("Alice's iPhone"));
+    assert_eq!(devices[0].class.as_deref(), Some("iPhone"));
+    assert_eq!(devices[0].product_type.as_deref(), Some("iPhone14,5"));
+    assert_eq!(devices[0].version.as_deref(), Some("17.4.1"));
+    assert_eq!(
+        exec.calls()[1],
+        ["ideviceinfo", "-u", "00008110-001A2B3C4D5E801E"]
+    );
+}
+
+#[test]
+fn untrusted_devices_are_listed_without_details() {
+    let exec = Arc::new(
+        FakeExecutor::new()
+            .succeed("idevice_id", b"00008110-001A2B3C4D5E801E\n")
+            .fail(
+                "ideviceinfo",
+                "ERROR: Could not connect to lockdownd: Pairing dialog response pending (-19)",
+            ),
+    );
+
+    let devices = Ios::with_executor(exec).devices().unwrap();
+
+    assert_eq!(devices.len(), 1);
+    assert!(!devices[0].is_paired());
+}
+
+#[test]
+fn mount_runs_ifuse_for_the_device() {
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("iphone");
+    let exec = Arc::new(FakeExecutor::new().succeed("ifuse", b""));
+
+    Ios::with_executor(exec.clone())
+        .mount(
+            "00008110-001A2B3C4D5E801E",
+            &target,
+            MountOptions { read_only: true },
+        )
+        .unwrap();
+
+    assert!(target.is_dir());
+    assert_eq!(
+        exec.calls(),
+        [[
+            "ifuse",
+            target.to_str().unwrap(),
+            "--udid",
+            "00008110-001A2B3C4D5E801E",
+            "-o",
+            "ro"
+        ]]
+    );
+}
@@ -0,0 +1,468 @@
+use lsusb::UsbError;
+use lsusb::iso::{Format, Image};
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+const SECTOR: usize = 2048;
+
+fn put(image: &mut Vec<u8>, offset: usize, bytes: &[u8]) {
+    if image.len() < offset + bytes.len() {
+        image.resize((offset + bytes.len()).div_ceil(SECTOR) * SECTOR, 0);
+    }
+    image[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+fn both_endian32(value: u32) -> Vec<u8> {
+    [value.to_le_bytes(), value.to_be_bytes()].concat()
+}
+
+/// An ISO 9660 directory record.
+fn record(name: &[u8], sector: u32, len: u32, flags: u8, system_use: &[u8]) -> Vec<u8> {
+    let mut record = vec![0u8; 33];
+    record[2..10].copy_from_slice(&both_endian32(sector));
+    record[10..18].copy_from_slice(&both_endian32(len));
+    record[25] = flags;
+    record[32] = name.len() as u8;
+    record.extend_from_slice(name);
+    if name.len().is_multiple_of(2) {
+        record.push(0);
+    }
+    record.extend_from_slice(system_use);
+    if !record.len().is_multiple_of(2) {
+        record.push(0);
+    }
+    record[0] = record.len() as u8;
+    record
+}
+
+fn directory(records: &[Vec<u8>]) -> Vec<u8> {
+    records.concat()
+}
+
+fn descriptor(kind: u8, label: &[u8], root: &[u8], escape: &[u8]) -> Vec<u8> {
+    let mut descriptor = vec![0u8; SECTOR];
+    descriptor[0] = kind;
+    descriptor[1..6].copy_from_slice(b"CD001");
+    descriptor[6] = 1;
+    for pad in descriptor[40..72].chunks_mut(2) {
+        pad.copy_from_slice(if kind == 2 { &[0, b' '] } else { b"  " });
+    }
+    descriptor[40..40 + label.len()].copy_from_slice(label);
+    descriptor[88..88 + escape.len()].copy_from_slice(escape);
+    descriptor[156..156 + root.len()].copy_from_slice(root);
+    descriptor
+}
+
+fn ucs2(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(u16::to_be_bytes).collect()
+}
+
+fn rock_ridge_nm(name: &str) -> Vec<u8> {
+    let mut entry = vec![b'N', b'M', (5 + name.len()) as u8, 1, 0];
+    entry.extend_from_slice(name.as_bytes());
+    entry
+}
+
+/// An image with `README.TXT` and `BOOT/GRUB.CFG` (the latter split into
+/// two extents), a Joliet tree naming them `readme.txt` and
+/// `boot/grub.cfg` when `joliet`, and Rock Ridge names `ReadMe.txt` and
+/// `boot/grub.cfg` when `rock_ridge`.
+fn iso9660(joliet: bool, rock_ridge: bool) -> Vec<u8> {
+    let mut image = vec![0u8; 16 * SECTOR];
+    let readme = b"Hello from the image\n";
+    let grub = [vec![b'a'; SECTOR], vec![b'b'; 100]];
+    // Sectors: 16 primary, 17 Joliet, 18 terminator, 20 root, 21 BOOT,
+    // 22 README, 23-24 GRUB.CFG, 25 Joliet root, 26 Joliet boot.
+    let su = |name: &str| {
+        if rock_ridge {
+            rock_ridge_nm(name)
+        } else {
+            Vec::new()
+        }
+    };
+    let sp = if rock_ridge {
+        vec![b'S', b'P', 7, 1, 0xbe, 0xef, 0]
+    } else {
+        Vec::new()
+    };
+    let root = directory(&[
+        record(&[0], 20, SECTOR as u32, 2, &sp),
+        record(&[1], 20, SECTOR as u32, 2, &[]),
+        record(b"BOOT", 21, SECTOR as u32, 2, &su("boot")),
+        record(
+            b"README.TXT;1",
+            22,
+            readme.len() as u32,
+            0,
+            &su("ReadMe.txt"),
+        ),
+    ]);
+    let boot = directory(&[
+        record(&[0], 21, SECTOR as u32, 2, &[]),
+        record(&[1], 20, SECTOR as u32, 2, &[]),
+        record(b"GRUB.CFG;1", 23, SECTOR as u32, 0x80, &su("grub.cfg")),
+        record(b"GRUB.CFG;1", 24, 100, 0, &su("grub.cfg")),
+    ]);
+    put(
+        &mut image,
+        16 * SECTOR,
+        &descriptor(
+            1,
+            b"INSTALLER",
+            &record(&[0], 20, SECTOR as u32, 2, &[]),
+            &[],
+        ),
+    );
+    put(&mut image, 18 * SECTOR, &descriptor(255, b"", &[], &[]));
+    put(&mut image, 20 * SECTOR, &root);
+    put(&mut image, 21 * SECTOR, &boot);
+    put(&mut image, 22 * SECTOR, readme);
+    put(&mut image, 23 * SECTOR, &grub[0]);
+    put(&mut image, 24 * SECTOR, &grub[1]);
+    if joliet {
+        let root = directory(&[
+            record(&[0], 25, SECTOR as u32, 2, &[]),
+            record(&[1], 25, SECTOR as u32, 2, &[]),
+            record(&ucs2("boot"), 26, SECTOR as u32, 2, &[]),
+            record(&ucs2("readme.txt;1"), 22, readme.len() as u32, 0, &[]),
+        ]);
+        let boot = directory(&[
+            record(&[0], 26, SECTOR as u32, 2, &[]),
+            record(&[1], 25, SECTOR as u32, 2, &[]),
+            record(&ucs2("grub.cfg;1"), 23, SECTOR as u32, 0x80, &[]),
+            record(&ucs2("grub.cfg;1"), 24, 100, 0, &[]),
+        ]);
+        let label = ucs2("Installer");
+        put(
+            &mut image,
+            17 * SECTOR,
+            &descriptor(2, &label, &record(&[0], 25, SECTOR as u32, 2, &[]), b"%/E"),
+        );
+        put(&mut image, 25 * SECTOR, &root);
+        put(&mut image, 26 * SECTOR, &boot);
+    } else {
+        put(&mut image, 17 * SECTOR, &descriptor(255, b"", &[], &[]));
+    }
+    image
+}
+
+fn paths(image: &mut Image<Cursor<Vec<u8>>>) -> Vec<String> {
+    let root = image.root().clone();
+    image
+        .walk(&root)
+        .unwrap()
+        .iter()
+        .map(|e| e.path.display().to_string())
+        .collect()
+}
+
+fn contents(image: &mut Image<Cursor<Vec<u8>>>, path: &str) -> Vec<u8> {
+    let entry = image.find(Path::new(path)).unwrap();
+    let mut out = Vec::new();
+    assert_eq!(image.read_to(&entry, &mut out).unwrap(), entry.size);
+    out
+}
+
+#[test]
+fn plain_iso9660_names_lose_their_version() {
+    let mut image = Image::open(Cursor::new(iso9660(false, false))).unwrap();
+    assert_eq!(image.format(), Format::Iso9660);
+    assert_eq!(image.label(), Some("INSTALLER"));
+    assert_eq!(
+        paths(&mut image),
+        ["", "BOOT", "BOOT/GRUB.CFG", "README.TXT"]
+    );
+    assert_eq!(
+        contents(&mut image, "/README.TXT"),
+        b"Hello from the image\n"
+    );
+}
+
+#[test]
+fn joliet_and_rock_ridge_names_are_preferred() {
+    let mut image = Image::open(Cursor::new(iso9660(true, false))).unwrap();
+    assert_eq!(image.format(), Format::Joliet);
+    assert_eq!(image.label(), Some("Installer"));
+    assert_eq!(
+        paths(&mut image),
+        ["", "boot", "boot/grub.cfg", "readme.txt"]
+    );
+
+    let mut image = Image::open(Cursor::new(iso9660(true, true))).unwrap();
+    assert_eq!(image.format(), Format::RockRidge);
+    assert_eq!(
+        paths(&mut image),
+        ["", "ReadMe.txt", "boot", "boot/grub.cfg"]
+    );
+}
+
+#[test]
+fn files_in_several_extents_are_joined() {
+    let mut image = Image::open(Cursor::new(iso9660(true, false))).unwrap();
+    let grub = contents(&mut image, "boot/grub.cfg");
+    assert_eq!(grub.len(), SECTOR + 100);
+    assert!(grub[..SECTOR].iter().all(|&b| b == b'a'));
+    assert!(grub[SECTOR..].iter().all(|&b| b == b'b'));
+}
+
+fn tag(id: u16, location: u32, body: &mut [u8]) {
+    body[0..2].copy_from_slice(&id.to_le_bytes());
+    body[2..4].copy_from_slice(&2u16.to_le_bytes());
+    body[12..16].copy_from_slice(&location.to_le_bytes());
+    let sum = body[..16]
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i != 4)
+        .fold(0u8, |s, (_, &b)| s.wrapping_add(b));
+    body[4] = sum;
+}
+
+fn long_ad(len: u32, block: u32) -> Vec<u8> {
+    [
+        len.to_le_bytes().to_vec(),
+        block.to_le_bytes().to_vec(),
+        vec![0; 8],
+    ]
+    .concat()
+}
+
+fn short_ad(len: u32, kind: u32, block: u32) -> Vec<u8> {
+    [(len | kind << 30).to_le_bytes(), block.to_le_bytes()].concat()
+}
+
+fn fid(name: &str, characteristics: u8, icb: u32) -> Vec<u8> {
+    let name: Vec<u8> = if name.is_empty() {
+        Vec::new()
+    } else {
+        [&[8u8][..], name.as_bytes()].concat()
+    };
+    let mut fid = vec![0u8; 38];
+    fid[16] = 1;
+    fid[18] = characteristics;
+    fid[19] = name.len() as u8;
+    fid[20..36].copy_from_slice(&long_ad(SECTOR as u32, icb));
+    fid.extend_from_slice(&name);
+    fid.resize(fid.len().div_ceil(4) * 4, 0);
+    tag(257, 0, &mut fid);
+    fid
+}
+
+/// A (non-extended, unless `extended`) file entry of `file_type` holding
+/// `size` bytes, described by `ads` of allocation type `ad_type`.
+fn file_entry(file_type: u8, size: u64, ad_type: u16, ads: &[u8], extended: bool) -> Vec<u8> {
+    let (id, start) = if extended { (266, 216) } else { (261, 176) };
+    let mut entry = vec![0u8; SECTOR];
+    entry[27] = file_type;
+    entry[34..36].copy_from_slice(&ad_type.to_le_bytes());
+    entry[56..64].copy_from_slice(&size.to_le_bytes());
+    entry[start - 4..start].copy_from_slice(&(ads.len() as u32).to_le_bytes());
+    entry[start..start + ads.len()].copy_from_slice(ads);
+    tag(id, 0, &mut entry);
+    entry
+}
+
+/// A UDF image holding `hello.txt` (stored in its file entry) and
+/// `docs/big.bin`: 3000 bytes in two blocks, then 100 unrecorded ones.
+fn udf() -> Vec<u8> {
+    const PARTITION: usize = 64 * SECTOR;
+    let block = |n: usize| PARTITION + n * SECTOR;
+    let mut image = vec![0u8; 16 * SECTOR];
+
+    let mut anchor = vec![0u8; SECTOR];
+    anchor[16..20].copy_from_slice(&(16 * SECTOR as u32).to_le_bytes());
+    anchor[20..24].copy_from_slice(&32u32.to_le_bytes());
+    tag(2, 256, &mut anchor);
+    put(&mut image, 256 * SECTOR, &anchor);
+
+    let mut partition = vec![0u8; SECTOR];
+    partition[188..192].copy_from_slice(&64u32.to_le_bytes());
+    partition[192..196].copy_from_slice(&100u32.to_le_bytes());
+    tag(5, 32, &mut partition);
+    put(&mut image, 32 * SECTOR, &partition);
+
+    let mut volume = vec![0u8; SECTOR];
+    let label = b"\x08WIN_INSTALL";
+    volume[84..84 + label.len()].copy_from_slice(label);
+    volume[211] = label.len() as u8;
+    volume[212..216].copy_from_slice(&(SECTOR as u32).to_le_bytes());
+    volume[248..264].copy_from_slice(&long_ad(SECTOR as u32, 0));
+    volume[268..272].copy_from_slice(&1u32.to_le_bytes());
+    volume[440..446].copy_from_slice(&[1, 6, 1, 0, 0, 0]);
+    tag(6, 33, &mut volume);
+    put(&mut image, 33 * SECTOR, &volume);
+    let mut end = vec![0u8; SECTOR];
+    tag(8, 34, &mut end);
+    put(&mut image, 34 * SECTOR, &end);
+
+    let mut file_set = vec![0u8; SECTOR];
+    file_set[400..416].copy_from_slice(&long_ad(SECTOR as u32, 1));
+    tag(256, 0, &mut file_set);
+    put(&mut image, block(0), &file_set);
+
+    let root = [
+        fid("", 0x0a, 1),
+        fid("hello.txt", 0, 3),
+        fid("docs", 0x02, 5),
+    ]
+    .concat();
+    put(
+        &mut image,
+        block(1),
+        &file_entry(
+            4,
+            root.len() as u64,
+            0,
+            &short_ad(root.len() as u32, 0, 2),
+            false,
+        ),
+    );
+    put(&mut image, block(2), &root);
+    put(
+        &mut image,
+        block(3),
+        &file_entry(5, 11, 3, b"hello, udf\n", false),
+    );
+
+    let docs = [fid("", 0x0a, 1), fid("big.bin", 0, 8)].concat();
+    put(
+        &mut image,
+        block(5),
+        &file_entry(
+            4,
+            docs.len() as u64,
+            0,
+            &short_ad(docs.len() as u32, 0, 7),
+            true,
+        ),
+    );
+    put(&mut image, block(7), &docs);
+    let ads = [
+        long_ad(3000, 9),
+        short_ad(100, 1, 0)[..4].to_vec(),
+        vec![0; 12],
+    ]
+    .concat();
+    put(&mut image, block(8), &file_entry(5, 3100, 1, &ads, false));
+    put(&mut image, block(9), &[b'x'; 3000]);
+    put(&mut image, block(12), &[0]);
+    image
+}
+
+#[test]
+fn udf_files_are_read_inline_from_extents_and_as_zeros() {
+    let mut image = Image::open(Cursor::new(udf())).unwrap();
+    assert_eq!(image.format(), Format::Udf);
+    assert_eq!(image.label(), Some("WIN_INSTALL"));
+    assert_eq!(paths(&mut image), ["", "docs", "docs/big.bin", "hello.txt"]);
+    assert_eq!(contents(&mut image, "hello.txt"), b"hello, udf\n");
+    let big = contents(&mut image, "docs/big.bin");
+    assert_eq!(big.len(), 3100);
+    assert!(big[..3000].iter().all(|&b| b == b'x'));
+    assert!(big[3000..].iter().all(|&b| b == 0));
+}
+
+#[test]
+fn extracting_a_directory_recreates_it_under_the_destination() {
+    let dest = tempfile::tempdir().unwrap();
+    let mut image = Image::open(Cursor::new(iso9660(true, false))).unwrap();
+    let boot = image.find(Path::new("boot")).unwrap();
+    assert_eq!(
+        image.extract(&boot, dest.path()).unwrap(),
+        (1, SECTOR as u64 + 100)
+    );
+    assert_eq!(
+        fs::metadata(dest.path().join("boot/grub.cfg"))
+            .unwrap()
+            .len(),
+        SECTOR as u64 + 100
+    );
+    assert!(!dest.path().join("readme.txt").exists());
+}
+
+#[test]
+fn missing_paths_and_other_files_are_errors() {
+    let mut image = Image::open(Cursor::new(iso9660(false, false))).unwrap();
+    assert!(matches!(
+        image.find(Path::new("EFI/BOOT")),
+        Err(UsbError::Unsupported(_))
+    ));
+    assert!(matches!(
+        image.find(Path::new("README.TXT/x")),
+        Err(UsbError::Unsupported(_))
+    ));
+    assert!(matches!(
+        Image::open(Cursor::new(vec![0u8; 40 * SECTOR])),
+        Err(UsbError::Parse { .. })
+    ));
+}
+
+/// [`udf`] with its logical volume descriptor changed by `change`.
+fn udf_volume(change: impl FnOnce(&mut [u8])) -> Vec<u8> {
+    let mut data = udf();
+    let volume = &mut data[33 * SECTOR..34 * SECTOR];
+    change(volume);
+    tag(6, 33, volume);
+    data
+}
+
+/// [`udf`] with `docs/big.bin` described by an allocation extent
+/// descriptor at block 13, reached through a continuation of `len` bytes.
+fn udf_continued(len: u32) -> Vec<u8> {
+    const PARTITION: usize = 64 * SECTOR;
+    let mut data = udf();
+    put(
+        &mut data,
+        PARTITION + 8 * SECTOR,
+        &file_entry(5, 3000, 0, &short_ad(len, 3, 13), false),
+    );
+    let mut extent = vec![0u8; SECTOR];
+    extent[20..24].copy_from_slice(&8u32.to_le_bytes());
+    extent[24..32].copy_from_slice(&short_ad(3000, 0, 9));
+    tag(258, 13, &mut extent);
+    put(&mut data, PARTITION + 13 * SECTOR, &extent);
+    data
+}
+
+#[test]
+fn corrupt_udf_structures_are_errors() {
+    // A continuation claiming a gigabyte is read as one block.
+    let mut image = Image::open(Cursor::new(udf_continued(0x3fff_ffff))).unwrap();
+    let big = contents(&mut image, "docs/big.bin");
+    assert!(big.len() == 3000 && big.iter().all(|&b| b == b'x'));
+
+    let mut truncated = udf();
+    truncated.truncate(64 * SECTOR + 6 * SECTOR);
+    let corrupt = [
+        (
+            "allocation extent shorter than its header",
+            udf_continued(16),
+        ),
+        (
+            "partition map cut short",
+            udf_volume(|v| v[440..446].copy_from_slice(&[1, 2, 0, 0, 0, 0])),
+        ),
+        (
+            "partition map at the very end",
+            // Seven maps fill the descriptor but for the first two bytes of an eighth.
+            udf_volume(|v| {
+                v[268..272].copy_from_slice(&8u32.to_le_bytes());
+                let mut at = 440;
+                for len in [255, 255, 255, 255, 255, 255, 76, 2] {
+                    v[at..at + 2].copy_from_slice(&[1, len]);
+                    at += usize::from(len);
+                }
+            }),
+        ),
+        (
+            "huge logical blocks",
+            udf_volume(|v| v[212..216].copy_from_slice(&0x8000_0000u32.to_le_bytes())),
+        ),
+        ("truncated image", truncated),
+    ];
+    for (what, data) in corrupt {
+        let result = Image::open(Cursor::new(data))
+            .and_then(|mut image| image.find(Path::new("docs/big.bin")));
+        assert!(matches!(result, Err(UsbError::Parse { .. })), "{}", what);
+    }
+}
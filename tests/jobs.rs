@@ -0,0 +1,103 @@
+use lsusb::jobs;
+use lsusb::{CancelToken, UsbError};
+use std::path::Path;
+use std::time::Duration;
+
+#[test]
+fn jobs_are_found_by_mountpoint_until_they_end() {
+    let stick = tempfile::tempdir().unwrap();
+    let other = tempfile::tempdir().unwrap();
+    let cancel = CancelToken::new();
+    let job = jobs::start(
+        "copy",
+        &[Path::new("/home/user/photos"), &stick.path().join("photos")],
+        Some(&cancel),
+    );
+
+    let using = jobs::using(stick.path());
+    assert_eq!(using.len(), 1);
+    assert_eq!(using[0].kind, "copy");
+    assert!(jobs::using(other.path()).is_empty());
+    assert!(matches!(
+        jobs::ensure_idle(stick.path()),
+        Err(UsbError::JobRunning { .. })
+    ));
+    assert!(!jobs::wait(stick.path(), Some(Duration::from_millis(10))));
+
+    assert_eq!(jobs::cancel(stick.path()), 1);
+    assert!(cancel.is_cancelled());
+    let waiter = std::thread::spawn({
+        let stick = stick.path().to_path_buf();
+        move || jobs::wait(&stick, Some(Duration::from_secs(10)))
+    });
+    drop(job);
+    assert!(waiter.join().unwrap());
+    assert!(jobs::ensure_idle(stick.path()).is_ok());
+}
+
+#[test]
+fn copies_register_while_they_run() {
+    let src = tempfile::tempdir().unwrap();
+    let dst = tempfile::tempdir().unwrap();
+    std::fs::write(src.path().join("a.txt"), b"hello").unwrap();
+    let mut seen = Vec::new();
+
+    lsusb::copy_with_progress(src.path(), dst.path(), &Default::default(), |_| {
+        seen.push(jobs::using(dst.path()).len());
+    })
+    .unwrap();
+
+    assert!(seen.iter().all(|&n| n == 1));
+    assert!(jobs::using(dst.path()).is_empty());
+}
+
+#[test]
+fn other_processes_list_and_cancel_jobs_through_the_socket() {
+    let sockets_dir = tempfile::tempdir().unwrap();
+    let sockets = jobs::JobSockets::in_dir(sockets_dir.path().join("sockets"));
+    sockets.publish();
+    let stick = tempfile::tempdir().unwrap();
+    let cancel = CancelToken::new();
+    let job = jobs::start("copy", &[stick.path()], Some(&cancel));
+    let mut progress = job.track(lsusb::progress::NoProgress);
+    lsusb::ProgressSink::emit(
+        &mut progress,
+        lsusb::ProgressEvent::Bytes {
+            done: 512,
+            total: 2048,
+        },
+    );
+    let flash = jobs::start("flash", &[Path::new("/tmp/firmware.bin")], None);
+
+    let listed = sockets.list().unwrap();
+    let ours: Vec<_> = listed
+        .iter()
+        .filter(|remote| remote.pid == std::process::id())
+        .collect();
+    let copy = ours
+        .iter()
+        .find(|remote| remote.job.kind == "copy")
+        .unwrap();
+    assert_eq!((copy.job.bytes_done, copy.job.bytes_total), (512, 2048));
+    assert!(copy.job.cancellable);
+    let flashing = ours
+        .iter()
+        .find(|remote| remote.job.kind == "flash")
+        .unwrap();
+    assert!(!flashing.job.cancellable);
+
+    assert!(!sockets.cancel(std::process::id(), flashing.job.id).unwrap());
+    assert!(sockets.cancel(std::process::id(), copy.job.id).unwrap());
+    assert!(cancel.is_cancelled());
+    // Nobody listens on a socket left by a process that is gone.
+    std::fs::write(sockets_dir.path().join("sockets/999999999.sock"), b"").unwrap();
+    assert!(
+        sockets
+            .list()
+            .unwrap()
+            .iter()
+            .all(|remote| remote.pid != 999999999)
+    );
+    assert!(!sockets_dir.path().join("sockets/999999999.sock").exists());
+    drop((job, flash));
+}
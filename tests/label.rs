@@ -0,0 +1,52 @@
+mod common;
+
+use common::FakeExecutor;
+use lsusb::UsbError;
+use lsusb::label::{Labeler, normalize, relabels_mounted};
+use std::sync::Arc;
+
+#[test]
+fn fat_labels_are_short_plain_and_upper_case() {
+    assert_eq!(normalize("vfat", "Camera").unwrap(), "CAMERA");
+    assert_eq!(normalize("vfat", "MY STICK").unwrap(), "MY STICK");
+    assert!(normalize("vfat", "HOLIDAYS2024").is_err());
+    assert!(normalize("vfat", "A/B").is_err());
+    assert!(normalize("vfat", "FOTOS ÜBER").is_err());
+}
+
+#[test]
+fn other_filesystems_count_their_own_units() {
+    assert_eq!(normalize("exfat", "Fotos Über").unwrap(), "Fotos Über");
+    assert!(normalize("exfat", "Fotos Über 2").is_err());
+    // 16 bytes, but only 6 characters.
+    assert!(normalize("ext4", "写真写真写真").is_err());
+    assert_eq!(normalize("ext4", "backup-2024").unwrap(), "backup-2024");
+    assert!(normalize("ntfs", &"x".repeat(129)).is_err());
+    assert!(normalize("ext4", "").is_err());
+    assert!(matches!(
+        normalize("iso9660", "DISC"),
+        Err(UsbError::Unsupported(_))
+    ));
+    assert!(relabels_mounted("ext4") && !relabels_mounted("vfat"));
+}
+
+#[test]
+fn runs_the_filesystems_own_tool() {
+    let exec = Arc::new(
+        FakeExecutor::new()
+            .succeed("fatlabel", b"")
+            .succeed("e2label", b""),
+    );
+    let labeler = Labeler::with_executor(exec.clone());
+
+    assert_eq!(labeler.set("sdb1", "vfat", "camera").unwrap(), "CAMERA");
+    labeler.set("sdc1", "ext4", "backup").unwrap();
+
+    assert_eq!(
+        exec.calls(),
+        vec![
+            vec!["fatlabel".to_string(), "/dev/sdb1".into(), "CAMERA".into()],
+            vec!["e2label".to_string(), "/dev/sdc1".into(), "backup".into()],
+        ]
+    );
+}
@@ -0,0 +1,48 @@
+use lsusb::UsbError;
+use lsusb::lock::Locks;
+use std::time::Duration;
+
+#[test]
+fn a_device_lock_is_held_until_dropped_and_names_its_holder() {
+    let dir = tempfile::tempdir().unwrap();
+    let locks = Locks::in_dir(dir.path().join("locks"));
+    assert_eq!(locks.holder("sdb").unwrap(), None);
+
+    let held = locks.acquire("sdb", "copy to /media/DISK").unwrap();
+    let holder = locks.holder("sdb").unwrap().unwrap();
+    assert_eq!(
+        (holder.pid, holder.operation.as_str()),
+        (std::process::id(), "copy to /media/DISK")
+    );
+    match locks.acquire("sdb", "multiboot init") {
+        Err(UsbError::DeviceLocked { device, holder }) => {
+            assert_eq!(device, "sdb");
+            assert!(
+                holder.starts_with("copy to /media/DISK (pid "),
+                "{}",
+                holder
+            );
+        }
+        other => panic!("expected DeviceLocked, got {:?}", other),
+    }
+    // Other devices are unaffected.
+    drop(locks.acquire("sdc", "rm").unwrap());
+    assert!(
+        locks
+            .wait("sdb", "rm", Some(Duration::from_millis(300)))
+            .is_err()
+    );
+
+    let waiter = std::thread::spawn({
+        let locks = locks.clone();
+        move || {
+            locks
+                .wait("sdb", "multiboot init", Some(Duration::from_secs(10)))
+                .map(|_| ())
+        }
+    });
+    std::thread::sleep(Duration::from_millis(100));
+    drop(held);
+    waiter.join().unwrap().unwrap();
+    assert!(locks.acquire("../etc/passwd", "rm").is_err());
+}
@@ -0,0 +1,313 @@
+mod common;
+
+use common::{FakeExecutor, fixture};
+use lsusb::backend::LsblkBackend;
+use lsusb::{MountOptions, SizeUnits, SystemBackend, UsbError};
+use std::path::Path;
+use std::sync::Arc;
+
+fn backend(exec: FakeExecutor) -> (LsblkBackend, Arc<FakeExecutor>) {
+    let exec = Arc::new(exec);
+    (LsblkBackend::with_executor(exec.clone()), exec)
+}
+
+#[test]
+fn enumerate_keeps_only_usb_disks() {
+    let (backend, _) =
+        backend(FakeExecutor::new().succeed("lsblk", &fixture("lsblk_usb_stick.json")));
+
+    let devices = backend.enumerate().unwrap();
+
+    assert_eq!(devices.len(), 1);
+    let stick = &devices[0];
+    assert_eq!(stick.name, "sdb");
+    assert_eq!(stick.size, "14.9G");
+    assert_eq!(stick.bytes, 16008609792);
+    assert_eq!(stick.model.as_deref(), Some("Ultra"));
+    assert_eq!(stick.hotplug, Some(true));
+    assert_eq!(stick.partitions.len(), 2);
+    assert_eq!(
+        stick.mountpoints().collect::<Vec<_>>(),
+        ["/run/media/user/SANDISK"]
+    );
+}
+
+#[test]
+fn enumerate_accepts_quoted_byte_counts() {
+    let json =
+        br#"{"blockdevices": [{"name": "sdc", "size": "8053063680", "type": "disk", "tran": "usb",
+        "mountpoint": null, "vendor": null, "model": null, "hotplug": true}]}"#;
+    let (backend, _) = backend(FakeExecutor::new().succeed("lsblk", json));
+
+    let devices = backend.enumerate().unwrap();
+
+    assert_eq!(devices[0].bytes, 8053063680);
+    assert_eq!(devices[0].size, "7.5G");
+}
+
+#[test]
+fn whole_disk_filesystem_becomes_a_partition() {
+    let json = br#"{"blockdevices": [{"name": "sdc", "size": 8053063680, "type": "disk", "tran": "usb",
+        "mountpoint": "/media/CAMERA", "fstype": "vfat", "uuid": "1A2B-3C4D", "vendor": null, "model": null,
+        "hotplug": true}]}"#;
+    let (backend, _) = backend(FakeExecutor::new().succeed("lsblk", json));
+
+    let stick = backend.find("sdc").unwrap();
+
+    assert_eq!(stick.partitions.len(), 1);
+    assert_eq!(stick.partitions[0].name, "sdc");
+    assert_eq!(stick.partitions[0].device_type, "disk");
+    assert_eq!(stick.partitions[0].fstype.as_deref(), Some("vfat"));
+    assert_eq!(stick.partitions[0].uuid.as_deref(), Some("1A2B-3C4D"));
+    assert_eq!(stick.mountpoints().collect::<Vec<_>>(), ["/media/CAMERA"]);
+}
+
+#[test]
+fn unicode_labels_and_spaced_mountpoints_survive_parsing() {
+    let (backend, _) =
+        backend(FakeExecutor::new().succeed("lsblk", &fixture("lsblk_unicode_label.json")));
+
+    let stick = backend.find("sdc").unwrap();
+
+    assert_eq!(
+        stick.partitions[0].label.as_deref(),
+        Some("Fotos Über 写真")
+    );
+    assert_eq!(
+        stick.mountpoints().collect::<Vec<_>>(),
+        ["/run/media/user/Fotos Über 写真"]
+    );
+}
+
+#[test]
+fn unmount_passes_spaced_mountpoint_as_one_argument() {
+    let (backend, exec) = backend(FakeExecutor::new().succeed("umount", b""));
+
+    backend.unmount("/run/media/user/Fotos Über 写真").unwrap();
+
+    assert_eq!(
+        exec.calls(),
+        [["umount", "/run/media/user/Fotos Über 写真"]]
+    );
+}
+
+#[test]
+fn mount_joins_spaced_mountpoint_unchanged() {
+    let (backend, exec) = backend(FakeExecutor::new().succeed("mount", b""));
+    let target = Path::new("/mnt/My Disk").join("Ü");
+
+    let mounted_at = backend
+        .mount("sdc1", Some(&target), MountOptions::default())
+        .unwrap();
+
+    assert_eq!(mounted_at, target);
+    assert_eq!(exec.calls(), [["mount", "/dev/sdc1", "/mnt/My Disk/Ü"]]);
+}
+
+#[test]
+fn size_units_follow_their_convention() {
+    assert_eq!(SizeUnits::Compact.format(16008609792), "14.9G");
+    assert_eq!(SizeUnits::Iec.format(16008609792), "14.9 GiB");
+    assert_eq!(SizeUnits::Si.format(16008609792), "16.0 GB");
+    assert_eq!(SizeUnits::Bytes.format(16008609792), "16008609792");
+    assert_eq!(SizeUnits::Si.format(512), "512 B");
+}
+
+#[test]
+fn enumerate_reports_lsblk_failure() {
+    let (backend, _) = backend(FakeExecutor::new().fail("lsblk", "lsblk: unknown column"));
+
+    let err = backend.enumerate().unwrap_err();
+
+    assert!(
+        matches!(&err, UsbError::CommandFailed { program, stderr } if program == "lsblk" && stderr.contains("unknown column")),
+        "{err}"
+    );
+}
+
+#[test]
+fn enumerate_rejects_malformed_json() {
+    let (backend, _) = backend(FakeExecutor::new().succeed("lsblk", b"{\"blockdevices\": ["));
+
+    assert!(matches!(backend.enumerate(), Err(UsbError::Parse { .. })));
+}
+
+#[test]
+fn find_unknown_device_fails() {
+    let (backend, _) =
+        backend(FakeExecutor::new().succeed("lsblk", &fixture("lsblk_usb_stick.json")));
+
+    let err = backend.find("nvme0n1").unwrap_err();
+
+    assert!(
+        matches!(err, UsbError::NotUsb(_) | UsbError::DeviceNotFound(_)),
+        "{err}"
+    );
+}
+
+#[test]
+fn unmount_passes_mountpoint_verbatim() {
+    let (backend, exec) = backend(FakeExecutor::new().succeed("umount", b""));
+
+    backend.unmount("/run/media/user/SANDISK").unwrap();
+
+    assert_eq!(exec.calls(), [["umount", "/run/media/user/SANDISK"]]);
+}
+
+#[test]
+fn unmount_failure_includes_umount_stderr() {
+    let (backend, _) = backend(
+        FakeExecutor::new().fail("umount", "umount: /run/media/user/SANDISK: target is busy."),
+    );
+
+    let err = backend.unmount("/run/media/user/SANDISK").unwrap_err();
+
+    assert!(
+        matches!(&err, UsbError::MountBusy { mountpoint, .. } if mountpoint == "/run/media/user/SANDISK"),
+        "{err}"
+    );
+}
+
+#[test]
+fn unmount_other_failures_stay_command_failures() {
+    let (backend, _) = backend(FakeExecutor::new().fail("umount", "umount: /mnt: not mounted."));
+
+    let err = backend.unmount("/mnt").unwrap_err();
+
+    assert!(matches!(err, UsbError::CommandFailed { .. }), "{err}");
+}
+
+#[test]
+fn missing_tool_is_reported() {
+    let (backend, _) = backend(FakeExecutor::new());
+
+    let err = backend.power_off("sdb").unwrap_err();
+
+    assert!(
+        matches!(&err, UsbError::ToolMissing { program } if program == "eject"),
+        "{err}"
+    );
+}
+
+#[test]
+fn flush_syncs_the_device_mountpoints_only() {
+    let mnt = tempfile::tempdir().unwrap();
+    let json = format!(
+        r#"{{"blockdevices": [{{"name": "lsusb-test-sdz", "size": 1024, "type": "disk", "tran": "usb",
+            "mountpoint": null, "vendor": null, "model": null, "hotplug": true,
+            "children": [{{"name": "lsusb-test-sdz1", "size": 1024, "type": "part", "tran": null,
+                "mountpoint": {:?}, "vendor": null, "model": null, "hotplug": true}}]}}]}}"#,
+        mnt.path()
+    );
+    let (backend, exec) = backend(FakeExecutor::new().succeed("lsblk", json.as_bytes()));
+
+    backend.flush("lsusb-test-sdz").unwrap();
+
+    assert!(
+        exec.calls().iter().all(|c| c[0] == "lsblk"),
+        "{:?}",
+        exec.calls()
+    );
+}
+
+#[test]
+fn mount_requires_a_mountpoint() {
+    let (backend, exec) = backend(FakeExecutor::new().succeed("mount", b""));
+
+    assert!(matches!(
+        backend.mount("sdb1", None, MountOptions::default()),
+        Err(UsbError::Unsupported(_))
+    ));
+    assert!(exec.calls().is_empty());
+}
+
+#[test]
+fn mmc_cards_are_listed_only_when_asked_for() {
+    let json = br#"{"blockdevices": [
+        {"name": "mmcblk0", "size": 63864569856, "type": "disk", "tran": "mmc", "mountpoint": null,
+         "vendor": null, "model": null, "hotplug": false},
+        {"name": "mmcblk0boot0", "size": 4194304, "type": "disk", "tran": "mmc", "mountpoint": null,
+         "vendor": null, "model": null, "hotplug": false}]}"#;
+
+    let (usb_only, _) = backend(FakeExecutor::new().succeed("lsblk", json));
+    assert!(usb_only.enumerate().unwrap().is_empty());
+
+    let (with_mmc, _) = backend(FakeExecutor::new().succeed("lsblk", json));
+    let names: Vec<_> = with_mmc
+        .include_mmc(true)
+        .enumerate()
+        .unwrap()
+        .into_iter()
+        .map(|d| d.name)
+        .collect();
+    assert_eq!(names, ["mmcblk0"]);
+}
+
+#[test]
+fn other_removable_disks_are_listed_only_when_asked_for() {
+    let json = br#"{"blockdevices": [
+        {"name": "sda", "size": 512110190592, "type": "disk", "tran": "sata", "mountpoint": null,
+         "vendor": "ATA", "model": "Samsung SSD", "hotplug": false, "rm": false},
+        {"name": "sdc", "size": 2000398934016, "type": "disk", "tran": "sata", "mountpoint": null,
+         "vendor": "ATA", "model": "WDC WD20EZRZ", "hotplug": true, "rm": false},
+        {"name": "sr0", "size": 1073741312, "type": "rom", "tran": "sata", "mountpoint": null,
+         "vendor": "HL-DT-ST", "model": "DVDRAM", "hotplug": true, "rm": true},
+        {"name": "mmcblk0", "size": 63864569856, "type": "disk", "tran": "mmc", "mountpoint": null,
+         "vendor": null, "model": null, "hotplug": false, "rm": false}]}"#;
+
+    let (usb_only, _) = backend(FakeExecutor::new().succeed("lsblk", json));
+    assert!(usb_only.enumerate().unwrap().is_empty());
+
+    let (all, _) = backend(FakeExecutor::new().succeed("lsblk", json));
+    let names: Vec<_> = all
+        .all_removable(true)
+        .enumerate()
+        .unwrap()
+        .into_iter()
+        .map(|d| d.name)
+        .collect();
+    assert_eq!(names, ["sdc", "mmcblk0"]);
+}
+
+#[test]
+fn temporary_mountpoints_are_fresh_private_directories() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let (mounting, exec) = backend(
+        FakeExecutor::new()
+            .succeed("mount", b"")
+            .succeed("umount", b""),
+    );
+    let mount = || {
+        lsusb::backend::with_mounted(&mounting, "sdz9", None, MountOptions::default(), |dir| {
+            let meta = std::fs::symlink_metadata(dir).unwrap();
+            assert!(meta.is_dir());
+            assert_eq!(meta.permissions().mode() & 0o777, 0o700);
+            Ok(dir.to_path_buf())
+        })
+        .unwrap()
+    };
+    let (first, second) = (mount(), mount());
+
+    assert_ne!(first, second);
+    assert_ne!(first, std::env::temp_dir().join("lsusb-sdz9"));
+    assert!(!first.exists() && !second.exists());
+    assert_eq!(exec.calls()[0][..2], ["mount", "/dev/sdz9"]);
+
+    // A failed mount leaves nothing behind either.
+    let (failing, _) = backend(FakeExecutor::new().fail("mount", "mount: wrong fs type"));
+    let result =
+        lsusb::backend::with_mounted(&failing, "sdz9", None, MountOptions::default(), |_| Ok(()));
+    assert!(result.is_err());
+    let left = std::fs::read_dir(std::env::temp_dir())
+        .unwrap()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with("lsusb-sdz9-")
+        })
+        .count();
+    assert_eq!(left, 0);
+}
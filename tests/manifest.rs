@@ -0,0 +1,55 @@
+use lsusb::manifest::{self, manifest_path};
+use lsusb::{CancelToken, UsbError};
+use std::fs;
+use std::path::PathBuf;
+
+#[test]
+fn manifests_list_each_file_relative_to_their_directory() {
+    let stick = tempfile::tempdir().unwrap();
+    let copy = stick.path().join("photos");
+    fs::create_dir_all(copy.join("2024")).unwrap();
+    fs::write(copy.join("2024/a.jpg"), b"abc").unwrap();
+    fs::write(copy.join("notes.txt"), b"").unwrap();
+
+    let path = manifest::write(&copy, &CancelToken::new()).unwrap();
+
+    assert_eq!(path, stick.path().join("photos.sha256"));
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  photos/2024/a.jpg\n\
+         e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855  photos/notes.txt\n"
+    );
+    let report = manifest::verify(&copy, &CancelToken::new()).unwrap();
+    assert!(report.is_ok());
+    assert_eq!((report.files_checked, report.bytes_checked), (2, 3));
+}
+
+#[test]
+fn verify_finds_corrupt_and_missing_files() {
+    let stick = tempfile::tempdir().unwrap();
+    let copy = stick.path().join("photos");
+    fs::create_dir(&copy).unwrap();
+    fs::write(copy.join("a.jpg"), b"abc").unwrap();
+    fs::write(copy.join("b.jpg"), b"def").unwrap();
+    manifest::write(&copy, &CancelToken::new()).unwrap();
+
+    fs::write(copy.join("a.jpg"), b"abd").unwrap();
+    fs::remove_file(copy.join("b.jpg")).unwrap();
+    let report = manifest::verify(&copy, &CancelToken::new()).unwrap();
+
+    assert_eq!(report.corrupt, [PathBuf::from("photos/a.jpg")]);
+    assert_eq!(report.missing, [PathBuf::from("photos/b.jpg")]);
+    assert_eq!(report.files_checked, 1);
+}
+
+#[test]
+fn entries_pointing_outside_the_manifests_directory_are_refused() {
+    let stick = tempfile::tempdir().unwrap();
+    let copy = stick.path().join("photos");
+    for entry in ["../../etc/passwd", "/etc/passwd"] {
+        let line = format!("{}  {}\n", "0".repeat(64), entry);
+        fs::write(manifest_path(&copy), line).unwrap();
+        let err = manifest::verify(&copy, &CancelToken::new()).unwrap_err();
+        assert!(matches!(err, UsbError::Parse { .. }), "{err}");
+    }
+}
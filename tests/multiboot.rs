@@ -0,0 +1,165 @@
+mod common;
+
+use common::{FakeExecutor, fixture};
+use lsusb::SystemBackend;
+use lsusb::backend::LsblkBackend;
+use lsusb::multiboot::{self, Iso, Multiboot};
+use lsusb::usb::partition_name;
+use std::fs;
+use std::sync::Arc;
+
+fn tools() -> Arc<FakeExecutor> {
+    Arc::new(
+        [
+            "sfdisk",
+            "blockdev",
+            "udevadm",
+            "mkfs.exfat",
+            "mkfs.vfat",
+            "grub-install",
+            "mount",
+            "umount",
+        ]
+        .into_iter()
+        .fold(FakeExecutor::new(), |exec, program| {
+            exec.succeed(program, b"")
+        })
+        .succeed("lsblk", &fixture("lsblk_usb_stick.json")),
+    )
+}
+
+#[test]
+fn the_data_partition_comes_first_and_fills_the_disk() {
+    let script = multiboot::partition_script(16_008_609_792).unwrap();
+
+    assert_eq!(
+        script,
+        "label: dos\nunit: sectors\n\nstart=2048, size=31133696, type=7\nstart=31135744, size=131072, type=ef, bootable\n"
+    );
+    assert!(multiboot::partition_script(128 * 1024 * 1024).is_err());
+}
+
+#[test]
+fn partitions_of_disks_ending_in_a_digit_get_a_p() {
+    assert_eq!(partition_name("sdb", 1), "sdb1");
+    assert_eq!(partition_name("mmcblk0", 2), "mmcblk0p2");
+    assert_eq!(partition_name("nvme0n1", 1), "nvme0n1p1");
+}
+
+#[test]
+fn init_partitions_formats_and_installs_grub() {
+    let exec = tools();
+    let backend = LsblkBackend::with_executor(exec.clone());
+    let mut device = backend.find("sdb").unwrap();
+    for partition in &mut device.partitions {
+        partition.mountpoint = None;
+    }
+
+    let dev = tempfile::tempdir().unwrap();
+    for node in ["sdb1", "sdb2"] {
+        fs::write(dev.path().join(node), b"").unwrap();
+    }
+
+    let data = Multiboot::with_executor(exec.clone())
+        .dev_dir(dev.path().to_path_buf())
+        .init(&backend, &device)
+        .unwrap();
+
+    assert_eq!(data, "sdb1");
+    let calls = exec.calls();
+    let programs: Vec<&str> = calls
+        .iter()
+        .map(|c| c[0].as_str())
+        .filter(|p| *p != "lsblk")
+        .collect();
+    assert_eq!(
+        programs,
+        [
+            "sfdisk",
+            "blockdev",
+            "udevadm",
+            "mkfs.exfat",
+            "mkfs.vfat",
+            "mount",
+            "grub-install",
+            "grub-install",
+            "umount",
+            "mount",
+            "umount"
+        ]
+    );
+    let sfdisk = calls.iter().find(|c| c[0] == "sfdisk").unwrap();
+    assert_eq!(sfdisk[1..4], ["--wipe", "always", "/dev/sdb"]);
+    assert!(sfdisk[4].contains("type=ef, bootable"), "{:?}", sfdisk);
+    assert!(calls.contains(&vec![
+        "mkfs.exfat".into(),
+        "-L".into(),
+        "LSUSB".into(),
+        "/dev/sdb1".into()
+    ]));
+    let mounts: Vec<&String> = calls
+        .iter()
+        .filter(|c| c[0] == "mount")
+        .map(|c| &c[1])
+        .collect();
+    assert_eq!(mounts, ["/dev/sdb2", "/dev/sdb1"]);
+}
+
+#[test]
+fn init_refuses_a_mounted_device() {
+    let exec = tools();
+    let backend = LsblkBackend::with_executor(exec.clone());
+    let device = backend.find("sdb").unwrap();
+
+    let err = Multiboot::with_executor(exec.clone())
+        .init(&backend, &device)
+        .unwrap_err();
+
+    assert!(err.to_string().contains("/run/media/user/SANDISK"), "{err}");
+    assert!(!exec.calls().iter().any(|c| c[0] == "sfdisk"));
+}
+
+#[test]
+fn the_menu_is_built_from_the_iso_directory_at_boot() {
+    let menu = multiboot::grub_menu();
+
+    assert!(
+        menu.contains("search --no-floppy --set=data --label LSUSB\n"),
+        "{menu}"
+    );
+    assert!(
+        menu.contains("for isofile in ($data)/isos/*.iso; do"),
+        "{menu}"
+    );
+    assert!(
+        menu.contains("configfile /boot/grub/loopback.cfg"),
+        "{menu}"
+    );
+}
+
+#[test]
+fn images_are_listed_and_removed_by_name() {
+    let stick = tempfile::tempdir().unwrap();
+    assert!(multiboot::isos(stick.path()).unwrap().is_empty());
+    let dir = multiboot::iso_dir(stick.path()).unwrap();
+    fs::write(dir.join("debian-12.5.0-amd64-netinst.iso"), [0u8; 4]).unwrap();
+    fs::write(dir.join("ARCH.ISO"), [0u8; 2]).unwrap();
+    fs::write(dir.join("README.txt"), b"").unwrap();
+
+    assert_eq!(
+        multiboot::isos(stick.path()).unwrap(),
+        [
+            Iso {
+                name: "ARCH.ISO".into(),
+                bytes: 2,
+            },
+            Iso {
+                name: "debian-12.5.0-amd64-netinst.iso".into(),
+                bytes: 4,
+            },
+        ]
+    );
+    assert!(multiboot::remove_iso(stick.path(), "../README.txt").is_err());
+    multiboot::remove_iso(stick.path(), "ARCH.ISO").unwrap();
+    assert_eq!(multiboot::isos(stick.path()).unwrap().len(), 1);
+}
@@ -0,0 +1,72 @@
+use lsusb::Config;
+use lsusb::policy::{Access, DeviceIdentity, Policy};
+
+fn stick(serial: &str, bytes: u64) -> DeviceIdentity {
+    DeviceIdentity {
+        vendor_id: "0781".into(),
+        product_id: "5583".into(),
+        serial: Some(serial.into()),
+        bytes,
+    }
+}
+
+fn policy(toml: &str) -> Policy {
+    toml::from_str::<Config>(toml).unwrap().policy
+}
+
+#[test]
+fn without_a_policy_everything_is_allowed() {
+    assert_eq!(
+        Policy::default().access(&stick("A", 1 << 34)),
+        Access::Allow
+    );
+}
+
+#[test]
+fn first_matching_rule_wins_over_the_default() {
+    let policy = policy(
+        r#"
+        [policy]
+        default = "deny"
+
+        [[policy.rules]]
+        serial = "4C530001230912104355"
+        access = "allow"
+
+        [[policy.rules]]
+        vid_pid = "0781:*"
+        max_bytes = 34359738368
+        access = "read-only"
+        "#,
+    );
+
+    assert_eq!(
+        policy.access(&stick("4C530001230912104355", 1 << 36)),
+        Access::Allow
+    );
+    assert_eq!(policy.access(&stick("OTHER", 1 << 34)), Access::ReadOnly);
+    assert_eq!(policy.access(&stick("OTHER", 1 << 36)), Access::Deny);
+}
+
+#[test]
+fn vid_pid_matches_case_insensitively() {
+    let policy = policy(
+        r#"
+        [policy]
+        default = "read-only"
+        [[policy.rules]]
+        vid_pid = "0781:5583"
+        access = "allow"
+        "#,
+    );
+    let mut id = stick("A", 0);
+    id.product_id = "558A".into();
+
+    assert_eq!(policy.access(&stick("A", 0)), Access::Allow);
+    assert_eq!(policy.access(&id), Access::ReadOnly);
+}
+
+#[test]
+fn unknown_access_levels_are_rejected() {
+    assert!(toml::from_str::<Config>("[policy]\ndefault = \"maybe\"\n").is_err());
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use common::{FakeExecutor, fixture};
+use lsusb::backend::LsblkBackend;
+use lsusb::porcelain::{Writer, escape};
+use lsusb::{SystemBackend, Usage, UsbDevice};
+use std::sync::Arc;
+
+fn devices() -> Vec<UsbDevice> {
+    let exec = Arc::new(FakeExecutor::new().succeed("lsblk", &fixture("lsblk_usb_stick.json")));
+    let mut devices = LsblkBackend::with_executor(exec).enumerate().unwrap();
+    devices[0].partitions[0].label = Some("MY STICK".into());
+    devices
+}
+
+#[test]
+fn records_have_every_field_in_order() {
+    let devices = devices();
+    let stick = &devices[0];
+    let mut out = Vec::new();
+    let mut writer = Writer::new(&mut out, false).unwrap();
+    writer.device(stick, None).unwrap();
+    let usage = Usage {
+        total: 1000,
+        used: 400,
+        available: 550,
+    };
+    writer
+        .partition(&stick.name, &stick.partitions[0], Some(usage))
+        .unwrap();
+    writer
+        .partition(&stick.name, &stick.partitions[1], None)
+        .unwrap();
+
+    let text = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], "version=1");
+    assert_eq!(
+        lines[1],
+        "record=device name=sdb size=16008609792 vendor=SanDisk model=Ultra serial= hotplug=1 port= driver="
+    );
+    assert_eq!(
+        lines[2],
+        "record=partition device=sdb name=sdb1 size=15974006784 type=part fstype= label=MY\\x20STICK uuid= \
+         mountpoint=/run/media/user/SANDISK fs_total=1000 fs_used=400 fs_available=550"
+    );
+    assert!(lines[3].ends_with("mountpoint= fs_total= fs_used= fs_available="));
+}
+
+#[test]
+fn nul_separated_records_are_not_escaped() {
+    let devices = devices();
+    let mut out = Vec::new();
+    let mut writer = Writer::new(&mut out, true).unwrap();
+    writer
+        .partition("sdb", &devices[0].partitions[0], None)
+        .unwrap();
+
+    let records: Vec<&[u8]> = out.split(|&b| b == 0).collect();
+    assert_eq!(records[0], b"version=1");
+    assert_eq!(records[1], b"");
+    assert_eq!(records[2], b"record=partition");
+    assert!(records.contains(&&b"label=MY STICK"[..]));
+    assert!(out.ends_with(b"fs_available=\0\0"));
+}
+
+#[test]
+fn escaping_covers_backslashes_spaces_and_control_characters() {
+    assert_eq!(escape("a b\\c\td\n"), "a\\x20b\\\\c\\x09d\\x0a");
+    assert_eq!(escape("Übergröße"), "Übergröße");
+}
@@ -0,0 +1,89 @@
+use lsusb::preflight::{Limits, Problem, check};
+use lsusb::{TransferOptions, UsbError, copy_to_usb};
+use std::path::{Path, PathBuf};
+
+fn check_name(name: &str, fstype: &str) -> Option<Problem> {
+    check(
+        &Path::new("/media/usb").join(name),
+        Path::new(name),
+        &Limits::for_fstype(fstype),
+    )
+}
+
+#[test]
+fn names_are_measured_in_the_destinations_units() {
+    // 100 characters, 200 bytes of UTF-8.
+    let name = "é".repeat(100) + &"x".repeat(100);
+    assert_eq!(check_name(&name, "vfat"), None);
+    assert_eq!(
+        check_name(&name, "ext4"),
+        Some(Problem::NameTooLong {
+            len: 300,
+            limit: 255
+        })
+    );
+    assert_eq!(
+        check_name(&"x".repeat(256), "exfat"),
+        Some(Problem::NameTooLong {
+            len: 256,
+            limit: 255
+        })
+    );
+}
+
+#[test]
+fn windows_rules_only_apply_to_windows_filesystems() {
+    assert_eq!(
+        check_name("a:b.txt", "vfat"),
+        Some(Problem::ForbiddenCharacter(':'))
+    );
+    assert_eq!(
+        check_name("notes.", "exfat"),
+        Some(Problem::TrailingDotOrSpace)
+    );
+    assert_eq!(check_name("aux.c", "ntfs3"), Some(Problem::ReservedName));
+    assert_eq!(check_name("console.log", "vfat"), None);
+    assert_eq!(check_name("a:b.txt", "ext4"), None);
+    assert_eq!(check_name("aux.c", "apfs"), None);
+}
+
+#[test]
+fn only_the_part_the_copy_creates_is_checked() {
+    let limits = Limits::for_fstype("vfat");
+    assert_eq!(
+        check(
+            Path::new("/media/a:b/photo.jpg"),
+            Path::new("photo.jpg"),
+            &limits
+        ),
+        None
+    );
+    assert_eq!(
+        check(
+            Path::new("/media/usb/a:b/photo.jpg"),
+            Path::new("a:b/photo.jpg"),
+            &limits
+        ),
+        Some(Problem::ForbiddenCharacter(':'))
+    );
+}
+
+#[test]
+fn a_copy_whose_paths_would_be_too_long_writes_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let component = "d".repeat(200);
+    let nest = |root: PathBuf, depth: usize| (0..depth).fold(root, |path, _| path.join(&component));
+    let source = dir.path().join("src");
+    let deepest = nest(source.clone(), 8);
+    std::fs::create_dir_all(&deepest).unwrap();
+    std::fs::write(deepest.join("file"), b"data").unwrap();
+    let dest = nest(dir.path().join("dest"), 15);
+    std::fs::create_dir_all(&dest).unwrap();
+
+    let err = copy_to_usb(&source, &dest, &TransferOptions::default()).unwrap_err();
+    let UsbError::Unsupported(message) = err else {
+        panic!("expected Unsupported, got {:?}", err);
+    };
+    assert!(message.contains("file: the path is"), "{}", message);
+    assert!(!dest.join("src").exists());
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use common::FakeExecutor;
+use lsusb::backend::LsblkBackend;
+use lsusb::privilege::needs_privileges;
+use lsusb::{SystemBackend, UsbError};
+use std::io;
+use std::sync::Arc;
+
+#[test]
+fn refusals_from_non_root_users_need_privileges() {
+    let exec =
+        Arc::new(FakeExecutor::new().fail("umount", "umount: /mnt: must be superuser to unmount."));
+    let err = LsblkBackend::with_executor(exec)
+        .unmount("/mnt")
+        .unwrap_err();
+
+    assert!(needs_privileges(&err), "{err}");
+    let eperm = UsbError::Io {
+        context: "Failed to mount sdb1".into(),
+        source: io::Error::from_raw_os_error(libc::EPERM),
+    };
+    assert!(needs_privileges(&eperm));
+}
+
+#[test]
+fn other_failures_do_not_escalate() {
+    let exec = Arc::new(FakeExecutor::new().fail("umount", "umount: /mnt: not mounted."));
+    let err = LsblkBackend::with_executor(exec)
+        .unmount("/mnt")
+        .unwrap_err();
+
+    assert!(!needs_privileges(&err), "{err}");
+    assert!(!needs_privileges(&UsbError::DeviceNotFound("sdz".into())));
+}
@@ -0,0 +1,235 @@
+use lsusb::probe::{Filesystem, probe};
+use std::io::Cursor;
+
+fn image(len: usize, fields: &[(usize, &[u8])]) -> Cursor<Vec<u8>> {
+    let mut data = vec![0u8; len];
+    for (offset, bytes) in fields {
+        data[*offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+    Cursor::new(data)
+}
+
+fn found(fstype: &str, label: Option<&str>, uuid: Option<&str>) -> Option<Filesystem> {
+    Some(Filesystem {
+        fstype: fstype.to_string(),
+        label: label.map(String::from),
+        uuid: uuid.map(String::from),
+    })
+}
+
+#[test]
+fn fat32_serial_and_label_come_from_the_boot_sector() {
+    let mut dev = image(
+        4096,
+        &[
+            (66, &[0x29]),
+            (67, &0x1a2b_3c4du32.to_le_bytes()),
+            (71, b"CAMERA     "),
+            (82, b"FAT32   "),
+            (510, &[0x55, 0xaa]),
+        ],
+    );
+    assert_eq!(
+        probe(&mut dev).unwrap(),
+        found("vfat", Some("CAMERA"), Some("1A2B-3C4D"))
+    );
+}
+
+#[test]
+fn fat16_without_a_label_says_no_name() {
+    let mut dev = image(
+        4096,
+        &[
+            (38, &[0x29]),
+            (39, &0x0000_00ffu32.to_le_bytes()),
+            (43, b"NO NAME    "),
+            (54, b"FAT16   "),
+            (510, &[0x55, 0xaa]),
+        ],
+    );
+    assert_eq!(
+        probe(&mut dev).unwrap(),
+        found("vfat", None, Some("0000-00FF"))
+    );
+}
+
+#[test]
+fn ext_versions_are_told_apart_by_features() {
+    let uuid: Vec<u8> = (0..16).collect();
+    let fields = |incompat: u32, compat: u32| {
+        image(
+            4096,
+            &[
+                (1024 + 56, &[0x53, 0xef]),
+                (1024 + 92, &compat.to_le_bytes()),
+                (1024 + 96, &incompat.to_le_bytes()),
+                (1024 + 104, &uuid),
+                (1024 + 120, b"backup"),
+            ],
+        )
+    };
+    let expected = |fstype| {
+        found(
+            fstype,
+            Some("backup"),
+            Some("00010203-0405-0607-0809-0a0b0c0d0e0f"),
+        )
+    };
+    assert_eq!(probe(&mut fields(0x40, 0x4)).unwrap(), expected("ext4"));
+    assert_eq!(probe(&mut fields(0, 0x4)).unwrap(), expected("ext3"));
+    assert_eq!(probe(&mut fields(0, 0)).unwrap(), expected("ext2"));
+}
+
+#[test]
+fn luks2_has_a_label_and_uuid() {
+    let uuid = b"6b1a7a1e-64b4-4c3e-9d51-2f0e1f2a3b4c";
+    let mut dev = image(
+        4096,
+        &[(0, b"LUKS\xba\xbe\x00\x02"), (24, b"stash"), (168, uuid)],
+    );
+    assert_eq!(
+        probe(&mut dev).unwrap(),
+        found(
+            "crypto_LUKS",
+            Some("stash"),
+            Some("6b1a7a1e-64b4-4c3e-9d51-2f0e1f2a3b4c")
+        )
+    );
+}
+
+#[test]
+fn exfat_label_is_read_from_the_root_directory() {
+    let label: Vec<u8> = "Fotos".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    // 512-byte sectors, 4K clusters, heap at sector 16, root in cluster 4.
+    let root = 16 * 512 + 2 * 4096;
+    let mut dev = image(
+        32768,
+        &[
+            (3, b"EXFAT   "),
+            (88, &16u32.to_le_bytes()),
+            (96, &4u32.to_le_bytes()),
+            (100, &0xdead_beefu32.to_le_bytes()),
+            (108, &[9, 3]),
+            (root, &[0x81]),
+            (root + 32, &[0x83, 5]),
+            (root + 34, &label),
+        ],
+    );
+    assert_eq!(
+        probe(&mut dev).unwrap(),
+        found("exfat", Some("Fotos"), Some("DEAD-BEEF"))
+    );
+}
+
+#[test]
+fn ntfs_label_is_read_from_the_volume_file() {
+    let name: Vec<u8> = "Daten".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    // 512-byte sectors, one per cluster, 1K records, MFT at cluster 4.
+    let record = 4 * 512 + 3 * 1024;
+    let attr = record + 56;
+    let mut dev = image(
+        8192,
+        &[
+            (3, b"NTFS    "),
+            (0x0b, &512u16.to_le_bytes()),
+            (0x0d, &[1]),
+            (0x30, &4u64.to_le_bytes()),
+            (0x40, &[0xf6]),
+            (0x48, &0x0123_4567_89ab_cdefu64.to_le_bytes()),
+            (record, b"FILE"),
+            // Update sequence at 48: number, then the real ends of sectors 1 and 2.
+            (record + 4, &48u16.to_le_bytes()),
+            (record + 6, &3u16.to_le_bytes()),
+            (record + 48, &[0xaa, 0xbb, 0, 0, 0, 0]),
+            (record + 510, &[0xaa, 0xbb]),
+            (record + 1022, &[0xaa, 0xbb]),
+            (record + 0x14, &56u16.to_le_bytes()),
+            (attr, &0x60u32.to_le_bytes()),
+            (attr + 4, &40u32.to_le_bytes()),
+            (attr + 16, &(name.len() as u32).to_le_bytes()),
+            (attr + 20, &24u16.to_le_bytes()),
+            (attr + 24, &name),
+            (attr + 40, &0xffff_ffffu32.to_le_bytes()),
+        ],
+    );
+    assert_eq!(
+        probe(&mut dev).unwrap(),
+        found("ntfs", Some("Daten"), Some("0123456789ABCDEF"))
+    );
+}
+
+#[test]
+fn a_corrupt_ntfs_boot_sector_has_no_label() {
+    let mut record = vec![0u8; 1024];
+    record[..4].copy_from_slice(b"FILE");
+    record[4..6].copy_from_slice(&48u16.to_le_bytes());
+    record[6..8].copy_from_slice(&3u16.to_le_bytes());
+    let corrupt: [&[(usize, &[u8])]; 4] = [
+        // One-byte sectors.
+        &[
+            (0x0b, &1u16.to_le_bytes()),
+            (0x0d, &[1]),
+            (0x30, &4u64.to_le_bytes()),
+            (0x40, &[0xf6]),
+        ],
+        // A record size of 2^128.
+        &[
+            (0x0b, &512u16.to_le_bytes()),
+            (0x0d, &[1]),
+            (0x30, &4u64.to_le_bytes()),
+            (0x40, &[0x80]),
+        ],
+        // An MFT past the end of any disk.
+        &[
+            (0x0b, &512u16.to_le_bytes()),
+            (0x0d, &[128]),
+            (0x30, &u64::MAX.to_le_bytes()),
+            (0x40, &[0xf6]),
+        ],
+        // No sectors per cluster.
+        &[
+            (0x0b, &512u16.to_le_bytes()),
+            (0x0d, &[0]),
+            (0x30, &4u64.to_le_bytes()),
+            (0x40, &[1]),
+        ],
+    ];
+    for fields in corrupt {
+        let mut all: Vec<(usize, &[u8])> = vec![(3, b"NTFS    "), (4 * 512 + 3 * 1024, &record)];
+        all.extend_from_slice(fields);
+        let mut dev = image(8192, &all);
+        assert_eq!(
+            probe(&mut dev).unwrap(),
+            found("ntfs", None, Some("0000000000000000")),
+            "{:?}",
+            fields
+        );
+    }
+}
+
+#[test]
+fn iso9660_uuid_is_the_creation_time() {
+    let pvd = 16 * 2048;
+    let mut dev = image(
+        pvd + 2048,
+        &[
+            (pvd, b"\x01CD001"),
+            (pvd + 40, b"UBUNTU_24_04                    "),
+            (pvd + 813, b"2024042316594400"),
+        ],
+    );
+    assert_eq!(
+        probe(&mut dev).unwrap(),
+        found(
+            "iso9660",
+            Some("UBUNTU_24_04"),
+            Some("2024-04-23-16-59-44-00")
+        )
+    );
+}
+
+#[test]
+fn blank_and_short_devices_are_not_filesystems() {
+    assert_eq!(probe(&mut image(65536, &[])).unwrap(), None);
+    assert_eq!(probe(&mut image(100, &[])).unwrap(), None);
+}
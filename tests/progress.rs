@@ -0,0 +1,174 @@
+use lsusb::progress::{JsonLines, Throttled, Throughput, Verification, sparkline};
+use lsusb::{Phase, ProgressEvent, ProgressSink};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+#[test]
+fn throttled_sink_coalesces_byte_updates() {
+    let mut seen = Vec::new();
+    let mut sink = Throttled::new(|e| seen.push(e), Duration::from_secs(3600));
+
+    sink.emit(ProgressEvent::Totals {
+        bytes: 100,
+        files: 1,
+    });
+    for done in (10..=60).step_by(10) {
+        sink.emit(ProgressEvent::Bytes { done, total: 100 });
+    }
+    sink.emit(ProgressEvent::Phase(Phase::Done));
+
+    assert_eq!(
+        seen,
+        [
+            ProgressEvent::Totals {
+                bytes: 100,
+                files: 1
+            },
+            ProgressEvent::Bytes {
+                done: 10,
+                total: 100
+            },
+            ProgressEvent::Bytes {
+                done: 60,
+                total: 100
+            },
+            ProgressEvent::Phase(Phase::Done),
+        ]
+    );
+}
+
+#[test]
+fn throttled_sink_always_delivers_the_final_count() {
+    let mut seen = Vec::new();
+    let mut sink = Throttled::new(|e| seen.push(e), Duration::from_secs(3600));
+
+    sink.emit(ProgressEvent::Bytes { done: 1, total: 2 });
+    sink.emit(ProgressEvent::Bytes { done: 2, total: 2 });
+
+    assert_eq!(
+        seen.last(),
+        Some(&ProgressEvent::Bytes { done: 2, total: 2 })
+    );
+}
+
+#[test]
+fn throughput_reports_average_and_peak_rates_and_the_file_that_failed() {
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    let mut meter = Throughput::new(start);
+    let file = |size| ProgressEvent::File {
+        path: PathBuf::from("a.bin"),
+        size,
+    };
+    let bytes = |done| ProgressEvent::Bytes {
+        done,
+        total: 3_000_000,
+    };
+    meter.record_at(&ProgressEvent::Phase(Phase::Scanning), at(0));
+    // A second of scanning, then 1 MB in the first second and 2 MB in the next.
+    meter.record_at(&ProgressEvent::Phase(Phase::Copying), at(1000));
+    meter.record_at(&file(1_000_000), at(1000));
+    meter.record_at(&bytes(1_000_000), at(2000));
+    meter.record_at(
+        &ProgressEvent::Skipped {
+            path: PathBuf::from("b.exe"),
+            reason: "infected".into(),
+        },
+        at(2000),
+    );
+    meter.record_at(&file(2_000_000), at(2000));
+    meter.record_at(&bytes(3_000_000), at(3000));
+
+    let summary = meter.summary_at("cp", None, at(3000));
+    assert_eq!(
+        (
+            summary.files_copied,
+            summary.files_skipped,
+            summary.files_failed
+        ),
+        (2, 1, 0)
+    );
+    assert_eq!(
+        (
+            summary.bytes_written,
+            summary.average_rate,
+            summary.peak_rate
+        ),
+        (3_000_000, 1_000_000, 2_000_000)
+    );
+    assert_eq!(summary.verification, Verification::Skipped);
+
+    meter.record_at(&file(5_000_000), at(3000));
+    meter.record_at(&bytes(4_000_000), at(3500));
+    let failed = meter.summary_at("cp", Some("disk full".into()), at(3500));
+    assert_eq!((failed.files_copied, failed.files_failed), (2, 1));
+    let json = serde_json::to_value(&failed).unwrap();
+    assert_eq!(json["error"], "disk full");
+    assert_eq!(json["verification"], "skipped");
+}
+
+#[test]
+fn throughput_keeps_a_rate_history_that_shows_stalls() {
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    let mut meter = Throughput::new(start);
+    let bytes = |done| ProgressEvent::Bytes {
+        done,
+        total: 10_000_000,
+    };
+    meter.record_at(&ProgressEvent::Phase(Phase::Copying), at(0));
+    meter.record_at(&bytes(4_000_000), at(1000));
+    meter.record_at(&bytes(8_000_000), at(2000));
+    // Nothing for three seconds, then a trickle.
+    meter.record_at(&bytes(8_600_000), at(5000));
+    assert_eq!(
+        meter.history(),
+        [4_000_000, 4_000_000, 200_000, 200_000, 200_000]
+    );
+
+    assert_eq!(sparkline(&meter.history()), "██▁▁▁");
+    assert_eq!(sparkline(&[0, 4, 8]), "▁▄█");
+    assert_eq!(sparkline(&[]), "");
+}
+
+#[test]
+fn json_lines_writes_one_object_per_event() {
+    let mut out = Vec::new();
+    let mut sink = JsonLines::new(&mut out);
+    sink.emit(ProgressEvent::Phase(Phase::Scanning));
+    sink.emit(ProgressEvent::Totals {
+        bytes: 10,
+        files: 1,
+    });
+    sink.emit(ProgressEvent::File {
+        path: PathBuf::from("a.txt"),
+        size: 10,
+    });
+    sink.emit(ProgressEvent::Bytes { done: 4, total: 10 });
+    sink.emit(ProgressEvent::Skipped {
+        path: PathBuf::from("b.txt"),
+        reason: "ignored".into(),
+    });
+    sink.emit(ProgressEvent::Phase(Phase::Done));
+
+    let lines: Vec<serde_json::Value> = String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 6);
+    assert_eq!(
+        lines[0],
+        serde_json::json!({ "event": "phase", "phase": "scanning" })
+    );
+    assert_eq!(
+        lines[2],
+        serde_json::json!({ "event": "file", "path": "a.txt", "size": 10 })
+    );
+    assert_eq!(
+        lines[3],
+        serde_json::json!({ "event": "bytes", "done": 4, "total": 10 })
+    );
+    assert_eq!(lines[4]["reason"], "ignored");
+    assert_eq!(lines[5]["phase"], "done");
+}
@@ -0,0 +1,281 @@
+use lsusb::TransferOptions;
+use lsusb::backup::{self, Amount, Conflict, Profile, Retention, Sample, Target, Verify};
+use lsusb::repo::{self, MAX_CHUNK, MIN_CHUNK, Repository};
+use std::fs;
+use std::path::Path;
+
+/// Incompressible, reproducible bytes.
+fn noise(len: usize, mut seed: u64) -> Vec<u8> {
+    (0..len)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed as u8
+        })
+        .collect()
+}
+
+fn chunk_lengths(data: &[u8]) -> Vec<usize> {
+    let mut lengths = Vec::new();
+    repo::chunks(&mut &data[..], Path::new("data"), |chunk| {
+        lengths.push(chunk.len());
+        Ok(())
+    })
+    .unwrap();
+    lengths
+}
+
+fn profile(source: &Path) -> Profile {
+    Profile {
+        sources: vec![source.to_path_buf()],
+        target: Target::default(),
+        dest: None,
+        include: Vec::new(),
+        exclude: Vec::new(),
+        verify: Verify::Full,
+        snapshots: false,
+        keep: Default::default(),
+        auto: false,
+        chunked: true,
+        mtime_tolerance: None,
+        timezone_shifts: false,
+        sign: None,
+    }
+}
+
+#[test]
+fn chunk_boundaries_follow_the_content() {
+    let data = noise(12 * 1024 * 1024, 7);
+    let lengths = chunk_lengths(&data);
+    assert_eq!(lengths.iter().sum::<usize>(), data.len());
+    assert!(
+        lengths[..lengths.len() - 1]
+            .iter()
+            .all(|&n| (MIN_CHUNK..=MAX_CHUNK).contains(&n)),
+        "{lengths:?}"
+    );
+
+    // Bytes inserted at the front only change the first chunk.
+    let mut shifted = b"a few new bytes".to_vec();
+    shifted.extend_from_slice(&data);
+    let moved = chunk_lengths(&shifted);
+    assert_eq!(moved[0], lengths[0] + 15);
+    assert_eq!(moved[1..], lengths[1..]);
+}
+
+#[test]
+fn unchanged_data_is_stored_once() {
+    let home = tempfile::tempdir().unwrap();
+    let vm = home.path().join("vm");
+    fs::create_dir_all(&vm).unwrap();
+    let mut image = noise(6 * 1024 * 1024, 42);
+    fs::write(vm.join("disk.img"), &image).unwrap();
+    fs::write(vm.join("copy.img"), &image).unwrap();
+    let stick = tempfile::tempdir().unwrap();
+    let dest = stick.path().join("lsusb-backup/vm");
+    let profile = profile(&vm);
+
+    let first = backup::run(&profile, &dest, &TransferOptions::default(), |_| {}).unwrap();
+    assert_eq!(
+        (first.files_copied, first.bytes_copied),
+        (2, image.len() as u64)
+    );
+
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    image[3 * 1024 * 1024] ^= 0xff;
+    fs::write(vm.join("disk.img"), &image).unwrap();
+    let second = backup::run(&profile, &dest, &TransferOptions::default(), |_| {}).unwrap();
+    assert_eq!((second.files_copied, second.files_unchanged), (1, 1));
+    assert!(
+        second.bytes_copied <= MAX_CHUNK as u64,
+        "{}",
+        second.bytes_copied
+    );
+
+    let repository = Repository::open(&dest).unwrap();
+    let archives = repository.archives().unwrap();
+    assert_eq!(archives.len(), 2);
+    let restored = tempfile::tempdir().unwrap();
+    let report = repo::restore(
+        &dest,
+        &archives[0],
+        restored.path(),
+        Conflict::Skip,
+        Verify::Full,
+        &TransferOptions::default(),
+        |_| {},
+    )
+    .unwrap();
+    assert_eq!((report.files_restored, report.files_verified), (2, 2));
+    assert_eq!(
+        fs::read(restored.path().join("vm/copy.img")).unwrap(),
+        fs::read(vm.join("copy.img")).unwrap()
+    );
+    assert_ne!(
+        fs::read(restored.path().join("vm/disk.img")).unwrap(),
+        image
+    );
+
+    let changes = repo::diff(&dest, &archives[0], &archives[1]).unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].path, Path::new("vm/disk.img"));
+}
+
+#[test]
+fn pruning_drops_chunks_no_archive_uses() {
+    let home = tempfile::tempdir().unwrap();
+    let file = home.path().join("notes.txt");
+    let stick = tempfile::tempdir().unwrap();
+    let dest = stick.path().join("lsusb-backup/notes");
+    let profile = profile(&file);
+    fs::write(&file, b"first").unwrap();
+    backup::run(&profile, &dest, &TransferOptions::default(), |_| {}).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    fs::write(&file, b"second").unwrap();
+    backup::run(&profile, &dest, &TransferOptions::default(), |_| {}).unwrap();
+
+    let pruned = repo::prune(
+        &dest,
+        &Retention {
+            last: 1,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(pruned.len(), 1);
+    let repository = Repository::open(&dest).unwrap();
+    let first = repository.chunk_path(&lsusb::digest::hex(&lsusb::digest::sha256(b"first")));
+    let second = repository.chunk_path(&lsusb::digest::hex(&lsusb::digest::sha256(b"second")));
+    assert!(!first.exists());
+    assert!(second.exists());
+    assert_eq!(
+        fs::read_to_string(dest.join("index"))
+            .unwrap()
+            .lines()
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn verify_finds_damaged_and_missing_chunks() {
+    let home = tempfile::tempdir().unwrap();
+    fs::write(home.path().join("a.txt"), b"alpha").unwrap();
+    fs::write(home.path().join("b.txt"), b"bravo").unwrap();
+    let stick = tempfile::tempdir().unwrap();
+    let dest = stick.path().join("lsusb-backup/home");
+    backup::run(
+        &profile(home.path()),
+        &dest,
+        &TransferOptions::default(),
+        |_| {},
+    )
+    .unwrap();
+    let repository = Repository::open(&dest).unwrap();
+    let archive = repository.archive_name(None).unwrap();
+    let verify =
+        || repo::verify(&dest, &archive, None, &TransferOptions::default(), |_| {}).unwrap();
+    assert!(verify().is_ok());
+
+    let chunk =
+        |data: &[u8]| repository.chunk_path(&lsusb::digest::hex(&lsusb::digest::sha256(data)));
+    fs::write(chunk(b"alpha"), b"alphx").unwrap();
+    fs::remove_file(chunk(b"bravo")).unwrap();
+    let report = verify();
+
+    let name = home.path().file_name().unwrap();
+    assert_eq!(report.corrupt, [Path::new(name).join("a.txt")]);
+    assert_eq!(report.missing, [Path::new(name).join("b.txt")]);
+    assert_eq!(report.files_checked, 1);
+}
+
+#[test]
+fn a_percentage_samples_the_chunks_of_large_files() {
+    let home = tempfile::tempdir().unwrap();
+    fs::write(home.path().join("disk.img"), noise(12 * 1024 * 1024, 3)).unwrap();
+    let stick = tempfile::tempdir().unwrap();
+    let dest = stick.path().join("lsusb-backup/home");
+    backup::run(
+        &profile(home.path()),
+        &dest,
+        &TransferOptions::default(),
+        |_| {},
+    )
+    .unwrap();
+    let archive = Repository::open(&dest).unwrap().archive_name(None).unwrap();
+    let verify = |sample| {
+        repo::verify(&dest, &archive, sample, &TransferOptions::default(), |_| {}).unwrap()
+    };
+    assert_eq!(verify(None).bytes_checked, 12 * 1024 * 1024);
+
+    let sample = Sample {
+        amount: Amount::Percent(25.0),
+        seed: 1,
+    };
+    let sampled = verify(Some(sample));
+    assert!(sampled.is_ok());
+    assert_eq!(sampled.files_checked, 1);
+    assert!(
+        sampled.bytes_checked > 0 && sampled.bytes_checked < 6 * 1024 * 1024,
+        "{}",
+        sampled.bytes_checked
+    );
+    assert_eq!(verify(Some(sample)).bytes_checked, sampled.bytes_checked);
+}
+
+#[test]
+fn archives_and_indexes_cannot_reach_outside_the_repository() {
+    let home = tempfile::tempdir().unwrap();
+    let file = home.path().join("notes.txt");
+    let stick = tempfile::tempdir().unwrap();
+    let dest = stick.path().join("lsusb-backup/notes");
+    let profile = profile(&file);
+    fs::write(&file, b"first").unwrap();
+    backup::run(&profile, &dest, &TransferOptions::default(), |_| {}).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    fs::write(&file, b"second").unwrap();
+    backup::run(&profile, &dest, &TransferOptions::default(), |_| {}).unwrap();
+
+    // A crafted archive path would restore over a file beside the target.
+    let repository = Repository::open(&dest).unwrap();
+    let newest = repository.archive_name(None).unwrap();
+    let archive = dest.join("archives").join(format!("{}.json", newest));
+    let json = fs::read_to_string(&archive).unwrap();
+    fs::write(
+        &archive,
+        json.replace(r#""path":"notes.txt""#, r#""path":"../escape.txt""#),
+    )
+    .unwrap();
+    let target = tempfile::tempdir().unwrap();
+    let restore_dir = target.path().join("restored");
+    let restored = repo::restore(
+        &dest,
+        &newest,
+        &restore_dir,
+        Conflict::Skip,
+        Verify::Full,
+        &TransferOptions::default(),
+        |_| {},
+    );
+    assert!(matches!(restored, Err(lsusb::UsbError::Parse { .. })));
+    assert!(!target.path().join("escape.txt").exists());
+    fs::write(&archive, json).unwrap();
+
+    // A crafted index line would have prune delete a file outside it.
+    let victim = stick.path().join("victim");
+    fs::write(&victim, b"keep me").unwrap();
+    let mut index = fs::read_to_string(dest.join("index")).unwrap();
+    index.push_str("../../victim\n");
+    fs::write(dest.join("index"), index).unwrap();
+    repo::prune(
+        &dest,
+        &Retention {
+            last: 1,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert!(victim.exists());
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use common::FakeExecutor;
+use lsusb::UsbError;
+use lsusb::rescan::Rescanner;
+use std::sync::Arc;
+
+#[test]
+fn rescan_rereads_the_table_and_waits_for_udev() {
+    let exec = Arc::new(
+        FakeExecutor::new()
+            .succeed("blockdev", b"")
+            .succeed("udevadm", b""),
+    );
+    let dev = tempfile::tempdir().unwrap();
+    std::fs::write(dev.path().join("sdb1"), b"").unwrap();
+
+    Rescanner::with_executor(exec.clone())
+        .dev_dir(dev.path().to_path_buf())
+        .rescan("sdb", &["sdb1"])
+        .unwrap();
+
+    assert_eq!(
+        exec.calls(),
+        [
+            vec!["blockdev", "--rereadpt", "/dev/sdb"],
+            vec!["udevadm", "settle"]
+        ]
+    );
+}
+
+#[test]
+fn a_disk_in_use_is_reported_as_such() {
+    let exec = Arc::new(FakeExecutor::new().fail(
+        "blockdev",
+        "blockdev: ioctl error on BLKRRPART: Device or resource busy",
+    ));
+
+    let err = Rescanner::with_executor(exec)
+        .rescan("sdb", &[])
+        .unwrap_err();
+
+    assert!(
+        matches!(&err, UsbError::Unsupported(message) if message.contains("unmount")),
+        "{:?}",
+        err
+    );
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use common::FakeExecutor;
+use lsusb::scan::{Scanner, Verdict, parse_clamd_reply};
+use lsusb::{TransferOptions, UsbError, copy_to_usb};
+use std::fs;
+use std::sync::Arc;
+
+#[test]
+fn parses_clamd_replies() {
+    assert_eq!(parse_clamd_reply("stream: OK").unwrap(), Verdict::Clean);
+    assert_eq!(
+        parse_clamd_reply("stream: Win.Test.EICAR_HDB-1 FOUND").unwrap(),
+        Verdict::Infected("Win.Test.EICAR_HDB-1".into())
+    );
+    assert!(matches!(
+        parse_clamd_reply("INSTREAM size limit exceeded. ERROR"),
+        Err(UsbError::Parse { .. })
+    ));
+}
+
+#[test]
+fn infected_files_are_skipped_and_reported() {
+    let src = tempfile::tempdir().unwrap();
+    let dest = tempfile::tempdir().unwrap();
+    fs::write(src.path().join("invoice.exe"), "X5O!P%@AP").unwrap();
+    let exec = Arc::new(FakeExecutor::new().exit(
+        "clamdscan",
+        1,
+        b"/tmp/invoice.exe: Win.Test.EICAR_HDB-1 FOUND\n",
+    ));
+    let options = TransferOptions {
+        scanner: Some(Scanner::command_with_executor(
+            vec!["clamdscan".into(), "--no-summary".into()],
+            exec.clone(),
+        )),
+        ..Default::default()
+    };
+
+    let report = copy_to_usb(&src.path().join("invoice.exe"), dest.path(), &options).unwrap();
+
+    assert_eq!(report.files_copied, 0);
+    assert_eq!(report.infected.len(), 1);
+    assert_eq!(report.infected[0].1, "Win.Test.EICAR_HDB-1");
+    assert!(!dest.path().join("invoice.exe").exists());
+    assert_eq!(exec.calls()[0][..2], ["clamdscan", "--no-summary"]);
+}
+
+#[test]
+fn scanner_errors_abort_the_copy() {
+    let src = tempfile::tempdir().unwrap();
+    let dest = tempfile::tempdir().unwrap();
+    fs::write(src.path().join("notes.txt"), "hello").unwrap();
+    let exec = Arc::new(FakeExecutor::new().exit("clamdscan", 2, b""));
+    let options = TransferOptions {
+        scanner: Some(Scanner::command_with_executor(
+            vec!["clamdscan".into()],
+            exec,
+        )),
+        ..Default::default()
+    };
+
+    let err = copy_to_usb(&src.path().join("notes.txt"), dest.path(), &options).unwrap_err();
+
+    assert!(matches!(err, UsbError::CommandFailed { .. }), "{err}");
+}
@@ -0,0 +1,180 @@
+mod common;
+
+use common::FakeExecutor;
+use lsusb::backup::{Profile, Target, Verify};
+use lsusb::schedule::{self, Scheduler, Trigger};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+fn profile(serial: Option<&str>, label: Option<&str>) -> Profile {
+    Profile {
+        sources: vec!["~/Documents".into()],
+        target: Target {
+            serial: serial.map(String::from),
+            label: label.map(String::from),
+        },
+        dest: None,
+        include: Vec::new(),
+        exclude: Vec::new(),
+        verify: Verify::Size,
+        snapshots: false,
+        keep: Default::default(),
+        auto: false,
+        chunked: false,
+        mtime_tolerance: None,
+        timezone_shifts: false,
+        sign: None,
+    }
+}
+
+#[test]
+fn daily_profiles_get_a_persistent_timer() {
+    let units = schedule::units(
+        "home-docs",
+        &profile(Some("4C53"), None),
+        Path::new("/usr/bin/lsusb"),
+        Trigger::Daily,
+    )
+    .unwrap();
+
+    assert_eq!(units.service.name, "lsusb-backup-home-docs.service");
+    assert!(
+        units
+            .service
+            .contents
+            .contains("ExecStart=\"/usr/bin/lsusb\" backup run home-docs\n")
+    );
+    let timer = units.timer.unwrap();
+    assert_eq!(timer.name, "lsusb-backup-home-docs.timer");
+    assert!(
+        timer
+            .contents
+            .contains("OnCalendar=daily\nPersistent=true\n"),
+        "{}",
+        timer.contents
+    );
+    assert!(units.udev_rule.is_none());
+}
+
+#[test]
+fn plug_rules_match_the_target_and_start_the_user_service() {
+    let units = schedule::units(
+        "home-docs",
+        &profile(Some("4C53"), Some("BACKUP")),
+        Path::new("/usr/bin/lsusb"),
+        Trigger::OnPlug,
+    )
+    .unwrap();
+
+    let rule = units.udev_rule.unwrap();
+    assert_eq!(rule.name, "90-lsusb-backup-home-docs.rules");
+    assert!(
+        rule.contents.contains(
+            "ACTION==\"add\", SUBSYSTEM==\"block\", ENV{ID_SERIAL_SHORT}==\"4C53\", ENV{ID_FS_LABEL}==\"BACKUP\", \
+             TAG+=\"systemd\", ENV{SYSTEMD_USER_WANTS}+=\"lsusb-backup-home-docs.service\"\n"
+        ),
+        "{}",
+        rule.contents
+    );
+    let serial_only = schedule::units(
+        "p",
+        &profile(Some("4C53"), None),
+        Path::new("/usr/bin/lsusb"),
+        Trigger::OnPlug,
+    )
+    .unwrap();
+    assert!(
+        serial_only
+            .udev_rule
+            .unwrap()
+            .contents
+            .contains("ENV{DEVTYPE}==\"disk\"")
+    );
+    assert!(units.timer.is_none());
+}
+
+#[test]
+fn names_that_cannot_be_unit_names_are_refused() {
+    let profile = profile(Some("4C53"), None);
+
+    assert!(
+        schedule::units(
+            "my docs",
+            &profile,
+            Path::new("/usr/bin/lsusb"),
+            Trigger::Daily
+        )
+        .is_err()
+    );
+    assert!(
+        schedule::units(
+            "../x",
+            &profile,
+            Path::new("/usr/bin/lsusb"),
+            Trigger::Daily
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn installing_writes_the_units_and_enables_the_timer() {
+    let units_dir = tempfile::tempdir().unwrap();
+    let rules_dir = tempfile::tempdir().unwrap();
+    let exec = Arc::new(
+        FakeExecutor::new()
+            .succeed("systemctl", b"")
+            .succeed("udevadm", b""),
+    );
+    let scheduler = Scheduler::with_executor(exec.clone()).directories(
+        units_dir.path().join("systemd/user"),
+        rules_dir.path().to_path_buf(),
+    );
+
+    let daily = schedule::units(
+        "docs",
+        &profile(Some("4C53"), None),
+        Path::new("/usr/bin/lsusb"),
+        Trigger::Daily,
+    )
+    .unwrap();
+    let installed = scheduler.install(&daily).unwrap();
+    let plug = schedule::units(
+        "docs",
+        &profile(Some("4C53"), None),
+        Path::new("/usr/bin/lsusb"),
+        Trigger::OnPlug,
+    )
+    .unwrap();
+    let installed_rule = scheduler.install(&plug).unwrap();
+
+    assert_eq!(installed.files.len(), 2);
+    assert!(
+        units_dir
+            .path()
+            .join("systemd/user/lsusb-backup-docs.timer")
+            .exists()
+    );
+    assert_eq!(installed_rule.rule_pending, None);
+    assert!(
+        fs::read_to_string(rules_dir.path().join("90-lsusb-backup-docs.rules"))
+            .unwrap()
+            .contains("4C53")
+    );
+    assert_eq!(
+        exec.calls(),
+        [
+            vec!["systemctl", "--user", "daemon-reload"],
+            vec![
+                "systemctl",
+                "--user",
+                "enable",
+                "--now",
+                "lsusb-backup-docs.timer"
+            ],
+            vec!["systemctl", "--user", "daemon-reload"],
+            vec!["udevadm", "control", "--reload"],
+        ]
+    );
+}
@@ -0,0 +1,26 @@
+use lsusb::SpeedClass;
+
+#[test]
+fn decodes_ratings_from_the_sd_status_register() {
+    let ssr = "8000000000000000040000000000311e000000000002000000000000000000000000000000000000000000000000000000000000000000000000000000000000";
+
+    let speed = SpeedClass::from_ssr(ssr).unwrap();
+
+    assert_eq!(
+        speed,
+        SpeedClass {
+            class: Some(10),
+            uhs: Some(3),
+            video: Some(30),
+            app: Some(2),
+        }
+    );
+    assert_eq!(speed.to_string(), "Class 10 U3 V30 A2");
+    assert_eq!(speed.min_write_mb_s(), 30);
+}
+
+#[test]
+fn unrated_cards_and_garbage_have_no_speed_class() {
+    assert_eq!(SpeedClass::from_ssr(&"0".repeat(128)), None);
+    assert_eq!(SpeedClass::from_ssr("80000000"), None);
+}
@@ -0,0 +1,166 @@
+mod common;
+
+use common::FakeExecutor;
+use lsusb::backup::{self, Profile, Target, Verify};
+use lsusb::sign::{Minisign, signature_path};
+use lsusb::{TransferOptions, UsbError};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+const PUBLIC_KEY: &str = "untrusted comment: minisign public key E7620F1842B4E81F\n\
+    RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3\n";
+const SIGNATURE: &str = "untrusted comment: signature from minisign secret key\n\
+    RWQf6LRCGA9i5wABAgMEBQYHCAkKCwwNDg8QERITFBUWFxgZGhscHR4fICEiIyQlJicoKSorLC0uLzAxMjM0NTY3ODk6Ozw9Pj8=\n\
+    trusted comment: timestamp:1760000000\tfile:photos.sha256\n\
+    QEFCQ0RFRkdISUpLTE1OT1BRUlNUVVZXWFlaW1xdXl9gYWJjZGVmZ2hpamtsbW5vcHFyc3R1dnd4eXp7fH1+fw==\n";
+
+#[test]
+fn signatures_go_next_to_the_manifest() {
+    let manifest = Path::new("/media/stick/photos.sha256");
+    assert_eq!(
+        signature_path(manifest),
+        Path::new("/media/stick/photos.sha256.minisig")
+    );
+
+    let exec = Arc::new(FakeExecutor::new().succeed("minisign", b""));
+    Minisign::with_executor(exec.clone())
+        .sign(manifest, Path::new("/home/alice/.minisign/lsusb.key"))
+        .unwrap();
+
+    assert_eq!(
+        exec.calls(),
+        [[
+            "minisign",
+            "-S",
+            "-s",
+            "/home/alice/.minisign/lsusb.key",
+            "-m",
+            "/media/stick/photos.sha256",
+            "-x",
+            "/media/stick/photos.sha256.minisig"
+        ]]
+    );
+}
+
+#[test]
+fn a_well_formed_signature_is_checked_by_minisign() {
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = dir.path().join("photos.sha256");
+    let key = dir.path().join("lsusb.pub");
+    fs::write(&manifest, "").unwrap();
+    fs::write(&key, PUBLIC_KEY).unwrap();
+    fs::write(signature_path(&manifest), SIGNATURE).unwrap();
+
+    let exec = Arc::new(FakeExecutor::new().succeed("minisign", b""));
+    Minisign::with_executor(exec.clone())
+        .verify(&manifest, &key)
+        .unwrap();
+    assert_eq!(exec.calls()[0][1..3], ["-V", "-q"]);
+
+    // Whatever minisign says when it fails, the signature didn't check out.
+    let exec = Arc::new(FakeExecutor::new().fail("minisign", "Signature verification failed"));
+    let err = Minisign::with_executor(exec)
+        .verify(&manifest, &key)
+        .unwrap_err();
+    assert!(
+        matches!(err, UsbError::VerificationFailed { ref path } if *path == signature_path(&manifest))
+    );
+    let exec = Arc::new(FakeExecutor::new().fail("minisign", "Unexpected error"));
+    let err = Minisign::with_executor(exec)
+        .verify(&manifest, &key)
+        .unwrap_err();
+    assert!(matches!(err, UsbError::VerificationFailed { .. }), "{err}");
+}
+
+#[test]
+fn missing_malformed_or_foreign_signatures_fail_without_running_minisign() {
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = dir.path().join("photos.sha256");
+    let key = dir.path().join("lsusb.pub");
+    fs::write(&manifest, "").unwrap();
+    fs::write(&key, PUBLIC_KEY).unwrap();
+    let exec = Arc::new(FakeExecutor::new().succeed("minisign", b""));
+    let minisign = Minisign::with_executor(exec.clone());
+
+    let err = minisign.verify(&manifest, &key).unwrap_err();
+    assert!(matches!(err, UsbError::VerificationFailed { .. }), "{err}");
+
+    fs::write(
+        signature_path(&manifest),
+        "untrusted comment: nothing else\n",
+    )
+    .unwrap();
+    let err = minisign.verify(&manifest, &key).unwrap_err();
+    assert!(matches!(err, UsbError::VerificationFailed { .. }), "{err}");
+
+    // Made with a key whose id differs in its last byte.
+    fs::write(
+        signature_path(&manifest),
+        SIGNATURE.replace("RWQf6LRCGA9i5w", "RWQf6LRCGA9i5g"),
+    )
+    .unwrap();
+    let err = minisign.verify(&manifest, &key).unwrap_err();
+    assert!(matches!(err, UsbError::VerificationFailed { .. }), "{err}");
+
+    assert!(exec.calls().is_empty());
+}
+
+#[test]
+fn an_unreadable_or_malformed_public_key_is_not_a_bad_signature() {
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = dir.path().join("photos.sha256");
+    let key = dir.path().join("lsusb.pub");
+    fs::write(&manifest, "").unwrap();
+    fs::write(signature_path(&manifest), SIGNATURE).unwrap();
+    let exec = Arc::new(FakeExecutor::new().succeed("minisign", b""));
+    let minisign = Minisign::with_executor(exec.clone());
+
+    let err = minisign.verify(&manifest, &key).unwrap_err();
+    assert!(matches!(err, UsbError::Io { .. }), "{err}");
+
+    fs::write(
+        &key,
+        "untrusted comment: minisign public key\nnot base64!\n",
+    )
+    .unwrap();
+    let err = minisign.verify(&manifest, &key).unwrap_err();
+    assert!(matches!(err, UsbError::Parse { .. }), "{err}");
+
+    assert!(exec.calls().is_empty());
+}
+
+#[test]
+fn rewriting_a_manifest_drops_its_old_signature_and_chunked_backups_are_not_signed() {
+    let home = tempfile::tempdir().unwrap();
+    fs::write(home.path().join("notes.txt"), b"v1").unwrap();
+    let stick = tempfile::tempdir().unwrap();
+    let dest = stick.path().join("lsusb-backup/docs");
+    let mut profile = Profile {
+        sources: vec![home.path().to_path_buf()],
+        target: Target::default(),
+        dest: None,
+        include: Vec::new(),
+        exclude: Vec::new(),
+        verify: Verify::Size,
+        snapshots: false,
+        keep: Default::default(),
+        auto: false,
+        chunked: false,
+        mtime_tolerance: None,
+        timezone_shifts: false,
+        sign: None,
+    };
+    let stale = signature_path(&backup::manifest_path(&dest));
+    fs::create_dir_all(stale.parent().unwrap()).unwrap();
+    fs::write(&stale, "untrusted comment: from an earlier run\n").unwrap();
+
+    backup::run(&profile, &dest, &TransferOptions::default(), |_| {}).unwrap();
+    assert!(backup::manifest_path(&dest).is_file());
+    assert!(!stale.exists());
+
+    profile.chunked = true;
+    profile.sign = Some("lsusb.key".into());
+    let err = backup::run(&profile, &dest, &TransferOptions::default(), |_| {}).unwrap_err();
+    assert!(matches!(err, UsbError::Unsupported(_)), "{err}");
+}
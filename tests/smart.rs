@@ -0,0 +1,93 @@
+mod common;
+
+use common::{FakeExecutor, fixture};
+use lsusb::UsbError;
+use lsusb::notify::{Notifier, NotifyConfig};
+use lsusb::smart::{AlertTracker, Reading, Smart, Thresholds, parse_smartctl_json};
+use std::sync::Arc;
+
+#[test]
+fn readings_above_thresholds_become_alerts() {
+    let report = parse_smartctl_json(&fixture("smartctl_usb_hdd.json")).unwrap();
+
+    assert_eq!(report.passed, Some(true));
+    assert_eq!(report.temperature, Some(38));
+    assert_eq!(report.power_on_hours, Some(6844));
+    assert_eq!(report.reallocated_sectors(), Some(12));
+    assert_eq!(report.pending_sectors(), Some(0));
+
+    let alerts = report.alerts(&Thresholds::default());
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].reading, Reading::ReallocatedSectors);
+    assert_eq!(
+        alerts[0].to_string(),
+        "12 reallocated sectors (threshold 0)"
+    );
+
+    let lenient = Thresholds {
+        reallocated_sectors: 50,
+        temperature: 35,
+        ..Thresholds::default()
+    };
+    let readings: Vec<_> = report
+        .alerts(&lenient)
+        .into_iter()
+        .map(|a| a.reading)
+        .collect();
+    assert_eq!(readings, [Reading::Temperature]);
+}
+
+#[test]
+fn an_alert_is_raised_once_until_it_clears() {
+    let report = parse_smartctl_json(&fixture("smartctl_usb_hdd.json")).unwrap();
+    let alerts = || report.alerts(&Thresholds::default());
+    let mut tracker = AlertTracker::new();
+
+    assert_eq!(tracker.update("sdb", alerts()).len(), 1);
+    assert!(tracker.update("sdb", alerts()).is_empty());
+    assert!(tracker.update("sdb", Vec::new()).is_empty());
+    assert_eq!(tracker.update("sdb", alerts()).len(), 1);
+
+    tracker.retain(["sdc"]);
+    assert_eq!(tracker.update("sdb", alerts()).len(), 1);
+}
+
+#[test]
+fn unreadable_devices_report_smartctls_message() {
+    let json = br#"{"smartctl": {"exit_status": 2, "messages": [
+        {"string": "/dev/sdb: Unknown USB bridge [0x0bda:0x9210 (0x2001)]", "severity": "error"}]}}"#;
+    let exec = Arc::new(FakeExecutor::new().exit("smartctl", 2, json));
+
+    let err = Smart::with_executor(exec).read("sdb").unwrap_err();
+
+    match err {
+        UsbError::CommandFailed { stderr, .. } => {
+            assert!(stderr.contains("Unknown USB bridge"), "{stderr}")
+        }
+        other => panic!("unexpected error: {other}"),
+    }
+}
+
+#[test]
+fn webhook_receives_the_alert_as_json() {
+    let exec = Arc::new(FakeExecutor::new().succeed("curl", b""));
+    let config = NotifyConfig {
+        desktop: false,
+        webhook: Some("https://hooks.example.com/lsusb".into()),
+    };
+
+    Notifier::with_executor(config, exec.clone())
+        .send(
+            "sdb",
+            "sdb: SMART warning",
+            "12 reallocated sectors (threshold 0)",
+        )
+        .unwrap();
+
+    let call = &exec.calls()[0];
+    assert_eq!(call[0], "curl");
+    assert_eq!(call[call.len() - 2], "https://hooks.example.com/lsusb");
+    let payload: serde_json::Value = serde_json::from_str(&call[call.len() - 1]).unwrap();
+    assert_eq!(payload["device"], "sdb");
+    assert_eq!(payload["body"], "12 reallocated sectors (threshold 0)");
+}
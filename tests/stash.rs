@@ -0,0 +1,201 @@
+mod common;
+
+use common::{FakeExecutor, fixture};
+use lsusb::SystemBackend;
+use lsusb::backend::LsblkBackend;
+use lsusb::stash::{self, PartitionTable, Shrink, Stash, TableEntry};
+use std::sync::Arc;
+
+const STICK_BYTES: u64 = 16_008_609_792;
+
+fn table() -> PartitionTable {
+    stash::parse_sfdisk_json(&String::from_utf8(fixture("sfdisk_stick.json")).unwrap()).unwrap()
+}
+
+#[test]
+fn a_full_stick_gives_up_the_end_of_its_last_partition() {
+    let layout = stash::plan("sdb", STICK_BYTES, &table(), 64 * 1024 * 1024).unwrap();
+
+    assert_eq!(
+        layout.shrink,
+        Some(Shrink {
+            partition: "sdb1".into(),
+            number: 1,
+            start: 2048,
+            sectors: 31_133_696,
+        })
+    );
+    assert_eq!(
+        (layout.partition.as_str(), layout.start, layout.sectors),
+        ("sdb2", 31_135_744, 131_072)
+    );
+}
+
+#[test]
+fn free_space_at_the_end_is_used_as_is() {
+    let mut table = table();
+    table.partitions[0].size = 20_000_000;
+
+    let layout = stash::plan("sdb", STICK_BYTES, &table, 1).unwrap();
+
+    assert_eq!(layout.shrink, None);
+    // Rounded up to a whole MiB at the very end.
+    assert_eq!((layout.start, layout.sectors), (31_264_768, 2048));
+}
+
+#[test]
+fn gpt_keeps_its_backup_header_and_full_mbr_tables_are_refused() {
+    let mut table = table();
+    table.label = "gpt".into();
+    let layout = stash::plan("sdb", STICK_BYTES, &table, 1).unwrap();
+    assert_eq!(layout.start + layout.sectors, 31_264_768);
+
+    let mut table = self::table();
+    table.partitions = (1..=4)
+        .map(|n| TableEntry {
+            node: format!("/dev/sdb{}", n),
+            start: n * 2048,
+            size: 2048,
+        })
+        .collect();
+    assert!(stash::plan("sdb", STICK_BYTES, &table, 1).is_err());
+}
+
+#[test]
+fn create_shrinks_the_filesystem_before_the_partition() {
+    let exec = Arc::new(
+        FakeExecutor::new()
+            .succeed("lsblk", &fixture("lsblk_usb_stick.json"))
+            .succeed("blkid", b"ext4\n")
+            .succeed("e2fsck", b"")
+            .succeed("resize2fs", b"")
+            .succeed("sfdisk", b"")
+            .succeed("blockdev", b"")
+            .succeed("udevadm", b"")
+            .succeed("cryptsetup", b"")
+            .succeed("mkfs.ext4", b""),
+    );
+    let backend = LsblkBackend::with_executor(exec.clone());
+    let mut device = backend.find("sdb").unwrap();
+    for partition in &mut device.partitions {
+        partition.mountpoint = None;
+    }
+    let layout = stash::plan("sdb", STICK_BYTES, &table(), 64 * 1024 * 1024).unwrap();
+
+    let dev = tempfile::tempdir().unwrap();
+    std::fs::write(dev.path().join("sdb2"), b"").unwrap();
+
+    Stash::with_executor(exec.clone())
+        .dev_dir(dev.path().to_path_buf())
+        .create(&device, &layout, "hunter2")
+        .unwrap();
+
+    let calls: Vec<Vec<String>> = exec
+        .calls()
+        .into_iter()
+        .filter(|c| c[0] != "lsblk")
+        .collect();
+    let programs: Vec<&str> = calls.iter().map(|c| c[0].as_str()).collect();
+    assert_eq!(
+        programs,
+        [
+            "blkid",
+            "e2fsck",
+            "resize2fs",
+            "sfdisk",
+            "sfdisk",
+            "blockdev",
+            "udevadm",
+            "cryptsetup",
+            "cryptsetup",
+            "mkfs.ext4",
+            "cryptsetup"
+        ]
+    );
+    assert_eq!(calls[2][1..], ["/dev/sdb1", "15566848K"]);
+    assert_eq!(
+        calls[3][1..],
+        ["--no-reread", "-N", "1", "/dev/sdb", "2048, 31133696\n"]
+    );
+    assert_eq!(
+        calls[4][1..],
+        [
+            "--append",
+            "/dev/sdb",
+            "start=31135744, size=131072, type=L\n"
+        ]
+    );
+    assert_eq!(calls[5][1..], ["--rereadpt", "/dev/sdb"]);
+    assert!(
+        calls[7].contains(&"LSUSB-STASH".to_string()),
+        "{:?}",
+        calls[7]
+    );
+    assert_eq!(calls[7].last().unwrap(), "hunter2");
+    assert_eq!(calls[9].last().unwrap(), "/dev/mapper/lsusb-stash-sdb2");
+}
+
+#[test]
+fn fat_sticks_are_shrunk_with_fatresize() {
+    let exec = Arc::new(
+        FakeExecutor::new()
+            .succeed("lsblk", &fixture("lsblk_usb_stick.json"))
+            .succeed("blkid", b"vfat\n")
+            .succeed("fatresize", b"")
+            .succeed("sfdisk", b"")
+            .succeed("blockdev", b"")
+            .succeed("udevadm", b"")
+            .succeed("cryptsetup", b"")
+            .succeed("mkfs.ext4", b""),
+    );
+    let backend = LsblkBackend::with_executor(exec.clone());
+    let mut device = backend.find("sdb").unwrap();
+    for partition in &mut device.partitions {
+        partition.mountpoint = None;
+    }
+    let layout = stash::plan("sdb", STICK_BYTES, &table(), 64 * 1024 * 1024).unwrap();
+
+    let dev = tempfile::tempdir().unwrap();
+    std::fs::write(dev.path().join("sdb2"), b"").unwrap();
+
+    Stash::with_executor(exec.clone())
+        .dev_dir(dev.path().to_path_buf())
+        .create(&device, &layout, "hunter2")
+        .unwrap();
+
+    let calls: Vec<Vec<String>> = exec
+        .calls()
+        .into_iter()
+        .filter(|c| c[0] != "lsblk")
+        .collect();
+    assert_eq!(
+        calls[1][..],
+        ["fatresize", "--size", "15940452352", "/dev/sdb1"]
+    );
+    assert_eq!(
+        calls[2][1..],
+        ["--no-reread", "-N", "1", "/dev/sdb", "2048, 31133696\n"]
+    );
+}
+
+#[test]
+fn filesystems_that_cannot_shrink_are_refused_untouched() {
+    let exec = Arc::new(
+        FakeExecutor::new()
+            .succeed("lsblk", &fixture("lsblk_usb_stick.json"))
+            .succeed("blkid", b"exfat\n"),
+    );
+    let backend = LsblkBackend::with_executor(exec.clone());
+    let mut device = backend.find("sdb").unwrap();
+    for partition in &mut device.partitions {
+        partition.mountpoint = None;
+    }
+    let layout = stash::plan("sdb", STICK_BYTES, &table(), 64 * 1024 * 1024).unwrap();
+
+    let err = Stash::with_executor(exec.clone())
+        .create(&device, &layout, "hunter2")
+        .unwrap_err();
+
+    assert!(err.to_string().contains("exfat"), "{err}");
+    assert!(!exec.calls().iter().any(|c| c[0] == "sfdisk"));
+}
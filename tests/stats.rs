@@ -0,0 +1,46 @@
+use lsusb::stats::{DriveStats, StatsDb};
+
+#[test]
+fn sessions_add_up_per_serial_number() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = StatsDb::new(dir.path().join("state/stats.json"));
+    assert!(db.get("4C53000123").unwrap().is_none());
+
+    db.seen("4C53000123", "SanDisk Ultra", 1_700_000_000)
+        .unwrap();
+    db.wrote("4C53000123", "SanDisk Ultra", 4_000_000_000, 1_700_000_600)
+        .unwrap();
+    db.wrote("4C53000123", "SanDisk Ultra", 1_000_000_000, 1_700_086_400)
+        .unwrap();
+    db.wrote("AA0001", "Kingston DataTraveler", 1, 1_700_000_100)
+        .unwrap();
+
+    let stick = db.get("4C53000123").unwrap().unwrap();
+    assert_eq!(
+        stick,
+        DriveStats {
+            bytes_written: 5_000_000_000,
+            sessions: 2,
+            first_seen: 1_700_000_000,
+            last_seen: 1_700_086_400,
+            models: vec!["SanDisk Ultra".into()],
+        }
+    );
+    assert_eq!(stick.first_seen_utc(), "2023-11-14 22:13:20");
+    assert_eq!(db.drives().unwrap().len(), 2);
+}
+
+#[test]
+fn a_serial_reported_by_two_models_keeps_both() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = StatsDb::new(dir.path().join("stats.json"));
+
+    db.seen("0123456789", "Generic Flash Disk", 1).unwrap();
+    db.seen("0123456789", "Generic USB3.0", 2).unwrap();
+    db.seen("0123456789", "Generic Flash Disk", 3).unwrap();
+
+    assert_eq!(
+        db.get("0123456789").unwrap().unwrap().models,
+        ["Generic Flash Disk", "Generic USB3.0"]
+    );
+}
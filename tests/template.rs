@@ -0,0 +1,77 @@
+use lsusb::UsbError;
+use lsusb::template::{Vars, expand, is_template};
+use std::path::Path;
+
+fn vars() -> Vars {
+    Vars {
+        hostname: Some("desk".into()),
+        ..Vars::for_drive(Some("PHOTOS".into()), Some("4C530001".into()))
+    }
+}
+
+#[test]
+fn variables_are_filled_in_and_braces_can_be_escaped() {
+    let expanded = expand(
+        Path::new("/media/usb/{label}/{hostname}-{serial}/{{raw}}"),
+        &vars(),
+    )
+    .unwrap();
+    assert_eq!(expanded, Path::new("/media/usb/PHOTOS/desk-4C530001/{raw}"));
+    assert!(is_template(Path::new("/media/usb/{date}")));
+    assert!(!is_template(Path::new("/media/usb/plain")));
+
+    let date = expand(Path::new("{date}"), &vars()).unwrap();
+    let date = date.to_str().unwrap();
+    assert_eq!(date.len(), 10);
+    assert!(date.chars().enumerate().all(|(i, c)| if i == 4 || i == 7 {
+        c == '-'
+    } else {
+        c.is_ascii_digit()
+    }));
+    assert_eq!(
+        expand(Path::new("{time}"), &vars())
+            .unwrap()
+            .as_os_str()
+            .len(),
+        6
+    );
+}
+
+#[test]
+fn values_never_add_directories() {
+    let vars = Vars {
+        label: Some("a/../b".into()),
+        ..vars()
+    };
+    assert_eq!(
+        expand(Path::new("x/{label}"), &vars).unwrap(),
+        Path::new("x/a_.._b")
+    );
+    let dots = Vars {
+        label: Some("..".into()),
+        ..vars
+    };
+    assert_eq!(
+        expand(Path::new("x/{label}"), &dots).unwrap(),
+        Path::new("x/__")
+    );
+}
+
+#[test]
+fn unknown_missing_and_unbalanced_variables_are_errors() {
+    let unlabelled = Vars::for_drive(None, None);
+    for (template, vars) in [
+        ("{nope}", vars()),
+        ("{label}", unlabelled),
+        ("{date", vars()),
+        ("date}", vars()),
+    ] {
+        let err = expand(Path::new(template), &vars).unwrap_err();
+        assert!(
+            matches!(err, UsbError::Unsupported(_)),
+            "{}: {:?}",
+            template,
+            err
+        );
+    }
+}
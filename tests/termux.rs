@@ -0,0 +1,70 @@
+mod common;
+
+use common::{FakeExecutor, fixture};
+use lsusb::CancelToken;
+use lsusb::termux::{Saf, SafEntry};
+use std::sync::Arc;
+
+const ROOT: &str =
+    "content://com.android.externalstorage.documents/tree/1A2B-3C4D%3A/document/1A2B-3C4D%3A";
+
+#[test]
+fn lists_granted_dirs() {
+    let exec =
+        Arc::new(FakeExecutor::new().succeed("termux-saf-dirs", &fixture("termux_saf_dirs.json")));
+
+    let dirs = Saf::with_executor(exec).dirs().unwrap();
+
+    assert_eq!(dirs.len(), 1);
+    assert_eq!(dirs[0].name, "SANDISK");
+    assert_eq!(dirs[0].uri, ROOT);
+    assert!(dirs[0].is_dir());
+}
+
+#[test]
+fn push_streams_file_contents() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("notes.txt");
+    std::fs::write(&source, "hello").unwrap();
+    let exec = Arc::new(
+        FakeExecutor::new()
+            .succeed("termux-saf-create", b"content://x/document/notes.txt\n")
+            .succeed("termux-saf-write", b""),
+    );
+
+    let report = Saf::with_executor(exec.clone())
+        .push(&source, ROOT, &CancelToken::new(), |_| {})
+        .unwrap();
+
+    assert_eq!((report.files, report.bytes), (1, 5));
+    assert_eq!(exec.calls()[0], ["termux-saf-create", ROOT, "notes.txt"]);
+    assert_eq!(
+        exec.calls()[1],
+        [
+            "termux-saf-write",
+            "content://x/document/notes.txt",
+            "hello"
+        ]
+    );
+}
+
+#[test]
+fn pull_writes_file_locally() {
+    let dir = tempfile::tempdir().unwrap();
+    let exec = Arc::new(FakeExecutor::new().succeed("termux-saf-read", b"photo bytes"));
+    let entry = SafEntry {
+        name: "photo.jpg".to_string(),
+        uri: "content://x/document/photo.jpg".to_string(),
+        mime_type: "image/jpeg".to_string(),
+        length: 11,
+    };
+
+    Saf::with_executor(exec)
+        .pull(&entry, dir.path(), &CancelToken::new(), |_| {})
+        .unwrap();
+
+    assert_eq!(
+        std::fs::read(dir.path().join("photo.jpg")).unwrap(),
+        b"photo bytes"
+    );
+}
@@ -0,0 +1,668 @@
+use lsusb::transfer::{AdaptiveBuffer, HINT_WINDOW, WritebackWindows};
+use lsusb::{CancelToken, Phase, ProgressEvent, TransferOptions, UsbError, copy_with_progress};
+use std::fs;
+use std::time::Duration;
+
+#[test]
+fn copies_directory_into_existing_destination() {
+    let src = tempfile::tempdir().unwrap();
+    let dst = tempfile::tempdir().unwrap();
+    fs::create_dir(src.path().join("photos")).unwrap();
+    fs::write(src.path().join("photos/a.jpg"), vec![1u8; 10_000]).unwrap();
+    fs::write(src.path().join("notes.txt"), b"hello").unwrap();
+
+    let mut events = Vec::new();
+    let report = copy_with_progress(src.path(), dst.path(), &TransferOptions::default(), |e| {
+        events.push(e)
+    })
+    .unwrap();
+
+    let copied = dst.path().join(src.path().file_name().unwrap());
+    assert_eq!(fs::read(copied.join("notes.txt")).unwrap(), b"hello");
+    assert_eq!(fs::read(copied.join("photos/a.jpg")).unwrap().len(), 10_000);
+    assert_eq!(report.files_copied, 2);
+    assert_eq!(report.bytes_copied, 10_005);
+
+    assert_eq!(events.first(), Some(&ProgressEvent::Phase(Phase::Scanning)));
+    assert!(events.contains(&ProgressEvent::Totals {
+        bytes: 10_005,
+        files: 2
+    }));
+    assert!(events.contains(&ProgressEvent::Bytes {
+        done: 10_005,
+        total: 10_005
+    }));
+    assert_eq!(events.last(), Some(&ProgressEvent::Phase(Phase::Done)));
+}
+
+#[test]
+fn cancelled_copy_leaves_no_partial_file() {
+    let src = tempfile::tempdir().unwrap();
+    let dst = tempfile::tempdir().unwrap();
+    let file = src.path().join("big.bin");
+    fs::write(&file, vec![0u8; 64 * 1024]).unwrap();
+
+    let cancel = CancelToken::new();
+    // Several writes, so there is one left to cancel.
+    let options = TransferOptions {
+        cancel: cancel.clone(),
+        buffer_size: 8192,
+        adaptive_buffer: false,
+        ..Default::default()
+    };
+    let err = copy_with_progress(&file, dst.path(), &options, |e| {
+        if matches!(e, ProgressEvent::Bytes { .. }) {
+            cancel.cancel();
+        }
+    })
+    .unwrap_err();
+
+    assert!(matches!(err, UsbError::Cancelled), "{err}");
+    assert!(!dst.path().join("big.bin").exists());
+}
+
+#[test]
+fn the_overwrite_hook_hears_of_each_replaced_file_first_and_can_stop_it() {
+    use lsusb::transfer::OverwriteHook;
+    use std::sync::{Arc, Mutex};
+
+    let src = tempfile::tempdir().unwrap();
+    let dst = tempfile::tempdir().unwrap();
+    fs::write(src.path().join("a.txt"), b"new a").unwrap();
+    fs::write(src.path().join("b.txt"), b"new b").unwrap();
+    let name = src.path().file_name().unwrap();
+    fs::create_dir(dst.path().join(name)).unwrap();
+    fs::write(dst.path().join(name).join("a.txt"), b"old a").unwrap();
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let heard = seen.clone();
+    let options = TransferOptions {
+        on_overwrite: Some(OverwriteHook::new(move |_, existing| {
+            heard.lock().unwrap().push(existing.to_path_buf());
+            Ok(())
+        })),
+        ..Default::default()
+    };
+    copy_with_progress(src.path(), dst.path(), &options, |_| {}).unwrap();
+    assert_eq!(*seen.lock().unwrap(), [dst.path().join(name).join("a.txt")]);
+    assert_eq!(
+        fs::read(dst.path().join(name).join("a.txt")).unwrap(),
+        b"new a"
+    );
+
+    fs::write(src.path().join("a.txt"), b"newer a").unwrap();
+    let refuse = TransferOptions {
+        on_overwrite: Some(OverwriteHook::new(|_, _| {
+            Err(UsbError::Unsupported("not logged".into()))
+        })),
+        ..Default::default()
+    };
+    assert!(copy_with_progress(src.path(), dst.path(), &refuse, |_| {}).is_err());
+    assert_eq!(
+        fs::read(dst.path().join(name).join("a.txt")).unwrap(),
+        b"new a"
+    );
+}
+
+#[test]
+fn wide_trees_are_sized_and_copied_in_one_pass_including_hidden_files() {
+    let src = tempfile::tempdir().unwrap();
+    let dst = tempfile::tempdir().unwrap();
+    for dir in ["2023", "2024", "2024/.thumbs"] {
+        fs::create_dir(src.path().join(dir)).unwrap();
+    }
+    for i in 0..200 {
+        fs::write(src.path().join(format!("2023/img{i:03}.jpg")), [7u8; 3]).unwrap();
+    }
+    fs::write(src.path().join("2024/.thumbs/a.jpg"), [1u8; 4]).unwrap();
+    fs::write(src.path().join(".index"), [0u8; 2]).unwrap();
+
+    let mut totals = None;
+    let target = dst.path().join("library");
+    let report = copy_with_progress(src.path(), &target, &TransferOptions::default(), |e| {
+        if let ProgressEvent::Totals { bytes, files } = e {
+            totals = Some((bytes, files));
+        }
+    })
+    .unwrap();
+
+    assert_eq!(totals, Some((606, 202)));
+    assert_eq!(report.files_copied, 202);
+    assert_eq!(
+        fs::read(target.join("2024/.thumbs/a.jpg")).unwrap(),
+        [1u8; 4]
+    );
+    assert_eq!(fs::read(target.join(".index")).unwrap(), [0u8; 2]);
+    assert_eq!(fs::read_dir(target.join("2023")).unwrap().count(), 200);
+}
+
+#[test]
+fn files_spanning_several_cache_hint_windows_copy_intact() {
+    let src = tempfile::tempdir().unwrap();
+    let dst = tempfile::tempdir().unwrap();
+    let data: Vec<u8> = (0..20 * 1024 * 1024u32).map(|i| (i % 251) as u8).collect();
+    fs::write(src.path().join("video.mp4"), &data).unwrap();
+    let options = TransferOptions {
+        buffer_size: 1024 * 1024,
+        ..Default::default()
+    };
+
+    let report =
+        copy_with_progress(&src.path().join("video.mp4"), dst.path(), &options, |_| {}).unwrap();
+
+    assert_eq!(report.bytes_copied, data.len() as u64);
+    assert!(fs::read(dst.path().join("video.mp4")).unwrap() == data);
+}
+
+#[test]
+fn writeback_starts_each_window_and_drops_the_one_before() {
+    let mut windows = WritebackWindows::default();
+    let mib = 1024 * 1024;
+
+    assert_eq!(windows.written(HINT_WINDOW - mib), None);
+    assert_eq!(
+        windows.written(HINT_WINDOW + mib),
+        Some((0..HINT_WINDOW + mib, None))
+    );
+    assert_eq!(windows.written(2 * HINT_WINDOW), None);
+    assert_eq!(
+        windows.written(2 * HINT_WINDOW + 2 * mib),
+        Some((
+            HINT_WINDOW + mib..2 * HINT_WINDOW + 2 * mib,
+            Some(0..HINT_WINDOW + mib)
+        ))
+    );
+    assert_eq!(
+        windows.written(3 * HINT_WINDOW + 2 * mib),
+        Some((
+            2 * HINT_WINDOW + 2 * mib..3 * HINT_WINDOW + 2 * mib,
+            Some(HINT_WINDOW + mib..2 * HINT_WINDOW + 2 * mib)
+        ))
+    );
+}
+
+#[test]
+fn lsusbignore_files_keep_their_matches_off_the_stick() {
+    let src = tempfile::tempdir().unwrap();
+    let dst = tempfile::tempdir().unwrap();
+    fs::create_dir_all(src.path().join("app/target/debug")).unwrap();
+    fs::create_dir_all(src.path().join("app/src")).unwrap();
+    fs::write(src.path().join(".lsusbignore"), b"*.log\n").unwrap();
+    fs::write(src.path().join("app/.lsusbignore"), b"target/\n").unwrap();
+    fs::write(src.path().join("app/target/debug/app"), b"binary").unwrap();
+    fs::write(src.path().join("app/src/main.rs"), b"fn main() {}").unwrap();
+    fs::write(src.path().join("app/build.log"), b"log").unwrap();
+    fs::write(src.path().join("notes.txt"), b"hello").unwrap();
+
+    let report =
+        copy_with_progress(src.path(), dst.path(), &TransferOptions::default(), |_| {}).unwrap();
+
+    let copied = dst.path().join(src.path().file_name().unwrap());
+    assert!(copied.join("app/src/main.rs").exists());
+    assert!(copied.join("notes.txt").exists());
+    assert!(!copied.join("app/target").exists());
+    assert!(!copied.join("app/build.log").exists());
+    assert_eq!(report.files_copied, 4);
+
+    let options = TransferOptions {
+        ignore: lsusb::ignore::Ignore::none(),
+        ..Default::default()
+    };
+    let everything = tempfile::tempdir().unwrap();
+    let report = copy_with_progress(src.path(), everything.path(), &options, |_| {}).unwrap();
+    assert_eq!(report.files_copied, 6);
+}
+
+#[test]
+fn the_buffer_grows_while_throughput_improves_and_shrinks_for_slow_writes() {
+    // 1 ms per write plus 100 MB/s: bigger buffers help less and less.
+    let device = |size: usize| Duration::from_secs_f64(0.001 + size as f64 / 100e6);
+    let mut buffer = AdaptiveBuffer::new(256 * 1024);
+    for _ in 0..200 {
+        let size = buffer.size();
+        buffer.record(size, device(size));
+        // Ends of files don't count.
+        buffer.record(100, Duration::from_secs(1));
+    }
+    assert_eq!(buffer.size(), 2 * 1024 * 1024);
+
+    for _ in 0..8 {
+        buffer.record(buffer.size(), Duration::from_millis(400));
+    }
+    assert_eq!(buffer.size(), 1024 * 1024);
+
+    let mut fixed = AdaptiveBuffer::fixed(8192);
+    for _ in 0..100 {
+        fixed.record(8192, Duration::from_millis(1));
+    }
+    assert_eq!(fixed.size(), 8192);
+}
+
+#[test]
+fn a_spanning_copy_that_fits_on_one_drive_leaves_a_complete_manifest() {
+    let src = tempfile::tempdir().unwrap();
+    let dst = tempfile::tempdir().unwrap();
+    fs::create_dir(src.path().join("photos")).unwrap();
+    fs::write(src.path().join("photos/a.jpg"), vec![1u8; 10_000]).unwrap();
+    fs::write(src.path().join("notes.txt"), b"hello").unwrap();
+
+    let mut asked = false;
+    let report = lsusb::copy_spanning(
+        src.path(),
+        dst.path(),
+        &TransferOptions::default(),
+        |_| {
+            asked = true;
+            Ok(None)
+        },
+        lsusb::progress::NoProgress,
+    )
+    .unwrap();
+
+    assert!(!asked);
+    let name = std::path::PathBuf::from(src.path().file_name().unwrap());
+    assert_eq!(
+        fs::read(dst.path().join(&name).join("notes.txt")).unwrap(),
+        b"hello"
+    );
+    assert_eq!(report.transfer.files_copied, 2);
+    let manifest: lsusb::SpanManifest =
+        serde_json::from_slice(&fs::read(dst.path().join(lsusb::transfer::SPAN_MANIFEST)).unwrap())
+            .unwrap();
+    assert_eq!(manifest, report.manifest);
+    assert!(manifest.complete);
+    assert_eq!(manifest.volumes.len(), 1);
+    assert_eq!(manifest.volumes[0].bytes, 10_005);
+    assert_eq!(
+        manifest.volumes[0].files,
+        vec![name.join("notes.txt"), name.join("photos/a.jpg")]
+    );
+}
+
+#[test]
+fn a_file_larger_than_a_whole_empty_drive_fails_at_once() {
+    let src = tempfile::tempdir().unwrap();
+    let dst = tempfile::tempdir().unwrap();
+    let spare = tempfile::tempdir().unwrap();
+    // Sparse, so it only claims to be bigger than the free space.
+    let available = lsusb::usb::usage(dst.path()).unwrap().available;
+    let image = src.path().join("disk.img");
+    fs::File::create(&image)
+        .unwrap()
+        .set_len(available + (1 << 30))
+        .unwrap();
+
+    let mut asked = 0;
+    let err = lsusb::copy_spanning(
+        src.path(),
+        dst.path(),
+        &TransferOptions::default(),
+        |_| {
+            asked += 1;
+            Ok(Some(spare.path().to_path_buf()))
+        },
+        lsusb::progress::NoProgress,
+    )
+    .unwrap_err();
+
+    assert_eq!(asked, 1);
+    assert!(matches!(err, UsbError::InsufficientSpace { path: Some(path), .. } if path == image));
+}
+
+#[test]
+fn files_being_replaced_count_towards_the_free_space() {
+    use lsusb::transfer::OverwriteHook;
+
+    let src = tempfile::tempdir().unwrap();
+    let dst = tempfile::tempdir().unwrap();
+    // Sparse, so both only claim to be bigger than the free space.
+    let size = lsusb::usb::usage(dst.path()).unwrap().available + (1 << 30);
+    fs::File::create(src.path().join("disk.img"))
+        .unwrap()
+        .set_len(size)
+        .unwrap();
+    fs::File::create(dst.path().join("disk.img"))
+        .unwrap()
+        .set_len(size)
+        .unwrap();
+
+    // Stop at the file instead of writing it.
+    let options = TransferOptions {
+        on_overwrite: Some(OverwriteHook::new(|_, _| {
+            Err(UsbError::Unsupported("stop".into()))
+        })),
+        ..Default::default()
+    };
+    let err =
+        copy_with_progress(&src.path().join("disk.img"), dst.path(), &options, |_| {}).unwrap_err();
+    assert!(matches!(err, UsbError::Unsupported(_)), "{:?}", err);
+    let err = lsusb::copy_mirrored(
+        &src.path().join("disk.img"),
+        &[dst.path().to_path_buf()],
+        &options,
+        lsusb::progress::NoProgress,
+    )
+    .unwrap_err();
+    assert!(matches!(err, UsbError::Unsupported(_)), "{:?}", err);
+}
+
+#[test]
+fn a_spanning_copy_needs_a_directory_to_copy_into() {
+    let src = tempfile::tempdir().unwrap();
+    let dst = tempfile::tempdir().unwrap();
+    let err = lsusb::copy_spanning(
+        src.path(),
+        &dst.path().join("missing"),
+        &TransferOptions::default(),
+        |_| Ok(None),
+        lsusb::progress::NoProgress,
+    )
+    .unwrap_err();
+    assert!(matches!(err, UsbError::Unsupported(_)));
+}
+
+#[test]
+fn a_mirrored_copy_writes_the_same_tree_to_every_destination() {
+    let src = tempfile::tempdir().unwrap();
+    let first = tempfile::tempdir().unwrap();
+    let second = tempfile::tempdir().unwrap();
+    fs::create_dir(src.path().join("photos")).unwrap();
+    let big: Vec<u8> = (0..3_000_000u32).map(|i| i as u8).collect();
+    fs::write(src.path().join("photos/a.raw"), &big).unwrap();
+    fs::write(src.path().join("notes.txt"), b"hello").unwrap();
+
+    let dests = vec![first.path().to_path_buf(), second.path().join("handout")];
+    let mut events = Vec::new();
+    let report = lsusb::copy_mirrored(src.path(), &dests, &TransferOptions::default(), |e| {
+        events.push(e)
+    })
+    .unwrap();
+
+    let name = src.path().file_name().unwrap();
+    for root in [first.path().join(name), second.path().join("handout")] {
+        assert_eq!(fs::read(root.join("notes.txt")).unwrap(), b"hello");
+        assert_eq!(fs::read(root.join("photos/a.raw")).unwrap(), big);
+    }
+    assert_eq!(report.files_copied, 2);
+    assert_eq!(report.bytes_copied, 3_000_005);
+    assert!(events.contains(&ProgressEvent::Bytes {
+        done: 3_000_005,
+        total: 3_000_005
+    }));
+}
+
+#[test]
+fn a_mirrored_copy_cannot_also_encrypt() {
+    let src = tempfile::tempdir().unwrap();
+    let dst = tempfile::tempdir().unwrap();
+    let options = TransferOptions {
+        crypt: Some(lsusb::crypt::Crypt::decrypt(None)),
+        ..TransferOptions::default()
+    };
+    let err = lsusb::copy_mirrored(
+        src.path(),
+        &[dst.path().to_path_buf()],
+        &options,
+        lsusb::progress::NoProgress,
+    )
+    .unwrap_err();
+    assert!(matches!(err, UsbError::Unsupported(_)));
+}
+
+#[test]
+fn preserve_lists_parse_like_cp() {
+    use lsusb::transfer::Preserve;
+    assert_eq!("all".parse::<Preserve>().unwrap(), Preserve::ALL);
+    let some: Preserve = "mode,timestamps".parse().unwrap();
+    assert!(some.mode && some.timestamps && !some.xattrs);
+    assert!("owner".parse::<Preserve>().is_err());
+}
+
+#[test]
+fn preserve_all_carries_modes_times_and_user_attributes() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let src = tempfile::tempdir().unwrap();
+    let dst = tempfile::tempdir().unwrap();
+    let file = src.path().join("script.sh");
+    fs::write(&file, b"#!/bin/sh\n").unwrap();
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o750)).unwrap();
+    let modified = std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    fs::File::options()
+        .write(true)
+        .open(&file)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    // Not every filesystem the tests run on has user attributes.
+    let tagged = lsusb::xattr::set(&file, "user.origin".as_ref(), b"https://example.org").is_ok();
+
+    let options = TransferOptions {
+        preserve: lsusb::transfer::Preserve::ALL,
+        ..TransferOptions::default()
+    };
+    let report = lsusb::copy_to_usb(&file, dst.path(), &options).unwrap();
+
+    let copy = dst.path().join("script.sh");
+    let metadata = fs::metadata(&copy).unwrap();
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o750);
+    assert_eq!(metadata.modified().unwrap(), modified);
+    if tagged && lsusb::xattr::list(&copy).is_ok() && report.dropped.is_empty() {
+        assert_eq!(
+            lsusb::xattr::get(&copy, "user.origin".as_ref()).unwrap(),
+            b"https://example.org"
+        );
+    }
+}
+
+#[test]
+fn mtime_tolerance_allows_fat_rounding_and_optionally_zone_shifts() {
+    use lsusb::transfer::MtimeTolerance;
+    use std::time::SystemTime;
+
+    let t = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let strict = MtimeTolerance::default();
+    assert!(strict.same(t, t + Duration::from_secs(2)));
+    assert!(!strict.same(t, t + Duration::from_secs(3)));
+    assert!(!strict.same(t, t + Duration::from_secs(3600)));
+
+    let shifts = MtimeTolerance {
+        timezone_shifts: true,
+        ..strict
+    };
+    assert!(shifts.same(t + Duration::from_secs(3601), t));
+    assert!(shifts.same(t, t + Duration::from_secs(5 * 3600 + 1800 - 1)));
+    assert!(!shifts.same(t, t + Duration::from_secs(3600 + 60)));
+    assert!(!shifts.same(t, t + Duration::from_secs(15 * 3600)));
+}
+
+#[test]
+fn mirrored_and_spanning_copies_keep_both_of_names_differing_only_in_case() {
+    use lsusb::backup::Conflict;
+
+    let src = tempfile::tempdir().unwrap();
+    fs::write(src.path().join("Notes.txt"), b"upper").unwrap();
+    fs::write(src.path().join("notes.txt"), b"lower").unwrap();
+    let options = TransferOptions {
+        on_collision: Conflict::KeepBoth,
+        ..Default::default()
+    };
+    let name = src.path().file_name().unwrap();
+    // Either way both files must land; only a case-blind drive reports a clash.
+    let check = |dest: &std::path::Path, collisions: usize| {
+        let insensitive = lsusb::casefold::is_case_insensitive(dest).unwrap();
+        assert_eq!(collisions, usize::from(insensitive));
+        assert_eq!(fs::read_dir(dest.join(name)).unwrap().count(), 2);
+    };
+
+    let first = tempfile::tempdir().unwrap();
+    let second = tempfile::tempdir().unwrap();
+    let dests = vec![first.path().to_path_buf(), second.path().to_path_buf()];
+    let report =
+        lsusb::copy_mirrored(src.path(), &dests, &options, lsusb::progress::NoProgress).unwrap();
+    assert_eq!(report.files_copied, 2);
+    for dest in &dests {
+        check(dest, report.collisions.len());
+    }
+
+    let spanned = tempfile::tempdir().unwrap();
+    let report = lsusb::copy_spanning(
+        src.path(),
+        spanned.path(),
+        &options,
+        |_| Ok(None),
+        lsusb::progress::NoProgress,
+    )
+    .unwrap();
+    assert_eq!(report.transfer.files_copied, 2);
+    check(spanned.path(), report.transfer.collisions.len());
+}
+
+#[test]
+fn flatten_gathers_every_file_into_the_prefix_and_keeps_both_of_a_clash() {
+    use lsusb::backup::Conflict;
+    use lsusb::transfer::Reshape;
+
+    let src = tempfile::tempdir().unwrap();
+    let dst = tempfile::tempdir().unwrap();
+    let docs = src.path().join("docs");
+    fs::create_dir_all(docs.join("2023/q4")).unwrap();
+    fs::create_dir_all(docs.join("2024")).unwrap();
+    fs::write(docs.join("2023/q4/report.pdf"), b"old").unwrap();
+    fs::write(docs.join("2024/report.pdf"), b"new").unwrap();
+    fs::write(docs.join("index.pdf"), b"index").unwrap();
+
+    let options = TransferOptions {
+        reshape: Reshape {
+            flatten: true,
+            prefix: Some("pdfs".into()),
+            ..Default::default()
+        },
+        on_collision: Conflict::KeepBoth,
+        ..Default::default()
+    };
+    let report = copy_with_progress(&docs, dst.path(), &options, |_| {}).unwrap();
+
+    assert_eq!(report.files_copied, 3);
+    let mut names: Vec<String> = fs::read_dir(dst.path().join("pdfs"))
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["index.pdf", "report (2).pdf", "report.pdf"]);
+    assert_eq!(
+        fs::read(dst.path().join("pdfs/report.pdf")).unwrap(),
+        b"old"
+    );
+    assert_eq!(
+        fs::read(dst.path().join("pdfs/report (2).pdf")).unwrap(),
+        b"new"
+    );
+    assert_eq!(report.collisions.len(), 1);
+    assert_eq!(fs::read_dir(dst.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn strip_components_drops_leading_directories() {
+    use lsusb::transfer::Reshape;
+
+    let src = tempfile::tempdir().unwrap();
+    let dst = tempfile::tempdir().unwrap();
+    let root = src.path().join("backup");
+    fs::create_dir_all(root.join("home/alice")).unwrap();
+    fs::write(root.join("home/alice/notes.txt"), b"notes").unwrap();
+    fs::write(root.join("README"), b"dropped").unwrap();
+
+    let options = TransferOptions {
+        reshape: Reshape {
+            strip_components: 2,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let report = copy_with_progress(&root, dst.path(), &options, |_| {}).unwrap();
+
+    assert_eq!(report.files_copied, 1);
+    assert_eq!(
+        fs::read(dst.path().join("alice/notes.txt")).unwrap(),
+        b"notes"
+    );
+    assert!(!dst.path().join("backup").exists());
+    assert!(!dst.path().join("README").exists());
+}
+
+#[test]
+fn reshaping_needs_a_directory_and_a_relative_prefix() {
+    use lsusb::transfer::Reshape;
+
+    let src = tempfile::tempdir().unwrap();
+    let dst = tempfile::tempdir().unwrap();
+    fs::write(src.path().join("a.txt"), b"a").unwrap();
+    let flatten = TransferOptions {
+        reshape: Reshape {
+            flatten: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let err =
+        copy_with_progress(&src.path().join("a.txt"), dst.path(), &flatten, |_| {}).unwrap_err();
+    assert!(matches!(err, UsbError::Unsupported(_)), "{:?}", err);
+
+    let escaping = TransferOptions {
+        reshape: Reshape {
+            prefix: Some("../outside".into()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let err = copy_with_progress(src.path(), dst.path(), &escaping, |_| {}).unwrap_err();
+    assert!(matches!(err, UsbError::Unsupported(_)), "{:?}", err);
+    assert_eq!(fs::read_dir(dst.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn files_already_at_the_destination_are_resolved_by_the_prompt() {
+    use lsusb::backup::Conflict;
+    use lsusb::transfer::ConflictPrompt;
+    use std::sync::{Arc, Mutex};
+
+    let src = tempfile::tempdir().unwrap();
+    let dst = tempfile::tempdir().unwrap();
+    let source = src.path().join("music");
+    fs::create_dir(&source).unwrap();
+    for name in ["keep.mp3", "skip.mp3", "both.mp3", "new.mp3"] {
+        fs::write(source.join(name), b"new").unwrap();
+    }
+    fs::create_dir(dst.path().join("music")).unwrap();
+    for name in ["keep.mp3", "skip.mp3", "both.mp3"] {
+        fs::write(dst.path().join("music").join(name), b"old").unwrap();
+    }
+
+    let asked = Arc::new(Mutex::new(Vec::new()));
+    let log = asked.clone();
+    let options = TransferOptions {
+        on_existing: Some(ConflictPrompt::new(move |_, existing| {
+            let name = existing.file_name().unwrap().to_string_lossy().into_owned();
+            let answer = match name.as_str() {
+                "keep.mp3" => Conflict::Overwrite,
+                "skip.mp3" => Conflict::Skip,
+                _ => Conflict::KeepBoth,
+            };
+            log.lock().unwrap().push(name);
+            answer
+        })),
+        ..Default::default()
+    };
+    let report = copy_with_progress(&source, dst.path(), &options, |_| {}).unwrap();
+
+    let copy = dst.path().join("music");
+    assert_eq!(*asked.lock().unwrap(), ["both.mp3", "keep.mp3", "skip.mp3"]);
+    assert_eq!(report.files_copied, 3);
+    assert_eq!(report.skipped, [copy.join("skip.mp3")]);
+    assert_eq!(fs::read(copy.join("keep.mp3")).unwrap(), b"new");
+    assert_eq!(fs::read(copy.join("skip.mp3")).unwrap(), b"old");
+    assert_eq!(fs::read(copy.join("both.mp3")).unwrap(), b"old");
+    assert_eq!(fs::read(copy.join("both (2).mp3")).unwrap(), b"new");
+    assert_eq!(fs::read(copy.join("new.mp3")).unwrap(), b"new");
+}
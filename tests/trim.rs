@@ -0,0 +1,96 @@
+mod common;
+
+use common::FakeExecutor;
+use lsusb::trim::{self, DiscardSupport, Trimmer};
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::Arc;
+
+/// A `/sys/block/sdb` for a UAS SSD whose bridge advertises UNMAP but
+/// the kernel left in provisioning mode `full`.
+fn uas_disk(root: &Path) {
+    let disk = root.join("sdb");
+    fs::create_dir_all(disk.join("queue")).unwrap();
+    fs::create_dir_all(disk.join("device/scsi_disk/2:0:0:0")).unwrap();
+    fs::write(disk.join("queue/discard_granularity"), "0\n").unwrap();
+    fs::write(disk.join("queue/discard_max_bytes"), "0\n").unwrap();
+    fs::write(
+        disk.join("device/scsi_disk/2:0:0:0/provisioning_mode"),
+        "full\n",
+    )
+    .unwrap();
+    fs::write(
+        disk.join("device/vpd_pgb2"),
+        [0x00, 0xb2, 0x00, 0x04, 0x00, 0x84, 0x02, 0x00],
+    )
+    .unwrap();
+}
+
+#[test]
+fn a_uas_bridge_that_advertises_unmap_can_have_it_enabled() {
+    let root = tempfile::tempdir().unwrap();
+    uas_disk(root.path());
+
+    let support = trim::discard_support_in(root.path(), "sdb");
+    assert_eq!(
+        support,
+        DiscardSupport {
+            granularity: 0,
+            max_bytes: 0,
+            provisioning_mode: Some("full".into()),
+            unmap_advertised: Some(true),
+            reads_zeros: Some(true),
+        }
+    );
+    assert!(!support.supported());
+    assert!(support.can_enable_unmap());
+
+    trim::enable_unmap_in(root.path(), "sdb").unwrap();
+    let mode = fs::read_to_string(
+        root.path()
+            .join("sdb/device/scsi_disk/2:0:0:0/provisioning_mode"),
+    )
+    .unwrap();
+    assert_eq!(mode, "unmap");
+    assert!(!trim::discard_support_in(root.path(), "sdb").can_enable_unmap());
+}
+
+#[test]
+fn disks_without_sysfs_entries_support_nothing() {
+    let root = tempfile::tempdir().unwrap();
+    let support = trim::discard_support_in(root.path(), "mmcblk0");
+    assert_eq!(support, DiscardSupport::default());
+    assert!(trim::enable_unmap_in(root.path(), "mmcblk0").is_err());
+}
+
+#[test]
+fn fstrim_reports_the_bytes_trimmed() {
+    assert_eq!(
+        trim::parse_fstrim("/media/usb: 14.9 GiB (15998234624 bytes) trimmed\n"),
+        Some(15_998_234_624)
+    );
+    assert_eq!(trim::parse_fstrim(""), None);
+
+    let exec = Arc::new(
+        FakeExecutor::new().succeed("fstrim", b"/media/usb: 1 MiB (1048576 bytes) trimmed\n"),
+    );
+    assert_eq!(
+        Trimmer::with_executor(exec.clone())
+            .fstrim("/media/usb")
+            .unwrap(),
+        Some(1_048_576)
+    );
+    assert_eq!(exec.calls(), [["fstrim", "--verbose", "/media/usb"]]);
+}
+
+#[test]
+fn only_erased_looking_data_counts_as_discarded() {
+    let len = 1 << 20;
+    assert!(trim::reads_back_erased(&mut Cursor::new(vec![0u8; len]), len as u64).unwrap());
+    assert!(trim::reads_back_erased(&mut Cursor::new(vec![0xffu8; len]), len as u64).unwrap());
+
+    let mut stale = vec![0u8; len];
+    stale[len - 100] = 1;
+    assert!(!trim::reads_back_erased(&mut Cursor::new(stale), len as u64).unwrap());
+}
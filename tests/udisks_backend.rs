@@ -0,0 +1,43 @@
+mod common;
+
+use common::FakeExecutor;
+use lsusb::backend::UdisksBackend;
+use lsusb::{MountOptions, SystemBackend};
+use std::path::Path;
+use std::sync::Arc;
+
+#[test]
+fn mount_returns_mountpoint_chosen_by_udisks() {
+    let exec = Arc::new(FakeExecutor::new().succeed(
+        "udisksctl",
+        b"Mounted /dev/sdb1 at /run/media/user/My Disk\n",
+    ));
+    let backend = UdisksBackend::with_executor(exec.clone());
+
+    let mounted_at = backend
+        .mount("sdb1", None, MountOptions::default())
+        .unwrap();
+
+    assert_eq!(mounted_at, Path::new("/run/media/user/My Disk"));
+    assert_eq!(
+        exec.calls(),
+        [[
+            "udisksctl",
+            "mount",
+            "--no-user-interaction",
+            "-b",
+            "/dev/sdb1"
+        ]]
+    );
+}
+
+#[test]
+fn power_off_failure_includes_stderr() {
+    let exec =
+        Arc::new(FakeExecutor::new().fail("udisksctl", "Error powering off drive: Not authorized"));
+    let backend = UdisksBackend::with_executor(exec);
+
+    let err = backend.power_off("sdb").unwrap_err();
+
+    assert!(err.to_string().contains("Not authorized"), "{err}");
+}
@@ -0,0 +1,38 @@
+use lsusb::Usage;
+
+#[test]
+fn percent_used_is_of_the_space_users_can_fill() {
+    let usage = Usage {
+        total: 1000,
+        used: 450,
+        available: 500,
+    };
+    // 450 of 950, rounded up like df; root's reserve of 50 doesn't count.
+    assert_eq!(usage.percent_used(), 48);
+    assert_eq!(
+        Usage {
+            total: 1000,
+            used: 950,
+            available: 0
+        }
+        .percent_used(),
+        100
+    );
+    assert_eq!(
+        Usage {
+            total: 0,
+            used: 0,
+            available: 0
+        }
+        .percent_used(),
+        0
+    );
+}
+
+#[test]
+fn usage_of_a_real_filesystem_adds_up() {
+    let dir = tempfile::tempdir().unwrap();
+    let usage = lsusb::usage(dir.path()).unwrap();
+    assert!(usage.total > 0);
+    assert!(usage.used + usage.available <= usage.total);
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use common::FakeExecutor;
+use lsusb::MountOptions;
+use lsusb::veracrypt::{self, OpenVolume, Veracrypt};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Bytes that pass for ciphertext.
+fn noise(len: usize) -> Vec<u8> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 32) as u8
+        })
+        .collect()
+}
+
+#[test]
+fn only_random_unrecognised_data_could_be_a_volume() {
+    assert!(veracrypt::looks_like_volume(&mut Cursor::new(noise(1 << 20))).unwrap());
+    assert!(!veracrypt::looks_like_volume(&mut Cursor::new(vec![0u8; 1 << 20])).unwrap());
+    // Too short for the header area.
+    assert!(!veracrypt::looks_like_volume(&mut Cursor::new(noise(4096))).unwrap());
+
+    let mut luks = noise(1 << 20);
+    luks[..6].copy_from_slice(b"LUKS\xba\xbe");
+    luks[6..8].copy_from_slice(&1u16.to_be_bytes());
+    assert!(!veracrypt::looks_like_volume(&mut Cursor::new(luks)).unwrap());
+}
+
+#[test]
+fn the_list_keeps_spaces_in_container_paths() {
+    let volumes = veracrypt::parse_list(
+        "1: /dev/sdb1 /dev/mapper/veracrypt1 /media/veracrypt1\n2: /home/a/My Secrets.hc /dev/mapper/veracrypt2 -\n",
+    );
+    assert_eq!(
+        volumes,
+        [
+            OpenVolume {
+                slot: 1,
+                volume: "/dev/sdb1".into(),
+                device: "/dev/mapper/veracrypt1".into(),
+                mountpoint: Some(PathBuf::from("/media/veracrypt1")),
+            },
+            OpenVolume {
+                slot: 2,
+                volume: "/home/a/My Secrets.hc".into(),
+                device: "/dev/mapper/veracrypt2".into(),
+                mountpoint: None,
+            },
+        ]
+    );
+}
+
+#[test]
+fn nothing_open_is_an_empty_list_not_an_error() {
+    let exec = Arc::new(FakeExecutor::new().fail("veracrypt", "Error: No volumes mounted."));
+    assert_eq!(Veracrypt::with_executor(exec).list().unwrap(), []);
+
+    let exec = Arc::new(FakeExecutor::new().fail("veracrypt", "Error: Permission denied"));
+    assert!(Veracrypt::with_executor(exec).list().is_err());
+}
+
+#[test]
+fn open_passes_the_password_on_stdin_and_honours_read_only() {
+    let exec = Arc::new(FakeExecutor::new().succeed("veracrypt", b""));
+    let tools = Veracrypt::with_executor(exec.clone());
+
+    let mounted = tools
+        .open(
+            "/dev/sdb1",
+            "hunter2",
+            Some(Path::new("/mnt/vc")),
+            MountOptions { read_only: true },
+        )
+        .unwrap();
+
+    assert_eq!(mounted, Path::new("/mnt/vc"));
+    let call = exec.calls().remove(0);
+    assert!(call.contains(&"--stdin".to_string()));
+    assert!(call.contains(&"--mount-options=ro".to_string()));
+    assert_eq!(call[call.len() - 3..], ["/dev/sdb1", "/mnt/vc", "hunter2"]);
+}
+
+#[test]
+fn without_a_mountpoint_open_looks_up_where_veracrypt_put_it() {
+    let exec = Arc::new(FakeExecutor::new().succeed(
+        "veracrypt",
+        b"1: /dev/sdb1 /dev/mapper/veracrypt1 /media/veracrypt1\n",
+    ));
+    let tools = Veracrypt::with_executor(exec.clone());
+
+    let mounted = tools
+        .open("/dev/sdb1", "hunter2", None, MountOptions::default())
+        .unwrap();
+
+    assert_eq!(mounted, Path::new("/media/veracrypt1"));
+    assert_eq!(exec.calls()[1], ["veracrypt", "--text", "--list"]);
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::fixture;
+use lsusb::smart::{SmartAttribute, SmartReport, parse_smartctl_json};
+use lsusb::wear::{self, Basis, Counter};
+
+const TB: u64 = 1_000_000_000_000;
+const GIB: u64 = 1024 * 1024 * 1024;
+
+#[test]
+fn nvme_drives_report_wear_and_writes_themselves() {
+    let report = parse_smartctl_json(&fixture("smartctl_nvme.json")).unwrap();
+    assert_eq!(report.wear_percent(), Some(4));
+    assert_eq!(report.bytes_written(), Some(12 * TB));
+
+    let wear = wear::estimate(Some(&report), Some(5 * GIB), TB).unwrap();
+    assert_eq!((wear.used_percent, wear.basis), (4.0, Basis::Drive));
+    assert_eq!(wear.written, Some((12 * TB, Counter::Drive)));
+    // 12 TB was 4%, so 300 TB in all.
+    assert_eq!(wear.remaining, Some(288 * TB));
+}
+
+#[test]
+fn sata_ssds_report_life_left_as_a_normalised_value() {
+    let attribute = |id, value, raw| SmartAttribute {
+        id,
+        name: String::new(),
+        value,
+        raw,
+    };
+    let report = SmartReport {
+        attributes: vec![
+            attribute(177, 99, 12),
+            attribute(231, 93, 0),
+            attribute(241, 100, 1 << 30),
+        ],
+        ..SmartReport::default()
+    };
+    assert_eq!(report.wear_percent(), Some(7));
+    assert_eq!(report.bytes_written(), Some(512 << 30));
+
+    // Without a count of writes, nothing says what is left.
+    let report = SmartReport {
+        attributes: vec![attribute(233, 90, 0)],
+        ..SmartReport::default()
+    };
+    let wear = wear::estimate(Some(&report), Some(GIB), 64 * GIB).unwrap();
+    assert_eq!(
+        (wear.used_percent, wear.written, wear.remaining),
+        (10.0, Some((GIB, Counter::Lsusb)), None)
+    );
+}
+
+#[test]
+fn sticks_without_smart_are_estimated_from_what_lsusb_wrote() {
+    let wear = wear::estimate(None, Some(160 * GIB), 32 * GIB).unwrap();
+
+    assert_eq!(wear.basis, Basis::Assumed);
+    assert_eq!(wear.used_percent, 1.0);
+    assert_eq!(
+        wear.remaining,
+        Some((32 * wear::ASSUMED_DRIVE_FILLS - 160) * GIB)
+    );
+
+    assert_eq!(wear::estimate(None, Some(0), 32 * GIB), None);
+    assert_eq!(
+        wear::estimate(Some(&SmartReport::default()), None, 32 * GIB),
+        None
+    );
+}
@@ -0,0 +1,65 @@
+use lsusb::writeback::{parse_bdi_stats, parse_meminfo};
+
+#[test]
+fn bdi_stats_count_dirty_and_in_flight_pages() {
+    let stats = "\
+BdiWriteback:          512 kB
+BdiReclaimable:      20480 kB
+BdiDirtyThresh:     114340 kB
+DirtyThresh:       1594644 kB
+BackgroundThresh:   796340 kB
+BdiDirtied:         481232 kB
+BdiWritten:         460240 kB
+BdiWriteBandwidth:   10240 kBps
+b_dirty:                 3
+";
+    assert_eq!(parse_bdi_stats(stats), Some((512 + 20480) * 1024));
+    assert_eq!(parse_bdi_stats("b_dirty: 3\n"), None);
+}
+
+#[test]
+fn meminfo_counts_dirty_and_writeback() {
+    let meminfo = "\
+MemTotal:       16314436 kB
+Dirty:             34816 kB
+Writeback:          1024 kB
+WritebackTmp:          0 kB
+";
+    assert_eq!(parse_meminfo(meminfo), Some((34816 + 1024) * 1024));
+}
+
+#[test]
+fn flushing_reports_its_phases_and_the_flush_result() {
+    let mut events = Vec::new();
+    lsusb::writeback::flush_with_progress("no-such-disk", || Ok(()), |event| events.push(event))
+        .unwrap();
+    assert_eq!(
+        events.first(),
+        Some(&lsusb::ProgressEvent::Phase(lsusb::Phase::Copying))
+    );
+    assert_eq!(
+        events.last(),
+        Some(&lsusb::ProgressEvent::Phase(lsusb::Phase::Done))
+    );
+
+    let failed = lsusb::writeback::flush_with_progress(
+        "no-such-disk",
+        || Err(lsusb::UsbError::Cancelled),
+        |_| {},
+    );
+    assert!(matches!(failed, Err(lsusb::UsbError::Cancelled)));
+}
+
+#[test]
+fn one_filesystem_can_be_synced_by_its_mountpoint() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("pending"), b"data").unwrap();
+    lsusb::backend::sync_filesystem(dir.path()).unwrap();
+
+    let missing = lsusb::backend::sync_filesystem(&dir.path().join("gone"));
+    assert!(
+        matches!(missing, Err(lsusb::UsbError::Io { .. })),
+        "{:?}",
+        missing
+    );
+}
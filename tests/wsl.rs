@@ -0,0 +1,49 @@
+mod common;
+
+use common::{FakeExecutor, fixture};
+use lsusb::UsbError;
+use lsusb::wsl::{Usbipd, parse_usbipd_list};
+use std::sync::Arc;
+
+#[test]
+fn parses_connected_devices_only() {
+    let devices = parse_usbipd_list(&String::from_utf8(fixture("usbipd_list.txt")).unwrap());
+
+    assert_eq!(devices.len(), 3);
+    assert_eq!(devices[0].busid, "1-4");
+    assert_eq!(devices[0].vid_pid, "0781:5581");
+    assert_eq!(devices[0].description, "USB Mass Storage Device");
+    assert!(!devices[0].is_shared());
+    assert_eq!(
+        devices[2].description,
+        "Logitech USB Input Device, USB Input Device"
+    );
+    assert!(devices[2].is_attached());
+}
+
+#[test]
+fn attach_refuses_unshared_device() {
+    let exec = Arc::new(FakeExecutor::new().succeed("usbipd.exe", &fixture("usbipd_list.txt")));
+    let usbipd = Usbipd::with_executor(exec.clone());
+
+    let err = usbipd.attach("1-4").unwrap_err();
+
+    assert!(
+        matches!(&err, UsbError::Unsupported(msg) if msg.contains("usbipd bind")),
+        "{err}"
+    );
+    assert_eq!(exec.calls().len(), 1);
+}
+
+#[test]
+fn attach_forwards_shared_device() {
+    let exec = Arc::new(FakeExecutor::new().succeed("usbipd.exe", &fixture("usbipd_list.txt")));
+    let usbipd = Usbipd::with_executor(exec.clone());
+
+    usbipd.attach("1-7").unwrap();
+
+    assert_eq!(
+        exec.calls()[1],
+        ["usbipd.exe", "attach", "--wsl", "--busid", "1-7"]
+    );
+}